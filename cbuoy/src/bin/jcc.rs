@@ -0,0 +1,229 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use clap::Parser;
+use jib::debug_info::DebugInfo;
+use jib_asm::{
+    assemble_object,
+    config::ProjectConfig,
+    object::{parse_section_base, LinkedImage, ObjectFile},
+    preprocess,
+};
+
+/// Builds a memory image from C/Buoy, assembly, and object files in a single command,
+/// selecting the tool for each input by its file extension. The settings of any scpu.toml
+/// project file within the working directory or its parents are applied
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// The input files, where .spc files are compiled, .jsm files are assembled, and .jo
+    /// files are read as objects
+    inputs: Vec<PathBuf>,
+
+    /// Reads additional inputs and options from a build manifest
+    #[arg(short, long)]
+    manifest: Option<PathBuf>,
+
+    /// The output memory image file, defaulting to the manifest output or a.bin
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Places the named relocatable section at the provided base address, as NAME=ADDRESS
+    #[arg(short, long = "section")]
+    sections: Vec<String>,
+
+    /// The C/Buoy optimization level, where 0 disables optimization, defaulting to the project
+    /// optimization level or 1
+    #[arg(short = 'O', long)]
+    optimization: Option<u32>,
+
+    /// Writes the labels and source lines of the program alongside the output, with the jdbg
+    /// extension, such that the debugger can show and break on source lines
+    #[arg(short = 'g', long)]
+    debug_info: bool,
+}
+
+/// Provides the build inputs and options, combined from the command line and any manifest
+#[derive(Debug, Default)]
+struct Build {
+    config: ProjectConfig,
+    inputs: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    bases: HashMap<String, u32>,
+    optimization: Option<u32>,
+}
+
+impl Build {
+    fn add_section(&mut self, s: &str) -> Result<(), String> {
+        let (name, addr) = parse_section_base(s)
+            .ok_or_else(|| format!("Invalid section base '{s}', expected NAME=ADDRESS"))?;
+        self.bases.insert(name, addr);
+        Ok(())
+    }
+
+    /// Reads a manifest, where each line provides an input file, `output PATH`, or
+    /// `section NAME=ADDRESS`. Paths are relative to the manifest directory, and lines
+    /// starting with # are ignored
+    fn read_manifest(&mut self, p: &Path) -> Result<(), String> {
+        let txt = std::fs::read_to_string(p).map_err(|e| format!("Unable to read - {e}"))?;
+        let dir = p.parent().unwrap_or(Path::new(""));
+
+        for (i, line) in txt.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.split_once(char::is_whitespace) {
+                Some(("output", path)) => self.output = Some(dir.join(path.trim())),
+                Some(("section", base)) => self
+                    .add_section(base.trim())
+                    .map_err(|e| format!("Line {} - {e}", i + 1))?,
+                _ => self.inputs.push(dir.join(line)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Provides the kind of each input file, based on its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputKind {
+    Source,
+    Assembly,
+    Object,
+}
+
+impl InputKind {
+    fn from_path(p: &Path) -> Option<Self> {
+        match p.extension()?.to_str()? {
+            "spc" => Some(Self::Source),
+            "jsm" => Some(Self::Assembly),
+            "jo" => Some(Self::Object),
+            _ => None,
+        }
+    }
+}
+
+/// Builds the memory image from the inputs. Compiled sources provide a complete memory image,
+/// and so must be the only input, while assembly and object files are linked together
+fn build_image(build: &Build) -> Result<LinkedImage, String> {
+    let mut inputs = Vec::new();
+    for p in build.inputs.iter() {
+        let kind = InputKind::from_path(p)
+            .ok_or_else(|| format!("{} - Unknown input file type", p.display()))?;
+        let txt = std::fs::read_to_string(p)
+            .map_err(|e| format!("{} - Unable to read - {e}", p.display()))?;
+        inputs.push((p, kind, txt));
+    }
+
+    match inputs.as_slice() {
+        [] => Err("No input files provided".into()),
+        [(p, InputKind::Source, txt)] => {
+            let level = build
+                .optimization
+                .or(build.config.optimization)
+                .unwrap_or(cbuoy::DEFAULT_OPTIMIZATION);
+            cbuoy::compile_image(txt, level)
+                .map_err(|e| format!("{} - Compiler Error: {e}", p.display()))
+        }
+        _ => {
+            let mut objects = Vec::new();
+            for (p, kind, txt) in inputs.iter() {
+                let obj = match kind {
+                    InputKind::Source => {
+                        return Err(format!(
+                            "{} - Compiled sources must be the only input",
+                            p.display()
+                        ))
+                    }
+                    InputKind::Assembly => preprocess::preprocess_source(
+                        txt,
+                        Some(p),
+                        &preprocess::IncludePaths::default(),
+                    )
+                    .and_then(|lines| assemble_object(&lines)),
+                    InputKind::Object => ObjectFile::from_text(txt),
+                };
+
+                objects.push(obj.map_err(|e| format!("{} - Assembler Error: {e}", p.display()))?);
+            }
+
+            build
+                .config
+                .link_image(&objects, &build.bases)
+                .map_err(|e| format!("Linker Error: {e}"))
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let mut build = Build {
+        config: match ProjectConfig::current() {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        },
+        optimization: args.optimization,
+        ..Default::default()
+    };
+
+    if let Some(p) = &args.manifest {
+        if let Err(e) = build.read_manifest(p) {
+            eprintln!("{} - {e}", p.display());
+            std::process::exit(1);
+        }
+    }
+
+    build.inputs.extend(args.inputs);
+    for s in args.sections.iter() {
+        if let Err(e) = build.add_section(s) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+
+    let output = args
+        .output
+        .or(build.output.clone())
+        .unwrap_or_else(|| PathBuf::from("a.bin"));
+
+    let image = match build_image(&build) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = std::fs::write(&output, &image.bytes) {
+        eprintln!("Unable to write {} - {e}", output.display());
+        std::process::exit(1);
+    }
+
+    println!(
+        "Built {} bytes into {}",
+        image.bytes.len(),
+        output.display()
+    );
+
+    if args.debug_info {
+        let names = build
+            .inputs
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>();
+        let debug_output = DebugInfo::sidecar_path(&output);
+        if let Err(e) = std::fs::write(&debug_output, image.debug_info(&names).to_text()) {
+            eprintln!("Unable to write {} - {e}", debug_output.display());
+            std::process::exit(1);
+        }
+    }
+}
@@ -2,20 +2,21 @@ use std::fmt::Display;
 
 use jib::cpu::{DataType, Register};
 use jib_asm::{
-    argument::ArgumentType,
+    argument::{ArgumentSource, ArgumentType},
     instructions::{
-        OpAdd, OpBand, OpBnot, OpBor, OpBshl, OpBshr, OpBxor, OpConv, OpDiv, OpJmp, OpJmpri, OpLd,
-        OpLdi, OpLdn, OpMul, OpNeg, OpNot, OpRem, OpSub, OpTeq, OpTneq, OpTnz, OpTz,
+        OpAdd, OpBand, OpBnot, OpBor, OpBshl, OpBshr, OpBxor, OpCallf, OpConv, OpCopy, OpDiv,
+        OpJmp, OpLd, OpLdi, OpLdn, OpMac, OpMul, OpNeg, OpNot, OpPopr, OpPush, OpRem, OpSav, OpSub,
+        OpTeq, OpTneq, OpTnz, OpTz,
     },
     AsmToken, AsmTokenLoc, FromLiteral, LocationInfo,
 };
 
 use crate::{
     tokenizer::Token,
-    types::{Type, TypeError},
+    types::{Type, TypeDict, TypeError},
 };
 
-use super::{AsmGenState, ErrorToken};
+use super::{argument_layout, load_u32, AsmGenState, ErrorToken};
 
 #[derive(Debug, Clone)]
 pub enum ExpressionError {
@@ -56,7 +57,11 @@ pub trait Expression {
         state: &mut AsmGenState,
     ) -> Result<Vec<AsmToken>, ErrorToken>;
 
-    fn load_address(&self, _reg: Register) -> Result<Vec<AsmToken>, ErrorToken> {
+    fn load_address(
+        &self,
+        _reg: Register,
+        _state: &mut AsmGenState,
+    ) -> Result<Vec<AsmToken>, ErrorToken> {
         Err(ErrorToken::new(
             self.get_token(),
             &ExpressionError::NotAddressable.to_string(),
@@ -64,6 +69,16 @@ pub trait Expression {
     }
 
     fn get_token(&self) -> Token;
+
+    /// Provides the value of the expression if it is known at compile time
+    fn get_literal(&self) -> Option<Literal> {
+        None
+    }
+
+    /// Provides the two operands of the expression if it is a product computed at runtime
+    fn get_product(&self) -> Option<(&dyn Expression, &dyn Expression)> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -139,6 +154,69 @@ impl Literal {
             base: Box::new(init),
         }
     }
+
+    /// Evaluates a binary operator on two literals of the same type, wrapping on overflow in
+    /// the same way as the processor. Comparisons, floating-point values, and any operation that
+    /// would fail at runtime, such as division by zero, are not evaluated
+    pub fn fold_binary(&self, op: BinaryOperator, rhs: &Literal) -> Option<Literal> {
+        macro_rules! fold {
+            ($v:path, $a:expr, $b:expr, $t:ty) => {{
+                let (a, b): ($t, $t) = (*$a, *$b);
+                let shift = u32::try_from(b).ok().filter(|s| *s < <$t>::BITS);
+                Some($v(match op {
+                    BinaryOperator::Add => a.wrapping_add(b),
+                    BinaryOperator::Sub => a.wrapping_sub(b),
+                    BinaryOperator::Mul => a.wrapping_mul(b),
+                    BinaryOperator::Div => a.checked_div(b)?,
+                    BinaryOperator::Rem => a.checked_rem(b)?,
+                    BinaryOperator::Band => a & b,
+                    BinaryOperator::Bor => a | b,
+                    BinaryOperator::Bxor => a ^ b,
+                    BinaryOperator::Bshl => a << shift?,
+                    BinaryOperator::Bshr => a >> shift?,
+                    _ => return None,
+                }))
+            }};
+        }
+
+        match (self, rhs) {
+            (Self::U8(a), Self::U8(b)) => fold!(Self::U8, a, b, u8),
+            (Self::I8(a), Self::I8(b)) => fold!(Self::I8, a, b, i8),
+            (Self::U16(a), Self::U16(b)) => fold!(Self::U16, a, b, u16),
+            (Self::I16(a), Self::I16(b)) => fold!(Self::I16, a, b, i16),
+            (Self::U32(a), Self::U32(b)) => fold!(Self::U32, a, b, u32),
+            (Self::I32(a), Self::I32(b)) => fold!(Self::I32, a, b, i32),
+            _ => None,
+        }
+    }
+
+    /// Evaluates an arithmetic or bitwise unary operator on the literal
+    pub fn fold_unary(&self, op: UnaryOperator) -> Option<Literal> {
+        macro_rules! fold {
+            ($v:path, $a:expr) => {
+                Some($v(match op {
+                    UnaryOperator::Positive => *$a,
+                    UnaryOperator::Negative => $a.wrapping_neg(),
+                    UnaryOperator::BitwiseNot => !*$a,
+                    _ => return None,
+                }))
+            };
+        }
+
+        match self {
+            Self::U8(a) => fold!(Self::U8, a),
+            Self::I8(a) => fold!(Self::I8, a),
+            Self::U16(a) => fold!(Self::U16, a),
+            Self::I16(a) => fold!(Self::I16, a),
+            Self::U32(a) => fold!(Self::U32, a),
+            Self::I32(a) => fold!(Self::I32, a),
+            Self::F32(a) => match op {
+                UnaryOperator::Positive => Some(Self::F32(*a)),
+                UnaryOperator::Negative => Some(Self::F32(-*a)),
+                _ => None,
+            },
+        }
+    }
 }
 
 impl From<u8> for Literal {
@@ -172,29 +250,29 @@ impl Expression for LiteralExpression {
         let (lit_token, lit_type) = match self.literal {
             Literal::U8(val) => (
                 AsmToken::OperationLiteral(Box::new(OpLdi::new(
-                    ArgumentType::new(reg, DataType::I8),
+                    ArgumentType::new(reg, DataType::U16),
                     val as u16,
                 ))),
                 None,
             ),
             Literal::I8(val) => (
                 AsmToken::OperationLiteral(Box::new(OpLdi::new(
-                    ArgumentType::new(reg, DataType::U8),
+                    ArgumentType::new(reg, DataType::I16),
                     (val as i16) as u16,
                 ))),
                 None,
             ),
             Literal::I16(val) => (
                 AsmToken::OperationLiteral(Box::new(OpLdi::new(
-                    ArgumentType::new(reg, DataType::U8),
+                    ArgumentType::new(reg, DataType::I16),
                     val as u16,
                 ))),
                 None,
             ),
             Literal::U16(val) => (
                 AsmToken::OperationLiteral(Box::new(OpLdi::new(
-                    ArgumentType::new(reg, DataType::U8),
-                    (val as i16) as u16,
+                    ArgumentType::new(reg, DataType::U16),
+                    val,
                 ))),
                 None,
             ),
@@ -216,6 +294,10 @@ impl Expression for LiteralExpression {
     fn get_token(&self) -> Token {
         self.tok.clone()
     }
+
+    fn get_literal(&self) -> Option<Literal> {
+        Some(self.literal.clone())
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -277,6 +359,65 @@ impl BinaryExpression {
     }
 }
 
+impl BinaryExpression {
+    /// Provides the code to load the address of the right-hand value into the temporary
+    /// register, if the value is held in memory and may be read directly by the operation
+    fn load_rhs_address(&self, state: &mut AsmGenState) -> Option<Vec<AsmToken>> {
+        fn in_memory(t: &Type) -> bool {
+            match t {
+                Type::Primitive { .. } | Type::Pointer { .. } => true,
+                Type::Constant { base } | Type::Alias { base, .. } => in_memory(base),
+                _ => false,
+            }
+        }
+
+        if matches!(self.operator, BinaryOperator::Land | BinaryOperator::Lor)
+            || !in_memory(&self.rhs.get_type().ok()?)
+        {
+            return None;
+        }
+
+        self.rhs
+            .load_address(state.temporary_register(), state)
+            .ok()
+    }
+
+    /// Provides the code for an addition with a product on the right-hand side, such as
+    /// `acc + (a * b)`, as a single multiply-accumulate into the left-hand value
+    fn load_multiply_accumulate(
+        &self,
+        reg: Register,
+        spare: Register,
+        state: &mut AsmGenState,
+    ) -> Result<Option<Vec<AsmToken>>, ErrorToken> {
+        let (a, b) = match (self.operator, self.rhs.get_product()) {
+            (BinaryOperator::Add, Some(p)) => p,
+            _ => return Ok(None),
+        };
+
+        let mut res = self.lhs.load_to(reg, spare, state)?;
+
+        state.push_registers();
+        let (reg_a, reg_b) = (state.reg_a(), state.reg_b());
+        let load_prod = a.load_to(reg_a, reg_b, state).and_then(|mut r| {
+            r.extend(load_in_window(reg_b, state, |ra, rb, s| {
+                b.load_to(ra, rb, s)
+            })?);
+            Ok(r)
+        });
+        state.pop_registers();
+
+        res.extend(load_prod?);
+        res.push(AsmToken::OperationLiteral(Box::new(OpMac::new(
+            ArgumentType::new(reg, self.lhs.get_base_primitive()?),
+            reg_a.into(),
+            reg_b.into(),
+        ))));
+
+        Ok(Some(res))
+    }
+}
+
 impl Expression for BinaryExpression {
     fn get_type(&self) -> Result<Type, TypeError> {
         self.lhs.get_type()
@@ -288,9 +429,30 @@ impl Expression for BinaryExpression {
         spare: Register,
         state: &mut AsmGenState,
     ) -> Result<Vec<AsmToken>, ErrorToken> {
+        if let Some(lit) = self.get_literal() {
+            return LiteralExpression::new(self.tok.clone(), lit).load_to(reg, spare, state);
+        }
+
+        if let Some(res) = self.load_multiply_accumulate(reg, spare, state)? {
+            return Ok(res);
+        }
+
         let mut res = self.lhs.load_to(reg, spare, state)?;
 
-        let load_val_b = self.rhs.load_to(state.temporary_register(), spare, state)?;
+        // Values held in memory are read as an indirect operand, rather than first being loaded
+        // into a register. Other values are computed in a new pair of working registers, as the
+        // right-hand side may itself use the temporary register
+        let (load_val_b, val_b) = match self.load_rhs_address(state) {
+            Some(v) => (v, ArgumentSource::indirect(state.temporary_register())),
+            None => {
+                state.push_registers();
+                let reg_rhs = state.reg_a();
+                let load_rhs = self.rhs.load_to(reg_rhs, state.reg_b(), state);
+                state.pop_registers();
+
+                (load_rhs?, reg_rhs.into())
+            }
+        };
         let mut uses_val_b = true;
 
         let reg_type = ArgumentType::new(reg, self.lhs.get_base_primitive()?);
@@ -299,70 +461,70 @@ impl Expression for BinaryExpression {
                 vec![AsmToken::OperationLiteral(Box::new(OpAdd::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
             BinaryOperator::Sub => {
                 vec![AsmToken::OperationLiteral(Box::new(OpSub::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
             BinaryOperator::Mul => {
                 vec![AsmToken::OperationLiteral(Box::new(OpMul::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
             BinaryOperator::Div => {
                 vec![AsmToken::OperationLiteral(Box::new(OpDiv::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
             BinaryOperator::Rem => {
                 vec![AsmToken::OperationLiteral(Box::new(OpRem::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
             BinaryOperator::Bshl => {
                 vec![AsmToken::OperationLiteral(Box::new(OpBshl::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
             BinaryOperator::Bshr => {
                 vec![AsmToken::OperationLiteral(Box::new(OpBshr::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
             BinaryOperator::Band => {
                 vec![AsmToken::OperationLiteral(Box::new(OpBand::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
             BinaryOperator::Bor => {
                 vec![AsmToken::OperationLiteral(Box::new(OpBor::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
             BinaryOperator::Bxor => {
                 vec![AsmToken::OperationLiteral(Box::new(OpBxor::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
             BinaryOperator::Land | BinaryOperator::Lor => {
@@ -375,48 +537,38 @@ impl Expression for BinaryExpression {
                         Box::new(OpTnz::new(reg.into()))
                     });
 
-                // TODO - Switch to using labels if needed
+                // Jump past the right-hand side by label rather than by a relative offset, as
+                // the length of the generated code may change when it is optimized
                 let load_val_b_new = self.rhs.load_to(reg, spare, state)?;
-                let mut test_code_vals = Vec::new();
-
-                // Determine if the value to load is less than the max allowed immediate.
-                // If so, use as a literal
-                // Otherwise, create the short circuit state and jump to that
-                if load_val_b_new.len() < u16::MAX as usize {
-                    test_code_vals.push(test_token);
-                    test_code_vals.push(AsmToken::OperationLiteral(Box::new(OpJmpri::new(
-                        load_val_b_new.len() as u16,
-                    ))));
-                    test_code_vals.extend(load_val_b_new);
-                } else {
-                    let label_val = format!("switch_short_circuit_val_{}", state.label_num);
-                    state.label_num += 1;
-
-                    test_code_vals.push(AsmToken::OperationLiteral(Box::new(OpLdn::new(
-                        ArgumentType::new(state.temporary_register(), DataType::U32),
-                    ))));
-                    test_code_vals.push(AsmToken::LoadLoc(label_val.clone()));
-                    test_code_vals.push(test_token);
-                    test_code_vals.push(AsmToken::OperationLiteral(Box::new(OpJmp::new(
+                let label_val = state.next_label("short_circuit");
+
+                let mut test_code_vals = vec![
+                    AsmToken::OperationLiteral(Box::new(OpLdn::new(ArgumentType::new(
+                        state.temporary_register(),
+                        DataType::U32,
+                    )))),
+                    AsmToken::LoadLoc(label_val.clone()),
+                    test_token,
+                    AsmToken::OperationLiteral(Box::new(OpJmp::new(
                         state.temporary_register().into(),
-                    ))));
-                    test_code_vals.extend(load_val_b_new);
-                    test_code_vals.push(AsmToken::CreateLabel(label_val));
-                }
+                    ))),
+                ];
+                test_code_vals.extend(load_val_b_new);
+                test_code_vals.push(AsmToken::CreateLabel(label_val));
                 test_code_vals
             }
             BinaryOperator::Eq => {
                 vec![AsmToken::OperationLiteral(Box::new(OpTeq::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
             BinaryOperator::Neq => {
                 vec![AsmToken::OperationLiteral(Box::new(OpTneq::new(
                     reg_type,
                     reg.into(),
-                    state.temporary_register().into(),
+                    val_b,
                 )))]
             }
         };
@@ -433,6 +585,20 @@ impl Expression for BinaryExpression {
     fn get_token(&self) -> Token {
         self.tok.clone()
     }
+
+    fn get_literal(&self) -> Option<Literal> {
+        self.lhs
+            .get_literal()?
+            .fold_binary(self.operator, &self.rhs.get_literal()?)
+    }
+
+    fn get_product(&self) -> Option<(&dyn Expression, &dyn Expression)> {
+        if self.operator == BinaryOperator::Mul && self.get_literal().is_none() {
+            Some((self.lhs.as_ref(), self.rhs.as_ref()))
+        } else {
+            None
+        }
+    }
 }
 
 pub struct AssignmentExpression {
@@ -453,7 +619,11 @@ impl Expression for AssignmentExpression {
         self.rval.get_type()
     }
 
-    fn load_address(&self, _reg: Register) -> Result<Vec<AsmToken>, ErrorToken> {
+    fn load_address(
+        &self,
+        _reg: Register,
+        _state: &mut AsmGenState,
+    ) -> Result<Vec<AsmToken>, ErrorToken> {
         Err(ErrorToken::new(
             self.get_token(),
             &ExpressionError::NotAddressable.to_string(),
@@ -466,7 +636,161 @@ impl Expression for AssignmentExpression {
         spare: Register,
         state: &mut AsmGenState,
     ) -> Result<Vec<AsmToken>, ErrorToken> {
-        panic!("assignment not implemented");
+        let mut res = self.lval.load_to(reg, spare, state)?;
+        res.extend(self.rval.load_address(spare, state)?);
+        res.push(AsmToken::OperationLiteral(Box::new(OpSav::new(
+            ArgumentType::new(spare, self.rval.get_base_primitive()?),
+            reg.into(),
+        ))));
+        Ok(res)
+    }
+
+    fn get_token(&self) -> Token {
+        self.tok.clone()
+    }
+}
+
+/// Provides the type used to compare values, where types with a base primitive are compared
+/// by that primitive
+fn value_type(t: Type) -> Type {
+    match t.base_primitive() {
+        Ok(base) => Type::Primitive { base },
+        Err(_) => t,
+    }
+}
+
+/// Provides a call to a function, following the calling convention described in
+/// [`FunctionDefinition`](super::FunctionDefinition)
+pub struct CallExpression {
+    tok: Token,
+    func: Box<dyn Expression>,
+    args: Vec<Box<dyn Expression>>,
+    params: Vec<Type>,
+    ret: Option<Type>,
+}
+
+impl CallExpression {
+    pub fn new(
+        tok: Token,
+        func: Box<dyn Expression>,
+        args: Vec<Box<dyn Expression>>,
+    ) -> Result<Self, ErrorToken> {
+        let (ret, params) = match func.get_type_tok()?.as_mut() {
+            Type::Function { ret, args } => (ret.map(|r| *r), args),
+            t => return Err(ErrorToken::new(tok, TypeError::NotCallable(t))),
+        };
+
+        if params.len() != args.len() {
+            return Err(ErrorToken::new(
+                tok,
+                TypeError::ArgumentCount(params.len(), args.len()),
+            ));
+        }
+
+        for (p, a) in params.iter().zip(args.iter()) {
+            let tp = value_type(p.clone());
+            let ta = value_type(a.get_type_tok()?);
+
+            if tp != ta {
+                return Err(ErrorToken::new(
+                    a.get_token(),
+                    TypeError::TypeMismatch(tp, ta),
+                ));
+            }
+        }
+
+        Ok(Self {
+            tok,
+            func,
+            args,
+            params,
+            ret,
+        })
+    }
+}
+
+impl Expression for CallExpression {
+    fn get_type(&self) -> Result<Type, TypeError> {
+        Ok(self.ret.clone().unwrap_or(Type::Opaque {
+            name: "void".to_string(),
+        }))
+    }
+
+    fn load_to(
+        &self,
+        reg: Register,
+        spare: Register,
+        state: &mut AsmGenState,
+    ) -> Result<Vec<AsmToken>, ErrorToken> {
+        let (offsets, frame_size) = ErrorToken::test(&self.tok, argument_layout(&self.params))?;
+        let sp_type = ArgumentType::new(Register::StackPointer, DataType::U32);
+
+        // Save the working registers that may hold values in use, as the frame call only saves
+        // the return address and the caller frame
+        let saved = (Register::first_gp_register().get_index()..state.current_register_count + 2)
+            .map(|i| Register::try_from(i).unwrap())
+            .collect::<Vec<_>>();
+        let mut res = saved
+            .iter()
+            .map(|r| AsmToken::OperationLiteral(Box::new(OpPush::new((*r).into()))))
+            .collect::<Vec<_>>();
+
+        // Reserve the argument frame, such that nested calls are placed after it
+        res.extend(load_u32(spare, frame_size as u32));
+        res.push(AsmToken::OperationLiteral(Box::new(OpAdd::new(
+            sp_type,
+            Register::StackPointer.into(),
+            spare.into(),
+        ))));
+
+        state.push_registers();
+        let (val_reg, addr_reg) = (state.reg_a(), state.reg_b());
+
+        for ((arg, param), offset) in self.args.iter().zip(&self.params).zip(offsets) {
+            res.extend(arg.load_to(val_reg, addr_reg, state)?);
+            res.extend(load_u32(addr_reg, (frame_size - offset) as u32));
+            res.push(AsmToken::OperationLiteral(Box::new(OpSub::new(
+                ArgumentType::new(addr_reg, DataType::U32),
+                Register::StackPointer.into(),
+                addr_reg.into(),
+            ))));
+            res.push(AsmToken::OperationLiteral(Box::new(OpSav::new(
+                ArgumentType::new(
+                    addr_reg,
+                    ErrorToken::test(&self.tok, param.base_primitive())?,
+                ),
+                val_reg.into(),
+            ))));
+        }
+
+        res.extend(self.func.load_to(val_reg, addr_reg, state)?);
+        res.push(AsmToken::OperationLiteral(Box::new(OpCallf::new(
+            val_reg.into(),
+        ))));
+        state.pop_registers();
+
+        // Release the argument frame, restore the working registers, and provide the return value
+        res.extend(load_u32(spare, frame_size as u32));
+        res.push(AsmToken::OperationLiteral(Box::new(OpSub::new(
+            sp_type,
+            Register::StackPointer.into(),
+            spare.into(),
+        ))));
+        res.extend(
+            saved
+                .iter()
+                .rev()
+                .map(|r| AsmToken::OperationLiteral(Box::new(OpPopr::new((*r).into())))),
+        );
+
+        if self.ret.is_some() {
+            res.push(AsmToken::OperationLiteral(Box::new(OpCopy::new(
+                reg.into(),
+                Register::Return.into(),
+            ))));
+        }
+
+        Ok(res)
     }
 
     fn get_token(&self) -> Token {
@@ -524,6 +848,12 @@ impl Expression for UnaryExpression {
         spare: Register,
         state: &mut AsmGenState,
     ) -> Result<Vec<AsmToken>, ErrorToken> {
+        if self.operator == UnaryOperator::AddressOf {
+            return self.expr.load_address(reg, state);
+        } else if let Some(lit) = self.get_literal() {
+            return LiteralExpression::new(self.tok.clone(), lit).load_to(reg, spare, state);
+        }
+
         let mut res = self.expr.load_to(reg, spare, state)?;
         let reg_type = ArgumentType::new(reg, self.get_base_primitive()?);
         match self.operator {
@@ -546,11 +876,8 @@ impl Expression for UnaryExpression {
                     reg.into(),
                 ))));
             }
-            UnaryOperator::AddressOf => {
-                res.extend(self.expr.load_address(reg)?);
-            }
+            UnaryOperator::AddressOf => (),
             UnaryOperator::Dereference => {
-                res.extend(self.expr.load_address(reg)?);
                 res.push(AsmToken::OperationLiteral(Box::new(OpLd::new(
                     reg_type,
                     reg.into(),
@@ -560,10 +887,16 @@ impl Expression for UnaryExpression {
         Ok(res)
     }
 
-    fn load_address(&self, reg: Register) -> Result<Vec<AsmToken>, ErrorToken> {
+    fn load_address(
+        &self,
+        reg: Register,
+        state: &mut AsmGenState,
+    ) -> Result<Vec<AsmToken>, ErrorToken> {
         match self.operator {
             UnaryOperator::Dereference => match self.expr.get_type_tok()? {
-                Type::Pointer { .. } => self.expr.load_address(reg),
+                Type::Pointer { .. } => Ok(load_in_window(reg, state, |a, b, state| {
+                    self.expr.load_to(a, b, state)
+                })?),
                 _ => Err(ErrorToken::new(
                     self.tok.clone(),
                     ExpressionError::NotAddressable,
@@ -579,6 +912,257 @@ impl Expression for UnaryExpression {
     fn get_token(&self) -> Token {
         self.tok.clone()
     }
+
+    fn get_literal(&self) -> Option<Literal> {
+        self.expr.get_literal()?.fold_unary(self.operator)
+    }
+}
+
+/// Provides an element of an array, or of the values following a pointer, as `base[index]`. The
+/// element address is the base address offset by the index multiplied by the element size
+pub struct IndexExpression {
+    tok: Token,
+    base: Box<dyn Expression>,
+    index: Box<dyn Expression>,
+    elem_type: Type,
+}
+
+impl IndexExpression {
+    pub fn new(
+        tok: Token,
+        base: Box<dyn Expression>,
+        index: Box<dyn Expression>,
+    ) -> Result<Self, TypeError> {
+        let elem_type = match base.get_type()? {
+            Type::Array { base, .. } | Type::Pointer { base } => *base,
+            t => return Err(TypeError::NotIndexable(t)),
+        };
+
+        let index_type = index.get_type()?;
+        if index_type.base_primitive()? == DataType::F32 {
+            return Err(TypeError::InvalidIndex(index_type));
+        }
+
+        elem_type.byte_count()?;
+
+        Ok(Self {
+            tok,
+            base,
+            index,
+            elem_type,
+        })
+    }
+
+    /// Provides the tokens to compute the element address into the first register, using the
+    /// second register as a spare
+    fn element_address(
+        &self,
+        reg: Register,
+        spare: Register,
+        state: &mut AsmGenState,
+    ) -> Result<Vec<AsmToken>, ErrorToken> {
+        let index_type = self.index.get_base_primitive()?;
+        let elem_size = ErrorToken::test(&self.tok, self.elem_type.byte_count())?;
+        let is_array = matches!(self.base.get_type_tok()?, Type::Array { .. });
+
+        let mut res = self.index.load_to(reg, spare, state)?;
+        if index_type != DataType::U32 {
+            res.push(AsmToken::OperationLiteral(Box::new(OpConv::new(
+                ArgumentType::new(reg, DataType::U32),
+                ArgumentType::new(reg, index_type),
+            ))));
+        }
+
+        res.extend(load_u32(spare, elem_size as u32));
+        res.push(AsmToken::OperationLiteral(Box::new(OpMul::new(
+            ArgumentType::new(reg, DataType::U32),
+            reg.into(),
+            spare.into(),
+        ))));
+
+        // Arrays provide the element storage directly, while pointers hold the storage address
+        res.extend(load_in_window(spare, state, |a, b, state| {
+            if is_array {
+                self.base.load_address(a, state)
+            } else {
+                self.base.load_to(a, b, state)
+            }
+        })?);
+
+        res.push(AsmToken::OperationLiteral(Box::new(OpAdd::new(
+            ArgumentType::new(reg, DataType::U32),
+            reg.into(),
+            spare.into(),
+        ))));
+
+        Ok(res)
+    }
+}
+
+impl Expression for IndexExpression {
+    fn get_type(&self) -> Result<Type, TypeError> {
+        Ok(self.elem_type.clone())
+    }
+
+    fn load_to(
+        &self,
+        reg: Register,
+        _spare: Register,
+        state: &mut AsmGenState,
+    ) -> Result<Vec<AsmToken>, ErrorToken> {
+        let mut res = self.load_address(reg, state)?;
+
+        // Nested arrays are provided by address, such that they may be indexed again
+        if !matches!(self.elem_type, Type::Array { .. }) {
+            res.push(AsmToken::OperationLiteral(Box::new(OpLd::new(
+                ArgumentType::new(reg, self.get_base_primitive()?),
+                reg.into(),
+            ))));
+        }
+
+        Ok(res)
+    }
+
+    fn load_address(
+        &self,
+        reg: Register,
+        state: &mut AsmGenState,
+    ) -> Result<Vec<AsmToken>, ErrorToken> {
+        load_in_window(reg, state, |a, b, state| self.element_address(a, b, state))
+    }
+
+    fn get_token(&self) -> Token {
+        self.tok.clone()
+    }
+}
+
+/// Provides a field of a struct, as `base.field`. When the base is a pointer to a struct, the
+/// field is accessed through the pointer. Field offsets are computed from the struct layout when
+/// the expression is created
+pub struct FieldExpression {
+    tok: Token,
+    base: Box<dyn Expression>,
+    is_pointer: bool,
+    offset: usize,
+    field_type: Type,
+}
+
+impl FieldExpression {
+    pub fn new(
+        tok: Token,
+        base: Box<dyn Expression>,
+        field: &str,
+        types: &TypeDict,
+    ) -> Result<Self, TypeError> {
+        let base_type = base.get_type()?;
+        let (struct_type, is_pointer) = match base_type {
+            Type::Pointer { base } => (*base, true),
+            t => (t, false),
+        };
+
+        // Structs referenced before their definition are stored as opaque types, and so are
+        // resolved by name
+        let def = match struct_type {
+            Type::Struct(def) => def,
+            Type::Opaque { name } => match types.parse_type(&name)? {
+                Type::Struct(def) => def,
+                t => return Err(TypeError::NotStruct(t)),
+            },
+            t => return Err(TypeError::NotStruct(t)),
+        };
+
+        let offset = def.offset_of(field)?;
+        let field_type = def
+            .fields
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, t)| *t.clone())
+            .ok_or_else(|| TypeError::FieldNotFound(field.into(), def.clone()))?;
+
+        Ok(Self {
+            tok,
+            base,
+            is_pointer,
+            offset,
+            field_type,
+        })
+    }
+}
+
+impl Expression for FieldExpression {
+    fn get_type(&self) -> Result<Type, TypeError> {
+        Ok(self.field_type.clone())
+    }
+
+    fn load_to(
+        &self,
+        reg: Register,
+        _spare: Register,
+        state: &mut AsmGenState,
+    ) -> Result<Vec<AsmToken>, ErrorToken> {
+        let mut res = self.load_address(reg, state)?;
+
+        // Array fields are provided by address, such that they may be indexed
+        if !matches!(self.field_type, Type::Array { .. }) {
+            res.push(AsmToken::OperationLiteral(Box::new(OpLd::new(
+                ArgumentType::new(reg, self.get_base_primitive()?),
+                reg.into(),
+            ))));
+        }
+
+        Ok(res)
+    }
+
+    fn load_address(
+        &self,
+        reg: Register,
+        state: &mut AsmGenState,
+    ) -> Result<Vec<AsmToken>, ErrorToken> {
+        load_in_window(reg, state, |a, b, state| {
+            let mut res = if self.is_pointer {
+                self.base.load_to(a, b, state)?
+            } else {
+                self.base.load_address(a, state)?
+            };
+
+            res.extend(load_u32(b, self.offset as u32));
+            res.push(AsmToken::OperationLiteral(Box::new(OpAdd::new(
+                ArgumentType::new(a, DataType::U32),
+                a.into(),
+                b.into(),
+            ))));
+
+            Ok(res)
+        })
+    }
+
+    fn get_token(&self) -> Token {
+        self.tok.clone()
+    }
+}
+
+/// Provides the tokens to compute a value in a new pair of working registers, such that the
+/// values held in the current working registers are kept, and then copies the result into the
+/// destination register
+fn load_in_window<F>(
+    reg: Register,
+    state: &mut AsmGenState,
+    f: F,
+) -> Result<Vec<AsmToken>, ErrorToken>
+where
+    F: FnOnce(Register, Register, &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken>,
+{
+    state.push_registers();
+    let (reg_a, reg_b) = (state.reg_a(), state.reg_b());
+    let res = f(reg_a, reg_b, state);
+    state.pop_registers();
+
+    let mut res = res?;
+    res.push(AsmToken::OperationLiteral(Box::new(OpCopy::new(
+        reg.into(),
+        reg_a.into(),
+    ))));
+    Ok(res)
 }
 
 pub struct AsExpression {
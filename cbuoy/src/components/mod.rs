@@ -1,11 +1,16 @@
 pub mod expression;
+pub mod optimize;
 pub mod statement;
 pub mod variable;
 
 use core::fmt;
 use expression::ExpressionError;
-use jib::cpu::Register;
-use jib_asm::{AsmToken, AsmTokenLoc, AssemblerErrorLoc, LocationInfo, TokenList};
+use jib::cpu::{DataType, Processor, Register};
+use jib_asm::{
+    argument::ArgumentType,
+    instructions::{OpAdd, OpLdn, OpRetf},
+    AsmToken, AsmTokenLoc, AssemblerErrorLoc, LocationInfo, TokenList,
+};
 use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
 
 use crate::{
@@ -16,14 +21,23 @@ use crate::{
 
 use self::{
     expression::Expression,
-    variable::{GlobalVariable, LocalVariable, Variable},
+    variable::{static_label, GlobalVariable, LocalVariable, Variable},
 };
 
 use super::types::{Type, TypeDict};
 
+/// Provides the labels that continue and break statements jump to within a loop
+#[derive(Debug, Clone)]
+pub struct LoopLabels {
+    pub continue_label: String,
+    pub break_label: String,
+}
+
 pub struct AsmGenState {
     pub label_num: u64,
     pub current_register_count: usize,
+    pub return_label: Option<String>,
+    pub loop_labels: Vec<LoopLabels>,
 }
 
 impl AsmGenState {
@@ -31,9 +45,27 @@ impl AsmGenState {
         Self {
             label_num: 0,
             current_register_count: Register::first_gp_register().get_index(),
+            return_label: None,
+            loop_labels: Vec::new(),
         }
     }
 
+    pub fn next_label(&mut self, prefix: &str) -> String {
+        let label = format!("{prefix}_{}", self.label_num);
+        self.label_num += 1;
+        label
+    }
+
+    /// Moves the working registers past the current pair, such that any values held in the
+    /// current registers are kept while generating the nested code
+    pub fn push_registers(&mut self) {
+        self.current_register_count += 2;
+    }
+
+    pub fn pop_registers(&mut self) {
+        self.current_register_count -= 2;
+    }
+
     pub fn reg_a(&self) -> Register {
         Register::try_from(self.current_register_count).unwrap()
     }
@@ -86,13 +118,19 @@ pub struct ParserScope {
 
 impl ParserScope {
     pub fn new(parent: Rc<RefCell<Self>>) -> Self {
+        let base_offset = parent.borrow().base_offset;
         Self {
             variables: HashMap::new(),
             scope_type: ParserScopeType::Child(parent),
-            base_offset: 0,
+            base_offset,
         }
     }
 
+    /// Sets the offset of the next local variable added to the scope, relative to the frame
+    pub fn set_offset(&mut self, offset: i32) {
+        self.base_offset = offset;
+    }
+
     pub fn has_variable(&self, name: &str) -> bool {
         let exists_local = self.variables.contains_key(name);
         let exists_global = match &self.scope_type {
@@ -130,6 +168,26 @@ impl ParserScope {
         }
     }
 
+    pub fn add_function(
+        &mut self,
+        tok: Token,
+        name: &str,
+        label: &str,
+        t: Type,
+    ) -> Result<(), ParserScopeError> {
+        if self.has_variable(name) {
+            return Err(ParserScopeError::DuplicateName(name.into()));
+        }
+
+        match &mut self.scope_type {
+            ParserScopeType::Root(global_vars) => {
+                global_vars.insert(name.into(), GlobalVariable::new_function(tok, label, t));
+                Ok(())
+            }
+            ParserScopeType::Child(parent) => parent.borrow_mut().add_function(tok, name, label, t),
+        }
+    }
+
     pub fn get_variable(&self, s: &str) -> Result<Box<dyn Variable>, ParserScopeError> {
         let gen_err = || Err(ParserScopeError::UnknownVariable(s.into()));
 
@@ -240,11 +298,16 @@ pub trait CodeComponent {
     }
 
     fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken>;
+
+    /// Provides any storage placed within the data section, after the program code
+    fn generate_data(&self, _state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        Ok(Vec::new())
+    }
 }
 
 pub trait BaseStatement: CodeComponent {}
 
-pub trait Statement {
+pub trait Statement: CodeComponent {
     fn stack_size(&self) -> usize;
 }
 
@@ -254,7 +317,52 @@ pub trait Function: BaseStatement {
     fn get_return_type(&self) -> Option<Type>;
 }
 
+/// Provides the number of bytes pushed onto the stack by the frame call instruction, being the
+/// return address and the caller frame
+pub const CALL_SAVE_SIZE: usize = 2 * Processor::BYTES_PER_WORD as usize;
+
+fn word_align(size: usize) -> usize {
+    size.next_multiple_of(Processor::BYTES_PER_WORD as usize)
+}
+
+/// Provides the offset of each parameter within the argument frame, along with the size of the
+/// frame. Parameters are packed in order, and the frame is padded to a whole number of words
+pub fn argument_layout(params: &[Type]) -> Result<(Vec<usize>, usize), TypeError> {
+    let mut offsets = Vec::new();
+    let mut size = 0;
+
+    for p in params {
+        offsets.push(size);
+        size += p.byte_count()?;
+    }
+
+    Ok((offsets, word_align(size)))
+}
+
+/// Provides the tokens to load a 32-bit constant into the provided register
+pub fn load_u32(reg: Register, val: u32) -> Vec<AsmToken> {
+    vec![
+        AsmToken::OperationLiteral(Box::new(OpLdn::new(ArgumentType::new(reg, DataType::U32)))),
+        AsmToken::Literal4(val),
+    ]
+}
+
+/// Provides a function with a body, using the following calling convention
+///
+/// 1. The caller pushes the working registers holding values in use, and then reserves the
+///    argument frame at the top of the stack and saves each argument at its offset within it
+/// 2. The caller loads the function address and calls the function with a frame call, which
+///    pushes the return address and the caller frame, and points the frame register (the
+///    argument base register) to the top of the stack
+/// 3. The callee reserves stack space for local variables at the start of the frame, such that
+///    local variables are at positive offsets from the frame register, and parameters are at
+///    negative offsets before the saved return address
+/// 4. The callee places any return value in the return register, and returns with a frame
+///    return, which releases the local variables and restores the caller frame
+/// 5. The caller releases the argument frame, pops the saved working registers, and reads the
+///    return register
 pub struct FunctionDefinition {
+    tok: Token,
     name: String,
     parameters: Vec<(String, Type)>,
     return_type: Option<Type>,
@@ -263,21 +371,22 @@ pub struct FunctionDefinition {
 
 impl FunctionDefinition {
     pub fn new(
-        name: &str,
+        tok: Token,
         parameters: Vec<(String, Type)>,
         return_type: Option<Type>,
         statements: Vec<Box<dyn Statement>>,
     ) -> Self {
         Self {
-            name: name.to_string(),
+            name: tok.get_value().to_string(),
+            tok,
             parameters,
             return_type,
             statements,
         }
     }
 
-    fn assembler_label(&self) -> String {
-        format!("func_def_{}", self.name)
+    pub fn assembler_label(name: &str) -> String {
+        format!("func_def_{name}")
     }
 }
 
@@ -285,13 +394,36 @@ impl BaseStatement for FunctionDefinition {}
 
 impl CodeComponent for FunctionDefinition {
     fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
-        let tokens = vec![AsmToken::CreateLabel(self.assembler_label())];
+        let label = Self::assembler_label(&self.name);
+        let return_label = format!("{label}_return");
+
+        let local_size = word_align(self.statements.iter().map(|s| s.stack_size()).sum()) as u32;
+
+        let tmp = state.temporary_register();
+        let sp_type = ArgumentType::new(Register::StackPointer, DataType::U32);
 
-        // TODO - Create spots for the input parameters?
+        // The function entry and exit are marked with the line of the function name
+        let line = AsmToken::SourceLine(self.tok.get_line() + 1);
+        let mut tokens = vec![AsmToken::CreateLabel(label), line.clone()];
 
+        // Reserve space for the local variables
+        tokens.extend(load_u32(tmp, local_size));
+        tokens.push(AsmToken::OperationLiteral(Box::new(OpAdd::new(
+            sp_type,
+            Register::StackPointer.into(),
+            tmp.into(),
+        ))));
+
+        let prev_return = state.return_label.replace(return_label.clone());
         for s in self.statements.iter() {
-            todo!("generate code for statements");
+            tokens.extend(s.generate_code(state)?);
         }
+        state.return_label = prev_return;
+
+        // Release the local variables and restore the caller frame
+        tokens.push(AsmToken::CreateLabel(return_label));
+        tokens.push(line);
+        tokens.push(AsmToken::OperationLiteral(Box::new(OpRetf)));
 
         Ok(tokens)
     }
@@ -307,15 +439,23 @@ impl Function for FunctionDefinition {
     }
 }
 
+/// Provides a function at a fixed address, stored as a global function pointer
 pub struct FunctionPtr {
+    name: String,
     parameters: Vec<(String, Type)>,
     return_type: Option<Type>,
-    addr: u32, // TODO - Update Type Here
+    addr: u32,
 }
 
 impl FunctionPtr {
-    pub fn new(parameters: Vec<(String, Type)>, return_type: Option<Type>, addr: u32) -> Self {
+    pub fn new(
+        name: &str,
+        parameters: Vec<(String, Type)>,
+        return_type: Option<Type>,
+        addr: u32,
+    ) -> Self {
         Self {
+            name: name.to_string(),
             parameters,
             return_type,
             addr,
@@ -326,8 +466,11 @@ impl FunctionPtr {
 impl BaseStatement for FunctionPtr {}
 
 impl CodeComponent for FunctionPtr {
-    fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
-        todo!("generate code to jump to the stored function pointer?");
+    fn generate_code(&self, _state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        Ok(vec![
+            AsmToken::CreateLabel(static_label(&self.name)),
+            AsmToken::Literal4(self.addr),
+        ])
     }
 }
 
@@ -363,6 +506,7 @@ impl AsmFunction {
                 line: 0,
                 full_line: Some(l.into()),
                 base_loc: None,
+                file: None,
             };
 
             if let Err(err) = state.parse_line(l, loc) {
@@ -370,6 +514,7 @@ impl AsmFunction {
                     err,
                     loc: LocationInfo {
                         base_loc: None,
+                        file: None,
                         full_line: Some(l.into()),
                         line: 0,
                     },
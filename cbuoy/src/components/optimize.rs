@@ -0,0 +1,337 @@
+use jib::cpu::{DataType, Opcode, Processor, Register};
+use jib_asm::{
+    argument::ArgumentType,
+    instructions::{OpCopy, OpLdi},
+    AsmToken,
+};
+
+/// Provides the instructions that skip the following instruction word when the test fails,
+/// such that the following instruction must not be removed or resized
+const CONDITIONAL_OPCODES: [Opcode; 8] = [
+    Processor::OP_EQ,
+    Processor::OP_NEQ,
+    Processor::OP_GREATER,
+    Processor::OP_GREATER_EQ,
+    Processor::OP_LESS,
+    Processor::OP_LESS_EQ,
+    Processor::OP_TEST_ZERO,
+    Processor::OP_TEST_NOT_ZERO,
+];
+
+/// Provides the decoded fields of a generated instruction
+#[derive(Debug, Clone, Copy)]
+struct Decoded {
+    opcode: Opcode,
+    args: [u8; 3],
+}
+
+impl Decoded {
+    const REGISTER_MASK: u8 = 0x1F;
+    const INDIRECT_FLAG: u8 = 0x20;
+
+    fn new(tok: &AsmToken) -> Option<Self> {
+        match tok {
+            AsmToken::OperationLiteral(op) => {
+                let bytes = op.to_bytes();
+                Some(Self {
+                    opcode: Opcode::from(bytes[0]),
+                    args: [bytes[1], bytes[2], bytes[3]],
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn reg(&self, i: usize) -> usize {
+        (self.args[i] & Self::REGISTER_MASK) as usize
+    }
+
+    /// Determines whether the source argument is a register-indirect operand
+    fn indirect(&self, i: usize) -> bool {
+        self.args[i] & Self::INDIRECT_FLAG != 0
+    }
+
+    fn data_type(&self) -> Option<DataType> {
+        DataType::try_from(self.args[0] >> 5).ok()
+    }
+
+    fn is(&self, opcode: Opcode) -> bool {
+        self.opcode == opcode
+    }
+
+    /// Determines whether the instruction loads the immediate value zero
+    fn is_zero_load(&self) -> bool {
+        self.is(Processor::OP_LOAD_IMM) && self.args[1] == 0 && self.args[2] == 0
+    }
+}
+
+/// Rewrites the generated assembly tokens, replacing common instruction sequences produced by
+/// the code generator with shorter equivalents.
+///
+/// Sequences are only matched within straight-line code, as any label between instructions
+/// ends the match, and an instruction following a conditional test is never removed or
+/// resized. The generated code must not contain relative jumps, as the distance between
+/// instructions may change
+pub fn peephole(tokens: Vec<AsmToken>) -> Vec<AsmToken> {
+    let mut out: Vec<AsmToken> = Vec::with_capacity(tokens.len());
+
+    for tok in tokens {
+        out.push(tok);
+        while reduce(&mut out) {}
+    }
+
+    out
+}
+
+/// Replaces the tokens at the provided ascending positions with the replacement, if any,
+/// keeping any source line markers between them
+fn replace(out: &mut Vec<AsmToken>, positions: &[usize], replacement: Option<AsmToken>) {
+    for p in positions.iter().rev() {
+        out.remove(*p);
+    }
+    if let Some(t) = replacement {
+        out.insert(positions[0], t);
+    }
+}
+
+/// Attempts to replace a sequence at the end of the token list, providing true if the list was
+/// modified. Source line markers generate no code, and so are skipped when matching sequences
+fn reduce(out: &mut Vec<AsmToken>) -> bool {
+    let mut idx = out
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, t)| !matches!(t, AsmToken::SourceLine(_)))
+        .map(|(i, _)| i)
+        .take(5)
+        .collect::<Vec<_>>();
+    idx.reverse();
+
+    let n = idx.len();
+    let tok = |i: usize| &out[idx[i]];
+    let decoded = |i: usize| idx.get(i).and_then(|j| Decoded::new(&out[*j]));
+    let guarded = |start: usize| {
+        start
+            .checked_sub(1)
+            .and_then(decoded)
+            .is_some_and(|d| CONDITIONAL_OPCODES.iter().any(|c| d.is(*c)))
+    };
+
+    // Load small 32-bit constants as an immediate rather than from the following word
+    if n >= 2 && !guarded(n - 2) {
+        if let (Some(ldn), AsmToken::Literal4(val)) = (decoded(n - 2), tok(n - 1)) {
+            let four_bytes = ldn.data_type().is_some_and(|dt| dt.byte_size() == 4);
+            if ldn.is(Processor::OP_LOAD_NEXT) && four_bytes {
+                let imm = if *val <= u16::MAX as u32 {
+                    Some((DataType::U16, *val as u16))
+                } else if let Ok(v) = i16::try_from(*val as i32) {
+                    Some((DataType::I16, v as u16))
+                } else {
+                    None
+                };
+
+                if let Some((dt, imm)) = imm {
+                    let reg = Register::GeneralPurpose(ldn.reg(0));
+                    let ldi = OpLdi::new(ArgumentType::new(reg, dt), imm);
+                    replace(
+                        out,
+                        &idx[n - 2..],
+                        Some(AsmToken::OperationLiteral(Box::new(ldi))),
+                    );
+                    return true;
+                }
+            }
+        }
+    }
+
+    if n >= 2 && !guarded(n - 2) {
+        if let (Some(a), Some(b)) = (decoded(n - 2), decoded(n - 1)) {
+            let word_op = matches!(b.data_type(), Some(DataType::U32 | DataType::I32))
+                && !b.indirect(1)
+                && !b.indirect(2);
+
+            // Adding a register to zero is a copy of that register
+            if a.is_zero_load()
+                && b.is(Processor::OP_ADD)
+                && word_op
+                && b.reg(0) == a.reg(0)
+                && b.reg(1) == a.reg(0)
+                && b.reg(2) != a.reg(0)
+            {
+                let copy = OpCopy::new(
+                    Register::GeneralPurpose(a.reg(0)).into(),
+                    Register::GeneralPurpose(b.reg(2)).into(),
+                );
+                replace(
+                    out,
+                    &idx[n - 2..],
+                    Some(AsmToken::OperationLiteral(Box::new(copy))),
+                );
+                return true;
+            }
+
+            // Adding or subtracting zero through the temporary register has no effect, as the
+            // temporary register is always loaded just before it is used
+            if a.is_zero_load()
+                && a.reg(0) == Register::last_register().get_index()
+                && (b.is(Processor::OP_ADD) || b.is(Processor::OP_SUB))
+                && word_op
+                && b.reg(0) == b.reg(1)
+                && b.reg(2) == a.reg(0)
+                && b.reg(0) != a.reg(0)
+            {
+                replace(out, &idx[n - 2..], None);
+                return true;
+            }
+
+            // Copying a value back to the register it was copied from has no effect
+            if a.is(Processor::OP_COPY)
+                && b.is(Processor::OP_COPY)
+                && a.reg(0) == b.reg(1)
+                && a.reg(1) == b.reg(0)
+            {
+                replace(out, &idx[n - 1..], None);
+                return true;
+            }
+        }
+    }
+
+    // Jumping to the label that immediately follows has no effect
+    if n >= 4 && !guarded(n - 4) {
+        if let (Some(ldn), AsmToken::LoadLoc(target), Some(jmp), AsmToken::CreateLabel(label)) =
+            (decoded(n - 4), tok(n - 3), decoded(n - 2), tok(n - 1))
+        {
+            if ldn.is(Processor::OP_LOAD_NEXT)
+                && jmp.is(Processor::OP_JUMP)
+                && jmp.reg(0) == ldn.reg(0)
+                && target == label
+            {
+                replace(out, &idx[n - 4..n - 1], None);
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jib_asm::instructions::{OpAdd, OpJmp, OpLdn, OpTz};
+
+    fn op<T: jib_asm::instructions::Instruction + 'static>(inst: T) -> AsmToken {
+        AsmToken::OperationLiteral(Box::new(inst))
+    }
+
+    fn words(tokens: &[AsmToken]) -> Vec<String> {
+        tokens
+            .iter()
+            .map(|t| match t {
+                AsmToken::OperationLiteral(op) => jib_asm::disassemble::disassemble(op.to_u32()),
+                AsmToken::CreateLabel(l) => format!("{l}:"),
+                AsmToken::LoadLoc(l) => format!(".loadloc {l}"),
+                AsmToken::Literal4(v) => format!(".u32 {v}"),
+                AsmToken::SourceLine(l) => format!(".loc {l}"),
+                _ => "?".into(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_peephole() {
+        let r6 = Register::GeneralPurpose(6);
+        let r7 = Register::GeneralPurpose(7);
+        let tmp = Register::last_register();
+        let arg = Register::ArgumentBase;
+
+        let tokens = vec![
+            op(OpLdn::new(ArgumentType::new(r6, DataType::U32))),
+            AsmToken::Literal4(0),
+            op(OpAdd::new(
+                ArgumentType::new(r6, DataType::U32),
+                r6.into(),
+                arg.into(),
+            )),
+            op(OpLdn::new(ArgumentType::new(tmp, DataType::U32))),
+            AsmToken::Literal4(0),
+            op(OpAdd::new(
+                ArgumentType::new(r6, DataType::U32),
+                r6.into(),
+                tmp.into(),
+            )),
+            op(OpLdn::new(ArgumentType::new(r7, DataType::I32))),
+            AsmToken::Literal4(-2i32 as u32),
+            op(OpLdn::new(ArgumentType::new(r7, DataType::U32))),
+            AsmToken::Literal4(0x12345),
+            op(OpLdn::new(ArgumentType::new(r7, DataType::U32))),
+            AsmToken::LoadLoc("end".into()),
+            op(OpJmp::new(r7.into())),
+            AsmToken::CreateLabel("end".into()),
+        ];
+
+        let expected = [
+            words(&[op(OpCopy::new(r6.into(), arg.into()))])[0].clone(),
+            words(&[op(OpLdi::new(ArgumentType::new(r7, DataType::I16), 0xfffe))])[0].clone(),
+            words(&[op(OpLdn::new(ArgumentType::new(r7, DataType::U32)))])[0].clone(),
+            ".u32 74565".into(),
+            "end:".into(),
+        ];
+
+        assert_eq!(words(&peephole(tokens)), expected);
+    }
+
+    #[test]
+    fn test_peephole_conditional() {
+        let r6 = Register::GeneralPurpose(6);
+        let r7 = Register::GeneralPurpose(7);
+
+        let tokens = vec![
+            op(OpLdn::new(ArgumentType::new(r7, DataType::U32))),
+            AsmToken::LoadLoc("end".into()),
+            op(OpTz::new(r6.into())),
+            op(OpJmp::new(r7.into())),
+            AsmToken::CreateLabel("end".into()),
+            op(OpTz::new(r6.into())),
+            op(OpLdn::new(ArgumentType::new(r7, DataType::U32))),
+            AsmToken::Literal4(1),
+        ];
+
+        let expected = words(&tokens);
+        assert_eq!(words(&peephole(tokens)), expected);
+    }
+
+    #[test]
+    fn test_peephole_source_lines() {
+        let r6 = Register::GeneralPurpose(6);
+        let r7 = Register::GeneralPurpose(7);
+
+        let tokens = vec![
+            AsmToken::SourceLine(3),
+            op(OpLdn::new(ArgumentType::new(r6, DataType::U32))),
+            AsmToken::Literal4(5),
+            op(OpTz::new(r6.into())),
+            AsmToken::SourceLine(4),
+            op(OpLdn::new(ArgumentType::new(r7, DataType::U32))),
+            AsmToken::Literal4(1),
+            op(OpLdn::new(ArgumentType::new(r7, DataType::U32))),
+            AsmToken::LoadLoc("end".into()),
+            op(OpJmp::new(r7.into())),
+            AsmToken::SourceLine(5),
+            AsmToken::CreateLabel("end".into()),
+        ];
+
+        let expected = [
+            ".loc 3".into(),
+            words(&[op(OpLdi::new(ArgumentType::new(r6, DataType::U16), 5))])[0].clone(),
+            words(&[op(OpTz::new(r6.into()))])[0].clone(),
+            ".loc 4".into(),
+            words(&[op(OpLdn::new(ArgumentType::new(r7, DataType::U32)))])[0].clone(),
+            ".u32 1".into(),
+            ".loc 5".into(),
+            "end:".into(),
+        ];
+
+        assert_eq!(words(&peephole(tokens)), expected);
+    }
+}
@@ -1,10 +1,16 @@
-use jib_asm::{AsmToken, AsmTokenLoc, LocationInfo};
+use jib::cpu::{DataType, Register};
+use jib_asm::{
+    argument::ArgumentType,
+    instructions::{OpCopy, OpJmp, OpLdn, OpSav, OpTz},
+    AsmToken,
+};
 
-use crate::types::Type;
+use crate::{tokenizer::Token, types::Type};
 
 use super::{
-    expression::Expression, variable::Variable, AsmGenState, BaseStatement, CodeComponent,
-    ErrorToken, Statement,
+    expression::{Expression, Literal},
+    variable::{static_label, Variable},
+    AsmGenState, BaseStatement, CodeComponent, ErrorToken, LoopLabels, Statement,
 };
 
 pub struct GlobalDefinitionStatement {
@@ -26,8 +32,13 @@ impl GlobalDefinitionStatement {
         self.init_expr = Some(expr);
     }
 
-    fn assmebler_label(&self) -> String {
-        format!("static_var_{}", self.name)
+    /// Provides the initial value if it can be stored directly within the data section, which
+    /// requires a literal initializer matching the size and primitive type of the variable
+    fn static_init(&self) -> Option<Literal> {
+        let lit = self.init_expr.as_ref()?.get_literal()?;
+        let matches = self.var_type.base_primitive().ok()? == lit.base_type()
+            && self.var_type.byte_count().ok()? == lit.to_bytes().len();
+        matches.then_some(lit)
     }
 }
 
@@ -41,27 +52,69 @@ impl BaseStatement for GlobalDefinitionStatement {}
 
 impl CodeComponent for GlobalDefinitionStatement {
     fn generate_init_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
-        if let Some(e) = &self.init_expr {
-            Ok(ErrorToken::test(
-                &e.get_token(),
-                e.load_to(state.reg_a(), state.reg_b(), state),
-            )?)
+        if self.static_init().is_some() {
+            Ok(Vec::new())
+        } else if let Some(e) = &self.init_expr {
+            let (reg_a, reg_b) = (state.reg_a(), state.reg_b());
+            let mut v = e.load_to(reg_a, reg_b, state)?;
+            v.push(AsmToken::OperationLiteral(Box::new(OpLdn::new(
+                ArgumentType::new(reg_b, DataType::U32),
+            ))));
+            v.push(AsmToken::LoadLoc(static_label(&self.name)));
+            v.push(AsmToken::OperationLiteral(Box::new(OpSav::new(
+                ArgumentType::new(reg_b, e.get_base_primitive()?),
+                reg_a.into(),
+            ))));
+            Ok(v)
         } else {
             Ok(Vec::new())
         }
     }
 
-    fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
-        let mut v = vec![jib_asm::AsmToken::CreateLabel(self.assmebler_label())];
+    fn generate_code(&self, _state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        Ok(Vec::new())
+    }
+
+    fn generate_data(&self, _state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        // Start each variable on a word boundary, as struct sizes need not be a multiple of a word
+        let mut v = vec![
+            AsmToken::AlignInstruction,
+            AsmToken::CreateLabel(static_label(&self.name)),
+        ];
 
-        for _ in 0..self.stack_size() {
-            v.push(jib_asm::AsmToken::Literal1(0));
+        if let Some(lit) = self.static_init() {
+            v.extend(lit.to_bytes().into_iter().map(AsmToken::Literal1));
+        } else {
+            for _ in 0..self.stack_size() {
+                v.push(AsmToken::Literal1(0));
+            }
         }
 
         Ok(v)
     }
 }
 
+/// Marks the code generated for a statement with the source line of the statement, such that
+/// the debug information of the program can map each instruction to its statement
+pub struct SourceLineStatement {
+    pub line: usize,
+    pub statement: Box<dyn Statement>,
+}
+
+impl Statement for SourceLineStatement {
+    fn stack_size(&self) -> usize {
+        self.statement.stack_size()
+    }
+}
+
+impl CodeComponent for SourceLineStatement {
+    fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        let mut v = vec![AsmToken::SourceLine(self.line)];
+        v.extend(self.statement.generate_code(state)?);
+        Ok(v)
+    }
+}
+
 pub struct ExpressionStatement {
     pub expr: Box<dyn Expression>,
 }
@@ -78,21 +131,195 @@ impl Statement for ExpressionStatement {
     }
 }
 
+impl CodeComponent for ExpressionStatement {
+    fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        self.expr.load_to(state.reg_a(), state.reg_b(), state)
+    }
+}
+
 pub struct IfStatement {
     pub conditional: Box<dyn Expression>,
     pub statements: Vec<Box<dyn Statement>>,
-    pub else_clause: Option<Box<dyn Statement>>,
+    pub else_statements: Vec<Box<dyn Statement>>,
 }
 
 impl Statement for IfStatement {
     fn stack_size(&self) -> usize {
         self.statements
             .iter()
+            .chain(self.else_statements.iter())
             .map(|s| s.stack_size())
             .fold(0, |a, b| a + b)
     }
 }
 
+impl CodeComponent for IfStatement {
+    fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        let (reg_a, reg_b) = (state.reg_a(), state.reg_b());
+        let else_label = state.next_label("if_else");
+        let end_label = state.next_label("if_end");
+
+        let mut v = self.conditional.load_to(reg_a, reg_b, state)?;
+        v.extend(jump_to(reg_b, &else_label, Some(reg_a)));
+
+        for s in self.statements.iter() {
+            v.extend(s.generate_code(state)?);
+        }
+
+        if !self.else_statements.is_empty() {
+            v.extend(jump_to(reg_b, &end_label, None));
+        }
+
+        v.push(AsmToken::CreateLabel(else_label));
+        for s in self.else_statements.iter() {
+            v.extend(s.generate_code(state)?);
+        }
+
+        v.push(AsmToken::CreateLabel(end_label));
+        Ok(v)
+    }
+}
+
+pub struct WhileStatement {
+    pub line: usize,
+    pub conditional: Box<dyn Expression>,
+    pub statements: Vec<Box<dyn Statement>>,
+}
+
+impl Statement for WhileStatement {
+    fn stack_size(&self) -> usize {
+        self.statements.iter().map(|s| s.stack_size()).sum()
+    }
+}
+
+impl CodeComponent for WhileStatement {
+    fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        let (reg_a, reg_b) = (state.reg_a(), state.reg_b());
+        let labels = LoopLabels {
+            continue_label: state.next_label("while_start"),
+            break_label: state.next_label("while_end"),
+        };
+
+        let mut v = vec![AsmToken::CreateLabel(labels.continue_label.clone())];
+        v.extend(self.conditional.load_to(reg_a, reg_b, state)?);
+        v.extend(jump_to(reg_b, &labels.break_label, Some(reg_a)));
+
+        v.extend(loop_body(&self.statements, &labels, state)?);
+
+        // The jump back to the condition belongs to the loop, rather than the last statement
+        v.push(AsmToken::SourceLine(self.line));
+        v.extend(jump_to(reg_b, &labels.continue_label, None));
+        v.push(AsmToken::CreateLabel(labels.break_label));
+        Ok(v)
+    }
+}
+
+pub struct ForStatement {
+    pub line: usize,
+    pub init: Option<Box<dyn Statement>>,
+    pub conditional: Option<Box<dyn Expression>>,
+    pub step: Option<Box<dyn Expression>>,
+    pub statements: Vec<Box<dyn Statement>>,
+}
+
+impl Statement for ForStatement {
+    fn stack_size(&self) -> usize {
+        self.init
+            .iter()
+            .chain(self.statements.iter())
+            .map(|s| s.stack_size())
+            .sum()
+    }
+}
+
+impl CodeComponent for ForStatement {
+    fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        let (reg_a, reg_b) = (state.reg_a(), state.reg_b());
+        let start_label = state.next_label("for_start");
+        let labels = LoopLabels {
+            continue_label: state.next_label("for_next"),
+            break_label: state.next_label("for_end"),
+        };
+
+        let mut v = Vec::new();
+        if let Some(s) = &self.init {
+            v.extend(s.generate_code(state)?);
+        }
+
+        v.push(AsmToken::CreateLabel(start_label.clone()));
+        if let Some(e) = &self.conditional {
+            v.extend(e.load_to(reg_a, reg_b, state)?);
+            v.extend(jump_to(reg_b, &labels.break_label, Some(reg_a)));
+        }
+
+        v.extend(loop_body(&self.statements, &labels, state)?);
+
+        v.push(AsmToken::CreateLabel(labels.continue_label));
+        v.push(AsmToken::SourceLine(self.line));
+        if let Some(e) = &self.step {
+            v.extend(e.load_to(reg_a, reg_b, state)?);
+        }
+
+        v.extend(jump_to(reg_b, &start_label, None));
+        v.push(AsmToken::CreateLabel(labels.break_label));
+        Ok(v)
+    }
+}
+
+/// Provides the code for the statements within a loop, where any break or continue statements
+/// jump to the provided loop labels
+fn loop_body(
+    statements: &[Box<dyn Statement>],
+    labels: &LoopLabels,
+    state: &mut AsmGenState,
+) -> Result<Vec<AsmToken>, ErrorToken> {
+    state.loop_labels.push(labels.clone());
+
+    let mut v = Vec::new();
+    for s in statements.iter() {
+        match s.generate_code(state) {
+            Ok(t) => v.extend(t),
+            Err(e) => {
+                state.loop_labels.pop();
+                return Err(e);
+            }
+        }
+    }
+
+    state.loop_labels.pop();
+    Ok(v)
+}
+
+/// Provides a break or continue statement, jumping to the end or the next iteration of the
+/// innermost loop
+pub struct LoopControlStatement {
+    pub tok: Token,
+    pub is_break: bool,
+}
+
+impl Statement for LoopControlStatement {
+    fn stack_size(&self) -> usize {
+        0
+    }
+}
+
+impl CodeComponent for LoopControlStatement {
+    fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        let label = match state.loop_labels.last() {
+            Some(l) if self.is_break => l.break_label.clone(),
+            Some(l) => l.continue_label.clone(),
+            None => {
+                return Err(ErrorToken::new(
+                    self.tok.clone(),
+                    format!("{} outside of a loop", self.tok.get_value()),
+                ))
+            }
+        };
+
+        Ok(jump_to(state.reg_b(), &label, None))
+    }
+}
+
 pub struct VariableInitStatement {
     var: Box<dyn Variable>,
     init_expr: Option<Box<dyn Expression>>,
@@ -112,8 +339,25 @@ impl Statement for VariableInitStatement {
     }
 }
 
+impl CodeComponent for VariableInitStatement {
+    fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        let mut v = Vec::new();
+        if let Some(e) = &self.init_expr {
+            let (reg_a, reg_b) = (state.reg_a(), state.reg_b());
+            v.extend(e.load_to(reg_a, reg_b, state)?);
+            v.extend(self.var.load_address(reg_b, state)?);
+            v.push(AsmToken::OperationLiteral(Box::new(OpSav::new(
+                ArgumentType::new(reg_b, self.var.get_base_primitive()?),
+                reg_a.into(),
+            ))));
+        }
+        Ok(v)
+    }
+}
+
 pub struct ReturnStatement {
-    pub expr: Box<dyn Expression>,
+    pub tok: Token,
+    pub expr: Option<Box<dyn Expression>>,
 }
 
 impl Statement for ReturnStatement {
@@ -121,3 +365,46 @@ impl Statement for ReturnStatement {
         0
     }
 }
+
+impl CodeComponent for ReturnStatement {
+    fn generate_code(&self, state: &mut AsmGenState) -> Result<Vec<AsmToken>, ErrorToken> {
+        let label = match &state.return_label {
+            Some(l) => l.clone(),
+            None => {
+                return Err(ErrorToken::new(
+                    self.tok.clone(),
+                    "return outside of a function",
+                ))
+            }
+        };
+
+        let mut v = Vec::new();
+        if let Some(e) = &self.expr {
+            let (reg_a, reg_b) = (state.reg_a(), state.reg_b());
+            v.extend(e.load_to(reg_a, reg_b, state)?);
+            v.push(AsmToken::OperationLiteral(Box::new(OpCopy::new(
+                Register::Return.into(),
+                reg_a.into(),
+            ))));
+        }
+
+        v.extend(jump_to(state.reg_b(), &label, None));
+        Ok(v)
+    }
+}
+
+/// Provides the tokens to jump to the provided label, using the register to hold the address. If
+/// a test register is provided, the jump is only taken if the test register is zero
+fn jump_to(reg: Register, label: &str, test_zero: Option<Register>) -> Vec<AsmToken> {
+    let mut v = vec![
+        AsmToken::OperationLiteral(Box::new(OpLdn::new(ArgumentType::new(reg, DataType::U32)))),
+        AsmToken::LoadLoc(label.to_string()),
+    ];
+
+    if let Some(t) = test_zero {
+        v.push(AsmToken::OperationLiteral(Box::new(OpTz::new(t.into()))));
+    }
+
+    v.push(AsmToken::OperationLiteral(Box::new(OpJmp::new(reg.into()))));
+    v
+}
@@ -14,7 +14,7 @@ use crate::{
 };
 
 use super::{
-    expression::{Expression, Literal},
+    expression::{Expression, ExpressionError, Literal},
     AsmGenState, ErrorToken,
 };
 
@@ -127,11 +127,11 @@ impl Expression for LocalVariable {
         &self,
         reg: Register,
         _spare: Register,
-        _state: &mut AsmGenState,
+        state: &mut AsmGenState,
     ) -> Result<Vec<AsmToken>, ErrorToken> {
         let base_type = self.get_base_primitive()?;
 
-        let mut res = self.load_address(reg)?;
+        let mut res = self.load_address(reg, state)?;
         res.push(AsmToken::OperationLiteral(Box::new(OpLd::new(
             ArgumentType::new(reg, base_type),
             reg.into(),
@@ -140,7 +140,11 @@ impl Expression for LocalVariable {
         Ok(res)
     }
 
-    fn load_address(&self, reg: Register) -> Result<Vec<AsmToken>, ErrorToken> {
+    fn load_address(
+        &self,
+        reg: Register,
+        _state: &mut AsmGenState,
+    ) -> Result<Vec<AsmToken>, ErrorToken> {
         Ok(vec![
             AsmToken::OperationLiteral(Box::new(OpLdn::new(ArgumentType::new(
                 reg,
@@ -166,11 +170,17 @@ impl Variable for LocalVariable {
     }
 }
 
+/// Provides the assembler label for the storage of a global variable
+pub fn static_label(name: &str) -> String {
+    format!("static_var_{name}")
+}
+
 #[derive(Debug, Clone)]
 pub struct GlobalVariable {
     tok: Token,
     var_type: Type,
     var_label: String,
+    is_function: bool,
 }
 
 impl GlobalVariable {
@@ -178,9 +188,30 @@ impl GlobalVariable {
         Self {
             tok,
             var_type: t,
-            var_label: format!("STATIC_VAR_LBL_{name}"),
+            var_label: static_label(name),
+            is_function: false,
+        }
+    }
+
+    /// Creates a function name, where the value is the address of the function label
+    pub fn new_function(tok: Token, label: &str, t: Type) -> Self {
+        Self {
+            tok,
+            var_type: t,
+            var_label: label.to_string(),
+            is_function: true,
         }
     }
+
+    fn load_label(&self, reg: Register) -> Vec<AsmToken> {
+        vec![
+            AsmToken::OperationLiteral(Box::new(OpLdn::new(ArgumentType::new(
+                reg,
+                jib::cpu::DataType::U32,
+            )))),
+            AsmToken::LoadLoc(self.var_label.clone()),
+        ]
+    }
 }
 
 impl Expression for GlobalVariable {
@@ -194,25 +225,29 @@ impl Expression for GlobalVariable {
         _spare: Register,
         _state: &mut AsmGenState,
     ) -> Result<Vec<AsmToken>, ErrorToken> {
-        let res = self
-            .load_address(reg)?
-            .into_iter()
-            .chain([AsmToken::OperationLiteral(Box::new(OpLd::new(
-                ArgumentType::new(reg, jib::cpu::DataType::U32),
+        let mut res = self.load_label(reg);
+        if !self.is_function {
+            res.push(AsmToken::OperationLiteral(Box::new(OpLd::new(
+                ArgumentType::new(reg, self.get_base_primitive()?),
                 reg.into(),
-            )))])
-            .collect();
+            ))));
+        }
         Ok(res)
     }
 
-    fn load_address(&self, reg: Register) -> Result<Vec<AsmToken>, ErrorToken> {
-        Ok(vec![
-            AsmToken::OperationLiteral(Box::new(OpLdn::new(ArgumentType::new(
-                reg,
-                jib::cpu::DataType::U32,
-            )))),
-            AsmToken::LoadLoc(self.var_label.clone()),
-        ])
+    fn load_address(
+        &self,
+        reg: Register,
+        _state: &mut AsmGenState,
+    ) -> Result<Vec<AsmToken>, ErrorToken> {
+        if self.is_function {
+            return Err(ErrorToken::new(
+                self.get_token(),
+                ExpressionError::NotAddressable,
+            ));
+        }
+
+        Ok(self.load_label(reg))
     }
 
     fn get_token(&self) -> Token {
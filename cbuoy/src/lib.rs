@@ -1,19 +1,466 @@
-use jib_asm::{AssemblerErrorLoc, AsmTokenLoc};
+use jib_asm::{object::LinkedImage, AssemblerErrorLoc, AsmTokenLoc};
 
 mod components;
 mod parser;
 mod tokenizer;
 mod types;
 
+/// Defines the optimization level used by compile, which enables the peephole pass
+pub const DEFAULT_OPTIMIZATION: u32 = 1;
+
 pub fn compile(s: &str) -> Result<Vec<u8>, String> {
+    compile_with_optimization(s, DEFAULT_OPTIMIZATION)
+}
+
+/// Compiles with the provided optimization level, where 0 disables the peephole pass over the
+/// generated assembly
+pub fn compile_with_optimization(s: &str, level: u32) -> Result<Vec<u8>, String> {
+    compile_image(s, level).map(|img| img.bytes)
+}
+
+/// Compiles with the provided optimization level into a linked image, which records the label
+/// locations and the source line of the statement producing each instruction
+pub fn compile_image(s: &str, level: u32) -> Result<LinkedImage, String> {
     let state = match parser::parse(s) {
         Ok(s) => s,
         Err(e) => return Err(format!("Parse error - {e}")),
     };
 
-    Ok(state.generate_code().unwrap())
+    state
+        .generate_code(level > 0)
+        .map_err(|e| format!("Code generation error - {e}"))
 }
 
 pub fn assemble(_s: &str) -> Result<Vec<AsmTokenLoc>, AssemblerErrorLoc> {
     panic!("compiling to assembly not yet fully supported");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jib::cpu::{Processor, Register, StepResult};
+    use jib::memory::{MemoryImage, ReadWriteSegment};
+    use std::{cell::RefCell, collections::BTreeSet, rc::Rc};
+
+    fn run(code: &str) -> Processor {
+        let bytes = compile(code).unwrap();
+
+        let mut cpu = Processor::new();
+        cpu.set_strict_encoding(true);
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x4000))))
+            .unwrap();
+        cpu.load_image(&MemoryImage::from_flat(bytes)).unwrap();
+
+        for _ in 0..10000 {
+            if cpu.step().unwrap() == StepResult::Halted {
+                break;
+            }
+        }
+
+        cpu
+    }
+
+    #[test]
+    fn test_function_call() {
+        let code = "
+        def offset: u32 = 10u32;
+
+        fn add(a: u32, b: u32) u32 {
+            return a + b;
+        }
+
+        fn fact(n: u32) u32 {
+            if (n) {
+                return n * fact(n - 1u32);
+            }
+            return 1u32;
+        }
+
+        fn pick(flag: u16, a: u32, b: u32) u32 {
+            if (flag) return a; else return b;
+        }
+
+        fn main() u32 {
+            def x: u32 = add(3u32, 4u32);
+            x = add(x, offset);
+            return add(x, fact(4u32)) + pick(0u16, 100u32, 200u32);
+        }";
+
+        let cpu = run(code);
+        assert_eq!(cpu.get_register_state().get(Register::Return).unwrap(), 241);
+    }
+
+    #[test]
+    fn test_call_frames() {
+        let code = "
+        fn sum(n: u32) u32 {
+            def prev: u32 = 0u32;
+            if (n) {
+                prev = sum(n - 1u32);
+            }
+            return prev + n;
+        }
+
+        fn main() u32 {
+            return sum(150u32);
+        }";
+
+        // Each call only saves the return address, the caller frame, and the registers in use,
+        // such that deep recursion fits within the stack
+        let cpu = run(code);
+        assert_eq!(
+            cpu.get_register_state().get(Register::Return).unwrap(),
+            11325
+        );
+    }
+
+    #[test]
+    fn test_if_else_chain() {
+        let code = "
+        fn classify(n: u32) u32 {
+            def result: u32 = 0u32;
+            if (n == 0u32) {
+                result = 1u32;
+            } else if (n == 1u32) {
+                result = 20u32;
+            } else if (n == 2u32)
+                result = 300u32;
+            else {
+                def big: u32 = 4000u32;
+                result = big;
+            }
+            return result;
+        }
+
+        fn main() u32 {
+            return ((classify(0u32) + classify(1u32)) + classify(2u32)) + classify(7u32);
+        }";
+
+        let cpu = run(code);
+        assert_eq!(
+            cpu.get_register_state().get(Register::Return).unwrap(),
+            4321
+        );
+    }
+
+    #[test]
+    fn test_arrays() {
+        let code = "
+        def table: [4]u32;
+
+        fn sum(p: *u32, n: u32) u32 {
+            def total: u32 = 0u32;
+            for (def i: u32 = 0u32; i != n; i = i + 1u32) {
+                total = total + p[i];
+            }
+            return total;
+        }
+
+        fn main() u32 {
+            def buf: [8]u16;
+            for (def i: u16 = 0u16; i != 8u16; i = i + 1u16) {
+                buf[i] = i * 3u16;
+            }
+
+            for (def j: u32 = 0u32; j != 4u32; j = j + 1u32) {
+                table[j] = j + 10u32;
+            }
+
+            def p: *u32 = &table[1u32];
+            *p = 100u32;
+            p[2u32] = 7u32;
+
+            if (buf[5u16] != 15u16) return 1u32;
+            return sum(&table[0u32], 4u32);
+        }";
+
+        let cpu = run(code);
+        assert_eq!(cpu.get_register_state().get(Register::Return).unwrap(), 129);
+    }
+
+    #[test]
+    fn test_array_errors() {
+        for expr in ["x[0u32]", "buf[f]", "buf[0u32][1u32]"] {
+            let code = format!("fn main() {{ def x: u32; def f: f32; def buf: [4]u32; {expr}; }}");
+            assert!(compile(&code).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn test_structs() {
+        let code = "
+        struct node;
+        struct point { x: u16, y: u32, tag: u8 }
+        struct node { pos: point, vals: [3]u32, next: *node }
+
+        def origin: point;
+
+        fn shift(p: *point, d: u32) {
+            p.y = p.y + d;
+        }
+
+        fn main() u32 {
+            def a: node;
+            def b: node;
+            a.next = &b;
+            a.pos.x = 2u16;
+            a.pos.y = 40u32;
+            a.vals[2u32] = 5u32;
+            a.next.pos.y = 600u32;
+            b.vals[0u32] = 9u32;
+
+            origin.y = 1000u32;
+            shift(&origin, 3000u32);
+            shift(&a.pos, 1u32);
+
+            if (a.pos.x != 2u16) return 1u32;
+            return ((((b.pos.y + a.pos.y) + a.vals[2u32]) + a.next.vals[0u32]) + origin.y);
+        }";
+
+        let cpu = run(code);
+        assert_eq!(
+            cpu.get_register_state().get(Register::Return).unwrap(),
+            4655
+        );
+    }
+
+    #[test]
+    fn test_struct_errors() {
+        for expr in ["x.a", "p.z", "p.x.a"] {
+            let code =
+                format!("struct pt {{ x: u32 }} fn main() {{ def x: u32; def p: pt; {expr}; }}");
+            assert!(compile(&code).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn test_globals() {
+        let code = "
+        def count: u32 = 5u32;
+        def flag: u8 = 1u8;
+        def scale: u32 = 2u32 + 1u32;
+        def history: [3]u32;
+
+        fn bump(n: u32) u32 {
+            count = count + n;
+            history[n] = 7u32;
+            return count;
+        }
+
+        fn main() u32 {
+            bump(1u32);
+            bump(2u32);
+            if (flag) count = count * scale;
+            return count + history[2u32];
+        }";
+
+        let cpu = run(code);
+        assert_eq!(cpu.get_register_state().get(Register::Return).unwrap(), 31);
+
+        // Literal initial values are stored directly within the data section
+        let code = "def a: u32 = 287454020u32; def b: u16 = 21862u16; fn main() u32 { return a; }";
+        let bytes = compile(code).unwrap();
+        assert!(bytes.ends_with(&[0x11, 0x22, 0x33, 0x44, 0x55, 0x66]));
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        let folded = compile("fn main() u32 { return (2u32 + 3u32) * ~(0u32 - 5u32); }").unwrap();
+        let literal = compile("fn main() u32 { return 20u32; }").unwrap();
+        assert_eq!(folded, literal);
+
+        let code = "
+        def scale: i16 = -(3i16 << 2i16);
+
+        fn main() u32 {
+            def a: u8 = 250u8 + 10u8;
+            def b: i32 = 7i32 / -2i32;
+            def r: u32 = 0u32;
+            if (scale == -12i16) r = r + 1u32;
+            if (a == 4u8) r = r + 10u32;
+            if (b == -3i32) r = r + 100u32;
+            return r;
+        }";
+
+        let cpu = run(code);
+        assert_eq!(cpu.get_register_state().get(Register::Return).unwrap(), 111);
+        assert!(compile(code).unwrap().ends_with(&[0xff, 0xf4]));
+    }
+
+    #[test]
+    fn test_memory_operands() {
+        let code = "
+        struct pair { a: i16, b: u8 }
+        def table: [2]i16;
+
+        fn main() u32 {
+            def p: pair;
+            p.a = -7i16;
+            p.b = 200u8;
+            table[1u32] = -3i16;
+
+            def x: i16 = 10i16;
+            def y: u8 = 100u8;
+            def r: u32 = 0u32;
+            if ((x + table[1u32]) == 7i16) r = r + 1u32;
+            if ((x - p.a) == 17i16) r = r + 10u32;
+            if ((y + p.b) == 44u8) r = r + 100u32;
+            return r;
+        }";
+
+        let cpu = run(code);
+        assert_eq!(cpu.get_register_state().get(Register::Return).unwrap(), 111);
+    }
+
+    #[test]
+    fn test_nested_right_operands() {
+        let code = "
+        def g: u32;
+
+        fn main() u32 {
+            def a: u32 = 3u32;
+            def b: u32 = 6u32;
+            g = 2u32;
+
+            def r: u32 = 0u32;
+            if ((a + (a + b)) == 12u32) r = r + 1u32;
+            if ((b - (a - (b - a))) == 6u32) r = r + 10u32;
+            if ((a * (g + (b * 2u32))) == 42u32) r = r + 100u32;
+            return r;
+        }";
+
+        let cpu = run(code);
+        assert_eq!(cpu.get_register_state().get(Register::Return).unwrap(), 111);
+    }
+
+    #[test]
+    fn test_optimization_level() {
+        let code = "fn main() u32 { def x: u32 = 3u32; return x + 4u32; }";
+        let optimized = compile(code).unwrap();
+        let unoptimized = compile_with_optimization(code, 0).unwrap();
+        assert!(optimized.len() < unoptimized.len());
+    }
+
+    #[test]
+    fn test_multiply_accumulate() {
+        let code = "
+        def xs: [4]i32;
+        def ys: [4]i32;
+
+        fn main() i32 {
+            xs[0u32] = 1i32;
+            xs[1u32] = 2i32;
+            xs[2u32] = 3i32;
+            xs[3u32] = 4i32;
+            ys[0u32] = -1i32;
+            ys[1u32] = 5i32;
+            ys[2u32] = 2i32;
+            ys[3u32] = 3i32;
+
+            def acc: i32 = 5i32;
+            for (def i: u32 = 0u32; i != 4u32; i = i + 1u32) {
+                acc = acc + (xs[i] * ys[i]);
+            }
+            return acc;
+        }";
+
+        let bytes = compile(code).unwrap();
+        assert!(bytes.chunks(4).any(|w| w[0] == Processor::OP_MAC.to_byte()));
+
+        let cpu = run(code);
+        assert_eq!(cpu.get_register_state().get(Register::Return).unwrap(), 32);
+    }
+
+    #[test]
+    fn test_loops() {
+        let code = "
+        fn main() u32 {
+            def total: u32 = 0u32;
+            def n: u32 = 0u32;
+
+            while (n != 10u32) {
+                n = n + 1u32;
+                if (n % 2u32) continue;
+                total = total + n;
+            }
+
+            for (def i: u32 = 0u32; i != 100u32; i = i + 1u32) {
+                if (i == 4u32) break;
+                total = total + 100u32;
+            }
+
+            def j: u32 = 3u32;
+            for (; j; ) j = j - 1u32;
+
+            while (1u16) {
+                for (def k: u32 = 0u32; k != 3u32; k = k + 1u32) {
+                    total = total + 1000u32;
+                }
+                break;
+            }
+
+            return total + j;
+        }";
+
+        let cpu = run(code);
+        assert_eq!(
+            cpu.get_register_state().get(Register::Return).unwrap(),
+            3430
+        );
+    }
+
+    #[test]
+    fn test_loop_control_errors() {
+        assert!(compile("fn main() { break; }").is_err());
+        assert!(compile("fn main() { if (1u16) continue; }").is_err());
+    }
+
+    #[test]
+    fn test_function_call_errors() {
+        let base = "fn add(a: u32, b: u32) u32 { return a + b; }";
+        for call in ["add(1u32)", "add(1u32, 2u16)", "offset(1u32)"] {
+            let code = format!("def offset: u32; {base} fn main() {{ {call}; }}");
+            assert!(compile(&code).is_err(), "{call}");
+        }
+
+        assert!(compile(base).is_err());
+    }
+
+    #[test]
+    fn test_source_lines() {
+        let code = "\
+fn main() u32 {
+    def x: u32 = 3u32;
+    while (x != 0u32) {
+        x = x - 1u32;
+    }
+    return x;
+}";
+        let image = compile_image(code, DEFAULT_OPTIMIZATION).unwrap();
+        let info = image.debug_info(&["main.spc".into()]);
+
+        let lines = info.lines.values().map(|l| l.line).collect::<BTreeSet<_>>();
+        assert_eq!(lines, BTreeSet::from([1, 2, 3, 4, 6]));
+        assert_eq!(
+            info.line_address("main.spc", 1),
+            Some(image.labels["func_def_main"])
+        );
+
+        // The first instruction of the loop body is executed once for each iteration
+        let body = info.line_address("main.spc", 4).unwrap();
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x4000))))
+            .unwrap();
+        cpu.load_image(&image.image).unwrap();
+
+        let mut hits = 0;
+        while !cpu.halted() {
+            if cpu.get_current_pc().unwrap() == body {
+                hits += 1;
+            }
+            cpu.step().unwrap();
+        }
+        assert_eq!(hits, 3);
+    }
+}
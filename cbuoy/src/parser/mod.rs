@@ -4,20 +4,28 @@ use std::fmt::Display;
 use std::rc::Rc;
 use std::sync::{LazyLock, OnceLock};
 
-use jib_asm::{AsmTokenLoc, LocationInfo};
+use jib::cpu::{DataType, Processor, Register};
+use jib_asm::{
+    argument::ArgumentType,
+    instructions::{OpHalt, OpLdn},
+    object::{link_image, LinkedImage, ObjectFile, VectorTarget},
+    AsmToken, AsmTokenLoc, LocationInfo,
+};
 use regex::Regex;
 
 use crate::components::expression::{
-    AssignmentExpression, BinaryExpression, BinaryOperator, Expression, Literal, LiteralExpression,
-    UnaryExpression, UnaryOperator,
+    AssignmentExpression, BinaryExpression, BinaryOperator, CallExpression, Expression,
+    FieldExpression, IndexExpression, Literal, LiteralExpression, UnaryExpression, UnaryOperator,
 };
+use crate::components::optimize::peephole;
 use crate::components::statement::{
-    ExpressionStatement, GlobalDefinitionStatement, IfStatement, ReturnStatement,
-    VariableInitStatement,
+    ExpressionStatement, ForStatement, GlobalDefinitionStatement, IfStatement,
+    LoopControlStatement, ReturnStatement, SourceLineStatement, VariableInitStatement,
+    WhileStatement,
 };
 use crate::components::{
-    AsmFunction, AsmGenState, BaseStatement, ErrorToken, FunctionDefinition, FunctionPtr,
-    ParserScope, Statement,
+    argument_layout, AsmFunction, AsmGenState, BaseStatement, CodeComponent, ErrorToken,
+    FunctionDefinition, FunctionPtr, ParserScope, Statement, CALL_SAVE_SIZE,
 };
 use crate::tokenizer::{tokenize, Token, TokenIter, TokenIterError, TokenizeError};
 use crate::types::{StructDef, TypeError};
@@ -146,7 +154,7 @@ fn parse_fn_statement(
         ret_tokens.push(tokens.expect()?);
     }
 
-    let ret_type = if ret_tokens.is_empty() {
+    let ret_type = if ret_tokens.is_empty() || Token::tok_str(&ret_tokens) == "void" {
         None
     } else {
         Some(check_type_error(
@@ -155,6 +163,15 @@ fn parse_fn_statement(
         )?)
     };
 
+    let param_types = parameters
+        .iter()
+        .map(|(_, t)| t.clone())
+        .collect::<Vec<_>>();
+    let fn_type = Type::Function {
+        ret: ret_type.clone().map(Box::new),
+        args: param_types.clone(),
+    };
+
     if tokens.peek_expect("=") {
         tokens.expect_value("=")?;
 
@@ -172,8 +189,48 @@ fn parse_fn_statement(
 
         tokens.expect_value(";")?;
 
-        Ok(Box::new(FunctionPtr::new(parameters, ret_type, addr)))
+        if let Err(e) = state
+            .root_scope
+            .borrow_mut()
+            .add_variable(name_tok.clone(), name, fn_type)
+        {
+            return Err(ParseError::new_tok(name_tok, format!("{e}")));
+        }
+
+        Ok(Box::new(FunctionPtr::new(name, parameters, ret_type, addr)))
     } else {
+        // Add the function before the body to allow recursive calls
+        if let Err(e) = state.root_scope.borrow_mut().add_function(
+            name_tok.clone(),
+            name,
+            &FunctionDefinition::assembler_label(name),
+            fn_type,
+        ) {
+            return Err(ParseError::new_tok(name_tok, format!("{e}")));
+        }
+
+        // Add the parameters within the argument frame, which is placed before the values saved
+        // by the frame call, followed by the local variables at the start of the frame
+        let (_, frame_size) = check_type_error(
+            argument_layout(&param_types),
+            std::slice::from_ref(&name_tok),
+        )?;
+        scope
+            .borrow_mut()
+            .set_offset(-((frame_size + CALL_SAVE_SIZE) as i32));
+
+        for (param_name, param_type) in parameters.iter() {
+            if let Err(e) =
+                scope
+                    .borrow_mut()
+                    .add_variable(name_tok.clone(), param_name, param_type.clone())
+            {
+                return Err(ParseError::new_tok(name_tok, format!("{e}")));
+            }
+        }
+
+        scope.borrow_mut().set_offset(0);
+
         tokens.expect_value("{")?;
 
         let mut statements = Vec::new();
@@ -184,7 +241,7 @@ fn parse_fn_statement(
         tokens.expect_value("}")?;
 
         Ok(Box::new(FunctionDefinition::new(
-            name, parameters, ret_type, statements,
+            name_tok, parameters, ret_type, statements,
         )))
     }
 }
@@ -368,51 +425,110 @@ fn parse_base_expression(
     Ok(init_expr)
 }
 
+/// Parses a statement, marking the generated code with the source line of its first token
 fn parse_statement(
     tokens: &mut TokenIter,
     state: &mut ParserState,
     scope: &Rc<RefCell<ParserScope>>,
+) -> Result<Box<dyn Statement>, ParseError> {
+    let line = tokens.peek().map(|t| t.get_line() + 1);
+    let statement = parse_statement_kind(tokens, state, scope)?;
+    Ok(match line {
+        Some(line) => Box::new(SourceLineStatement { line, statement }),
+        None => statement,
+    })
+}
+
+fn parse_statement_kind(
+    tokens: &mut TokenIter,
+    state: &mut ParserState,
+    scope: &Rc<RefCell<ParserScope>>,
 ) -> Result<Box<dyn Statement>, ParseError> {
     if let Some(pt) = tokens.peek() {
         let pt_val = pt.get_value();
+        let line = pt.get_line() + 1;
 
         if pt_val == "while" {
-            tokens.expect()?;
-            panic!("while statement not yet supported");
-        } else if pt_val == "if" {
             tokens.expect()?;
             tokens.expect_value("(")?;
 
-            let if_expr = parse_base_expression(tokens, state, scope)?;
+            let conditional = parse_base_expression(tokens, state, scope)?;
 
             tokens.expect_value(")")?;
 
-            let mut statements = Vec::<Box<dyn Statement>>::new();
-
-            if tokens.peek_expect("{") {
-                tokens.expect_value("{")?;
+            let statements = parse_body(tokens, state, scope)?;
 
-                let new_scope = Rc::new(RefCell::new(ParserScope::new(scope.clone())));
+            return Ok(Box::new(WhileStatement {
+                line,
+                conditional,
+                statements,
+            }));
+        } else if pt_val == "for" {
+            tokens.expect()?;
+            tokens.expect_value("(")?;
 
-                while !tokens.peek_expect("}") {
-                    statements.push(parse_statement(tokens, state, &new_scope)?);
-                }
+            // Variables defined in the loop initializer are only visible within the loop
+            let for_scope = Rc::new(RefCell::new(ParserScope::new(scope.clone())));
 
-                tokens.expect_value("}")?;
+            let init = if tokens.peek_expect(";") {
+                tokens.expect()?;
+                None
             } else {
-                statements.push(parse_statement(tokens, state, scope)?);
-            }
+                Some(parse_statement(tokens, state, &for_scope)?)
+            };
 
-            let else_statement = if tokens.peek_expect("else") {
-                Some(parse_statement(tokens, state, scope)?)
+            let conditional = if tokens.peek_expect(";") {
+                None
             } else {
+                Some(parse_base_expression(tokens, state, &for_scope)?)
+            };
+            tokens.expect_value(";")?;
+
+            let step = if tokens.peek_expect(")") {
                 None
+            } else {
+                Some(parse_base_expression(tokens, state, &for_scope)?)
+            };
+            tokens.expect_value(")")?;
+
+            let statements = parse_body(tokens, state, &for_scope)?;
+
+            return Ok(Box::new(ForStatement {
+                line,
+                init,
+                conditional,
+                step,
+                statements,
+            }));
+        } else if pt_val == "break" || pt_val == "continue" {
+            let tok = tokens.expect()?;
+            tokens.expect_value(";")?;
+            return Ok(Box::new(LoopControlStatement {
+                is_break: tok.get_value() == "break",
+                tok,
+            }));
+        } else if pt_val == "if" {
+            tokens.expect()?;
+            tokens.expect_value("(")?;
+
+            let if_expr = parse_base_expression(tokens, state, scope)?;
+
+            tokens.expect_value(")")?;
+
+            let statements = parse_body(tokens, state, scope)?;
+
+            // An else if is parsed as an else clause containing a single nested if statement
+            let else_statements = if tokens.peek_expect("else") {
+                tokens.expect()?;
+                parse_body(tokens, state, scope)?
+            } else {
+                Vec::new()
             };
 
             return Ok(Box::new(IfStatement {
                 conditional: if_expr,
                 statements,
-                else_clause: else_statement,
+                else_statements,
             }));
         } else if pt_val == "def" {
             tokens.expect()?;
@@ -454,15 +570,14 @@ fn parse_statement(
                 ));
             }
         } else if pt_val == "return" {
-            tokens.expect()?;
-            if tokens.peek_expect(";") {
-                tokens.expect()?;
-                panic!("Immediate Return")
+            let tok = tokens.expect()?;
+            let expr = if tokens.peek_expect(";") {
+                None
             } else {
-                let expr = parse_base_expression(tokens, state, scope)?;
-                tokens.expect_value(";")?;
-                return Ok(Box::new(ReturnStatement { expr }));
-            }
+                Some(parse_base_expression(tokens, state, scope)?)
+            };
+            tokens.expect_value(";")?;
+            return Ok(Box::new(ReturnStatement { tok, expr }));
         } else if pt_val == "{" {
             tokens.expect()?;
         }
@@ -476,6 +591,32 @@ fn parse_statement(
     Ok(expr)
 }
 
+/// Parses the body of a control statement, either as a block of statements within braces,
+/// which are given a new scope, or as a single statement
+fn parse_body(
+    tokens: &mut TokenIter,
+    state: &mut ParserState,
+    scope: &Rc<RefCell<ParserScope>>,
+) -> Result<Vec<Box<dyn Statement>>, ParseError> {
+    let mut statements = Vec::<Box<dyn Statement>>::new();
+
+    if tokens.peek_expect("{") {
+        tokens.expect_value("{")?;
+
+        let new_scope = Rc::new(RefCell::new(ParserScope::new(scope.clone())));
+
+        while !tokens.peek_expect("}") {
+            statements.push(parse_statement(tokens, state, &new_scope)?);
+        }
+
+        tokens.expect_value("}")?;
+    } else {
+        statements.push(parse_statement(tokens, state, scope)?);
+    }
+
+    Ok(statements)
+}
+
 static IDENTIFIER_REGEX: OnceLock<Regex> = OnceLock::new();
 
 fn is_identifier(s: &str) -> bool {
@@ -583,16 +724,41 @@ fn parse_expression(
             &[first],
         )?))
     } else if is_identifier(first.get_value()) {
-        return match scope.borrow().get_variable_expr(first.get_value()) {
+        let var = scope.borrow().get_variable_expr(first.get_value());
+        match var {
             Ok(var) => {
-                if check_type_error(var.get_type(), &[first])?.is_func() {
-                    panic!("check for function call?");
+                let mut expr = if tokens.peek_expect("(") {
+                    let args = parse_call_arguments(tokens, state, scope)?;
+                    Box::new(CallExpression::new(first.clone(), var, args)?)
                 } else {
-                    Ok(var)
+                    var
+                };
+
+                loop {
+                    if tokens.peek_expect("[") {
+                        let tok = tokens.expect()?;
+                        let index = parse_base_expression(tokens, state, scope)?;
+                        tokens.expect_value("]")?;
+                        expr = Box::new(check_type_error(
+                            IndexExpression::new(tok, expr, index),
+                            std::slice::from_ref(&first),
+                        )?);
+                    } else if tokens.peek_expect(".") {
+                        let tok = tokens.expect()?;
+                        let field = tokens.expect()?;
+                        expr = Box::new(check_type_error(
+                            FieldExpression::new(tok, expr, field.get_value(), &state.types),
+                            &[first.clone(), field],
+                        )?);
+                    } else {
+                        break;
+                    }
                 }
+
+                Ok(expr)
             }
             Err(e) => Err(ParseError::new_tok(first, format!("{e}"))),
-        };
+        }
     } else if let Ok(lit) = parse_literal(&first) {
         Ok(Box::new(LiteralExpression::new(first, lit)))
     } else {
@@ -603,6 +769,38 @@ fn parse_expression(
     }
 }
 
+fn parse_call_arguments(
+    tokens: &mut TokenIter,
+    state: &mut ParserState,
+    scope: &Rc<RefCell<ParserScope>>,
+) -> Result<Vec<Box<dyn Expression>>, ParseError> {
+    tokens.expect_value("(")?;
+
+    let mut args = Vec::new();
+    if tokens.peek_expect(")") {
+        tokens.expect()?;
+        return Ok(args);
+    }
+
+    loop {
+        args.push(parse_base_expression(tokens, state, scope)?);
+
+        let sep = tokens.expect()?;
+        match sep.get_value() {
+            "," => (),
+            ")" => break,
+            _ => {
+                return Err(ParseError::new_tok(
+                    sep,
+                    "expected comma or closing parenthesis after fn argument".into(),
+                ));
+            }
+        }
+    }
+
+    Ok(args)
+}
+
 fn parse_def_statement(
     tokens: &mut TokenIter,
     state: &mut ParserState,
@@ -669,7 +867,14 @@ fn parse_def_statement(
 
     if !expr_tokens.is_empty() {
         let mut expr_iter = TokenIter::new(expr_tokens);
-        def_statement.set_init(parse_expression(&mut expr_iter, state, scope)?);
+        def_statement.set_init(parse_base_expression(&mut expr_iter, state, scope)?);
+
+        if let Some(t) = expr_iter.next() {
+            return Err(ParseError::new_tok(
+                t,
+                format!("unexpected token in initializer for '{name}'"),
+            ));
+        }
     }
 
     if let Err(e) = scope
@@ -690,22 +895,75 @@ pub struct ParserState {
 }
 
 impl ParserState {
-    pub fn generate_code(&self) -> Result<Vec<u8>, ErrorToken> {
-        let mut tokens = Vec::new();
+    const START_LABEL: &'static str = "cb_start";
+    const DATA_LABEL: &'static str = "cb_data";
+    const STACK_LABEL: &'static str = "cb_stack_base";
+
+    /// Generates the program image, which initializes the stack and global variables before
+    /// calling the main function and halting once it returns. Global variables are stored in a
+    /// data section following the program code, where literal initial values are stored
+    /// directly rather than being set by the startup code. The stack starts after the end of the
+    /// data section. If optimizing, the generated assembly is simplified with a peephole pass
+    /// before assembly. The source line of each statement is recorded for the instructions
+    /// generated for it
+    pub fn generate_code(&self, optimize: bool) -> Result<LinkedImage, ErrorToken> {
         let mut state = AsmGenState::new();
 
+        let main_tok = Token::new(0, 0, "main".into());
+        let main = ErrorToken::test(
+            &main_tok,
+            self.root_scope.borrow().get_variable_expr("main"),
+        )?;
+        let call_main =
+            ExpressionStatement::new(Box::new(CallExpression::new(main_tok, main, Vec::new())?));
+
+        let mut tokens = vec![
+            AsmToken::Vector(
+                VectorTarget::Address(Processor::HARD_RESET_VECTOR),
+                Self::START_LABEL.into(),
+            ),
+            AsmToken::Vector(
+                VectorTarget::Address(Processor::SOFT_RESET_VECTOR),
+                Self::START_LABEL.into(),
+            ),
+            AsmToken::ChangeAddress(Processor::TOP_VEC_SEG_ADDR),
+            AsmToken::CreateLabel(Self::START_LABEL.into()),
+            AsmToken::OperationLiteral(Box::new(OpLdn::new(ArgumentType::new(
+                Register::StackPointer,
+                DataType::U32,
+            )))),
+            AsmToken::LoadLoc(Self::STACK_LABEL.into()),
+        ];
+
         for s in self.statements.iter() {
-            for t in s.generate_code(&mut state)? {
-                tokens.push(t);
-            }
+            tokens.extend(s.generate_init_code(&mut state)?);
+        }
+
+        tokens.extend(call_main.generate_code(&mut state)?);
+        tokens.push(AsmToken::OperationLiteral(Box::new(OpHalt)));
+
+        for s in self.statements.iter() {
+            tokens.extend(s.generate_code(&mut state)?);
         }
 
+        tokens.push(AsmToken::AlignInstruction);
+        tokens.push(AsmToken::CreateLabel(Self::DATA_LABEL.into()));
+
+        for s in self.statements.iter() {
+            tokens.extend(s.generate_data(&mut state)?);
+        }
+
+        tokens.push(AsmToken::AlignInstruction);
+        tokens.push(AsmToken::CreateLabel(Self::STACK_LABEL.into()));
+
+        let tokens = if optimize { peephole(tokens) } else { tokens };
         let tokens_loc = tokens.into_iter().map(|v| AsmTokenLoc {
             tok: v,
             loc: LocationInfo::default(),
         });
 
-        Ok(jib_asm::assemble_tokens(tokens_loc)?)
+        let obj = ObjectFile::from_tokens(&tokens_loc.collect::<Vec<_>>())?;
+        Ok(link_image(&[obj], &HashMap::new())?)
     }
 }
 
@@ -16,6 +16,11 @@ pub enum TypeError {
     FieldNotFound(String, StructDef),
     TypeMismatch(Type, Type),
     CannotDereference(Type),
+    NotCallable(Type),
+    ArgumentCount(usize, usize),
+    NotIndexable(Type),
+    InvalidIndex(Type),
+    NotStruct(Type),
     ParenthesisError,
     UnexpectedCharacters(String),
 }
@@ -41,6 +46,13 @@ impl std::fmt::Display for TypeError {
             }
             Self::TypeMismatch(a, b) => write!(f, "types {a} and {b} do not match"),
             Self::CannotDereference(t) => write!(f, "cannot dereference type '{t}'"),
+            Self::NotCallable(t) => write!(f, "cannot call non-function type '{t}'"),
+            Self::ArgumentCount(expected, provided) => {
+                write!(f, "expected {expected} arguments, but {provided} provided")
+            }
+            Self::NotIndexable(t) => write!(f, "cannot index non-array type '{t}'"),
+            Self::InvalidIndex(t) => write!(f, "cannot index with non-integer type '{t}'"),
+            Self::NotStruct(t) => write!(f, "cannot access field of non-struct type '{t}'"),
             Self::ParenthesisError => write!(f, "parenthesis error"),
             Self::UnexpectedCharacters(s) => write!(f, "unexpected characters \"{s}\""),
         }
@@ -140,6 +152,7 @@ impl Type {
         match self {
             Self::Pointer { .. } => Ok(DataType::U32),
             Self::Array { .. } => Ok(DataType::U32),
+            Self::Function { .. } => Ok(DataType::U32),
             Self::Primitive { base } => Ok(*base),
             Self::Alias { base, .. } => base.base_primitive(),
             Self::Constant { base } => base.base_primitive(),
@@ -104,6 +104,89 @@ impl TryFrom<u8> for ArgumentRegister {
     }
 }
 
+/// Provides a source argument of an arithmetic, bitwise, or test instruction, which is either
+/// the value of a register or, when written as `[reg]`, the value in memory at the address held
+/// by the register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgumentSource {
+    reg: ArgumentRegister,
+    indirect: bool,
+}
+
+impl ArgumentSource {
+    const INDIRECT_FLAG: u8 = 0x20;
+
+    /// Creates a register-indirect argument, reading memory at the address held by the register
+    pub fn indirect(reg: Register) -> Self {
+        Self {
+            reg: reg.into(),
+            indirect: true,
+        }
+    }
+
+    pub fn is_indirect(&self) -> bool {
+        self.indirect
+    }
+
+    pub fn to_byte(self) -> u8 {
+        if self.indirect {
+            self.reg.to_byte() | Self::INDIRECT_FLAG
+        } else {
+            self.reg.to_byte()
+        }
+    }
+}
+
+impl fmt::Display for ArgumentSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.indirect {
+            write!(f, "[{}]", self.reg)
+        } else {
+            write!(f, "{}", self.reg)
+        }
+    }
+}
+
+impl From<Register> for ArgumentSource {
+    fn from(value: Register) -> Self {
+        ArgumentRegister::from(value).into()
+    }
+}
+
+impl From<ArgumentRegister> for ArgumentSource {
+    fn from(value: ArgumentRegister) -> Self {
+        Self {
+            reg: value,
+            indirect: false,
+        }
+    }
+}
+
+impl TryFrom<&str> for ArgumentSource {
+    type Error = ArgumentError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Some(inner) => Ok(Self {
+                reg: ArgumentRegister::try_from(inner)?,
+                indirect: true,
+            }),
+            None => Ok(ArgumentRegister::try_from(value)?.into()),
+        }
+    }
+}
+
+impl TryFrom<u8> for ArgumentSource {
+    type Error = ArgumentError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(Self {
+            reg: ArgumentRegister::try_from(value)?,
+            indirect: value & Self::INDIRECT_FLAG != 0,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ArgumentType {
     reg: ArgumentRegister,
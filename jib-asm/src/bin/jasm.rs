@@ -0,0 +1,268 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use clap::{Parser, ValueEnum};
+use jib::{debug_info::DebugInfo, memory::RelocationTable};
+use jib_asm::{
+    config::ProjectConfig,
+    image_format,
+    preprocess::{self, IncludePaths},
+    project::{assemble_project, preprocess_source_file, ObjectCache, ProjectSource},
+};
+
+/// Provides the supported memory image output formats
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputType {
+    /// Raw binary image, starting at address zero
+    Binary,
+    /// Intel HEX records with address information
+    IntelHex,
+    /// Motorola S-records with address information
+    Srec,
+    /// Memory image made up of segments with base addresses, loaded with Processor::load_image
+    Image,
+}
+
+impl OutputType {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Binary => "bin",
+            Self::IntelHex => "hex",
+            Self::Srec => "srec",
+            Self::Image => "jimg",
+        }
+    }
+}
+
+/// Assembles Jib assembly source into a memory image, linked with the layout and entry label of
+/// any scpu.toml project file within the working directory or its parents
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// The input assembly files, assembled in parallel and linked together in the order provided
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// The output file, defaulting to the first input file with the extension of the output
+    /// format
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// The memory image output format
+    #[arg(short, long, value_enum, default_value_t = OutputType::Binary)]
+    format: OutputType,
+
+    /// Outputs the preprocessed source, annotated with the originating source lines,
+    /// instead of assembling
+    #[arg(short = 'E', long)]
+    preprocess_only: bool,
+
+    /// Outputs an object file for each input, to be combined with other object files by the
+    /// linker, instead of a memory image
+    #[arg(short = 'c', long)]
+    object: bool,
+
+    /// The number of files to assemble in parallel, defaulting to the available parallelism
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Caches the object file of each input in the provided directory, keyed by the content of
+    /// the source and any included files, such that only changed inputs are assembled again
+    #[arg(long)]
+    cache: Option<PathBuf>,
+
+    /// Writes the labels and source lines of the program alongside the output, with the jdbg
+    /// extension, for the debugger and front-ends to show in place of raw addresses
+    #[arg(short = 'g', long)]
+    debug_info: bool,
+
+    /// Writes the relocation table of the program alongside the output, with the jrel
+    /// extension, such that the debugger and front-ends may load the program at another base
+    /// address with --base
+    #[arg(short = 'r', long)]
+    relocations: bool,
+
+    /// Adds a directory searched for the files of include directives, after the directory of
+    /// the including file, in the order provided
+    #[arg(short = 'I', long = "include-dir")]
+    include_dirs: Vec<PathBuf>,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let config = match ProjectConfig::current() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut sources = Vec::new();
+    for p in args.inputs.iter() {
+        match std::fs::read_to_string(p) {
+            Ok(text) => sources.push(ProjectSource {
+                name: p.display().to_string(),
+                text,
+            }),
+            Err(e) => {
+                eprintln!("Unable to read {} - {e}", p.display());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if args.output.is_some() && sources.len() > 1 && (args.preprocess_only || args.object) {
+        eprintln!("An output file may only be provided for a single input");
+        std::process::exit(1);
+    }
+
+    let includes = IncludePaths::new(args.include_dirs.clone());
+
+    if args.preprocess_only {
+        for src in sources.iter() {
+            let lines = match preprocess_source_file(src, &includes) {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("Preprocessor Error: {e}");
+                    std::process::exit(2);
+                }
+            };
+
+            let txt = preprocess::format_preprocessed(&lines);
+            let res = match &args.output {
+                Some(p) => std::fs::write(p, txt),
+                None => {
+                    print!("{txt}");
+                    Ok(())
+                }
+            };
+
+            if let Err(e) = res {
+                eprintln!("Unable to write output - {e}");
+                std::process::exit(1);
+            }
+        }
+
+        return;
+    }
+
+    let mut cache = match &args.cache {
+        Some(dir) => match ObjectCache::load(dir) {
+            Ok(c) => Some(c),
+            Err(e) => {
+                eprintln!("Unable to read cache {} - {e}", dir.display());
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let jobs = args.jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    });
+
+    let objects = match assemble_project(&sources, jobs, cache.as_mut(), &includes) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Assembler Error: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    if let (Some(dir), Some(cache)) = (&args.cache, cache.as_mut()) {
+        cache.retain_used();
+        if let Err(e) = cache.save(dir) {
+            eprintln!("Unable to write cache {} - {e}", dir.display());
+        }
+    }
+
+    if args.object {
+        for (p, obj) in args.inputs.iter().zip(objects.iter()) {
+            let output = args
+                .output
+                .clone()
+                .unwrap_or_else(|| p.with_extension("jo"));
+
+            if let Err(e) = std::fs::write(&output, obj.to_text()) {
+                eprintln!("Unable to write {} - {e}", output.display());
+                std::process::exit(1);
+            }
+        }
+
+        return;
+    }
+
+    let linked = match config.link_image(&objects, &HashMap::new()) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Linker Error: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    let input = &args.inputs[0];
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| input.with_extension(args.format.extension()));
+
+    let bytes = &linked.bytes;
+
+    let data = match args.format {
+        OutputType::Binary => bytes.clone(),
+        OutputType::IntelHex => image_format::to_intel_hex(0, bytes).into_bytes(),
+        OutputType::Srec => {
+            let name = input
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            image_format::to_srec(&name, 0, bytes).into_bytes()
+        }
+        OutputType::Image => linked.image.to_bytes(),
+    };
+
+    if let Err(e) = std::fs::write(&output, data) {
+        eprintln!("Unable to write {} - {e}", output.display());
+        std::process::exit(1);
+    }
+
+    println!("Assembled {} bytes into {}", bytes.len(), output.display());
+
+    if args.debug_info {
+        let names = sources.iter().map(|s| s.name.clone()).collect::<Vec<_>>();
+        let debug_output = DebugInfo::sidecar_path(&output);
+        if let Err(e) = std::fs::write(&debug_output, linked.debug_info(&names).to_text()) {
+            eprintln!("Unable to write {} - {e}", debug_output.display());
+            std::process::exit(1);
+        }
+    }
+
+    if args.relocations {
+        let reloc_output = RelocationTable::sidecar_path(&output);
+        let res = match linked.relocation_table() {
+            Some(table) => {
+                std::fs::write(&reloc_output, table.to_text()).map_err(|e| e.to_string())
+            }
+            None => Err("No program sections to relocate".to_string()),
+        };
+
+        if let Err(e) = res {
+            eprintln!("Unable to write {} - {e}", reloc_output.display());
+            std::process::exit(1);
+        }
+    }
+
+    // Each bank is written as a separate segmented image, addressed within the bank window
+    for (bank, image) in linked.banks.iter() {
+        let bank_output = output.with_extension(format!("bank{bank}.jimg"));
+        if let Err(e) = std::fs::write(&bank_output, image.to_bytes()) {
+            eprintln!("Unable to write {} - {e}", bank_output.display());
+            std::process::exit(1);
+        }
+
+        println!("Assembled bank {bank} into {}", bank_output.display());
+    }
+}
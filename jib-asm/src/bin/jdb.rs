@@ -0,0 +1,831 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use clap::Parser;
+use jib::{
+    cpu::{Processor, ProcessorError, Register, StepResult, StopReason},
+    debug_info::DebugInfo,
+    device::{InputPlaybackDevice, PlaybackScript},
+    memory::{FaultSegment, MemoryImage, ReadWriteSegment},
+};
+use jib_asm::{
+    assemble_object,
+    config::{DeviceConfig, ProjectConfig},
+    disassemble::{disassemble_range, DisassembledWord},
+    fusion::FusionReport,
+    machine::{MachineBuilder, StandardDevices},
+    object::{link_image, parse_address},
+    preprocess,
+    profile::ProfileReport,
+    trace::TextTracer,
+    unwind::{unwind, Backtrace, Symbolizer},
+};
+
+/// Loads a program image and provides an interactive debugger for stepping through it. The
+/// layout, entry label, and playback script of any scpu.toml project file within the working
+/// directory or its parents are applied
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// The program to debug, either an assembly file, a raw memory image with a .bin extension,
+    /// or a segmented memory image with a .jimg extension. Labels and source lines for memory
+    /// images are read from any debug information written alongside by jasm --debug-info
+    input: PathBuf,
+
+    /// The maximum number of instructions to execute for each continue command
+    #[arg(short, long, default_value_t = 100_000_000)]
+    max_instructions: usize,
+
+    /// Plays back timestamped serial input and interrupts from a script, restarting the script
+    /// on each reset
+    #[arg(short, long)]
+    playback: Option<PathBuf>,
+
+    /// The probability, from 0 to 1, that each byte read from RAM is corrupted by a single
+    /// flipped bit
+    #[arg(long, default_value_t = 0.0)]
+    fault_rate: f64,
+
+    /// Corrupts the first read of a RAM address or label after each reset, as LOC or LOC:MASK
+    /// where the mask selects the bits to flip, defaulting to the lowest bit
+    #[arg(long)]
+    fault_at: Vec<String>,
+
+    /// The seed of the random fault sequence, defaulting to the seed of any playback script,
+    /// or one otherwise
+    #[arg(long)]
+    fault_seed: Option<u64>,
+
+    /// The hardware interrupt raised when a corrupted value is read, as a parity error
+    #[arg(long)]
+    parity_irq: Option<u32>,
+
+    /// Executes the debugger commands within the file before reading commands from the
+    /// terminal, and may be repeated. Lines starting with # are ignored
+    #[arg(short = 'x', long = "command", value_name = "FILE")]
+    commands: Vec<PathBuf>,
+
+    /// Exits once the command files have been executed instead of reading commands from the
+    /// terminal, with a nonzero status if any command fails
+    #[arg(long)]
+    batch: bool,
+
+    /// Writes a line to the file for each executed instruction, with the disassembly and the
+    /// registers modified by the instruction
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+
+    /// The number of instructions that may be undone with the back command, where zero
+    /// disables recording
+    #[arg(long, default_value_t = 10_000)]
+    history: usize,
+
+    /// Counts the executions of each instruction and the memory accesses of the program from
+    /// startup, as with the profile on command
+    #[arg(long)]
+    profile: bool,
+
+    /// Loads the program at the provided base address, moving the program with its relocation
+    /// table, where memory images require a table written alongside by jasm --relocations
+    #[arg(long, value_parser = parse_address)]
+    base: Option<u32>,
+}
+
+/// Provides the fault injection options applied to the RAM segment
+#[derive(Default)]
+struct FaultOptions {
+    rate: f64,
+    addresses: Vec<(u32, u8)>,
+    seed: u64,
+    parity_irq: Option<u32>,
+}
+
+impl FaultOptions {
+    fn is_enabled(&self) -> bool {
+        self.rate > 0.0 || !self.addresses.is_empty()
+    }
+}
+
+const HELP: &str = "\
+commands:
+    s, step [n]            execute n instructions, defaulting to 1
+    back [n]               undo n instructions, defaulting to 1
+    c, continue            run until a breakpoint, halt, or error
+    run                    reset the processor and continue
+    b, break <loc>         add a breakpoint at an address, label, or FILE:LINE
+    d, delete <loc>        remove the breakpoint at an address or label
+    i, info                list breakpoints
+    r, regs                print the register values
+    bt, backtrace          print the guest call stack
+    trap                   print the last trap recorded for an unhandled fault
+    profile on|off         start or stop counting executions and memory accesses
+    profile [n]            print the n most frequent entries of each list, defaulting to 20
+    fuse [n]               print the n most executed opcodes and adjacent instruction pairs
+    x <loc> [n]            examine n memory words, defaulting to 8
+    set <loc> <val>        write a word to memory
+    l, disas [loc] [n]     disassemble n words, defaulting to around the program counter
+    dump <loc> <n> <file>  write n bytes of memory to a binary file
+    symbols <file>         load labels from an assembly source or jdbg file
+    source <file>          execute the commands within a command file
+    reset                  hard-reset the processor and reload the program
+    h, help                print this message
+    q, quit                exit the debugger";
+
+/// Provides the processor state and the standard devices attached to it
+struct Debugger {
+    cpu: Processor,
+    image: MemoryImage,
+    labels: HashMap<String, u32>,
+    symbols: Symbolizer,
+    debug_info: DebugInfo,
+    config: ProjectConfig,
+    devices: StandardDevices,
+    trace: Option<Rc<RefCell<TextTracer<BufWriter<File>>>>>,
+    playback: Option<PlaybackScript>,
+    faults: FaultOptions,
+    max_instructions: usize,
+    history: usize,
+    profile: bool,
+    source_depth: usize,
+}
+
+impl Debugger {
+    const MAX_BACKTRACE: usize = 64;
+    const MAX_SOURCE_DEPTH: usize = 8;
+    const PROFILE_LINES: usize = 20;
+
+    fn new(image: MemoryImage, debug_info: DebugInfo, max_instructions: usize) -> Self {
+        let labels = debug_info.labels.clone().into_iter().collect();
+        Self {
+            cpu: Processor::new(),
+            image,
+            symbols: Symbolizer::new(&labels),
+            labels,
+            debug_info,
+            config: ProjectConfig::default(),
+            devices: StandardDevices::new(DeviceConfig::default().host_time_device()),
+            trace: None,
+            playback: None,
+            faults: FaultOptions::default(),
+            max_instructions,
+            history: 0,
+            profile: false,
+            source_depth: 0,
+        }
+    }
+
+    /// Rebuilds the processor and memory map, reloading the program while retaining breakpoints
+    fn reset(&mut self) -> Result<(), ProcessorError> {
+        let breakpoints = self.cpu.breakpoints().collect::<Vec<_>>();
+
+        self.cpu = Processor::new();
+        self.cpu.set_history_limit(self.history);
+        for brk in breakpoints {
+            self.cpu.add_breakpoint(brk);
+        }
+
+        self.devices.reset();
+
+        let mut builder = MachineBuilder::standard(&self.devices);
+
+        if self.faults.is_enabled() {
+            let ram = Rc::new(RefCell::new(ReadWriteSegment::new(MachineBuilder::RAM_LEN)));
+            let mut seg = FaultSegment::new(ram, self.faults.seed);
+            seg.set_fault_probability(self.faults.rate);
+            seg.set_parity_irq(self.faults.parity_irq);
+            for (addr, mask) in self.faults.addresses.iter() {
+                seg.add_fault(addr - MachineBuilder::RAM_START, *mask);
+            }
+
+            builder = builder.ram(Rc::new(RefCell::new(seg)));
+        }
+
+        builder.build(&mut self.cpu)?;
+
+        if let Some(trace) = &self.trace {
+            self.cpu.set_tracer(trace.clone());
+        }
+
+        self.cpu.load_image(&self.image)?;
+
+        // Playback starts after the image is loaded, such that the reset doesn't clear any input
+        // provided before the first instruction
+        if let Some(script) = &self.playback {
+            self.cpu
+                .device_add(Rc::new(RefCell::new(InputPlaybackDevice::new(
+                    script.clone(),
+                    self.devices.serial.clone(),
+                ))))?;
+        }
+
+        // Profiling starts once the reset vector has been read
+        if self.profile {
+            self.cpu.start_profile();
+        }
+        Ok(())
+    }
+
+    /// Parses an address or value, given as a label name, a source line as FILE:LINE, or a
+    /// decimal or hexadecimal number
+    fn parse_loc(&self, s: &str) -> Result<u32, String> {
+        if let Some(addr) = self.labels.get(s) {
+            Ok(*addr)
+        } else if let Some((file, line)) = s.rsplit_once(':') {
+            let line = line
+                .parse::<usize>()
+                .map_err(|_| format!("invalid line number '{line}'"))?;
+            self.debug_info
+                .line_address(file, line)
+                .ok_or_else(|| format!("no code for source line '{s}'"))
+        } else if let Some(hex) = s.strip_prefix("0x") {
+            u32::from_str_radix(hex, 16).map_err(|_| format!("invalid address '{s}'"))
+        } else {
+            s.parse::<u32>()
+                .map_err(|_| format!("unknown label or address '{s}'"))
+        }
+    }
+
+    fn label_for(&self, addr: u32) -> Option<&str> {
+        self.labels
+            .iter()
+            .filter(|(_, v)| **v == addr)
+            .map(|(k, _)| k.as_str())
+            .min()
+    }
+
+    fn describe_location(&self, addr: u32) -> String {
+        match self.label_for(addr) {
+            Some(l) => format!("0x{addr:08x} <{l}>"),
+            None => format!("0x{addr:08x}"),
+        }
+    }
+
+    /// Prints any pending serial output and log messages produced by the program
+    fn flush_devices(&self) {
+        let mut serial = String::new();
+        while let Some(w) = self.devices.serial.borrow_mut().pop_output() {
+            serial.push(jib::text::byte_to_character(w).unwrap_or('?'));
+        }
+
+        if !serial.is_empty() {
+            print!("{serial}");
+            if !serial.ends_with('\n') {
+                println!();
+            }
+        }
+
+        while let Some(entry) = self.devices.log.borrow_mut().pop_entry() {
+            match entry.read_message(&self.cpu) {
+                Ok(m) => println!("[{}] {m}", entry.level),
+                Err(e) => println!(
+                    "[{}] unable to read log message at 0x{:08x} => {e}",
+                    entry.level, entry.address
+                ),
+            }
+        }
+    }
+
+    fn print_pc(&self) {
+        if let Ok(pc) = self.cpu.get_current_pc() {
+            if let Some(w) = disassemble_range(&self.cpu, pc, 1).first() {
+                println!("{}", self.format_word(w));
+            }
+        }
+    }
+
+    fn format_word(&self, w: &DisassembledWord) -> String {
+        let marker = if self.cpu.get_current_pc().ok() == Some(w.address) {
+            "=>"
+        } else if self.cpu.breakpoints().any(|b| b == w.address) {
+            " *"
+        } else {
+            "  "
+        };
+
+        let line = match self.debug_info.source_line(w.address) {
+            Some((file, n)) => format!("{marker} {w}  ; {file}:{n}"),
+            None => format!("{marker} {w}"),
+        };
+
+        match self.label_for(w.address) {
+            Some(l) => format!("{l}:\n{line}"),
+            None => line,
+        }
+    }
+
+    fn step(&mut self, count: usize) -> Result<(), String> {
+        for _ in 0..count {
+            let res = match self.cpu.step() {
+                Ok(StepResult::Breakpoint(_)) => self.cpu.step(),
+                r => r,
+            };
+
+            match res {
+                Ok(StepResult::Halted) => {
+                    println!("halted");
+                    break;
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    self.flush_devices();
+                    return Err(self.fault_message(e));
+                }
+            }
+        }
+
+        self.flush_devices();
+        self.print_pc();
+        Ok(())
+    }
+
+    /// Undoes up to the provided number of instructions. Device state and output are not undone
+    fn step_back(&mut self, count: usize) -> Result<(), String> {
+        for _ in 0..count {
+            if !self.cpu.step_back().map_err(|e| e.to_string())? {
+                println!("no instructions to undo");
+                break;
+            }
+        }
+
+        self.print_pc();
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), String> {
+        let summary = self.cpu.run(self.max_instructions);
+        self.flush_devices();
+
+        match summary.stop_reason {
+            StopReason::Halted => println!("halted after {} instructions", summary.instructions),
+            StopReason::Breakpoint(addr) => {
+                println!("breakpoint at {}", self.describe_location(addr))
+            }
+            StopReason::DebugHalt => println!("halted by debug port"),
+            StopReason::BudgetExhausted => {
+                println!("stopped after {} instructions", summary.instructions)
+            }
+            StopReason::Error(e) => return Err(self.fault_message(e)),
+        }
+
+        self.print_pc();
+        Ok(())
+    }
+
+    /// Provides the symbolic guest call stack at the current program counter
+    fn backtrace(&self) -> String {
+        let frames = unwind(&self.cpu, Self::MAX_BACKTRACE);
+        Backtrace {
+            frames: &frames,
+            symbols: &self.symbols,
+        }
+        .to_string()
+    }
+
+    /// Provides the error raised by the program, along with the trap recorded for it and the
+    /// guest call stack
+    fn fault_message(&self, e: ProcessorError) -> String {
+        match self.devices.trap_info.borrow().last_trap() {
+            Some(trap) => format!("{e}\n{trap}{}", self.backtrace()),
+            None => format!("{e}\n{}", self.backtrace()),
+        }
+    }
+
+    fn print_registers(&self) {
+        let regs = self.cpu.get_register_state().get_state();
+
+        for (i, val) in regs.iter().enumerate() {
+            let name = match Register::try_from(i) {
+                Ok(r) => r.to_string(),
+                Err(_) => i.to_string(),
+            };
+
+            print!("{name:>8} = 0x{val:08x}");
+            if i % 4 == 3 {
+                println!();
+            } else {
+                print!("  ");
+            }
+        }
+    }
+
+    fn examine(&self, addr: u32, count: usize) -> Result<(), String> {
+        for i in 0..count as u32 {
+            let a = addr.wrapping_add(i * Processor::BYTES_PER_WORD);
+            let val = self
+                .cpu
+                .memory_inspect_u32(a)
+                .map_err(|e| format!("0x{a:08x} - {e}"))?;
+            println!("0x{a:08x}  0x{val:08x}  {val}");
+        }
+
+        Ok(())
+    }
+
+    fn write_word(&mut self, addr: u32, val: u32) -> Result<(), String> {
+        for (i, b) in val.to_be_bytes().into_iter().enumerate() {
+            self.cpu
+                .memory_set(addr.wrapping_add(i as u32), b)
+                .map_err(|e| e.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    fn disassemble(&self, addr: Option<u32>, count: Option<usize>) -> Result<(), String> {
+        let (start, count) = match addr {
+            Some(a) => (a, count.unwrap_or(8)),
+            None => {
+                let pc = self.cpu.get_current_pc().map_err(|e| e.to_string())?;
+                let before = pc.min(4 * Processor::BYTES_PER_WORD);
+                (pc - before, count.unwrap_or(9))
+            }
+        };
+
+        for w in disassemble_range(&self.cpu, start, count) {
+            println!("{}", self.format_word(&w));
+        }
+
+        Ok(())
+    }
+
+    /// Writes the memory bytes from the address to a binary file
+    fn dump(&self, addr: u32, count: usize, path: &str) -> Result<(), String> {
+        let mut bytes = vec![0; count];
+        self.cpu
+            .memory_inspect_range(addr, &mut bytes)
+            .map_err(|e| e.to_string())?;
+
+        std::fs::write(path, bytes).map_err(|e| format!("unable to write {path} - {e}"))?;
+        println!("wrote {count} bytes from 0x{addr:08x} to {path}");
+        Ok(())
+    }
+
+    /// Starts or stops profiling, or prints the report of the counts recorded since profiling
+    /// started or the processor was last reset
+    fn profile(&mut self, arg: Option<&str>) -> Result<(), String> {
+        match arg {
+            Some("on") => {
+                self.profile = true;
+                self.cpu.start_profile();
+                println!("profiling started");
+            }
+            Some("off") => {
+                self.profile = false;
+                self.cpu.stop_profile();
+                println!("profiling stopped");
+            }
+            arg => {
+                let limit = match arg {
+                    Some(s) => s
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid count '{s}'"))?,
+                    None => Self::PROFILE_LINES,
+                };
+                let profile = self
+                    .cpu
+                    .profile()
+                    .ok_or("profiling is not enabled, see 'profile on'")?;
+
+                print!(
+                    "{}",
+                    ProfileReport {
+                        profile: &profile,
+                        symbols: &self.symbols,
+                        limit,
+                    }
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints the opcodes and the adjacent instruction pairs executed most often since profiling
+    /// started, as candidates for fused superinstructions
+    fn fusion(&self, limit: Option<usize>) -> Result<(), String> {
+        let profile = self
+            .cpu
+            .profile()
+            .ok_or("profiling is not enabled, see 'profile on'")?;
+
+        let limit = limit.unwrap_or(Self::PROFILE_LINES);
+        print!("{}", FusionReport::new(&profile, &self.cpu, limit));
+        Ok(())
+    }
+
+    /// Adds the labels and source lines of an assembly source file or debug information file
+    /// to those known, such that programs loaded from memory images may be debugged by name
+    fn load_symbols(&mut self, path: &str) -> Result<(), String> {
+        let txt =
+            std::fs::read_to_string(path).map_err(|e| format!("unable to read {path} - {e}"))?;
+        let info = if path.ends_with(&format!(".{}", DebugInfo::EXTENSION)) {
+            DebugInfo::from_text(&txt).map_err(|e| format!("{path} - {e}"))?
+        } else {
+            preprocess::preprocess_source(
+                &txt,
+                Some(Path::new(path)),
+                &preprocess::IncludePaths::default(),
+            )
+            .and_then(|lines| assemble_object(&lines))
+            .and_then(|obj| link_image(&[obj], &self.config.section_bases))
+            .map_err(|e| format!("{path} - Assembler Error: {e}"))?
+            .debug_info(&[path.to_string()])
+        };
+
+        println!("loaded {} labels from {path}", info.labels.len());
+        self.labels
+            .extend(info.labels.iter().map(|(k, v)| (k.clone(), *v)));
+        self.symbols = Symbolizer::new(&self.labels);
+        self.debug_info.merge(info);
+        Ok(())
+    }
+
+    /// Executes each command within a command file, stopping at the first command that fails.
+    /// Returns false if a command requested that the debugger exit
+    fn source(&mut self, path: &Path) -> Result<bool, String> {
+        if self.source_depth >= Self::MAX_SOURCE_DEPTH {
+            return Err(format!(
+                "{} - command files nested too deeply",
+                path.display()
+            ));
+        }
+
+        let txt = std::fs::read_to_string(path)
+            .map_err(|e| format!("unable to read {} - {e}", path.display()))?;
+
+        self.source_depth += 1;
+        let mut res = Ok(true);
+        for (i, line) in txt.lines().enumerate() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            res = self
+                .command(line)
+                .map_err(|e| format!("{}:{} - {e}", path.display(), i + 1));
+            if !matches!(res, Ok(true)) {
+                break;
+            }
+        }
+        self.source_depth -= 1;
+
+        res
+    }
+
+    /// Executes a single debugger command, returning false if the debugger should exit
+    /// Executes a single debugger command, flushing any instruction trace once it completes
+    fn command(&mut self, line: &str) -> Result<bool, String> {
+        let res = self.dispatch(line);
+
+        if let Some(trace) = &self.trace {
+            let mut trace = trace.borrow_mut();
+            if let Some(e) = trace.error() {
+                return Err(format!("unable to write trace - {e}"));
+            }
+            trace
+                .flush()
+                .map_err(|e| format!("unable to write trace - {e}"))?;
+        }
+
+        res
+    }
+
+    fn dispatch(&mut self, line: &str) -> Result<bool, String> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let Some(cmd) = words.first() else {
+            return Ok(true);
+        };
+        let args = &words[1..];
+
+        let arg_loc = |i: usize| args.get(i).map(|s| self.parse_loc(s)).transpose();
+        let arg_count = |i: usize| {
+            args.get(i)
+                .map(|s| {
+                    s.parse::<usize>()
+                        .map_err(|_| format!("invalid count '{s}'"))
+                })
+                .transpose()
+        };
+
+        match *cmd {
+            "s" | "step" => self.step(arg_count(0)?.unwrap_or(1))?,
+            "back" => self.step_back(arg_count(0)?.unwrap_or(1))?,
+            "c" | "continue" => self.resume()?,
+            "run" => {
+                self.reset().map_err(|e| e.to_string())?;
+                self.resume()?;
+            }
+            "b" | "break" => {
+                let addr = arg_loc(0)?.ok_or("break requires a location")?;
+                if self.cpu.add_breakpoint(addr) {
+                    println!("breakpoint added at {}", self.describe_location(addr));
+                } else {
+                    println!(
+                        "breakpoint already exists at {}",
+                        self.describe_location(addr)
+                    );
+                }
+            }
+            "d" | "delete" => {
+                let addr = arg_loc(0)?.ok_or("delete requires a location")?;
+                if self.cpu.remove_breakpoint(addr) {
+                    println!("breakpoint removed at {}", self.describe_location(addr));
+                } else {
+                    println!("no breakpoint at {}", self.describe_location(addr));
+                }
+            }
+            "i" | "info" => {
+                let mut brks = self.cpu.breakpoints().collect::<Vec<_>>();
+                brks.sort();
+
+                if brks.is_empty() {
+                    println!("no breakpoints");
+                }
+
+                for b in brks {
+                    println!("breakpoint at {}", self.describe_location(b));
+                }
+            }
+            "r" | "regs" => self.print_registers(),
+            "bt" | "backtrace" => println!("{}", self.backtrace()),
+            "trap" => match self.devices.trap_info.borrow().last_trap() {
+                Some(trap) => print!("{trap}"),
+                None => println!("no trap recorded"),
+            },
+            "profile" => self.profile(args.first().copied())?,
+            "fuse" => self.fusion(arg_count(0)?)?,
+            "x" => {
+                let addr = arg_loc(0)?.ok_or("x requires a location")?;
+                self.examine(addr, arg_count(1)?.unwrap_or(8))?;
+            }
+            "set" => {
+                let addr = arg_loc(0)?.ok_or("set requires a location")?;
+                let val = arg_loc(1)?.ok_or("set requires a value")?;
+                self.write_word(addr, val)?;
+            }
+            "l" | "disas" => self.disassemble(arg_loc(0)?, arg_count(1)?)?,
+            "dump" => {
+                let addr = arg_loc(0)?.ok_or("dump requires a location")?;
+                let count = arg_count(1)?.ok_or("dump requires a byte count")?;
+                let path = args.get(2).ok_or("dump requires a file")?;
+                self.dump(addr, count, path)?;
+            }
+            "symbols" => self.load_symbols(args.first().ok_or("symbols requires a file")?)?,
+            "source" => {
+                let path = args.first().ok_or("source requires a file")?;
+                return self.source(Path::new(path));
+            }
+            "reset" => {
+                self.reset().map_err(|e| e.to_string())?;
+                self.print_pc();
+            }
+            "h" | "help" => println!("{HELP}"),
+            "q" | "quit" => return Ok(false),
+            c => return Err(format!("unknown command '{c}', see 'help'")),
+        }
+
+        Ok(true)
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let config = match ProjectConfig::current() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let (image, debug_info) = match config.read_program(&args.input, args.base) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{} - {e}", args.input.display());
+            std::process::exit(2);
+        }
+    };
+
+    let mut dbg = Debugger::new(image, debug_info, args.max_instructions);
+
+    if let Some(p) = args.playback.as_ref().or(config.devices.playback.as_ref()) {
+        let script = std::fs::read_to_string(p)
+            .map_err(|e| format!("Unable to read - {e}"))
+            .and_then(|txt| PlaybackScript::parse(&txt).map_err(|e| e.to_string()));
+
+        match script {
+            Ok(s) => dbg.playback = Some(s),
+            Err(e) => {
+                eprintln!("{} - {e}", p.display());
+                std::process::exit(2);
+            }
+        }
+    }
+
+    dbg.devices = StandardDevices::new(config.devices.host_time_device());
+    dbg.config = config;
+    dbg.history = args.history;
+    dbg.profile = args.profile;
+    dbg.faults.rate = args.fault_rate;
+    dbg.faults.seed = args
+        .fault_seed
+        .or(dbg.playback.as_ref().and_then(|s| s.seed()))
+        .unwrap_or(1);
+    dbg.faults.parity_irq = args.parity_irq;
+    for s in args.fault_at.iter() {
+        let (loc, mask) = s.split_once(':').unwrap_or((s, "1"));
+        let fault = dbg.parse_loc(loc).and_then(|addr| {
+            if !(MachineBuilder::RAM_START..MachineBuilder::DEVICE_START).contains(&addr) {
+                return Err(format!("address 0x{addr:08x} is not within RAM"));
+            }
+            let mask = dbg.parse_loc(mask)?;
+            u8::try_from(mask)
+                .map(|m| (addr, m))
+                .map_err(|_| format!("invalid fault mask '{mask}'"))
+        });
+
+        match fault {
+            Ok(f) => dbg.faults.addresses.push(f),
+            Err(e) => {
+                eprintln!("--fault-at {s} - {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(p) = &args.trace {
+        match TextTracer::create(p) {
+            Ok(t) => {
+                let t = t.with_debug_info(dbg.debug_info.clone());
+                dbg.trace = Some(Rc::new(RefCell::new(t)));
+            }
+            Err(e) => {
+                eprintln!("{} - {e}", p.display());
+                std::process::exit(2);
+            }
+        }
+    }
+
+    if let Err(e) = dbg.reset() {
+        eprintln!("Unable to initialize processor - {e}");
+        std::process::exit(1);
+    }
+
+    dbg.print_pc();
+
+    for p in args.commands.iter() {
+        match dbg.source(p) {
+            Ok(true) => (),
+            Ok(false) => return,
+            Err(e) => {
+                println!("error: {e}");
+                if args.batch {
+                    std::process::exit(1);
+                }
+                break;
+            }
+        }
+    }
+
+    if args.batch {
+        return;
+    }
+
+    let stdin = std::io::stdin();
+    let mut last_line = String::new();
+
+    loop {
+        print!("(jdb) ");
+        std::io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => (),
+            Err(e) => {
+                eprintln!("Unable to read input - {e}");
+                std::process::exit(1);
+            }
+        }
+
+        // Repeat the previous command on an empty line, matching gdb
+        if line.trim().is_empty() {
+            line = last_line.clone();
+        } else {
+            last_line = line.clone();
+        }
+
+        match dbg.command(&line) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(e) => println!("error: {e}"),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use clap::Parser;
+use jib::memory::RelocationTable;
+use jib_asm::{
+    config::ProjectConfig,
+    object::{parse_section_base, ObjectFile},
+};
+
+/// Links Jib object files into a single memory image, with the layout and entry label of any
+/// scpu.toml project file within the working directory or its parents
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// The input object files
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// The output memory image file
+    #[arg(short, long, default_value = "a.bin")]
+    output: PathBuf,
+
+    /// Places the named relocatable section at the provided base address, as NAME=ADDRESS
+    #[arg(short, long = "section")]
+    sections: Vec<String>,
+
+    /// Writes the relocation table of the program alongside the output, with the jrel
+    /// extension, as with jasm --relocations
+    #[arg(short, long)]
+    relocations: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let config = match ProjectConfig::current() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut bases = HashMap::new();
+    for s in args.sections.iter() {
+        match parse_section_base(s) {
+            Some((name, addr)) => {
+                bases.insert(name, addr);
+            }
+            None => {
+                eprintln!("Invalid section base '{s}', expected NAME=ADDRESS");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut objects = Vec::new();
+    for p in args.inputs.iter() {
+        let txt = match std::fs::read_to_string(p) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Unable to read {} - {e}", p.display());
+                std::process::exit(1);
+            }
+        };
+
+        match ObjectFile::from_text(&txt) {
+            Ok(o) => objects.push(o),
+            Err(e) => {
+                eprintln!("{} - {e}", p.display());
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let linked = match config.link_image(&objects, &bases) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Linker Error: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    let bytes = &linked.bytes;
+    if let Err(e) = std::fs::write(&args.output, bytes) {
+        eprintln!("Unable to write {} - {e}", args.output.display());
+        std::process::exit(1);
+    }
+
+    println!(
+        "Linked {} bytes into {}",
+        bytes.len(),
+        args.output.display()
+    );
+
+    if args.relocations {
+        let reloc_output = RelocationTable::sidecar_path(&args.output);
+        let res = match linked.relocation_table() {
+            Some(table) => {
+                std::fs::write(&reloc_output, table.to_text()).map_err(|e| e.to_string())
+            }
+            None => Err("No program sections to relocate".to_string()),
+        };
+
+        if let Err(e) = res {
+            eprintln!("Unable to write {} - {e}", reloc_output.display());
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    io::{BufRead, IsTerminal, Read, Write},
+    path::PathBuf,
+    rc::Rc,
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
+};
+
+use clap::Parser;
+use jib::{
+    cpu::{Processor, ProcessorError, Register, StopReason},
+    device::{
+        HostTimeDevice, InputPlaybackDevice, InputRecordDevice, LogDevice, PlaybackScript,
+        SerialInputOutputDevice, TrapInfoDevice,
+    },
+    memory::MemoryImage,
+};
+use jib_asm::{
+    config::ProjectConfig,
+    engine::{Engine, EngineCommand, EngineEvent, EngineMachine},
+    machine::{MachineBuilder, StandardDevice, StandardDevices},
+    object::parse_address,
+    unwind::{unwind, Backtrace, Symbolizer},
+};
+
+/// Runs a program without a user interface until it halts, bridging the serial device to the
+/// standard input and output. The exit status is zero once the program halts, or the value of
+/// the exit register if provided, one if the processor stops with an error or reaches the
+/// instruction limit, and two if the program cannot be read or the recorded input cannot be
+/// written
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// The program to run, either an assembly file, a raw memory image with a .bin extension,
+    /// or a segmented memory image with a .jimg extension
+    input: PathBuf,
+
+    /// The maximum number of instructions to execute before stopping with an error
+    #[arg(short, long, default_value_t = 100_000_000)]
+    max_instructions: usize,
+
+    /// The standard devices to map into memory, at the same addresses as in the debugger and
+    /// the front-ends, where the address range of each device not listed is left unmapped
+    #[arg(
+        short,
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = StandardDevice::ALL
+    )]
+    devices: Vec<StandardDevice>,
+
+    /// Plays back timestamped serial input, key presses, and interrupts from a script, such as
+    /// one recorded by a front-end, instead of reading serial input from the standard input
+    #[arg(short, long)]
+    playback: Option<PathBuf>,
+
+    /// Exits with the lowest byte of the register value once the program halts, given as the
+    /// register index
+    #[arg(short, long, value_parser = parse_register)]
+    exit_register: Option<Register>,
+
+    /// Rejects any instruction with an undefined encoding, such as unused argument bits being set
+    #[arg(long)]
+    strict: bool,
+
+    /// Loads the program at the provided base address, moving the program with its relocation
+    /// table, where memory images require a table written alongside by jasm --relocations
+    #[arg(long, value_parser = parse_address)]
+    base: Option<u32>,
+
+    /// Derives device timing from processor cycles alone, such that a program run with the same
+    /// playback script or piped input behaves identically on every run. Piped input is read in
+    /// full before the program starts
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Writes the serial input provided to the program to a playback script, timestamped with
+    /// the cycle count it was provided at, such that the run may be replayed with --playback
+    #[arg(long, value_name = "PATH", conflicts_with = "playback")]
+    record: Option<PathBuf>,
+}
+
+fn parse_register(s: &str) -> Result<Register, String> {
+    s.parse::<usize>()
+        .ok()
+        .and_then(|i| Register::try_from(i).ok())
+        .ok_or_else(|| format!("invalid register '{s}'"))
+}
+
+/// Provides the processor and the standard devices attached to it
+struct Runner {
+    cpu: Processor,
+    symbols: Symbolizer,
+    serial_io_dev: Rc<RefCell<SerialInputOutputDevice>>,
+    input_record_dev: Rc<RefCell<InputRecordDevice>>,
+    log_dev: Rc<RefCell<LogDevice>>,
+    trap_dev: Rc<RefCell<TrapInfoDevice>>,
+    serial_input: Option<Receiver<u8>>,
+    pending_input: VecDeque<u8>,
+    /// Receives the recorded serial input once the runner is dropped, if requested
+    recording: Option<Sender<PlaybackScript>>,
+}
+
+impl Runner {
+    const MAX_BACKTRACE: usize = 16;
+
+    /// The number of instructions executed between each exchange of serial data with the host
+    const SLICE_INSTRUCTIONS: usize = 10_000;
+
+    /// The time to wait for the engine to respond to a command before giving up
+    const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+    fn new(
+        image: &MemoryImage,
+        labels: &HashMap<String, u32>,
+        devices: &[StandardDevice],
+        host_time_dev: HostTimeDevice,
+        playback: Option<PlaybackScript>,
+        deterministic: bool,
+    ) -> Result<Self, ProcessorError> {
+        let mut cpu = Processor::new();
+
+        let standard = StandardDevices::new(host_time_dev);
+
+        let mut builder = MachineBuilder::standard(&standard);
+        for dev in StandardDevice::ALL {
+            if !devices.contains(&dev) {
+                builder = builder.without(dev);
+            }
+        }
+        builder.build(&mut cpu)?;
+
+        let serial_io_dev = standard.serial;
+        let input_record_dev = Rc::new(RefCell::new(InputRecordDevice::new(serial_io_dev.clone())));
+        cpu.device_add(input_record_dev.clone())?;
+
+        // The image is loaded before playback starts, such that the reset doesn't clear any
+        // input provided before the first instruction
+        cpu.load_image(image)?;
+
+        let mut pending_input = VecDeque::new();
+        let serial_input = match playback {
+            Some(script) => {
+                let dev = InputPlaybackDevice::new(script, serial_io_dev.clone());
+                cpu.device_add(Rc::new(RefCell::new(dev.with_keyboard(standard.keyboard))))?;
+                None
+            }
+            None if !devices.contains(&StandardDevice::Serial) => None,
+            // Piped input is provided as the program makes room for it, rather than as it
+            // arrives, such that the time taken by the host to provide it has no effect
+            None if deterministic && !std::io::stdin().is_terminal() => {
+                let mut bytes = Vec::new();
+                if let Err(e) = std::io::stdin().lock().read_to_end(&mut bytes) {
+                    eprintln!("Unable to read standard input - {e}");
+                }
+                let text = String::from_utf8_lossy(&bytes);
+                pending_input.extend(text.chars().map(host_character_to_byte));
+                None
+            }
+            None => Some(spawn_stdin_reader()),
+        };
+
+        Ok(Self {
+            cpu,
+            symbols: Symbolizer::new(labels),
+            serial_io_dev,
+            input_record_dev,
+            log_dev: standard.log,
+            trap_dev: standard.trap_info,
+            serial_input,
+            pending_input,
+            recording: None,
+        })
+    }
+
+    /// Moves any input read from the host into the serial device, retaining input that does
+    /// not fit within the serial input buffer
+    fn pump_input(&mut self) {
+        if let Some(rx) = &self.serial_input {
+            self.pending_input.extend(rx.try_iter());
+        }
+
+        while let Some(b) = self.pending_input.front() {
+            if !self.input_record_dev.borrow_mut().push_input(*b) {
+                break;
+            }
+            self.pending_input.pop_front();
+        }
+    }
+
+    /// Provides the serial input provided to the program so far
+    fn recorded_input(&self) -> PlaybackScript {
+        self.input_record_dev.borrow().script().clone()
+    }
+
+    /// Writes pending serial output to the standard output and log messages to the standard
+    /// error
+    fn flush_devices(&self) {
+        let mut serial = String::new();
+        while let Some(w) = self.serial_io_dev.borrow_mut().pop_output() {
+            serial.push(jib::text::byte_to_character(w).unwrap_or('?'));
+        }
+
+        if !serial.is_empty() {
+            let mut out = std::io::stdout().lock();
+            let _ = out.write_all(serial.as_bytes());
+            let _ = out.flush();
+        }
+
+        while let Some(entry) = self.log_dev.borrow_mut().pop_entry() {
+            match entry.read_message(&self.cpu) {
+                Ok(m) => eprintln!("[{}] {m}", entry.level),
+                Err(e) => eprintln!(
+                    "[{}] unable to read log message at 0x{:08x} => {e}",
+                    entry.level, entry.address
+                ),
+            }
+        }
+    }
+
+    /// Runs the program on the engine until it halts or stops with an error, providing the exit
+    /// status
+    fn run(engine: &Engine, max_instructions: usize, exit_register: Option<Register>) -> i32 {
+        let mut remaining = max_instructions;
+
+        loop {
+            // Input is provided after each step has run, such that playback of a recorded
+            // script, which is checked after each instruction, provides it at the same point
+            engine.send(EngineCommand::Step(remaining.min(Self::SLICE_INSTRUCTIONS)));
+
+            let reason = loop {
+                match engine.next_event(Self::RESPONSE_TIMEOUT) {
+                    Ok(EngineEvent::Stopped(reason)) => break reason,
+                    Ok(EngineEvent::State(_)) => (),
+                    Ok(EngineEvent::Error(e)) => {
+                        eprintln!("{e}");
+                        return 1;
+                    }
+                    Ok(EngineEvent::Exited) | Err(_) => {
+                        eprintln!("processor stopped responding");
+                        return 1;
+                    }
+                }
+            };
+
+            let Some(state) = engine.query_state(Self::RESPONSE_TIMEOUT) else {
+                eprintln!("processor stopped responding");
+                return 1;
+            };
+            remaining = max_instructions.saturating_sub(state.instructions as usize);
+
+            match reason {
+                StopReason::BudgetExhausted | StopReason::Breakpoint(_) if remaining > 0 => (),
+                StopReason::BudgetExhausted | StopReason::Breakpoint(_) => {
+                    eprintln!("instruction limit of {max_instructions} reached");
+                    return 1;
+                }
+                StopReason::Halted | StopReason::DebugHalt => {
+                    return match exit_register {
+                        Some(reg) => {
+                            let val = state.registers.get_state()[reg.get_index()];
+                            (val & 0xFF) as i32
+                        }
+                        None => 0,
+                    };
+                }
+                // The error is reported by the runner while the processor state is available
+                StopReason::Error(_) => return 1,
+            }
+        }
+    }
+
+    /// Provides the error raised by the program, along with the trap recorded for it and the
+    /// guest call stack
+    fn fault_message(&self, e: ProcessorError) -> String {
+        let frames = unwind(&self.cpu, Self::MAX_BACKTRACE);
+        let backtrace = Backtrace {
+            frames: &frames,
+            symbols: &self.symbols,
+        };
+        match self.trap_dev.borrow().last_trap() {
+            Some(trap) => format!("{e}\n{trap}{backtrace}"),
+            None => format!("{e}\n{backtrace}"),
+        }
+    }
+}
+
+impl EngineMachine for Runner {
+    type Command = ();
+
+    fn processor(&mut self) -> &mut Processor {
+        &mut self.cpu
+    }
+
+    fn service(&mut self) {
+        self.pump_input();
+        self.flush_devices();
+    }
+
+    fn stopped(&mut self, reason: &StopReason) {
+        if let StopReason::Error(e) = reason {
+            eprintln!("{}", self.fault_message(e.clone()));
+        }
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        if let Some(tx) = &self.recording {
+            let _ = tx.send(self.recorded_input());
+        }
+    }
+}
+
+/// Reads the standard input on a separate thread, such that the program is not blocked while
+/// waiting for input, providing each line as serial bytes followed by a newline
+fn spawn_stdin_reader() -> Receiver<u8> {
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            for c in line.chars().chain(['\n']) {
+                if tx.send(host_character_to_byte(c)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    rx
+}
+
+/// Provides the serial byte for a character of host input, replacing unsupported characters
+fn host_character_to_byte(c: char) -> u8 {
+    jib::text::character_to_byte(c)
+        .or_else(|_| jib::text::character_to_byte('?'))
+        .unwrap_or_default()
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let config = match ProjectConfig::current() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+
+    let (image, debug_info) = match config.read_program(&args.input, args.base) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{} - {e}", args.input.display());
+            std::process::exit(2);
+        }
+    };
+
+    let playback = match args.playback.as_ref().or(config.devices.playback.as_ref()) {
+        Some(p) => {
+            let script = std::fs::read_to_string(p)
+                .map_err(|e| format!("Unable to read - {e}"))
+                .and_then(|txt| PlaybackScript::parse(&txt).map_err(|e| e.to_string()));
+
+            match script {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    eprintln!("{} - {e}", p.display());
+                    std::process::exit(2);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let mut device_config = config.devices.clone();
+    device_config.deterministic |= args.deterministic;
+
+    let (tx_recording, rx_recording) = mpsc::channel();
+    let recording = args.record.is_some().then_some(tx_recording);
+
+    // The processor and its devices are built on the engine thread, as they may not be moved
+    // between threads
+    let labels = debug_info.labels.into_iter().collect();
+    let devices = args.devices.clone();
+    let strict = args.strict;
+    let engine = Engine::spawn(move || {
+        let mut runner = Runner::new(
+            &image,
+            &labels,
+            &devices,
+            device_config.host_time_device(),
+            playback,
+            device_config.deterministic,
+        )
+        .map_err(|e| format!("Unable to initialize processor - {e}"))?;
+        runner.cpu.set_strict_encoding(strict);
+        runner.recording = recording;
+        Ok(runner)
+    });
+
+    let mut status = Runner::run(&engine, args.max_instructions, args.exit_register);
+    drop(engine);
+
+    // Nothing is recorded if the processor could not be initialized
+    if let (Some(p), Ok(script)) = (&args.record, rx_recording.recv()) {
+        if let Err(e) = std::fs::write(p, script.to_text()) {
+            eprintln!("{} - Unable to write - {e}", p.display());
+            status = 2;
+        }
+    }
+
+    std::process::exit(status);
+}
@@ -0,0 +1,327 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use clap::Parser;
+use jib_asm::{
+    assemble_object,
+    coverage::{Coverage, CoverageReport},
+    object::ObjectFile,
+    preprocess,
+    testing::{junit_report, SuiteResult, TestResult, TestRunner, TEST_SECTION},
+};
+
+/// Runs guest test functions, each within a new processor, and reports the results
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// The input assembly or object files, where files with a .jo extension are read as objects
+    #[arg(required_unless_present = "suite")]
+    inputs: Vec<PathBuf>,
+
+    /// Runs each assembly or object file within the directory as a separate test suite
+    #[arg(long, conflicts_with = "inputs")]
+    suite: Option<PathBuf>,
+
+    /// The number of suites to run in parallel, defaulting to the available parallelism
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Writes a JUnit XML report of the results to the provided file
+    #[arg(long)]
+    junit: Option<PathBuf>,
+
+    /// The maximum number of instructions to execute for each test
+    #[arg(short, long, default_value_t = 1_000_000)]
+    max_instructions: usize,
+
+    /// Rejects any instruction with an undefined encoding, such as unused argument bits being set
+    #[arg(long)]
+    strict: bool,
+
+    /// Writes the processor snapshots requested by tests to the provided directory, named by
+    /// the suite, test, and snapshot tag
+    #[arg(long)]
+    snapshot_dir: Option<PathBuf>,
+
+    /// Writes a coverage listing of each assembly input to the provided file, marking each
+    /// line as covered (+), partially covered (~), or not executed (-) by any test
+    #[arg(long)]
+    coverage: Option<PathBuf>,
+}
+
+fn is_object(p: &Path) -> bool {
+    p.extension().is_some_and(|e| e == "jo")
+}
+
+/// Reads the object file, providing the source text for assembly files
+fn read_object(p: &Path) -> Result<(ObjectFile, Option<String>), String> {
+    let txt = std::fs::read_to_string(p).map_err(|e| format!("Unable to read - {e}"))?;
+
+    let res = if is_object(p) {
+        ObjectFile::from_text(&txt).map(|o| (o, None))
+    } else {
+        preprocess::preprocess_source(&txt, Some(p), &preprocess::IncludePaths::default())
+            .and_then(|lines| assemble_object(&lines))
+            .map(|o| (o, Some(txt)))
+    };
+
+    res.map_err(|e| format!("Assembler Error: {e}"))
+}
+
+/// Provides the sorted list of assembly and object files within the suite directory
+fn discover_suite(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("Unable to read - {e}"))?;
+
+    let mut files = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && (is_object(p) || p.extension().is_some_and(|e| e == "jsm")))
+        .collect::<Vec<_>>();
+
+    files.sort();
+    Ok(files)
+}
+
+/// Creates a test runner for the objects, with the options provided by the arguments
+fn create_runner(objects: &[ObjectFile], args: &Args) -> Result<TestRunner, String> {
+    let mut runner = TestRunner::new(objects, args.max_instructions)
+        .map_err(|e| format!("Linker Error: {e}"))?;
+    runner.set_strict_encoding(args.strict);
+    runner.set_coverage(args.coverage.is_some());
+    Ok(runner)
+}
+
+/// Provides the coverage listing of each input file, merging the coverage of every test
+fn coverage_report(
+    runner: &TestRunner,
+    results: &[TestResult],
+    inputs: &[(&Path, Option<String>)],
+) -> String {
+    let mut coverage = Coverage::new();
+    for c in results.iter().filter_map(|r| r.coverage.as_ref()) {
+        coverage.merge(c);
+    }
+
+    let mut report = String::new();
+    for (i, (p, source)) in inputs.iter().enumerate() {
+        report += &format!("== {}\n", p.display());
+        match source {
+            Some(source) => {
+                report += &CoverageReport {
+                    coverage: &coverage,
+                    image: runner.image(),
+                    object: i + 1,
+                    source,
+                }
+                .to_string()
+            }
+            None => report += "no source available for object files\n",
+        }
+    }
+
+    report
+}
+
+/// Runs every test within a single suite file, providing the coverage listing if enabled
+fn run_suite(p: &Path, args: &Args) -> (SuiteResult, Option<String>) {
+    let mut coverage = None;
+    let results = read_object(p).and_then(|(obj, source)| {
+        let runner = create_runner(&[obj], args)?;
+        let results = runner.run_all();
+        if args.coverage.is_some() {
+            coverage = Some(coverage_report(&runner, &results, &[(p, source)]));
+        }
+        Ok(results)
+    });
+
+    let suite = SuiteResult {
+        name: p.display().to_string(),
+        results,
+    };
+    (suite, coverage)
+}
+
+/// Runs each suite on a pool of worker threads, each with its own processor instances,
+/// providing the results in the same order as the input files
+fn run_suites(files: &[PathBuf], jobs: usize, args: &Args) -> Vec<(SuiteResult, Option<String>)> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![None; files.len()]);
+
+    std::thread::scope(|s| {
+        for _ in 0..jobs.clamp(1, files.len().max(1)) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(p) = files.get(i) else {
+                    break;
+                };
+
+                let res = run_suite(p, args);
+                results.lock().unwrap()[i] = Some(res);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Writes each snapshot requested by the tests of the suites into the directory
+fn write_snapshots(dir: &Path, suites: &[SuiteResult]) -> std::io::Result<usize> {
+    std::fs::create_dir_all(dir)?;
+
+    let mut count = 0;
+    for suite in suites.iter() {
+        let suite_name = Path::new(&suite.name)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        for res in suite.results.iter().flatten() {
+            for (tag, snapshot) in res.snapshots.iter() {
+                let path = dir.join(format!("{suite_name}.{}.{tag}.snap", res.name));
+                std::fs::write(path, snapshot.to_bytes())?;
+                count += 1;
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let (suites, coverage): (Vec<_>, Vec<_>) = if let Some(dir) = &args.suite {
+        let files = match discover_suite(dir) {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!("{} - {e}", dir.display());
+                std::process::exit(2);
+            }
+        };
+
+        let jobs = args.jobs.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        });
+
+        println!("Running {} suites", files.len());
+        run_suites(&files, jobs, &args).into_iter().unzip()
+    } else {
+        let mut objects = Vec::new();
+        let mut inputs = Vec::new();
+        for p in args.inputs.iter() {
+            match read_object(p) {
+                Ok((o, source)) => {
+                    objects.push(o);
+                    inputs.push((p.as_path(), source));
+                }
+                Err(e) => {
+                    eprintln!("{} - {e}", p.display());
+                    std::process::exit(2);
+                }
+            }
+        }
+
+        let runner = match create_runner(&objects, &args) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(2);
+            }
+        };
+
+        let tests = runner.tests();
+        if tests.is_empty() {
+            println!("No tests found in the '{TEST_SECTION}' section");
+        } else {
+            println!("Running {} tests", tests.len());
+        }
+
+        let results = tests
+            .into_iter()
+            .map(|(name, addr)| runner.run_test(name, addr))
+            .collect::<Vec<_>>();
+
+        let coverage = args
+            .coverage
+            .is_some()
+            .then(|| coverage_report(&runner, &results, &inputs));
+
+        let suite = SuiteResult {
+            name: "tests".into(),
+            results: Ok(results),
+        };
+        (vec![suite], vec![coverage])
+    };
+
+    let mut total = 0;
+    let mut failed = 0;
+    for suite in suites.iter() {
+        if args.suite.is_some() {
+            println!("\nsuite {}", suite.name);
+        }
+
+        match &suite.results {
+            Ok(results) => {
+                for res in results.iter() {
+                    println!("test {} ... {}", res.name, res.outcome);
+                }
+                total += results.len();
+            }
+            Err(e) => {
+                println!("FAILED - {e}");
+                total += 1;
+            }
+        }
+
+        failed += suite.failures();
+    }
+
+    println!(
+        "\ntest result: {}. {} passed; {failed} failed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        total - failed
+    );
+
+    if let Some(dir) = &args.snapshot_dir {
+        match write_snapshots(dir, &suites) {
+            Ok(n) => println!("Wrote {n} snapshots to {}", dir.display()),
+            Err(e) => {
+                eprintln!("{} - Unable to write snapshots - {e}", dir.display());
+                std::process::exit(2);
+            }
+        }
+    }
+
+    if let Some(p) = &args.coverage {
+        let report = coverage.into_iter().flatten().collect::<String>();
+        match std::fs::write(p, report) {
+            Ok(()) => println!("Wrote coverage to {}", p.display()),
+            Err(e) => {
+                eprintln!("{} - Unable to write coverage - {e}", p.display());
+                std::process::exit(2);
+            }
+        }
+    }
+
+    if let Some(p) = &args.junit {
+        if let Err(e) = std::fs::write(p, junit_report(&suites)) {
+            eprintln!("{} - Unable to write - {e}", p.display());
+            std::process::exit(2);
+        }
+    }
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use jib::cpu::Processor;
+use jib::debug_info::DebugInfo;
+use jib::device::HostTimeDevice;
+use jib::memory::{MemoryImage, RelocationTable};
+use toml::{Table, Value};
+
+use crate::object::{link_image, LinkedImage, ObjectFile, VectorEntry, VectorTarget};
+use crate::preprocess::{preprocess_source, IncludePaths};
+use crate::{assemble_object, AssemblerErrorLoc};
+
+/// Provides the devices attached by the front-ends and debugger when running the project
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceConfig {
+    /// A disk image file to attach to the block storage device
+    pub disk: Option<PathBuf>,
+    /// An address to listen on for a TCP client bridged to the serial device
+    pub serial_tcp: Option<String>,
+    /// A playback script providing timestamped serial input
+    pub playback: Option<PathBuf>,
+    /// Derives device timing from processor cycles alone, rather than host time, such that a
+    /// program run with the same playback script behaves identically on every run
+    pub deterministic: bool,
+}
+
+impl DeviceConfig {
+    /// Defines the processor cycles per millisecond of the host time device in deterministic
+    /// runs
+    pub const DETERMINISTIC_CYCLES_PER_MS: u64 = 1_000;
+
+    /// Provides the host time device, reading either the host time since the device was
+    /// created or, for deterministic runs, the time derived from the processor cycles
+    pub fn host_time_device(&self) -> HostTimeDevice {
+        if self.deterministic {
+            HostTimeDevice::cycle_clock(Self::DETERMINISTIC_CYCLES_PER_MS)
+        } else {
+            let start = Instant::now();
+            HostTimeDevice::new(move || start.elapsed().as_millis() as u64)
+        }
+    }
+}
+
+/// Provides the settings shared by each tool for a project, read from a `scpu.toml` file, such
+/// that they need not be repeated as command-line arguments for each tool. Command-line
+/// arguments take priority over the project settings. A project file may contain:
+///
+/// ```toml
+/// entry = "start"
+/// optimization = 1
+///
+/// [layout]
+/// data = 0x8000
+///
+/// [devices]
+/// disk = "disk.img"
+/// serial_tcp = "127.0.0.1:2323"
+/// playback = "input.txt"
+/// deterministic = true
+/// ```
+///
+/// Paths are relative to the directory containing the project file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProjectConfig {
+    /// The directory containing the project file
+    pub dir: PathBuf,
+    /// The base address of each relocatable section placed by the linker
+    pub section_bases: HashMap<String, u32>,
+    /// The label used for the hard and soft reset vectors, for programs that do not set the
+    /// reset vectors themselves
+    pub entry: Option<String>,
+    /// The C/Buoy optimization level, where 0 disables optimization
+    pub optimization: Option<u32>,
+    pub devices: DeviceConfig,
+}
+
+impl ProjectConfig {
+    pub const FILE_NAME: &'static str = "scpu.toml";
+
+    /// Reads the project file within the provided directory, or the nearest parent directory
+    /// containing one. Provides the default settings if no project file is found
+    pub fn discover(dir: &Path) -> Result<Self, String> {
+        match dir
+            .ancestors()
+            .map(|d| d.join(Self::FILE_NAME))
+            .find(|p| p.is_file())
+        {
+            Some(p) => Self::load(&p).map_err(|e| format!("{} - {e}", p.display())),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Reads the project file for the current working directory, as with discover
+    pub fn current() -> Result<Self, String> {
+        let dir = std::env::current_dir().map_err(|e| format!("Unable to read directory - {e}"))?;
+        Self::discover(&dir)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let txt = std::fs::read_to_string(path).map_err(|e| format!("Unable to read - {e}"))?;
+        Self::parse(&txt, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Parses the project file text, with relative paths resolved against the provided
+    /// directory. Unknown keys are rejected, such that misspelled settings are not ignored
+    pub fn parse(txt: &str, dir: &Path) -> Result<Self, String> {
+        let table = txt.parse::<Table>().map_err(|e| e.to_string())?;
+
+        let mut config = Self {
+            dir: dir.to_path_buf(),
+            ..Default::default()
+        };
+
+        for (key, val) in table.iter() {
+            match key.as_str() {
+                "entry" => config.entry = Some(Self::string(key, val)?.to_string()),
+                "optimization" => config.optimization = Some(Self::integer(key, val)?),
+                "layout" => {
+                    for (name, base) in Self::table(key, val)?.iter() {
+                        let base = Self::integer(&format!("{key}.{name}"), base)?;
+                        config.section_bases.insert(name.to_lowercase(), base);
+                    }
+                }
+                "devices" => {
+                    for (name, v) in Self::table(key, val)?.iter() {
+                        let full_name = format!("{key}.{name}");
+                        if name == "deterministic" {
+                            config.devices.deterministic = Self::boolean(&full_name, v)?;
+                            continue;
+                        }
+
+                        let s = Self::string(&full_name, v)?;
+                        match name.as_str() {
+                            "disk" => config.devices.disk = Some(dir.join(s)),
+                            "serial_tcp" => config.devices.serial_tcp = Some(s.to_string()),
+                            "playback" => config.devices.playback = Some(dir.join(s)),
+                            _ => return Err(format!("unknown setting '{full_name}'")),
+                        }
+                    }
+                }
+                _ => return Err(format!("unknown setting '{key}'")),
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn string<'a>(key: &str, val: &'a Value) -> Result<&'a str, String> {
+        val.as_str()
+            .ok_or_else(|| format!("'{key}' must be a string"))
+    }
+
+    fn boolean(key: &str, val: &Value) -> Result<bool, String> {
+        val.as_bool()
+            .ok_or_else(|| format!("'{key}' must be a boolean"))
+    }
+
+    fn integer(key: &str, val: &Value) -> Result<u32, String> {
+        val.as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("'{key}' must be an unsigned 32-bit integer"))
+    }
+
+    fn table<'a>(key: &str, val: &'a Value) -> Result<&'a Table, String> {
+        val.as_table()
+            .ok_or_else(|| format!("'{key}' must be a table"))
+    }
+
+    /// Provides the section bases of the layout, replaced by any of the provided bases
+    pub fn bases_with(&self, bases: &HashMap<String, u32>) -> HashMap<String, u32> {
+        let mut all = self.section_bases.clone();
+        all.extend(bases.iter().map(|(k, v)| (k.clone(), *v)));
+        all
+    }
+
+    /// Provides an object file setting the reset vectors to the entry label, if provided
+    pub fn entry_object(&self) -> Option<ObjectFile> {
+        let label = self.entry.as_ref()?;
+        let vectors = [Processor::HARD_RESET_VECTOR, Processor::SOFT_RESET_VECTOR]
+            .into_iter()
+            .map(|addr| VectorEntry {
+                target: VectorTarget::Address(addr),
+                label: label.to_lowercase(),
+                loc: Default::default(),
+            })
+            .collect();
+
+        Some(ObjectFile {
+            sections: Vec::new(),
+            vectors,
+        })
+    }
+
+    /// Links the object files as with [`link_image`], using the section bases of the layout,
+    /// replaced by any of the provided bases, and setting the reset vectors to any entry label
+    pub fn link_image(
+        &self,
+        objects: &[ObjectFile],
+        bases: &HashMap<String, u32>,
+    ) -> Result<LinkedImage, AssemblerErrorLoc> {
+        let mut objects = objects.to_vec();
+        objects.extend(self.entry_object());
+        link_image(&objects, &self.bases_with(bases))
+    }
+
+    /// Reads the program, assembling and linking it with the project settings if required,
+    /// providing the memory image along with the labels and source lines of the program. Debug
+    /// information and relocation tables for memory images are read from any jdbg and jrel files
+    /// written alongside the image. If a base address is provided, the program is moved to start
+    /// at the base address
+    pub fn read_program(
+        &self,
+        p: &Path,
+        base: Option<u32>,
+    ) -> Result<(MemoryImage, DebugInfo), String> {
+        let image = match p.extension().and_then(|e| e.to_str()) {
+            Some("bin") => {
+                let bytes = std::fs::read(p).map_err(|e| format!("Unable to read - {e}"))?;
+                Some(MemoryImage::from_flat(bytes))
+            }
+            Some("jimg") => {
+                let bytes = std::fs::read(p).map_err(|e| format!("Unable to read - {e}"))?;
+                Some(MemoryImage::from_bytes(&bytes).map_err(|e| e.to_string())?)
+            }
+            _ => None,
+        };
+
+        let (image, info, table) = match image {
+            Some(image) => {
+                let info = DebugInfo::load_sidecar(p)?.unwrap_or_default();
+                let table = match base {
+                    Some(_) => RelocationTable::load_sidecar(p)?,
+                    None => None,
+                };
+                (image, info, table)
+            }
+            None => {
+                let txt =
+                    std::fs::read_to_string(p).map_err(|e| format!("Unable to read - {e}"))?;
+
+                let linked = preprocess_source(&txt, Some(p), &IncludePaths::default())
+                    .and_then(|lines| assemble_object(&lines))
+                    .and_then(|obj| self.link_image(&[obj], &HashMap::new()))
+                    .map_err(|e| format!("Assembler Error: {e}"))?;
+
+                let info = linked.debug_info(&[p.display().to_string()]);
+                let table = linked.relocation_table();
+                (linked.image, info, table)
+            }
+        };
+
+        match base {
+            Some(base) => {
+                let table = table.ok_or(
+                    "No relocation table for the program, as written by jasm --relocations",
+                )?;
+                let image = table
+                    .relocate_image(&image, base)
+                    .map_err(|e| e.to_string())?;
+                Ok((image, info.relocated(&table, base)))
+            }
+            None => Ok((image, info)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::preprocess::preprocess_text;
+
+    #[test]
+    fn test_project_config() {
+        let txt = "\
+entry = \"Main\"
+optimization = 0
+
+[layout]
+data = 0x2000
+
+[devices]
+disk = \"disk.img\"
+serial_tcp = \"127.0.0.1:2323\"
+deterministic = true
+";
+        let config = ProjectConfig::parse(txt, Path::new("proj")).unwrap();
+        assert_eq!(config.entry.as_deref(), Some("Main"));
+        assert_eq!(config.optimization, Some(0));
+        assert_eq!(config.section_bases["data"], 0x2000);
+        assert_eq!(config.devices.disk, Some(PathBuf::from("proj/disk.img")));
+        assert_eq!(config.devices.serial_tcp.as_deref(), Some("127.0.0.1:2323"));
+        assert_eq!(config.devices.playback, None);
+        assert!(config.devices.deterministic);
+
+        let asm = ".org 0x400\n:main\nhalt\n.section data\n:value\n.u32 1\n";
+        let obj = assemble_object(&preprocess_text(asm).unwrap()).unwrap();
+
+        let image = config
+            .link_image(std::slice::from_ref(&obj), &HashMap::new())
+            .unwrap();
+        assert_eq!(image.labels["value"], 0x2000);
+        assert_eq!(&image.bytes[0..8], &[0, 0, 4, 0, 0, 0, 4, 0]);
+
+        let bases = HashMap::from([("data".to_string(), 0x3000)]);
+        let image = config.link_image(&[obj], &bases).unwrap();
+        assert_eq!(image.labels["value"], 0x3000);
+
+        assert!(ProjectConfig::parse("entry = 5", Path::new("")).is_err());
+        assert!(ProjectConfig::parse("[layout]\ndata = -1", Path::new("")).is_err());
+        assert!(ProjectConfig::parse("[devices]\nprinter = \"lp\"", Path::new("")).is_err());
+        assert!(ProjectConfig::parse("[devices]\ndeterministic = 1", Path::new("")).is_err());
+    }
+}
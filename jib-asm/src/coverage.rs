@@ -0,0 +1,229 @@
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use jib::cpu::{DecodedInstruction, Processor, Register, TraceEvent, Tracer};
+
+use crate::object::LinkedImage;
+
+/// Provides the outcomes seen for a conditional test instruction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BranchCoverage {
+    /// The following instruction was executed
+    pub continued: bool,
+    /// The following instruction was skipped
+    pub skipped: bool,
+}
+
+impl BranchCoverage {
+    pub fn is_complete(&self) -> bool {
+        self.continued && self.skipped
+    }
+}
+
+/// Records the address of each instruction executed, along with the outcomes of each
+/// conditional test instruction, when provided to the processor as a tracer. Coverage from
+/// several runs, such as each test of a suite, may be merged together
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    pub executed: BTreeSet<u32>,
+    pub branches: BTreeMap<u32, BranchCoverage>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_executed(&self, addr: u32) -> bool {
+        self.executed.contains(&addr)
+    }
+
+    /// Adds the instructions and branch outcomes recorded by the other coverage
+    pub fn merge(&mut self, other: &Coverage) {
+        self.executed.extend(other.executed.iter().copied());
+        for (addr, b) in other.branches.iter() {
+            let entry = self.branches.entry(*addr).or_default();
+            entry.continued |= b.continued;
+            entry.skipped |= b.skipped;
+        }
+    }
+}
+
+impl Tracer for Coverage {
+    fn trace(&mut self, event: &TraceEvent) {
+        self.executed.insert(event.pc);
+
+        if !matches!(event.decoded(), Some(DecodedInstruction::TestZero { .. })) {
+            return;
+        }
+
+        // Outcomes are unknown when an interrupt was entered at the end of the step
+        let next = event.after[Register::IDX_PROGRAM_COUNTER];
+        let entry = self.branches.entry(event.pc).or_default();
+        if next == event.pc.wrapping_add(Processor::BYTES_PER_WORD) {
+            entry.continued = true;
+        } else if next == event.pc.wrapping_add(2 * Processor::BYTES_PER_WORD) {
+            entry.skipped = true;
+        }
+    }
+}
+
+/// Provides the coverage state of a single source line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCoverage {
+    /// Every instruction of the line was executed, with both outcomes of any test
+    Covered,
+    /// Some of the instructions of the line were executed, or only one outcome of a test
+    Partial,
+    /// None of the instructions of the line were executed
+    Uncovered,
+}
+
+impl LineCoverage {
+    fn marker(&self) -> char {
+        match self {
+            Self::Covered => '+',
+            Self::Partial => '~',
+            Self::Uncovered => '-',
+        }
+    }
+}
+
+/// Provides a printable coverage listing of a single object linked into an image, with each
+/// line of the object source marked as covered (+), partially covered (~), or uncovered (-).
+/// Lines without instructions are left unmarked
+pub struct CoverageReport<'a> {
+    pub coverage: &'a Coverage,
+    pub image: &'a LinkedImage,
+    /// The index of the object file within the objects provided to the linker
+    pub object: usize,
+    /// The assembly source of the object file
+    pub source: &'a str,
+}
+
+impl CoverageReport<'_> {
+    /// Provides the coverage state of each source line containing an instruction, by line
+    pub fn lines(&self) -> BTreeMap<usize, LineCoverage> {
+        // Track whether any instruction of each line was executed, and whether every
+        // instruction was executed with both outcomes of any test
+        let mut lines = BTreeMap::<usize, (bool, bool)>::new();
+        for (addr, loc) in self
+            .image
+            .lines
+            .iter()
+            .filter(|(_, l)| l.object == self.object)
+        {
+            let executed = self.coverage.is_executed(*addr);
+            let complete = executed
+                && self
+                    .coverage
+                    .branches
+                    .get(addr)
+                    .is_none_or(|b| b.is_complete());
+
+            let entry = lines.entry(loc.line).or_insert((false, true));
+            entry.0 |= executed;
+            entry.1 &= complete;
+        }
+
+        lines
+            .into_iter()
+            .map(|(line, state)| {
+                let state = match state {
+                    (_, true) => LineCoverage::Covered,
+                    (true, false) => LineCoverage::Partial,
+                    (false, false) => LineCoverage::Uncovered,
+                };
+                (line, state)
+            })
+            .collect()
+    }
+
+    /// Provides the number of lines fully covered and the number of lines with instructions
+    pub fn summary(&self) -> (usize, usize) {
+        let lines = self.lines();
+        let covered = lines
+            .values()
+            .filter(|c| **c == LineCoverage::Covered)
+            .count();
+        (covered, lines.len())
+    }
+}
+
+impl fmt::Display for CoverageReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = self.lines();
+        for (i, text) in self.source.lines().enumerate() {
+            let marker = lines.get(&(i + 1)).map_or(' ', |c| c.marker());
+            writeln!(f, "{marker} {:>5}  {text}", i + 1)?;
+        }
+
+        let (covered, total) = self.summary();
+        let percent = if total == 0 {
+            100.0
+        } else {
+            covered as f64 * 100.0 / total as f64
+        };
+        writeln!(f, "{covered} of {total} lines covered ({percent:.1}%)")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    use jib::memory::ReadWriteSegment;
+
+    use super::*;
+    use crate::{assemble_object, object::link_image, preprocess::preprocess_text};
+
+    #[test]
+    fn test_coverage_report() {
+        let txt = "\
+.loadloc start
+.org 0x400
+:start
+ldi 6:u16 0
+tz 6
+jmpri 8
+ldi 7:u16 1
+halt
+:unused
+ldi 7:u16 2
+";
+        let obj = assemble_object(&preprocess_text(txt).unwrap()).unwrap();
+        let image = link_image(&[obj], &HashMap::new()).unwrap();
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x1000))))
+            .unwrap();
+        cpu.load_image(&image.image).unwrap();
+
+        let coverage = Rc::new(RefCell::new(Coverage::new()));
+        cpu.set_tracer(coverage.clone());
+        while !cpu.halted() {
+            cpu.step().unwrap();
+        }
+
+        let coverage = coverage.borrow();
+        let report = CoverageReport {
+            coverage: &coverage,
+            image: &image,
+            object: 0,
+            source: txt,
+        };
+
+        let lines = report.lines();
+        assert_eq!(lines[&4], LineCoverage::Covered);
+        assert_eq!(lines[&5], LineCoverage::Partial);
+        assert_eq!(lines[&7], LineCoverage::Uncovered);
+        assert_eq!(lines[&8], LineCoverage::Covered);
+        assert_eq!(lines[&10], LineCoverage::Uncovered);
+        assert!(!lines.contains_key(&3));
+        assert_eq!(report.summary(), (3, 6));
+
+        let text = report.to_string();
+        assert!(text.contains("~     5  tz 6"));
+        assert!(text.ends_with("3 of 6 lines covered (50.0%)\n"));
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+
+use jib::cpu::{DecodedInstruction, Processor};
+
+/// Provides the assembly text for a single instruction word, using the decoder shared with the
+/// processor. Words that do not decode to a valid instruction are provided as a data directive
+pub fn disassemble(word: u32) -> String {
+    match DecodedInstruction::decode(word.into()) {
+        Ok(inst) => inst.to_string(),
+        Err(_) => data_word(word),
+    }
+}
+
+fn data_word(word: u32) -> String {
+    format!(".u32 0x{word:08x}")
+}
+
+/// Provides a single disassembled word of memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassembledWord {
+    pub address: u32,
+    pub value: Option<u32>,
+    pub text: String,
+}
+
+impl fmt::Display for DisassembledWord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            Some(v) => write!(f, "0x{:08x}  {v:08x}  {}", self.address, self.text),
+            None => write!(f, "0x{:08x}  ????????  {}", self.address, self.text),
+        }
+    }
+}
+
+/// Disassembles the provided number of words from processor memory, starting at the given
+/// address. The word following a load-next instruction is provided as data rather than being
+/// decoded as an instruction, and memory that cannot be inspected is marked as unknown
+pub fn disassemble_range(cpu: &Processor, start: u32, count: usize) -> Vec<DisassembledWord> {
+    disassemble_words(start, count, |address| cpu.memory_inspect_u32(address).ok())
+}
+
+/// Disassembles a copy of memory starting at the given address, as with disassemble_range.
+/// Trailing bytes that do not fill a whole word are ignored
+pub fn disassemble_bytes(start: u32, bytes: &[u8]) -> Vec<DisassembledWord> {
+    disassemble_words(start, bytes.len() / 4, |address| {
+        let offset = address.wrapping_sub(start) as usize;
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+    })
+}
+
+fn disassemble_words(
+    start: u32,
+    count: usize,
+    read: impl Fn(u32) -> Option<u32>,
+) -> Vec<DisassembledWord> {
+    let mut words = Vec::with_capacity(count);
+    let mut next_is_data = false;
+
+    for i in 0..count as u32 {
+        let address = match i
+            .checked_mul(Processor::BYTES_PER_WORD)
+            .and_then(|off| start.checked_add(off))
+        {
+            Some(a) => a,
+            None => break,
+        };
+
+        let value = read(address);
+
+        let text = match value {
+            Some(v) if next_is_data => data_word(v),
+            Some(v) => disassemble(v),
+            None => "??".to_string(),
+        };
+
+        next_is_data = !next_is_data
+            && value
+                .and_then(|v| DecodedInstruction::decode(v.into()).ok())
+                .is_some_and(|inst| inst.size() > Processor::BYTES_PER_WORD);
+
+        words.push(DisassembledWord {
+            address,
+            value,
+            text,
+        });
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use jib::memory::ReadOnlySegment;
+
+    use super::*;
+    use crate::assemble_text;
+
+    #[test]
+    fn test_disassemble() {
+        for (line, expected) in [
+            ("ldi 8:u16 25", "ldi 8:u16 0x0019"),
+            ("add 9:u32 10 11", "add 9:u32 10 11"),
+            ("sub 9:i16 [10] 11", "sub 9:i16 [10] 11"),
+            ("teq 9:u32 10 [11]", "teq 9:u32 10 [11]"),
+            ("neg 5:u32 [6]", "neg 5:u32 [6]"),
+            ("mac 9:f32 10 11", "mac 9:f32 10 11"),
+            ("bcpy 9:u32 10 11", "bcpy 9:u32 10 11"),
+            ("cpuid 9 10", "cpuid 9 10"),
+            ("ldn 7:i16", "ldn 7:i16"),
+            ("jmpri -4", "jmpri -4"),
+            ("halt", "halt"),
+            ("esc 3 0x1234", "esc 3 0x1234"),
+        ] {
+            let bytes = assemble_text(line).unwrap();
+            let word = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+            assert_eq!(disassemble(word), expected);
+        }
+
+        assert_eq!(disassemble(0xff000000), ".u32 0xff000000");
+        assert!(assemble_text("copy [9] 10").is_err());
+        assert!(assemble_text("bset 9:u8 [10] 11").is_err());
+    }
+
+    #[test]
+    fn test_disassemble_round_trip() {
+        use jib::cpu::{InstructionFormat, INSTRUCTION_TABLE};
+
+        for def in INSTRUCTION_TABLE {
+            let args = match def.format {
+                InstructionFormat::NoArgument => "",
+                InstructionFormat::Immediate => " -4",
+                InstructionFormat::Register => " 9",
+                InstructionFormat::RegisterType => " 9:u32",
+                InstructionFormat::RegisterTypeImmediate => " 9:u16 0x000c",
+                InstructionFormat::DoubleRegister => " 9 10",
+                InstructionFormat::DoubleRegisterType => " 9:u32 10",
+                InstructionFormat::Conversion => " 9:u32 10:f32",
+                InstructionFormat::Arithmetic => " 9:u32 10 11",
+                InstructionFormat::Extension => " 3 0x1234",
+            };
+
+            let line = format!("{}{args}", def.mnemonic);
+            let bytes = assemble_text(&line).unwrap();
+            let word = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+            assert_eq!(disassemble(word), line);
+        }
+    }
+
+    #[test]
+    fn test_disassemble_reassemble() {
+        let mut word = 0x2545_d2c1u32;
+
+        for _ in 0..0x2000 {
+            word ^= word << 13;
+            word ^= word >> 17;
+            word ^= word << 5;
+
+            let text = disassemble(word);
+            let bytes = assemble_text(&text).unwrap_or_else(|e| panic!("{text} => {e}"));
+            let reassembled = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+            assert_eq!(disassemble(reassembled), text);
+        }
+    }
+
+    #[test]
+    fn test_disassemble_range() {
+        let bytes = assemble_text("ldn 8:u32\n.u32 0x01020304\nhalt").unwrap();
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadOnlySegment::new(bytes))))
+            .unwrap();
+
+        let words = disassemble_range(&cpu, 0, 4);
+        assert_eq!(
+            words.iter().map(|w| w.text.as_str()).collect::<Vec<_>>(),
+            ["ldn 8:u32", ".u32 0x01020304", "halt", "??"]
+        );
+        assert_eq!(words[1].value, Some(0x01020304));
+        assert_eq!(words[3].value, None);
+    }
+
+    #[test]
+    fn test_disassemble_bytes() {
+        let bytes = assemble_text("ldn 8:u32\n.u32 0x01020304\nhalt").unwrap();
+
+        let words = disassemble_bytes(0x400, &bytes[..11]);
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0].address, 0x400);
+        assert_eq!(
+            words[1].to_string(),
+            "0x00000404  01020304  .u32 0x01020304"
+        );
+    }
+}
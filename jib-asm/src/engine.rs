@@ -0,0 +1,575 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use jib::cpu::{FramePacer, Processor, RegisterManager, StopReason};
+use jib::device::TextDisplayDevice;
+
+/// Provides the machine run by an execution engine, being the processor along with any devices
+/// that the host exchanges data with while the processor runs
+pub trait EngineMachine {
+    /// Provides the commands specific to the machine, sent as [`EngineCommand::Machine`]
+    type Command: Send + 'static;
+
+    fn processor(&mut self) -> &mut Processor;
+
+    /// Carries out a command specific to the machine, where clearing `running` stops the
+    /// processor if it was running
+    fn command(&mut self, _cmd: Self::Command, _running: &mut bool) {}
+
+    /// Provides the number of processor cycles run for each frame when paced by
+    /// [`Speed::FramesPerSecond`], which should match the frame period of any display
+    fn frame_cycles(&mut self) -> u64 {
+        TextDisplayDevice::DEFAULT_FRAME_CYCLES as u64
+    }
+
+    /// Exchanges data with host devices, called after each slice of execution and each command
+    fn service(&mut self) {}
+
+    /// Called when execution stops, before the stop is reported to the host, such that the
+    /// machine may inspect the processor at the point it stopped
+    fn stopped(&mut self, _reason: &StopReason) {}
+}
+
+impl EngineMachine for Processor {
+    type Command = ();
+
+    fn processor(&mut self) -> &mut Processor {
+        self
+    }
+}
+
+/// Provides the rate at which the engine runs the processor while started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    /// Runs the processor as fast as the host allows
+    Unlimited,
+    /// Runs the provided number of processor cycles per second of host time
+    CyclesPerSecond(u64),
+    /// Runs the provided number of display frames per second of host time, each running the
+    /// cycles of a single frame of the machine, where frames missed as the host falls behind
+    /// are skipped rather than run in a burst
+    FramesPerSecond(u32),
+}
+
+/// Provides the commands accepted by an execution engine, along with any commands specific to
+/// the machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineCommand<C = ()> {
+    Start,
+    Stop,
+    /// Executes up to the provided number of instructions, stepping past any breakpoint at the
+    /// program counter, which stops the processor if running
+    Step(usize),
+    SetSpeed(Speed),
+    /// Triggers the hardware interrupt with the provided number
+    Irq(u32),
+    /// Requests the current state, provided as [`EngineEvent::State`]
+    QueryState,
+    /// Provides the command to the machine, carried out by [`EngineMachine::command`]
+    Machine(C),
+    Exit,
+}
+
+/// Provides the state of the processor run by an execution engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineState {
+    pub running: bool,
+    pub speed: Speed,
+    pub registers: RegisterManager,
+    pub cycles: u64,
+    /// The number of instructions executed by the engine since it started
+    pub instructions: u64,
+    /// The number of frames skipped as the host fell behind, while paced by frame rate
+    pub frames_skipped: u64,
+}
+
+/// Provides the events sent by an execution engine
+#[derive(Debug, Clone)]
+pub enum EngineEvent {
+    State(EngineState),
+    /// Provides the reason that execution stopped, where a stop requested by the host or the
+    /// end of a step provides [`StopReason::BudgetExhausted`]
+    Stopped(StopReason),
+    /// Provides a message for a command that could not be carried out
+    Error(String),
+    Exited,
+}
+
+/// Runs a machine on a background thread, controlled through commands sent over a channel and
+/// reporting back through events, such that each front-end shares the same execution loop. The
+/// machine is built on the engine thread, as the processor may not be moved between threads.
+/// The thread exits when the engine is dropped
+pub struct Engine<C = ()> {
+    commands: Sender<EngineCommand<C>>,
+    events: Receiver<EngineEvent>,
+    /// Events received while waiting for a state, which are provided before any newer events
+    pending: RefCell<VecDeque<EngineEvent>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<C: Send + 'static> Engine<C> {
+    /// Starts the engine thread with the machine provided by the build function, where any
+    /// build error is reported as an error event before the thread exits
+    pub fn spawn<M, F>(build: F) -> Self
+    where
+        M: EngineMachine<Command = C>,
+        F: FnOnce() -> Result<M, String> + Send + 'static,
+    {
+        let (commands, rx_commands) = mpsc::channel();
+        let (tx_events, events) = mpsc::channel();
+
+        let thread = std::thread::spawn(move || {
+            match build() {
+                Ok(machine) => EngineLoop::new(machine, rx_commands, tx_events.clone()).run(),
+                Err(e) => {
+                    let _ = tx_events.send(EngineEvent::Error(e));
+                }
+            }
+            let _ = tx_events.send(EngineEvent::Exited);
+        });
+
+        Self {
+            commands,
+            events,
+            pending: RefCell::new(VecDeque::new()),
+            thread: Some(thread),
+        }
+    }
+
+    /// Sends the command to the engine, providing false if the engine thread has exited
+    pub fn send(&self, cmd: EngineCommand<C>) -> bool {
+        self.commands.send(cmd).is_ok()
+    }
+
+    /// Provides the next event sent by the engine, if one is available
+    pub fn try_event(&self) -> Result<EngineEvent, TryRecvError> {
+        match self.pending.borrow_mut().pop_front() {
+            Some(event) => Ok(event),
+            None => self.events.try_recv(),
+        }
+    }
+
+    /// Provides the next event sent by the engine, waiting up to the timeout for one to arrive
+    pub fn next_event(&self, timeout: Duration) -> Result<EngineEvent, RecvTimeoutError> {
+        match self.pending.borrow_mut().pop_front() {
+            Some(event) => Ok(event),
+            None => self.events.recv_timeout(timeout),
+        }
+    }
+
+    /// Requests the current state, waiting up to the timeout for it to be provided. Any other
+    /// events received while waiting are kept, and provided by the next calls for events
+    pub fn query_state(&self, timeout: Duration) -> Option<EngineState> {
+        if !self.send(EngineCommand::QueryState) {
+            return None;
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.events.recv_timeout(remaining).ok()? {
+                EngineEvent::State(s) => return Some(s),
+                event => {
+                    let exited = matches!(event, EngineEvent::Exited);
+                    self.pending.borrow_mut().push_back(event);
+                    if exited {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<C> Drop for Engine<C> {
+    fn drop(&mut self) {
+        let _ = self.commands.send(EngineCommand::Exit);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Provides the progress of the current run against the host time that it started, used to
+/// limit the speed of the processor
+enum Pacing {
+    Unlimited,
+    /// The cycles per second, along with the cycles run since the start
+    Cycles(Instant, u64, u64),
+    Frames(Instant, FramePacer),
+}
+
+/// Provides the state of the engine thread
+struct EngineLoop<M: EngineMachine> {
+    machine: M,
+    commands: Receiver<EngineCommand<M::Command>>,
+    events: Sender<EngineEvent>,
+    running: bool,
+    exit: bool,
+    speed: Speed,
+    instructions: u64,
+    pacing: Pacing,
+}
+
+impl<M: EngineMachine> EngineLoop<M> {
+    /// The host time between each check for commands while running or stopped
+    const SLICE_TIME: Duration = Duration::from_millis(10);
+
+    /// The number of instructions executed between each check for commands without a speed limit
+    const SLICE_INSTRUCTIONS: usize = 10_000;
+
+    fn new(
+        machine: M,
+        commands: Receiver<EngineCommand<M::Command>>,
+        events: Sender<EngineEvent>,
+    ) -> Self {
+        Self {
+            machine,
+            commands,
+            events,
+            running: false,
+            exit: false,
+            speed: Speed::Unlimited,
+            instructions: 0,
+            pacing: Pacing::Unlimited,
+        }
+    }
+
+    fn send(&mut self, event: EngineEvent) {
+        // The engine continues until told to exit, even if events are no longer received
+        let _ = self.events.send(event);
+    }
+
+    fn stop(&mut self, reason: StopReason) {
+        self.machine.stopped(&reason);
+        self.send(EngineEvent::Stopped(reason));
+    }
+
+    fn run(mut self) {
+        while !self.exit {
+            let cmd = if self.running {
+                match self.commands.try_recv() {
+                    Ok(cmd) => Some(cmd),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            } else {
+                match self.commands.recv_timeout(Self::SLICE_TIME) {
+                    Ok(cmd) => Some(cmd),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            };
+
+            if let Some(cmd) = cmd {
+                self.handle_command(cmd);
+            } else if self.running {
+                self.run_slice();
+            }
+
+            self.machine.service();
+        }
+    }
+
+    /// Restarts the pacing for the current speed from the current host time
+    fn restart_pacing(&mut self) {
+        let start = Instant::now();
+        self.pacing = match self.speed {
+            Speed::Unlimited => Pacing::Unlimited,
+            Speed::CyclesPerSecond(hz) => Pacing::Cycles(start, hz, 0),
+            Speed::FramesPerSecond(rate) => {
+                Pacing::Frames(start, FramePacer::new(rate, self.machine.frame_cycles()))
+            }
+        };
+    }
+
+    fn handle_command(&mut self, cmd: EngineCommand<M::Command>) {
+        match cmd {
+            EngineCommand::Start => {
+                self.running = true;
+                self.restart_pacing();
+            }
+            EngineCommand::Stop => {
+                if self.running {
+                    self.running = false;
+                    self.stop(StopReason::BudgetExhausted);
+                }
+            }
+            EngineCommand::Step(count) => {
+                self.running = false;
+
+                let cpu = self.machine.processor();
+                let mut summary = cpu.run(count);
+
+                // A breakpoint at the program counter is reported once before it may be passed
+                if matches!(summary.stop_reason, StopReason::Breakpoint(_))
+                    && summary.instructions == 0
+                {
+                    summary = cpu.run(count);
+                }
+
+                self.instructions += summary.instructions as u64;
+                self.stop(summary.stop_reason);
+            }
+            EngineCommand::SetSpeed(speed) => {
+                self.speed = speed;
+                self.restart_pacing();
+            }
+            EngineCommand::Irq(irq) => {
+                match self.machine.processor().trigger_hardware_interrupt(irq) {
+                    Ok(true) => (),
+                    Ok(false) => self.send(EngineEvent::Error(format!("irq {irq} not triggered"))),
+                    Err(e) => self.send(EngineEvent::Error(format!("irq {irq} failed - {e}"))),
+                }
+            }
+            EngineCommand::QueryState => {
+                let cpu = self.machine.processor();
+                let state = EngineState {
+                    running: self.running,
+                    speed: self.speed,
+                    registers: cpu.get_register_state(),
+                    cycles: cpu.cycle_count(),
+                    instructions: self.instructions,
+                    frames_skipped: match &self.pacing {
+                        Pacing::Frames(_, pacer) => pacer.frames_skipped(),
+                        _ => 0,
+                    },
+                };
+                self.send(EngineEvent::State(state));
+            }
+            EngineCommand::Machine(cmd) => {
+                let mut running = self.running;
+                self.machine.command(cmd, &mut running);
+                if self.running && !running {
+                    self.running = false;
+                    self.stop(StopReason::BudgetExhausted);
+                }
+            }
+            EngineCommand::Exit => self.exit = true,
+        }
+    }
+
+    /// Runs the processor for a single slice, sleeping until the next slice is due if limited
+    /// by the speed
+    fn run_slice(&mut self) {
+        let summary = match &mut self.pacing {
+            Pacing::Unlimited => self.machine.processor().run(Self::SLICE_INSTRUCTIONS),
+            Pacing::Cycles(start, hz, run) => {
+                let hz = *hz as u128;
+                let due = (start.elapsed().as_nanos() * hz / 1_000_000_000) as u64;
+
+                // Time lost while the host is busy is not made up, avoiding a burst of cycles
+                let max_slice = (Self::SLICE_TIME.as_nanos() * hz / 1_000_000_000) as u64;
+                let budget = due.saturating_sub(*run).min(max_slice.max(1));
+
+                if budget == 0 {
+                    std::thread::sleep(Self::SLICE_TIME);
+                    return;
+                }
+
+                let summary = self.machine.processor().run_cycles(budget);
+                *run = (*run).max(due.saturating_sub(max_slice)) + summary.cycles;
+                summary
+            }
+            Pacing::Frames(start, pacer) => {
+                let budget = pacer.budget(start.elapsed());
+                if budget == 0 {
+                    let wait = pacer.until_next_frame(start.elapsed());
+                    std::thread::sleep(wait.min(Self::SLICE_TIME));
+                    return;
+                }
+
+                self.machine.processor().run_cycles(budget)
+            }
+        };
+
+        self.instructions += summary.instructions as u64;
+
+        if !matches!(summary.stop_reason, StopReason::BudgetExhausted) {
+            self.running = false;
+            self.stop(summary.stop_reason);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use jib::cpu::Register;
+    use jib::memory::{MemoryImage, ReadWriteSegment};
+
+    use super::*;
+    use crate::assemble_text;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn spawn_program(txt: &'static str) -> Engine {
+        Engine::spawn(move || {
+            let image = MemoryImage::from_flat(assemble_text(txt).map_err(|e| e.to_string())?);
+            let mut cpu = Processor::new();
+            cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x1000))))
+                .map_err(|e| e.to_string())?;
+            cpu.load_image(&image).map_err(|e| e.to_string())?;
+            Ok(cpu)
+        })
+    }
+
+    fn next_stop<C: Send + 'static>(engine: &Engine<C>) -> StopReason {
+        loop {
+            match engine.next_event(TIMEOUT).unwrap() {
+                EngineEvent::Stopped(reason) => return reason,
+                EngineEvent::State(_) => (),
+                e => panic!("unexpected event {e:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_engine_run() {
+        let engine = spawn_program(
+            "\
+.loadloc start
+.org 0x400
+:start
+ldi 6:u16 5
+add 6:u32 6 6
+halt
+",
+        );
+
+        assert!(engine.send(EngineCommand::Start));
+        assert!(matches!(next_stop(&engine), StopReason::Halted));
+
+        let state = engine.query_state(TIMEOUT).unwrap();
+        assert!(!state.running);
+        assert_eq!(state.instructions, 2);
+        assert_eq!(state.registers.get(Register::GeneralPurpose(6)), Ok(10));
+    }
+
+    #[test]
+    fn test_engine_query_keeps_events() {
+        let engine = spawn_program(
+            "\
+.loadloc start
+.org 0x400
+:start
+ldi 6:u16 5
+halt
+",
+        );
+
+        // The stop is sent before the state, and is still provided once the state is received
+        assert!(engine.send(EngineCommand::Step(4)));
+        assert_eq!(engine.query_state(TIMEOUT).unwrap().instructions, 1);
+        assert!(matches!(next_stop(&engine), StopReason::Halted));
+        assert!(engine.try_event().is_err());
+    }
+
+    #[test]
+    fn test_engine_step() {
+        let engine = spawn_program(
+            "\
+.loadloc start
+.org 0x400
+:start
+ldi 6:u16 1
+add 6:u32 6 6
+jmpri -4
+",
+        );
+
+        assert!(engine.send(EngineCommand::Step(2)));
+        assert!(matches!(next_stop(&engine), StopReason::BudgetExhausted));
+
+        let state = engine.query_state(TIMEOUT).unwrap();
+        assert_eq!(state.instructions, 2);
+        assert_eq!(state.registers.get(Register::GeneralPurpose(6)), Ok(2));
+
+        // A limited speed keeps the processor running until stopped by the host
+        engine.send(EngineCommand::SetSpeed(Speed::CyclesPerSecond(100_000)));
+        engine.send(EngineCommand::Start);
+        std::thread::sleep(Duration::from_millis(50));
+
+        let state = engine.query_state(TIMEOUT).unwrap();
+        assert!(state.running);
+        assert_eq!(state.speed, Speed::CyclesPerSecond(100_000));
+        assert!(state.instructions > 2);
+
+        engine.send(EngineCommand::Stop);
+        assert!(matches!(next_stop(&engine), StopReason::BudgetExhausted));
+        assert!(!engine.query_state(TIMEOUT).unwrap().running);
+    }
+
+    #[test]
+    fn test_engine_frame_pacing() {
+        let engine = spawn_program(
+            "\
+.loadloc start
+.org 0x400
+:start
+jmpri 0
+",
+        );
+
+        // The first frame is run as soon as the processor starts, with later frames each due
+        // after the frame period
+        engine.send(EngineCommand::SetSpeed(Speed::FramesPerSecond(10)));
+        engine.send(EngineCommand::Start);
+        std::thread::sleep(Duration::from_millis(50));
+
+        let frame = TextDisplayDevice::DEFAULT_FRAME_CYCLES as u64;
+        let state = engine.query_state(TIMEOUT).unwrap();
+        assert!(state.running);
+        assert_eq!(state.speed, Speed::FramesPerSecond(10));
+        assert!((frame..5 * frame).contains(&state.cycles), "{state:?}");
+        assert_eq!(state.frames_skipped, 0);
+    }
+
+    /// Provides a processor that sets a register from a machine command
+    struct CommandMachine {
+        cpu: Processor,
+    }
+
+    impl EngineMachine for CommandMachine {
+        type Command = u32;
+
+        fn processor(&mut self) -> &mut Processor {
+            &mut self.cpu
+        }
+
+        fn command(&mut self, cmd: u32, running: &mut bool) {
+            self.cpu
+                .set_register(Register::GeneralPurpose(7), cmd)
+                .unwrap();
+            *running = false;
+        }
+    }
+
+    #[test]
+    fn test_engine_machine_command() {
+        let engine = Engine::spawn(|| {
+            let image = MemoryImage::from_flat(
+                assemble_text(".loadloc start\n.org 0x400\n:start\njmpri 0\n")
+                    .map_err(|e| e.to_string())?,
+            );
+            let mut cpu = Processor::new();
+            cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x1000))))
+                .map_err(|e| e.to_string())?;
+            cpu.load_image(&image).map_err(|e| e.to_string())?;
+            Ok(CommandMachine { cpu })
+        });
+
+        // A command clearing the running flag stops the processor
+        engine.send(EngineCommand::Start);
+        engine.send(EngineCommand::Machine(42));
+        assert!(matches!(next_stop(&engine), StopReason::BudgetExhausted));
+
+        let state = engine.query_state(TIMEOUT).unwrap();
+        assert!(!state.running);
+        assert_eq!(state.registers.get(Register::GeneralPurpose(7)), Ok(42));
+    }
+}
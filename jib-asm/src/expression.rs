@@ -0,0 +1,369 @@
+use core::fmt;
+
+/// Provides error conditions for parsing and evaluating constant expressions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpressionError {
+    UnexpectedCharacter(char),
+    UnexpectedEnd,
+    UnbalancedParenthesis,
+    InvalidNumber(String),
+    UnknownSymbol(String),
+    UnknownFunction(String),
+    FunctionArguments(String, usize),
+    DivideByZero,
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedCharacter(c) => write!(f, "Unexpected Character '{c}'"),
+            Self::UnexpectedEnd => write!(f, "Unexpected End of Expression"),
+            Self::UnbalancedParenthesis => write!(f, "Unbalanced Parenthesis"),
+            Self::InvalidNumber(n) => write!(f, "Invalid Number '{n}'"),
+            Self::UnknownSymbol(s) => write!(f, "Unknown Symbol '{s}'"),
+            Self::UnknownFunction(s) => write!(f, "Unknown Function '{s}'"),
+            Self::FunctionArguments(s, n) => {
+                write!(f, "Function '{s}' Expects {n} Arguments")
+            }
+            Self::DivideByZero => write!(f, "Divide by Zero"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ExprToken {
+    Number(i64),
+    Symbol(String),
+    Function(String),
+    Operator(char),
+    Comma,
+    Open,
+    Close,
+}
+
+/// Provides an arithmetic expression on numbers and symbols, given in parentheses, such as
+/// `(buffer_base + 4 * 2)`. Symbols are constant names or labels, resolved when the expression
+/// is evaluated. Supported operators are `+`, `-`, `*`, `/`, and `%`, along with unary negation.
+/// The built-in functions `min(a, b)`, `max(a, b)`, and `abs(a)` are provided, along with
+/// `sin(x, period, amplitude)` and `cos(x, period, amplitude)`, which provide the amplitude
+/// scaled by the sine or cosine of `x` steps of a full turn of `period` steps, rounded to the
+/// nearest integer, such that lookup tables may be generated by the assembler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    tokens: Vec<ExprToken>,
+}
+
+impl Expression {
+    /// Determines whether the argument should be parsed as an expression
+    pub fn is_expression(s: &str) -> bool {
+        s.starts_with('(')
+    }
+
+    /// Parses the expression text, checking that the expression is well-formed
+    pub fn parse(s: &str) -> Result<Self, ExpressionError> {
+        let mut tokens = Vec::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let tok = match c {
+                c if c.is_whitespace() => continue,
+                '(' => ExprToken::Open,
+                ')' => ExprToken::Close,
+                ',' => ExprToken::Comma,
+                '+' | '-' | '*' | '/' | '%' => ExprToken::Operator(c),
+                c if c.is_ascii_alphanumeric() || c == '_' => {
+                    let mut word = c.to_string();
+                    while let Some(n) = chars.next_if(|n| n.is_ascii_alphanumeric() || *n == '_') {
+                        word.push(n);
+                    }
+
+                    if c.is_ascii_digit() {
+                        let val = match word.strip_prefix("0x") {
+                            Some(h) => i64::from_str_radix(h, 16),
+                            None => word.parse(),
+                        };
+                        ExprToken::Number(val.map_err(|_| ExpressionError::InvalidNumber(word))?)
+                    } else if chars.peek() == Some(&'(') {
+                        ExprToken::Function(word)
+                    } else {
+                        ExprToken::Symbol(word)
+                    }
+                }
+                c => return Err(ExpressionError::UnexpectedCharacter(c)),
+            };
+
+            tokens.push(tok);
+        }
+
+        let expr = Self { tokens };
+
+        // Evaluate with placeholder symbol values to check the structure of the expression
+        match expr.evaluate(|_| Some(1)) {
+            Ok(_) | Err(ExpressionError::DivideByZero) => Ok(expr),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Provides the names of the symbols within the expression
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.tokens.iter().filter_map(|t| match t {
+            ExprToken::Symbol(s) => Some(s.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Replaces each symbol with a known value by that value, leaving any other symbols in place
+    pub fn substitute(&mut self, lookup: impl Fn(&str) -> Option<i64>) {
+        for t in self.tokens.iter_mut() {
+            if let ExprToken::Symbol(s) = t {
+                if let Some(val) = lookup(s) {
+                    *t = ExprToken::Number(val);
+                }
+            }
+        }
+    }
+
+    /// Evaluates the expression, using the lookup function to provide the value of each symbol
+    pub fn evaluate(&self, lookup: impl Fn(&str) -> Option<i64>) -> Result<i64, ExpressionError> {
+        let mut parser = Parser {
+            tokens: &self.tokens,
+            pos: 0,
+            lookup: &lookup,
+        };
+
+        let val = parser.expr()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(val),
+            Some(ExprToken::Close) => Err(ExpressionError::UnbalancedParenthesis),
+            Some(_) => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    /// Provides the expression text without whitespace, such that it may be used as a single
+    /// assembler argument
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for t in self.tokens.iter() {
+            match t {
+                ExprToken::Number(n) => write!(f, "{n}")?,
+                ExprToken::Symbol(s) | ExprToken::Function(s) => write!(f, "{s}")?,
+                ExprToken::Operator(c) => write!(f, "{c}")?,
+                ExprToken::Comma => write!(f, ",")?,
+                ExprToken::Open => write!(f, "(")?,
+                ExprToken::Close => write!(f, ")")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Evaluates expression tokens by recursive descent, with multiplication, division, and
+/// remainder taking precedence over addition and subtraction
+struct Parser<'a, F: Fn(&str) -> Option<i64>> {
+    tokens: &'a [ExprToken],
+    pos: usize,
+    lookup: &'a F,
+}
+
+impl<F: Fn(&str) -> Option<i64>> Parser<'_, F> {
+    fn next_operator(&mut self, ops: &[char]) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(ExprToken::Operator(c)) if ops.contains(c) => {
+                self.pos += 1;
+                Some(*c)
+            }
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self) -> Result<i64, ExpressionError> {
+        let mut val = self.term()?;
+        while let Some(op) = self.next_operator(&['+', '-']) {
+            let rhs = self.term()?;
+            val = match op {
+                '+' => val.wrapping_add(rhs),
+                _ => val.wrapping_sub(rhs),
+            };
+        }
+        Ok(val)
+    }
+
+    fn term(&mut self) -> Result<i64, ExpressionError> {
+        let mut val = self.factor()?;
+        while let Some(op) = self.next_operator(&['*', '/', '%']) {
+            let rhs = self.factor()?;
+            val = match op {
+                '*' => val.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err(ExpressionError::DivideByZero),
+                '/' => val.wrapping_div(rhs),
+                _ => val.wrapping_rem(rhs),
+            };
+        }
+        Ok(val)
+    }
+
+    fn factor(&mut self) -> Result<i64, ExpressionError> {
+        let tok = self
+            .tokens
+            .get(self.pos)
+            .ok_or(ExpressionError::UnexpectedEnd)?;
+        self.pos += 1;
+
+        match tok {
+            ExprToken::Number(n) => Ok(*n),
+            ExprToken::Symbol(s) => {
+                (self.lookup)(s).ok_or_else(|| ExpressionError::UnknownSymbol(s.clone()))
+            }
+            ExprToken::Operator('-') => Ok(self.factor()?.wrapping_neg()),
+            ExprToken::Open => {
+                let val = self.expr()?;
+                match self.tokens.get(self.pos) {
+                    Some(ExprToken::Close) => {
+                        self.pos += 1;
+                        Ok(val)
+                    }
+                    _ => Err(ExpressionError::UnbalancedParenthesis),
+                }
+            }
+            ExprToken::Function(name) => {
+                let args = self.arguments()?;
+                call_function(name, &args)
+            }
+            ExprToken::Operator(c) => Err(ExpressionError::UnexpectedCharacter(*c)),
+            ExprToken::Comma => Err(ExpressionError::UnexpectedCharacter(',')),
+            ExprToken::Close => Err(ExpressionError::UnbalancedParenthesis),
+        }
+    }
+
+    /// Provides the comma-separated arguments of a function call, within parentheses
+    fn arguments(&mut self) -> Result<Vec<i64>, ExpressionError> {
+        if self.tokens.get(self.pos) != Some(&ExprToken::Open) {
+            return Err(ExpressionError::UnexpectedEnd);
+        }
+        self.pos += 1;
+
+        let mut args = vec![self.expr()?];
+        loop {
+            match self.tokens.get(self.pos) {
+                Some(ExprToken::Comma) => {
+                    self.pos += 1;
+                    args.push(self.expr()?);
+                }
+                Some(ExprToken::Close) => {
+                    self.pos += 1;
+                    return Ok(args);
+                }
+                _ => return Err(ExpressionError::UnbalancedParenthesis),
+            }
+        }
+    }
+}
+
+/// Evaluates the built-in function with the provided arguments
+fn call_function(name: &str, args: &[i64]) -> Result<i64, ExpressionError> {
+    let expected = match name {
+        "abs" => 1,
+        "min" | "max" => 2,
+        "sin" | "cos" => 3,
+        _ => return Err(ExpressionError::UnknownFunction(name.to_string())),
+    };
+
+    if args.len() != expected {
+        return Err(ExpressionError::FunctionArguments(
+            name.to_string(),
+            expected,
+        ));
+    }
+
+    Ok(match name {
+        "abs" => args[0].wrapping_abs(),
+        "min" => args[0].min(args[1]),
+        "max" => args[0].max(args[1]),
+        _ => {
+            if args[1] == 0 {
+                return Err(ExpressionError::DivideByZero);
+            }
+
+            let angle = core::f64::consts::TAU * args[0] as f64 / args[1] as f64;
+            let ratio = if name == "sin" {
+                angle.sin()
+            } else {
+                angle.cos()
+            };
+            (args[2] as f64 * ratio).round() as i64
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn eval(s: &str) -> Result<i64, ExpressionError> {
+        Expression::parse(s)?.evaluate(|s| (s == "base").then_some(0x100))
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(eval("(base + 4*2)"), Ok(0x108));
+        assert_eq!(eval("((base - 0x10) / 2)"), Ok(0x78));
+        assert_eq!(eval("(-3 * -(2 + 1) % 5)"), Ok(4));
+        assert_eq!(eval("(2 - 3 - 4)"), Ok(-5));
+        assert_eq!(
+            eval("(other + 1)"),
+            Err(ExpressionError::UnknownSymbol("other".into()))
+        );
+        assert_eq!(
+            eval("(1 / (base - base))"),
+            Err(ExpressionError::DivideByZero)
+        );
+        assert_eq!(eval("(1 +)"), Err(ExpressionError::UnbalancedParenthesis));
+        assert_eq!(eval("(1 + 2"), Err(ExpressionError::UnbalancedParenthesis));
+        assert_eq!(eval("(1) 2"), Err(ExpressionError::UnexpectedEnd));
+        assert_eq!(
+            eval("(1 & 2)"),
+            Err(ExpressionError::UnexpectedCharacter('&'))
+        );
+        assert_eq!(
+            eval("(0xzz)"),
+            Err(ExpressionError::InvalidNumber("0xzz".into()))
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(eval("(max(base, 3) + min(-2, abs(-5)))"), Ok(0xFE));
+        assert_eq!(eval("(sin(16, 64, 127))"), Ok(127));
+        assert_eq!(eval("(sin(8, 64, 127))"), Ok(90));
+        assert_eq!(eval("(cos(32, 64, base))"), Ok(-0x100));
+        assert_eq!(eval("(sin(1, 0, 1))"), Err(ExpressionError::DivideByZero));
+        assert_eq!(
+            eval("(tan(1, 2, 3))"),
+            Err(ExpressionError::UnknownFunction("tan".into()))
+        );
+        assert_eq!(
+            eval("(min(1))"),
+            Err(ExpressionError::FunctionArguments("min".into(), 2))
+        );
+        assert_eq!(
+            eval("(min(1, 2)"),
+            Err(ExpressionError::UnbalancedParenthesis)
+        );
+
+        let expr = Expression::parse("(sin(i, 64, 127) + lbl)").unwrap();
+        assert_eq!(expr.symbols().collect::<Vec<_>>(), ["i", "lbl"]);
+        assert_eq!(expr.to_string(), "(sin(i,64,127)+lbl)");
+    }
+
+    #[test]
+    fn test_substitute() {
+        let mut expr = Expression::parse("(lbl + size * 2)").unwrap();
+        expr.substitute(|s| (s == "size").then_some(-4));
+        assert_eq!(expr.to_string(), "(lbl+-4*2)");
+        assert_eq!(expr.symbols().collect::<Vec<_>>(), ["lbl"]);
+
+        let expr = Expression::parse(&expr.to_string()).unwrap();
+        assert_eq!(expr.evaluate(|_| Some(10)), Ok(2));
+    }
+}
@@ -0,0 +1,208 @@
+use core::fmt;
+use std::collections::BTreeMap;
+
+use jib::cpu::{DecodedInstruction, Processor, Profile};
+
+use crate::disassemble::disassemble;
+
+/// Provides a pair of instructions that could be fused into a single superinstruction, along
+/// with the number of times the pair was executed in sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FusionCandidate {
+    pub first: String,
+    pub second: String,
+    pub count: u64,
+}
+
+impl FusionCandidate {
+    /// Provides the estimated number of cycles saved had the pair been fused
+    pub fn saved_cycles(&self) -> u64 {
+        self.count * FusionReport::CYCLES_SAVED_PER_FUSION
+    }
+}
+
+/// Provides an experimental report of the opcodes executed by a profiled program, along with the
+/// pairs of adjacent instructions most often executed in sequence, as candidates for fused
+/// superinstructions. Pairs are only counted where the second instruction directly follows the
+/// first in memory and was executed without a jump or interrupt in between. Instructions are
+/// named by their mnemonic, such that each pair covers every operand and data type
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FusionReport {
+    /// The executions and cycles of each mnemonic, from the mnemonic with the most cycles
+    pub opcodes: Vec<(String, u64, u64)>,
+    /// The candidate pairs, from the most executed pair
+    pub candidates: Vec<FusionCandidate>,
+    pub total_cycles: u64,
+    pub limit: usize,
+}
+
+impl FusionReport {
+    /// Defines the cycles assumed to be saved by each execution of a fused pair, being the
+    /// issue cycle of the second instruction
+    pub const CYCLES_SAVED_PER_FUSION: u64 = 1;
+
+    /// Builds the report from the profile, reading the instruction at each executed address
+    /// from processor memory. Each list is limited to the provided number of lines when printed
+    pub fn new(profile: &Profile, cpu: &Processor, limit: usize) -> Self {
+        let word = |addr: u32| cpu.memory_inspect_u32(addr).ok();
+        let mnemonic = |w: u32| {
+            let text = disassemble(w);
+            let name = text.split_whitespace().next().unwrap_or_default();
+            (!name.starts_with('.')).then(|| name.to_string())
+        };
+
+        let mut opcodes = BTreeMap::<String, (u64, u64)>::new();
+        for (addr, count) in profile.executions.iter() {
+            if let Some(name) = word(*addr).and_then(mnemonic) {
+                let entry = opcodes.entry(name).or_default();
+                entry.0 += count;
+                entry.1 += profile.cycles.get(addr).copied().unwrap_or(0);
+            }
+        }
+
+        let mut pairs = BTreeMap::<(String, String), u64>::new();
+        for ((from, to), count) in profile.transitions.iter() {
+            let (Some(a), Some(b)) = (word(*from), word(*to)) else {
+                continue;
+            };
+
+            // The word following a load-next instruction is data, rather than an instruction
+            let size = DecodedInstruction::decode(a.into())
+                .map_or(Processor::BYTES_PER_WORD, |inst| inst.size());
+
+            if from.checked_add(size) != Some(*to) {
+                continue;
+            }
+
+            if let (Some(a), Some(b)) = (mnemonic(a), mnemonic(b)) {
+                *pairs.entry((a, b)).or_default() += count;
+            }
+        }
+
+        let mut opcodes = opcodes
+            .into_iter()
+            .map(|(name, (count, cycles))| (name, count, cycles))
+            .collect::<Vec<_>>();
+        opcodes.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+
+        let mut candidates = pairs
+            .into_iter()
+            .map(|((first, second), count)| FusionCandidate {
+                first,
+                second,
+                count,
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(a.first.cmp(&b.first))
+                .then(a.second.cmp(&b.second))
+        });
+
+        Self {
+            opcodes,
+            candidates,
+            total_cycles: profile.total_cycles(),
+            limit,
+        }
+    }
+
+    fn percent(&self, part: u64) -> f64 {
+        if self.total_cycles == 0 {
+            0.0
+        } else {
+            part as f64 * 100.0 / self.total_cycles as f64
+        }
+    }
+}
+
+impl fmt::Display for FusionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "opcodes:")?;
+        for (name, count, cycles) in self.opcodes.iter().take(self.limit) {
+            writeln!(
+                f,
+                "  {name:<17} {count:>12} {cycles:>12} {:>6.2}%",
+                self.percent(*cycles)
+            )?;
+        }
+
+        writeln!(
+            f,
+            "candidates, saving {} cycle per fused execution:",
+            Self::CYCLES_SAVED_PER_FUSION
+        )?;
+        for c in self.candidates.iter().take(self.limit) {
+            let saved = c.saved_cycles();
+            writeln!(
+                f,
+                "  {:<17} {:>12} {saved:>12} {:>6.2}%",
+                format!("{}+{}", c.first, c.second),
+                c.count,
+                self.percent(saved)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    use jib::memory::ReadWriteSegment;
+
+    use super::*;
+    use crate::{assemble_object, object::link_image, preprocess::preprocess_text};
+
+    #[test]
+    fn test_fusion_report() {
+        let txt = "\
+.loadloc start
+.org 0x400
+:start
+ldn 6:u32
+.u32 3
+:loop
+sub 6:u32 6 1
+tnz 6
+jmpri -8
+halt
+";
+        let obj = assemble_object(&preprocess_text(txt).unwrap()).unwrap();
+        let image = link_image(&[obj], &HashMap::new()).unwrap();
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x1000))))
+            .unwrap();
+        cpu.load_image(&image.image).unwrap();
+        cpu.start_profile();
+        while !cpu.halted() {
+            cpu.step().unwrap();
+        }
+
+        let profile = cpu.profile().unwrap();
+        let report = FusionReport::new(&profile, &cpu, 3);
+
+        assert_eq!(report.opcodes[0].0, "sub");
+        assert_eq!(report.opcodes[0].1, 3);
+
+        let pair = |a: &str, b: &str| {
+            report
+                .candidates
+                .iter()
+                .find(|c| c.first == a && c.second == b)
+                .map(|c| c.count)
+        };
+        assert_eq!(pair("sub", "tnz"), Some(3));
+        assert_eq!(pair("tnz", "jmpri"), Some(2));
+        assert_eq!(pair("ldn", "sub"), Some(1));
+        assert_eq!(pair("jmpri", "sub"), None);
+        assert_eq!(report.candidates[0].saved_cycles(), 3);
+
+        let text = report.to_string();
+        assert!(text.contains("  sub+tnz "));
+        assert!(text.contains("candidates, saving 1 cycle per fused execution:"));
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt::Write;
+
+/// Defines the number of data bytes provided in each record
+const RECORD_SIZE: usize = 16;
+
+/// Defines the maximum number of name bytes provided in the S-record header
+const MAX_SREC_HEADER: usize = 64;
+
+/// Provides the wrapping sum of the bytes, from which the record checksums are calculated
+fn byte_sum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b))
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02X}");
+        s
+    })
+}
+
+fn intel_hex_record(record_type: u8, offset: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend(offset.to_be_bytes());
+    bytes.push(record_type);
+    bytes.extend(data);
+
+    let checksum = byte_sum(&bytes).wrapping_neg();
+    format!(":{}{checksum:02X}\n", hex_bytes(&bytes))
+}
+
+/// Provides the Intel HEX representation of the image, loaded at the provided base address.
+/// Extended linear address records are provided whenever the upper 16 bits of the address change
+pub fn to_intel_hex(base: u32, data: &[u8]) -> String {
+    let mut out = String::new();
+    let mut upper = None;
+
+    for (i, chunk) in data.chunks(RECORD_SIZE).enumerate() {
+        let addr = base.wrapping_add((i * RECORD_SIZE) as u32);
+
+        // Records may not wrap past the 16-bit offset, so split the chunk if required
+        let split = (0x10000 - (addr & 0xFFFF) as usize).min(chunk.len());
+        for (addr, part) in [
+            (addr, &chunk[..split]),
+            (addr.wrapping_add(split as u32), &chunk[split..]),
+        ] {
+            if part.is_empty() {
+                continue;
+            }
+
+            let addr_upper = (addr >> 16) as u16;
+            if upper != Some(addr_upper) {
+                out.push_str(&intel_hex_record(0x04, 0, &addr_upper.to_be_bytes()));
+                upper = Some(addr_upper);
+            }
+
+            out.push_str(&intel_hex_record(0x00, addr as u16, part));
+        }
+    }
+
+    out.push_str(&intel_hex_record(0x01, 0, &[]));
+    out
+}
+
+fn srec_record(record_type: u8, address: &[u8], data: &[u8]) -> String {
+    let mut bytes = vec![(address.len() + data.len() + 1) as u8];
+    bytes.extend(address);
+    bytes.extend(data);
+
+    let checksum = !byte_sum(&bytes);
+    format!("S{record_type}{}{checksum:02X}\n", hex_bytes(&bytes))
+}
+
+/// Provides the Motorola S-record representation of the image, loaded at the provided base
+/// address, using 32-bit address records. The header record contains the provided name, and
+/// the termination record provides the base address as the start address. Names longer than
+/// the maximum header size are truncated
+pub fn to_srec(name: &str, base: u32, data: &[u8]) -> String {
+    let name = &name.as_bytes()[..name.len().min(MAX_SREC_HEADER)];
+    let mut out = srec_record(0, &[0, 0], name);
+
+    let mut count = 0u32;
+    for (i, chunk) in data.chunks(RECORD_SIZE).enumerate() {
+        let addr = base.wrapping_add((i * RECORD_SIZE) as u32);
+        out.push_str(&srec_record(3, &addr.to_be_bytes(), chunk));
+        count += 1;
+    }
+
+    if let Ok(count) = u16::try_from(count) {
+        out.push_str(&srec_record(5, &count.to_be_bytes(), &[]));
+    } else if count <= 0xFF_FFFF {
+        out.push_str(&srec_record(6, &count.to_be_bytes()[1..], &[]));
+    }
+
+    out.push_str(&srec_record(7, &base.to_be_bytes(), &[]));
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_intel_hex() {
+        let data = (0..20).collect::<Vec<u8>>();
+        assert_eq!(
+            to_intel_hex(0, &data),
+            ":020000040000FA\n\
+             :10000000000102030405060708090A0B0C0D0E0F78\n\
+             :0400100010111213A6\n\
+             :00000001FF\n"
+        );
+
+        let hex = to_intel_hex(0xFFF8, &data[..16]);
+        assert_eq!(
+            hex,
+            ":020000040000FA\n\
+             :08FFF8000001020304050607E5\n\
+             :020000040001F9\n\
+             :0800000008090A0B0C0D0E0F9C\n\
+             :00000001FF\n"
+        );
+    }
+
+    #[test]
+    fn test_srec() {
+        assert_eq!(
+            to_srec("HDR", 0x1000, &[0x01, 0x02, 0x03]),
+            "S00600004844521B\n\
+             S30800001000010203E1\n\
+             S5030001FB\n\
+             S70500001000EA\n"
+        );
+    }
+}
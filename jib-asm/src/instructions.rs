@@ -2,19 +2,23 @@ use core::fmt;
 use std::fmt::Debug;
 
 use crate::{
-    argument::{ArgumentError, ArgumentRegister, ArgumentType},
-    immediate::{ImmediateError, parse_imm_i16},
+    argument::{ArgumentError, ArgumentRegister, ArgumentSource, ArgumentType},
+    immediate::{parse_imm_i16, parse_imm_u16, parse_imm_u8, ImmediateError},
 };
-use jib::cpu::{Opcode, Processor};
+use jib::cpu::{InstructionDefinition, Opcode, Processor};
 
 const INST_SIZE: usize = 4;
 
+/// Provides the mnemonic of the opcode from the instruction table shared with the processor
+fn mnemonic(op: Opcode) -> &'static str {
+    InstructionDefinition::lookup(op).map_or("??", |d| d.mnemonic)
+}
+
 #[derive(Debug, Clone)]
 pub enum InstructionError {
     CountMismatch(usize, usize),
     Immediate(ImmediateError),
     Argument(ArgumentError),
-    OpcodeMismatch(Opcode, u8),
 }
 
 impl fmt::Display for InstructionError {
@@ -23,9 +27,6 @@ impl fmt::Display for InstructionError {
             Self::CountMismatch(num, expected) => write!(f, "Found {num}, Expected {expected}"),
             Self::Immediate(i) => write!(f, "Immediate Error => {i}"),
             Self::Argument(a) => write!(f, "Argument Error => {a}"),
-            Self::OpcodeMismatch(op, b) => {
-                write!(f, "Opcode Mismatch - {op} does not match provided {b}")
-            }
         }
     }
 }
@@ -62,11 +63,7 @@ macro_rules! InstNoArg {
             const NUM_ARGS: usize = 0;
 
             pub fn name() -> String {
-                stringify!($op_name)
-                    .to_lowercase()
-                    .strip_prefix("op")
-                    .unwrap()
-                    .into()
+                mnemonic(Self::OP).into()
             }
         }
 
@@ -97,18 +94,6 @@ macro_rules! InstNoArg {
                 }
             }
         }
-
-        impl TryFrom<[u8; 4]> for $op_name {
-            type Error = InstructionError;
-
-            fn try_from(bytes: [u8; 4]) -> Result<Self, Self::Error> {
-                if bytes[0] != Self::OP.to_byte() {
-                    Err(InstructionError::OpcodeMismatch(Self::OP, bytes[0]))
-                } else {
-                    Ok(Self)
-                }
-            }
-        }
     };
 }
 
@@ -128,11 +113,7 @@ macro_rules! InstSingleArg {
             }
 
             pub fn name() -> String {
-                stringify!($op_name)
-                    .to_lowercase()
-                    .strip_prefix("op")
-                    .unwrap()
-                    .into()
+                mnemonic(Self::OP).into()
             }
         }
 
@@ -163,20 +144,6 @@ macro_rules! InstSingleArg {
                 }
             }
         }
-
-        impl TryFrom<[u8; 4]> for $op_name {
-            type Error = InstructionError;
-
-            fn try_from(bytes: [u8; 4]) -> Result<Self, Self::Error> {
-                if bytes[0] != Self::OP.to_byte() {
-                    Err(InstructionError::OpcodeMismatch(Self::OP, bytes[0]))
-                } else {
-                    Ok(Self {
-                        arg: ArgumentRegister::try_from(bytes[1])?,
-                    })
-                }
-            }
-        }
     };
 }
 
@@ -196,11 +163,7 @@ macro_rules! InstSingleArgDataType {
             }
 
             pub fn name() -> String {
-                stringify!($op_name)
-                    .to_lowercase()
-                    .strip_prefix("op")
-                    .unwrap()
-                    .into()
+                mnemonic(Self::OP).into()
             }
         }
 
@@ -231,20 +194,6 @@ macro_rules! InstSingleArgDataType {
                 }
             }
         }
-
-        impl TryFrom<[u8; 4]> for $op_name {
-            type Error = InstructionError;
-
-            fn try_from(bytes: [u8; 4]) -> Result<Self, Self::Error> {
-                if bytes[0] != Self::OP.to_byte() {
-                    Err(InstructionError::OpcodeMismatch(Self::OP, bytes[0]))
-                } else {
-                    Ok(Self {
-                        arg: ArgumentType::try_from(bytes[1])?,
-                    })
-                }
-            }
-        }
     };
 }
 
@@ -264,11 +213,7 @@ macro_rules! InstImmediateArg {
             }
 
             pub fn name() -> String {
-                stringify!($op_name)
-                    .to_lowercase()
-                    .strip_prefix("op")
-                    .unwrap()
-                    .into()
+                mnemonic(Self::OP).into()
             }
         }
 
@@ -301,20 +246,6 @@ macro_rules! InstImmediateArg {
                 }
             }
         }
-
-        impl TryFrom<[u8; 4]> for $op_name {
-            type Error = InstructionError;
-
-            fn try_from(bytes: [u8; 4]) -> Result<Self, Self::Error> {
-                if bytes[0] != Self::OP.to_byte() {
-                    Err(InstructionError::OpcodeMismatch(Self::OP, bytes[0]))
-                } else {
-                    Ok(Self {
-                        imm: u16::from_be_bytes([bytes[2], bytes[3]]),
-                    })
-                }
-            }
-        }
     };
 }
 
@@ -335,11 +266,7 @@ macro_rules! InstSingleArgImm {
             }
 
             pub fn name() -> String {
-                stringify!($op_name)
-                    .to_lowercase()
-                    .strip_prefix("op")
-                    .unwrap()
-                    .into()
+                mnemonic(Self::OP).into()
             }
         }
 
@@ -368,26 +295,14 @@ macro_rules! InstSingleArgImm {
                     Err(InstructionError::CountMismatch(args.len(), Self::NUM_ARGS))
                 } else {
                     let a0 = ArgumentType::try_from(args[0].as_ref())?;
-                    let imm = parse_imm_i16(&args[1])? as u16;
+                    let imm = match parse_imm_i16(&args[1]) {
+                        Ok(v) => v as u16,
+                        Err(_) => parse_imm_u16(&args[1])?,
+                    };
                     Ok(Self::new(a0, imm))
                 }
             }
         }
-
-        impl TryFrom<[u8; 4]> for $op_name {
-            type Error = InstructionError;
-
-            fn try_from(bytes: [u8; 4]) -> Result<Self, Self::Error> {
-                if bytes[0] != Self::OP.to_byte() {
-                    Err(InstructionError::OpcodeMismatch(Self::OP, bytes[0]))
-                } else {
-                    Ok(Self {
-                        arg: ArgumentType::try_from(bytes[1])?,
-                        imm: u16::from_be_bytes([bytes[2], bytes[3]]),
-                    })
-                }
-            }
-        }
     };
 }
 
@@ -408,11 +323,7 @@ macro_rules! InstDoubleArg {
             }
 
             pub fn name() -> String {
-                stringify!($op_name)
-                    .to_lowercase()
-                    .strip_prefix("op")
-                    .unwrap()
-                    .into()
+                mnemonic(Self::OP).into()
             }
         }
 
@@ -450,46 +361,30 @@ macro_rules! InstDoubleArg {
                 }
             }
         }
-
-        impl TryFrom<[u8; 4]> for $op_name {
-            type Error = InstructionError;
-
-            fn try_from(bytes: [u8; 4]) -> Result<Self, Self::Error> {
-                if bytes[0] != Self::OP.to_byte() {
-                    Err(InstructionError::OpcodeMismatch(Self::OP, bytes[0]))
-                } else {
-                    Ok(Self {
-                        arg0: ArgumentRegister::try_from(bytes[1])?,
-                        arg1: ArgumentRegister::try_from(bytes[2])?,
-                    })
-                }
-            }
-        }
     };
 }
 
 macro_rules! InstDoubleArgType {
     ($op_name:ident, $opcode:expr) => {
+        InstDoubleArgType!($op_name, $opcode, ArgumentRegister);
+    };
+    ($op_name:ident, $opcode:expr, $arg_src:ident) => {
         #[derive(Debug, Copy, Clone, Eq, PartialEq)]
         pub struct $op_name {
             arg0: ArgumentType,
-            arg1: ArgumentRegister,
+            arg1: $arg_src,
         }
 
         impl $op_name {
             pub const OP: Opcode = $opcode;
             const NUM_ARGS: usize = 2;
 
-            pub fn new(arg0: ArgumentType, arg1: ArgumentRegister) -> Self {
+            pub fn new(arg0: ArgumentType, arg1: $arg_src) -> Self {
                 Self { arg0, arg1 }
             }
 
             pub fn name() -> String {
-                stringify!($op_name)
-                    .to_lowercase()
-                    .strip_prefix("op")
-                    .unwrap()
-                    .into()
+                mnemonic(Self::OP).into()
             }
         }
 
@@ -522,26 +417,11 @@ macro_rules! InstDoubleArgType {
                     Err(InstructionError::CountMismatch(args.len(), Self::NUM_ARGS))
                 } else {
                     let a0 = ArgumentType::try_from(args[0].as_ref())?;
-                    let a1 = ArgumentRegister::try_from(args[1].as_ref())?;
+                    let a1 = $arg_src::try_from(args[1].as_ref())?;
                     Ok(Self::new(a0, a1))
                 }
             }
         }
-
-        impl TryFrom<[u8; 4]> for $op_name {
-            type Error = InstructionError;
-
-            fn try_from(bytes: [u8; 4]) -> Result<Self, Self::Error> {
-                if bytes[0] != Self::OP.to_byte() {
-                    Err(InstructionError::OpcodeMismatch(Self::OP, bytes[0]))
-                } else {
-                    Ok(Self {
-                        arg0: ArgumentType::try_from(bytes[1])?,
-                        arg1: ArgumentRegister::try_from(bytes[2])?,
-                    })
-                }
-            }
-        }
     };
 }
 
@@ -562,11 +442,7 @@ macro_rules! InstDoubleArgDoubleType {
             }
 
             pub fn name() -> String {
-                stringify!($op_name)
-                    .to_lowercase()
-                    .strip_prefix("op")
-                    .unwrap()
-                    .into()
+                mnemonic(Self::OP).into()
             }
         }
 
@@ -604,47 +480,31 @@ macro_rules! InstDoubleArgDoubleType {
                 }
             }
         }
-
-        impl TryFrom<[u8; 4]> for $op_name {
-            type Error = InstructionError;
-
-            fn try_from(bytes: [u8; 4]) -> Result<Self, Self::Error> {
-                if bytes[0] != Self::OP.to_byte() {
-                    Err(InstructionError::OpcodeMismatch(Self::OP, bytes[0]))
-                } else {
-                    Ok(Self {
-                        arg0: ArgumentType::try_from(bytes[1])?,
-                        arg1: ArgumentType::try_from(bytes[2])?,
-                    })
-                }
-            }
-        }
     };
 }
 
 macro_rules! InstArith {
     ($op_name:ident, $opcode:expr) => {
+        InstArith!($op_name, $opcode, ArgumentSource);
+    };
+    ($op_name:ident, $opcode:expr, $arg_src:ident) => {
         #[derive(Debug, Copy, Clone, Eq, PartialEq)]
         pub struct $op_name {
             arg0: ArgumentType,
-            arg1: ArgumentRegister,
-            arg2: ArgumentRegister,
+            arg1: $arg_src,
+            arg2: $arg_src,
         }
 
         impl $op_name {
             pub const OP: Opcode = $opcode;
             const NUM_ARGS: usize = 3;
 
-            pub fn new(arg0: ArgumentType, arg1: ArgumentRegister, arg2: ArgumentRegister) -> Self {
+            pub fn new(arg0: ArgumentType, arg1: $arg_src, arg2: $arg_src) -> Self {
                 Self { arg0, arg1, arg2 }
             }
 
             pub fn name() -> String {
-                stringify!($op_name)
-                    .to_lowercase()
-                    .strip_prefix("op")
-                    .unwrap()
-                    .into()
+                mnemonic(Self::OP).into()
             }
         }
 
@@ -684,28 +544,12 @@ macro_rules! InstArith {
                     Err(InstructionError::CountMismatch(args.len(), Self::NUM_ARGS))
                 } else {
                     let a0 = ArgumentType::try_from(args[0].as_ref())?;
-                    let a1 = ArgumentRegister::try_from(args[1].as_ref())?;
-                    let a2 = ArgumentRegister::try_from(args[2].as_ref())?;
+                    let a1 = $arg_src::try_from(args[1].as_ref())?;
+                    let a2 = $arg_src::try_from(args[2].as_ref())?;
                     Ok(Self::new(a0, a1, a2))
                 }
             }
         }
-
-        impl TryFrom<[u8; 4]> for $op_name {
-            type Error = InstructionError;
-
-            fn try_from(bytes: [u8; 4]) -> Result<Self, Self::Error> {
-                if bytes[0] != Self::OP.to_byte() {
-                    Err(InstructionError::OpcodeMismatch(Self::OP, bytes[0]))
-                } else {
-                    Ok(Self {
-                        arg0: ArgumentType::try_from(bytes[1])?,
-                        arg1: ArgumentRegister::try_from(bytes[2])?,
-                        arg2: ArgumentRegister::try_from(bytes[3])?,
-                    })
-                }
-            }
-        }
     };
 }
 
@@ -713,13 +557,16 @@ InstNoArg!(OpNoop, Processor::OP_NOOP);
 InstNoArg!(OpReset, Processor::OP_RESET);
 InstNoArg!(OpRetInt, Processor::OP_INTERRUPT_RETURN);
 InstNoArg!(OpRet, Processor::OP_RETURN);
+InstNoArg!(OpRetf, Processor::OP_RETURN_FRAME);
 InstNoArg!(OpHalt, Processor::OP_HALT);
 InstNoArg!(OpInton, Processor::OP_INTERRUPT_ENABLE);
 InstNoArg!(OpIntoff, Processor::OP_INTERRUPT_DISABLE);
 
 InstImmediateArg!(OpInt, Processor::OP_INTERRUPT);
+InstImmediateArg!(OpSys, Processor::OP_SYSCALL);
 InstSingleArg!(OpIntr, Processor::OP_INTERRUPT_REGISTER);
 InstSingleArg!(OpCall, Processor::OP_CALL);
+InstSingleArg!(OpCallf, Processor::OP_CALL_FRAME);
 InstSingleArg!(OpPush, Processor::OP_PUSH);
 InstNoArg!(OpPop, Processor::OP_POP);
 InstSingleArg!(OpPopr, Processor::OP_POP_REG);
@@ -731,12 +578,21 @@ InstSingleArg!(OpTz, Processor::OP_TEST_ZERO);
 InstSingleArg!(OpTnz, Processor::OP_TEST_NOT_ZERO);
 
 InstImmediateArg!(OpJmpri, Processor::OP_JUMP_REL_IMM);
+InstImmediateArg!(OpJc, Processor::OP_JUMP_CARRY);
+InstImmediateArg!(OpJnc, Processor::OP_JUMP_NOT_CARRY);
+InstImmediateArg!(OpJo, Processor::OP_JUMP_OVERFLOW);
+InstImmediateArg!(OpJno, Processor::OP_JUMP_NOT_OVERFLOW);
+InstImmediateArg!(OpJz, Processor::OP_JUMP_ZERO);
+InstImmediateArg!(OpJnz, Processor::OP_JUMP_NOT_ZERO);
+InstImmediateArg!(OpJn, Processor::OP_JUMP_NEGATIVE);
+InstImmediateArg!(OpJnn, Processor::OP_JUMP_NOT_NEGATIVE);
 InstSingleArgImm!(OpLdi, Processor::OP_LOAD_IMM);
 InstSingleArgImm!(OpLdri, Processor::OP_LOAD_IMM_REL);
 
 InstDoubleArg!(OpNot, Processor::OP_NOT);
 InstDoubleArg!(OpBool, Processor::OP_BOOL);
 InstDoubleArg!(OpCopy, Processor::OP_COPY);
+InstDoubleArg!(OpCpuid, Processor::OP_CPUID);
 
 InstDoubleArgType!(OpSav, Processor::OP_SAVE);
 InstDoubleArgType!(OpSavr, Processor::OP_SAVE_REL);
@@ -744,19 +600,24 @@ InstDoubleArgType!(OpLd, Processor::OP_LOAD);
 InstDoubleArgType!(OpLdr, Processor::OP_LOAD_REL);
 
 InstDoubleArgDoubleType!(OpConv, Processor::OP_CONV);
+InstArith!(OpBcpy, Processor::OP_BLOCK_COPY, ArgumentRegister);
+InstArith!(OpBset, Processor::OP_BLOCK_SET, ArgumentRegister);
 
 InstArith!(OpAdd, Processor::OP_ADD);
 InstArith!(OpSub, Processor::OP_SUB);
 InstArith!(OpMul, Processor::OP_MUL);
 InstArith!(OpDiv, Processor::OP_DIV);
 InstArith!(OpRem, Processor::OP_REM);
-InstDoubleArgType!(OpNeg, Processor::OP_NEG);
+InstDoubleArgType!(OpNeg, Processor::OP_NEG, ArgumentSource);
+InstArith!(OpMac, Processor::OP_MAC);
+InstArith!(OpMulw, Processor::OP_MULW);
+InstArith!(OpDivrem, Processor::OP_DIVREM);
 InstArith!(OpBand, Processor::OP_BAND);
 InstArith!(OpBor, Processor::OP_BOR);
 InstArith!(OpBxor, Processor::OP_BXOR);
 InstArith!(OpBshl, Processor::OP_BSHL);
 InstArith!(OpBshr, Processor::OP_BSHR);
-InstDoubleArgType!(OpBnot, Processor::OP_BNOT);
+InstDoubleArgType!(OpBnot, Processor::OP_BNOT, ArgumentSource);
 
 InstArith!(OpTeq, Processor::OP_EQ);
 InstArith!(OpTneq, Processor::OP_NEQ);
@@ -764,3 +625,55 @@ InstArith!(OpTg, Processor::OP_GREATER);
 InstArith!(OpTge, Processor::OP_GREATER_EQ);
 InstArith!(OpTl, Processor::OP_LESS);
 InstArith!(OpTle, Processor::OP_LESS_EQ);
+
+/// Provides the escape instruction, which is executed by the host-side extension handler
+/// registered for the extension identifier
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct OpEsc {
+    ext_id: u8,
+    imm: u16,
+}
+
+impl OpEsc {
+    pub const OP: Opcode = Processor::OP_ESCAPE;
+    const NUM_ARGS: usize = 2;
+
+    pub fn new(ext_id: u8, imm: u16) -> Self {
+        Self { ext_id, imm }
+    }
+
+    pub fn name() -> String {
+        mnemonic(Self::OP).into()
+    }
+}
+
+impl Instruction for OpEsc {
+    fn to_bytes(&self) -> [u8; INST_SIZE] {
+        let imm = self.imm.to_be_bytes();
+        [Self::OP.to_byte(), self.ext_id, imm[0], imm[1]]
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Instruction> {
+        Box::new(*self)
+    }
+}
+
+impl fmt::Display for OpEsc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} 0x{:04x}", Self::name(), self.ext_id, self.imm)
+    }
+}
+
+impl TryFrom<Vec<String>> for OpEsc {
+    type Error = InstructionError;
+
+    fn try_from(args: Vec<String>) -> Result<Self, Self::Error> {
+        if args.len() != Self::NUM_ARGS {
+            Err(InstructionError::CountMismatch(args.len(), Self::NUM_ARGS))
+        } else {
+            let ext_id = parse_imm_u8(&args[0])?;
+            let imm = parse_imm_u16(&args[1])?;
+            Ok(Self::new(ext_id, imm))
+        }
+    }
+}
@@ -1,19 +1,48 @@
+//! Provides the assembler and linker for the jib processor, along with the tools built on
+//! them. The processor crate is re-exported as [`jib`], such that a host assembling and running
+//! programs depends on the same implementation of the instruction set as the assembler.
+//! [`assemble_text`] is the stable entry point, assembling source text into a program loaded
+//! at address zero
 pub mod argument;
+pub mod config;
+pub mod coverage;
+pub mod disassemble;
+pub mod engine;
+pub mod expression;
+pub mod fusion;
+pub mod image_format;
 mod immediate;
 pub mod instructions;
+pub mod machine;
+pub mod object;
+pub mod preprocess;
+pub mod profile;
+pub mod project;
+pub mod relocate;
+pub mod state_diff;
+pub mod testing;
+pub mod trace;
+pub mod unwind;
 
 use core::fmt;
-use std::{collections::HashMap, rc::Rc};
+use std::{collections::HashMap, rc::Rc, sync::Arc};
+
+pub use jib;
 
 use instructions::{
-    Instruction, InstructionError, OpAdd, OpBand, OpBnot, OpBool, OpBor, OpBshl, OpBshr, OpBxor,
-    OpCall, OpConv, OpCopy, OpDiv, OpHalt, OpInt, OpIntoff, OpInton, OpIntr, OpJmp, OpJmpr,
-    OpJmpri, OpLd, OpLdi, OpLdn, OpLdr, OpLdri, OpMul, OpNeg, OpNoop, OpNot, OpPop, OpPopr, OpPush,
-    OpRem, OpReset, OpRet, OpRetInt, OpSav, OpSavr, OpSub, OpTeq, OpTg, OpTge, OpTl, OpTle, OpTneq,
-    OpTnz, OpTz,
+    Instruction, InstructionError, OpAdd, OpBand, OpBcpy, OpBnot, OpBool, OpBor, OpBset, OpBshl,
+    OpBshr, OpBxor, OpCall, OpCallf, OpConv, OpCopy, OpCpuid, OpDiv, OpDivrem, OpEsc, OpHalt,
+    OpInt, OpIntoff, OpInton, OpIntr, OpJc, OpJmp, OpJmpr, OpJmpri, OpJn, OpJnc, OpJnn, OpJno,
+    OpJnz, OpJo, OpJz, OpLd, OpLdi, OpLdn, OpLdr, OpLdri, OpMac, OpMul, OpMulw, OpNeg, OpNoop,
+    OpNot, OpPop, OpPopr, OpPush, OpRem, OpReset, OpRet, OpRetInt, OpRetf, OpSav, OpSavr, OpSub,
+    OpSys, OpTeq, OpTg, OpTge, OpTl, OpTle, OpTneq, OpTnz, OpTz,
 };
 
-use jib::cpu::{Opcode, Processor, ProcessorError};
+use jib::cpu::{DecodedInstruction, Opcode, Processor, ProcessorError};
+
+use expression::{Expression, ExpressionError};
+use object::{ObjectFile, VectorTarget};
+use preprocess::SourceLine;
 
 use immediate::{
     parse_imm_i16, parse_imm_i32, parse_imm_i8, parse_imm_u16, parse_imm_u32, parse_imm_u8,
@@ -27,12 +56,24 @@ pub enum AssemblerError {
     Instruction(InstructionError),
     ArgumentCountMismatch(usize, usize),
     CannotBackupAddress(u32),
+    InvalidAlignment(u32),
     InvalidLabel(String),
     Immediate(ImmediateError),
     BadLabel(String),
     DuplicateLabel(String),
     Character(jib::text::CharacterError),
     AddressTaken(u32),
+    InvalidVector(String),
+    DuplicateVector(VectorTarget),
+    InvalidObject(String),
+    Expression(ExpressionError),
+    DuplicateConstant(String),
+    UnterminatedRepeat,
+    UnmatchedEndRepeat,
+    InvalidRepeatCount(i64),
+    InvalidInclude(String),
+    IncludeNotFound(String),
+    IncludeCycle(String),
     Parser(ParseError),
     Processor(ProcessorError),
 }
@@ -48,6 +89,7 @@ impl fmt::Display for AssemblerError {
             Self::ArgumentCountMismatch(num, expected) => {
                 write!(f, "Argument Count Expected {expected}, found {num}")
             }
+            Self::InvalidAlignment(n) => write!(f, "Invalid Alignment {n}"),
             Self::InvalidLabel(l) => write!(f, "Invalid Label {l}"),
             Self::Instruction(i) => write!(f, "Instruction Error => {i}"),
             Self::Immediate(i) => write!(f, "Immediate Error => {i}"),
@@ -55,6 +97,17 @@ impl fmt::Display for AssemblerError {
             Self::DuplicateLabel(l) => write!(f, "Duplicate Label '{l}'"),
             Self::Character(c) => write!(f, "Character Error => {c}"),
             Self::AddressTaken(addr) => write!(f, "Address 0x{addr:08x} Taken"),
+            Self::InvalidVector(v) => write!(f, "Invalid Vector '{v}'"),
+            Self::DuplicateVector(v) => write!(f, "Duplicate Vector {v}"),
+            Self::InvalidObject(msg) => write!(f, "Invalid Object - {msg}"),
+            Self::Expression(e) => write!(f, "Expression Error - {e}"),
+            Self::DuplicateConstant(c) => write!(f, "Duplicate Constant '{c}'"),
+            Self::UnterminatedRepeat => write!(f, "Repeat Block Missing .endr"),
+            Self::UnmatchedEndRepeat => write!(f, ".endr Without Repeat Block"),
+            Self::InvalidRepeatCount(n) => write!(f, "Invalid Repeat Count {n}"),
+            Self::InvalidInclude(msg) => write!(f, "Invalid Include - {msg}"),
+            Self::IncludeNotFound(name) => write!(f, "Include File '{name}' Not Found"),
+            Self::IncludeCycle(name) => write!(f, "Include Cycle Through '{name}'"),
             Self::Parser(e) => write!(f, "Parser Error - {e}"),
             Self::Processor(e) => write!(f, "Processor Error - {e}"),
             Self::CannotBackupAddress(addr) => {
@@ -88,6 +141,12 @@ impl From<ProcessorError> for AssemblerError {
     }
 }
 
+impl From<ExpressionError> for AssemblerError {
+    fn from(value: ExpressionError) -> Self {
+        Self::Expression(value)
+    }
+}
+
 impl From<ParseError> for AssemblerError {
     fn from(value: ParseError) -> Self {
         Self::Parser(value)
@@ -99,6 +158,19 @@ pub struct LocationInfo {
     pub line: usize,
     pub full_line: Option<String>,
     pub base_loc: Option<Box<LocationInfo>>,
+    /// The included file containing the line, or None for the file being assembled
+    pub file: Option<Arc<str>>,
+}
+
+impl LocationInfo {
+    /// Provides the line of the file being assembled that the location originated from,
+    /// following lines of included files back to the include directive
+    pub fn root_line(&self) -> usize {
+        match (&self.file, &self.base_loc) {
+            (Some(_), Some(base)) => base.root_line(),
+            _ => self.line,
+        }
+    }
 }
 
 impl Default for LocationInfo {
@@ -107,13 +179,17 @@ impl Default for LocationInfo {
             line: 0,
             full_line: None,
             base_loc: None,
+            file: None,
         }
     }
 }
 
 impl fmt::Display for LocationInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "[{}]", self.line)?;
+        match &self.file {
+            Some(file) => write!(f, "[{file}:{}]", self.line)?,
+            None => write!(f, "[{}]", self.line)?,
+        }
         if let Some(txt) = &self.full_line {
             write!(f, " {}", txt)
         } else {
@@ -130,7 +206,11 @@ pub struct AssemblerErrorLoc {
 
 impl fmt::Display for AssemblerErrorLoc {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Line {} - {}", self.loc.line, self.err)?;
+        write!(f, "Line {}", self.loc.line)?;
+        if let Some(file) = self.loc.file.as_ref() {
+            write!(f, " of {file}, included from line {}", self.loc.root_line())?;
+        }
+        write!(f, " - {}", self.err)?;
         if let Some(s) = self.loc.full_line.as_ref() {
             write!(f, " - \"{}\"", s)?;
         }
@@ -140,7 +220,6 @@ impl fmt::Display for AssemblerErrorLoc {
 }
 
 type FnInst = fn(Vec<String>) -> Result<Rc<dyn Instruction>, InstructionError>;
-type FnDisp = fn([u8; 4]) -> Option<String>;
 
 pub trait FromLiteral<T> {
     fn from_literal(v: T) -> Self;
@@ -149,15 +228,29 @@ pub trait FromLiteral<T> {
 #[derive(Clone)]
 pub enum AsmToken {
     ChangeAddress(u32),
-    Operation(FnInst, Vec<String>),
+    ChangeOrigin(u32),
+    ChangeSection(String),
+    ChangeBank(u8),
+    Operation(String, Vec<String>),
     OperationLiteral(Box<dyn Instruction>),
     CreateLabel(String),
     LoadLoc(String),
+    /// Loads the offset from the following word to the label, which is unchanged when the
+    /// program is relocated
+    LoadRel(String),
+    LoadExpr(String),
     Literal1(u8),
     Literal2(u16),
     Literal4(u32),
     LiteralText(String),
+    Zero(u32),
     AlignInstruction,
+    AlignBoundary(u32),
+    Vector(VectorTarget, String),
+    /// Sets the source line recorded for the following instructions, in place of the line of
+    /// the assembly source, such that generated assembly may refer to the lines of the source
+    /// it was generated from
+    SourceLine(usize),
 }
 
 impl Clone for Box<dyn Instruction> {
@@ -217,17 +310,15 @@ pub struct AsmTokenLoc {
 pub struct InstructionList {
     inst_map: HashMap<String, FnInst>,
     name_map: HashMap<Opcode, String>,
-    disp_map: HashMap<Opcode, FnDisp>,
 }
 
 macro_rules! create_instruction_map {
     ($($op:ident),*) => {
-        Vec::<(Opcode, String, FnInst, FnDisp)>::from([
+        Vec::<(Opcode, String, FnInst)>::from([
             $( { (
                 $op::OP,
                 $op::name().into(),
-                (|a| Ok(Rc::new($op::try_from(a)?) as Rc<dyn Instruction>)) as FnInst,
-                (|b| { let res = $op::try_from(b); if let Ok(s) = res { Some(s.to_string()) } else { None } }) as FnDisp )
+                (|a| Ok(Rc::new($op::try_from(a)?) as Rc<dyn Instruction>)) as FnInst )
             } ),*
         ])
     };
@@ -242,13 +333,12 @@ impl InstructionList {
         self.inst_map.get(s)
     }
 
+    /// Provides the assembly text of the instruction, using the decoder shared with the
+    /// processor
     pub fn get_display(&self, inst: [u8; 4]) -> Option<String> {
-        let op = Opcode::from(inst[0]);
-        if let Some(f) = self.disp_map.get(&op) {
-            f(inst)
-        } else {
-            None
-        }
+        DecodedInstruction::decode(jib::cpu::Instruction::new(inst))
+            .ok()
+            .map(|d| d.to_string())
     }
 
     pub fn get_display_inst(&self, inst: u32) -> Option<String> {
@@ -259,22 +349,18 @@ impl InstructionList {
 impl Default for InstructionList {
     fn default() -> Self {
         let inst = create_instruction_map!(
-            OpAdd, OpBand, OpBnot, OpBool, OpBor, OpBshl, OpBshr, OpBxor, OpCall, OpConv, OpCopy,
-            OpDiv, OpHalt, OpInt, OpIntoff, OpInton, OpIntr, OpJmp, OpJmpr, OpJmpri, OpLd, OpLdi,
-            OpLdn, OpLdr, OpLdri, OpMul, OpNeg, OpNoop, OpNot, OpPop, OpPopr, OpPush, OpRem,
-            OpReset, OpRet, OpRetInt, OpSav, OpSavr, OpSub, OpTeq, OpTg, OpTge, OpTl, OpTle,
-            OpTneq, OpTnz, OpTz
+            OpAdd, OpBand, OpBcpy, OpBnot, OpBool, OpBor, OpBset, OpBshl, OpBshr, OpBxor, OpCall,
+            OpCallf, OpConv, OpCopy, OpCpuid, OpDiv, OpDivrem, OpEsc, OpHalt, OpInt, OpIntoff,
+            OpInton, OpIntr, OpJc, OpJmp, OpJmpr, OpJmpri, OpJn, OpJnc, OpJnn, OpJno, OpJnz, OpJo,
+            OpJz, OpLd, OpLdi, OpLdn, OpLdr, OpLdri, OpMac, OpMul, OpMulw, OpNeg, OpNoop, OpNot,
+            OpPop, OpPopr, OpPush, OpRem, OpReset, OpRet, OpRetInt, OpRetf, OpSav, OpSavr, OpSub,
+            OpSys, OpTeq, OpTg, OpTge, OpTl, OpTle, OpTneq, OpTnz, OpTz
         );
 
-        let inst_map = inst.iter().map(|(_, n, f, _)| (n.to_owned(), *f)).collect();
-        let name_map = inst.iter().map(|(o, n, _, _)| (*o, n.to_owned())).collect();
-        let disp_map = inst.iter().map(|(o, _, _, d)| (*o, *d)).collect();
+        let inst_map = inst.iter().map(|(_, n, f)| (n.to_owned(), *f)).collect();
+        let name_map = inst.iter().map(|(o, n, _)| (*o, n.to_owned())).collect();
 
-        Self {
-            inst_map,
-            name_map,
-            disp_map,
-        }
+        Self { inst_map, name_map }
     }
 }
 
@@ -283,6 +369,7 @@ pub enum ParseError {
     UnknownEscapeCode(char),
     WithinQuote,
     WithinEscape,
+    WithinParenthesis,
     ExpectedSpaceBetweenQuote,
 }
 
@@ -292,6 +379,7 @@ impl fmt::Display for ParseError {
             Self::UnknownEscapeCode(c) => write!(f, "Unknown escape code '\\{c}'"),
             Self::WithinQuote => write!(f, "Parser ended within a quote"),
             Self::WithinEscape => write!(f, "Parser ending with an unfinished escape code"),
+            Self::WithinParenthesis => write!(f, "Parser ended within a parenthesis"),
             Self::ExpectedSpaceBetweenQuote => write!(f, "Expected space between quote"),
         }
     }
@@ -301,6 +389,7 @@ pub struct TokenList {
     tokens: Vec<AsmTokenLoc>,
     inst: InstructionList,
     label_regex: regex::Regex,
+    constants: HashMap<String, i64>,
 }
 
 impl TokenList {
@@ -317,6 +406,7 @@ impl TokenList {
         let mut within_quote = false;
         let mut is_escape = false;
         let mut last_was_quote = false;
+        let mut paren_depth = 0usize;
 
         let mut so_far = Vec::<char>::new();
         let mut words = Vec::new();
@@ -358,6 +448,16 @@ impl TokenList {
                     return Err(ParseError::ExpectedSpaceBetweenQuote);
                 }
                 within_quote = true;
+            } else if c == '(' || c == ')' {
+                // Keep parenthesized expressions together as a single word
+                if c == '(' {
+                    paren_depth += 1;
+                } else {
+                    paren_depth = paren_depth.saturating_sub(1);
+                }
+                so_far.push(c);
+            } else if c.is_whitespace() && paren_depth > 0 {
+                continue;
             } else if c.is_whitespace() {
                 if !so_far.is_empty() {
                     words.push(so_far.iter().collect());
@@ -372,6 +472,8 @@ impl TokenList {
             return Err(ParseError::WithinQuote);
         } else if is_escape {
             return Err(ParseError::WithinEscape);
+        } else if paren_depth > 0 {
+            return Err(ParseError::WithinParenthesis);
         }
 
         if !so_far.is_empty() {
@@ -394,11 +496,26 @@ impl TokenList {
         };
 
         let tok = if let Some(op) = first.strip_prefix('.') {
-            let args = &words[1..];
+            if op == "equ" {
+                return self.define_constant(&words[1..]);
+            }
+
+            let args = match op {
+                "text" | "str" | "section" | "loadloc" | "loadrel" | "vector" => {
+                    words[1..].to_vec()
+                }
+                _ => self.resolve_args(&words[1..])?,
+            };
+            let args = &args[..];
+
+            if op == "word" {
+                return self.parse_words(args, loc);
+            }
 
             if args.is_empty() {
                 match op {
                     "align" => AsmToken::AlignInstruction,
+                    "str" => AsmToken::LiteralText(String::new()),
                     _ => {
                         return Err(AssemblerError::UnknownInstruction(
                             op.to_string(),
@@ -410,23 +527,34 @@ impl TokenList {
                 let arg = &args[0];
 
                 match op {
-                    "oper" => {
-                        let addr = if let Some(r) = arg.strip_prefix('#') {
-                            Processor::interrupt_address(jib::cpu::Interrupt::Hardware(
-                                parse_imm_u32(r)?,
-                            ))?
-                        } else if let Some(r) = arg.strip_prefix('@') {
-                            Processor::interrupt_address(jib::cpu::Interrupt::Software(
-                                parse_imm_u32(r)?,
-                            ))?
-                        } else {
-                            parse_imm_u32(arg)?
-                        };
+                    "oper" | "org" => {
+                        let addr = Self::parse_address(arg)?;
 
-                        AsmToken::ChangeAddress(addr)
+                        if op == "org" {
+                            AsmToken::ChangeOrigin(addr)
+                        } else {
+                            AsmToken::ChangeAddress(addr)
+                        }
+                    }
+                    "section" => {
+                        if !self.label_regex.is_match(arg) {
+                            return Err(AssemblerError::BadLabel(arg.to_string()));
+                        }
+                        AsmToken::ChangeSection(arg.into())
                     }
+                    "bank" => AsmToken::ChangeBank(parse_imm_u8(arg)?),
+                    "loc" => AsmToken::SourceLine(parse_imm_u32(arg)? as usize),
                     "loadloc" => AsmToken::LoadLoc(arg.into()),
-                    "text" => AsmToken::LiteralText(arg.into()),
+                    "loadrel" => AsmToken::LoadRel(arg.into()),
+                    "text" | "str" => AsmToken::LiteralText(arg.into()),
+                    "zero" => AsmToken::Zero(parse_imm_u32(arg)?),
+                    "align" => {
+                        let n = parse_imm_u32(arg)?;
+                        if !n.is_power_of_two() {
+                            return Err(AssemblerError::InvalidAlignment(n));
+                        }
+                        AsmToken::AlignBoundary(n)
+                    }
                     "u8" => AsmToken::Literal1(parse_imm_u8(arg)?),
                     "u16" => AsmToken::Literal2(parse_imm_u16(arg)?),
                     "u32" => AsmToken::Literal4(parse_imm_u32(arg)?),
@@ -447,6 +575,27 @@ impl TokenList {
                         ));
                     }
                 }
+            } else if op == "vector" {
+                if args.len() != 2 {
+                    return Err(AssemblerError::ArgumentCountMismatch(args.len(), 2));
+                }
+
+                let target = match args[0].as_str() {
+                    "default" => VectorTarget::Default,
+                    "reset" => VectorTarget::Address(Processor::HARD_RESET_VECTOR),
+                    "soft_reset" => VectorTarget::Address(Processor::SOFT_RESET_VECTOR),
+                    t if t.starts_with('#') || t.starts_with('@') => {
+                        VectorTarget::Address(Self::parse_address(t)?)
+                    }
+                    t => return Err(AssemblerError::InvalidVector(t.to_string())),
+                };
+
+                let lbl = &args[1];
+                if !self.label_regex.is_match(lbl) {
+                    return Err(AssemblerError::BadLabel(lbl.to_string()));
+                }
+
+                AsmToken::Vector(target, lbl.to_string())
             } else {
                 return Err(AssemblerError::ArgumentCountMismatch(args.len(), 1));
             }
@@ -460,9 +609,8 @@ impl TokenList {
             }
 
             AsmToken::CreateLabel(lbl.to_string())
-        } else if let Some(inst_fn) = self.inst.get_instruction(&words[0]) {
-            let args = &words[1..];
-            AsmToken::Operation(*inst_fn, args.iter().map(|s| s.to_string()).collect())
+        } else if self.inst.get_instruction(&words[0]).is_some() {
+            AsmToken::Operation(words[0].to_string(), self.resolve_args(&words[1..])?)
         } else {
             return Err(AssemblerError::UnknownInstruction(line.into(), None));
         };
@@ -472,231 +620,140 @@ impl TokenList {
         Ok(())
     }
 
-    pub fn add_token(&mut self, tok: AsmTokenLoc) {
-        self.tokens.push(tok)
-    }
+    /// Defines a constant from the name and value arguments of an equ directive. The value may
+    /// be a number, a previously-defined constant, or an expression of these
+    fn define_constant(&mut self, args: &[String]) -> Result<(), AssemblerError> {
+        let [name, value] = args else {
+            return Err(AssemblerError::ArgumentCountMismatch(args.len(), 2));
+        };
 
-    pub fn to_bytes(&self) -> Result<Vec<u8>, AssemblerErrorLoc> {
-        let mut state = ParserState::new();
+        if !self.label_regex.is_match(name) {
+            return Err(AssemblerError::BadLabel(name.to_string()));
+        } else if self.constants.contains_key(name) {
+            return Err(AssemblerError::DuplicateConstant(name.to_string()));
+        }
 
-        for t in self.tokens.iter() {
-            let loc = t.loc.clone();
+        let val = Expression::parse(&format!("({value})"))?
+            .evaluate(|s| self.constants.get(s).copied())?;
+        self.constants.insert(name.to_string(), val);
 
-            match &t.tok {
-                AsmToken::AlignInstruction => state.align_boundary(Processor::BYTES_PER_WORD),
-                AsmToken::OperationLiteral(op) => {
-                    state.add_bytes(&op.to_u32().to_be_bytes(), loc)?;
-                }
-                AsmToken::ChangeAddress(new_addr) => {
-                    if *new_addr < state.addr {
-                        return Err(AssemblerErrorLoc {
-                            err: AssemblerError::CannotBackupAddress(*new_addr),
-                            loc,
-                        });
+        Ok(())
+    }
+
+    /// Replaces any constant arguments with their values, and evaluates any expression
+    /// arguments. Expressions that refer to labels are left to be evaluated when linking,
+    /// with any constants replaced by their values
+    fn resolve_args(&self, args: &[String]) -> Result<Vec<String>, AssemblerError> {
+        let lookup = |s: &str| self.constants.get(s).copied();
+
+        args.iter()
+            .map(|arg| {
+                if let Some(val) = lookup(arg) {
+                    Ok(val.to_string())
+                } else if Expression::is_expression(arg) {
+                    let mut expr = Expression::parse(arg)?;
+                    expr.substitute(lookup);
+                    if expr.symbols().next().is_none() {
+                        Ok(expr.evaluate(lookup)?.to_string())
                     } else {
-                        state.addr = *new_addr;
-                    }
-                }
-                AsmToken::LiteralText(s) => {
-                    for c in s.chars() {
-                        let bv = match jib::text::character_to_byte(c) {
-                            Ok(v) => v,
-                            Err(e) => {
-                                return Err(AssemblerErrorLoc {
-                                    err: AssemblerError::from(e),
-                                    loc,
-                                });
-                            }
-                        };
-                        state.add_bytes(&[bv], loc.clone())?;
-                    }
-                    state.add_bytes(&[0], loc.clone())?;
-                }
-                AsmToken::Literal1(i) => {
-                    state.add_bytes(&[*i], loc)?;
-                }
-                AsmToken::Literal2(i) => {
-                    state.add_bytes(&i.to_be_bytes(), loc)?;
-                }
-                AsmToken::Literal4(i) => {
-                    state.add_bytes(&i.to_be_bytes(), loc)?;
-                }
-                AsmToken::CreateLabel(lbl) => {
-                    if state.labels.contains_key(lbl) {
-                        return Err(AssemblerErrorLoc {
-                            err: AssemblerError::DuplicateLabel(lbl.to_string()),
-                            loc,
-                        });
+                        Ok(expr.to_string())
                     }
-                    state.labels.insert(lbl.into(), state.addr);
-                }
-                AsmToken::LoadLoc(lbl) => {
-                    state.add_delay(DelayToken::LoadLoc { label: lbl.into() }, t.loc.clone())?;
-                }
-                AsmToken::Operation(func, args) => {
-                    state.add_delay(
-                        DelayToken::Operation {
-                            inst: *func,
-                            args: args.to_owned(),
-                        },
-                        t.loc.clone(),
-                    )?;
+                } else {
+                    Ok(arg.to_string())
                 }
-            }
-        }
-
-        state.process_delays()?;
-
-        let mut bytes = Vec::new();
-
-        if let Some(max_addr) = state.values.keys().max() {
-            bytes.resize(*max_addr as usize + 1, 0);
-
-            for (a, v) in state.values {
-                bytes[a as usize] = v;
-            }
-        }
-
-        Ok(bytes)
+            })
+            .collect()
     }
-}
 
-impl Default for TokenList {
-    fn default() -> Self {
-        Self {
-            tokens: Vec::new(),
-            inst: InstructionList::default(),
-            label_regex: regex::Regex::new("^[a-z](a-z0-9_)*").unwrap(),
+    /// Parses the arguments of a word directive, adding a word for each argument. Each argument
+    /// is a numeric value, a label, or an expression, where label addresses are resolved when
+    /// linking
+    fn parse_words(&mut self, args: &[String], loc: LocationInfo) -> Result<(), AssemblerError> {
+        if args.is_empty() {
+            return Err(AssemblerError::ArgumentCountMismatch(0, 1));
         }
-    }
-}
-
-#[derive(Debug, Clone)]
-enum DelayToken {
-    LoadLoc { label: String },
-    Operation { inst: FnInst, args: Vec<String> },
-}
-
-#[derive(Default)]
-struct ParserState {
-    addr: u32,
-    labels: HashMap<String, u32>,
-    values: HashMap<u32, u8>,
-    delay_vals: HashMap<u32, (DelayToken, LocationInfo)>,
-}
-
-impl ParserState {
-    pub fn new() -> Self {
-        Self::default()
-    }
 
-    fn add_delay(&mut self, delay: DelayToken, loc: LocationInfo) -> Result<(), AssemblerErrorLoc> {
-        let base = self.add_bytes(&0u32.to_be_bytes(), loc.clone())?;
+        for arg in args {
+            let tok = match parse_imm_u32(arg).or_else(|_| parse_imm_i32(arg).map(|v| v as u32)) {
+                Ok(v) => AsmToken::Literal4(v),
+                Err(_) if Expression::is_expression(arg) => AsmToken::LoadExpr(arg.into()),
+                Err(_) if self.label_regex.is_match(arg) => AsmToken::LoadLoc(arg.into()),
+                Err(e) => return Err(e.into()),
+            };
 
-        if self.delay_vals.insert(base, (delay, loc.clone())).is_some() {
-            return Err(AssemblerErrorLoc {
-                err: AssemblerError::AddressTaken(base),
-                loc,
+            self.tokens.push(AsmTokenLoc {
+                tok,
+                loc: loc.clone(),
             });
         }
 
         Ok(())
     }
 
-    fn add_bytes(&mut self, vals: &[u8], loc: LocationInfo) -> Result<u32, AssemblerErrorLoc> {
-        self.align_boundary(vals.len() as u32);
-        let base = self.addr;
-        for v in vals {
-            if self.values.insert(self.addr, *v).is_some() {
-                return Err(AssemblerErrorLoc {
-                    err: AssemblerError::AddressTaken(self.addr),
-                    loc,
-                });
-            }
-            self.addr += 1;
-        }
-        Ok(base)
+    /// Parses an address, where a '#' or '@' prefix provides the vector address of the
+    /// hardware or software interrupt with the following number
+    fn parse_address(arg: &str) -> Result<u32, AssemblerError> {
+        Ok(if let Some(r) = arg.strip_prefix('#') {
+            Processor::interrupt_address(jib::cpu::Interrupt::Hardware(parse_imm_u32(r)?))?
+        } else if let Some(r) = arg.strip_prefix('@') {
+            Processor::interrupt_address(jib::cpu::Interrupt::Software(parse_imm_u32(r)?))?
+        } else {
+            parse_imm_u32(arg)?
+        })
     }
 
-    fn align_boundary(&mut self, val: u32) {
-        if val > 0 && self.addr % val != 0 {
-            self.addr += val - (self.addr % val);
-        }
+    pub fn add_token(&mut self, tok: AsmTokenLoc) {
+        self.tokens.push(tok)
     }
 
-    fn process_delays(&mut self) -> Result<(), AssemblerErrorLoc> {
-        for (addr, (tok, loc)) in self.delay_vals.iter() {
-            let insert_value = match tok {
-                DelayToken::LoadLoc { label } => {
-                    if let Some(loc) = self.labels.get(label) {
-                        *loc
-                    } else {
-                        return Err(AssemblerErrorLoc {
-                            err: AssemblerError::UnknownLabel(label.into()),
-                            loc: loc.clone(),
-                        });
-                    }
-                }
-                DelayToken::Operation { inst, args } => {
-                    // Create new arguments to get relative values for any label parameters
-                    let mut new_args = Vec::new();
-
-                    for a in args.iter() {
-                        let na = if let Some(v) = self.labels.get(a) {
-                            format!("{}", (*v as i32) - (*addr as i32))
-                        } else {
-                            a.to_string()
-                        };
-
-                        new_args.push(na);
-                    }
-
-                    // Call the instruction function and obtain the resulting parameters
-                    let val = match inst(new_args) {
-                        Ok(v) => v,
-                        Err(err) => {
-                            return Err(AssemblerErrorLoc {
-                                err: err.into(),
-                                loc: loc.clone(),
-                            });
-                        }
-                    };
+    pub fn to_object(&self) -> Result<ObjectFile, AssemblerErrorLoc> {
+        ObjectFile::from_tokens(&self.tokens)
+    }
 
-                    // Replace the
-                    val.to_u32()
-                }
-            };
+    pub fn to_bytes(&self) -> Result<Vec<u8>, AssemblerErrorLoc> {
+        object::link(&[self.to_object()?], &HashMap::new())
+    }
+}
 
-            for (i, b) in insert_value.to_be_bytes().iter().enumerate() {
-                self.values.insert(addr + i as u32, *b);
-            }
+impl Default for TokenList {
+    fn default() -> Self {
+        Self {
+            tokens: Vec::new(),
+            inst: InstructionList::default(),
+            label_regex: regex::Regex::new("^[a-z](a-z0-9_)*").unwrap(),
+            constants: HashMap::new(),
         }
-
-        self.delay_vals.clear();
-
-        Ok(())
     }
 }
 
+/// Assembles and links the source text into the bytes of a program loaded at address zero,
+/// without any include paths or other source files
 pub fn assemble_text(txt: &str) -> Result<Vec<u8>, AssemblerErrorLoc> {
     assemble_lines(&txt.lines().collect::<Vec<_>>())
 }
 
 pub fn assemble_lines(txt: &[&str]) -> Result<Vec<u8>, AssemblerErrorLoc> {
+    assemble_source(&preprocess::preprocess_lines(txt)?)
+}
+
+pub fn assemble_source(lines: &[SourceLine]) -> Result<Vec<u8>, AssemblerErrorLoc> {
+    object::link(&[assemble_object(lines)?], &HashMap::new())
+}
+
+/// Assembles the provided source lines into an object file, to be linked with other objects
+pub fn assemble_object(lines: &[SourceLine]) -> Result<ObjectFile, AssemblerErrorLoc> {
     let mut state = TokenList::default();
 
-    for (i, l) in txt.iter().enumerate() {
-        let loc = LocationInfo {
-            line: i + 1,
-            full_line: Some(l.to_string()),
-            base_loc: None,
-        };
-        if let Err(e) = state.parse_line(&l.to_lowercase(), loc.clone()) {
-            return Err(AssemblerErrorLoc { err: e, loc });
+    for l in lines {
+        if let Err(e) = state.parse_line(&l.text, l.loc.clone()) {
+            return Err(AssemblerErrorLoc {
+                err: e,
+                loc: l.loc.clone(),
+            });
         }
     }
 
-    state.to_bytes()
+    state.to_object()
 }
 
 pub fn assemble_tokens<T>(tokens: T) -> Result<Vec<u8>, AssemblerErrorLoc>
@@ -714,6 +771,108 @@ where
 mod test {
     use super::*;
 
+    #[test]
+    fn test_data_directives() {
+        let txt = ".str \"hi\"\n.align 8\n:tbl\n.word 1 -1 tbl\n.zero 3\n.u8 7\n.str \"\"\n";
+        let bytes = assemble_text(txt).unwrap();
+
+        let hi = ['h', 'i'].map(|c| jib::text::character_to_byte(c).unwrap());
+        assert_eq!(bytes[..3], [hi[0], hi[1], 0]);
+        assert_eq!(bytes[3..8], [0; 5]);
+        assert_eq!(bytes[8..12], 1u32.to_be_bytes());
+        assert_eq!(bytes[12..16], u32::MAX.to_be_bytes());
+        assert_eq!(bytes[16..20], 8u32.to_be_bytes());
+        assert_eq!(bytes[20..], [0, 0, 0, 7, 0]);
+
+        assert!(matches!(
+            assemble_text(".align 3\n"),
+            Err(AssemblerErrorLoc {
+                err: AssemblerError::InvalidAlignment(3),
+                ..
+            })
+        ));
+        assert!(assemble_text(".word\n").is_err());
+        assert!(assemble_text(".word 0xzz\n").is_err());
+    }
+
+    #[test]
+    fn test_constant_expressions() {
+        let txt = ".equ BUFFER_BASE 0x100\n.equ count (buffer_base / 0x40)\n\
+            ldi 6:u16 (BUFFER_BASE + 4*2)\nldi 7:u16 (tbl + count)\n:tbl\n.word (tbl - 4) count\n";
+        let bytes = assemble_text(txt).unwrap();
+
+        let expected = assemble_text("ldi 6:u16 264\nldi 7:u16 12\n.u32 4\n.u32 4\n").unwrap();
+        assert_eq!(bytes, expected);
+
+        let obj = assemble_object(&preprocess::preprocess_text(txt).unwrap()).unwrap();
+        let read = ObjectFile::from_text(&obj.to_text()).unwrap();
+        assert_eq!(object::link(&[read], &HashMap::new()).unwrap(), expected);
+
+        assert!(matches!(
+            assemble_text(".equ a 1\n.equ a 2\n"),
+            Err(AssemblerErrorLoc {
+                err: AssemblerError::DuplicateConstant(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            assemble_text(".equ a (lbl + 1)\n:lbl\n"),
+            Err(AssemblerErrorLoc {
+                err: AssemblerError::Expression(ExpressionError::UnknownSymbol(_)),
+                ..
+            })
+        ));
+        assert!(assemble_text("ldi 6:u16 (missing + 1)\n").is_err());
+        assert!(assemble_text("ldi 6:u16 (1 + 2\n").is_err());
+    }
+
+    #[test]
+    fn test_repeat_tables() {
+        let txt = ".equ steps 4\n.rept steps i\n.i8 (sin(i, steps, 100))\n.endr\n\
+            .rept 2 i\n.word (tbl + i * 4)\n.endr\n:tbl\n";
+        let bytes = assemble_text(txt).unwrap();
+
+        let expected =
+            assemble_text(".i8 0\n.i8 100\n.i8 0\n.i8 -100\n.u32 12\n.u32 16\n").unwrap();
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn test_strict_encodings() {
+        use jib::cpu::InstructionFormat;
+
+        let list = InstructionList::default();
+
+        for op in 0..=u8::MAX {
+            let opcode = Opcode::from(op);
+            let Some(format) = Processor::instruction_format(opcode) else {
+                assert!(list.get_name_for_opcode(&opcode).is_none());
+                continue;
+            };
+
+            let args = match format {
+                InstructionFormat::NoArgument => "",
+                InstructionFormat::Immediate => "-4",
+                InstructionFormat::Register => "9",
+                InstructionFormat::RegisterType => "9:u32",
+                InstructionFormat::RegisterTypeImmediate => "9:u16 12",
+                InstructionFormat::DoubleRegister => "9 10",
+                InstructionFormat::DoubleRegisterType => "9:u32 10",
+                InstructionFormat::Conversion => "9:u32 10:f32",
+                InstructionFormat::Arithmetic => "9:u32 10 11",
+                InstructionFormat::Extension => "3 0x1234",
+            };
+
+            let name = list.get_name_for_opcode(&opcode).unwrap();
+            let bytes = assemble_text(&format!("{name} {args}")).unwrap();
+            let inst = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+            assert!(
+                Processor::validate_encoding(inst.into()).is_ok(),
+                "{name} {args}"
+            );
+        }
+    }
+
     #[test]
     fn test_counter() {
         let txt = include_str!("../../jib-asm/examples/counter.jsm");
@@ -0,0 +1,428 @@
+use std::{cell::RefCell, rc::Rc};
+
+use clap::ValueEnum;
+use jib::{
+    cpu::{Processor, ProcessorError},
+    device::{
+        BlockStorageDevice, GpioDevice, HostTimeDevice, InterruptClockDevice, KeyboardDevice,
+        LogDevice, ProcessorDevice, SerialInputOutputDevice, SerialMuxDevice, TextDisplayDevice,
+        TrapInfoDevice, DEVICE_MEM_SIZE,
+    },
+    memory::{FaultSegment, MemorySegment, ProtectionUnit, ReadOnlySegment, ReadWriteSegment},
+};
+
+/// Provides the devices of the standard machine, in address order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StandardDevice {
+    Serial,
+    Clock,
+    Log,
+    #[value(name = "time")]
+    HostTime,
+    Keyboard,
+    Display,
+    Disk,
+    Mux,
+    Protection,
+    #[value(name = "trap")]
+    TrapInfo,
+    Gpio,
+}
+
+impl StandardDevice {
+    pub const ALL: [Self; 11] = [
+        Self::Serial,
+        Self::Clock,
+        Self::Log,
+        Self::HostTime,
+        Self::Keyboard,
+        Self::Display,
+        Self::Disk,
+        Self::Mux,
+        Self::Protection,
+        Self::TrapInfo,
+        Self::Gpio,
+    ];
+}
+
+/// Provides the standard set of devices mapped by the runner, the debugger, and the front-ends,
+/// which are kept by the host across resets to exchange data with the program
+pub struct StandardDevices {
+    pub serial: Rc<RefCell<SerialInputOutputDevice>>,
+    pub clock: Rc<RefCell<InterruptClockDevice>>,
+    pub log: Rc<RefCell<LogDevice>>,
+    pub host_time: Rc<RefCell<HostTimeDevice>>,
+    pub keyboard: Rc<RefCell<KeyboardDevice>>,
+    pub display: Rc<RefCell<TextDisplayDevice>>,
+    pub disk: Rc<RefCell<BlockStorageDevice>>,
+    pub mux: Rc<RefCell<SerialMuxDevice>>,
+    pub protection: Rc<RefCell<ProtectionUnit>>,
+    pub trap_info: Rc<RefCell<TrapInfoDevice>>,
+    pub gpio: Rc<RefCell<GpioDevice>>,
+}
+
+impl StandardDevices {
+    /// The number of bytes buffered by the serial device and by each multiplexed channel
+    pub const SERIAL_BUFFER_LEN: usize = 2048;
+
+    /// The number of log messages buffered before the oldest is dropped
+    pub const LOG_ENTRIES: usize = 256;
+
+    /// The number of key codes buffered by the keyboard device
+    pub const KEY_BUFFER_LEN: usize = 64;
+
+    /// The number of channels of the serial multiplexer
+    pub const MUX_CHANNELS: u8 = 4;
+
+    /// The number of regions of the protection unit
+    pub const PROTECTION_REGIONS: u8 = 8;
+
+    /// The number of pins of the GPIO port
+    pub const GPIO_PINS: u8 = 8;
+
+    /// Creates the standard devices, reading the time from the provided host time device
+    pub fn new(host_time: HostTimeDevice) -> Self {
+        Self {
+            serial: Rc::new(RefCell::new(SerialInputOutputDevice::new(
+                Self::SERIAL_BUFFER_LEN,
+            ))),
+            clock: Rc::new(RefCell::new(InterruptClockDevice::new(0))),
+            log: Rc::new(RefCell::new(LogDevice::new(Self::LOG_ENTRIES))),
+            host_time: Rc::new(RefCell::new(host_time)),
+            keyboard: Rc::new(RefCell::new(KeyboardDevice::new(Self::KEY_BUFFER_LEN))),
+            display: Rc::new(RefCell::new(TextDisplayDevice::new(
+                TextDisplayDevice::DEFAULT_COLUMNS,
+                TextDisplayDevice::DEFAULT_ROWS,
+            ))),
+            disk: Rc::new(RefCell::new(BlockStorageDevice::new())),
+            mux: Rc::new(RefCell::new(SerialMuxDevice::new(
+                Self::MUX_CHANNELS,
+                Self::SERIAL_BUFFER_LEN,
+            ))),
+            protection: Rc::new(RefCell::new(ProtectionUnit::new(Self::PROTECTION_REGIONS))),
+            trap_info: Rc::new(RefCell::new(TrapInfoDevice::new())),
+            gpio: Rc::new(RefCell::new(GpioDevice::new(Self::GPIO_PINS))),
+        }
+    }
+
+    /// Resets each device, keeping any disk attached to the block storage device
+    pub fn reset(&self) {
+        self.serial.borrow_mut().reset();
+        self.clock.borrow_mut().reset();
+        self.log.borrow_mut().reset();
+        self.host_time.borrow_mut().reset();
+        self.keyboard.borrow_mut().reset();
+        self.display.borrow_mut().reset();
+        self.disk.borrow_mut().reset();
+        self.mux.borrow_mut().reset();
+        self.protection.borrow_mut().reset();
+        self.trap_info.borrow_mut().reset();
+        self.gpio.borrow_mut().reset();
+    }
+}
+
+/// Builds the standard memory map shared by the runner, the debugger, and the front-ends, being
+/// the read-only vector table, the read-write memory below the device region, and the devices
+/// provided, in a fixed order from the start of the device region. Each device keeps its
+/// address when earlier devices are left unmapped
+#[derive(Default)]
+pub struct MachineBuilder {
+    vector_table: Option<Vec<u8>>,
+    ram: Option<Rc<RefCell<FaultSegment>>>,
+    serial: Option<Rc<RefCell<SerialInputOutputDevice>>>,
+    clock: Option<Rc<RefCell<InterruptClockDevice>>>,
+    log: Option<Rc<RefCell<LogDevice>>>,
+    host_time: Option<Rc<RefCell<HostTimeDevice>>>,
+    keyboard: Option<Rc<RefCell<KeyboardDevice>>>,
+    display: Option<Rc<RefCell<TextDisplayDevice>>>,
+    disk: Option<Rc<RefCell<BlockStorageDevice>>>,
+    mux: Option<Rc<RefCell<SerialMuxDevice>>>,
+    protection: Option<Rc<RefCell<ProtectionUnit>>>,
+    trap_info: Option<Rc<RefCell<TrapInfoDevice>>>,
+    gpio: Option<Rc<RefCell<GpioDevice>>>,
+}
+
+impl MachineBuilder {
+    /// The address of the read-write memory, following the read-only vector table
+    pub const RAM_START: u32 = Processor::TOP_VEC_SEG_ADDR;
+
+    /// The address of the device region, which ends the read-write memory
+    pub const DEVICE_START: u32 = 0xA000;
+
+    /// The fixed address of the protection unit
+    pub const PROTECTION_START: u32 = 0xB800;
+
+    /// The fixed address of the trap-info device
+    pub const TRAP_INFO_START: u32 = 0xB880;
+
+    /// The fixed address of the GPIO port
+    pub const GPIO_START: u32 = 0xC000;
+
+    /// The size of the read-write memory, in bytes
+    pub const RAM_LEN: usize = (Self::DEVICE_START - Self::RAM_START) as usize;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps each of the standard devices
+    pub fn standard(devices: &StandardDevices) -> Self {
+        Self::new()
+            .serial(devices.serial.clone())
+            .clock(devices.clock.clone())
+            .log(devices.log.clone())
+            .host_time(devices.host_time.clone())
+            .keyboard(devices.keyboard.clone())
+            .display(devices.display.clone())
+            .disk(devices.disk.clone())
+            .mux(devices.mux.clone())
+            .protection(devices.protection.clone())
+            .trap_info(devices.trap_info.clone())
+            .gpio(devices.gpio.clone())
+    }
+
+    /// Leaves the device unmapped, keeping the address range of the device unused
+    pub fn without(mut self, dev: StandardDevice) -> Self {
+        match dev {
+            StandardDevice::Serial => self.serial = None,
+            StandardDevice::Clock => self.clock = None,
+            StandardDevice::Log => self.log = None,
+            StandardDevice::HostTime => self.host_time = None,
+            StandardDevice::Keyboard => self.keyboard = None,
+            StandardDevice::Display => self.display = None,
+            StandardDevice::Disk => self.disk = None,
+            StandardDevice::Mux => self.mux = None,
+            StandardDevice::Protection => self.protection = None,
+            StandardDevice::TrapInfo => self.trap_info = None,
+            StandardDevice::Gpio => self.gpio = None,
+        }
+        self
+    }
+
+    /// Fills the read-only vector table with the provided bytes, rather than leaving it to be
+    /// filled in when the image is loaded
+    pub fn vector_table(mut self, bytes: Vec<u8>) -> Self {
+        self.vector_table = Some(bytes);
+        self
+    }
+
+    /// Maps the provided segment in place of the read-write memory, such as to inject faults,
+    /// where the segment should wrap memory of [`Self::RAM_LEN`] bytes
+    pub fn ram(mut self, seg: Rc<RefCell<FaultSegment>>) -> Self {
+        self.ram = Some(seg);
+        self
+    }
+
+    pub fn serial(mut self, dev: Rc<RefCell<SerialInputOutputDevice>>) -> Self {
+        self.serial = Some(dev);
+        self
+    }
+
+    pub fn clock(mut self, dev: Rc<RefCell<InterruptClockDevice>>) -> Self {
+        self.clock = Some(dev);
+        self
+    }
+
+    pub fn log(mut self, dev: Rc<RefCell<LogDevice>>) -> Self {
+        self.log = Some(dev);
+        self
+    }
+
+    pub fn host_time(mut self, dev: Rc<RefCell<HostTimeDevice>>) -> Self {
+        self.host_time = Some(dev);
+        self
+    }
+
+    pub fn keyboard(mut self, dev: Rc<RefCell<KeyboardDevice>>) -> Self {
+        self.keyboard = Some(dev);
+        self
+    }
+
+    pub fn display(mut self, dev: Rc<RefCell<TextDisplayDevice>>) -> Self {
+        self.display = Some(dev);
+        self
+    }
+
+    pub fn disk(mut self, dev: Rc<RefCell<BlockStorageDevice>>) -> Self {
+        self.disk = Some(dev);
+        self
+    }
+
+    pub fn mux(mut self, dev: Rc<RefCell<SerialMuxDevice>>) -> Self {
+        self.mux = Some(dev);
+        self
+    }
+
+    /// Maps the protection unit at its fixed address, also consulting it for memory accesses
+    /// made in user mode
+    pub fn protection(mut self, unit: Rc<RefCell<ProtectionUnit>>) -> Self {
+        self.protection = Some(unit);
+        self
+    }
+
+    /// Maps the trap-info device at its fixed address, also recording each trap raised into it
+    pub fn trap_info(mut self, dev: Rc<RefCell<TrapInfoDevice>>) -> Self {
+        self.trap_info = Some(dev);
+        self
+    }
+
+    /// Maps the GPIO port at its fixed address
+    pub fn gpio(mut self, dev: Rc<RefCell<GpioDevice>>) -> Self {
+        self.gpio = Some(dev);
+        self
+    }
+
+    /// Maps the memory and devices into the processor, which should not have any memory mapped
+    pub fn build(self, cpu: &mut Processor) -> Result<(), ProcessorError> {
+        // The read-only vector table is filled in when the image is loaded, unless provided
+        let mut vector_table = self.vector_table.unwrap_or_default();
+        vector_table.resize(Self::RAM_START as usize, 0);
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadOnlySegment::new(vector_table))))?;
+
+        match self.ram {
+            Some(seg) => cpu.device_attach("ram", Self::RAM_START, seg)?,
+            None => cpu.memory_add_segment(
+                Self::RAM_START,
+                Rc::new(RefCell::new(ReadWriteSegment::new(Self::RAM_LEN))),
+            )?,
+        }
+
+        let mut base = Self::DEVICE_START;
+        attach(cpu, &mut base, "serial", self.serial, || DEVICE_MEM_SIZE)?;
+        attach(cpu, &mut base, "clock", self.clock, || DEVICE_MEM_SIZE)?;
+        attach(cpu, &mut base, "log", self.log, || DEVICE_MEM_SIZE)?;
+        attach(cpu, &mut base, "host time", self.host_time, || {
+            DEVICE_MEM_SIZE
+        })?;
+        attach(cpu, &mut base, "keyboard", self.keyboard, || {
+            DEVICE_MEM_SIZE
+        })?;
+        attach(cpu, &mut base, "display", self.display, || {
+            TextDisplayDevice::new(
+                TextDisplayDevice::DEFAULT_COLUMNS,
+                TextDisplayDevice::DEFAULT_ROWS,
+            )
+            .len()
+        })?;
+        attach(cpu, &mut base, "disk", self.disk, || {
+            BlockStorageDevice::new().len()
+        })?;
+        attach(cpu, &mut base, "mux", self.mux, || DEVICE_MEM_SIZE)?;
+
+        // The protection unit is mapped at a fixed address, such that it may only be configured
+        // from supervisor mode unless a region covers it
+        if let Some(unit) = self.protection {
+            cpu.device_attach("protection", Self::PROTECTION_START, unit.clone())?;
+            cpu.set_protection_unit(Some(unit));
+        }
+
+        if let Some(dev) = self.trap_info {
+            cpu.device_attach("trap info", Self::TRAP_INFO_START, dev.clone())?;
+            cpu.set_trap_reporter(Some(dev));
+        }
+
+        if let Some(dev) = self.gpio {
+            cpu.device_attach("gpio", Self::GPIO_START, dev)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Attaches the device at the base address if provided, advancing the base address past the
+/// device, or past the standard size of the device if left unmapped
+fn attach<T, F>(
+    cpu: &mut Processor,
+    base: &mut u32,
+    name: &str,
+    dev: Option<Rc<RefCell<T>>>,
+    unmapped_len: F,
+) -> Result<(), ProcessorError>
+where
+    T: MemorySegment + ProcessorDevice + 'static,
+    F: FnOnce() -> u32,
+{
+    let len = match dev {
+        Some(dev) => {
+            let len = dev.borrow().len();
+            cpu.device_attach(name, *base, dev)?;
+            len
+        }
+        None => unmapped_len(),
+    };
+
+    *base += len;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_machine_layout() {
+        let serial = Rc::new(RefCell::new(SerialInputOutputDevice::new(16)));
+        let keyboard = Rc::new(RefCell::new(KeyboardDevice::new(16)));
+        let trap = Rc::new(RefCell::new(TrapInfoDevice::new()));
+
+        let mut cpu = Processor::new();
+        MachineBuilder::new()
+            .serial(serial)
+            .keyboard(keyboard)
+            .trap_info(trap)
+            .build(&mut cpu)
+            .unwrap();
+
+        // Unmapped devices keep their space, such that the keyboard stays at the same address
+        let devices = cpu.devices().map(|d| (d.name, d.base)).collect::<Vec<_>>();
+        assert_eq!(
+            devices,
+            [
+                ("serial".into(), Some(MachineBuilder::DEVICE_START)),
+                ("keyboard".into(), Some(MachineBuilder::DEVICE_START + 0x80)),
+                ("trap info".into(), Some(MachineBuilder::TRAP_INFO_START)),
+            ]
+        );
+
+        assert!(cpu
+            .memory_inspect_u32(MachineBuilder::DEVICE_START - 4)
+            .is_ok());
+        assert!(cpu
+            .memory_inspect_u32(MachineBuilder::DEVICE_START + 0x20)
+            .is_err());
+    }
+
+    #[test]
+    fn test_standard_machine() {
+        let devices = StandardDevices::new(HostTimeDevice::cycle_clock(1));
+
+        let mut cpu = Processor::new();
+        MachineBuilder::standard(&devices)
+            .without(StandardDevice::Log)
+            .build(&mut cpu)
+            .unwrap();
+
+        let names = cpu.devices().map(|d| d.name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "serial",
+                "clock",
+                "host time",
+                "keyboard",
+                "display",
+                "disk",
+                "mux",
+                "protection",
+                "trap info",
+                "gpio"
+            ]
+        );
+
+        let bases = cpu.devices().filter_map(|d| d.base).collect::<Vec<_>>();
+        assert_eq!(bases[1], MachineBuilder::DEVICE_START + 0x20);
+        assert_eq!(bases[2], MachineBuilder::DEVICE_START + 0x60);
+        assert!(bases[6] + DEVICE_MEM_SIZE <= MachineBuilder::PROTECTION_START);
+        assert_eq!(bases[9], MachineBuilder::GPIO_START);
+    }
+}
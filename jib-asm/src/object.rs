@@ -0,0 +1,1020 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
+
+use jib::cpu::{Interrupt, Processor};
+use jib::debug_info::{DebugInfo, SourceLocation};
+use jib::memory::MemoryImage;
+
+use crate::expression::Expression;
+use crate::{
+    AsmToken, AsmTokenLoc, AssemblerError, AssemblerErrorLoc, InstructionList, LocationInfo,
+};
+
+/// Provides the value to be resolved for a relocation when linking
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelocationKind {
+    /// Inserts the absolute address of the provided label
+    Address(String),
+    /// Inserts the named instruction, replacing any label arguments with the offset to the label
+    /// and any expression arguments with their value
+    Operation(String, Vec<String>),
+    /// Inserts the value of the expression, where labels provide their absolute address
+    Expression(String),
+    /// Inserts the signed offset from the word following the relocation to the label, such that
+    /// adding the address of the following word provides the label address wherever the
+    /// program is loaded
+    Offset(String),
+}
+
+/// Provides a word within a section that must be resolved when linking
+#[derive(Debug, Clone)]
+pub struct Relocation {
+    pub offset: u32,
+    pub kind: RelocationKind,
+    pub loc: LocationInfo,
+}
+
+/// Provides the interrupt or reset vector to be filled in by a vector directive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VectorTarget {
+    /// The handler used for any interrupt vector not otherwise specified
+    Default,
+    /// The vector stored at the provided address
+    Address(u32),
+}
+
+impl VectorTarget {
+    /// Provides the addresses of all hardware and software interrupt vectors
+    pub fn interrupt_addresses() -> impl Iterator<Item = u32> {
+        (0..Processor::NUM_INTERRUPT)
+            .flat_map(|i| [Interrupt::Hardware(i), Interrupt::Software(i)])
+            .filter_map(|int| Processor::interrupt_address(int).ok())
+    }
+}
+
+impl fmt::Display for VectorTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::Address(addr) => write!(f, "0x{addr:x}"),
+        }
+    }
+}
+
+/// Provides a vector table entry, to be filled in with the address of the handler label when linking
+#[derive(Debug, Clone)]
+pub struct VectorEntry {
+    pub target: VectorTarget,
+    pub label: String,
+    pub loc: LocationInfo,
+}
+
+/// Provides a contiguous section of assembled data. Sections with an origin are placed at that
+/// absolute address, while sections without an origin are placed by the linker. Sections within
+/// a bank are placed at addresses within the bank window, separately from unbanked memory
+#[derive(Debug, Clone, Default)]
+pub struct ObjectSection {
+    pub name: String,
+    pub origin: Option<u32>,
+    pub bank: Option<u8>,
+    pub size: u32,
+    pub values: BTreeMap<u32, u8>,
+    pub labels: BTreeMap<String, u32>,
+    pub relocations: Vec<Relocation>,
+    /// The source line of each instruction word, by offset within the section
+    pub lines: BTreeMap<u32, usize>,
+}
+
+impl ObjectSection {
+    fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.labels.is_empty() && self.relocations.is_empty()
+    }
+}
+
+/// Provides an assembled module, with unresolved label references, that may be linked
+/// together with other modules into a final memory image
+#[derive(Debug, Clone, Default)]
+pub struct ObjectFile {
+    pub sections: Vec<ObjectSection>,
+    pub vectors: Vec<VectorEntry>,
+}
+
+impl ObjectFile {
+    const HEADER: &'static str = "jobj 1";
+
+    /// Defines the name of the initial section, starting at address 0
+    pub const DEFAULT_SECTION: &'static str = "text";
+
+    /// Builds an object file from the provided assembly tokens
+    pub fn from_tokens(tokens: &[AsmTokenLoc]) -> Result<Self, AssemblerErrorLoc> {
+        let mut builder = ObjectBuilder::new();
+
+        for t in tokens.iter() {
+            builder.add_token(t)?;
+        }
+
+        Ok(builder.finish())
+    }
+
+    /// Converts the object file into its text representation
+    pub fn to_text(&self) -> String {
+        let mut s = String::new();
+        writeln!(s, "{}", Self::HEADER).unwrap();
+
+        for v in self.vectors.iter() {
+            writeln!(s, "vector {} {} {}", v.target, v.loc.line, v.label).unwrap();
+        }
+
+        for sec in self.sections.iter() {
+            let origin = match sec.origin {
+                Some(o) => format!("0x{o:x}"),
+                None => "-".into(),
+            };
+            write!(s, "section {} {origin} 0x{:x}", sec.name, sec.size).unwrap();
+            match sec.bank {
+                Some(b) => writeln!(s, " {b}").unwrap(),
+                None => writeln!(s).unwrap(),
+            }
+
+            for (lbl, offset) in sec.labels.iter() {
+                writeln!(s, "label {lbl} 0x{offset:x}").unwrap();
+            }
+
+            let mut run: Option<(u32, Vec<u8>)> = None;
+            for (offset, val) in sec.values.iter() {
+                run = match run {
+                    Some((start, mut vals)) if start + vals.len() as u32 == *offset => {
+                        vals.push(*val);
+                        Some((start, vals))
+                    }
+                    prev => {
+                        if let Some((start, vals)) = prev {
+                            Self::write_data(&mut s, start, &vals);
+                        }
+                        Some((*offset, vec![*val]))
+                    }
+                };
+            }
+
+            if let Some((start, vals)) = run {
+                Self::write_data(&mut s, start, &vals);
+            }
+
+            for r in sec.relocations.iter() {
+                write!(s, "reloc 0x{:x} {} ", r.offset, r.loc.line).unwrap();
+                match &r.kind {
+                    RelocationKind::Address(lbl) => writeln!(s, "addr {lbl}").unwrap(),
+                    RelocationKind::Expression(expr) => writeln!(s, "expr {expr}").unwrap(),
+                    RelocationKind::Offset(lbl) => writeln!(s, "rel {lbl}").unwrap(),
+                    RelocationKind::Operation(name, args) => {
+                        writeln!(s, "inst {name} {}", args.join(" ")).unwrap()
+                    }
+                }
+            }
+
+            for (offset, line) in sec.lines.iter() {
+                writeln!(s, "line 0x{offset:x} {line}").unwrap();
+            }
+        }
+
+        s
+    }
+
+    fn write_data(s: &mut String, start: u32, vals: &[u8]) {
+        let hex = vals.iter().map(|v| format!("{v:02x}")).collect::<String>();
+        writeln!(s, "data 0x{start:x} {hex}").unwrap();
+    }
+
+    /// Reads an object file from its text representation
+    pub fn from_text(txt: &str) -> Result<Self, AssemblerErrorLoc> {
+        let mut obj = Self::default();
+
+        for (i, l) in txt.lines().enumerate() {
+            let loc = LocationInfo {
+                line: i + 1,
+                full_line: Some(l.to_string()),
+                base_loc: None,
+                file: None,
+            };
+
+            let err = |msg: &str| AssemblerErrorLoc {
+                err: AssemblerError::InvalidObject(msg.into()),
+                loc: loc.clone(),
+            };
+
+            let words = l.split_whitespace().collect::<Vec<_>>();
+
+            if i == 0 {
+                if l.trim() != Self::HEADER {
+                    return Err(err("unknown object header"));
+                }
+                continue;
+            }
+
+            let parse_num = |s: &str| match s.strip_prefix("0x") {
+                Some(h) => u32::from_str_radix(h, 16).ok(),
+                None => s.parse::<u32>().ok(),
+            };
+
+            match words.as_slice() {
+                [] => (),
+                ["vector", target, line, label] => {
+                    let target = match *target {
+                        "default" => VectorTarget::Default,
+                        t => VectorTarget::Address(
+                            parse_num(t).ok_or_else(|| err("invalid vector address"))?,
+                        ),
+                    };
+
+                    obj.vectors.push(VectorEntry {
+                        target,
+                        label: label.to_string(),
+                        loc: LocationInfo {
+                            line: line.parse::<usize>().map_err(|_| err("invalid line"))?,
+                            full_line: None,
+                            base_loc: None,
+                            file: None,
+                        },
+                    });
+                }
+                ["section", name, origin, size, bank @ ..] if bank.len() <= 1 => {
+                    let origin = match *origin {
+                        "-" => None,
+                        o => Some(parse_num(o).ok_or_else(|| err("invalid section origin"))?),
+                    };
+                    let bank = match bank.first() {
+                        Some(b) => Some(b.parse::<u8>().map_err(|_| err("invalid section bank"))?),
+                        None => None,
+                    };
+                    obj.sections.push(ObjectSection {
+                        name: name.to_string(),
+                        origin,
+                        bank,
+                        size: parse_num(size).ok_or_else(|| err("invalid section size"))?,
+                        ..Default::default()
+                    });
+                }
+                [kind, rest @ ..] => {
+                    let sec = match obj.sections.last_mut() {
+                        Some(s) => s,
+                        None => return Err(err("entry provided before section")),
+                    };
+
+                    match (*kind, rest) {
+                        ("label", [name, offset]) => {
+                            let offset = parse_num(offset).ok_or_else(|| err("invalid offset"))?;
+                            sec.labels.insert(name.to_string(), offset);
+                        }
+                        ("data", [offset, hex]) => {
+                            let offset = parse_num(offset).ok_or_else(|| err("invalid offset"))?;
+                            if hex.len() % 2 != 0 {
+                                return Err(err("invalid data length"));
+                            }
+
+                            for j in 0..hex.len() / 2 {
+                                let val = u8::from_str_radix(&hex[2 * j..2 * j + 2], 16)
+                                    .map_err(|_| err("invalid data"))?;
+                                sec.values.insert(offset + j as u32, val);
+                            }
+                        }
+                        ("line", [offset, line]) => {
+                            let offset = parse_num(offset).ok_or_else(|| err("invalid offset"))?;
+                            let line = line.parse::<usize>().map_err(|_| err("invalid line"))?;
+                            sec.lines.insert(offset, line);
+                        }
+                        ("reloc", [offset, line, rkind, args @ ..]) => {
+                            let offset = parse_num(offset).ok_or_else(|| err("invalid offset"))?;
+                            let line = line.parse::<usize>().map_err(|_| err("invalid line"))?;
+
+                            let kind = match (*rkind, args) {
+                                ("addr", [lbl]) => RelocationKind::Address(lbl.to_string()),
+                                ("expr", [expr]) => RelocationKind::Expression(expr.to_string()),
+                                ("rel", [lbl]) => RelocationKind::Offset(lbl.to_string()),
+                                ("inst", [name, args @ ..]) => RelocationKind::Operation(
+                                    name.to_string(),
+                                    args.iter().map(|a| a.to_string()).collect(),
+                                ),
+                                _ => return Err(err("invalid relocation")),
+                            };
+
+                            sec.relocations.push(Relocation {
+                                offset,
+                                kind,
+                                loc: LocationInfo {
+                                    line,
+                                    full_line: None,
+                                    base_loc: None,
+                                    file: None,
+                                },
+                            });
+                        }
+                        _ => return Err(err("unknown object entry")),
+                    }
+                }
+            }
+        }
+
+        Ok(obj)
+    }
+}
+
+/// Builds the sections of an object file from assembly tokens
+struct ObjectBuilder {
+    sections: Vec<ObjectSection>,
+    vectors: Vec<VectorEntry>,
+    addr: u32,
+    bank: Option<u8>,
+    source_line: Option<usize>,
+}
+
+impl ObjectBuilder {
+    fn new() -> Self {
+        Self {
+            sections: vec![ObjectSection {
+                name: ObjectFile::DEFAULT_SECTION.into(),
+                origin: Some(0),
+                ..Default::default()
+            }],
+            vectors: Vec::new(),
+            addr: 0,
+            bank: None,
+            source_line: None,
+        }
+    }
+
+    fn current(&mut self) -> &mut ObjectSection {
+        self.sections.last_mut().unwrap()
+    }
+
+    fn start_section(&mut self, name: String, origin: Option<u32>) {
+        let addr = self.addr;
+        self.current().size = addr;
+
+        let bank = self.bank;
+        self.sections.push(ObjectSection {
+            name,
+            origin,
+            bank,
+            ..Default::default()
+        });
+        self.addr = 0;
+    }
+
+    fn add_token(&mut self, t: &AsmTokenLoc) -> Result<(), AssemblerErrorLoc> {
+        let loc = t.loc.clone();
+
+        match &t.tok {
+            AsmToken::AlignInstruction => self.align_boundary(Processor::BYTES_PER_WORD),
+            AsmToken::AlignBoundary(n) => self.align_boundary(*n),
+            AsmToken::OperationLiteral(op) => {
+                let line = self.source_line.unwrap_or(loc.root_line());
+                let offset = self.add_bytes(&op.to_u32().to_be_bytes(), loc)?;
+                self.current().lines.insert(offset, line);
+            }
+            AsmToken::ChangeAddress(new_addr) => {
+                let base = self.current().origin.unwrap_or(0);
+                if *new_addr < base + self.addr {
+                    return Err(AssemblerErrorLoc {
+                        err: AssemblerError::CannotBackupAddress(*new_addr),
+                        loc,
+                    });
+                } else {
+                    self.addr = *new_addr - base;
+                }
+            }
+            AsmToken::ChangeOrigin(origin) => {
+                let name = self.current().name.clone();
+                self.start_section(name, Some(*origin));
+            }
+            AsmToken::ChangeSection(name) => self.start_section(name.clone(), None),
+            AsmToken::ChangeBank(bank) => {
+                let name = self.current().name.clone();
+                self.bank = Some(*bank);
+                self.start_section(name, None);
+            }
+            AsmToken::LiteralText(s) => {
+                for c in s.chars() {
+                    let bv = match jib::text::character_to_byte(c) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            return Err(AssemblerErrorLoc {
+                                err: AssemblerError::from(e),
+                                loc,
+                            });
+                        }
+                    };
+                    self.add_bytes(&[bv], loc.clone())?;
+                }
+                self.add_bytes(&[0], loc.clone())?;
+            }
+            AsmToken::Zero(n) => {
+                for _ in 0..*n {
+                    self.add_bytes(&[0], loc.clone())?;
+                }
+            }
+            AsmToken::Literal1(i) => {
+                self.add_bytes(&[*i], loc)?;
+            }
+            AsmToken::Literal2(i) => {
+                self.add_bytes(&i.to_be_bytes(), loc)?;
+            }
+            AsmToken::Literal4(i) => {
+                self.add_bytes(&i.to_be_bytes(), loc)?;
+            }
+            AsmToken::CreateLabel(lbl) => {
+                let addr = self.addr;
+                if self.current().labels.insert(lbl.into(), addr).is_some() {
+                    return Err(AssemblerErrorLoc {
+                        err: AssemblerError::DuplicateLabel(lbl.to_string()),
+                        loc,
+                    });
+                }
+            }
+            AsmToken::LoadLoc(lbl) => {
+                self.add_relocation(RelocationKind::Address(lbl.into()), loc)?;
+            }
+            AsmToken::LoadRel(lbl) => {
+                self.add_relocation(RelocationKind::Offset(lbl.into()), loc)?;
+            }
+            AsmToken::LoadExpr(expr) => {
+                self.add_relocation(RelocationKind::Expression(expr.into()), loc)?;
+            }
+            AsmToken::Operation(name, args) => {
+                let line = self.source_line.unwrap_or(loc.root_line());
+                let offset = self
+                    .add_relocation(RelocationKind::Operation(name.into(), args.to_owned()), loc)?;
+                self.current().lines.insert(offset, line);
+            }
+            AsmToken::Vector(target, label) => {
+                if self.vectors.iter().any(|v| v.target == *target) {
+                    return Err(AssemblerErrorLoc {
+                        err: AssemblerError::DuplicateVector(*target),
+                        loc,
+                    });
+                }
+
+                self.vectors.push(VectorEntry {
+                    target: *target,
+                    label: label.into(),
+                    loc,
+                });
+            }
+            AsmToken::SourceLine(line) => self.source_line = Some(*line),
+        }
+
+        Ok(())
+    }
+
+    fn add_relocation(
+        &mut self,
+        kind: RelocationKind,
+        loc: LocationInfo,
+    ) -> Result<u32, AssemblerErrorLoc> {
+        let offset = self.add_bytes(&0u32.to_be_bytes(), loc.clone())?;
+        self.current()
+            .relocations
+            .push(Relocation { offset, kind, loc });
+        Ok(offset)
+    }
+
+    fn add_bytes(&mut self, vals: &[u8], loc: LocationInfo) -> Result<u32, AssemblerErrorLoc> {
+        self.align_boundary(vals.len() as u32);
+        let base = self.addr;
+        for v in vals {
+            let addr = self.addr;
+            if self.current().values.insert(addr, *v).is_some() {
+                return Err(AssemblerErrorLoc {
+                    err: AssemblerError::AddressTaken(addr),
+                    loc,
+                });
+            }
+            self.addr += 1;
+        }
+        Ok(base)
+    }
+
+    fn align_boundary(&mut self, val: u32) {
+        let addr = self.current().origin.unwrap_or(0) + self.addr;
+        if val > 0 && addr % val != 0 {
+            self.addr += val - (addr % val);
+        }
+    }
+
+    fn finish(mut self) -> ObjectFile {
+        let addr = self.addr;
+        self.current().size = addr;
+
+        ObjectFile {
+            sections: self
+                .sections
+                .into_iter()
+                .filter(|s| !s.is_empty())
+                .collect(),
+            vectors: self.vectors,
+        }
+    }
+}
+
+/// Provides the placement of a single section within a linked memory image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkedSection {
+    pub name: String,
+    pub base: u32,
+    pub size: u32,
+    pub bank: Option<u8>,
+}
+
+/// Provides the source line that produced an instruction word within a linked image, along with
+/// the index of the object file containing the line, in the order provided to the linker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineLocation {
+    pub object: usize,
+    pub line: usize,
+}
+
+/// Provides a linked memory image, along with the resolved label and section locations. The
+/// image is provided both as a flat image starting at address zero and as segments, where each
+/// segment is a contiguous range of linked values. Banked sections are provided as a separate
+/// image for each bank, addressed by their location within the bank window. The address of each
+/// word containing an absolute label address is kept, such that the program may later be
+/// relocated. The source line of each instruction outside of a bank is provided by address
+#[derive(Debug, Clone, Default)]
+pub struct LinkedImage {
+    pub bytes: Vec<u8>,
+    pub image: MemoryImage,
+    pub banks: BTreeMap<u8, MemoryImage>,
+    pub labels: HashMap<String, u32>,
+    pub sections: Vec<LinkedSection>,
+    pub absolute_refs: Vec<u32>,
+    pub lines: BTreeMap<u32, LineLocation>,
+}
+
+impl LinkedImage {
+    /// Provides the debug information for the image, with the labels and source lines of each
+    /// object file, where the provided files name the source of each object file in the order
+    /// provided to the linker. Lines of object files without a name are not included, nor are
+    /// generated instructions without a source line
+    pub fn debug_info(&self, files: &[String]) -> DebugInfo {
+        let lines = self
+            .lines
+            .iter()
+            .filter(|(_, loc)| loc.object < files.len() && loc.line > 0)
+            .map(|(addr, loc)| {
+                let src = SourceLocation {
+                    file: loc.object,
+                    line: loc.line,
+                };
+                (*addr, src)
+            })
+            .collect();
+
+        DebugInfo {
+            files: files.to_vec(),
+            labels: self.labels.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+            lines,
+        }
+    }
+}
+
+/// Parses a section base address argument, provided as NAME=ADDRESS, where the address may be
+/// decimal or hexadecimal with a 0x prefix. Section names are not case sensitive
+pub fn parse_section_base(s: &str) -> Option<(String, u32)> {
+    let (name, addr) = s.split_once('=')?;
+    Some((name.to_lowercase(), parse_address(addr).ok()?))
+}
+
+/// Parses an address argument, given as a decimal or hexadecimal number
+pub fn parse_address(s: &str) -> Result<u32, String> {
+    match s.strip_prefix("0x") {
+        Some(h) => u32::from_str_radix(h, 16).ok(),
+        None => s.parse().ok(),
+    }
+    .ok_or_else(|| format!("invalid address '{s}'"))
+}
+
+/// Links the provided object files into a single memory image, discarding the label and section
+/// locations. See [`link_image`] for details on section placement
+pub fn link(
+    objects: &[ObjectFile],
+    section_bases: &HashMap<String, u32>,
+) -> Result<Vec<u8>, AssemblerErrorLoc> {
+    link_image(objects, section_bases).map(|img| img.bytes)
+}
+
+/// Links the provided object files into a single memory image. Sections with an origin are
+/// placed at their absolute address. Remaining sections are grouped by name, in order of first
+/// appearance, and placed either at the base address provided for that section name or after
+/// the previously-placed sections. Banked sections are placed at their origin within the bank
+/// window, or otherwise directly after the previous section of the same bank. Any vector entries
+/// are then written into the vector table, with unspecified interrupt vectors pointing to the
+/// default handler, if provided
+pub fn link_image(
+    objects: &[ObjectFile],
+    section_bases: &HashMap<String, u32>,
+) -> Result<LinkedImage, AssemblerErrorLoc> {
+    let sections = objects
+        .iter()
+        .flat_map(|o| o.sections.iter())
+        .collect::<Vec<_>>();
+    let section_objects = objects
+        .iter()
+        .enumerate()
+        .flat_map(|(i, o)| o.sections.iter().map(move |_| i))
+        .collect::<Vec<_>>();
+
+    // Determine the base address of each section
+    let mut bases = vec![0; sections.len()];
+
+    let align = |addr: u32| addr.next_multiple_of(Processor::BYTES_PER_WORD);
+
+    let mut next_addr = 0;
+    let mut next_bank_addr = BTreeMap::new();
+    for (i, sec) in sections.iter().enumerate() {
+        match (sec.bank, sec.origin) {
+            (None, Some(origin)) => {
+                bases[i] = origin;
+                next_addr = next_addr.max(origin + sec.size);
+            }
+            (None, None) => (),
+            (Some(bank), origin) => {
+                let next = next_bank_addr.entry(bank).or_insert(0);
+                bases[i] = origin.unwrap_or(*next);
+                *next = align(bases[i] + sec.size);
+            }
+        }
+    }
+
+    let mut section_names = Vec::new();
+    for sec in sections.iter() {
+        if sec.origin.is_none() && sec.bank.is_none() && !section_names.contains(&&sec.name) {
+            section_names.push(&sec.name);
+        }
+    }
+
+    for name in section_names {
+        let fixed_base = section_bases.get(name);
+        let mut addr = align(*fixed_base.unwrap_or(&next_addr));
+
+        for (i, sec) in sections.iter().enumerate() {
+            if sec.origin.is_none() && sec.bank.is_none() && sec.name == *name {
+                bases[i] = addr;
+                addr = align(addr + sec.size);
+            }
+        }
+
+        if fixed_base.is_none() {
+            next_addr = addr;
+        }
+    }
+
+    // Determine the absolute address of each label
+    let mut labels = HashMap::new();
+    for (sec, base) in sections.iter().zip(bases.iter()) {
+        for (lbl, offset) in sec.labels.iter() {
+            if labels.insert(lbl.to_string(), base + offset).is_some() {
+                return Err(AssemblerErrorLoc {
+                    err: AssemblerError::DuplicateLabel(lbl.to_string()),
+                    loc: LocationInfo::default(),
+                });
+            }
+        }
+    }
+
+    // Place the section values into memory, or into the bank containing the section
+    let mut values = HashMap::new();
+    let mut bank_values: BTreeMap<u8, HashMap<u32, u8>> = BTreeMap::new();
+    for (sec, base) in sections.iter().zip(bases.iter()) {
+        let target = match sec.bank {
+            Some(b) => bank_values.entry(b).or_default(),
+            None => &mut values,
+        };
+
+        for (offset, val) in sec.values.iter() {
+            let addr = base + offset;
+            if target.insert(addr, *val).is_some() {
+                return Err(AssemblerErrorLoc {
+                    err: AssemblerError::AddressTaken(addr),
+                    loc: LocationInfo::default(),
+                });
+            }
+        }
+    }
+
+    // Fill in the vector table
+    let mut vectors = BTreeMap::new();
+    for v in objects.iter().flat_map(|o| o.vectors.iter()) {
+        let label_addr = match labels.get(&v.label) {
+            Some(addr) => *addr,
+            None => {
+                return Err(AssemblerErrorLoc {
+                    err: AssemblerError::UnknownLabel(v.label.clone()),
+                    loc: v.loc.clone(),
+                });
+            }
+        };
+
+        if vectors.insert(v.target, (label_addr, &v.loc)).is_some() {
+            return Err(AssemblerErrorLoc {
+                err: AssemblerError::DuplicateVector(v.target),
+                loc: v.loc.clone(),
+            });
+        }
+    }
+
+    if let Some(default) = vectors.remove(&VectorTarget::Default) {
+        for addr in VectorTarget::interrupt_addresses() {
+            vectors
+                .entry(VectorTarget::Address(addr))
+                .or_insert(default);
+        }
+    }
+
+    let mut absolute_refs = Vec::new();
+
+    for (target, (label_addr, loc)) in vectors {
+        if let VectorTarget::Address(addr) = target {
+            absolute_refs.push(addr);
+            for (i, b) in label_addr.to_be_bytes().into_iter().enumerate() {
+                let addr = addr + i as u32;
+                if values.insert(addr, b).is_some() {
+                    return Err(AssemblerErrorLoc {
+                        err: AssemblerError::AddressTaken(addr),
+                        loc: loc.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    // Resolve relocations
+    let inst_list = InstructionList::default();
+
+    let eval_expression = |expr: &str, loc: &LocationInfo| {
+        Expression::parse(expr)
+            .and_then(|e| e.evaluate(|lbl| labels.get(lbl).map(|v| *v as i64)))
+            .map_err(|e| AssemblerErrorLoc {
+                err: e.into(),
+                loc: loc.clone(),
+            })
+    };
+
+    for (sec, base) in sections.iter().zip(bases.iter()) {
+        for r in sec.relocations.iter() {
+            let addr = base + r.offset;
+
+            if sec.bank.is_none()
+                && matches!(
+                    r.kind,
+                    RelocationKind::Address(_) | RelocationKind::Expression(_)
+                )
+            {
+                absolute_refs.push(addr);
+            }
+
+            let insert_value = match &r.kind {
+                RelocationKind::Address(label) => {
+                    if let Some(loc) = labels.get(label) {
+                        *loc
+                    } else {
+                        return Err(AssemblerErrorLoc {
+                            err: AssemblerError::UnknownLabel(label.into()),
+                            loc: r.loc.clone(),
+                        });
+                    }
+                }
+                RelocationKind::Expression(expr) => eval_expression(expr, &r.loc)? as u32,
+                RelocationKind::Offset(label) => match labels.get(label) {
+                    Some(loc) => loc.wrapping_sub(addr + Processor::BYTES_PER_WORD),
+                    None => {
+                        return Err(AssemblerErrorLoc {
+                            err: AssemblerError::UnknownLabel(label.into()),
+                            loc: r.loc.clone(),
+                        });
+                    }
+                },
+                RelocationKind::Operation(name, args) => {
+                    let inst = match inst_list.get_instruction(name) {
+                        Some(i) => i,
+                        None => {
+                            return Err(AssemblerErrorLoc {
+                                err: AssemblerError::UnknownInstruction(name.into(), None),
+                                loc: r.loc.clone(),
+                            });
+                        }
+                    };
+
+                    // Create new arguments to get relative values for any label parameters,
+                    // and absolute values for any expression parameters
+                    let new_args = args
+                        .iter()
+                        .map(|a| match labels.get(a) {
+                            Some(v) => Ok(format!("{}", (*v as i32) - (addr as i32))),
+                            None if Expression::is_expression(a) => {
+                                eval_expression(a, &r.loc).map(|v| v.to_string())
+                            }
+                            None => Ok(a.to_string()),
+                        })
+                        .collect::<Result<_, _>>()?;
+
+                    // Call the instruction function and obtain the resulting parameters
+                    match inst(new_args) {
+                        Ok(v) => v.to_u32(),
+                        Err(err) => {
+                            return Err(AssemblerErrorLoc {
+                                err: err.into(),
+                                loc: r.loc.clone(),
+                            });
+                        }
+                    }
+                }
+            };
+
+            let target = match sec.bank {
+                Some(b) => bank_values.entry(b).or_default(),
+                None => &mut values,
+            };
+
+            for (i, b) in insert_value.to_be_bytes().iter().enumerate() {
+                target.insert(addr + i as u32, *b);
+            }
+        }
+    }
+
+    let mut lines = BTreeMap::new();
+    for ((sec, base), object) in sections.iter().zip(bases.iter()).zip(section_objects) {
+        if sec.bank.is_none() {
+            for (offset, line) in sec.lines.iter() {
+                lines.insert(
+                    base + offset,
+                    LineLocation {
+                        object,
+                        line: *line,
+                    },
+                );
+            }
+        }
+    }
+
+    let (bytes, image) = build_image(values);
+
+    Ok(LinkedImage {
+        bytes,
+        image,
+        banks: bank_values
+            .into_iter()
+            .map(|(b, vals)| (b, build_image(vals).1))
+            .collect(),
+        labels,
+        absolute_refs,
+        sections: sections
+            .iter()
+            .zip(bases)
+            .map(|(sec, base)| LinkedSection {
+                name: sec.name.clone(),
+                base,
+                size: sec.size,
+                bank: sec.bank,
+            })
+            .collect(),
+        lines,
+    })
+}
+
+/// Provides the flat memory image, starting at address zero, and the segmented memory image,
+/// where each segment is a contiguous range of the provided values
+pub(crate) fn build_image(values: impl IntoIterator<Item = (u32, u8)>) -> (Vec<u8>, MemoryImage) {
+    let image = MemoryImage::from_values(values.into_iter().collect());
+    (image.to_flat(), image)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::preprocess::preprocess_text;
+    use crate::TokenList;
+
+    fn build_object(txt: &str) -> ObjectFile {
+        let mut tokens = TokenList::default();
+        for l in preprocess_text(txt).unwrap() {
+            tokens.parse_line(&l.text, l.loc).unwrap();
+        }
+        tokens.to_object().unwrap()
+    }
+
+    #[test]
+    fn test_link_sections() {
+        let main = build_object(".loadloc start\n.org 0x400\n:start\njmpri func\n");
+        let func = build_object(".section code\n:func\nnoop\n:value\n.loadloc value\n");
+
+        let image = link(&[main, func], &HashMap::new()).unwrap();
+        assert_eq!(image.len(), 0x40c);
+        assert_eq!(image[0..4], 0x400u32.to_be_bytes());
+        assert_eq!(image[0x404..0x408], [0; 4]);
+        assert_eq!(image[0x408..0x40c], 0x408u32.to_be_bytes());
+
+        let mut bases = HashMap::new();
+        bases.insert("code".to_string(), 0x800);
+        let main = build_object(".loadloc func\n");
+        let func = build_object(".section code\n:func\nnoop\n");
+        let image = link(&[main, func], &bases).unwrap();
+        assert_eq!(image[0..4], 0x800u32.to_be_bytes());
+    }
+
+    #[test]
+    fn test_link_segments() {
+        let main = build_object(".loadloc start\n.org 0x400\n:start\nnoop\n.org 0x2000\n.u8 1\n");
+
+        let linked = link_image(&[main], &HashMap::new()).unwrap();
+        let segments = linked.image.segments();
+        assert_eq!(segments.len(), 3);
+        assert_eq!((segments[0].base, segments[0].data.len()), (0, 4));
+        assert_eq!((segments[1].base, segments[1].data.len()), (0x400, 4));
+        assert_eq!((segments[2].base, segments[2].data.len()), (0x2000, 1));
+        assert_eq!(linked.image.to_flat(), linked.bytes);
+    }
+
+    #[test]
+    fn test_link_vectors() {
+        let main = build_object(
+            ".vector reset start\n.vector #1 irq\n.vector default unused\n.org 0x400\n:start\nnoop\n",
+        );
+        let handlers = build_object(".section code\n:irq\nretint\n:unused\nretint\n");
+
+        let image = link(&[main, handlers], &HashMap::new()).unwrap();
+        assert_eq!(image[0..4], 0x400u32.to_be_bytes());
+        assert_eq!(image[4..8], [0; 4]);
+        assert_eq!(image[0x100..0x104], 0x408u32.to_be_bytes());
+        assert_eq!(image[0x104..0x108], 0x404u32.to_be_bytes());
+        assert_eq!(image[0x17c..0x180], 0x408u32.to_be_bytes());
+        assert_eq!(image[0x80..0x84], 0x408u32.to_be_bytes());
+
+        let dup = build_object(".vector #1 start\n:start\n");
+        assert!(matches!(
+            link(&[build_object(".vector #1 start\n"), dup], &HashMap::new()),
+            Err(AssemblerErrorLoc {
+                err: AssemblerError::DuplicateVector(VectorTarget::Address(0x104)),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_object_text() {
+        let obj = build_object(
+            ".vector @2 lbl\n.u8 3\n.section data\n:lbl\n.u16 0x1234\njmpri lbl\n.loadloc lbl\n",
+        );
+        let txt = obj.to_text();
+        let read = ObjectFile::from_text(&txt).unwrap();
+        assert_eq!(read.to_text(), txt);
+
+        assert_eq!(
+            link(&[obj], &HashMap::new()).unwrap(),
+            link(&[read], &HashMap::new()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_link_banks() {
+        let main = build_object(
+            ".org 0x400\n:start\njmpri far\n.bank 1\n.org 0x8000\n:far\nnoop\n.loadloc start\n.section more\n:after\n.u8 7\n.bank 2\n.org 0x8000\n.loadloc after\n",
+        );
+        let txt = main.to_text();
+        assert_eq!(ObjectFile::from_text(&txt).unwrap().to_text(), txt);
+
+        let linked = link_image(&[main], &HashMap::new()).unwrap();
+        assert_eq!(linked.bytes.len(), 0x404);
+        assert_eq!(linked.labels["far"], 0x8000);
+        assert_eq!(linked.labels["after"], 0x8008);
+        assert_eq!(linked.banks.len(), 2);
+
+        let bank1 = &linked.banks[&1];
+        assert_eq!(bank1.get(0x8004), Some(0));
+        assert_eq!(bank1.get(0x8006), Some(0x4));
+        assert_eq!(bank1.get(0x8008), Some(7));
+        assert_eq!(linked.banks[&2].get(0x8003), Some(0x8));
+        assert!(linked.sections.iter().any(|s| s.bank == Some(2)));
+    }
+
+    #[test]
+    fn test_debug_info() {
+        let main = build_object(".org 0x400\n:start\nnoop\n\n:next\nhalt\n");
+        let other = build_object(".section more\n:other\nnoop\n");
+        let linked = link_image(&[main, other], &HashMap::new()).unwrap();
+
+        let info = linked.debug_info(&["main.jsm".to_string()]);
+        assert_eq!(info.address_of("next"), Some(0x404));
+        assert_eq!(info.address_of("other"), Some(linked.labels["other"]));
+        assert_eq!(info.source_line(0x400), Some(("main.jsm", 3)));
+        assert_eq!(info.source_line(0x404), Some(("main.jsm", 6)));
+        assert_eq!(info.lines.len(), 2);
+
+        // Generated assembly may provide the lines of the source it was generated from
+        let generated = build_object(".org 0x400\nnoop\n.loc 20\nnoop\nnoop\n.loc 30\nhalt\n");
+        let linked = link_image(&[generated], &HashMap::new()).unwrap();
+        let info = linked.debug_info(&["gen.spc".to_string()]);
+        assert_eq!(info.source_line(0x400), Some(("gen.spc", 2)));
+        assert_eq!(info.source_line(0x404), Some(("gen.spc", 20)));
+        assert_eq!(info.source_line(0x408), Some(("gen.spc", 20)));
+        assert_eq!(info.source_line(0x40c), Some(("gen.spc", 30)));
+    }
+}
@@ -0,0 +1,461 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{expression::Expression, AssemblerError, AssemblerErrorLoc, LocationInfo, TokenList};
+
+/// Defines the maximum number of lines that repeat blocks may expand to, such that a mistaken
+/// count doesn't exhaust memory
+const MAX_REPEAT_LINES: usize = 1 << 20;
+
+/// Provides a single source line after preprocessing, along with the location it originated from
+#[derive(Debug, Clone)]
+pub struct SourceLine {
+    pub text: String,
+    pub loc: LocationInfo,
+}
+
+/// Provides the directories searched for the files named by include directives, after the
+/// directory of the including file
+#[derive(Debug, Clone, Default)]
+pub struct IncludePaths {
+    pub dirs: Vec<PathBuf>,
+}
+
+impl IncludePaths {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        Self { dirs }
+    }
+
+    /// Provides the path of the first file found with the provided name, relative to the
+    /// directory of the including file, or to the working directory if not known, and then to
+    /// each include directory in order
+    pub fn resolve(&self, name: &str, including: Option<&Path>) -> Option<PathBuf> {
+        let base = including.and_then(|p| p.parent()).unwrap_or(Path::new(""));
+        std::iter::once(base)
+            .chain(self.dirs.iter().map(|d| d.as_path()))
+            .map(|d| d.join(name))
+            .find(|p| p.is_file())
+    }
+}
+
+/// Preprocesses the provided assembly text, with included files relative to the working
+/// directory
+pub fn preprocess_text(txt: &str) -> Result<Vec<SourceLine>, AssemblerErrorLoc> {
+    preprocess_source(txt, None, &IncludePaths::default())
+}
+
+/// Preprocesses the provided assembly lines, as with [`preprocess_text`]
+pub fn preprocess_lines(txt: &[&str]) -> Result<Vec<SourceLine>, AssemblerErrorLoc> {
+    preprocess_file_lines(txt, None, &IncludePaths::default())
+}
+
+/// Preprocesses the assembly text of the file at the provided path, removing comments and empty
+/// lines, replacing each `.include "file"` line with the lines of the named file, normalizing
+/// the case of the remaining text, and expanding repeat blocks. Included files are searched for
+/// relative to the including file, and then within each of the include paths. Lines of included
+/// files keep the location of the include line as their base location
+pub fn preprocess_source(
+    txt: &str,
+    path: Option<&Path>,
+    includes: &IncludePaths,
+) -> Result<Vec<SourceLine>, AssemblerErrorLoc> {
+    preprocess_file_lines(&txt.lines().collect::<Vec<_>>(), path, includes)
+}
+
+fn preprocess_file_lines(
+    txt: &[&str],
+    path: Option<&Path>,
+    includes: &IncludePaths,
+) -> Result<Vec<SourceLine>, AssemblerErrorLoc> {
+    let mut stack = path.map(canonical_path).into_iter().collect();
+    let lines = read_lines(txt, path, None, includes, &mut stack)?;
+
+    let mut expanded = Vec::new();
+    expand_repeats(&lines, &mut HashMap::new(), &mut expanded)?;
+    Ok(expanded)
+}
+
+fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Reads the non-empty lines of a single file, recursively reading included files. The stack
+/// provides the files currently being read, such that a file including itself, directly or
+/// through other files, is reported rather than read forever
+fn read_lines(
+    txt: &[&str],
+    path: Option<&Path>,
+    include_loc: Option<&LocationInfo>,
+    includes: &IncludePaths,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Vec<SourceLine>, AssemblerErrorLoc> {
+    let mut lines = Vec::new();
+    let file = include_loc
+        .and(path)
+        .map(|p| Arc::from(p.display().to_string()));
+
+    for (i, l) in txt.iter().enumerate() {
+        let loc = LocationInfo {
+            line: i + 1,
+            full_line: Some(l.to_string()),
+            base_loc: include_loc.map(|b| Box::new(b.clone())),
+            file: file.clone(),
+        };
+
+        let text = TokenList::trim_line(l).trim();
+        let Some(name) = include_name(text) else {
+            let text = text.to_lowercase();
+            if !text.is_empty() {
+                lines.push(SourceLine { text, loc });
+            }
+            continue;
+        };
+
+        let err = |err: AssemblerError| AssemblerErrorLoc {
+            err,
+            loc: loc.clone(),
+        };
+
+        let name = name.map_err(|_| {
+            err(AssemblerError::InvalidInclude(
+                "expected a quoted file name".into(),
+            ))
+        })?;
+        let file = includes
+            .resolve(name, path)
+            .ok_or_else(|| err(AssemblerError::IncludeNotFound(name.into())))?;
+
+        let canonical = canonical_path(&file);
+        if stack.contains(&canonical) {
+            return Err(err(AssemblerError::IncludeCycle(name.into())));
+        }
+
+        let included = std::fs::read_to_string(&file)
+            .map_err(|e| err(AssemblerError::InvalidInclude(format!("{name} - {e}"))))?;
+
+        stack.push(canonical);
+        lines.extend(read_lines(
+            &included.lines().collect::<Vec<_>>(),
+            Some(&file),
+            Some(&loc),
+            includes,
+            stack,
+        )?);
+        stack.pop();
+    }
+
+    Ok(lines)
+}
+
+/// Provides the quoted file name of an include line, or None if the line is not an include
+fn include_name(text: &str) -> Option<Result<&str, ()>> {
+    let (directive, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    if !directive.eq_ignore_ascii_case(".include") {
+        return None;
+    }
+
+    Some(
+        rest.trim()
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .filter(|s| !s.is_empty() && !s.contains('"'))
+            .ok_or(()),
+    )
+}
+
+/// Provides the location of a line copied by a repeat block, with the repeat line as the base
+/// location of the line within the file being assembled
+fn repeat_location(loc: &LocationInfo, repeat: &LocationInfo) -> LocationInfo {
+    let base = match (&loc.file, &loc.base_loc) {
+        (Some(_), Some(b)) => repeat_location(b, repeat),
+        _ => repeat.clone(),
+    };
+
+    LocationInfo {
+        base_loc: Some(Box::new(base)),
+        ..loc.clone()
+    }
+}
+
+/// Expands each `.rept count [name]` block, up to the matching `.endr`, into count copies of the
+/// lines within it. Each copy replaces the optional iteration name with the iteration number,
+/// starting from zero, such that expressions may generate tables. The count may be an
+/// expression of numbers and constants defined by earlier `.equ` lines. Expanded lines keep the
+/// location of the repeat line as their base location
+fn expand_repeats(
+    lines: &[SourceLine],
+    constants: &mut HashMap<String, i64>,
+    output: &mut Vec<SourceLine>,
+) -> Result<(), AssemblerErrorLoc> {
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = &lines[i];
+        let words = TokenList::split_asm_delim(&line.text).unwrap_or_default();
+        let err = |err: AssemblerError| AssemblerErrorLoc {
+            err,
+            loc: line.loc.clone(),
+        };
+
+        match words.first().map(|w| w.as_str()) {
+            Some(".equ") if words.len() == 3 => {
+                // Constants that can't be evaluated here are reported by the assembler
+                if let Ok(val) = Expression::parse(&format!("({})", words[2]))
+                    .and_then(|e| e.evaluate(|s| constants.get(s).copied()))
+                {
+                    constants.entry(words[1].clone()).or_insert(val);
+                }
+            }
+            Some(".endr") => return Err(err(AssemblerError::UnmatchedEndRepeat)),
+            Some(".rept") => {
+                let (count, name) = match &words[1..] {
+                    [count] => (count, None),
+                    [count, name] => (count, Some(name)),
+                    args => return Err(err(AssemblerError::ArgumentCountMismatch(args.len(), 1))),
+                };
+
+                let count = Expression::parse(&format!("({count})"))
+                    .and_then(|e| e.evaluate(|s| constants.get(s).copied()))
+                    .map_err(|e| err(e.into()))?;
+
+                let end =
+                    repeat_end(lines, i).ok_or_else(|| err(AssemblerError::UnterminatedRepeat))?;
+                let body = &lines[i + 1..end];
+
+                let total = usize::try_from(count)
+                    .ok()
+                    .and_then(|c| c.checked_mul(body.len().max(1)));
+                if total.is_none_or(|t| t > MAX_REPEAT_LINES) {
+                    return Err(err(AssemblerError::InvalidRepeatCount(count)));
+                }
+
+                for n in 0..count {
+                    let copy = body
+                        .iter()
+                        .map(|l| SourceLine {
+                            text: match name {
+                                Some(name) => replace_word(&l.text, name, &n.to_string()),
+                                None => l.text.clone(),
+                            },
+                            loc: repeat_location(&l.loc, &line.loc),
+                        })
+                        .collect::<Vec<_>>();
+
+                    expand_repeats(&copy, constants, output)?;
+                }
+
+                i = end + 1;
+                continue;
+            }
+            _ => (),
+        }
+
+        output.push(line.clone());
+        i += 1;
+    }
+
+    Ok(())
+}
+
+/// Provides the index of the `.endr` line matching the `.rept` line at the provided index
+fn repeat_end(lines: &[SourceLine], start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+
+    for (i, l) in lines.iter().enumerate().skip(start) {
+        match l.text.split_whitespace().next() {
+            Some(".rept") => depth += 1,
+            Some(".endr") => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Replaces each whole word in the text, outside of quoted text, matching the name
+fn replace_word(text: &str, name: &str, value: &str) -> String {
+    let mut result = String::new();
+    let mut word = String::new();
+    let mut within_quote = false;
+    let mut is_escape = false;
+
+    for c in text.chars().chain(core::iter::once('\0')) {
+        if !within_quote && (c.is_ascii_alphanumeric() || c == '_') {
+            word.push(c);
+            continue;
+        }
+
+        result.push_str(if word == name { value } else { &word });
+        word.clear();
+
+        if within_quote {
+            within_quote = is_escape || c != '"';
+            is_escape = !is_escape && c == '\\';
+        } else {
+            within_quote = c == '"';
+        }
+
+        if c != '\0' {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Formats the preprocessed lines into assembly text, with a comment on each line
+/// indicating the source line that it originated from
+pub fn format_preprocessed(lines: &[SourceLine]) -> String {
+    let width = lines.iter().map(|l| l.text.len()).max().unwrap_or(0);
+
+    let mut s = String::new();
+
+    for l in lines {
+        let line_origin = |loc: &LocationInfo| match &loc.file {
+            Some(file) => format!("{file} line {}", loc.line),
+            None => format!("line {}", loc.line),
+        };
+
+        let mut origin = line_origin(&l.loc);
+        let mut base = l.loc.base_loc.as_ref();
+
+        while let Some(b) = base {
+            write!(origin, " <- {}", line_origin(b)).unwrap();
+            base = b.base_loc.as_ref();
+        }
+
+        writeln!(s, "{:width$} ; {origin}", l.text).unwrap();
+    }
+
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_preprocess_origin() {
+        let txt = "; header\n\nNOOP ; first\n  jmpri -4\n";
+        let lines = preprocess_text(txt).unwrap();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].text, "noop");
+        assert_eq!(lines[0].loc.line, 3);
+        assert_eq!(lines[1].text, "jmpri -4");
+        assert_eq!(lines[1].loc.line, 4);
+
+        assert_eq!(
+            format_preprocessed(&lines),
+            "noop     ; line 3\njmpri -4 ; line 4\n"
+        );
+    }
+
+    #[test]
+    fn test_repeat() {
+        let txt = ".equ size 2\n.rept (size + 1) i\n.u8 i\n.rept 2\nnoop\n.endr\n.endr\n\
+            .rept 2 n\n.text \"n\"\n.endr\n";
+        let lines = preprocess_text(txt).unwrap();
+        let text = lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>();
+
+        assert_eq!(
+            text,
+            [
+                ".equ size 2",
+                ".u8 0",
+                "noop",
+                "noop",
+                ".u8 1",
+                "noop",
+                "noop",
+                ".u8 2",
+                "noop",
+                "noop",
+                ".text \"n\"",
+                ".text \"n\""
+            ]
+        );
+        assert_eq!(
+            format_preprocessed(&lines[2..3]),
+            "noop ; line 5 <- line 4 <- line 2\n"
+        );
+
+        let err = |txt: &str| preprocess_text(txt).unwrap_err().err;
+        assert!(matches!(
+            err(".rept 2\nnoop\n"),
+            AssemblerError::UnterminatedRepeat
+        ));
+        assert!(matches!(
+            err("noop\n.endr\n"),
+            AssemblerError::UnmatchedEndRepeat
+        ));
+        assert!(matches!(
+            err(".rept -1\n.endr\n"),
+            AssemblerError::InvalidRepeatCount(-1)
+        ));
+        assert!(matches!(
+            err(".rept count\n.endr\n"),
+            AssemblerError::Expression(_)
+        ));
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = std::env::temp_dir().join(format!("jib-asm-preprocess-{}", std::process::id()));
+        let inc = dir.join("inc");
+        std::fs::create_dir_all(&inc).unwrap();
+        std::fs::write(dir.join("regs.jsm"), ".equ Count 2\n.include \"Dev.jsm\"\n").unwrap();
+        std::fs::write(inc.join("Dev.jsm"), "; device\nNOOP\n").unwrap();
+        std::fs::write(inc.join("loop.jsm"), ".include \"loop.jsm\"\n").unwrap();
+
+        let main = dir.join("main.jsm");
+        let includes = IncludePaths::new(vec![inc.clone()]);
+        let preprocess = |txt: &str| preprocess_source(txt, Some(&main), &includes);
+
+        let lines = preprocess(
+            "halt\n.INCLUDE \"regs.jsm\" ; registers\n.rept count\n.include \"Dev.jsm\"\n.endr\n",
+        )
+        .unwrap();
+        let text = lines.iter().map(|l| l.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(text, ["halt", ".equ count 2", "noop", "noop", "noop"]);
+
+        assert_eq!(lines[2].loc.line, 2);
+        assert_eq!(lines[2].loc.root_line(), 2);
+        assert_eq!(lines[3].loc.root_line(), 4);
+        let dev = inc.join("Dev.jsm").display().to_string();
+        let regs = dir.join("regs.jsm").display().to_string();
+        assert_eq!(
+            format_preprocessed(&lines[2..4]),
+            format!("noop ; {dev} line 2 <- {regs} line 2 <- line 2\nnoop ; {dev} line 2 <- line 4 <- line 3\n")
+        );
+
+        let err = |txt: &str| preprocess(txt).unwrap_err();
+        assert!(matches!(
+            err(".include \"missing.jsm\"").err,
+            AssemblerError::IncludeNotFound(_)
+        ));
+        assert!(matches!(
+            err(".include dev.jsm").err,
+            AssemblerError::InvalidInclude(_)
+        ));
+
+        let cycle = err("noop\n.include \"loop.jsm\"\n");
+        assert!(matches!(cycle.err, AssemblerError::IncludeCycle(_)));
+        assert_eq!(
+            cycle.loc.file.as_deref(),
+            Some(inc.join("loop.jsm").display().to_string().as_str())
+        );
+        assert_eq!(cycle.loc.root_line(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+use core::fmt;
+use std::collections::{BTreeMap, HashMap};
+
+use jib::cpu::Profile;
+
+use crate::unwind::Symbolizer;
+
+/// Provides a printable profile report, with the time spent after each label, followed by the
+/// most executed instructions and the most read and written memory addresses. Addresses are
+/// described relative to the nearest label at or before them, where known. Each list is limited
+/// to the provided number of lines
+pub struct ProfileReport<'a> {
+    pub profile: &'a Profile,
+    pub symbols: &'a Symbolizer,
+    pub limit: usize,
+}
+
+impl ProfileReport<'_> {
+    /// Provides the executions and cycles within each label, from the label with the most
+    /// cycles, where the name is None for addresses before the first label
+    pub fn labels(&self) -> Vec<(Option<&str>, u64, u64)> {
+        let mut totals = HashMap::<Option<&str>, (u64, u64)>::new();
+        for (addr, count) in self.profile.executions.iter() {
+            let name = self.symbols.nearest(*addr).map(|(_, n)| n);
+            let entry = totals.entry(name).or_default();
+            entry.0 += count;
+            entry.1 += self.profile.cycles.get(addr).copied().unwrap_or(0);
+        }
+
+        let mut labels = totals
+            .into_iter()
+            .map(|(name, (count, cycles))| (name, count, cycles))
+            .collect::<Vec<_>>();
+        labels.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        labels
+    }
+
+    fn percent(part: u64, total: u64) -> f64 {
+        if total == 0 {
+            0.0
+        } else {
+            part as f64 * 100.0 / total as f64
+        }
+    }
+
+    fn write_location(&self, f: &mut fmt::Formatter<'_>, addr: u32) -> fmt::Result {
+        match self.symbols.describe(addr) {
+            Some(s) => writeln!(f, "  <{s}>"),
+            None => writeln!(f),
+        }
+    }
+
+    fn write_accesses(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        title: &str,
+        counts: &BTreeMap<u32, u64>,
+    ) -> fmt::Result {
+        let mut counts = counts.iter().collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        writeln!(f, "{title}:")?;
+        for (addr, count) in counts.into_iter().take(self.limit) {
+            write!(f, "  0x{addr:08x} {count:>12}")?;
+            self.write_location(f, *addr)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ProfileReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let executions = self.profile.total_executions();
+        let cycles = self.profile.total_cycles();
+        writeln!(f, "{executions} instructions in {cycles} cycles")?;
+
+        writeln!(f, "labels:")?;
+        for (name, count, label_cycles) in self.labels().into_iter().take(self.limit) {
+            writeln!(
+                f,
+                "  {count:>12} {label_cycles:>12} {:>6.2}%  {}",
+                Self::percent(label_cycles, cycles),
+                name.unwrap_or("?")
+            )?;
+        }
+
+        writeln!(f, "instructions:")?;
+        for (addr, count) in self.profile.hot_spots().into_iter().take(self.limit) {
+            let spot_cycles = self.profile.cycles.get(&addr).copied().unwrap_or(0);
+            write!(
+                f,
+                "  0x{addr:08x} {count:>12} {spot_cycles:>12} {:>6.2}%",
+                Self::percent(spot_cycles, cycles)
+            )?;
+            self.write_location(f, addr)?;
+        }
+
+        self.write_accesses(f, "reads", &self.profile.accesses.reads)?;
+        self.write_accesses(f, "writes", &self.profile.accesses.writes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use jib::{cpu::Processor, memory::ReadWriteSegment};
+
+    use super::*;
+    use crate::{assemble_object, object::link_image, preprocess::preprocess_text};
+
+    #[test]
+    fn test_profile_report() {
+        let txt = "\
+.loadloc start
+.org 0x400
+:start
+ldn 6:u32
+.loadloc counter
+ldi 7:u16 4
+:loop
+ld 8:u32 6
+add 8:u32 8 7
+sav 6:u32 8
+sub 7:u32 7 1
+jmpri 0
+:done
+halt
+.align
+:counter
+.u32 0
+";
+        let obj = assemble_object(&preprocess_text(txt).unwrap()).unwrap();
+        let image = link_image(&[obj], &HashMap::new()).unwrap();
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x1000))))
+            .unwrap();
+        cpu.load_image(&image.image).unwrap();
+        cpu.start_profile();
+        for _ in 0..7 {
+            cpu.step().unwrap();
+        }
+
+        let profile = cpu.profile().unwrap();
+        let symbols = Symbolizer::new(&image.labels);
+        let report = ProfileReport {
+            profile: &profile,
+            symbols: &symbols,
+            limit: 2,
+        };
+
+        let labels = report.labels();
+        assert_eq!(labels[0].0, Some("loop"));
+        assert_eq!(labels[0].1, 5);
+        assert_eq!(labels[1].0, Some("start"));
+
+        let text = report.to_string();
+        assert!(text.starts_with("7 instructions in "));
+        assert!(text.contains("<counter>"));
+        assert!(text.contains("<start+0x8>"));
+    }
+}
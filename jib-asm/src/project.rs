@@ -0,0 +1,361 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::object::ObjectFile;
+use crate::preprocess::{format_preprocessed, preprocess_source, IncludePaths, SourceLine};
+use crate::{assemble_object, AssemblerErrorLoc};
+
+/// Provides a single named source file of a multi-file project, where the name is also used as
+/// the path of the file when searching for included files
+#[derive(Debug, Clone)]
+pub struct ProjectSource {
+    pub name: String,
+    pub text: String,
+}
+
+/// Provides an assembler error, along with the name of the source file it occurred in
+#[derive(Debug, Clone)]
+pub struct ProjectError {
+    pub name: String,
+    pub err: AssemblerErrorLoc,
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} - {}", self.name, self.err)
+    }
+}
+
+/// Provides the content hash of the source text used to key cached object files. The hash
+/// includes the assembler version, such that objects from other versions are not reused
+pub fn content_hash(text: &str) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    env!("CARGO_PKG_VERSION")
+        .bytes()
+        .chain([0])
+        .chain(text.bytes())
+        .fold(FNV_OFFSET, |h, b| (h ^ b as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Provides the assembled object files of previous builds, keyed by the content hash of the
+/// preprocessed source text, such that only files changed directly or through an included file
+/// must be assembled again
+#[derive(Debug, Clone, Default)]
+pub struct ObjectCache {
+    objects: HashMap<u64, ObjectFile>,
+    used: HashSet<u64>,
+    hits: usize,
+}
+
+impl ObjectCache {
+    const EXTENSION: &'static str = "jo";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the cached object files from the provided directory, where each object file is
+    /// named by its content hash. Files that cannot be read as objects are skipped, and a
+    /// missing directory provides an empty cache
+    pub fn load(dir: &Path) -> std::io::Result<Self> {
+        let mut cache = Self::new();
+
+        let entries = match std::fs::read_dir(dir) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(cache),
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != Self::EXTENSION) {
+                continue;
+            }
+
+            let hash = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| u64::from_str_radix(s, 16).ok());
+
+            if let Some(hash) = hash {
+                if let Ok(obj) = ObjectFile::from_text(&std::fs::read_to_string(&path)?) {
+                    cache.objects.insert(hash, obj);
+                }
+            }
+        }
+
+        Ok(cache)
+    }
+
+    /// Writes each cached object file to the provided directory, creating it if required
+    pub fn save(&self, dir: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+
+        for (hash, obj) in self.objects.iter() {
+            let path = dir.join(format!("{hash:016x}.{}", Self::EXTENSION));
+            if !path.exists() {
+                std::fs::write(path, obj.to_text())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Provides the cached object file for the preprocessed source text, if present
+    pub fn get(&self, text: &str) -> Option<&ObjectFile> {
+        self.objects.get(&content_hash(text))
+    }
+
+    pub fn insert(&mut self, text: &str, obj: ObjectFile) {
+        self.objects.insert(content_hash(text), obj);
+    }
+
+    /// Provides the key text of the preprocessed lines, being the text along with the file and
+    /// line each originated from, such that objects are reused only if every line would be
+    /// assembled the same
+    pub fn key_text(lines: &[SourceLine]) -> String {
+        format_preprocessed(lines)
+    }
+
+    /// Provides the number of object files reused from the cache since it was created
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Removes every cached object file not used by the most recent project build
+    pub fn retain_used(&mut self) {
+        self.objects.retain(|h, _| self.used.contains(h));
+    }
+}
+
+/// Preprocesses a single source file, with included files searched for relative to the source
+/// and then within the include paths
+pub fn preprocess_source_file(
+    source: &ProjectSource,
+    includes: &IncludePaths,
+) -> Result<Vec<SourceLine>, ProjectError> {
+    preprocess_source(&source.text, Some(Path::new(&source.name)), includes).map_err(|err| {
+        ProjectError {
+            name: source.name.clone(),
+            err,
+        }
+    })
+}
+
+/// Assembles a single source file into an object file
+pub fn assemble_source_file(
+    source: &ProjectSource,
+    includes: &IncludePaths,
+) -> Result<ObjectFile, ProjectError> {
+    let lines = preprocess_source_file(source, includes)?;
+    assemble_object(&lines).map_err(|err| ProjectError {
+        name: source.name.clone(),
+        err,
+    })
+}
+
+/// Assembles each source file on a pool of worker threads, providing the object files in the
+/// same order as the input sources. Sources are preprocessed before assembly, such that the
+/// cache key covers any included files. If a cache is provided, unchanged sources reuse the
+/// cached object file, and newly assembled object files are added to the cache. If any source
+/// fails to assemble, the error of the first failing source in input order is provided
+pub fn assemble_project(
+    sources: &[ProjectSource],
+    jobs: usize,
+    mut cache: Option<&mut ObjectCache>,
+    includes: &IncludePaths,
+) -> Result<Vec<ObjectFile>, ProjectError> {
+    let preprocessed = sources
+        .iter()
+        .map(|s| preprocess_source_file(s, includes))
+        .collect::<Vec<_>>();
+    let keys = preprocessed
+        .iter()
+        .map(|r| r.as_ref().ok().map(|lines| ObjectCache::key_text(lines)))
+        .collect::<Vec<_>>();
+
+    if let Some(c) = cache.as_deref_mut() {
+        c.used = keys.iter().flatten().map(|k| content_hash(k)).collect();
+    }
+
+    let results = preprocessed
+        .iter()
+        .zip(keys.iter())
+        .map(|(lines, key)| match (lines, key) {
+            (Err(e), _) => Some(Err(e.clone())),
+            (Ok(_), key) => cache
+                .as_deref()
+                .zip(key.as_deref())
+                .and_then(|(c, k)| c.get(k))
+                .cloned()
+                .map(Ok),
+        })
+        .collect::<Vec<_>>();
+
+    let pending = results
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| r.is_none().then_some(i))
+        .collect::<Vec<_>>();
+
+    if let Some(c) = cache.as_deref_mut() {
+        c.hits += results.iter().filter(|r| matches!(r, Some(Ok(_)))).count();
+    }
+
+    let next = AtomicUsize::new(0);
+    let assembled = Mutex::new(results);
+
+    std::thread::scope(|s| {
+        for _ in 0..jobs.clamp(1, pending.len().max(1)) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&ind) = pending.get(i) else {
+                    break;
+                };
+
+                let res = match &preprocessed[ind] {
+                    Ok(lines) => assemble_object(lines).map_err(|err| ProjectError {
+                        name: sources[ind].name.clone(),
+                        err,
+                    }),
+                    Err(e) => Err(e.clone()),
+                };
+                assembled.lock().unwrap()[ind] = Some(res);
+            });
+        }
+    });
+
+    let mut objects = Vec::with_capacity(sources.len());
+    for (key, res) in keys.iter().zip(assembled.into_inner().unwrap()) {
+        let obj = res.expect("all sources assembled")?;
+        if let (Some(c), Some(key)) = (cache.as_deref_mut(), key) {
+            c.insert(key, obj.clone());
+        }
+        objects.push(obj);
+    }
+
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::object::link_image;
+    use crate::preprocess::preprocess_source;
+
+    fn sources() -> Vec<ProjectSource> {
+        (0..8)
+            .map(|i| ProjectSource {
+                name: format!("file{i}.jsm"),
+                text: if i == 0 {
+                    ".loadloc value1\n.loadloc value7\n".into()
+                } else {
+                    format!(".section data{i}\n:value{i}\n.u8 {i}\n")
+                },
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_project() {
+        let srcs = sources();
+        let serial = srcs
+            .iter()
+            .map(|s| {
+                assemble_source_file(s, &IncludePaths::default())
+                    .unwrap()
+                    .to_text()
+            })
+            .collect::<Vec<_>>();
+
+        let objs = assemble_project(&srcs, 4, None, &IncludePaths::default()).unwrap();
+        assert_eq!(objs.iter().map(|o| o.to_text()).collect::<Vec<_>>(), serial);
+
+        let expected = link_image(&objs, &HashMap::new()).unwrap();
+        let objs = assemble_project(&srcs, 1, None, &IncludePaths::default()).unwrap();
+        assert_eq!(
+            link_image(&objs, &HashMap::new()).unwrap().bytes,
+            expected.bytes
+        );
+
+        let mut bad = srcs.clone();
+        bad[3].text = "notaninstruction\n".into();
+        bad[5].text = "alsonotaninstruction\n".into();
+        assert_eq!(
+            assemble_project(&bad, 4, None, &IncludePaths::default())
+                .unwrap_err()
+                .name,
+            "file3.jsm"
+        );
+    }
+
+    #[test]
+    fn test_object_cache() {
+        let mut srcs = sources();
+        let mut cache = ObjectCache::new();
+
+        let first = assemble_project(&srcs, 4, Some(&mut cache), &IncludePaths::default()).unwrap();
+        assert_eq!(cache.len(), srcs.len());
+        assert_eq!(cache.hits(), 0);
+
+        srcs[2].text.push_str(".u8 9\n");
+        let second =
+            assemble_project(&srcs, 4, Some(&mut cache), &IncludePaths::default()).unwrap();
+        assert_eq!(cache.hits(), srcs.len() - 1);
+        assert_eq!(cache.len(), srcs.len() + 1);
+        assert_eq!(first[1].to_text(), second[1].to_text());
+        assert_ne!(first[2].to_text(), second[2].to_text());
+
+        cache.retain_used();
+        assert_eq!(cache.len(), srcs.len());
+
+        let dir = std::env::temp_dir().join(format!("jib-asm-cache-{}", std::process::id()));
+        cache.save(&dir).unwrap();
+        let loaded = ObjectCache::load(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.len(), cache.len());
+        let key = ObjectCache::key_text(
+            &preprocess_source(&srcs[2].text, None, &Default::default()).unwrap(),
+        );
+        assert_eq!(loaded.get(&key).unwrap().to_text(), second[2].to_text());
+    }
+
+    #[test]
+    fn test_object_cache_includes() {
+        let dir = std::env::temp_dir().join(format!("jib-asm-include-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("value.jsm"), ".u8 1\n").unwrap();
+
+        let srcs = [ProjectSource {
+            name: dir.join("main.jsm").display().to_string(),
+            text: ":value\n.include \"value.jsm\"\n".into(),
+        }];
+        let includes = IncludePaths::default();
+        let mut cache = ObjectCache::new();
+
+        let first = assemble_project(&srcs, 1, Some(&mut cache), &includes).unwrap();
+        std::fs::write(dir.join("value.jsm"), ".u8 2\n").unwrap();
+        let second = assemble_project(&srcs, 1, Some(&mut cache), &includes).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(cache.hits(), 0);
+        assert_ne!(first[0].to_text(), second[0].to_text());
+
+        cache.retain_used();
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,257 @@
+use core::fmt;
+use std::ops::Range;
+
+use jib::cpu::{DebugRequest, Processor, ProcessorError, Register};
+use jib::memory::RelocationTable;
+
+use crate::object::{LinkedImage, VectorTarget};
+
+/// Provides error conditions for relocating a linked program
+#[derive(Debug, Clone)]
+pub enum RelocateError {
+    NoProgram,
+    AddressBounds(u32),
+    Processor(ProcessorError),
+}
+
+impl fmt::Display for RelocateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoProgram => write!(f, "No Program Sections to Relocate"),
+            Self::AddressBounds(base) => {
+                write!(f, "Program Relocated to 0x{base:08x} Exceeds Memory")
+            }
+            Self::Processor(e) => write!(f, "Processor Error - {e}"),
+        }
+    }
+}
+
+impl core::error::Error for RelocateError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Processor(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ProcessorError> for RelocateError {
+    fn from(value: ProcessorError) -> Self {
+        Self::Processor(value)
+    }
+}
+
+impl LinkedImage {
+    /// Provides the range of addresses containing the program sections. Sections within the
+    /// vector table are not included, as the vector table remains fixed when relocating
+    pub fn program_range(&self) -> Option<Range<u32>> {
+        let vector_end = VectorTarget::interrupt_addresses()
+            .max()
+            .unwrap_or_default()
+            + Processor::BYTES_PER_WORD;
+
+        let mut sections = self
+            .sections
+            .iter()
+            .filter(|s| s.size > 0 && s.bank.is_none() && s.base >= vector_end);
+
+        let first = sections.next()?;
+        let (start, end) = sections.fold((first.base, first.base + first.size), |(s, e), sec| {
+            (s.min(sec.base), e.max(sec.base + sec.size))
+        });
+
+        Some(start..end)
+    }
+
+    /// Provides the table used to load the program at another base address, being the program
+    /// range along with the absolute references of the linker
+    pub fn relocation_table(&self) -> Option<RelocationTable> {
+        Some(RelocationTable::new(
+            self.program_range()?,
+            self.absolute_refs.clone(),
+        ))
+    }
+
+    /// Provides the linked image with the program moved to start at the new base address.
+    /// Relative references within the program are unchanged, while each absolute reference to
+    /// an address within the program is updated to the relocated address
+    pub fn relocated(&self, new_base: u32) -> Result<LinkedImage, RelocateError> {
+        let table = checked_table(self, new_base)?;
+        let mv = |addr: u32| table.relocate_address(addr, new_base);
+
+        let image = table
+            .relocate_image(&self.image, new_base)
+            .map_err(|_| RelocateError::AddressBounds(new_base))?;
+
+        let mut sections = self.sections.clone();
+        for s in sections
+            .iter_mut()
+            .filter(|s| s.size > 0 && s.bank.is_none())
+        {
+            s.base = mv(s.base);
+        }
+
+        Ok(LinkedImage {
+            bytes: image.to_flat(),
+            image,
+            banks: self.banks.clone(),
+            labels: self
+                .labels
+                .iter()
+                .map(|(k, v)| (k.clone(), mv(*v)))
+                .collect(),
+            sections,
+            absolute_refs: self.absolute_refs.iter().map(|a| mv(*a)).collect(),
+            lines: self.lines.iter().map(|(a, l)| (mv(*a), *l)).collect(),
+        })
+    }
+}
+
+/// Provides the relocation table of the image, ensuring the program fits within memory at the
+/// new base address
+fn checked_table(image: &LinkedImage, new_base: u32) -> Result<RelocationTable, RelocateError> {
+    let table = image.relocation_table().ok_or(RelocateError::NoProgram)?;
+    table
+        .check_base(new_base)
+        .map_err(|_| RelocateError::AddressBounds(new_base))?;
+    Ok(table)
+}
+
+/// Moves the program within the memory of a paused processor to start at the new base address,
+/// providing the relocated image. The current memory contents of the program are moved, such
+/// that any modified data is kept, and the previous program locations are cleared. Absolute
+/// references from the relocation table, the program counter, and any breakpoints within the
+/// program are updated. Addresses calculated at runtime, such as return addresses on the
+/// stack, are not updated
+pub fn relocate_program(
+    cpu: &mut Processor,
+    image: &LinkedImage,
+    new_base: u32,
+) -> Result<LinkedImage, RelocateError> {
+    let table = checked_table(image, new_base)?;
+    let range = table.program.clone();
+    let mv = |addr: u32| table.relocate_address(addr, new_base);
+    let relocated = image.relocated(new_base)?;
+
+    let mut data = vec![0; range.len()];
+    cpu.memory_inspect_range(range.start, &mut data)?;
+
+    for addr in image.absolute_refs.iter() {
+        let val = cpu.memory_inspect_u32(*addr)?;
+        let new_val = mv(val);
+
+        if range.contains(addr) {
+            let offset = (addr - range.start) as usize;
+            data[offset..offset + 4].copy_from_slice(&new_val.to_be_bytes());
+        } else if new_val != val {
+            cpu.memory_load_range(*addr, &new_val.to_be_bytes())?;
+        }
+    }
+
+    cpu.memory_load_range(range.start, &vec![0; data.len()])?;
+    cpu.memory_load_range(new_base, &data)?;
+
+    let pc = cpu.get_current_pc()?;
+    cpu.debug_request(DebugRequest::WriteRegister(
+        Register::ProgramCounter.get_index(),
+        mv(pc),
+    ))?;
+
+    let breakpoints = cpu.breakpoints().collect::<Vec<_>>();
+    cpu.clear_breakpoints();
+    for brk in breakpoints {
+        cpu.add_breakpoint(mv(brk));
+    }
+
+    Ok(relocated)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assemble_object, object::link_image, preprocess::preprocess_text};
+    use jib::cpu::StepResult;
+    use jib::memory::{ReadOnlySegment, ReadWriteSegment};
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    #[test]
+    fn test_relocate_program() {
+        let txt = ".loadloc start\n.org 0x400\n:start\nnoop\njmpri start\n:ptr\n.loadloc value\n:value\n.u32 7\n";
+        let obj = assemble_object(&preprocess_text(txt).unwrap()).unwrap();
+        let image = link_image(&[obj], &HashMap::new()).unwrap();
+        assert_eq!(image.program_range(), Some(0x400..0x410));
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(
+            0,
+            Rc::new(RefCell::new(ReadOnlySegment::new(vec![0; 0x400]))),
+        )
+        .unwrap();
+        cpu.memory_add_segment(0x400, Rc::new(RefCell::new(ReadWriteSegment::new(0x1000))))
+            .unwrap();
+        cpu.load_image(&image.image).unwrap();
+        cpu.memory_set(0x40c, 9).unwrap();
+        cpu.add_breakpoint(0x404);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(1));
+
+        let moved = relocate_program(&mut cpu, &image, 0x1000).unwrap();
+        assert_eq!(moved.labels["start"], 0x1000);
+        assert_eq!(moved.program_range(), Some(0x1000..0x1010));
+
+        assert_eq!(cpu.get_current_pc().unwrap(), 0x1004);
+        assert_eq!(cpu.breakpoints().collect::<Vec<_>>(), [0x1004]);
+        assert_eq!(cpu.memory_inspect_u32(0).unwrap(), 0x1000);
+        assert_eq!(cpu.memory_inspect_u32(0x1008).unwrap(), 0x100c);
+        assert_eq!(cpu.memory_inspect_u32(0x100c).unwrap(), 0x09000007);
+        assert_eq!(cpu.memory_inspect_u32(0x408).unwrap(), 0);
+
+        assert_eq!(moved.bytes[0..4], 0x1000u32.to_be_bytes());
+        assert_eq!(moved.bytes[0x1008..0x100c], 0x100cu32.to_be_bytes());
+
+        cpu.remove_breakpoint(0x1004);
+        cpu.step().unwrap();
+        assert_eq!(cpu.get_current_pc().unwrap(), 0x1000);
+
+        assert!(matches!(
+            image.relocated(u32::MAX - 4),
+            Err(RelocateError::AddressBounds(_))
+        ));
+    }
+
+    #[test]
+    fn test_position_independent() {
+        let txt = "\
+.loadloc start
+.org 0x400
+:start
+ldn 6:u32
+.loadrel value
+add 6:u32 6 $pc
+ldri 7:u32 value
+halt
+:value
+.u32 7
+";
+        let obj = assemble_object(&preprocess_text(txt).unwrap()).unwrap();
+        assert!(obj.to_text().contains(" rel value\n"));
+        let image = link_image(&[obj], &HashMap::new()).unwrap();
+
+        let table = image.relocation_table().unwrap();
+        assert_eq!(table.program, 0x400..0x418);
+        assert_eq!(table.refs, [0]);
+
+        let moved = table.relocate_image(&image.image, 0x2000).unwrap();
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x4000))))
+            .unwrap();
+        cpu.load_image(&moved).unwrap();
+        while !cpu.halted() {
+            cpu.step().unwrap();
+        }
+
+        let regs = cpu.get_register_state().get_state();
+        assert_eq!(regs[6], 0x2014);
+        assert_eq!(regs[7], 7);
+    }
+}
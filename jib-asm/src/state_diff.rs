@@ -0,0 +1,282 @@
+use core::fmt;
+
+use jib::cpu::{CpuSnapshot, Register};
+
+use crate::unwind::Symbolizer;
+
+/// Defines the maximum number of bytes provided within a single memory difference
+const MAX_MEMORY_RUN: usize = 8;
+
+/// Provides a register with a different value in each snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterDiff {
+    pub index: usize,
+    pub before: u32,
+    pub after: u32,
+}
+
+/// Provides a run of consecutive bytes with different values in each snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryDiff {
+    pub address: u32,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+/// Provides the differences between two processor snapshots, such as a passing and a failing
+/// run of the same program.
+///
+/// Memory segments are matched by base address. The saved state of a memory segment is its
+/// contents, while device segments provide their internal state instead, and so differences
+/// within device segments are given relative to the segment base. Segments that are only
+/// present in one snapshot, or with a different state size, are listed separately
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    pub cycle_count: (u64, u64),
+    pub halted: (bool, bool),
+    pub registers: Vec<RegisterDiff>,
+    pub memory: Vec<MemoryDiff>,
+    pub mismatched_segments: Vec<u32>,
+}
+
+impl StateDiff {
+    pub fn new(before: &CpuSnapshot, after: &CpuSnapshot) -> Self {
+        let registers = before
+            .registers
+            .iter()
+            .zip(after.registers.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(index, (a, b))| RegisterDiff {
+                index,
+                before: *a,
+                after: *b,
+            })
+            .collect();
+
+        let mut memory = Vec::new();
+        let mut mismatched_segments = Vec::new();
+
+        for seg in before.memory.iter() {
+            let other = match after.memory.iter().find(|s| s.base == seg.base) {
+                Some(s) if s.data.len() == seg.data.len() => s,
+                _ => {
+                    mismatched_segments.push(seg.base);
+                    continue;
+                }
+            };
+
+            let mut offset = 0;
+            while offset < seg.data.len() {
+                if seg.data[offset] == other.data[offset] {
+                    offset += 1;
+                    continue;
+                }
+
+                let start = offset;
+                while offset < seg.data.len()
+                    && offset - start < MAX_MEMORY_RUN
+                    && seg.data[offset] != other.data[offset]
+                {
+                    offset += 1;
+                }
+
+                memory.push(MemoryDiff {
+                    address: seg.base + start as u32,
+                    before: seg.data[start..offset].to_vec(),
+                    after: other.data[start..offset].to_vec(),
+                });
+            }
+        }
+
+        mismatched_segments.extend(
+            after
+                .memory
+                .iter()
+                .map(|s| s.base)
+                .filter(|b| !before.memory.iter().any(|s| s.base == *b)),
+        );
+        mismatched_segments.sort();
+
+        Self {
+            cycle_count: (before.cycle_count, after.cycle_count),
+            halted: (before.halted, after.halted),
+            registers,
+            memory,
+            mismatched_segments,
+        }
+    }
+
+    /// Determines whether the register and memory state of both snapshots is the same
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.memory.is_empty() && self.mismatched_segments.is_empty()
+    }
+}
+
+/// Provides a printable side-by-side report of the differences, where addresses and register
+/// values that refer to a label are annotated with the symbol
+pub struct DiffReport<'a> {
+    pub diff: &'a StateDiff,
+    pub symbols: &'a Symbolizer,
+}
+
+impl DiffReport<'_> {
+    const COLUMN_WIDTH: usize = 36;
+
+    fn register_value(&self, index: usize, val: u32) -> String {
+        let sym = if index == Register::IDX_PROGRAM_COUNTER {
+            self.symbols.describe(val)
+        } else {
+            self.symbols.exact(val).map(|s| s.to_string())
+        };
+
+        match sym {
+            Some(s) => format!("0x{val:08x} <{s}>"),
+            None => format!("0x{val:08x}"),
+        }
+    }
+
+    fn row(f: &mut fmt::Formatter<'_>, name: &str, a: &str, b: &str) -> fmt::Result {
+        writeln!(f, "{name:<24} {a:<w$} | {b}", w = Self::COLUMN_WIDTH)
+    }
+}
+
+impl fmt::Display for DiffReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = |v: &[u8]| {
+            v.iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        Self::row(f, "", "Snapshot A", "Snapshot B")?;
+        Self::row(
+            f,
+            "cycles",
+            &self.diff.cycle_count.0.to_string(),
+            &self.diff.cycle_count.1.to_string(),
+        )?;
+        if self.diff.halted.0 != self.diff.halted.1 {
+            Self::row(
+                f,
+                "halted",
+                &self.diff.halted.0.to_string(),
+                &self.diff.halted.1.to_string(),
+            )?;
+        }
+
+        if self.diff.is_empty() {
+            return writeln!(f, "No register or memory differences");
+        }
+
+        for r in self.diff.registers.iter() {
+            let name = match Register::try_from(r.index) {
+                Ok(reg) => reg.to_string(),
+                Err(_) => r.index.to_string(),
+            };
+            Self::row(
+                f,
+                &name,
+                &self.register_value(r.index, r.before),
+                &self.register_value(r.index, r.after),
+            )?;
+        }
+
+        for m in self.diff.memory.iter() {
+            let name = match self.symbols.describe(m.address) {
+                Some(s) => format!("0x{:08x} <{s}>", m.address),
+                None => format!("0x{:08x}", m.address),
+            };
+            Self::row(f, &name, &bytes(&m.before), &bytes(&m.after))?;
+        }
+
+        for base in self.diff.mismatched_segments.iter() {
+            writeln!(f, "segment 0x{base:08x} does not match between snapshots")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jib::memory::SegmentState;
+    use std::collections::HashMap;
+
+    fn snapshot(pc: u32, data: Vec<u8>, cycles: u64) -> CpuSnapshot {
+        let mut registers = [0; jib::cpu::RegisterManager::REGISTER_COUNT];
+        registers[Register::IDX_PROGRAM_COUNTER] = pc;
+        CpuSnapshot {
+            registers,
+            interrupt_hold: None,
+            breakpoint_resume: None,
+            cycle_count: cycles,
+            halted: false,
+            memory: vec![SegmentState { base: 0x400, data }],
+        }
+    }
+
+    #[test]
+    fn test_state_diff() {
+        let mut data = vec![0; 0x20];
+        let a = snapshot(0x404, data.clone(), 10);
+        data[0x10] = 1;
+        data[0x11] = 2;
+        data[0x1f] = 3;
+        let b = snapshot(0x410, data, 12);
+
+        let diff = StateDiff::new(&a, &b);
+        assert_eq!(diff.cycle_count, (10, 12));
+        assert_eq!(
+            diff.registers,
+            [RegisterDiff {
+                index: Register::IDX_PROGRAM_COUNTER,
+                before: 0x404,
+                after: 0x410,
+            }]
+        );
+        assert_eq!(
+            diff.memory,
+            [
+                MemoryDiff {
+                    address: 0x410,
+                    before: vec![0, 0],
+                    after: vec![1, 2],
+                },
+                MemoryDiff {
+                    address: 0x41f,
+                    before: vec![0],
+                    after: vec![3],
+                },
+            ]
+        );
+        assert!(diff.mismatched_segments.is_empty());
+
+        let symbols = Symbolizer::new(&HashMap::from([
+            ("start".to_string(), 0x400),
+            ("value".to_string(), 0x410),
+        ]));
+        let report = DiffReport {
+            diff: &diff,
+            symbols: &symbols,
+        }
+        .to_string();
+        let lines = report
+            .lines()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines[2],
+            "pc (0) 0x00000404 <start+0x4> | 0x00000410 <value>"
+        );
+        assert_eq!(lines[3], "0x00000410 <value> 00 00 | 01 02");
+
+        let mut c = snapshot(0x404, vec![0; 4], 10);
+        c.memory[0].base = 0x800;
+        let diff = StateDiff::new(&a, &c);
+        assert_eq!(diff.mismatched_segments, [0x400, 0x800]);
+        assert!(StateDiff::new(&a, &a).is_empty());
+    }
+}
@@ -0,0 +1,649 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use jib::{
+    cpu::{CpuSnapshot, Processor, ProcessorError, ResetType, StepResult, StopReason},
+    device::{HypercallDevice, HypercallRequest},
+    memory::{MemorySegment, MemorySegmentError, ReadOnlySegment, ReadWriteSegment},
+};
+
+use crate::{
+    assemble_object,
+    coverage::Coverage,
+    object::{link_image, LinkedImage, ObjectFile},
+    preprocess::preprocess_text,
+    AssemblerError, AssemblerErrorLoc, LocationInfo,
+};
+
+/// Provides the guest runtime source, containing the test entry point and assertion functions
+pub const RUNTIME_SOURCE: &str = include_str!("../runtime/jtest.jsm");
+
+/// Defines the name of the section containing the addresses of each test function
+pub const TEST_SECTION: &str = "tests";
+
+/// Provides the result of a single guest test
+#[derive(Debug, Clone)]
+pub enum TestOutcome {
+    /// The test function returned without any failed assertions
+    Passed,
+    /// An assertion failed, with the actual and expected values reported by the test
+    Failed { actual: u32, expected: u32 },
+    /// The test did not complete within the instruction budget, or the cycle budget requested
+    /// by the test
+    Timeout,
+    /// The processor stopped without the test reporting a result
+    Incomplete,
+    /// The processor encountered an error while running the test
+    Error(ProcessorError),
+}
+
+impl TestOutcome {
+    pub fn is_pass(&self) -> bool {
+        matches!(self, Self::Passed)
+    }
+}
+
+impl fmt::Display for TestOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Passed => write!(f, "ok"),
+            Self::Failed { actual, expected } => write!(
+                f,
+                "FAILED - actual 0x{actual:08x} ({actual}), expected 0x{expected:08x} ({expected})"
+            ),
+            Self::Timeout => write!(f, "FAILED - timed out"),
+            Self::Incomplete => write!(f, "FAILED - stopped without reporting a result"),
+            Self::Error(e) => write!(f, "FAILED - {e}"),
+        }
+    }
+}
+
+/// Provides the outcome of a single named guest test
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub name: String,
+    pub address: u32,
+    pub outcome: TestOutcome,
+    pub instructions: usize,
+    /// The processor snapshots requested by the test, along with the tag provided for each
+    pub snapshots: Vec<(u32, CpuSnapshot)>,
+    /// The instructions executed by the test, if coverage is enabled
+    pub coverage: Option<Coverage>,
+}
+
+/// Provides the results of every test within a single suite, or the error that prevented the
+/// suite from being run
+#[derive(Debug, Clone)]
+pub struct SuiteResult {
+    pub name: String,
+    pub results: Result<Vec<TestResult>, String>,
+}
+
+impl SuiteResult {
+    /// Provides the number of tests that did not pass, with a suite that could not be run
+    /// counting as a single failure
+    pub fn failures(&self) -> usize {
+        match &self.results {
+            Ok(r) => r.iter().filter(|t| !t.outcome.is_pass()).count(),
+            Err(_) => 1,
+        }
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Provides a JUnit XML report for the provided suite results
+pub fn junit_report(suites: &[SuiteResult]) -> String {
+    let mut lines = vec!["<?xml version=\"1.0\" encoding=\"UTF-8\"?>".to_string()];
+
+    let total: usize = suites
+        .iter()
+        .map(|s| s.results.as_ref().map_or(1, |r| r.len()))
+        .sum();
+    let failures: usize = suites.iter().map(|s| s.failures()).sum();
+
+    lines.push(format!(
+        "<testsuites tests=\"{total}\" failures=\"{failures}\">"
+    ));
+
+    for suite in suites.iter() {
+        let name = xml_escape(&suite.name);
+
+        match &suite.results {
+            Ok(results) => {
+                lines.push(format!(
+                    "  <testsuite name=\"{name}\" tests=\"{}\" failures=\"{}\">",
+                    results.len(),
+                    suite.failures()
+                ));
+
+                for t in results.iter() {
+                    let case = format!(
+                        "    <testcase name=\"{}\" classname=\"{name}\"",
+                        xml_escape(&t.name)
+                    );
+                    if t.outcome.is_pass() {
+                        lines.push(format!("{case}/>"));
+                    } else {
+                        lines.push(format!("{case}>"));
+                        lines.push(format!(
+                            "      <failure message=\"{}\"/>",
+                            xml_escape(&t.outcome.to_string())
+                        ));
+                        lines.push("    </testcase>".to_string());
+                    }
+                }
+            }
+            Err(e) => {
+                lines.push(format!(
+                    "  <testsuite name=\"{name}\" tests=\"1\" failures=\"1\">"
+                ));
+                lines.push(format!(
+                    "    <testcase name=\"{name}\" classname=\"{name}\">"
+                ));
+                lines.push(format!("      <error message=\"{}\"/>", xml_escape(e)));
+                lines.push("    </testcase>".to_string());
+            }
+        }
+
+        lines.push("  </testsuite>".to_string());
+    }
+
+    lines.push("</testsuites>".to_string());
+    lines.join("\n")
+}
+
+/// Provides the memory-mapped device used by the runtime to obtain the test to run and to
+/// report the test result
+struct TestDevice {
+    test_address: u32,
+    stack_base: u32,
+    status: u32,
+    actual: u32,
+    expected: u32,
+}
+
+impl TestDevice {
+    const DEVICE_ID: u16 = 0x10;
+
+    const STATUS_PASS: u32 = 1;
+    const STATUS_FAIL: u32 = 2;
+
+    fn new(test_address: u32, stack_base: u32) -> Self {
+        Self {
+            test_address,
+            stack_base,
+            status: 0,
+            actual: 0,
+            expected: 0,
+        }
+    }
+
+    /// Provides the device memory words, with the device ID in the first two bytes
+    fn words(&self) -> [u32; 6] {
+        [
+            (Self::DEVICE_ID as u32) << 16,
+            self.test_address,
+            self.stack_base,
+            self.status,
+            self.actual,
+            self.expected,
+        ]
+    }
+}
+
+impl MemorySegment for TestDevice {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        let index = (offset / Processor::BYTES_PER_WORD) as usize;
+        let within = (offset % Processor::BYTES_PER_WORD) as usize;
+
+        match self.words().get(index) {
+            Some(v) => Ok(v.to_be_bytes()[within]),
+            None => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        let index = (offset / Processor::BYTES_PER_WORD) as usize;
+        let within = (offset % Processor::BYTES_PER_WORD) as usize;
+
+        let val = match index {
+            3 => &mut self.status,
+            4 => &mut self.actual,
+            5 => &mut self.expected,
+            _ => return Err(MemorySegmentError::InvalidMemoryWrite(offset, data)),
+        };
+
+        let mut bytes = val.to_be_bytes();
+        bytes[within] = data;
+        *val = u32::from_be_bytes(bytes);
+
+        Ok(())
+    }
+
+    /// Resets the memory segment
+    fn reset(&mut self) {
+        self.status = 0;
+        self.actual = 0;
+        self.expected = 0;
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        self.words().len() as u32 * Processor::BYTES_PER_WORD
+    }
+}
+
+/// Runs guest tests, each within a freshly-initialized processor
+pub struct TestRunner {
+    image: LinkedImage,
+    max_instructions: usize,
+    strict_encoding: bool,
+    coverage: bool,
+}
+
+impl TestRunner {
+    /// Defines the address that the test device is mapped to, as used by the runtime
+    pub const DEVICE_ADDRESS: u32 = 0xA000;
+
+    /// Defines the address that the hypercall device is mapped to, in test mode, as used by the
+    /// runtime
+    pub const HYPERCALL_ADDRESS: u32 = Self::DEVICE_ADDRESS + 0x20;
+
+    /// Defines the minimum stack size, in bytes, available to each test
+    pub const MIN_STACK_SIZE: u32 = 0x400;
+
+    /// Links the provided test objects together with the guest runtime
+    pub fn new(objects: &[ObjectFile], max_instructions: usize) -> Result<Self, AssemblerErrorLoc> {
+        let mut all_objects = vec![assemble_object(&preprocess_text(RUNTIME_SOURCE)?)?];
+        all_objects.extend_from_slice(objects);
+
+        let image = link_image(&all_objects, &HashMap::new())?;
+
+        let stack_base = Self::stack_base(&image);
+        if stack_base + Self::MIN_STACK_SIZE > Self::DEVICE_ADDRESS {
+            return Err(AssemblerErrorLoc {
+                err: AssemblerError::AddressTaken(Self::DEVICE_ADDRESS),
+                loc: LocationInfo::default(),
+            });
+        }
+
+        Ok(Self {
+            image,
+            max_instructions,
+            strict_encoding: false,
+            coverage: false,
+        })
+    }
+
+    /// Sets whether each test processor rejects undefined instruction encodings
+    pub fn set_strict_encoding(&mut self, strict: bool) {
+        self.strict_encoding = strict;
+    }
+
+    /// Sets whether the instructions executed by each test are recorded in the test result
+    pub fn set_coverage(&mut self, coverage: bool) {
+        self.coverage = coverage;
+    }
+
+    /// Provides the linked test image, where the runtime is the first object, followed by the
+    /// test objects in the order provided
+    pub fn image(&self) -> &LinkedImage {
+        &self.image
+    }
+
+    fn stack_base(image: &LinkedImage) -> u32 {
+        (image.bytes.len() as u32).next_multiple_of(Processor::BYTES_PER_WORD)
+    }
+
+    /// Provides the name and address of each registered test, in registration order
+    pub fn tests(&self) -> Vec<(String, u32)> {
+        let mut tests = Vec::new();
+
+        for sec in self
+            .image
+            .sections
+            .iter()
+            .filter(|s| s.name == TEST_SECTION)
+        {
+            for i in (0..sec.size).step_by(Processor::BYTES_PER_WORD as usize) {
+                let start = (sec.base + i) as usize;
+                let addr = match self.image.bytes.get(start..start + 4) {
+                    Some(b) => u32::from_be_bytes(b.try_into().unwrap()),
+                    None => continue,
+                };
+
+                let name = self
+                    .image
+                    .labels
+                    .iter()
+                    .filter(|(_, v)| **v == addr)
+                    .map(|(k, _)| k.clone())
+                    .min()
+                    .unwrap_or_else(|| format!("0x{addr:08x}"));
+
+                tests.push((name, addr));
+            }
+        }
+
+        tests
+    }
+
+    /// Runs each registered test, providing the results in registration order
+    pub fn run_all(&self) -> Vec<TestResult> {
+        self.tests()
+            .into_iter()
+            .map(|(name, addr)| self.run_test(name, addr))
+            .collect()
+    }
+
+    /// Runs a single test function within a new processor
+    pub fn run_test(&self, name: String, address: u32) -> TestResult {
+        let device = Rc::new(RefCell::new(TestDevice::new(
+            address,
+            Self::stack_base(&self.image),
+        )));
+
+        let hypercall = Rc::new(RefCell::new(HypercallDevice::new(true)));
+        let mut snapshots = Vec::new();
+        let coverage = self
+            .coverage
+            .then(|| Rc::new(RefCell::new(Coverage::new())));
+
+        let (outcome, instructions) = match self.build_processor(device.clone(), hypercall.clone())
+        {
+            Ok(mut cpu) => {
+                if let Some(c) = &coverage {
+                    cpu.set_tracer(c.clone());
+                }
+
+                let (stop_reason, instructions) =
+                    self.run_guest(&mut cpu, &hypercall, &mut snapshots);
+                let dev = device.borrow();
+
+                let outcome = match stop_reason {
+                    StopReason::Error(e) => TestOutcome::Error(e),
+                    _ if dev.status == TestDevice::STATUS_PASS => TestOutcome::Passed,
+                    _ if dev.status == TestDevice::STATUS_FAIL => TestOutcome::Failed {
+                        actual: dev.actual,
+                        expected: dev.expected,
+                    },
+                    StopReason::BudgetExhausted => TestOutcome::Timeout,
+                    _ => TestOutcome::Incomplete,
+                };
+
+                (outcome, instructions)
+            }
+            Err(e) => (TestOutcome::Error(e), 0),
+        };
+
+        TestResult {
+            name,
+            address,
+            outcome,
+            instructions,
+            snapshots,
+            coverage: coverage.map(|c| c.take()),
+        }
+    }
+
+    /// Runs the test processor until it stops, or until the instruction budget or the cycle
+    /// budget requested by the test is exhausted, carrying out the hypercall requests made by
+    /// the test after each step
+    fn run_guest(
+        &self,
+        cpu: &mut Processor,
+        hypercall: &RefCell<HypercallDevice>,
+        snapshots: &mut Vec<(u32, CpuSnapshot)>,
+    ) -> (StopReason, usize) {
+        let mut instructions = 0;
+        let mut cycle_limit = None;
+
+        let stop_reason = loop {
+            if instructions >= self.max_instructions
+                || cycle_limit.is_some_and(|c| cpu.cycle_count() > c)
+            {
+                break StopReason::BudgetExhausted;
+            }
+
+            match cpu.step() {
+                Ok(StepResult::Executed(_)) => instructions += 1,
+                Ok(StepResult::Breakpoint(addr)) => break StopReason::Breakpoint(addr),
+                Ok(StepResult::DebugHalt) => break StopReason::DebugHalt,
+                Ok(StepResult::Halted) => break StopReason::Halted,
+                Err(e) => break StopReason::Error(e),
+            }
+
+            let requests = hypercall.borrow_mut().take_requests();
+            for req in requests {
+                match req {
+                    HypercallRequest::Snapshot(tag) => snapshots.push((tag, cpu.save_state())),
+                    HypercallRequest::SetCycleBudget(cycles) => {
+                        cycle_limit = Some(cpu.cycle_count() + cycles as u64)
+                    }
+                }
+            }
+        };
+
+        (stop_reason, instructions)
+    }
+
+    fn build_processor(
+        &self,
+        device: Rc<RefCell<TestDevice>>,
+        hypercall: Rc<RefCell<HypercallDevice>>,
+    ) -> Result<Processor, ProcessorError> {
+        const INIT_RO_LEN: u32 = Processor::TOP_VEC_SEG_ADDR;
+
+        let bytes = &self.image.bytes;
+
+        let mut cpu = Processor::new();
+        cpu.set_strict_encoding(self.strict_encoding);
+        cpu.set_stack_bounds(Some(Self::stack_base(&self.image)..Self::DEVICE_ADDRESS));
+
+        let vector_data = (0..INIT_RO_LEN as usize)
+            .map(|i| bytes.get(i).copied().unwrap_or(0))
+            .collect();
+
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadOnlySegment::new(vector_data))))?;
+        cpu.memory_add_segment(
+            INIT_RO_LEN,
+            Rc::new(RefCell::new(ReadWriteSegment::new(
+                (Self::DEVICE_ADDRESS - INIT_RO_LEN) as usize,
+            ))),
+        )?;
+        cpu.memory_add_segment(Self::DEVICE_ADDRESS, device)?;
+        cpu.memory_add_segment(Self::HYPERCALL_ADDRESS, hypercall.clone())?;
+        cpu.device_add(hypercall)?;
+
+        cpu.reset(ResetType::Hard)?;
+
+        for (i, val) in bytes.iter().enumerate().skip(INIT_RO_LEN as usize) {
+            cpu.memory_set(i as u32, *val)?;
+        }
+
+        Ok(cpu)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_runner() {
+        let txt = "
+            .section tests
+            .loadloc test_pass
+            .loadloc test_fail
+            .loadloc test_loop
+
+            .section code
+            :test_pass
+            ldi 6:u16 5
+            ldi 7:u16 5
+            ldn 8:u32
+            .loadloc jtest_assert_eq
+            call 8
+            ret
+
+            :test_fail
+            ldi 6:u16 4
+            ldi 7:u16 5
+            ldn 8:u32
+            .loadloc jtest_assert_eq
+            call 8
+            ret
+
+            :test_loop
+            jmpri test_loop
+        ";
+
+        let obj = assemble_object(&preprocess_text(txt).unwrap()).unwrap();
+        let mut runner = TestRunner::new(&[obj], 1000).unwrap();
+        runner.set_strict_encoding(true);
+
+        let results = runner.run_all();
+        assert_eq!(
+            results.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(),
+            ["test_pass", "test_fail", "test_loop"]
+        );
+
+        assert!(results[0].outcome.is_pass());
+        assert!(matches!(
+            results[1].outcome,
+            TestOutcome::Failed {
+                actual: 4,
+                expected: 5
+            }
+        ));
+        assert!(matches!(results[2].outcome, TestOutcome::Timeout));
+
+        let report = junit_report(&[
+            SuiteResult {
+                name: "suite".into(),
+                results: Ok(results),
+            },
+            SuiteResult {
+                name: "broken".into(),
+                results: Err("Unknown Label <a>".into()),
+            },
+        ]);
+
+        assert!(report.contains("<testsuites tests=\"4\" failures=\"3\">"));
+        assert!(report.contains("<testcase name=\"test_pass\" classname=\"suite\"/>"));
+        assert!(report.contains("<error message=\"Unknown Label &lt;a&gt;\"/>"));
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let txt = "
+            .section tests
+            .loadloc test_recurse
+
+            .section code
+            :test_recurse
+            ldn 8:u32
+            .loadloc test_recurse
+            call 8
+            ret
+        ";
+
+        let obj = assemble_object(&preprocess_text(txt).unwrap()).unwrap();
+        let runner = TestRunner::new(&[obj], 1000).unwrap();
+
+        let results = runner.run_all();
+        assert!(matches!(
+            results[0].outcome,
+            TestOutcome::Error(ProcessorError::StackOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_hypercalls() {
+        let txt = "
+            .vector #1 irq_handler
+
+            .section tests
+            .loadloc test_snapshot
+            .loadloc test_budget
+            .loadloc test_irq
+
+            .section code
+            :test_snapshot
+            ldi 6:u16 42
+            ldn 8:u32
+            .loadloc jtest_snapshot
+            call 8
+            copy 6 $ret
+            ldi 7:u16 0
+            ldn 8:u32
+            .loadloc jtest_assert_eq
+            call 8
+            ret
+
+            :test_budget
+            ldi 6:u16 100
+            ldn 8:u32
+            .loadloc jtest_set_cycle_budget
+            call 8
+            :test_budget_loop
+            jmpri test_budget_loop
+
+            :test_irq
+            inton
+            ldi 6:u16 1
+            ldn 8:u32
+            .loadloc jtest_raise_irq
+            call 8
+            ldn 9:u32
+            .loadloc irq_flag
+            ld 6:u32 9
+            ldi 7:u16 1
+            ldn 8:u32
+            .loadloc jtest_assert_eq
+            call 8
+            ret
+
+            :irq_handler
+            ldn 9:u32
+            .loadloc irq_flag
+            ldi 10:u16 1
+            sav 9:u32 10
+            retint
+
+            .section data
+            :irq_flag
+            .u32 0
+        ";
+
+        let obj = assemble_object(&preprocess_text(txt).unwrap()).unwrap();
+        let mut runner = TestRunner::new(&[obj], 1000).unwrap();
+        runner.set_strict_encoding(true);
+
+        let results = runner.run_all();
+        assert!(results[0].outcome.is_pass(), "{}", results[0].outcome);
+        assert_eq!(results[0].snapshots.len(), 1);
+        assert_eq!(results[0].snapshots[0].0, 42);
+
+        assert!(matches!(results[1].outcome, TestOutcome::Timeout));
+        assert!(results[1].instructions < 100);
+
+        assert!(results[2].outcome.is_pass(), "{}", results[2].outcome);
+        assert!(results[2].snapshots.is_empty());
+    }
+}
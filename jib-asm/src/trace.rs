@@ -0,0 +1,176 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use jib::cpu::{Processor, Register, TraceEvent, Tracer};
+use jib::debug_info::DebugInfo;
+
+use crate::disassemble::disassemble;
+
+/// Provides a tracer writing a line of text for each executed instruction, with the address,
+/// the instruction word and its disassembly, and each register modified by the instruction.
+/// The program counter is only listed when it does not advance to the following word. When
+/// debug information is provided, labelled addresses are preceded by a label line and each
+/// line ends with the source file and line of the instruction
+pub struct TextTracer<W: Write> {
+    writer: W,
+    error: Option<io::Error>,
+    debug_info: Option<DebugInfo>,
+}
+
+impl TextTracer<BufWriter<File>> {
+    /// Creates a tracer writing to the file at the provided path, replacing any existing file
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> TextTracer<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            error: None,
+            debug_info: None,
+        }
+    }
+
+    /// Annotates the trace with the labels and source lines of the debug information
+    pub fn with_debug_info(mut self, info: DebugInfo) -> Self {
+        self.debug_info = Some(info);
+        self
+    }
+
+    /// Provides the first error raised while writing the trace, if any. No further lines are
+    /// written once an error is raised
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Flushes the trace, providing the writer or the first error raised
+    pub fn into_inner(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush().map(|_| self.writer),
+        }
+    }
+
+    fn write_event(&mut self, event: &TraceEvent) -> io::Result<()> {
+        let mut line = format!(
+            "0x{:08x}  {:08x}  {:<24}",
+            event.pc,
+            event.word,
+            disassemble(event.word)
+        );
+
+        let next_pc = event.pc.wrapping_add(Processor::BYTES_PER_WORD);
+        for (reg, before, after) in event.deltas() {
+            if reg == Register::ProgramCounter && after == next_pc {
+                continue;
+            }
+
+            let name = match reg.as_special() {
+                Some(r) => r.get_special_name().to_string(),
+                None => format!("r{}", reg.get_index()),
+            };
+            line += &format!("  {name}: 0x{before:08x} -> 0x{after:08x}");
+        }
+
+        if let Some(info) = &self.debug_info {
+            if let Some(label) = info.label_at(event.pc) {
+                writeln!(self.writer, "{label}:")?;
+            }
+            if let Some((file, n)) = info.source_line(event.pc) {
+                line = format!("{}  ; {file}:{n}", line.trim_end());
+            }
+        }
+
+        writeln!(self.writer, "{}", line.trim_end())
+    }
+}
+
+impl<W: Write> Tracer for TextTracer<W> {
+    fn trace(&mut self, event: &TraceEvent) {
+        if self.error.is_none() {
+            if let Err(e) = self.write_event(event) {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    use jib::memory::ReadWriteSegment;
+
+    use super::*;
+    use crate::{assemble_lines, assemble_object, object::link_image, preprocess::preprocess_text};
+
+    #[test]
+    fn test_text_trace() {
+        let program = assemble_lines(&["ldi 6:u16 5", "add 6:u32 6 6", "jmpri -8"]).unwrap();
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x100))))
+            .unwrap();
+        for (i, b) in program.iter().enumerate() {
+            cpu.memory_set(i as u32, *b).unwrap();
+        }
+
+        let tracer = Rc::new(RefCell::new(TextTracer::new(Vec::new())));
+        cpu.set_tracer(tracer.clone());
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+        cpu.clear_tracer();
+
+        let tracer = Rc::try_unwrap(tracer).ok().unwrap().into_inner();
+        let text = String::from_utf8(tracer.into_inner().unwrap()).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("0x00000000  "));
+        assert!(lines[0].contains(&disassemble(u32::from_be_bytes(
+            program[0..4].try_into().unwrap()
+        ))));
+        assert!(lines[0].ends_with("r6: 0x00000000 -> 0x00000005"));
+        assert!(lines[1].ends_with("r6: 0x00000005 -> 0x0000000a"));
+        assert!(lines[2].ends_with("pc: 0x00000008 -> 0x00000000"));
+    }
+
+    #[test]
+    fn test_text_trace_debug_info() {
+        let txt = ".loadloc start\n.org 0x400\n:start\nldi 6:u16 5\nhalt\n";
+        let obj = assemble_object(&preprocess_text(txt).unwrap()).unwrap();
+        let image = link_image(&[obj], &HashMap::new()).unwrap();
+        let info = image.debug_info(&["main.jsm".to_string()]);
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x1000))))
+            .unwrap();
+        cpu.load_image(&image.image).unwrap();
+
+        let tracer = Rc::new(RefCell::new(
+            TextTracer::new(Vec::new()).with_debug_info(info),
+        ));
+        cpu.set_tracer(tracer.clone());
+        cpu.step().unwrap();
+        cpu.clear_tracer();
+
+        let tracer = Rc::try_unwrap(tracer).ok().unwrap().into_inner();
+        let text = String::from_utf8(tracer.into_inner().unwrap()).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "start:");
+        assert!(lines[1].starts_with("0x00000400  "));
+        assert!(lines[1].ends_with("r6: 0x00000000 -> 0x00000005  ; main.jsm:4"));
+    }
+}
@@ -0,0 +1,425 @@
+use core::fmt;
+use std::collections::HashMap;
+
+use jib::cpu::{Processor, Register};
+
+/// Defines the number of bytes pushed onto the stack by a call, containing every register
+const SAVED_REGISTERS_SIZE: u32 = Register::NUM_REGISTERS as u32 * Processor::BYTES_PER_WORD;
+
+/// Defines the number of bytes pushed onto the stack by a frame call, containing the return
+/// address and the caller frame
+const SAVED_FRAME_SIZE: u32 = 2 * Processor::BYTES_PER_WORD;
+
+/// Defines the maximum distance below a stack pointer searched for the saved registers of the
+/// calling frame, bounding the space used by arguments and locals within a single frame
+const MAX_FRAME_SEARCH: u32 = 0x4000;
+
+/// Provides a single frame of the guest call stack. The first frame provides the current
+/// program counter, while each following frame provides the call instruction that entered the
+/// frame before it. The function start is provided when known from the call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackFrame {
+    pub pc: u32,
+    pub stack_pointer: u32,
+    pub function: Option<u32>,
+    /// Determines whether the call instruction is a frame call, which only saves the return
+    /// address and the caller frame at the stack pointer
+    pub frame_call: bool,
+}
+
+/// Provides the guest call stack of a paused processor, starting with the current frame.
+///
+/// Each call pushes every register, such that the saved stack pointer within the block is the
+/// address of the block itself. The saved registers of each calling frame are found by
+/// searching below the stack pointer for a block with this property, where the saved program
+/// counter must follow a call instruction within the caller. The saved value of the call
+/// target register then provides the start of the called function. Frame calls are instead
+/// followed through the chain of frames starting at the frame register, where the start of the
+/// called function is not known. The most recent of the two candidates is taken for each
+/// frame. Up to the provided number of frames are returned
+pub fn unwind(cpu: &Processor, max_frames: usize) -> Vec<StackFrame> {
+    let regs = cpu.get_register_state().get_state();
+
+    let mut frames = vec![StackFrame {
+        pc: regs[Register::IDX_PROGRAM_COUNTER],
+        stack_pointer: regs[Register::IDX_STACK_POINTER],
+        function: None,
+        frame_call: false,
+    }];
+    let mut fp = regs[Register::IDX_ARGUMENT_BASE];
+
+    while frames.len() < max_frames {
+        let sp = frames.last().map(|f| f.stack_pointer).unwrap_or_default();
+        let call = find_call_frame(cpu, sp);
+        let frame_call = find_frame_call(cpu, sp, fp);
+
+        let ((base, call_addr, target, caller_fp), is_frame_call) = match (call, frame_call) {
+            (Some(c), Some(f)) if f.0 > c.0 => (f, true),
+            (Some(c), _) => (c, false),
+            (None, Some(f)) => (f, true),
+            (None, None) => break,
+        };
+
+        if let Some(f) = frames.last_mut() {
+            f.function = target;
+        }
+
+        frames.push(StackFrame {
+            pc: call_addr,
+            stack_pointer: base,
+            function: None,
+            frame_call: is_frame_call,
+        });
+        fp = caller_fp;
+    }
+
+    frames
+}
+
+/// Provides a single word of the guest stack. Words within the registers saved by a call
+/// provide the saved register, along with the index of the frame of the call as provided by
+/// [`unwind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackWord {
+    pub address: u32,
+    pub value: u32,
+    pub saved: Option<(usize, Register)>,
+}
+
+/// Provides up to the requested number of words below the stack pointer, starting with the
+/// most recently pushed word, where the registers saved by each call are found as with
+/// [`unwind`], searching up to the provided number of frames
+pub fn stack_words(cpu: &Processor, count: usize, max_frames: usize) -> Vec<StackWord> {
+    let frames = unwind(cpu, max_frames);
+
+    cpu.stack_words(count)
+        .into_iter()
+        .map(|(address, value)| {
+            let saved = frames.iter().enumerate().skip(1).find_map(|(i, f)| {
+                let offset = address.checked_sub(f.stack_pointer)?;
+                let word = (offset / Processor::BYTES_PER_WORD) as usize;
+                if f.frame_call {
+                    let reg = [Register::ProgramCounter, Register::ArgumentBase].get(word)?;
+                    Some((i, *reg))
+                } else if offset < SAVED_REGISTERS_SIZE {
+                    Some((i, Register::try_from(word).ok()?))
+                } else {
+                    None
+                }
+            });
+
+            StackWord {
+                address,
+                value,
+                saved,
+            }
+        })
+        .collect()
+}
+
+/// Provides the base address of the values saved by a call, the address of the call
+/// instruction, the call target if known, and the frame register of the caller
+type CallFrame = (u32, u32, Option<u32>, u32);
+
+/// Searches downward from the stack pointer for the most recent block of registers saved by a
+/// call instruction
+fn find_call_frame(cpu: &Processor, sp: u32) -> Option<CallFrame> {
+    let top = sp.checked_sub(SAVED_REGISTERS_SIZE)?;
+    let saved = |base: u32, reg: usize| {
+        cpu.memory_inspect_u32(base + reg as u32 * Processor::BYTES_PER_WORD)
+            .ok()
+    };
+
+    (top.saturating_sub(MAX_FRAME_SEARCH)..=top)
+        .rev()
+        .filter(|base| saved(*base, Register::IDX_STACK_POINTER) == Some(*base))
+        .find_map(|base| {
+            let call_addr = saved(base, Register::IDX_PROGRAM_COUNTER)?
+                .checked_sub(Processor::BYTES_PER_WORD)?;
+            let [opcode, arg0, _, _] = cpu.memory_inspect_u32(call_addr).ok()?.to_be_bytes();
+
+            if opcode == Processor::OP_CALL.to_byte() {
+                Some((
+                    base,
+                    call_addr,
+                    Some(saved(base, (arg0 & 0x1F) as usize)?),
+                    saved(base, Register::IDX_ARGUMENT_BASE)?,
+                ))
+            } else {
+                None
+            }
+        })
+}
+
+/// Provides the values saved by the frame call that entered the frame at the frame register,
+/// where the frame must be within the stack and the saved return address must follow a frame
+/// call instruction within the caller
+fn find_frame_call(cpu: &Processor, sp: u32, fp: u32) -> Option<CallFrame> {
+    let base = fp.checked_sub(SAVED_FRAME_SIZE).filter(|_| fp <= sp)?;
+    let call_addr = cpu
+        .memory_inspect_u32(base)
+        .ok()?
+        .checked_sub(Processor::BYTES_PER_WORD)?;
+    let [opcode, _, _, _] = cpu.memory_inspect_u32(call_addr).ok()?.to_be_bytes();
+
+    if opcode == Processor::OP_CALL_FRAME.to_byte() {
+        let caller_fp = cpu
+            .memory_inspect_u32(base + Processor::BYTES_PER_WORD)
+            .ok()?;
+        Some((base, call_addr, None, caller_fp))
+    } else {
+        None
+    }
+}
+
+/// Provides symbolic names for addresses from the labels of a linked program
+#[derive(Debug, Clone, Default)]
+pub struct Symbolizer {
+    symbols: Vec<(u32, String)>,
+}
+
+impl Symbolizer {
+    pub fn new(labels: &HashMap<String, u32>) -> Self {
+        let mut symbols = labels
+            .iter()
+            .map(|(k, v)| (*v, k.clone()))
+            .collect::<Vec<_>>();
+        symbols.sort();
+        Self { symbols }
+    }
+
+    /// Provides the label at the requested address, choosing the first name if several
+    /// labels share the address
+    pub fn exact(&self, addr: u32) -> Option<&str> {
+        let i = self.symbols.partition_point(|(a, _)| *a < addr);
+        self.symbols
+            .get(i)
+            .filter(|(a, _)| *a == addr)
+            .map(|(_, s)| s.as_str())
+    }
+
+    /// Provides the address and name of the nearest label at or before the address
+    pub fn nearest(&self, addr: u32) -> Option<(u32, &str)> {
+        let i = self.symbols.partition_point(|(a, _)| *a <= addr);
+        let (base, _) = self.symbols.get(i.checked_sub(1)?)?;
+        Some((*base, self.exact(*base)?))
+    }
+
+    /// Provides the address as the nearest label at or before it, with any offset from the label
+    pub fn describe(&self, addr: u32) -> Option<String> {
+        let (base, name) = self.nearest(addr)?;
+
+        Some(if base == addr {
+            name.to_string()
+        } else {
+            format!("{name}+0x{:x}", addr - base)
+        })
+    }
+
+    /// Provides the symbol for the location within the frame. When the start of the function
+    /// is known, the location is given relative to the function label
+    pub fn describe_frame(&self, frame: &StackFrame) -> Option<String> {
+        match frame.function.and_then(|f| self.exact(f).map(|s| (f, s))) {
+            Some((f, name)) if frame.pc == f => Some(name.to_string()),
+            Some((f, name)) if frame.pc > f => Some(format!("{name}+0x{:x}", frame.pc - f)),
+            _ => self.describe(frame.pc),
+        }
+    }
+}
+
+/// Provides a printable backtrace, with one line for each frame
+pub struct Backtrace<'a> {
+    pub frames: &'a [StackFrame],
+    pub symbols: &'a Symbolizer,
+}
+
+impl fmt::Display for Backtrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, frame) in self.frames.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            write!(f, "#{i:<2} 0x{:08x}", frame.pc)?;
+            if let Some(s) = self.symbols.describe_frame(frame) {
+                write!(f, " <{s}>")?;
+            }
+            write!(f, " sp=0x{:08x}", frame.stack_pointer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assemble_object, object::link_image, preprocess::preprocess_text};
+    use jib::memory::ReadWriteSegment;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn test_unwind() {
+        let txt = "\
+.loadloc start
+.org 0x400
+:start
+ldn $sp:u32
+.loadloc stack
+ldn 6:u32
+.loadloc outer
+call 6
+halt
+:outer
+ldi 7:u16 5
+ldn 8:u32
+.loadloc inner
+call 8
+ret
+:inner
+noop
+:fault
+div 9:u32 9 10
+ret
+.align
+:stack
+";
+        let obj = assemble_object(&preprocess_text(txt).unwrap()).unwrap();
+        let image = link_image(&[obj], &HashMap::new()).unwrap();
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x1000))))
+            .unwrap();
+        cpu.load_image(&image.image).unwrap();
+
+        while cpu.get_current_pc().unwrap() != image.labels["fault"] {
+            cpu.step().unwrap();
+        }
+
+        let frames = unwind(&cpu, 16);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].function, Some(image.labels["inner"]));
+        assert_eq!(frames[1].pc, image.labels["inner"] - 8);
+        assert_eq!(frames[1].function, Some(image.labels["outer"]));
+        assert_eq!(frames[2].pc, image.labels["outer"] - 8);
+        assert_eq!(frames[2].function, None);
+        assert_eq!(frames[2].stack_pointer, image.labels["stack"]);
+
+        let symbols = Symbolizer::new(&image.labels);
+        assert_eq!(
+            symbols.describe(image.labels["fault"] + 2).unwrap(),
+            "fault+0x2"
+        );
+        assert_eq!(
+            Backtrace {
+                frames: &frames,
+                symbols: &symbols,
+            }
+            .to_string()
+            .lines()
+            .map(|l| l.split(" sp=").next().unwrap().to_string())
+            .collect::<Vec<_>>(),
+            [
+                format!("#0  0x{:08x} <inner+0x4>", image.labels["fault"]),
+                format!("#1  0x{:08x} <outer+0xc>", image.labels["inner"] - 8),
+                format!("#2  0x{:08x} <start+0x10>", image.labels["outer"] - 8),
+            ]
+        );
+
+        let words = stack_words(&cpu, 2 * Register::NUM_REGISTERS, 16);
+        assert_eq!(words[0].saved, Some((1, Register::GeneralPurpose(31))));
+        assert_eq!(
+            words[Register::NUM_REGISTERS - 1],
+            StackWord {
+                address: frames[1].stack_pointer,
+                value: image.labels["inner"] - 4,
+                saved: Some((1, Register::ProgramCounter)),
+            }
+        );
+        assert_eq!(
+            words[Register::NUM_REGISTERS + 2].saved,
+            Some((2, Register::GeneralPurpose(29)))
+        );
+        assert_eq!(words.last().map(|w| w.address), Some(image.labels["stack"]));
+
+        assert!(cpu.step().is_err());
+        assert_eq!(unwind(&cpu, 2).len(), 2);
+    }
+
+    /// Ensure that frames entered by frame calls are followed through the frame register,
+    /// alongside frames entered by calls saving every register
+    #[test]
+    fn test_unwind_frame_call() {
+        let txt = "\
+.loadloc start
+.org 0x400
+:start
+ldn $sp:u32
+.loadloc stack
+ldn 6:u32
+.loadloc outer
+callf 6
+halt
+:outer
+ldi 7:u16 8
+add $sp:u32 $sp 7
+ldn 8:u32
+.loadloc middle
+call 8
+retf
+:middle
+ldn 8:u32
+.loadloc inner
+callf 8
+ret
+:inner
+:fault
+div 9:u32 9 10
+retf
+.align
+:stack
+";
+        let obj = assemble_object(&preprocess_text(txt).unwrap()).unwrap();
+        let image = link_image(&[obj], &HashMap::new()).unwrap();
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x1000))))
+            .unwrap();
+        cpu.load_image(&image.image).unwrap();
+
+        while cpu.get_current_pc().unwrap() != image.labels["fault"] {
+            cpu.step().unwrap();
+        }
+
+        let stack = image.labels["stack"];
+        let frames = unwind(&cpu, 16);
+        assert_eq!(
+            frames
+                .iter()
+                .skip(1)
+                .map(|f| (f.pc, f.stack_pointer, f.frame_call))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    image.labels["middle"] + 8,
+                    stack + 16 + SAVED_REGISTERS_SIZE,
+                    true
+                ),
+                (image.labels["outer"] + 16, stack + 16, false),
+                (image.labels["outer"] - 8, stack, true),
+            ]
+        );
+        assert_eq!(frames[1].function, Some(image.labels["middle"]));
+        assert_eq!(frames[2].function, None);
+
+        let words = stack_words(&cpu, 2, 16);
+        assert_eq!(words[0].saved, Some((1, Register::ArgumentBase)));
+        assert_eq!(
+            words[1],
+            StackWord {
+                address: stack + 16 + SAVED_REGISTERS_SIZE,
+                value: image.labels["middle"] + 12,
+                saved: Some((1, Register::ProgramCounter)),
+            }
+        );
+    }
+}
@@ -0,0 +1,37 @@
+//! Assembles and runs a program through the stable interface alone, as a downstream host
+//! depending only on the assembler crate would
+
+use std::{cell::RefCell, rc::Rc};
+
+use jib_asm::assemble_text;
+use jib_asm::jib::memory::ReadWriteSegment;
+use jib_asm::jib::{DecodedInstruction, MemoryImage, MemorySegment, Processor, StepResult};
+
+#[test]
+fn test_stable_api() {
+    let txt = "\
+.loadloc start
+.org 0x400
+:start
+ldi 6:u16 5
+add 6:u32 6 6
+halt
+";
+    let image = MemoryImage::from_flat(assemble_text(txt).unwrap());
+
+    let memory = Rc::new(RefCell::new(ReadWriteSegment::new(0x1000)));
+    assert_eq!(memory.borrow().len(), 0x1000);
+
+    let mut cpu = Processor::new();
+    cpu.memory_add_segment(0, memory).unwrap();
+    cpu.load_image(&image).unwrap();
+
+    let word = cpu.get_current_inst().unwrap();
+    assert_eq!(
+        DecodedInstruction::decode(word.into()).unwrap().to_string(),
+        "ldi 6:u16 0x0005"
+    );
+
+    while !matches!(cpu.step().unwrap(), StepResult::Halted) {}
+    assert_eq!(cpu.get_register_state().get_state()[6], 10);
+}
@@ -0,0 +1,141 @@
+//! Runs the example programs on a processor with the standard devices used by each tool,
+//! checking the behavior of each program as seen by the host
+
+use std::{cell::RefCell, rc::Rc};
+
+use jib::cpu::{Processor, StopReason};
+use jib::device::{HostTimeDevice, KeyboardDevice};
+use jib_asm::{
+    assemble_object,
+    machine::{MachineBuilder, StandardDevices},
+    object::link_image,
+    preprocess::preprocess_text,
+};
+
+/// Provides a processor running an example program, along with the devices used by the
+/// examples
+struct Machine {
+    cpu: Processor,
+    devices: StandardDevices,
+}
+
+impl Machine {
+    /// Assembles the example source and loads it into a new processor
+    fn new(source: &str) -> Self {
+        let obj = preprocess_text(source)
+            .and_then(|lines| assemble_object(&lines))
+            .unwrap();
+        let image = link_image(&[obj], &Default::default()).unwrap();
+
+        let mut m = Self {
+            cpu: Processor::new(),
+            devices: StandardDevices::new(HostTimeDevice::new(|| 0)),
+        };
+
+        MachineBuilder::standard(&m.devices)
+            .build(&mut m.cpu)
+            .unwrap();
+
+        m.cpu.load_image(&image.image).unwrap();
+        m
+    }
+
+    /// Runs the processor for the provided number of cycles, providing the reason it stopped
+    fn run(&mut self, cycles: u64) -> StopReason {
+        self.cpu.run_cycles(cycles).stop_reason
+    }
+
+    fn serial_input(&mut self, text: &str) {
+        for c in text.chars() {
+            let b = jib::text::character_to_byte(c).unwrap();
+            assert!(self.devices.serial.borrow_mut().push_input(b));
+        }
+    }
+
+    fn serial_output(&mut self) -> String {
+        let mut s = String::new();
+        while let Some(b) = self.devices.serial.borrow_mut().pop_output() {
+            s.push(jib::text::byte_to_character(b).unwrap());
+        }
+        s
+    }
+
+    fn display_lines(&self) -> Vec<String> {
+        self.devices.display.borrow().screen().lines()
+    }
+
+    fn key_press(&mut self, key: u8) {
+        assert!(self.devices.keyboard.borrow_mut().push_key(key));
+    }
+}
+
+#[test]
+fn test_serial_echo() {
+    let mut m = Machine::new(include_str!("../examples/serial_echo.jsm"));
+    assert!(matches!(m.run(1000), StopReason::BudgetExhausted));
+    assert_eq!(m.serial_output(), "");
+
+    m.serial_input("hello\n");
+    m.run(1000);
+    assert_eq!(m.serial_output(), "hello\n");
+
+    m.serial_input("again");
+    m.run(1000);
+    assert_eq!(m.serial_output(), "again");
+}
+
+#[test]
+fn test_bubble_sort() {
+    let mut m = Machine::new(include_str!("../examples/bubble_sort.jsm"));
+    assert!(matches!(m.run(100_000), StopReason::Halted));
+    assert_eq!(m.serial_output(), "0 3 7 7 19 42 64 128 1000 65535\n");
+}
+
+#[test]
+fn test_gpio_blink() {
+    let mut m = Machine::new(include_str!("../examples/gpio_blink.jsm"));
+
+    let changes = Rc::new(RefCell::new(Vec::new()));
+    let hook_changes = changes.clone();
+    m.devices
+        .gpio
+        .borrow_mut()
+        .add_output_hook(move |c| hook_changes.borrow_mut().push(c.current));
+
+    assert!(matches!(m.run(10_200), StopReason::BudgetExhausted));
+    assert_eq!(m.devices.gpio.borrow().direction(), 1);
+
+    // The pin toggles once for each clock interval, starting from low
+    let changes = changes.borrow();
+    assert!((19..=21).contains(&changes.len()), "{changes:?}");
+    assert!(changes.iter().step_by(2).all(|v| *v == 1));
+    assert!(changes.iter().skip(1).step_by(2).all(|v| *v == 0));
+}
+
+#[test]
+fn test_snake() {
+    let mut m = Machine::new(include_str!("../examples/snake.jsm"));
+    m.run(200_000);
+
+    // The snake moves right along its starting row, and then turns down from its head once steered
+    let lines = m.display_lines();
+    let row = &lines[12];
+    let head = row.rfind('#').unwrap();
+    assert_eq!(&row[head - 4..=head], "#####");
+    assert_eq!(row.matches('#').count(), 5);
+
+    m.key_press(KeyboardDevice::KEY_DOWN);
+    m.run(100_000);
+    let columns = m
+        .display_lines()
+        .iter()
+        .flat_map(|l| l.match_indices('#').map(|(i, _)| i).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(columns.len(), 5);
+    assert!(columns.iter().all(|c| *c == head), "{columns:?}");
+
+    // Reversing into the body ends the game
+    m.key_press(KeyboardDevice::KEY_UP);
+    assert!(matches!(m.run(100_000), StopReason::Halted));
+    assert!(m.display_lines()[0].starts_with("game over"));
+}
@@ -0,0 +1,19 @@
+//! Decodes arbitrary instruction words, checking that each defined encoding decodes to an
+//! instruction with the same opcode
+
+#![no_main]
+
+use jib::cpu::{DecodedInstruction, Instruction, Processor};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|word: u32| {
+    let inst = Instruction::from(word);
+
+    match DecodedInstruction::decode(inst) {
+        Ok(decoded) => {
+            assert_eq!(decoded.opcode().to_byte(), inst.opcode());
+            let _ = decoded.to_string();
+        }
+        Err(_) => assert!(Processor::validate_encoding(inst).is_err()),
+    }
+});
@@ -0,0 +1,39 @@
+//! Executes arbitrary instruction streams, where the input provides the initial register values
+//! followed by the instruction words. The words are also written to memory, such that operands
+//! read from memory are taken from the stream. A device is mapped after the memory, such that
+//! accesses made to device memory are also exercised
+
+#![no_main]
+
+use std::{cell::RefCell, rc::Rc};
+
+use jib::cpu::{Processor, Register};
+use jib::device::RingBufferDevice;
+use jib::memory::ReadWriteSegment;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut cpu = Processor::new();
+    let memory = Rc::new(RefCell::new(ReadWriteSegment::new(0x1000)));
+    cpu.memory_add_segment(0, memory).unwrap();
+    cpu.device_attach(
+        "ring buffer",
+        0x1000,
+        Rc::new(RefCell::new(RingBufferDevice::new(64))),
+    )
+    .unwrap();
+
+    let (regs, words) = data.split_at(data.len().min(4 * Register::NUM_REGISTERS));
+    for (i, b) in words.iter().take(0x1000).enumerate() {
+        cpu.memory_set(i as u32, *b).unwrap();
+    }
+
+    for (i, val) in regs.chunks_exact(4).enumerate() {
+        let val = u32::from_be_bytes(val.try_into().unwrap());
+        cpu.set_register(Register::try_from(i).unwrap(), val).unwrap();
+    }
+
+    for word in words.chunks_exact(4) {
+        let _ = cpu.step_with_instruction(u32::from_be_bytes(word.try_into().unwrap()));
+    }
+});
@@ -0,0 +1,277 @@
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+use super::{Processor, ProcessorError, Register, StepResult};
+
+/// Provides a request made to the processor through the external debug port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugRequest {
+    /// Halts the core at the next instruction boundary
+    Halt,
+    /// Resumes execution of a halted core
+    Resume,
+    /// Executes a single instruction while the core remains halted
+    Step,
+    /// Provides the current halt state and program counter
+    Status,
+    /// Reads the register with the provided index
+    ReadRegister(usize),
+    /// Writes the value to the register with the provided index
+    WriteRegister(usize, u32),
+    /// Reads the word at the provided address without triggering device side effects
+    ReadMemory(u32),
+    /// Writes the word to the provided address
+    WriteMemory(u32, u32),
+}
+
+impl DebugRequest {
+    const CMD_HALT: u8 = 1;
+    const CMD_RESUME: u8 = 2;
+    const CMD_STEP: u8 = 3;
+    const CMD_STATUS: u8 = 4;
+    const CMD_READ_REGISTER: u8 = 5;
+    const CMD_WRITE_REGISTER: u8 = 6;
+    const CMD_READ_MEMORY: u8 = 7;
+    const CMD_WRITE_MEMORY: u8 = 8;
+
+    /// Encodes the request as the words shifted into the debug port. The first word contains
+    /// the command in the upper byte and any register index in the lowest byte, followed by
+    /// any address and data words
+    pub fn to_words(&self) -> Vec<u32> {
+        let cmd = |c: u8, reg: usize| ((c as u32) << 24) | (reg as u32 & 0xFF);
+
+        match *self {
+            Self::Halt => vec![cmd(Self::CMD_HALT, 0)],
+            Self::Resume => vec![cmd(Self::CMD_RESUME, 0)],
+            Self::Step => vec![cmd(Self::CMD_STEP, 0)],
+            Self::Status => vec![cmd(Self::CMD_STATUS, 0)],
+            Self::ReadRegister(r) => vec![cmd(Self::CMD_READ_REGISTER, r)],
+            Self::WriteRegister(r, val) => vec![cmd(Self::CMD_WRITE_REGISTER, r), val],
+            Self::ReadMemory(addr) => vec![cmd(Self::CMD_READ_MEMORY, 0), addr],
+            Self::WriteMemory(addr, val) => vec![cmd(Self::CMD_WRITE_MEMORY, 0), addr, val],
+        }
+    }
+
+    /// Decodes a single request from the start of the provided words, providing the request
+    /// and the number of words consumed
+    pub fn from_words(words: &[u32]) -> Result<(Self, usize), DebugPortError> {
+        let word = |i: usize| words.get(i).copied().ok_or(DebugPortError::MissingOperand);
+
+        let first = word(0)?;
+        let reg = (first & 0xFF) as usize;
+
+        Ok(match (first >> 24) as u8 {
+            Self::CMD_HALT => (Self::Halt, 1),
+            Self::CMD_RESUME => (Self::Resume, 1),
+            Self::CMD_STEP => (Self::Step, 1),
+            Self::CMD_STATUS => (Self::Status, 1),
+            Self::CMD_READ_REGISTER => (Self::ReadRegister(reg), 1),
+            Self::CMD_WRITE_REGISTER => (Self::WriteRegister(reg, word(1)?), 2),
+            Self::CMD_READ_MEMORY => (Self::ReadMemory(word(1)?), 2),
+            Self::CMD_WRITE_MEMORY => (Self::WriteMemory(word(1)?, word(2)?), 3),
+            c => return Err(DebugPortError::UnknownCommand(c)),
+        })
+    }
+}
+
+/// Provides the response to a debug port request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugResponse {
+    /// The request was completed
+    Ack,
+    /// The value read by the request
+    Value(u32),
+    /// The halt state and program counter of the core
+    Status { halted: bool, pc: u32 },
+}
+
+impl DebugResponse {
+    /// Encodes the response as the words shifted out of the debug port
+    pub fn to_words(&self) -> Vec<u32> {
+        match *self {
+            Self::Ack => vec![0],
+            Self::Value(v) => vec![v],
+            Self::Status { halted, pc } => vec![halted as u32, pc],
+        }
+    }
+}
+
+/// Provides errors that may occur when accessing the processor through the debug port
+#[derive(Debug, Clone)]
+pub enum DebugPortError {
+    UnknownCommand(u8),
+    MissingOperand,
+    Processor(ProcessorError),
+}
+
+impl fmt::Display for DebugPortError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCommand(c) => write!(f, "Unknown Debug Command 0x{c:02x}"),
+            Self::MissingOperand => write!(f, "Missing Debug Command Operand"),
+            Self::Processor(e) => write!(f, "Processor Error => {e}"),
+        }
+    }
+}
+
+impl core::error::Error for DebugPortError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Processor(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ProcessorError> for DebugPortError {
+    fn from(value: ProcessorError) -> Self {
+        Self::Processor(value)
+    }
+}
+
+impl Processor {
+    /// Determines whether the core is halted by the debug port
+    pub fn debug_halted(&self) -> bool {
+        self.debug_halt
+    }
+
+    /// Performs a single debug port request. Register and memory access may be performed
+    /// whether or not the core is halted, and single steps are performed without checking
+    /// breakpoints so that a halted core may always make progress
+    pub fn debug_request(&mut self, req: DebugRequest) -> Result<DebugResponse, ProcessorError> {
+        Ok(match req {
+            DebugRequest::Halt => {
+                self.debug_halt = true;
+                DebugResponse::Ack
+            }
+            DebugRequest::Resume => {
+                self.debug_halt = false;
+                DebugResponse::Ack
+            }
+            DebugRequest::Step => {
+                let halted = core::mem::replace(&mut self.debug_halt, false);
+
+                let res = match self.step() {
+                    Ok(StepResult::Breakpoint(_)) => self.step(),
+                    r => r,
+                };
+
+                self.debug_halt = halted;
+                res?;
+
+                self.debug_status()?
+            }
+            DebugRequest::Status => self.debug_status()?,
+            DebugRequest::ReadRegister(r) => {
+                DebugResponse::Value(self.registers.get(Register::try_from(r)?)?)
+            }
+            DebugRequest::WriteRegister(r, val) => {
+                self.registers.set(Register::try_from(r)?, val)?;
+                DebugResponse::Ack
+            }
+            DebugRequest::ReadMemory(addr) => DebugResponse::Value(self.memory.inspect_u32(addr)?),
+            DebugRequest::WriteMemory(addr, val) => {
+                self.memory.set_u32(addr, val)?;
+                DebugResponse::Ack
+            }
+        })
+    }
+
+    /// Decodes and performs each request within the words shifted into the debug port,
+    /// providing the concatenated response words
+    pub fn debug_scan(&mut self, words: &[u32]) -> Result<Vec<u32>, DebugPortError> {
+        let mut resp = Vec::new();
+        let mut i = 0;
+
+        while i < words.len() {
+            let (req, len) = DebugRequest::from_words(&words[i..])?;
+            resp.extend(self.debug_request(req)?.to_words());
+            i += len;
+        }
+
+        Ok(resp)
+    }
+
+    fn debug_status(&self) -> Result<DebugResponse, ProcessorError> {
+        Ok(DebugResponse::Status {
+            halted: self.debug_halt,
+            pc: self.registers.get(Register::ProgramCounter)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{ResetType, StopReason};
+    use crate::memory::ReadWriteSegment;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    fn build_processor() -> Processor {
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x2000))))
+            .unwrap();
+        cpu.reset(ResetType::Hard).unwrap();
+        cpu
+    }
+
+    /// Ensure that requests survive the round trip through the debug port word encoding
+    #[test]
+    fn test_debug_request_encoding() {
+        let reqs = [
+            DebugRequest::Halt,
+            DebugRequest::Resume,
+            DebugRequest::Step,
+            DebugRequest::Status,
+            DebugRequest::ReadRegister(7),
+            DebugRequest::WriteRegister(31, 0xdeadbeef),
+            DebugRequest::ReadMemory(0x1234),
+            DebugRequest::WriteMemory(0x1000, 5),
+        ];
+
+        for r in reqs {
+            let words = r.to_words();
+            assert_eq!(DebugRequest::from_words(&words).unwrap(), (r, words.len()));
+        }
+
+        assert!(matches!(
+            DebugRequest::from_words(&[0xFF000000]),
+            Err(DebugPortError::UnknownCommand(0xFF))
+        ));
+        assert!(matches!(
+            DebugRequest::from_words(&DebugRequest::WriteMemory(0, 0).to_words()[..2]),
+            Err(DebugPortError::MissingOperand)
+        ));
+    }
+
+    /// Ensure that a halted core does not execute, and may be inspected and single-stepped
+    #[test]
+    fn test_debug_halt_and_step() {
+        let mut cpu = build_processor();
+
+        let halt = DebugRequest::Halt.to_words();
+        assert_eq!(cpu.debug_scan(&halt).unwrap(), [0]);
+        assert!(cpu.debug_halted());
+
+        let summary = cpu.run(10);
+        assert_eq!(summary.instructions, 0);
+        assert!(matches!(summary.stop_reason, StopReason::DebugHalt));
+
+        // Memory is all noop instructions, so each step advances the program counter by a word
+        let mut words = DebugRequest::WriteRegister(6, 42).to_words();
+        words.extend(DebugRequest::Step.to_words());
+        words.extend(DebugRequest::ReadRegister(6).to_words());
+        words.extend(DebugRequest::WriteMemory(0x1800, 0x01020304).to_words());
+        words.extend(DebugRequest::ReadMemory(0x1800).to_words());
+
+        assert_eq!(
+            cpu.debug_scan(&words).unwrap(),
+            [0, 1, 4, 42, 0, 0x01020304]
+        );
+        assert!(cpu.debug_halted());
+
+        cpu.debug_request(DebugRequest::Resume).unwrap();
+        assert_eq!(cpu.run(10).instructions, 10);
+    }
+}
@@ -0,0 +1,892 @@
+use core::fmt;
+
+use super::{
+    DataType, DataTypeError, Instruction, InstructionFormat, Opcode, Processor, ProcessorError,
+    Register, RegisterFlag,
+};
+
+/// Provides a source argument of an arithmetic, bitwise, or test instruction, which is either
+/// the value of the register or the value in memory at the address held by the register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceOperand {
+    pub reg: Register,
+    pub indirect: bool,
+}
+
+impl fmt::Display for SourceOperand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.indirect {
+            write!(f, "[{}]", self.reg.get_index())
+        } else {
+            write!(f, "{}", self.reg.get_index())
+        }
+    }
+}
+
+/// Provides the address read by a load instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadAddress {
+    /// The address held by the register
+    Register(Register),
+    /// The address held by the register, relative to the program counter
+    Relative(Register),
+    /// The immediate offset from the program counter
+    Immediate(i16),
+    /// The word following the instruction
+    Next,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Neg,
+    Mac,
+    /// Multiplies, providing the upper half of the full product in the excess register
+    MulWide,
+    /// Divides, providing the remainder in the excess register
+    DivRem,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitwiseOp {
+    And,
+    Or,
+    Xor,
+    ShiftLeft,
+    ShiftRight,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+}
+
+/// Provides an instruction word decoded into its operation and arguments, as executed by the
+/// processor and shown by the disassembler. Bits not used by the instruction format are
+/// ignored, such that [`Processor::validate_encoding`] must be used to reject them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodedInstruction {
+    Noop,
+    Reset,
+    InterruptEnable,
+    InterruptDisable,
+    Interrupt(u16),
+    InterruptRegister(Register),
+    Syscall(u16),
+    Call(Register),
+    Return,
+    /// Calls the address in the register, saving the return address and the frame register
+    /// and starting a new frame at the top of the stack
+    CallFrame(Register),
+    /// Releases the current frame and returns to the address saved by the frame call
+    ReturnFrame,
+    InterruptReturn,
+    Push(Register),
+    Pop,
+    PopRegister(Register),
+    Jump(Register),
+    JumpRelative(Register),
+    JumpRelativeImmediate(i16),
+    /// Jumps by the offset if the flag matches the expected value
+    Branch {
+        flag: RegisterFlag,
+        expected: bool,
+        offset: i16,
+    },
+    Cpuid {
+        dst: Register,
+        selector: Register,
+    },
+    Escape {
+        ext_id: u8,
+        imm: u16,
+    },
+    Halt,
+    Not {
+        dst: Register,
+        src: Register,
+    },
+    Bool {
+        dst: Register,
+        src: Register,
+    },
+    /// Skips the following instruction unless the register is zero, or non-zero if not
+    /// testing for zero
+    TestZero {
+        reg: Register,
+        zero: bool,
+    },
+    LoadImmediate {
+        dst: Register,
+        data_type: DataType,
+        imm: u16,
+    },
+    Load {
+        dst: Register,
+        data_type: DataType,
+        address: LoadAddress,
+    },
+    /// Saves the source register to the address held by the address register, relative to the
+    /// program counter if requested
+    Save {
+        address: Register,
+        data_type: DataType,
+        src: Register,
+        relative: bool,
+    },
+    Copy {
+        dst: Register,
+        src: Register,
+    },
+    Convert {
+        dst: Register,
+        dst_type: DataType,
+        src: Register,
+        src_type: DataType,
+    },
+    /// Copies the count of elements from the source address, or sets each element to the
+    /// source value if not copying
+    Block {
+        copy: bool,
+        data_type: DataType,
+        dst: Register,
+        src: Register,
+        count: Register,
+    },
+    /// Provides an arithmetic operation, where the negate operation only uses the first source
+    Arithmetic {
+        op: ArithmeticOp,
+        data_type: DataType,
+        dst: Register,
+        a: SourceOperand,
+        b: SourceOperand,
+    },
+    /// Provides a bitwise operation, where the not operation only uses the first source
+    Bitwise {
+        op: BitwiseOp,
+        data_type: DataType,
+        dst: Register,
+        a: SourceOperand,
+        b: SourceOperand,
+    },
+    Compare {
+        op: CompareOp,
+        data_type: DataType,
+        dst: Register,
+        a: SourceOperand,
+        b: SourceOperand,
+    },
+}
+
+type DecodeFn = fn(Instruction) -> Result<DecodedInstruction, DataTypeError>;
+
+/// Provides the definition of a single opcode within the instruction table
+#[derive(Clone, Copy)]
+pub struct InstructionDefinition {
+    pub opcode: Opcode,
+    pub mnemonic: &'static str,
+    pub format: InstructionFormat,
+    decode: DecodeFn,
+}
+
+impl fmt::Debug for InstructionDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstructionDefinition")
+            .field("opcode", &self.opcode)
+            .field("mnemonic", &self.mnemonic)
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+const fn def(
+    opcode: Opcode,
+    mnemonic: &'static str,
+    format: InstructionFormat,
+    decode: DecodeFn,
+) -> InstructionDefinition {
+    InstructionDefinition {
+        opcode,
+        mnemonic,
+        format,
+        decode,
+    }
+}
+
+fn source(arg: u8) -> SourceOperand {
+    SourceOperand {
+        reg: Register::GeneralPurpose((arg & 0x1F) as usize),
+        indirect: arg & Instruction::INDIRECT_FLAG != 0,
+    }
+}
+
+/// Provides the instruction with the last argument byte cleared, such that the unused second
+/// source of a unary instruction is ignored rather than read
+fn unary(inst: Instruction) -> Instruction {
+    Instruction::new([inst.opcode(), inst.arg0(), inst.arg1(), 0])
+}
+
+fn arithmetic(inst: Instruction, op: ArithmeticOp) -> Result<DecodedInstruction, DataTypeError> {
+    Ok(DecodedInstruction::Arithmetic {
+        op,
+        data_type: inst.arg0_data_type()?,
+        dst: inst.arg0_register(),
+        a: source(inst.arg1()),
+        b: source(inst.arg2()),
+    })
+}
+
+fn bitwise(inst: Instruction, op: BitwiseOp) -> Result<DecodedInstruction, DataTypeError> {
+    Ok(DecodedInstruction::Bitwise {
+        op,
+        data_type: inst.arg0_data_type()?,
+        dst: inst.arg0_register(),
+        a: source(inst.arg1()),
+        b: source(inst.arg2()),
+    })
+}
+
+fn compare(inst: Instruction, op: CompareOp) -> Result<DecodedInstruction, DataTypeError> {
+    Ok(DecodedInstruction::Compare {
+        op,
+        data_type: inst.arg0_data_type()?,
+        dst: inst.arg0_register(),
+        a: source(inst.arg1()),
+        b: source(inst.arg2()),
+    })
+}
+
+fn branch(inst: Instruction, flag: RegisterFlag, expected: bool) -> DecodedInstruction {
+    DecodedInstruction::Branch {
+        flag,
+        expected,
+        offset: inst.imm_signed() as i16,
+    }
+}
+
+fn load(inst: Instruction, address: LoadAddress) -> Result<DecodedInstruction, DataTypeError> {
+    Ok(DecodedInstruction::Load {
+        dst: inst.arg0_register(),
+        data_type: inst.arg0_data_type()?,
+        address,
+    })
+}
+
+fn save(inst: Instruction, relative: bool) -> Result<DecodedInstruction, DataTypeError> {
+    Ok(DecodedInstruction::Save {
+        address: inst.arg0_register(),
+        data_type: inst.arg0_data_type()?,
+        src: inst.arg1_register(),
+        relative,
+    })
+}
+
+fn block(inst: Instruction, copy: bool) -> Result<DecodedInstruction, DataTypeError> {
+    Ok(DecodedInstruction::Block {
+        copy,
+        data_type: inst.arg0_data_type()?,
+        dst: inst.arg0_register(),
+        src: inst.arg1_register(),
+        count: inst.arg2_register(),
+    })
+}
+
+/// Provides every defined opcode, along with the mnemonic used by the assembler and the
+/// decoder providing the operation and arguments of the instruction
+pub const INSTRUCTION_TABLE: &[InstructionDefinition] = {
+    use DecodedInstruction as D;
+    use InstructionFormat::*;
+    use RegisterFlag as F;
+
+    &[
+        def(Processor::OP_NOOP, "noop", NoArgument, |_| Ok(D::Noop)),
+        def(Processor::OP_RESET, "reset", NoArgument, |_| Ok(D::Reset)),
+        def(Processor::OP_INTERRUPT, "int", Immediate, |i| {
+            Ok(D::Interrupt(i.imm_unsigned() as u16))
+        }),
+        def(Processor::OP_INTERRUPT_REGISTER, "intr", Register, |i| {
+            Ok(D::InterruptRegister(i.arg0_register()))
+        }),
+        def(Processor::OP_INTERRUPT_RETURN, "retint", NoArgument, |_| {
+            Ok(D::InterruptReturn)
+        }),
+        def(Processor::OP_CALL, "call", Register, |i| {
+            Ok(D::Call(i.arg0_register()))
+        }),
+        def(Processor::OP_RETURN, "ret", NoArgument, |_| Ok(D::Return)),
+        def(Processor::OP_PUSH, "push", Register, |i| {
+            Ok(D::Push(i.arg0_register()))
+        }),
+        def(Processor::OP_POP, "pop", NoArgument, |_| Ok(D::Pop)),
+        def(Processor::OP_POP_REG, "popr", Register, |i| {
+            Ok(D::PopRegister(i.arg0_register()))
+        }),
+        def(Processor::OP_JUMP, "jmp", Register, |i| {
+            Ok(D::Jump(i.arg0_register()))
+        }),
+        def(Processor::OP_JUMP_REL, "jmpr", Register, |i| {
+            Ok(D::JumpRelative(i.arg0_register()))
+        }),
+        def(Processor::OP_JUMP_REL_IMM, "jmpri", Immediate, |i| {
+            Ok(D::JumpRelativeImmediate(i.imm_signed() as i16))
+        }),
+        def(Processor::OP_CPUID, "cpuid", DoubleRegister, |i| {
+            Ok(D::Cpuid {
+                dst: i.arg0_register(),
+                selector: i.arg1_register(),
+            })
+        }),
+        def(Processor::OP_ESCAPE, "esc", Extension, |i| {
+            Ok(D::Escape {
+                ext_id: i.arg0(),
+                imm: i.imm_unsigned() as u16,
+            })
+        }),
+        def(Processor::OP_HALT, "halt", NoArgument, |_| Ok(D::Halt)),
+        def(Processor::OP_INTERRUPT_ENABLE, "inton", NoArgument, |_| {
+            Ok(D::InterruptEnable)
+        }),
+        def(
+            Processor::OP_INTERRUPT_DISABLE,
+            "intoff",
+            NoArgument,
+            |_| Ok(D::InterruptDisable),
+        ),
+        def(Processor::OP_SYSCALL, "sys", Immediate, |i| {
+            Ok(D::Syscall(i.imm_unsigned() as u16))
+        }),
+        def(Processor::OP_LOAD, "ld", DoubleRegisterType, |i| {
+            load(i, LoadAddress::Register(i.arg1_register()))
+        }),
+        def(Processor::OP_LOAD_REL, "ldr", DoubleRegisterType, |i| {
+            load(i, LoadAddress::Relative(i.arg1_register()))
+        }),
+        def(Processor::OP_LOAD_IMM, "ldi", RegisterTypeImmediate, |i| {
+            Ok(D::LoadImmediate {
+                dst: i.arg0_register(),
+                data_type: i.arg0_data_type()?,
+                imm: i.imm_unsigned() as u16,
+            })
+        }),
+        def(
+            Processor::OP_LOAD_IMM_REL,
+            "ldri",
+            RegisterTypeImmediate,
+            |i| load(i, LoadAddress::Immediate(i.imm_signed() as i16)),
+        ),
+        def(Processor::OP_LOAD_NEXT, "ldn", RegisterType, |i| {
+            load(i, LoadAddress::Next)
+        }),
+        def(Processor::OP_SAVE, "sav", DoubleRegisterType, |i| {
+            save(i, false)
+        }),
+        def(Processor::OP_SAVE_REL, "savr", DoubleRegisterType, |i| {
+            save(i, true)
+        }),
+        def(Processor::OP_COPY, "copy", DoubleRegister, |i| {
+            Ok(D::Copy {
+                dst: i.arg0_register(),
+                src: i.arg1_register(),
+            })
+        }),
+        def(Processor::OP_CONV, "conv", Conversion, |i| {
+            // The source type is decoded first, such that an invalid source type is reported
+            let src_type = i.arg1_data_type()?;
+            Ok(D::Convert {
+                dst: i.arg0_register(),
+                dst_type: i.arg0_data_type()?,
+                src: i.arg1_register(),
+                src_type,
+            })
+        }),
+        def(Processor::OP_BLOCK_COPY, "bcpy", Arithmetic, |i| {
+            block(i, true)
+        }),
+        def(Processor::OP_BLOCK_SET, "bset", Arithmetic, |i| {
+            block(i, false)
+        }),
+        def(Processor::OP_JUMP_CARRY, "jc", Immediate, |i| {
+            Ok(branch(i, F::Carry, true))
+        }),
+        def(Processor::OP_JUMP_NOT_CARRY, "jnc", Immediate, |i| {
+            Ok(branch(i, F::Carry, false))
+        }),
+        def(Processor::OP_JUMP_OVERFLOW, "jo", Immediate, |i| {
+            Ok(branch(i, F::Overflow, true))
+        }),
+        def(Processor::OP_JUMP_NOT_OVERFLOW, "jno", Immediate, |i| {
+            Ok(branch(i, F::Overflow, false))
+        }),
+        def(Processor::OP_JUMP_ZERO, "jz", Immediate, |i| {
+            Ok(branch(i, F::Zero, true))
+        }),
+        def(Processor::OP_JUMP_NOT_ZERO, "jnz", Immediate, |i| {
+            Ok(branch(i, F::Zero, false))
+        }),
+        def(Processor::OP_JUMP_NEGATIVE, "jn", Immediate, |i| {
+            Ok(branch(i, F::Negative, true))
+        }),
+        def(Processor::OP_JUMP_NOT_NEGATIVE, "jnn", Immediate, |i| {
+            Ok(branch(i, F::Negative, false))
+        }),
+        def(Processor::OP_CALL_FRAME, "callf", Register, |i| {
+            Ok(D::CallFrame(i.arg0_register()))
+        }),
+        def(Processor::OP_RETURN_FRAME, "retf", NoArgument, |_| {
+            Ok(D::ReturnFrame)
+        }),
+        def(Processor::OP_NOT, "not", DoubleRegister, |i| {
+            Ok(D::Not {
+                dst: i.arg0_register(),
+                src: i.arg1_register(),
+            })
+        }),
+        def(Processor::OP_BOOL, "bool", DoubleRegister, |i| {
+            Ok(D::Bool {
+                dst: i.arg0_register(),
+                src: i.arg1_register(),
+            })
+        }),
+        def(Processor::OP_TEST_ZERO, "tz", Register, |i| {
+            Ok(D::TestZero {
+                reg: i.arg0_register(),
+                zero: true,
+            })
+        }),
+        def(Processor::OP_TEST_NOT_ZERO, "tnz", Register, |i| {
+            Ok(D::TestZero {
+                reg: i.arg0_register(),
+                zero: false,
+            })
+        }),
+        def(Processor::OP_EQ, "teq", Arithmetic, |i| {
+            compare(i, CompareOp::Equal)
+        }),
+        def(Processor::OP_NEQ, "tneq", Arithmetic, |i| {
+            compare(i, CompareOp::NotEqual)
+        }),
+        def(Processor::OP_GREATER, "tg", Arithmetic, |i| {
+            compare(i, CompareOp::Greater)
+        }),
+        def(Processor::OP_GREATER_EQ, "tge", Arithmetic, |i| {
+            compare(i, CompareOp::GreaterEqual)
+        }),
+        def(Processor::OP_LESS, "tl", Arithmetic, |i| {
+            compare(i, CompareOp::Less)
+        }),
+        def(Processor::OP_LESS_EQ, "tle", Arithmetic, |i| {
+            compare(i, CompareOp::LessEqual)
+        }),
+        def(Processor::OP_ADD, "add", Arithmetic, |i| {
+            arithmetic(i, ArithmeticOp::Add)
+        }),
+        def(Processor::OP_SUB, "sub", Arithmetic, |i| {
+            arithmetic(i, ArithmeticOp::Sub)
+        }),
+        def(Processor::OP_MUL, "mul", Arithmetic, |i| {
+            arithmetic(i, ArithmeticOp::Mul)
+        }),
+        def(Processor::OP_DIV, "div", Arithmetic, |i| {
+            arithmetic(i, ArithmeticOp::Div)
+        }),
+        def(Processor::OP_REM, "rem", Arithmetic, |i| {
+            arithmetic(i, ArithmeticOp::Rem)
+        }),
+        def(Processor::OP_NEG, "neg", DoubleRegisterType, |i| {
+            arithmetic(unary(i), ArithmeticOp::Neg)
+        }),
+        def(Processor::OP_MAC, "mac", Arithmetic, |i| {
+            arithmetic(i, ArithmeticOp::Mac)
+        }),
+        def(Processor::OP_MULW, "mulw", Arithmetic, |i| {
+            arithmetic(i, ArithmeticOp::MulWide)
+        }),
+        def(Processor::OP_DIVREM, "divrem", Arithmetic, |i| {
+            arithmetic(i, ArithmeticOp::DivRem)
+        }),
+        def(Processor::OP_BAND, "band", Arithmetic, |i| {
+            bitwise(i, BitwiseOp::And)
+        }),
+        def(Processor::OP_BOR, "bor", Arithmetic, |i| {
+            bitwise(i, BitwiseOp::Or)
+        }),
+        def(Processor::OP_BXOR, "bxor", Arithmetic, |i| {
+            bitwise(i, BitwiseOp::Xor)
+        }),
+        def(Processor::OP_BSHL, "bshl", Arithmetic, |i| {
+            bitwise(i, BitwiseOp::ShiftLeft)
+        }),
+        def(Processor::OP_BSHR, "bshr", Arithmetic, |i| {
+            bitwise(i, BitwiseOp::ShiftRight)
+        }),
+        def(Processor::OP_BNOT, "bnot", DoubleRegisterType, |i| {
+            bitwise(unary(i), BitwiseOp::Not)
+        }),
+    ]
+};
+
+/// Provides the index of the definition of each opcode byte within the instruction table
+const TABLE_INDEX: [u8; 256] = {
+    let mut index = [u8::MAX; 256];
+    let mut i = 0;
+    while i < INSTRUCTION_TABLE.len() {
+        index[INSTRUCTION_TABLE[i].opcode.to_byte() as usize] = i as u8;
+        i += 1;
+    }
+    index
+};
+
+impl InstructionDefinition {
+    /// Provides the definition of the opcode, or None if the opcode is not defined
+    pub fn lookup(opcode: Opcode) -> Option<&'static Self> {
+        INSTRUCTION_TABLE.get(TABLE_INDEX[opcode.to_byte() as usize] as usize)
+    }
+}
+
+impl DecodedInstruction {
+    /// Decodes the instruction, where unknown opcodes result in an unknown instruction error,
+    /// and invalid data types in a data type error
+    pub fn decode(inst: Instruction) -> Result<Self, ProcessorError> {
+        match InstructionDefinition::lookup(Opcode::from(inst.opcode())) {
+            Some(def) => Ok((def.decode)(inst)?),
+            None => Err(ProcessorError::UnknownInstruction(inst)),
+        }
+    }
+
+    /// Provides the number of bytes occupied by the instruction, including the data word
+    /// following a load-next instruction
+    pub fn size(&self) -> u32 {
+        match self {
+            Self::Load {
+                address: LoadAddress::Next,
+                ..
+            } => 2 * Processor::BYTES_PER_WORD,
+            _ => Processor::BYTES_PER_WORD,
+        }
+    }
+
+    /// Provides the opcode that decodes to the instruction
+    pub fn opcode(&self) -> Opcode {
+        use DecodedInstruction as D;
+
+        match *self {
+            D::Noop => Processor::OP_NOOP,
+            D::Reset => Processor::OP_RESET,
+            D::InterruptEnable => Processor::OP_INTERRUPT_ENABLE,
+            D::InterruptDisable => Processor::OP_INTERRUPT_DISABLE,
+            D::Interrupt(_) => Processor::OP_INTERRUPT,
+            D::InterruptRegister(_) => Processor::OP_INTERRUPT_REGISTER,
+            D::Syscall(_) => Processor::OP_SYSCALL,
+            D::Call(_) => Processor::OP_CALL,
+            D::Return => Processor::OP_RETURN,
+            D::CallFrame(_) => Processor::OP_CALL_FRAME,
+            D::ReturnFrame => Processor::OP_RETURN_FRAME,
+            D::InterruptReturn => Processor::OP_INTERRUPT_RETURN,
+            D::Push(_) => Processor::OP_PUSH,
+            D::Pop => Processor::OP_POP,
+            D::PopRegister(_) => Processor::OP_POP_REG,
+            D::Jump(_) => Processor::OP_JUMP,
+            D::JumpRelative(_) => Processor::OP_JUMP_REL,
+            D::JumpRelativeImmediate(_) => Processor::OP_JUMP_REL_IMM,
+            D::Branch { flag, expected, .. } => Opcode {
+                base: Processor::OP_BASE_BRANCH,
+                code: 2 * match flag {
+                    RegisterFlag::Overflow => 1,
+                    RegisterFlag::Zero => 2,
+                    RegisterFlag::Negative => 3,
+                    _ => 0,
+                } + !expected as u8,
+            },
+            D::Cpuid { .. } => Processor::OP_CPUID,
+            D::Escape { .. } => Processor::OP_ESCAPE,
+            D::Halt => Processor::OP_HALT,
+            D::Not { .. } => Processor::OP_NOT,
+            D::Bool { .. } => Processor::OP_BOOL,
+            D::TestZero { zero: true, .. } => Processor::OP_TEST_ZERO,
+            D::TestZero { zero: false, .. } => Processor::OP_TEST_NOT_ZERO,
+            D::LoadImmediate { .. } => Processor::OP_LOAD_IMM,
+            D::Load { address, .. } => match address {
+                LoadAddress::Register(_) => Processor::OP_LOAD,
+                LoadAddress::Relative(_) => Processor::OP_LOAD_REL,
+                LoadAddress::Immediate(_) => Processor::OP_LOAD_IMM_REL,
+                LoadAddress::Next => Processor::OP_LOAD_NEXT,
+            },
+            D::Save {
+                relative: false, ..
+            } => Processor::OP_SAVE,
+            D::Save { relative: true, .. } => Processor::OP_SAVE_REL,
+            D::Copy { .. } => Processor::OP_COPY,
+            D::Convert { .. } => Processor::OP_CONV,
+            D::Block { copy: true, .. } => Processor::OP_BLOCK_COPY,
+            D::Block { copy: false, .. } => Processor::OP_BLOCK_SET,
+            D::Arithmetic { op, .. } => match op {
+                ArithmeticOp::Add => Processor::OP_ADD,
+                ArithmeticOp::Sub => Processor::OP_SUB,
+                ArithmeticOp::Mul => Processor::OP_MUL,
+                ArithmeticOp::Div => Processor::OP_DIV,
+                ArithmeticOp::Rem => Processor::OP_REM,
+                ArithmeticOp::Neg => Processor::OP_NEG,
+                ArithmeticOp::Mac => Processor::OP_MAC,
+                ArithmeticOp::MulWide => Processor::OP_MULW,
+                ArithmeticOp::DivRem => Processor::OP_DIVREM,
+            },
+            D::Bitwise { op, .. } => match op {
+                BitwiseOp::And => Processor::OP_BAND,
+                BitwiseOp::Or => Processor::OP_BOR,
+                BitwiseOp::Xor => Processor::OP_BXOR,
+                BitwiseOp::ShiftLeft => Processor::OP_BSHL,
+                BitwiseOp::ShiftRight => Processor::OP_BSHR,
+                BitwiseOp::Not => Processor::OP_BNOT,
+            },
+            D::Compare { op, .. } => match op {
+                CompareOp::Equal => Processor::OP_EQ,
+                CompareOp::NotEqual => Processor::OP_NEQ,
+                CompareOp::Greater => Processor::OP_GREATER,
+                CompareOp::GreaterEqual => Processor::OP_GREATER_EQ,
+                CompareOp::Less => Processor::OP_LESS,
+                CompareOp::LessEqual => Processor::OP_LESS_EQ,
+            },
+        }
+    }
+}
+
+/// Writes the instruction as assembly text, with the mnemonic of the instruction table
+impl fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DecodedInstruction as D;
+
+        let name = InstructionDefinition::lookup(self.opcode()).map_or("??", |d| d.mnemonic);
+        let typed = |reg: &Register, dt: &DataType| (reg.get_index(), *dt);
+
+        match self {
+            D::Noop
+            | D::Reset
+            | D::InterruptEnable
+            | D::InterruptDisable
+            | D::Return
+            | D::ReturnFrame
+            | D::InterruptReturn
+            | D::Pop
+            | D::Halt => write!(f, "{name}"),
+            D::Interrupt(imm) | D::Syscall(imm) => write!(f, "{name} {}", *imm as i16),
+            D::JumpRelativeImmediate(offset) | D::Branch { offset, .. } => {
+                write!(f, "{name} {offset}")
+            }
+            D::InterruptRegister(reg)
+            | D::Call(reg)
+            | D::CallFrame(reg)
+            | D::Push(reg)
+            | D::PopRegister(reg)
+            | D::Jump(reg)
+            | D::JumpRelative(reg)
+            | D::TestZero { reg, .. } => write!(f, "{name} {}", reg.get_index()),
+            D::Cpuid { dst, selector: src }
+            | D::Not { dst, src }
+            | D::Bool { dst, src }
+            | D::Copy { dst, src } => write!(f, "{name} {} {}", dst.get_index(), src.get_index()),
+            D::Escape { ext_id, imm } => write!(f, "{name} {ext_id} 0x{imm:04x}"),
+            D::LoadImmediate {
+                dst,
+                data_type,
+                imm,
+            } => {
+                let (r, dt) = typed(dst, data_type);
+                write!(f, "{name} {r}:{dt} 0x{imm:04x}")
+            }
+            D::Load {
+                dst,
+                data_type,
+                address,
+            } => {
+                let (r, dt) = typed(dst, data_type);
+                match address {
+                    LoadAddress::Register(src) | LoadAddress::Relative(src) => {
+                        write!(f, "{name} {r}:{dt} {}", src.get_index())
+                    }
+                    LoadAddress::Immediate(offset) => {
+                        write!(f, "{name} {r}:{dt} 0x{:04x}", *offset as u16)
+                    }
+                    LoadAddress::Next => write!(f, "{name} {r}:{dt}"),
+                }
+            }
+            D::Save {
+                address,
+                data_type,
+                src,
+                ..
+            } => {
+                let (r, dt) = typed(address, data_type);
+                write!(f, "{name} {r}:{dt} {}", src.get_index())
+            }
+            D::Convert {
+                dst,
+                dst_type,
+                src,
+                src_type,
+            } => write!(
+                f,
+                "{name} {}:{dst_type} {}:{src_type}",
+                dst.get_index(),
+                src.get_index()
+            ),
+            D::Block {
+                data_type,
+                dst,
+                src,
+                count,
+                ..
+            } => {
+                let (r, dt) = typed(dst, data_type);
+                write!(
+                    f,
+                    "{name} {r}:{dt} {} {}",
+                    src.get_index(),
+                    count.get_index()
+                )
+            }
+            D::Arithmetic {
+                op: ArithmeticOp::Neg,
+                data_type,
+                dst,
+                a,
+                ..
+            }
+            | D::Bitwise {
+                op: BitwiseOp::Not,
+                data_type,
+                dst,
+                a,
+                ..
+            } => {
+                let (r, dt) = typed(dst, data_type);
+                write!(f, "{name} {r}:{dt} {a}")
+            }
+            D::Arithmetic {
+                data_type,
+                dst,
+                a,
+                b,
+                ..
+            }
+            | D::Bitwise {
+                data_type,
+                dst,
+                a,
+                b,
+                ..
+            }
+            | D::Compare {
+                data_type,
+                dst,
+                a,
+                b,
+                ..
+            } => {
+                let (r, dt) = typed(dst, data_type);
+                write!(f, "{name} {r}:{dt} {a} {b}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    /// Ensure that every opcode in the table decodes to an instruction providing the same opcode,
+    /// and that undefined opcodes and invalid data types are rejected
+    #[test]
+    fn test_decode_table() {
+        for def in INSTRUCTION_TABLE {
+            let word = u32::from_be_bytes([def.opcode.to_byte(), 5 << 5 | 9, 5 << 5 | 10, 11]);
+            let inst = DecodedInstruction::decode(word.into()).unwrap();
+            assert_eq!(inst.opcode(), def.opcode, "{}", def.mnemonic);
+            assert!(inst.to_string().starts_with(def.mnemonic));
+            assert_eq!(
+                InstructionDefinition::lookup(def.opcode).map(|d| d.mnemonic),
+                Some(def.mnemonic)
+            );
+        }
+
+        for op in 0..=u8::MAX {
+            let opcode = Opcode::from(op);
+            let defined = INSTRUCTION_TABLE
+                .iter()
+                .filter(|d| d.opcode == opcode)
+                .count();
+            assert!(defined <= 1);
+            assert_eq!(
+                InstructionDefinition::lookup(opcode).is_some(),
+                defined == 1
+            );
+        }
+
+        assert!(matches!(
+            DecodedInstruction::decode(0xff000000.into()),
+            Err(ProcessorError::UnknownInstruction(_))
+        ));
+        assert!(matches!(
+            DecodedInstruction::decode(
+                u32::from_be_bytes([Processor::OP_ADD.to_byte(), 9, 0, 0]).into()
+            ),
+            Err(ProcessorError::DataType(_))
+        ));
+    }
+
+    /// Ensure that instructions are decoded with their arguments and written as assembly text
+    #[test]
+    fn test_decode_arguments() {
+        let decode = |bytes: [u8; 4]| DecodedInstruction::decode(u32::from_be_bytes(bytes).into());
+
+        let inst = decode([Processor::OP_SUB.to_byte(), 4 << 5 | 9, 0x20 | 10, 11]).unwrap();
+        assert_eq!(
+            inst,
+            DecodedInstruction::Arithmetic {
+                op: ArithmeticOp::Sub,
+                data_type: DataType::I16,
+                dst: Register::GeneralPurpose(9),
+                a: SourceOperand {
+                    reg: Register::GeneralPurpose(10),
+                    indirect: true
+                },
+                b: SourceOperand {
+                    reg: Register::GeneralPurpose(11),
+                    indirect: false
+                },
+            }
+        );
+        assert_eq!(inst.to_string(), "sub 9:i16 [10] 11");
+
+        let inst = decode([Processor::OP_JUMP_NOT_ZERO.to_byte(), 0, 0xff, 0xfc]).unwrap();
+        assert_eq!(
+            inst,
+            DecodedInstruction::Branch {
+                flag: RegisterFlag::Zero,
+                expected: false,
+                offset: -4
+            }
+        );
+        assert_eq!(inst.to_string(), "jnz -4");
+
+        let inst = decode([Processor::OP_LOAD_NEXT.to_byte(), 6 << 5 | 7, 0, 0]).unwrap();
+        assert_eq!(inst.size(), 8);
+        assert_eq!(inst.to_string(), "ldn 7:i32");
+        assert_eq!(
+            decode([Processor::OP_HALT.to_byte(), 0, 0, 0])
+                .unwrap()
+                .size(),
+            4
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use alloc::boxed::Box;
+
+use super::{Processor, ProcessorError, RegisterManager};
+use crate::memory::MemoryMap;
+
+/// Provides a host-side handler for an experimental instruction, executed through the escape
+/// opcode. This allows instructions to be prototyped before being added to the core
+/// instruction set
+pub trait InstructionExtension {
+    /// Executes the instruction with the 16-bit operand provided in the lower bytes of the
+    /// instruction word, providing the number of cycles consumed. The program counter is
+    /// advanced past the instruction once the handler returns
+    fn execute(
+        &mut self,
+        registers: &mut RegisterManager,
+        memory: &mut MemoryMap,
+        operand: u16,
+    ) -> Result<u32, ProcessorError>;
+}
+
+impl<F> InstructionExtension for F
+where
+    F: FnMut(&mut RegisterManager, &mut MemoryMap, u16) -> Result<u32, ProcessorError>,
+{
+    fn execute(
+        &mut self,
+        registers: &mut RegisterManager,
+        memory: &mut MemoryMap,
+        operand: u16,
+    ) -> Result<u32, ProcessorError> {
+        self(registers, memory, operand)
+    }
+}
+
+impl Processor {
+    /// Registers the handler for the provided extension identifier, which is provided in the
+    /// second byte of an escape instruction. Any existing handler for the identifier is replaced
+    pub fn register_extension<T: InstructionExtension + 'static>(
+        &mut self,
+        ext_id: u8,
+        handler: T,
+    ) {
+        self.extensions.insert(ext_id, Box::new(handler));
+    }
+
+    /// Removes the handler for the provided extension identifier, returning true if a handler
+    /// was registered
+    pub fn unregister_extension(&mut self, ext_id: u8) -> bool {
+        self.extensions.remove(&ext_id).is_some()
+    }
+
+    /// Executes the extension instruction with the registered handler
+    pub(super) fn execute_extension(
+        &mut self,
+        ext_id: u8,
+        operand: u16,
+    ) -> Result<u32, ProcessorError> {
+        match self.extensions.get_mut(&ext_id) {
+            Some(ext) => ext.execute(&mut self.registers, &mut self.memory, operand),
+            None => Err(ProcessorError::UnknownExtension(ext_id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{Register, StepResult};
+    use crate::memory::ReadWriteSegment;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    /// Ensure that escape instructions are dispatched to the registered extension handler
+    #[test]
+    fn test_extension() {
+        let esc = |id: u8, operand: u16| {
+            let [hi, lo] = operand.to_be_bytes();
+            u32::from_be_bytes([Processor::OP_ESCAPE.to_byte(), id, hi, lo])
+        };
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x100))))
+            .unwrap();
+        for (i, inst) in [esc(3, 0x1234), esc(4, 0)].iter().enumerate() {
+            for (j, b) in inst.to_be_bytes().into_iter().enumerate() {
+                cpu.memory_set((i * 4 + j) as u32, b).unwrap();
+            }
+        }
+
+        cpu.register_extension(
+            3,
+            |regs: &mut RegisterManager, _: &mut MemoryMap, val: u16| {
+                regs.set(Register::GeneralPurpose(6), val as u32 * 2)?;
+                Ok(5)
+            },
+        );
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(5));
+        assert_eq!(
+            cpu.get_register_state()
+                .get(Register::GeneralPurpose(6))
+                .unwrap(),
+            0x2468
+        );
+        assert_eq!(cpu.get_current_pc().unwrap(), 4);
+
+        assert!(matches!(
+            cpu.step(),
+            Err(ProcessorError::UnknownExtension(4))
+        ));
+
+        assert!(cpu.unregister_extension(3));
+        assert!(!cpu.unregister_extension(3));
+    }
+}
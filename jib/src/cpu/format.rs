@@ -0,0 +1,311 @@
+use super::{DataType, Instruction, InstructionDefinition, Opcode, Processor, ProcessorError};
+
+/// Defines the layout of the argument bytes of an instruction, matching the format identifiers
+/// of the instruction set documentation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionFormat {
+    /// Format A, with no arguments
+    NoArgument,
+    /// Format B, with a 16-bit immediate in the last two bytes
+    Immediate,
+    /// Format C, with a single untyped register
+    Register,
+    /// Format D, with a single typed register
+    RegisterType,
+    /// Format E, with a typed register and a 16-bit immediate
+    RegisterTypeImmediate,
+    /// Format F, with two untyped registers
+    DoubleRegister,
+    /// Format G, with a typed register and an untyped register
+    DoubleRegisterType,
+    /// Format H, with two typed registers
+    Conversion,
+    /// Format I, with a typed register and two untyped registers
+    Arithmetic,
+    /// Format J, with an extension identifier and a 16-bit immediate
+    Extension,
+}
+
+/// Defines the allowed values of a single argument byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArgumentByte {
+    Unused,
+    Register,
+    RegisterType,
+    Source,
+    Any,
+}
+
+impl ArgumentByte {
+    fn valid(&self, val: u8) -> bool {
+        match self {
+            Self::Unused => val == 0,
+            Self::Register => val & !0x1F == 0,
+            Self::RegisterType => DataType::try_from(val >> 5).is_ok(),
+            Self::Source => val & !(0x1F | Instruction::INDIRECT_FLAG) == 0,
+            Self::Any => true,
+        }
+    }
+}
+
+impl Processor {
+    /// Provides the argument format of the opcode, or None if the opcode is not defined
+    pub fn instruction_format(opcode: Opcode) -> Option<InstructionFormat> {
+        InstructionDefinition::lookup(opcode).map(|d| d.format)
+    }
+
+    /// Provides the allowed values of each argument byte of the opcode, where the source
+    /// registers of the arithmetic, bitwise, and test instructions may be register-indirect
+    fn argument_bytes(opcode: Opcode) -> Option<[ArgumentByte; 3]> {
+        use ArgumentByte::*;
+
+        let indirect = matches!(
+            opcode.base,
+            Self::OP_BASE_MATH | Self::OP_BASE_BITS | Self::OP_BASE_TEST
+        );
+        let src = if indirect { Source } else { Register };
+
+        Some(match Self::instruction_format(opcode)? {
+            InstructionFormat::NoArgument => [Unused, Unused, Unused],
+            InstructionFormat::Immediate => [Unused, Any, Any],
+            InstructionFormat::Register => [Register, Unused, Unused],
+            InstructionFormat::RegisterType => [RegisterType, Unused, Unused],
+            InstructionFormat::RegisterTypeImmediate => [RegisterType, Any, Any],
+            InstructionFormat::DoubleRegister => [Register, Register, Unused],
+            InstructionFormat::DoubleRegisterType => [RegisterType, src, Unused],
+            InstructionFormat::Conversion => [RegisterType, RegisterType, Unused],
+            InstructionFormat::Arithmetic => [RegisterType, src, src],
+            InstructionFormat::Extension => [Any, Any, Any],
+        })
+    }
+
+    /// Checks that the instruction is a defined encoding, where the opcode is known, each
+    /// typed register has a valid data type, and any bits not used by the instruction format
+    /// are clear. Undefined encodings result in an unknown instruction error
+    pub fn validate_encoding(inst: Instruction) -> Result<(), ProcessorError> {
+        let args = [inst.arg0(), inst.arg1(), inst.arg2()];
+
+        match Self::argument_bytes(Opcode::from(inst.opcode())) {
+            Some(allowed) if allowed.iter().zip(args).all(|(a, v)| a.valid(v)) => Ok(()),
+            _ => Err(ProcessorError::UnknownInstruction(inst)),
+        }
+    }
+
+    /// Determines if each instruction is checked to be a defined encoding before execution
+    pub fn strict_encoding(&self) -> bool {
+        self.strict_encoding
+    }
+
+    /// Sets whether each instruction is checked to be a defined encoding before execution.
+    /// Otherwise, bits not used by an instruction are ignored, although unknown opcodes are
+    /// always rejected
+    pub fn set_strict_encoding(&mut self, strict: bool) {
+        self.strict_encoding = strict;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Register;
+    use crate::memory::ReadWriteSegment;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    /// Provides the format identifier of each instruction, as listed in the instruction table of
+    /// the instruction set documentation, by opcode base and code
+    const ISA_TABLE: &[(u8, u8, char)] = &[
+        (0, 0, 'A'),
+        (0, 1, 'A'),
+        (0, 2, 'B'),
+        (0, 3, 'C'),
+        (0, 4, 'A'),
+        (0, 5, 'C'),
+        (0, 6, 'A'),
+        (0, 7, 'C'),
+        (0, 8, 'A'),
+        (0, 9, 'C'),
+        (0, 10, 'C'),
+        (0, 11, 'C'),
+        (0, 12, 'B'),
+        (0, 13, 'F'),
+        (0, 14, 'J'),
+        (0, 15, 'A'),
+        (1, 0, 'G'),
+        (1, 1, 'G'),
+        (1, 2, 'E'),
+        (1, 3, 'E'),
+        (1, 4, 'D'),
+        (1, 5, 'G'),
+        (1, 6, 'G'),
+        (1, 7, 'F'),
+        (1, 8, 'H'),
+        (1, 9, 'I'),
+        (1, 10, 'I'),
+        (2, 0, 'I'),
+        (2, 1, 'I'),
+        (2, 2, 'I'),
+        (2, 3, 'I'),
+        (2, 4, 'I'),
+        (2, 5, 'I'),
+        (3, 0, 'F'),
+        (3, 1, 'F'),
+        (3, 2, 'C'),
+        (3, 3, 'C'),
+        (4, 0, 'A'),
+        (4, 1, 'A'),
+        (4, 2, 'B'),
+        (5, 0, 'B'),
+        (5, 1, 'B'),
+        (5, 2, 'B'),
+        (5, 3, 'B'),
+        (5, 4, 'B'),
+        (5, 5, 'B'),
+        (5, 6, 'B'),
+        (5, 7, 'B'),
+        (6, 0, 'C'),
+        (6, 1, 'A'),
+        (10, 0, 'I'),
+        (10, 1, 'I'),
+        (10, 2, 'I'),
+        (10, 3, 'I'),
+        (10, 4, 'I'),
+        (10, 5, 'G'),
+        (10, 6, 'I'),
+        (10, 7, 'I'),
+        (10, 8, 'I'),
+        (11, 0, 'I'),
+        (11, 1, 'I'),
+        (11, 2, 'I'),
+        (11, 3, 'I'),
+        (11, 4, 'I'),
+        (11, 5, 'G'),
+    ];
+
+    /// Determines if the value is allowed for the argument byte, as described by the columns of
+    /// the format table of the instruction set documentation
+    fn table_allows(format: char, base: u8, byte: usize, val: u8) -> bool {
+        let column = match format {
+            'A' => ["-", "-", "-"],
+            'B' => ["-", "Imm", "Imm"],
+            'C' => ["Register", "-", "-"],
+            'D' => ["Register Type", "-", "-"],
+            'E' => ["Register Type", "Imm", "Imm"],
+            'F' => ["Register", "Register", "-"],
+            'G' => ["Register Type", "Register", "-"],
+            'H' => ["Register Type", "Register Type", "-"],
+            'I' => ["Register Type", "Register", "Register"],
+            'J' => ["ID", "Imm", "Imm"],
+            _ => unreachable!(),
+        }[byte];
+
+        let indirect = byte > 0 && matches!(base, 2 | 10 | 11);
+
+        match column {
+            "-" => val == 0,
+            "Imm" | "ID" => true,
+            "Register Type" => (1..=7).contains(&(val >> 5)),
+            _ if indirect => val & 0xC0 == 0,
+            _ => val < 0x20,
+        }
+    }
+
+    /// Ensure that the instruction formats match the instruction set documentation, and that
+    /// every value of each argument byte, for every opcode, is only accepted when defined
+    #[test]
+    fn test_encoding_space() {
+        for op in 0..=u8::MAX {
+            let opcode = Opcode::from(op);
+            let entry = ISA_TABLE
+                .iter()
+                .find(|(b, c, _)| *b == opcode.base && *c == opcode.code);
+
+            let format = Processor::instruction_format(opcode).map(|f| match f {
+                InstructionFormat::NoArgument => 'A',
+                InstructionFormat::Immediate => 'B',
+                InstructionFormat::Register => 'C',
+                InstructionFormat::RegisterType => 'D',
+                InstructionFormat::RegisterTypeImmediate => 'E',
+                InstructionFormat::DoubleRegister => 'F',
+                InstructionFormat::DoubleRegisterType => 'G',
+                InstructionFormat::Conversion => 'H',
+                InstructionFormat::Arithmetic => 'I',
+                InstructionFormat::Extension => 'J',
+            });
+            assert_eq!(format, entry.map(|e| e.2), "opcode {opcode}");
+
+            // Start from an encoding where every other argument byte is valid
+            let base_args = match format {
+                Some('A') => [0, 0, 0],
+                Some('B') => [0, 0x12, 0x34],
+                Some('C') => [3, 0, 0],
+                Some('D') => [0xA3, 0, 0],
+                Some('F') => [3, 4, 0],
+                Some('G') => [0xA3, 4, 0],
+                Some('H') => [0xA3, 0xC4, 0],
+                Some('I') => [0xA3, 4, 5],
+                _ => [0xA3, 0x12, 0x34],
+            };
+
+            for byte in 0..3 {
+                for val in 0..=u8::MAX {
+                    let mut data = [op, base_args[0], base_args[1], base_args[2]];
+                    data[byte + 1] = val;
+                    let inst = Instruction::new(data);
+
+                    let expected = format.is_some_and(|f| table_allows(f, opcode.base, byte, val));
+
+                    assert_eq!(
+                        Processor::validate_encoding(inst).is_ok(),
+                        expected,
+                        "instruction {inst}"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Ensure that undefined encodings are only rejected when executing in strict mode, while
+    /// unknown opcodes are always rejected
+    #[test]
+    fn test_strict_step() {
+        let program = [
+            [Processor::OP_NOOP.to_byte(), 0, 0, 1],
+            [Processor::OP_PUSH.to_byte(), 0xA3, 0, 0],
+            [0x70, 0, 0, 0],
+        ];
+
+        for strict in [false, true] {
+            let mut cpu = Processor::new();
+            cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x100))))
+                .unwrap();
+            for (i, b) in program.iter().flatten().enumerate() {
+                cpu.memory_set(i as u32, *b).unwrap();
+            }
+            cpu.registers.set(Register::StackPointer, 0x80).unwrap();
+
+            cpu.set_strict_encoding(strict);
+            assert_eq!(cpu.strict_encoding(), strict);
+
+            for _ in 0..2 {
+                let res = cpu.step();
+                if strict {
+                    assert!(matches!(res, Err(ProcessorError::UnknownInstruction(_))));
+                    cpu.registers
+                        .set(
+                            Register::ProgramCounter,
+                            cpu.registers.get(Register::ProgramCounter).unwrap() + 4,
+                        )
+                        .unwrap();
+                } else {
+                    assert!(res.is_ok());
+                }
+            }
+
+            assert!(matches!(
+                cpu.step(),
+                Err(ProcessorError::UnknownInstruction(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use alloc::vec::Vec;
+
+use super::{Interrupt, Processor, ProcessorError, RegisterChanges, RegisterManager};
+
+/// Provides the state required to undo a single step, recorded before the step
+#[derive(Debug, Clone)]
+pub(super) struct HistoryEntry {
+    pc: u32,
+    registers: [u32; RegisterManager::REGISTER_COUNT],
+    interrupt_hold: Option<Interrupt>,
+    cycle_count: u64,
+    writes: Vec<(u32, u8)>,
+}
+
+impl Processor {
+    /// Sets the number of steps that may be undone with step_back, where zero disables the
+    /// history. Recorded steps beyond the new limit are discarded, oldest first
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+    }
+
+    pub fn history_limit(&self) -> usize {
+        self.history_limit
+    }
+
+    /// Provides the number of steps that may currently be undone
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Undoes the most recent step, restoring the registers and each memory value written by
+    /// the step, returning false if no steps are recorded. The state of memory-mapped devices,
+    /// and any side effects of the step on the host, are not undone. The processor resumes at
+    /// the restored instruction without stopping for any breakpoint at that address
+    pub fn step_back(&mut self) -> Result<bool, ProcessorError> {
+        let Some(entry) = self.history.pop_back() else {
+            return Ok(false);
+        };
+
+        for (addr, val) in entry.writes.into_iter().rev() {
+            if self.devices.device_at(addr).is_none() {
+                self.memory.load(addr, val)?;
+            }
+        }
+
+        self.registers.set_state(entry.registers);
+        self.interrupt_hold = entry.interrupt_hold;
+        self.breakpoint_resume = Some(entry.pc);
+        self.cycle_count = entry.cycle_count;
+        self.halted = false;
+        self.last_register_changes = RegisterChanges::default();
+        self.profile_break();
+
+        Ok(true)
+    }
+
+    /// Starts recording the memory written by a step, if the history is enabled
+    pub(super) fn history_begin(&mut self) {
+        if self.history_limit > 0 {
+            self.memory.start_journal();
+        }
+    }
+
+    /// Records the state before the step, along with the memory written by the step, if the
+    /// history is enabled
+    pub(super) fn history_record(
+        &mut self,
+        pc: u32,
+        initial_registers: &RegisterManager,
+        interrupt_hold: Option<Interrupt>,
+        cycle_count: u64,
+    ) {
+        if self.history_limit == 0 {
+            return;
+        } else if self.history.len() == self.history_limit {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(HistoryEntry {
+            pc,
+            registers: initial_registers.get_state(),
+            interrupt_hold,
+            cycle_count,
+            writes: self.memory.take_journal(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{Register, StepResult};
+    use crate::memory::ReadWriteSegment;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    /// Ensure that stepping back restores the registers and memory written by each step, and
+    /// that only the configured number of steps are kept
+    #[test]
+    fn test_step_back() {
+        let ldi = |reg: u8, val: u16| {
+            let [hi, lo] = val.to_be_bytes();
+            u32::from_be_bytes([Processor::OP_LOAD_IMM.to_byte(), (3 << 5) | reg, hi, lo])
+        };
+        let sav = |dst: u8, src: u8| {
+            u32::from_be_bytes([Processor::OP_SAVE.to_byte(), (5 << 5) | dst, src, 0])
+        };
+        let program = [
+            ldi(6, 0x80),
+            ldi(7, 0x1234),
+            sav(6, 7),
+            ldi(7, 5),
+            sav(6, 7),
+        ];
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x100))))
+            .unwrap();
+        for (i, inst) in program.iter().enumerate() {
+            cpu.memory_set_range(i as u32 * 4, &inst.to_be_bytes())
+                .unwrap();
+        }
+
+        assert!(!cpu.step_back().unwrap());
+        cpu.set_history_limit(4);
+
+        for _ in 0..program.len() {
+            assert!(matches!(cpu.step(), Ok(StepResult::Executed(_))));
+        }
+        assert_eq!(cpu.memory_inspect_u32(0x80).unwrap(), 5);
+        assert_eq!(cpu.history_len(), 4);
+
+        assert!(cpu.step_back().unwrap());
+        assert_eq!(cpu.memory_inspect_u32(0x80).unwrap(), 0x1234);
+        assert_eq!(cpu.get_current_pc().unwrap(), 16);
+
+        assert!(cpu.step_back().unwrap());
+        assert!(cpu.step_back().unwrap());
+        assert_eq!(cpu.memory_inspect_u32(0x80).unwrap(), 0);
+        let regs = cpu.get_register_state();
+        assert_eq!(regs.get(Register::GeneralPurpose(7)).unwrap(), 0x1234);
+        assert_eq!(regs.get(Register::ProgramCounter).unwrap(), 8);
+
+        // The first step was discarded once the limit was reached
+        assert!(cpu.step_back().unwrap());
+        assert!(!cpu.step_back().unwrap());
+        assert_eq!(cpu.get_current_pc().unwrap(), 4);
+        assert_eq!(cpu.cycle_count(), 1);
+
+        // Execution continues forward from the restored state
+        for _ in 1..program.len() {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.memory_inspect_u32(0x80).unwrap(), 5);
+    }
+}
@@ -0,0 +1,112 @@
+use super::{Processor, RegisterManager};
+
+impl Processor {
+    /// Defines the version of the instruction set, with the major version in the upper half-word
+    /// and the minor version in the lower half-word
+    pub const ISA_VERSION: u32 = 0x0001_0000;
+
+    /// Selects the instruction set version
+    pub const CPUID_VERSION: u32 = 0;
+    /// Selects the optional feature flags
+    pub const CPUID_FEATURES: u32 = 1;
+    /// Selects the number of registers
+    pub const CPUID_REGISTERS: u32 = 2;
+    /// Selects whether the extension identifier, added to this base, has a registered handler
+    pub const CPUID_EXTENSION_BASE: u32 = 0x100;
+
+    /// Register-indirect source operands are supported
+    pub const FEATURE_INDIRECT_OPERANDS: u32 = 1 << 0;
+    /// The multiply-accumulate instruction is supported
+    pub const FEATURE_MULTIPLY_ACCUMULATE: u32 = 1 << 1;
+    /// The block copy and block set instructions are supported
+    pub const FEATURE_BLOCK_MEMORY: u32 = 1 << 2;
+    /// The external debug port is available to the host
+    pub const FEATURE_DEBUG_PORT: u32 = 1 << 3;
+    /// At least one escape instruction extension is registered
+    pub const FEATURE_EXTENSIONS: u32 = 1 << 4;
+    /// The system call instruction is supported
+    pub const FEATURE_SYSCALL: u32 = 1 << 5;
+
+    /// Provides the identification word for the selector, as read by the `cpuid` instruction.
+    /// Unknown selectors provide zero, such that programs may probe for newer selectors
+    pub fn identification(&self, selector: u32) -> u32 {
+        match selector {
+            Self::CPUID_VERSION => Self::ISA_VERSION,
+            Self::CPUID_FEATURES => {
+                let mut features = Self::FEATURE_INDIRECT_OPERANDS
+                    | Self::FEATURE_MULTIPLY_ACCUMULATE
+                    | Self::FEATURE_BLOCK_MEMORY
+                    | Self::FEATURE_DEBUG_PORT
+                    | Self::FEATURE_SYSCALL;
+
+                if !self.extensions.is_empty() {
+                    features |= Self::FEATURE_EXTENSIONS;
+                }
+
+                features
+            }
+            Self::CPUID_REGISTERS => RegisterManager::REGISTER_COUNT as u32,
+            n => match n.checked_sub(Self::CPUID_EXTENSION_BASE) {
+                Some(id) if id <= u8::MAX as u32 => {
+                    self.extensions.contains_key(&(id as u8)) as u32
+                }
+                _ => 0,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{ProcessorError, Register};
+    use crate::memory::{MemoryMap, ReadWriteSegment};
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    /// Ensure that the identification instruction reports the version, features, and registered
+    /// extensions
+    #[test]
+    fn test_identification() {
+        let cpuid = u32::from_be_bytes([Processor::OP_CPUID.to_byte(), 6, 7, 0]);
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x100))))
+            .unwrap();
+        for i in 0..4 {
+            for (j, b) in cpuid.to_be_bytes().into_iter().enumerate() {
+                cpu.memory_set((i * 4 + j) as u32, b).unwrap();
+            }
+        }
+
+        cpu.register_extension(
+            3,
+            |_: &mut RegisterManager, _: &mut MemoryMap, _: u16| -> Result<u32, ProcessorError> {
+                Ok(1)
+            },
+        );
+
+        let mut read = |selector: u32| {
+            cpu.registers
+                .set(Register::GeneralPurpose(7), selector)
+                .unwrap();
+            cpu.step().unwrap();
+            cpu.registers.get(Register::GeneralPurpose(6)).unwrap()
+        };
+
+        assert_eq!(read(Processor::CPUID_VERSION), Processor::ISA_VERSION);
+        assert_ne!(
+            read(Processor::CPUID_FEATURES) & Processor::FEATURE_EXTENSIONS,
+            0
+        );
+        assert_eq!(read(Processor::CPUID_EXTENSION_BASE + 3), 1);
+        assert_eq!(read(Processor::CPUID_EXTENSION_BASE + 4), 0);
+
+        assert_eq!(cpu.identification(0x1234), 0);
+        assert!(cpu.unregister_extension(3));
+        assert_eq!(
+            cpu.identification(Processor::CPUID_FEATURES) & Processor::FEATURE_EXTENSIONS,
+            0
+        );
+    }
+}
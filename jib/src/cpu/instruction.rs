@@ -64,6 +64,8 @@ impl fmt::Display for DataTypeError {
     }
 }
 
+impl core::error::Error for DataTypeError {}
+
 impl TryFrom<u8> for DataType {
     type Error = DataTypeError;
 
@@ -117,6 +119,9 @@ impl Instruction {
         val
     };
 
+    /// Defines the flag within a source argument that marks a register-indirect operand
+    pub const INDIRECT_FLAG: u8 = 0x20;
+
     pub fn new(data: [u8; 4]) -> Self {
         Self { data }
     }
@@ -158,6 +163,11 @@ impl Instruction {
         Self::reg_from_arg(self.arg1())
     }
 
+    /// Determines whether the first source argument is a register-indirect operand
+    pub fn arg1_indirect(&self) -> bool {
+        self.arg1() & Self::INDIRECT_FLAG != 0
+    }
+
     pub fn arg2(&self) -> u8 {
         self.data[3]
     }
@@ -170,6 +180,11 @@ impl Instruction {
         Self::reg_from_arg(self.arg2())
     }
 
+    /// Determines whether the second source argument is a register-indirect operand
+    pub fn arg2_indirect(&self) -> bool {
+        self.arg2() & Self::INDIRECT_FLAG != 0
+    }
+
     pub fn imm_unsigned(&self) -> u32 {
         ((self.arg1() as u32) << 8) | (self.arg2() as u32)
     }
@@ -1,24 +1,61 @@
+mod debug_port;
+mod decode;
+mod extension;
+mod format;
+mod history;
+mod identification;
 mod instruction;
 mod operations;
+mod pacing;
+mod profile;
 mod register;
-
-use alloc::{fmt, rc::Rc, vec::Vec};
+mod snapshot;
+pub mod spec;
+mod syscall;
+mod trace;
+
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt,
+    rc::Rc,
+    vec::Vec,
+};
 use core::cell::RefCell;
 use core::hash::{Hash, Hasher};
+use core::ops::Range;
 
+pub use self::debug_port::{DebugPortError, DebugRequest, DebugResponse};
+pub use self::decode::{
+    ArithmeticOp, BitwiseOp, CompareOp, DecodedInstruction, InstructionDefinition, LoadAddress,
+    SourceOperand, INSTRUCTION_TABLE,
+};
+pub use self::extension::InstructionExtension;
+pub use self::format::InstructionFormat;
+pub use self::syscall::{SyscallAction, SyscallHandler};
+pub use self::trace::{RingBufferTracer, TraceEvent, Tracer};
 pub use crate::cpu::instruction::{DataType, DataTypeError};
-use crate::device::{DeviceAction, ProcessorDevice};
-use crate::memory::{MemoryError, MemoryMap, MemorySegment};
+use crate::device::{
+    AttachedDevice, DeviceAction, DeviceBus, ProcessorDevice, TrapInfo, TrapInfoDevice,
+};
+use crate::memory::{
+    Access, MemoryError, MemoryImage, MemoryMap, MemorySegment, ProtectionFault, ProtectionUnit,
+    SegmentState,
+};
 
-use self::instruction::Instruction;
+use self::history::HistoryEntry;
+pub use self::instruction::Instruction;
 use self::operations::{
     ArithmeticOperations, BinaryOperations, FloatOperations, IntegerI8Operations,
     IntegerI16Operations, IntegerI32Operations, IntegerU8Operations, IntegerU16Operations,
-    IntegerU32Operations, OperationError, RelationalOperations,
+    IntegerU32Operations, RelationalOperations,
 };
+pub use self::operations::OperationError;
+pub use self::pacing::FramePacer;
+pub use self::profile::Profile;
 
-use self::register::RegisterFlag;
-pub use self::register::{Register, RegisterError, RegisterManager};
+pub use self::register::{Register, RegisterChanges, RegisterError, RegisterFlag, RegisterManager};
+pub use self::snapshot::SnapshotError;
 
 #[derive(Debug, Clone)]
 pub enum ProcessorError {
@@ -29,8 +66,64 @@ pub enum ProcessorError {
     UnsupportedDataType(Instruction, DataType),
     Operation(OperationError),
     StackUnderflow,
+    StackOverflow,
     DataType(DataTypeError),
     OpcodeAlignment(u32),
+    Device(u16, Box<ProcessorError>),
+    UnknownExtension(u8),
+    Protection(ProtectionFault),
+}
+
+/// Provides a machine-readable category for a processor error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    MemoryFault,
+    Arithmetic,
+    ControlFlow,
+    Device,
+}
+
+impl ErrorCategory {
+    /// Provides a stable numeric code for the category, suitable for passing across an FFI boundary
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::MemoryFault => 1,
+            Self::Arithmetic => 2,
+            Self::ControlFlow => 3,
+            Self::Device => 4,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MemoryFault => write!(f, "Memory Fault"),
+            Self::Arithmetic => write!(f, "Arithmetic"),
+            Self::ControlFlow => write!(f, "Control Flow"),
+            Self::Device => write!(f, "Device"),
+        }
+    }
+}
+
+impl ProcessorError {
+    /// Provides the category associated with the error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Self::Memory(_) | Self::StackUnderflow | Self::StackOverflow | Self::Protection(_) => {
+                ErrorCategory::MemoryFault
+            }
+            Self::Operation(_) | Self::DataType(_) | Self::UnsupportedDataType(_, _) => {
+                ErrorCategory::Arithmetic
+            }
+            Self::UnsupportedInterrupt(_)
+            | Self::Register(_)
+            | Self::UnknownInstruction(_)
+            | Self::UnknownExtension(_)
+            | Self::OpcodeAlignment(_) => ErrorCategory::ControlFlow,
+            Self::Device(_, _) => ErrorCategory::Device,
+        }
+    }
 }
 
 impl fmt::Display for ProcessorError {
@@ -46,7 +139,25 @@ impl fmt::Display for ProcessorError {
             }
             Self::Operation(o) => write!(f, "Operation Error => {o}"),
             Self::StackUnderflow => write!(f, "Stack Underflow"),
+            Self::StackOverflow => write!(f, "Stack Overflow"),
             Self::OpcodeAlignment(o) => write!(f, "Opcode Alignment Error => 0x{o:08x}"),
+            Self::Device(id, e) => write!(f, "Device 0x{id:04x} Error => {e}"),
+            Self::UnknownExtension(id) => write!(f, "Unknown Extension 0x{id:02x}"),
+            Self::Protection(p) => write!(f, "Protection Fault => {p}"),
+        }
+    }
+}
+
+impl core::error::Error for ProcessorError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Memory(e) => Some(e),
+            Self::Register(e) => Some(e),
+            Self::Operation(e) => Some(e),
+            Self::DataType(e) => Some(e),
+            Self::Device(_, e) => Some(e.as_ref()),
+            Self::Protection(e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -75,6 +186,70 @@ impl From<OperationError> for ProcessorError {
     }
 }
 
+impl From<ProtectionFault> for ProcessorError {
+    fn from(value: ProtectionFault) -> Self {
+        Self::Protection(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /// The instruction at the program counter was executed, consuming the provided number of cycles
+    Executed(u32),
+    /// Execution stopped before the instruction at the provided breakpoint address
+    Breakpoint(u32),
+    /// The core is halted by the debug port, and no instruction was executed
+    DebugHalt,
+    /// The core is halted by a halt instruction, and no further instructions are executed
+    /// until the processor is reset
+    Halted,
+}
+
+/// Provides the reason that a processor run stopped
+#[derive(Debug, Clone)]
+pub enum StopReason {
+    /// The processor reached a halt instruction
+    Halted,
+    /// Execution stopped before the instruction at the provided breakpoint address
+    Breakpoint(u32),
+    /// The instruction budget was consumed
+    BudgetExhausted,
+    /// The core was halted by the debug port
+    DebugHalt,
+    /// An error occurred while executing an instruction
+    Error(ProcessorError),
+}
+
+/// Provides a summary of a processor run
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    /// The number of instructions executed during the run
+    pub instructions: usize,
+    /// The number of cycles consumed during the run
+    pub cycles: u64,
+    /// The reason that the run stopped
+    pub stop_reason: StopReason,
+}
+
+/// Provides the saved state of a processor, including registers, memory, and memory-mapped devices
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuSnapshot {
+    /// The register values
+    pub registers: [u32; RegisterManager::REGISTER_COUNT],
+    /// Any interrupt waiting to be called
+    pub interrupt_hold: Option<Interrupt>,
+    /// The breakpoint address that execution is to resume past, if stopped at a breakpoint
+    pub breakpoint_resume: Option<u32>,
+    /// The cumulative cycle count
+    pub cycle_count: u64,
+    /// Whether the core is halted by a halt instruction
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub halted: bool,
+    /// The state of each memory segment, including memory-mapped devices
+    pub memory: Vec<SegmentState>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ResetType {
     Hard,
@@ -88,7 +263,7 @@ pub struct Opcode {
 }
 
 impl Opcode {
-    pub fn to_byte(&self) -> u8 {
+    pub const fn to_byte(&self) -> u8 {
         ((self.base & 0xF) << 4) | (self.code & 0xF)
     }
 }
@@ -116,6 +291,7 @@ impl Hash for Opcode {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Interrupt {
     Software(u32),
     Hardware(u32),
@@ -153,7 +329,7 @@ impl fmt::Display for Interrupt {
 
 pub struct Processor {
     memory: MemoryMap,
-    devices: Vec<Rc<RefCell<dyn ProcessorDevice>>>,
+    devices: DeviceBus,
     registers: RegisterManager,
     op_f32: FloatOperations,
     op_u8: IntegerU8Operations,
@@ -163,6 +339,22 @@ pub struct Processor {
     op_i16: IntegerI16Operations,
     op_i32: IntegerI32Operations,
     interrupt_hold: Option<Interrupt>,
+    breakpoints: BTreeSet<u32>,
+    breakpoint_resume: Option<u32>,
+    last_register_changes: RegisterChanges,
+    cycle_count: u64,
+    halted: bool,
+    debug_halt: bool,
+    extensions: BTreeMap<u8, Box<dyn InstructionExtension>>,
+    syscalls: BTreeMap<u16, Box<dyn SyscallHandler>>,
+    tracer: Option<Box<dyn Tracer>>,
+    history: VecDeque<HistoryEntry>,
+    history_limit: usize,
+    profile: Option<Profile>,
+    strict_encoding: bool,
+    protection: Option<Rc<RefCell<ProtectionUnit>>>,
+    trap_reporter: Option<Rc<RefCell<TrapInfoDevice>>>,
+    stack_bounds: Option<Range<u32>>,
 }
 
 impl Processor {
@@ -187,6 +379,23 @@ impl Processor {
     /// Provides the top address (next free address) after the vector memory segments
     pub const TOP_VEC_SEG_ADDR: u32 = Self::BASE_SW_INT_ADDR * Self::NUM_INTERRUPT;
 
+    /// Defines the number of cycles required to push or pop every register on the stack
+    pub const CYCLES_REGISTER_STATE: u32 = RegisterManager::REGISTER_COUNT as u32;
+
+    /// Defines the number of cycles required to enter an interrupt, in addition to the instruction cycles
+    pub const CYCLES_INTERRUPT_CALL: u32 = 1 + Self::CYCLES_REGISTER_STATE;
+
+    /// Defines the number of additional cycles required to read each register-indirect operand
+    pub const CYCLES_INDIRECT_OPERAND: u32 = 1;
+
+    /// Defines the number of additional cycles required for each element moved by a block copy
+    /// or block set instruction
+    pub const CYCLES_BLOCK_ELEMENT: u32 = 1;
+
+    /// Defines the maximum number of elements moved by each step of a block copy or block set
+    /// instruction, which is repeated until every element is moved
+    pub const BLOCK_STEP_ELEMENTS: u32 = 64;
+
     const OP_BASE_CPU: u8 = 0;
     pub const OP_NOOP: Opcode = Opcode {
         base: Self::OP_BASE_CPU,
@@ -240,6 +449,14 @@ impl Processor {
         base: Self::OP_BASE_CPU,
         code: 12,
     };
+    pub const OP_CPUID: Opcode = Opcode {
+        base: Self::OP_BASE_CPU,
+        code: 13,
+    };
+    pub const OP_ESCAPE: Opcode = Opcode {
+        base: Self::OP_BASE_CPU,
+        code: 14,
+    };
     pub const OP_HALT: Opcode = Opcode {
         base: Self::OP_BASE_CPU,
         code: 15,
@@ -282,6 +499,14 @@ impl Processor {
         base: Self::OP_BASE_MEM,
         code: 8,
     };
+    pub const OP_BLOCK_COPY: Opcode = Opcode {
+        base: Self::OP_BASE_MEM,
+        code: 9,
+    };
+    pub const OP_BLOCK_SET: Opcode = Opcode {
+        base: Self::OP_BASE_MEM,
+        code: 10,
+    };
 
     const OP_BASE_TEST: u8 = 2;
     pub const OP_EQ: Opcode = Opcode {
@@ -336,6 +561,54 @@ impl Processor {
         base: Self::OP_BASE_STATUS_FLAGS,
         code: 1,
     };
+    pub const OP_SYSCALL: Opcode = Opcode {
+        base: Self::OP_BASE_STATUS_FLAGS,
+        code: 2,
+    };
+
+    const OP_BASE_BRANCH: u8 = 5;
+    pub const OP_JUMP_CARRY: Opcode = Opcode {
+        base: Self::OP_BASE_BRANCH,
+        code: 0,
+    };
+    pub const OP_JUMP_NOT_CARRY: Opcode = Opcode {
+        base: Self::OP_BASE_BRANCH,
+        code: 1,
+    };
+    pub const OP_JUMP_OVERFLOW: Opcode = Opcode {
+        base: Self::OP_BASE_BRANCH,
+        code: 2,
+    };
+    pub const OP_JUMP_NOT_OVERFLOW: Opcode = Opcode {
+        base: Self::OP_BASE_BRANCH,
+        code: 3,
+    };
+    pub const OP_JUMP_ZERO: Opcode = Opcode {
+        base: Self::OP_BASE_BRANCH,
+        code: 4,
+    };
+    pub const OP_JUMP_NOT_ZERO: Opcode = Opcode {
+        base: Self::OP_BASE_BRANCH,
+        code: 5,
+    };
+    pub const OP_JUMP_NEGATIVE: Opcode = Opcode {
+        base: Self::OP_BASE_BRANCH,
+        code: 6,
+    };
+    pub const OP_JUMP_NOT_NEGATIVE: Opcode = Opcode {
+        base: Self::OP_BASE_BRANCH,
+        code: 7,
+    };
+
+    const OP_BASE_FRAME: u8 = 6;
+    pub const OP_CALL_FRAME: Opcode = Opcode {
+        base: Self::OP_BASE_FRAME,
+        code: 0,
+    };
+    pub const OP_RETURN_FRAME: Opcode = Opcode {
+        base: Self::OP_BASE_FRAME,
+        code: 1,
+    };
 
     const OP_BASE_MATH: u8 = 10;
     pub const OP_ADD: Opcode = Opcode {
@@ -362,6 +635,18 @@ impl Processor {
         base: Self::OP_BASE_MATH,
         code: 5,
     };
+    pub const OP_MAC: Opcode = Opcode {
+        base: Self::OP_BASE_MATH,
+        code: 6,
+    };
+    pub const OP_MULW: Opcode = Opcode {
+        base: Self::OP_BASE_MATH,
+        code: 7,
+    };
+    pub const OP_DIVREM: Opcode = Opcode {
+        base: Self::OP_BASE_MATH,
+        code: 8,
+    };
 
     const OP_BASE_BITS: u8 = 11;
     pub const OP_BAND: Opcode = Opcode {
@@ -393,7 +678,7 @@ impl Processor {
     pub fn new() -> Self {
         Self {
             memory: MemoryMap::default(),
-            devices: Vec::new(),
+            devices: DeviceBus::new(),
             registers: RegisterManager::default(),
             op_f32: FloatOperations,
             op_u8: IntegerU8Operations,
@@ -403,12 +688,29 @@ impl Processor {
             op_i16: IntegerI16Operations,
             op_i32: IntegerI32Operations,
             interrupt_hold: None,
+            breakpoints: BTreeSet::new(),
+            breakpoint_resume: None,
+            last_register_changes: RegisterChanges::default(),
+            cycle_count: 0,
+            halted: false,
+            debug_halt: false,
+            extensions: BTreeMap::new(),
+            syscalls: BTreeMap::new(),
+            tracer: None,
+            history: VecDeque::new(),
+            history_limit: 0,
+            profile: None,
+            strict_encoding: false,
+            protection: None,
+            trap_reporter: None,
+            stack_bounds: None,
         }
     }
 
     pub fn reset(&mut self, reset_type: ResetType) -> Result<(), ProcessorError> {
         if ResetType::Hard == reset_type {
             self.memory.reset();
+            self.cycle_count = 0;
         }
 
         let reset_vec_addr = match reset_type {
@@ -416,6 +718,20 @@ impl Processor {
             ResetType::Soft => Self::SOFT_RESET_VECTOR,
         };
 
+        self.reset_core(reset_vec_addr)
+    }
+
+    /// Performs a hard reset with the program image loaded into memory, including any
+    /// read-only segments, such that the reset vector is read from the loaded image
+    pub fn load_image(&mut self, image: &MemoryImage) -> Result<(), ProcessorError> {
+        self.memory.reset();
+        self.cycle_count = 0;
+        self.memory.load_image(image)?;
+        self.reset_core(Self::HARD_RESET_VECTOR)
+    }
+
+    /// Resets the registers and execution state, starting at the address in the reset vector
+    fn reset_core(&mut self, reset_vec_addr: u32) -> Result<(), ProcessorError> {
         self.registers.reset();
         self.registers.set(
             Register::ProgramCounter,
@@ -425,14 +741,124 @@ impl Processor {
             .set_flag(RegisterFlag::InterruptEnable, true)?;
 
         self.interrupt_hold = None;
+        self.breakpoint_resume = None;
+        self.halted = false;
+        self.history.clear();
 
         Ok(())
     }
 
+    /// Saves the current processor state. Note that the state of devices is only saved for
+    /// devices that are also mapped into memory, and that breakpoints are not included
+    pub fn save_state(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            registers: self.registers.get_state(),
+            interrupt_hold: self.interrupt_hold,
+            breakpoint_resume: self.breakpoint_resume,
+            cycle_count: self.cycle_count,
+            halted: self.halted,
+            memory: self.memory.save_state(),
+        }
+    }
+
+    /// Restores the processor state from a snapshot. The memory segments of the snapshot must
+    /// match the memory segments of the processor
+    pub fn load_state(&mut self, snapshot: &CpuSnapshot) -> Result<(), ProcessorError> {
+        self.memory.load_state(&snapshot.memory)?;
+        self.registers.set_state(snapshot.registers);
+        self.interrupt_hold = snapshot.interrupt_hold;
+        self.breakpoint_resume = snapshot.breakpoint_resume;
+        self.cycle_count = snapshot.cycle_count;
+        self.halted = snapshot.halted;
+        self.last_register_changes = RegisterChanges::default();
+        self.history.clear();
+        Ok(())
+    }
+
     pub fn get_register_state(&self) -> RegisterManager {
         self.registers
     }
 
+    /// Provides the current stack pointer. The stack grows upward, such that the stack pointer
+    /// addresses the word following the most recently pushed value
+    pub fn stack_pointer(&self) -> u32 {
+        self.registers
+            .get(Register::StackPointer)
+            .unwrap_or_default()
+    }
+
+    /// Provides the address and value of up to the requested number of words below the stack
+    /// pointer, starting with the most recently pushed word, without affecting any device
+    /// state. Fewer words are provided if the bottom of memory or memory that cannot be
+    /// inspected is reached
+    pub fn stack_words(&self, count: usize) -> Vec<(u32, u32)> {
+        let sp = self.stack_pointer();
+        (1..=count as u32)
+            .map_while(|i| {
+                let addr = sp.checked_sub(i.checked_mul(Self::BYTES_PER_WORD)?)?;
+                Some((addr, self.memory.inspect_u32(addr).ok()?))
+            })
+            .collect()
+    }
+
+    /// Sets the register value directly, such as from a host system call handler
+    pub fn set_register(&mut self, reg: Register, val: u32) -> Result<(), ProcessorError> {
+        self.registers.set(reg, val)?;
+        Ok(())
+    }
+
+    /// Determines whether the core is halted by a halt instruction
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Provides the total number of cycles consumed since the processor was created or last hard reset
+    pub fn cycle_count(&self) -> u64 {
+        self.cycle_count
+    }
+
+    /// Provides the number of cycles required to execute the provided opcode
+    pub fn instruction_cycles(opcode: Opcode) -> u32 {
+        match opcode {
+            Self::OP_CALL | Self::OP_RETURN | Self::OP_INTERRUPT_RETURN => {
+                1 + Self::CYCLES_REGISTER_STATE
+            }
+            Self::OP_RESET => 4,
+            Self::OP_INTERRUPT | Self::OP_INTERRUPT_REGISTER | Self::OP_SYSCALL => 2,
+            Self::OP_PUSH | Self::OP_POP | Self::OP_POP_REG => 2,
+            Self::OP_CALL_FRAME | Self::OP_RETURN_FRAME => 3,
+            Self::OP_LOAD
+            | Self::OP_LOAD_REL
+            | Self::OP_LOAD_IMM_REL
+            | Self::OP_LOAD_NEXT
+            | Self::OP_SAVE
+            | Self::OP_SAVE_REL => 2,
+            Self::OP_BLOCK_COPY | Self::OP_BLOCK_SET => 2,
+            Self::OP_MUL | Self::OP_MAC | Self::OP_MULW => 3,
+            Self::OP_DIV | Self::OP_REM | Self::OP_DIVREM => 8,
+            _ => 1,
+        }
+    }
+
+    /// Determines whether the opcode may only be executed in supervisor mode when a protection
+    /// unit is provided
+    pub fn privileged(opcode: Opcode) -> bool {
+        matches!(
+            opcode,
+            Self::OP_RESET
+                | Self::OP_HALT
+                | Self::OP_INTERRUPT_ENABLE
+                | Self::OP_INTERRUPT_DISABLE
+                | Self::OP_INTERRUPT_RETURN
+        )
+    }
+
+    /// Provides the set of registers modified by the last executed instruction,
+    /// including any interrupt call made at the end of that step
+    pub fn last_register_changes(&self) -> RegisterChanges {
+        self.last_register_changes
+    }
+
     pub fn interrupt_address(int: Interrupt) -> Result<u32, ProcessorError> {
         let (base, num) = match int {
             Interrupt::Software(n) => (Self::BASE_SW_INT_ADDR, n),
@@ -484,16 +910,178 @@ impl Processor {
             return Ok(false);
         }
 
-        // Push all register values to the stack
-        self.push_all_registers()?;
-
-        // Update the program counter to the value in the interrupt vector
-        self.registers.set(Register::ProgramCounter, new_pc)?;
+        // Save the register state and start the handler
+        self.enter_interrupt(new_pc)?;
 
         // Return true if the interrupt was called
         Ok(true)
     }
 
+    /// Pushes all register values to the stack and starts the interrupt handler at the provided
+    /// address. Interrupts taken in user mode enter supervisor mode, loading the supervisor stack
+    /// pointer if configured, while the saved status keeps the user mode flag for retint
+    fn enter_interrupt(&mut self, handler: u32) -> Result<(), ProcessorError> {
+        let reg_vals = self.registers.get_state();
+
+        if self.user_mode() {
+            self.registers.set_flag(RegisterFlag::UserMode, false)?;
+
+            let sp = self
+                .protection
+                .as_ref()
+                .map_or(0, |p| p.borrow().supervisor_stack());
+            if sp != 0 {
+                self.registers.set(Register::StackPointer, sp)?;
+            }
+        }
+
+        for r in reg_vals {
+            self.stack_push(r)?;
+        }
+
+        self.registers.set(Register::ProgramCounter, handler)?;
+        Ok(())
+    }
+
+    /// Traps a protection fault made by the instruction at the program counter, discarding any
+    /// register changes made by the instruction and calling the trap interrupt of the protection
+    /// unit, such that the handler may retry the instruction with retint. The fault stops the
+    /// processor if the trap interrupt is disabled or has no vector
+    fn protection_trap(
+        &mut self,
+        fault: ProtectionFault,
+        initial_registers: RegisterManager,
+    ) -> Result<StepResult, ProcessorError> {
+        let Some(unit) = self.protection.clone() else {
+            return Err(fault.into());
+        };
+        let Some(irq) = unit.borrow().trap_irq() else {
+            return Err(fault.into());
+        };
+
+        let handler = self
+            .memory
+            .get_u32(Self::interrupt_address(Interrupt::Hardware(irq as u32))?)?;
+        if handler == 0 {
+            return Err(fault.into());
+        }
+
+        unit.borrow_mut().record_fault(fault);
+        self.call_trap(handler, initial_registers)
+    }
+
+    /// Records the error raised by the instruction at the provided address in the trap reporter,
+    /// if any, writing the registers before the instruction to the register snapshot pointer.
+    /// The trap interrupt is then called if enabled, while otherwise the error is provided. A
+    /// fault raised while a trap is pending is a double fault, which keeps the pending trap and
+    /// provides the error
+    fn report_trap(
+        &mut self,
+        err: ProcessorError,
+        pc: u32,
+        initial_registers: RegisterManager,
+    ) -> Result<StepResult, ProcessorError> {
+        let Some(reporter) = self.trap_reporter.clone() else {
+            return Err(err);
+        };
+
+        let (pending, irq, pointer) = {
+            let r = reporter.borrow();
+            (r.pending(), r.trap_irq(), r.snapshot_pointer())
+        };
+
+        let handler = irq
+            .and_then(|irq| Self::interrupt_address(Interrupt::Hardware(irq as u32)).ok())
+            .and_then(|addr| self.memory.get_u32(addr).ok())
+            .unwrap_or(0);
+
+        if pending && handler != 0 {
+            return Err(err);
+        }
+
+        reporter
+            .borrow_mut()
+            .record(TrapInfo::new(&err, pc, &initial_registers));
+
+        if pointer != 0 {
+            for (i, val) in initial_registers.get_state().into_iter().enumerate() {
+                let addr = pointer.wrapping_add(i as u32 * Self::BYTES_PER_WORD);
+                if self.memory.set_u32(addr, val).is_err() {
+                    break;
+                }
+            }
+        }
+
+        if handler == 0 {
+            return Err(err);
+        }
+
+        self.call_trap(handler, initial_registers)
+    }
+
+    /// Calls the trap handler at the provided address in place of the faulting instruction,
+    /// discarding any register changes made by the instruction
+    fn call_trap(
+        &mut self,
+        handler: u32,
+        initial_registers: RegisterManager,
+    ) -> Result<StepResult, ProcessorError> {
+        self.registers = initial_registers;
+        self.enter_interrupt(handler)?;
+
+        let cycles = 1 + Self::CYCLES_INTERRUPT_CALL + self.memory.take_stall_cycles();
+        self.last_register_changes = RegisterChanges::between(&initial_registers, &self.registers);
+        self.cycle_count += cycles as u64;
+
+        Ok(StepResult::Executed(cycles))
+    }
+
+    /// Provides the trap-info block written when an instruction raises an error, if any. The
+    /// device should also be attached, such that a guest monitor may read it
+    pub fn set_trap_reporter(&mut self, reporter: Option<Rc<RefCell<TrapInfoDevice>>>) {
+        self.trap_reporter = reporter;
+    }
+
+    /// Provides the protection unit consulted for memory accesses made in user mode, if any.
+    /// Without a protection unit, the user mode flag has no effect. The unit should also be
+    /// attached as a device, such that supervisor code may configure it
+    pub fn set_protection_unit(&mut self, unit: Option<Rc<RefCell<ProtectionUnit>>>) {
+        self.protection = unit;
+    }
+
+    /// Provides the range of addresses that the stack may occupy, if limited
+    pub fn stack_bounds(&self) -> Option<Range<u32>> {
+        self.stack_bounds.clone()
+    }
+
+    /// Sets the range of addresses that the stack may occupy, where the start provides the
+    /// stack base and the end provides the stack limit. Pushing a word past the limit, or
+    /// popping a word from before the base, stops with an error instead of accessing memory.
+    /// Without bounds, only popping past address zero is checked
+    pub fn set_stack_bounds(&mut self, bounds: Option<Range<u32>>) {
+        self.stack_bounds = bounds;
+    }
+
+    /// Determines whether the processor is running in user mode, which requires a protection unit
+    pub fn user_mode(&self) -> bool {
+        self.protection.is_some()
+            && self
+                .registers
+                .get_flag(RegisterFlag::UserMode)
+                .unwrap_or(false)
+    }
+
+    /// Checks that the memory access made by an instruction is permitted by the protection unit
+    /// when running in user mode
+    fn check_access(&self, address: u32, size: u32, access: Access) -> Result<(), ProcessorError> {
+        match &self.protection {
+            Some(unit) if self.user_mode() && !unit.borrow().permits(address, size, access) => {
+                Err(ProtectionFault::Access(access, address).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn push_all_registers(&mut self) -> Result<(), ProcessorError> {
         let reg_vals = self.registers.get_state();
 
@@ -524,6 +1112,13 @@ impl Processor {
         Ok(())
     }
 
+    /// Sets the memory value as when loading a program image, allowing read-only memory
+    /// to be modified
+    pub fn memory_load(&mut self, address: u32, val: u8) -> Result<(), ProcessorError> {
+        self.memory.load(address, val)?;
+        Ok(())
+    }
+
     pub fn memory_inspect(&self, address: u32) -> Result<u8, ProcessorError> {
         Ok(self.memory.inspect(address)?)
     }
@@ -532,6 +1127,23 @@ impl Processor {
         Ok(self.memory.inspect_u32(address)?)
     }
 
+    /// Fills the buffer with the memory values starting at the address, without affecting any
+    /// device state
+    pub fn memory_inspect_range(&self, address: u32, buf: &mut [u8]) -> Result<(), ProcessorError> {
+        Ok(self.memory.inspect_range(address, buf)?)
+    }
+
+    /// Sets the memory values starting at the address
+    pub fn memory_set_range(&mut self, address: u32, data: &[u8]) -> Result<(), ProcessorError> {
+        Ok(self.memory.set_range(address, data)?)
+    }
+
+    /// Sets the memory values starting at the address as when loading a program image, allowing
+    /// read-only memory to be modified
+    pub fn memory_load_range(&mut self, address: u32, data: &[u8]) -> Result<(), ProcessorError> {
+        Ok(self.memory.load_range(address, data)?)
+    }
+
     pub fn memory_add_segment(
         &mut self,
         address: u32,
@@ -541,14 +1153,95 @@ impl Processor {
         Ok(())
     }
 
+    /// Adds a device that is stepped with the processor, without claiming any memory
     pub fn device_add(
         &mut self,
         seg: Rc<RefCell<dyn ProcessorDevice>>,
     ) -> Result<(), ProcessorError> {
-        self.devices.push(seg);
+        self.devices.attach_unmapped(seg);
         Ok(())
     }
 
+    /// Attaches the named device, mapping it into memory at the provided base address and
+    /// stepping it with the processor. Devices may be attached while the processor is running,
+    /// although snapshots may only be restored with the same devices attached
+    pub fn device_attach<T: MemorySegment + ProcessorDevice + 'static>(
+        &mut self,
+        name: &str,
+        base: u32,
+        dev: Rc<RefCell<T>>,
+    ) -> Result<(), ProcessorError> {
+        self.devices.attach(&mut self.memory, name, base, dev)?;
+        Ok(())
+    }
+
+    /// Detaches the first device with the provided name, releasing any memory it claimed, and
+    /// provides the description of the removed device
+    pub fn device_detach(&mut self, name: &str) -> Option<AttachedDevice> {
+        self.devices.detach(&mut self.memory, name)
+    }
+
+    /// Provides the description of each attached device, in the order attached
+    pub fn devices(&self) -> impl Iterator<Item = AttachedDevice> + '_ {
+        self.devices.devices()
+    }
+
+    /// Adds a breakpoint at the provided address, returning false if it already existed
+    pub fn add_breakpoint(&mut self, address: u32) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// Removes the breakpoint at the provided address, returning true if it existed
+    pub fn remove_breakpoint(&mut self, address: u32) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Removes all breakpoints
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+        self.breakpoint_resume = None;
+    }
+
+    /// Provides the currently-set breakpoint addresses
+    pub fn breakpoints(&self) -> impl Iterator<Item = u32> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Provides the values of the two source arguments of an arithmetic, bitwise, or test
+    /// instruction. Register-indirect arguments provide the value in memory at the address held
+    /// by the register, read with the data type of the instruction
+    fn source_operands(
+        &mut self,
+        dt: DataType,
+        a: SourceOperand,
+        b: SourceOperand,
+    ) -> Result<(u32, u32), ProcessorError> {
+        Ok((self.source_operand(a, dt)?, self.source_operand(b, dt)?))
+    }
+
+    fn source_operand(&mut self, src: SourceOperand, dt: DataType) -> Result<u32, ProcessorError> {
+        let val = self.registers.get(src.reg)?;
+        if !src.indirect {
+            return Ok(val);
+        }
+
+        self.check_access(val, dt.byte_size() as u32, Access::Read)?;
+
+        Ok(match (dt.byte_size(), dt.signed()) {
+            (1, false) => self.memory.get(val)? as u32,
+            (1, true) => self.memory.get(val)? as i8 as u32,
+            (2, false) => self.memory.get_u16(val)? as u32,
+            (2, true) => self.memory.get_u16(val)? as i16 as u32,
+            _ => self.memory.get_u32(val)?,
+        })
+    }
+
+    /// Provides the additional cycles required to read any register-indirect source arguments
+    fn indirect_cycles(a: SourceOperand, b: SourceOperand) -> u32 {
+        [a.indirect, b.indirect].into_iter().filter(|i| *i).count() as u32
+            * Self::CYCLES_INDIRECT_OPERAND
+    }
+
     fn get_arith_operation(
         &self,
         dt: DataType,
@@ -595,350 +1288,602 @@ impl Processor {
         })
     }
 
-    pub fn step(&mut self) -> Result<(), ProcessorError> {
-        let mut inst_jump = Some(1);
+    /// Runs the processor until a breakpoint is hit or an error occurs. If the processor
+    /// is currently stopped at a breakpoint, execution resumes past that breakpoint. If the
+    /// core is halted, by either the debug port or a halt instruction, the current program
+    /// counter is returned
+    pub fn run_until_break(&mut self) -> Result<u32, ProcessorError> {
+        loop {
+            match self.step()? {
+                StepResult::Breakpoint(addr) => return Ok(addr),
+                StepResult::DebugHalt | StepResult::Halted => return self.get_current_pc(),
+                StepResult::Executed(_) => (),
+            }
+        }
+    }
+
+    /// Runs the processor for up to the provided number of instructions, stopping early
+    /// if a halt instruction, breakpoint, or error is reached
+    pub fn run(&mut self, max_instructions: usize) -> RunSummary {
+        self.run_within(|instructions, _| instructions < max_instructions)
+    }
+
+    /// Runs the processor until at least the provided number of cycles have been consumed,
+    /// stopping early if a halt instruction, breakpoint, or error is reached. The final
+    /// instruction may consume cycles past the budget
+    pub fn run_cycles(&mut self, max_cycles: u64) -> RunSummary {
+        self.run_within(|_, cycles| cycles < max_cycles)
+    }
+
+    /// Runs the processor while the budget check passes for the instructions executed and
+    /// cycles consumed so far
+    fn run_within(&mut self, within: impl Fn(usize, u64) -> bool) -> RunSummary {
+        let mut instructions = 0;
+        let initial_cycles = self.cycle_count;
+
+        let stop_reason = loop {
+            if !within(instructions, self.cycle_count - initial_cycles) {
+                break StopReason::BudgetExhausted;
+            }
+
+            match self.step() {
+                Ok(StepResult::Executed(_)) => instructions += 1,
+                Ok(StepResult::Breakpoint(addr)) => break StopReason::Breakpoint(addr),
+                Ok(StepResult::DebugHalt) => break StopReason::DebugHalt,
+                Ok(StepResult::Halted) => break StopReason::Halted,
+                Err(e) => break StopReason::Error(e),
+            }
+        };
+
+        RunSummary {
+            instructions,
+            cycles: self.cycle_count - initial_cycles,
+            stop_reason,
+        }
+    }
+
+    /// Steps the processor by a single instruction. If the program counter is at a breakpoint,
+    /// the breakpoint is reported without executing the instruction, and the next call to step
+    /// will execute the instruction at the breakpoint. The number of cycles consumed by the
+    /// instruction, including any interrupt call made at the end of the step, is returned.
+    /// No instruction is executed while the core is halted by the debug port. Executing a
+    /// halt instruction halts the core, without consuming any cycles, until the next reset.
+    /// Any instruction word, register, and memory state is reported as an error rather than a
+    /// panic, such that arbitrary programs may be executed safely
+    pub fn step(&mut self) -> Result<StepResult, ProcessorError> {
+        self.step_instruction(None)
+    }
+
+    /// Steps the processor by a single instruction as with [`Self::step`], executing the
+    /// provided instruction word in place of the word fetched from the program counter. Operands
+    /// that follow the instruction, such as for `ldn`, are still read from memory. This allows
+    /// arbitrary instruction streams to be executed without writing each word to memory, such
+    /// as when fuzzing the processor
+    pub fn step_with_instruction(&mut self, word: u32) -> Result<StepResult, ProcessorError> {
+        self.step_instruction(Some(word))
+    }
+
+    /// Steps the processor, with the instruction word fetched from the program counter if not
+    /// provided
+    fn step_instruction(&mut self, word: Option<u32>) -> Result<StepResult, ProcessorError> {
+        if self.debug_halt {
+            return Ok(StepResult::DebugHalt);
+        } else if self.halted {
+            return Ok(StepResult::Halted);
+        }
 
         let pc = self.registers.get(Register::ProgramCounter)?;
-        if pc % 4 != 0 {
-            return Err(ProcessorError::OpcodeAlignment(pc));
+
+        if self.breakpoint_resume.take() != Some(pc) && self.breakpoints.contains(&pc) {
+            self.breakpoint_resume = Some(pc);
+            return Ok(StepResult::Breakpoint(pc));
         }
 
-        let inst = Instruction::from(self.memory.get_u32(pc)?);
+        let initial_registers = self.registers;
+        let initial_interrupt = self.interrupt_hold;
+        let initial_cycles = self.cycle_count;
+        self.history_begin();
+
+        let res = if pc % 4 != 0 {
+            Err(ProcessorError::OpcodeAlignment(pc))
+        } else {
+            match self.execute(pc, initial_registers, word) {
+                Err(ProcessorError::Protection(fault)) => {
+                    self.protection_trap(fault, initial_registers)
+                }
+                Ok(res) => {
+                    let cycles = match res {
+                        StepResult::Executed(cycles) => {
+                            self.profile_step(pc, cycles);
+                            cycles
+                        }
+                        _ => 0,
+                    };
+                    self.trace_step(pc, &initial_registers, cycles);
+                    Ok(res)
+                }
+                res => res,
+            }
+        };
+
+        let res = res.or_else(|e| self.report_trap(e, pc, initial_registers));
+        self.history_record(pc, &initial_registers, initial_interrupt, initial_cycles);
+        res
+    }
+
+    /// Executes the instruction at the program counter, or the provided instruction word, stepping
+    /// the devices and calling any interrupt waiting once the instruction completes
+    fn execute(
+        &mut self,
+        pc: u32,
+        initial_registers: RegisterManager,
+        word: Option<u32>,
+    ) -> Result<StepResult, ProcessorError> {
+        let mut inst_jump = Some(1);
+        let user_mode = self.user_mode();
+
+        // Discard any stall cycles from memory accesses made outside of instruction execution
+        self.memory.take_stall_cycles();
+
+        let inst = match word {
+            Some(w) => Instruction::from(w),
+            None => {
+                self.check_access(pc, Self::BYTES_PER_WORD, Access::Execute)?;
+                Instruction::from(self.memory.fetch_u32(pc)?)
+            }
+        };
+
+        if self.strict_encoding {
+            Self::validate_encoding(inst)?;
+        }
 
         let opcode = Opcode::from(inst.opcode());
+        let mut cycles = Self::instruction_cycles(opcode);
+
+        if user_mode && Self::privileged(opcode) {
+            return Err(ProtectionFault::PrivilegedInstruction(pc).into());
+        }
 
         // TODO - Jump Condition
 
-        match opcode {
-            Self::OP_NOOP => (),
-            Self::OP_RESET => self.reset(ResetType::Soft)?,
-            Self::OP_INTERRUPT_ENABLE => self
+        match DecodedInstruction::decode(inst)? {
+            DecodedInstruction::Noop => (),
+            DecodedInstruction::Reset => {
+                // Start directly at the soft reset vector, rather than the following instruction
+                self.reset(ResetType::Soft)?;
+                inst_jump = None;
+            }
+            DecodedInstruction::InterruptEnable => self
                 .registers
                 .set_flag(RegisterFlag::InterruptEnable, true)?,
-            Self::OP_INTERRUPT_DISABLE => self
+            DecodedInstruction::InterruptDisable => self
                 .registers
                 .set_flag(RegisterFlag::InterruptEnable, false)?,
-            Self::OP_INTERRUPT => {
-                self.queue_interrupt(Interrupt::Software(inst.imm_unsigned()))?;
+            DecodedInstruction::Interrupt(num) => {
+                self.queue_interrupt(Interrupt::Software(num as u32))?;
             }
-            Self::OP_INTERRUPT_REGISTER => {
-                self.queue_interrupt(Interrupt::Software(
-                    self.registers.get(inst.arg0_register())?,
-                ))?;
+            DecodedInstruction::InterruptRegister(reg) => {
+                self.queue_interrupt(Interrupt::Software(self.registers.get(reg)?))?;
+            }
+            DecodedInstruction::Syscall(num) => {
+                // Host handlers take precedence over the software interrupt of the guest
+                match self.execute_syscall(num) {
+                    Some(res) => {
+                        if res? == SyscallAction::Halt {
+                            self.halted = true;
+                            return Ok(StepResult::Halted);
+                        }
+                    }
+                    None => {
+                        self.queue_interrupt(Interrupt::Software(num as u32))?;
+                    }
+                }
             }
-            Self::OP_CALL => {
+            DecodedInstruction::Call(reg) => {
                 // Increment the program counter before pushing registers so we return to the next instruction
-                self.registers
-                    .set(Register::ProgramCounter, pc + Self::BYTES_PER_WORD)?;
+                self.registers.set(
+                    Register::ProgramCounter,
+                    pc.wrapping_add(Self::BYTES_PER_WORD),
+                )?;
 
                 // Push all registers
                 self.push_all_registers()?;
 
                 // Set the new program counter and ensure that the next instruction
                 // starts incrementing directly from the new value
+                self.registers
+                    .set(Register::ProgramCounter, self.registers.get(reg)?)?;
+                inst_jump = None;
+            }
+            DecodedInstruction::Return => {
+                self.pop_all_registers(true)?;
+                inst_jump = None;
+            }
+            DecodedInstruction::CallFrame(reg) => {
+                // Save the return address and the caller frame, and start the new frame at the
+                // top of the stack
+                let target = self.registers.get(reg)?;
+                self.stack_push(pc.wrapping_add(Self::BYTES_PER_WORD))?;
+                self.stack_push(self.registers.get(Register::ArgumentBase)?)?;
                 self.registers.set(
-                    Register::ProgramCounter,
-                    self.registers.get(inst.arg0_register())?,
+                    Register::ArgumentBase,
+                    self.registers.get(Register::StackPointer)?,
+                )?;
+
+                self.registers.set(Register::ProgramCounter, target)?;
+                inst_jump = None;
+            }
+            DecodedInstruction::ReturnFrame => {
+                // Release the frame, and restore the caller frame and return address
+                self.registers.set(
+                    Register::StackPointer,
+                    self.registers.get(Register::ArgumentBase)?,
                 )?;
+                let frame = self.stack_pop()?;
+                let ret = self.stack_pop()?;
+                self.registers.set(Register::ArgumentBase, frame)?;
+
+                self.registers.set(Register::ProgramCounter, ret)?;
                 inst_jump = None;
             }
-            Self::OP_RETURN | Self::OP_INTERRUPT_RETURN => {
-                self.pop_all_registers(opcode == Self::OP_RETURN)?;
+            DecodedInstruction::InterruptReturn => {
+                self.pop_all_registers(false)?;
                 inst_jump = None;
             }
-            Self::OP_PUSH => {
-                let val = self.registers.get(inst.arg0_register())?;
+            DecodedInstruction::Push(reg) => {
+                let val = self.registers.get(reg)?;
                 self.stack_push(val)?;
             }
-            Self::OP_POP => {
+            DecodedInstruction::Pop => {
                 self.stack_pop()?;
             }
-            Self::OP_POP_REG => {
+            DecodedInstruction::PopRegister(reg) => {
                 let val = self.stack_pop()?;
-                self.registers.set(inst.arg0_register(), val)?;
+                self.registers.set(reg, val)?;
             }
-            Self::OP_JUMP => {
-                self.registers.set(
-                    Register::ProgramCounter,
-                    self.registers.get(inst.arg0_register())?,
-                )?;
+            DecodedInstruction::Jump(reg) => {
+                self.registers
+                    .set(Register::ProgramCounter, self.registers.get(reg)?)?;
                 inst_jump = None;
             }
-            Self::OP_JUMP_REL => {
+            DecodedInstruction::JumpRelative(reg) => {
                 self.registers.set(
                     Register::ProgramCounter,
-                    (pc as i32 + self.registers.get(inst.arg0_register())? as i32) as u32,
+                    pc.wrapping_add(self.registers.get(reg)?),
                 )?;
                 inst_jump = None;
             }
-            Self::OP_JUMP_REL_IMM => {
+            DecodedInstruction::JumpRelativeImmediate(offset) => {
                 self.registers.set(
                     Register::ProgramCounter,
-                    (pc as i32 + inst.imm_signed()) as u32,
+                    pc.wrapping_add_signed(offset as i32),
                 )?;
                 inst_jump = None;
             }
-            Self::OP_HALT => {
-                inst_jump = None;
+            DecodedInstruction::Cpuid { dst, selector } => {
+                let selector = self.registers.get(selector)?;
+                self.registers.set(dst, self.identification(selector))?;
             }
-            Self::OP_NOT => {
-                let val = self.registers.get(inst.arg1_register())?;
-                self.registers
-                    .set(inst.arg0_register(), if val != 0 { 0 } else { 1 })?;
+            DecodedInstruction::Escape { ext_id, imm } => {
+                cycles = self.execute_extension(ext_id, imm)?;
             }
-            Self::OP_BOOL => {
-                let val = self.registers.get(inst.arg1_register())?;
-                self.registers
-                    .set(inst.arg0_register(), if val != 0 { 1 } else { 0 })?;
-            }
-            Self::OP_TEST_ZERO | Self::OP_TEST_NOT_ZERO => {
-                let val = self.registers.get(inst.arg0_register())?;
-                let is_true = match opcode {
-                    Self::OP_TEST_ZERO => val == 0,
-                    Self::OP_TEST_NOT_ZERO => val != 0,
-                    _ => return Err(ProcessorError::UnknownInstruction(inst)),
-                };
-
-                inst_jump = Some(if is_true { 1 } else { 2 });
+            DecodedInstruction::Halt => {
+                self.halted = true;
+                return Ok(StepResult::Halted);
             }
-            Self::OP_LOAD_IMM => {
-                let dt = inst.arg0_data_type()?;
-                match dt {
-                    DataType::I16 => self
-                        .registers
-                        .set(inst.arg0_register(), inst.imm_signed() as u32)?,
-                    DataType::U16 => self
-                        .registers
-                        .set(inst.arg0_register(), inst.imm_unsigned())?,
-                    _ => return Err(ProcessorError::UnsupportedDataType(inst, dt)),
+            DecodedInstruction::Branch {
+                flag,
+                expected,
+                offset,
+            } => {
+                if self.registers.get_flag(flag)? == expected {
+                    self.registers.set(
+                        Register::ProgramCounter,
+                        pc.wrapping_add_signed(offset as i32),
+                    )?;
+                    inst_jump = None;
                 }
             }
-            Self::OP_LOAD | Self::OP_LOAD_REL | Self::OP_LOAD_IMM_REL | Self::OP_LOAD_NEXT => {
-                let dt = inst.arg0_data_type()?;
-                let addr = match opcode {
-                    Self::OP_LOAD => self.registers.get(inst.arg1_register())?,
-                    Self::OP_LOAD_REL => {
-                        self.registers.get(inst.arg1_register())?
-                            + self.registers.get(Register::ProgramCounter)?
-                    }
-                    Self::OP_LOAD_IMM_REL => {
-                        (self.registers.get(Register::ProgramCounter)? as i32 + inst.imm_signed())
-                            as u32
-                    }
-                    Self::OP_LOAD_NEXT => {
+            DecodedInstruction::Not { dst, src } => {
+                let val = self.registers.get(src)?;
+                self.registers.set(dst, if val != 0 { 0 } else { 1 })?;
+            }
+            DecodedInstruction::Bool { dst, src } => {
+                let val = self.registers.get(src)?;
+                self.registers.set(dst, if val != 0 { 1 } else { 0 })?;
+            }
+            DecodedInstruction::TestZero { reg, zero } => {
+                let val = self.registers.get(reg)?;
+                let is_true = (val == 0) == zero;
+                inst_jump = Some(if is_true { 1 } else { 2 });
+            }
+            DecodedInstruction::LoadImmediate {
+                dst,
+                data_type,
+                imm,
+            } => match data_type {
+                DataType::I16 => self.registers.set(dst, imm as i16 as u32)?,
+                DataType::U16 => self.registers.set(dst, imm as u32)?,
+                _ => return Err(ProcessorError::UnsupportedDataType(inst, data_type)),
+            },
+            DecodedInstruction::Load {
+                dst,
+                data_type: dt,
+                address,
+            } => {
+                let addr = match address {
+                    LoadAddress::Register(reg) => self.registers.get(reg)?,
+                    LoadAddress::Relative(reg) => pc.wrapping_add(self.registers.get(reg)?),
+                    LoadAddress::Immediate(offset) => pc.wrapping_add_signed(offset as i32),
+                    LoadAddress::Next => {
                         inst_jump = Some(2);
-                        pc + 4
+                        pc.wrapping_add(Self::BYTES_PER_WORD)
                     }
-                    _ => return Err(ProcessorError::UnknownInstruction(inst)),
                 };
-                let reg_target = inst.arg0_register();
-
-                if dt.signed() {
-                    match dt.byte_size() {
-                        1 => self
-                            .registers
-                            .set(reg_target, (self.memory.get(addr)? as i32) as u32)?,
-                        2 => self
-                            .registers
-                            .set(reg_target, (self.memory.get_u16(addr)? as i32) as u32)?,
-                        4 => self.registers.set(reg_target, self.memory.get_u32(addr)?)?,
-                        _ => return Err(ProcessorError::UnknownInstruction(inst)),
-                    }
+                let access = if address == LoadAddress::Next {
+                    Access::Execute
                 } else {
-                    match dt.byte_size() {
-                        1 => self
-                            .registers
-                            .set(reg_target, self.memory.get(addr)? as u32)?,
-                        2 => self
-                            .registers
-                            .set(reg_target, self.memory.get_u16(addr)? as u32)?,
-                        4 => self.registers.set(reg_target, self.memory.get_u32(addr)?)?,
-                        _ => return Err(ProcessorError::UnknownInstruction(inst)),
-                    }
-                }
+                    Access::Read
+                };
+                self.check_access(addr, dt.byte_size() as u32, access)?;
+
+                let val = match (dt.byte_size(), dt.signed()) {
+                    (1, false) => self.memory.get(addr)? as u32,
+                    (1, true) => self.memory.get(addr)? as i8 as u32,
+                    (2, false) => self.memory.get_u16(addr)? as u32,
+                    (2, true) => self.memory.get_u16(addr)? as i16 as u32,
+                    _ => self.memory.get_u32(addr)?,
+                };
+                self.registers.set(dst, val)?;
             }
-            Self::OP_SAVE | Self::OP_SAVE_REL => {
-                let dt = inst.arg0_data_type()?;
-                let source_reg = self.registers.get(inst.arg1_register())?;
+            DecodedInstruction::Save {
+                address,
+                data_type: dt,
+                src,
+                relative,
+            } => {
+                let source_reg = self.registers.get(src)?;
 
-                let addr = match opcode {
-                    Self::OP_SAVE => self.registers.get(inst.arg0_register())?,
-                    Self::OP_SAVE_REL => {
-                        (inst.imm_signed() + self.registers.get(inst.arg0_register())? as i32)
-                            as u32
-                    }
-                    _ => return Err(ProcessorError::UnknownInstruction(inst)),
+                let addr = if relative {
+                    pc.wrapping_add(self.registers.get(address)?)
+                } else {
+                    self.registers.get(address)?
                 };
+                self.check_access(addr, dt.byte_size() as u32, Access::Write)?;
 
                 match dt.byte_size() {
                     1 => self.memory.set(addr, (source_reg & 0xFF) as u8)?,
                     2 => self.memory.set_u16(addr, (source_reg & 0xFFFF) as u16)?,
-                    4 => self.memory.set_u32(addr, source_reg)?,
-                    _ => return Err(ProcessorError::UnknownInstruction(inst)),
+                    _ => self.memory.set_u32(addr, source_reg)?,
                 };
             }
-            Self::OP_COPY => self.registers.set(
-                inst.arg0_register(),
-                self.registers.get(inst.arg1_register())?,
-            )?,
-            Self::OP_CONV => {
-                let t_src = inst.arg1_data_type()?;
-                let v_src = self.registers.get(inst.arg1_register())?;
-
-                let t_dest = inst.arg0_data_type()?;
+            DecodedInstruction::Copy { dst, src } => {
+                self.registers.set(dst, self.registers.get(src)?)?
+            }
+            DecodedInstruction::Convert {
+                dst,
+                dst_type: t_dest,
+                src,
+                src_type: t_src,
+            } => {
+                let v_src = self.registers.get(src)?;
+
+                // Interpret the register as a value of the source type before converting, where
+                // floats are converted through a saturating signed 32-bit integer
+                let v_int = match t_src {
+                    DataType::U8 => v_src as u8 as i64,
+                    DataType::I8 => v_src as i8 as i64,
+                    DataType::U16 => v_src as u16 as i64,
+                    DataType::I16 => v_src as i16 as i64,
+                    DataType::U32 => v_src as i64,
+                    DataType::I32 => v_src as i32 as i64,
+                    DataType::F32 => f32::from_bits(v_src) as i32 as i64,
+                };
 
                 let v_dest = match t_dest {
-                    DataType::U8 => {
-                        (match t_src {
-                            DataType::U8 | DataType::U16 | DataType::U32 => v_src as u8,
-                            DataType::I8 | DataType::I16 | DataType::I32 => (v_src as i32) as u8,
-                            DataType::F32 => (f32::from_bits(v_src) as i32) as u8,
-                        }) as u32
-                    }
-                    DataType::U16 => {
-                        (match t_src {
-                            DataType::U8 | DataType::U16 | DataType::U32 => v_src as u16,
-                            DataType::I8 | DataType::I16 | DataType::I32 => (v_src as i32) as u16,
-                            DataType::F32 => (f32::from_bits(v_src) as i32) as u16,
-                        }) as u32
-                    }
-                    DataType::U32 => match t_src {
-                        DataType::U8
-                        | DataType::U16
-                        | DataType::U32
-                        | DataType::I8
-                        | DataType::I16
-                        | DataType::I32 => v_src,
-                        DataType::F32 => (f32::from_bits(v_src) as i32) as u32,
-                    },
-                    DataType::I8 => {
-                        ((match t_src {
-                            DataType::U8 => (v_src as u8) as i8,
-                            DataType::U16 => (v_src as u16) as i8,
-                            DataType::U32 => v_src as i8,
-                            DataType::I8 => v_src as i8,
-                            DataType::I16 => (v_src as i16) as i8,
-                            DataType::I32 => (v_src as i32) as i8,
-                            DataType::F32 => (f32::from_bits(v_src) as i32) as i8,
-                        }) as i32) as u32
+                    DataType::U8 => v_int as u8 as u32,
+                    DataType::I8 => v_int as i8 as u32,
+                    DataType::U16 => v_int as u16 as u32,
+                    DataType::I16 => v_int as i16 as u32,
+                    DataType::U32 | DataType::I32 => v_int as u32,
+                    DataType::F32 if t_src == DataType::F32 => v_src,
+                    DataType::F32 => (v_int as f32).to_bits(),
+                };
+
+                self.registers.set(dst, v_dest)?;
+            }
+            DecodedInstruction::Block {
+                copy: is_copy,
+                data_type,
+                dst: dst_reg,
+                src: src_reg,
+                count: count_reg,
+            } => {
+                let size = data_type.byte_size() as u32;
+                let dst = self.registers.get(dst_reg)?;
+                let src = self.registers.get(src_reg)?;
+                let count = self.registers.get(count_reg)?;
+
+                // Copy from the end when the destination follows the source, such that
+                // overlapping regions are copied as if through an intermediate buffer
+                let backward = is_copy && dst > src;
+                let step_count = count.min(Self::BLOCK_STEP_ELEMENTS);
+                let offset = |i: u32| {
+                    let index = if backward { count - 1 - i } else { i };
+                    index.wrapping_mul(size)
+                };
+
+                // Check each element of the step before any are written, such that a
+                // protection trap may be retried from the registers alone
+                for i in 0..step_count {
+                    if is_copy {
+                        self.check_access(src.wrapping_add(offset(i)), size, Access::Read)?;
                     }
-                    DataType::I16 => {
-                        ((match t_src {
-                            DataType::U8 => (v_src as u8) as i16,
-                            DataType::U16 => (v_src as u16) as i16,
-                            DataType::U32 => v_src as i16,
-                            DataType::I8 => (v_src as i8) as i16,
-                            DataType::I16 => v_src as i16,
-                            DataType::I32 => (v_src as i32) as i16,
-                            DataType::F32 => (f32::from_bits(v_src) as i32) as i16,
-                        }) as i32) as u32
+                    self.check_access(dst.wrapping_add(offset(i)), size, Access::Write)?;
+                }
+
+                for i in 0..step_count {
+                    let val = if is_copy {
+                        let addr = src.wrapping_add(offset(i));
+                        match size {
+                            1 => self.memory.get(addr)? as u32,
+                            2 => self.memory.get_u16(addr)? as u32,
+                            _ => self.memory.get_u32(addr)?,
+                        }
+                    } else {
+                        src
+                    };
+
+                    let addr = dst.wrapping_add(offset(i));
+                    match size {
+                        1 => self.memory.set(addr, val as u8)?,
+                        2 => self.memory.set_u16(addr, val as u16)?,
+                        _ => self.memory.set_u32(addr, val)?,
                     }
-                    DataType::I32 => {
-                        (match t_src {
-                            DataType::U8 => (v_src as u8) as i32,
-                            DataType::U16 => (v_src as u16) as i32,
-                            DataType::U32 => v_src as i32,
-                            DataType::I8 => (v_src as i8) as i32,
-                            DataType::I16 => (v_src as i16) as i32,
-                            DataType::I32 => v_src as i32,
-                            DataType::F32 => f32::from_bits(v_src) as i32,
-                        }) as u32
+                }
+
+                // Record the progress in the registers, repeating the instruction until every
+                // element is moved, such that interrupts may be entered between each step
+                if !backward {
+                    let advance = step_count.wrapping_mul(size);
+                    self.registers.set(dst_reg, dst.wrapping_add(advance))?;
+                    if is_copy {
+                        self.registers.set(src_reg, src.wrapping_add(advance))?;
                     }
-                    DataType::F32 => (match t_src {
-                        DataType::U8 => (v_src as u8) as f32,
-                        DataType::U16 => (v_src as u16) as f32,
-                        DataType::U32 => v_src as f32,
-                        DataType::I8 => (v_src as i8) as f32,
-                        DataType::I16 => (v_src as i16) as f32,
-                        DataType::I32 => (v_src as i32) as f32,
-                        DataType::F32 => f32::from_bits(v_src),
-                    })
-                    .to_bits(),
-                };
+                }
+                self.registers.set(count_reg, count - step_count)?;
 
-                self.registers.set(inst.arg0_register(), v_dest)?;
+                if step_count < count {
+                    inst_jump = None;
+                    self.breakpoint_resume = Some(pc);
+                }
+
+                cycles = cycles.saturating_add(step_count * Self::CYCLES_BLOCK_ELEMENT);
             }
-            Opcode {
-                base: Self::OP_BASE_MATH,
-                ..
+            DecodedInstruction::Arithmetic {
+                op,
+                data_type,
+                dst,
+                a,
+                b,
             } => {
-                let arith = self.get_arith_operation(inst.arg0_data_type()?)?;
-
-                let val_a = self.registers.get(inst.arg1_register())?;
-                let val_b = self.registers.get(inst.arg2_register())?;
-
-                let res = match opcode {
-                    Self::OP_ADD => arith.add(val_a, val_b)?,
-                    Self::OP_SUB => arith.sub(val_a, val_b)?,
-                    Self::OP_MUL => arith.mul(val_a, val_b)?,
-                    Self::OP_DIV => arith.div(val_a, val_b)?,
-                    Self::OP_REM => arith.rem(val_a, val_b)?,
-                    Self::OP_NEG => arith.neg(val_a)?,
-                    _ => return Err(ProcessorError::UnknownInstruction(inst)),
+                let (val_a, val_b) = self.source_operands(data_type, a, b)?;
+                cycles += Self::indirect_cycles(a, b);
+
+                let arith = self.get_arith_operation(data_type)?;
+
+                let res = match op {
+                    ArithmeticOp::Add => arith.add(val_a, val_b)?,
+                    ArithmeticOp::Sub => arith.sub(val_a, val_b)?,
+                    ArithmeticOp::Mul | ArithmeticOp::MulWide => arith.mul(val_a, val_b)?,
+                    ArithmeticOp::Div | ArithmeticOp::DivRem => arith.div(val_a, val_b)?,
+                    ArithmeticOp::Rem => arith.rem(val_a, val_b)?,
+                    ArithmeticOp::Neg => arith.neg(val_a)?,
+                    ArithmeticOp::Mac => arith.mac(self.registers.get(dst)?, val_a, val_b)?,
                 };
 
-                self.registers.set(inst.arg0_register(), res.val)?;
+                // The excess register is written before the destination, such that the
+                // destination takes precedence if the same register
+                let excess = match op {
+                    ArithmeticOp::MulWide => Some(arith.mul_high(val_a, val_b)?),
+                    ArithmeticOp::DivRem => Some(arith.rem(val_a, val_b)?.val),
+                    _ => None,
+                };
+                if let Some(val) = excess {
+                    self.registers.set(Register::Overflow, val)?;
+                }
+
+                self.registers.set(dst, res.val)?;
                 self.registers.set_flag(RegisterFlag::Carry, res.carry)?;
+
+                if let Some(flags) = res.flags {
+                    self.registers
+                        .set_flag(RegisterFlag::Overflow, flags.overflow)?;
+                    self.registers.set_flag(RegisterFlag::Zero, flags.zero)?;
+                    self.registers
+                        .set_flag(RegisterFlag::Negative, flags.negative)?;
+                }
             }
-            Opcode {
-                base: Self::OP_BASE_BITS,
-                ..
+            DecodedInstruction::Bitwise {
+                op,
+                data_type,
+                dst,
+                a,
+                b,
             } => {
-                let bitwise = self.get_bitwise_operation(inst.arg0_data_type()?)?;
-
-                let val_a = self.registers.get(inst.arg1_register())?;
-                let val_b = self.registers.get(inst.arg2_register())?;
-
-                let res = match opcode {
-                    Self::OP_BAND => bitwise.band(val_a, val_b)?,
-                    Self::OP_BOR => bitwise.bor(val_a, val_b)?,
-                    Self::OP_BXOR => bitwise.bxor(val_a, val_b)?,
-                    Self::OP_BSHL => bitwise.bsftl(val_a, val_b)?,
-                    Self::OP_BSHR => bitwise.bsftr(val_a, val_b)?,
-                    Self::OP_BNOT => bitwise.bnot(val_a)?,
-                    _ => return Err(ProcessorError::UnknownInstruction(inst)),
+                let (val_a, val_b) = self.source_operands(data_type, a, b)?;
+                cycles += Self::indirect_cycles(a, b);
+
+                let bitwise = self.get_bitwise_operation(data_type)?;
+
+                let res = match op {
+                    BitwiseOp::And => bitwise.band(val_a, val_b)?,
+                    BitwiseOp::Or => bitwise.bor(val_a, val_b)?,
+                    BitwiseOp::Xor => bitwise.bxor(val_a, val_b)?,
+                    BitwiseOp::ShiftLeft => bitwise.bsftl(val_a, val_b)?,
+                    BitwiseOp::ShiftRight => bitwise.bsftr(val_a, val_b)?,
+                    BitwiseOp::Not => bitwise.bnot(val_a)?,
                 };
 
-                self.registers.set(inst.arg0_register(), res.val)?;
+                self.registers.set(dst, res.val)?;
                 self.registers.set_flag(RegisterFlag::Carry, res.carry)?;
             }
-            Opcode {
-                base: Self::OP_BASE_TEST,
-                ..
+            DecodedInstruction::Compare {
+                op,
+                data_type,
+                dst,
+                a,
+                b,
             } => {
-                let relative = self.get_relative_operation(inst.arg0_data_type()?)?;
-
-                let val_a = self.registers.get(inst.arg1_register())?;
-                let val_b = self.registers.get(inst.arg2_register())?;
-
-                let res = match opcode {
-                    Self::OP_EQ => relative.eq(val_a, val_b)?,
-                    Self::OP_NEQ => relative.neq(val_a, val_b)?,
-                    Self::OP_GREATER => relative.gt(val_a, val_b)?,
-                    Self::OP_GREATER_EQ => relative.geq(val_a, val_b)?,
-                    Self::OP_LESS => relative.lt(val_a, val_b)?,
-                    Self::OP_LESS_EQ => relative.leq(val_a, val_b)?,
-                    _ => return Err(ProcessorError::UnknownInstruction(inst)),
+                let (val_a, val_b) = self.source_operands(data_type, a, b)?;
+                cycles += Self::indirect_cycles(a, b);
+
+                let relative = self.get_relative_operation(data_type)?;
+
+                let res = match op {
+                    CompareOp::Equal => relative.eq(val_a, val_b)?,
+                    CompareOp::NotEqual => relative.neq(val_a, val_b)?,
+                    CompareOp::Greater => relative.gt(val_a, val_b)?,
+                    CompareOp::GreaterEqual => relative.geq(val_a, val_b)?,
+                    CompareOp::Less => relative.lt(val_a, val_b)?,
+                    CompareOp::LessEqual => relative.leq(val_a, val_b)?,
                 };
 
-                self.registers
-                    .set(inst.arg0_register(), if res { 1 } else { 0 })?;
+                self.registers.set(dst, if res { 1 } else { 0 })?;
             }
-            _ => return Err(ProcessorError::UnknownInstruction(inst)),
         };
 
         // Step the program counter
         if let Some(jmp_val) = inst_jump {
             self.registers
-                .set(Register::ProgramCounter, pc + jmp_val * 4)?;
+                .set(Register::ProgramCounter, pc.wrapping_add(jmp_val * 4))?;
+        }
+
+        // Keep the mode and interrupt enable flags of user-mode code, which may only be changed
+        // from supervisor mode
+        if user_mode {
+            let mask = RegisterFlag::UserMode.get_mask() | RegisterFlag::InterruptEnable.get_mask();
+            let status = self.registers.get(Register::Status)?;
+            self.registers.set(
+                Register::Status,
+                (status & !mask) | (initial_registers.get(Register::Status)? & mask),
+            )?;
         }
 
+        // Stall for any slow memory accesses made by the instruction
+        cycles += self.memory.take_stall_cycles();
+
         // Check for any actions
-        for dev in self.devices.clone() {
-            if let Some(action) = dev.borrow_mut().on_step() {
+        for dev in self.devices.step_devices() {
+            let action = dev.borrow_mut().on_step(cycles);
+            if let Some(action) = action {
                 match action {
                     DeviceAction::CallInterrupt(num) => {
-                        self.queue_interrupt(Interrupt::Hardware(num))?;
+                        let int = Interrupt::Hardware(num);
+                        Self::interrupt_address(int).map_err(|e| {
+                            ProcessorError::Device(dev.borrow().device_id(), Box::new(e))
+                        })?;
+                        self.queue_interrupt(int)?;
                     }
                 }
             }
@@ -948,21 +1893,39 @@ impl Processor {
         if let Some(int) = self.interrupt_hold {
             if self.call_interrupt(int)? {
                 self.interrupt_hold = None;
+                cycles += Self::CYCLES_INTERRUPT_CALL + self.memory.take_stall_cycles();
             }
         }
 
-        Ok(())
+        self.last_register_changes = RegisterChanges::between(&initial_registers, &self.registers);
+        self.cycle_count += cycles as u64;
+
+        Ok(StepResult::Executed(cycles))
     }
 
     fn stack_push(&mut self, val: u32) -> Result<(), ProcessorError> {
         let sp_curr = self.registers.get(Register::StackPointer)?;
+        self.check_stack_bounds(sp_curr)?;
+        self.check_access(sp_curr, Self::BYTES_PER_WORD, Access::Write)?;
         self.memory.set_u32(sp_curr, val)?;
-        assert_eq!(self.memory.inspect_u32(sp_curr)?, val);
-        self.registers
-            .set(Register::StackPointer, sp_curr + Self::BYTES_PER_WORD)?;
+        self.registers.set(
+            Register::StackPointer,
+            sp_curr.wrapping_add(Self::BYTES_PER_WORD),
+        )?;
         Ok(())
     }
 
+    /// Checks that the stack word at the provided address is within the stack bounds, if any
+    fn check_stack_bounds(&self, address: u32) -> Result<(), ProcessorError> {
+        match &self.stack_bounds {
+            Some(b) if address < b.start => Err(ProcessorError::StackUnderflow),
+            Some(b) if address as u64 + Self::BYTES_PER_WORD as u64 > b.end as u64 => {
+                Err(ProcessorError::StackOverflow)
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn stack_pop(&mut self) -> Result<u32, ProcessorError> {
         let mut sp_curr = self.registers.get(Register::StackPointer)?;
 
@@ -971,6 +1934,8 @@ impl Processor {
         }
 
         sp_curr -= Self::BYTES_PER_WORD;
+        self.check_stack_bounds(sp_curr)?;
+        self.check_access(sp_curr, Self::BYTES_PER_WORD, Access::Read)?;
 
         self.registers.set(Register::StackPointer, sp_curr)?;
         Ok(self.memory.get_u32(sp_curr)?)
@@ -990,3 +1955,903 @@ impl Default for Processor {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{RingBufferDevice, TrapCause};
+    use crate::memory::{ProtectionRegion, ReadOnlySegment, ReadWriteSegment};
+    use alloc::vec;
+
+    /// Creates a processor with read-write memory, with the provided instruction words starting at address 0
+    fn build_processor(code: &[u32]) -> Processor {
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x1000))))
+            .unwrap();
+
+        for (i, inst) in code.iter().enumerate() {
+            for (j, b) in inst.to_be_bytes().into_iter().enumerate() {
+                cpu.memory_set((i * 4 + j) as u32, b).unwrap();
+            }
+        }
+
+        cpu
+    }
+
+    /// Provides a relative immediate jump instruction word
+    fn jmpri(offset: i16) -> u32 {
+        ((Processor::OP_JUMP_REL_IMM.to_byte() as u32) << 24) | (offset as u16 as u32)
+    }
+
+    /// Ensure that a breakpoint stops execution before the instruction is run, and resumes on the next step
+    #[test]
+    fn test_breakpoint_step() {
+        let mut cpu = build_processor(&[0, 0, jmpri(-8)]);
+        assert!(cpu.add_breakpoint(4));
+        assert!(!cpu.add_breakpoint(4));
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(1));
+        assert_eq!(cpu.step().unwrap(), StepResult::Breakpoint(4));
+        assert_eq!(cpu.get_current_pc().unwrap(), 4);
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(1));
+        assert_eq!(cpu.get_current_pc().unwrap(), 8);
+    }
+
+    /// Ensure that run until break stops at each breakpoint hit within a loop
+    #[test]
+    fn test_run_until_break() {
+        let mut cpu = build_processor(&[0, 0, jmpri(-8)]);
+        cpu.add_breakpoint(8);
+
+        for _ in 0..3 {
+            assert_eq!(cpu.run_until_break().unwrap(), 8);
+            assert_eq!(cpu.get_current_pc().unwrap(), 8);
+        }
+
+        assert!(cpu.remove_breakpoint(8));
+        assert!(!cpu.remove_breakpoint(8));
+
+        cpu.add_breakpoint(0);
+        cpu.add_breakpoint(4);
+        assert_eq!(cpu.breakpoints().count(), 2);
+        cpu.clear_breakpoints();
+        assert_eq!(cpu.breakpoints().count(), 0);
+    }
+
+    /// Provides a test device that requests the provided hardware interrupt on each step
+    struct InterruptRequestDevice(u32);
+
+    impl ProcessorDevice for InterruptRequestDevice {
+        fn on_step(&mut self, _cycles: u32) -> Option<DeviceAction> {
+            Some(DeviceAction::CallInterrupt(self.0))
+        }
+
+        fn device_id(&self) -> u16 {
+            0x42
+        }
+    }
+
+    /// Ensure that errors are categorized, and that device errors chain to their source
+    #[test]
+    fn test_error_category() {
+        use core::error::Error;
+
+        let div_inst = u32::from_be_bytes([Processor::OP_DIV.to_byte(), (5 << 5) | 6, 7, 8]);
+        let mut cpu = build_processor(&[div_inst]);
+        let err = cpu.step().unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Arithmetic);
+        assert!(err.source().is_some());
+
+        let mut cpu = build_processor(&[0]);
+        cpu.device_add(Rc::new(RefCell::new(InterruptRequestDevice(
+            Processor::NUM_INTERRUPT,
+        ))))
+        .unwrap();
+        let err = cpu.step().unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Device);
+
+        let source = err.source().unwrap();
+        assert!(source
+            .downcast_ref::<ProcessorError>()
+            .is_some_and(|e| e.category() == ErrorCategory::ControlFlow));
+    }
+
+    /// Ensure that the registers changed by the last instruction are recorded
+    #[test]
+    fn test_last_register_changes() {
+        let ldi = u32::from_be_bytes([Processor::OP_LOAD_IMM.to_byte(), (3 << 5) | 7, 0, 5]);
+        let mut cpu = build_processor(&[ldi, 0]);
+
+        cpu.step().unwrap();
+        let changes = cpu.last_register_changes();
+        assert_eq!(changes.len(), 2);
+        assert!(changes.contains(Register::ProgramCounter));
+        assert!(changes.contains(Register::GeneralPurpose(7)));
+        assert!(!changes.contains(Register::GeneralPurpose(6)));
+
+        cpu.step().unwrap();
+        let changes = cpu.last_register_changes();
+        assert_eq!(
+            changes.iter().collect::<Vec<_>>(),
+            [Register::ProgramCounter]
+        );
+    }
+
+    /// Ensure that a run stops on each of the supported stop conditions
+    #[test]
+    fn test_run_summary() {
+        let halt = (Processor::OP_HALT.to_byte() as u32) << 24;
+
+        let mut cpu = build_processor(&[0, 0, 0, halt]);
+        let summary = cpu.run(100);
+        assert_eq!(summary.instructions, 3);
+        assert_eq!(summary.cycles, 3);
+        assert!(matches!(summary.stop_reason, StopReason::Halted));
+
+        let mut cpu = build_processor(&[0, 0, jmpri(-8)]);
+        let summary = cpu.run(10);
+        assert_eq!(summary.instructions, 10);
+        assert!(matches!(summary.stop_reason, StopReason::BudgetExhausted));
+
+        cpu.add_breakpoint(8);
+        let summary = cpu.run(10);
+        assert!(matches!(summary.stop_reason, StopReason::Breakpoint(8)));
+
+        let mut cpu = build_processor(&[0, u32::MAX]);
+        let summary = cpu.run(10);
+        assert_eq!(summary.instructions, 1);
+        assert!(matches!(summary.stop_reason, StopReason::Error(_)));
+
+        let mut cpu = build_processor(&[0, 0, jmpri(-8)]);
+        let summary = cpu.run_cycles(7);
+        assert_eq!(summary.cycles, 7);
+        assert!(matches!(summary.stop_reason, StopReason::BudgetExhausted));
+    }
+
+    /// Ensure that a halt instruction halts the core until the processor is reset
+    #[test]
+    fn test_halt() {
+        let halt = (Processor::OP_HALT.to_byte() as u32) << 24;
+
+        let mut cpu = build_processor(&[0, halt]);
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(1));
+        assert_eq!(cpu.step().unwrap(), StepResult::Halted);
+        assert!(cpu.halted());
+        assert_eq!(cpu.get_current_pc().unwrap(), 4);
+        assert_eq!(cpu.cycle_count(), 1);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Halted);
+        assert_eq!(cpu.run(10).instructions, 0);
+        assert_eq!(cpu.run_until_break().unwrap(), 4);
+
+        cpu.reset(ResetType::Hard).unwrap();
+        assert!(!cpu.halted());
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(1));
+    }
+
+    /// Ensure that images are loaded into read-only and read-write memory before the reset vector is read
+    #[test]
+    fn test_load_image() {
+        let halt = (Processor::OP_HALT.to_byte() as u32) << 24;
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(
+            0,
+            Rc::new(RefCell::new(ReadOnlySegment::new(vec![0; 0x400]))),
+        )
+        .unwrap();
+        cpu.memory_add_segment(0x400, Rc::new(RefCell::new(ReadWriteSegment::new(0x2000))))
+            .unwrap();
+
+        let mut image = MemoryImage::new();
+        let (reset_vec, code) = (0x1000u32.to_be_bytes(), halt.to_be_bytes());
+        image.add_segment(0, reset_vec.to_vec()).unwrap();
+        image.add_segment(0x1000, code.to_vec()).unwrap();
+        image.add_segment(0x2000, vec![1, 2, 3]).unwrap();
+
+        cpu.load_image(&image).unwrap();
+        assert_eq!(cpu.get_current_pc().unwrap(), 0x1000);
+        assert_eq!(cpu.memory_inspect(0x2002).unwrap(), 3);
+        assert_eq!(cpu.step().unwrap(), StepResult::Halted);
+
+        assert!(matches!(
+            cpu.memory_set(0, 1),
+            Err(ProcessorError::Memory(MemoryError::ReadOnlyMemory(0)))
+        ));
+
+        image.add_segment(0x2400, vec![1]).unwrap();
+        assert!(cpu.load_image(&image).is_err());
+    }
+
+    /// Ensure that stack words are provided from the most recently pushed word, stopping at
+    /// the bottom of memory
+    #[test]
+    fn test_stack_words() {
+        let ldi = u32::from_be_bytes([Processor::OP_LOAD_IMM.to_byte(), (3 << 5) | 7, 0, 5]);
+        let push = u32::from_be_bytes([Processor::OP_PUSH.to_byte(), 7, 0, 0]);
+
+        let mut cpu = build_processor(&[ldi, push, push]);
+        cpu.registers.set(Register::StackPointer, 0x800).unwrap();
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+
+        assert_eq!(cpu.stack_pointer(), 0x808);
+        assert_eq!(cpu.stack_words(3), [(0x804, 5), (0x800, 5), (0x7fc, 0)]);
+
+        cpu.registers.set(Register::StackPointer, 8).unwrap();
+        assert_eq!(cpu.stack_words(4), [(4, push), (0, ldi)]);
+    }
+
+    /// Ensure that cycles are counted per instruction, including interrupt entry
+    #[test]
+    fn test_cycle_count() {
+        let push = u32::from_be_bytes([Processor::OP_PUSH.to_byte(), 7, 0, 0]);
+        let div = u32::from_be_bytes([Processor::OP_DIV.to_byte(), (5 << 5) | 6, 7, 7]);
+        let ldi = u32::from_be_bytes([Processor::OP_LOAD_IMM.to_byte(), (3 << 5) | 7, 0, 5]);
+
+        let mut cpu = build_processor(&[ldi, push, div]);
+        cpu.registers.set(Register::StackPointer, 0x800).unwrap();
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(1));
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(2));
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(cpu.cycle_count(), 11);
+
+        let mut cpu = build_processor(&[0]);
+        cpu.memory_set(Processor::BASE_HW_INT_ADDR + 3, 0x10)
+            .unwrap();
+        cpu.registers.set(Register::StackPointer, 0x800).unwrap();
+        cpu.registers
+            .set_flag(RegisterFlag::InterruptEnable, true)
+            .unwrap();
+        cpu.device_add(Rc::new(RefCell::new(InterruptRequestDevice(0))))
+            .unwrap();
+
+        assert_eq!(
+            cpu.step().unwrap(),
+            StepResult::Executed(1 + Processor::CYCLES_INTERRUPT_CALL)
+        );
+        assert_eq!(cpu.get_current_pc().unwrap(), 0x10);
+
+        cpu.reset(ResetType::Hard).unwrap();
+        assert_eq!(cpu.cycle_count(), 0);
+    }
+
+    /// Ensure that memory access latencies stall the instruction that performs the access
+    #[test]
+    fn test_memory_latency() {
+        use crate::memory::LatencySegment;
+
+        let ld = u32::from_be_bytes([Processor::OP_LOAD.to_byte(), (5 << 5) | 7, 6, 0]);
+        let sav = u32::from_be_bytes([Processor::OP_SAVE.to_byte(), (1 << 5) | 6, 7, 0]);
+
+        let mut cpu = build_processor(&[ld, sav]);
+        cpu.registers
+            .set(Register::GeneralPurpose(6), 0x1000)
+            .unwrap();
+
+        let mut slow = LatencySegment::new(Rc::new(RefCell::new(ReadWriteSegment::new(16))));
+        slow.set_read_latency(0, 4, 10);
+        slow.set_write_latency(0, 16, 4);
+        cpu.memory_add_segment(0x1000, Rc::new(RefCell::new(slow)))
+            .unwrap();
+
+        cpu.memory_set(0x1003, 0x10).unwrap();
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(2 + 10));
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(2 + 4));
+        assert_eq!(cpu.memory_inspect(0x1000).unwrap(), 0x10);
+    }
+
+    /// Ensure that restoring a snapshot returns the processor and memory-mapped devices to the saved state
+    #[test]
+    fn test_snapshot() {
+        use crate::device::InterruptClockDevice;
+
+        let ldi = u32::from_be_bytes([Processor::OP_LOAD_IMM.to_byte(), (3 << 5) | 7, 0, 5]);
+        let add = u32::from_be_bytes([Processor::OP_ADD.to_byte(), (5 << 5) | 7, 7, 7]);
+        let mut cpu = build_processor(&[ldi, add, add, add]);
+
+        let clock = Rc::new(RefCell::new(InterruptClockDevice::new(0)));
+        clock.borrow_mut().set(3, 100).unwrap();
+        cpu.memory_add_segment(0x1000, clock.clone()).unwrap();
+        cpu.device_add(clock.clone()).unwrap();
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        let snapshot = cpu.save_state();
+
+        cpu.step().unwrap();
+        cpu.memory_set(0x800, 1).unwrap();
+        assert_eq!(
+            cpu.get_register_state()
+                .get(Register::GeneralPurpose(7))
+                .unwrap(),
+            20
+        );
+        assert_ne!(cpu.save_state(), snapshot);
+
+        cpu.load_state(&snapshot).unwrap();
+        assert_eq!(cpu.save_state(), snapshot);
+        assert_eq!(cpu.get_current_pc().unwrap(), 8);
+        assert_eq!(
+            cpu.get_register_state()
+                .get(Register::GeneralPurpose(7))
+                .unwrap(),
+            10
+        );
+        assert_eq!(cpu.memory_inspect(0x800).unwrap(), 0);
+        assert_eq!(cpu.memory_inspect_u32(0x1006).unwrap(), 2);
+        assert_eq!(cpu.cycle_count(), 2);
+
+        let mut other = build_processor(&[]);
+        assert!(other.load_state(&snapshot).is_err());
+    }
+
+    /// Ensure that arithmetic sets the status flags, and that conditional jumps follow them
+    #[test]
+    fn test_status_flags() {
+        let add_u8 = u32::from_be_bytes([Processor::OP_ADD.to_byte(), (1 << 5) | 7, 6, 7]);
+        let add_i8 = u32::from_be_bytes([Processor::OP_ADD.to_byte(), (2 << 5) | 7, 6, 7]);
+        let jz = u32::from_be_bytes([Processor::OP_JUMP_ZERO.to_byte(), 0, 0, 0x10]);
+        let jnz = u32::from_be_bytes([Processor::OP_JUMP_NOT_ZERO.to_byte(), 0, 0, 0x10]);
+
+        let mut cpu = build_processor(&[add_u8, jz]);
+        cpu.registers.set(Register::GeneralPurpose(6), 255).unwrap();
+        cpu.registers.set(Register::GeneralPurpose(7), 1).unwrap();
+
+        cpu.step().unwrap();
+        assert!(cpu.registers.get_flag(RegisterFlag::Carry).unwrap());
+        assert!(cpu.registers.get_flag(RegisterFlag::Zero).unwrap());
+        assert!(!cpu.registers.get_flag(RegisterFlag::Overflow).unwrap());
+        assert!(!cpu.registers.get_flag(RegisterFlag::Negative).unwrap());
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.get_current_pc().unwrap(), 0x14);
+
+        let mut cpu = build_processor(&[add_i8, jz, jnz]);
+        cpu.registers.set(Register::GeneralPurpose(6), 127).unwrap();
+        cpu.registers.set(Register::GeneralPurpose(7), 1).unwrap();
+
+        cpu.step().unwrap();
+        assert!(!cpu.registers.get_flag(RegisterFlag::Carry).unwrap());
+        assert!(cpu.registers.get_flag(RegisterFlag::Overflow).unwrap());
+        assert!(cpu.registers.get_flag(RegisterFlag::Negative).unwrap());
+        assert!(!cpu.registers.get_flag(RegisterFlag::Zero).unwrap());
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.get_current_pc().unwrap(), 8);
+        cpu.step().unwrap();
+        assert_eq!(cpu.get_current_pc().unwrap(), 0x18);
+    }
+
+    /// Ensure that register-indirect source arguments read memory with the instruction data type
+    #[test]
+    fn test_indirect_operands() {
+        let flag = Instruction::INDIRECT_FLAG;
+        let add = u32::from_be_bytes([Processor::OP_ADD.to_byte(), (5 << 5) | 6, 6, 7 | flag]);
+        let sub = u32::from_be_bytes([Processor::OP_SUB.to_byte(), (4 << 5) | 9, 8 | flag, 6]);
+
+        let mut cpu = build_processor(&[add, sub]);
+        cpu.memory.set_u32(0x800, 1000).unwrap();
+        cpu.memory.set_u16(0x804, (-5i16) as u16).unwrap();
+        cpu.registers.set(Register::GeneralPurpose(6), 24).unwrap();
+        cpu.registers
+            .set(Register::GeneralPurpose(7), 0x800)
+            .unwrap();
+        cpu.registers
+            .set(Register::GeneralPurpose(8), 0x804)
+            .unwrap();
+
+        assert_eq!(
+            cpu.step().unwrap(),
+            StepResult::Executed(1 + Processor::CYCLES_INDIRECT_OPERAND)
+        );
+        assert_eq!(
+            cpu.registers.get(Register::GeneralPurpose(6)).unwrap(),
+            1024
+        );
+
+        cpu.step().unwrap();
+        assert_eq!(
+            cpu.registers.get(Register::GeneralPurpose(9)).unwrap() as i16,
+            -5 - 1024
+        );
+    }
+
+    /// Ensure that multiply-accumulate adds the product to the destination, setting the flags
+    /// if either the multiply or the add exceeds the data type
+    #[test]
+    fn test_multiply_accumulate() {
+        let mac_u32 = u32::from_be_bytes([Processor::OP_MAC.to_byte(), (5 << 5) | 7, 5, 6]);
+        let mac_i8 = u32::from_be_bytes([Processor::OP_MAC.to_byte(), (2 << 5) | 7, 5, 6]);
+        let mac_u8 = u32::from_be_bytes([Processor::OP_MAC.to_byte(), (1 << 5) | 7, 5, 6]);
+
+        let mut cpu = build_processor(&[mac_u32, mac_i8, mac_u8]);
+        cpu.registers.set(Register::GeneralPurpose(5), 3).unwrap();
+        cpu.registers.set(Register::GeneralPurpose(6), 4).unwrap();
+        cpu.registers.set(Register::GeneralPurpose(7), 10).unwrap();
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(3));
+        assert_eq!(cpu.registers.get(Register::GeneralPurpose(7)).unwrap(), 22);
+
+        cpu.registers.set(Register::GeneralPurpose(5), 10).unwrap();
+        cpu.registers.set(Register::GeneralPurpose(6), 3).unwrap();
+        cpu.registers.set(Register::GeneralPurpose(7), 100).unwrap();
+
+        cpu.step().unwrap();
+        assert_eq!(
+            cpu.registers.get(Register::GeneralPurpose(7)).unwrap() as i32,
+            -126
+        );
+        assert!(!cpu.registers.get_flag(RegisterFlag::Carry).unwrap());
+        assert!(cpu.registers.get_flag(RegisterFlag::Overflow).unwrap());
+        assert!(cpu.registers.get_flag(RegisterFlag::Negative).unwrap());
+
+        cpu.registers.set(Register::GeneralPurpose(5), 16).unwrap();
+        cpu.registers.set(Register::GeneralPurpose(6), 16).unwrap();
+        cpu.registers.set(Register::GeneralPurpose(7), 0).unwrap();
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(Register::GeneralPurpose(7)).unwrap(), 0);
+        assert!(cpu.registers.get_flag(RegisterFlag::Carry).unwrap());
+        assert!(cpu.registers.get_flag(RegisterFlag::Zero).unwrap());
+    }
+
+    /// Ensure that the widening multiply provides the upper half of the product, and the combined
+    /// divide provides the remainder, in the excess register
+    #[test]
+    fn test_wide_multiply_divide() {
+        let mulw_u32 = u32::from_be_bytes([Processor::OP_MULW.to_byte(), (5 << 5) | 7, 8, 9]);
+        let mulw_i16 = u32::from_be_bytes([Processor::OP_MULW.to_byte(), (4 << 5) | 7, 8, 9]);
+        let divrem_i32 = u32::from_be_bytes([Processor::OP_DIVREM.to_byte(), (6 << 5) | 7, 8, 9]);
+
+        let mut cpu = build_processor(&[mulw_u32, mulw_i16, divrem_i32]);
+        cpu.registers
+            .set(Register::GeneralPurpose(8), 0x1234_5678)
+            .unwrap();
+        cpu.registers
+            .set(Register::GeneralPurpose(9), 0x100)
+            .unwrap();
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(3));
+        assert_eq!(
+            cpu.registers.get(Register::GeneralPurpose(7)).unwrap(),
+            0x3456_7800
+        );
+        assert_eq!(cpu.registers.get(Register::Overflow).unwrap(), 0x12);
+        assert!(cpu.registers.get_flag(RegisterFlag::Carry).unwrap());
+
+        cpu.registers
+            .set(Register::GeneralPurpose(8), -300i32 as u32)
+            .unwrap();
+        cpu.registers.set(Register::GeneralPurpose(9), 200).unwrap();
+
+        cpu.step().unwrap();
+        assert_eq!(
+            cpu.registers.get(Register::GeneralPurpose(7)).unwrap(),
+            0x15A0
+        );
+        assert_eq!(cpu.registers.get(Register::Overflow).unwrap(), u32::MAX);
+
+        cpu.registers
+            .set(Register::GeneralPurpose(8), -7i32 as u32)
+            .unwrap();
+        cpu.registers.set(Register::GeneralPurpose(9), 2).unwrap();
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(8));
+        assert_eq!(
+            cpu.registers.get(Register::GeneralPurpose(7)).unwrap() as i32,
+            -3
+        );
+        assert_eq!(cpu.registers.get(Register::Overflow).unwrap() as i32, -1);
+    }
+
+    /// Ensure that pushing onto a stack placed in device memory doesn't panic, as device memory
+    /// may not read back the value written
+    #[test]
+    fn test_push_device_stack() {
+        let push = u32::from_be_bytes([Processor::OP_PUSH.to_byte(), 6, 0, 0]);
+
+        let mut cpu = build_processor(&[push]);
+        cpu.device_attach(
+            "ring buffer",
+            0x2000,
+            Rc::new(RefCell::new(RingBufferDevice::new(16))),
+        )
+        .unwrap();
+        cpu.registers.set(Register::StackPointer, 0x2010).unwrap();
+        cpu.registers
+            .set(Register::GeneralPurpose(6), 0x1234_5678)
+            .unwrap();
+
+        assert!(cpu.step().is_ok());
+        assert_eq!(cpu.registers.get(Register::StackPointer).unwrap(), 0x2014);
+    }
+
+    /// Ensure that the unused second source of a unary instruction is never read, even when its
+    /// argument byte has the indirect flag set
+    #[test]
+    fn test_unary_ignores_second_source() {
+        let neg = 0xa59240b1;
+        let bnot = u32::from_be_bytes([Processor::OP_BNOT.to_byte(), 0x92, 0x40, 0xb1]);
+
+        for word in [neg, bnot] {
+            let mut cpu = build_processor(&[word]);
+            cpu.registers
+                .set(Register::GeneralPurpose(17), 0xFFFF_0000)
+                .unwrap();
+
+            let expected = Processor::instruction_cycles(Processor::OP_NEG);
+            assert_eq!(cpu.step().unwrap(), StepResult::Executed(expected));
+        }
+    }
+
+    /// Ensure that the stack bounds stop pushes past the limit and pops before the base, leaving
+    /// the stack pointer unchanged
+    #[test]
+    fn test_stack_bounds() {
+        let push = u32::from_be_bytes([Processor::OP_PUSH.to_byte(), 6, 0, 0]);
+        let pop = u32::from_be_bytes([Processor::OP_POP.to_byte(), 0, 0, 0]);
+
+        let mut cpu = build_processor(&[push, push, pop, pop, pop]);
+        cpu.set_stack_bounds(Some(0x800..0x808));
+        assert_eq!(cpu.stack_bounds(), Some(0x800..0x808));
+        cpu.registers.set(Register::StackPointer, 0x800).unwrap();
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(Register::StackPointer).unwrap(), 0x808);
+
+        cpu.registers.set(Register::ProgramCounter, 0).unwrap();
+        assert!(matches!(cpu.step(), Err(ProcessorError::StackOverflow)));
+        assert_eq!(cpu.registers.get(Register::StackPointer).unwrap(), 0x808);
+
+        cpu.registers.set(Register::ProgramCounter, 8).unwrap();
+        cpu.registers.set(Register::StackPointer, 0x804).unwrap();
+        cpu.step().unwrap();
+        assert!(matches!(cpu.step(), Err(ProcessorError::StackUnderflow)));
+        assert_eq!(cpu.registers.get(Register::StackPointer).unwrap(), 0x800);
+
+        // Without bounds, the stack may be placed anywhere in memory
+        cpu.set_stack_bounds(None);
+        cpu.registers.set(Register::ProgramCounter, 0).unwrap();
+        cpu.registers.set(Register::StackPointer, 0x80c).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers.get(Register::StackPointer).unwrap(), 0x810);
+    }
+
+    /// Ensure that a frame call saves only the return address and the caller frame, and that the
+    /// frame return releases any stack space reserved within the frame
+    #[test]
+    fn test_frame_call_return() {
+        let callf = u32::from_be_bytes([Processor::OP_CALL_FRAME.to_byte(), 7, 0, 0]);
+        let retf = u32::from_be_bytes([Processor::OP_RETURN_FRAME.to_byte(), 0, 0, 0]);
+
+        let mut cpu = build_processor(&[callf, 0, 0, 0, retf]);
+        cpu.registers.set(Register::StackPointer, 0x800).unwrap();
+        cpu.registers.set(Register::ArgumentBase, 0x123).unwrap();
+        cpu.registers
+            .set(Register::GeneralPurpose(7), 0x10)
+            .unwrap();
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(3));
+        assert_eq!(cpu.registers.get(Register::ProgramCounter).unwrap(), 0x10);
+        assert_eq!(cpu.registers.get(Register::StackPointer).unwrap(), 0x808);
+        assert_eq!(cpu.registers.get(Register::ArgumentBase).unwrap(), 0x808);
+        assert_eq!(cpu.memory_inspect_u32(0x800).unwrap(), 4);
+        assert_eq!(cpu.memory_inspect_u32(0x804).unwrap(), 0x123);
+
+        // Reserve space for local variables within the frame
+        cpu.registers.set(Register::StackPointer, 0x820).unwrap();
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Executed(3));
+        assert_eq!(cpu.registers.get(Register::ProgramCounter).unwrap(), 4);
+        assert_eq!(cpu.registers.get(Register::StackPointer).unwrap(), 0x800);
+        assert_eq!(cpu.registers.get(Register::ArgumentBase).unwrap(), 0x123);
+    }
+
+    /// Ensure that block copies handle overlapping regions, and that block sets store the value
+    /// in each element, consuming a cycle for each element
+    #[test]
+    fn test_block_copy_set() {
+        let bcpy = u32::from_be_bytes([Processor::OP_BLOCK_COPY.to_byte(), (3 << 5) | 5, 6, 7]);
+        let bset = u32::from_be_bytes([Processor::OP_BLOCK_SET.to_byte(), (1 << 5) | 5, 6, 7]);
+
+        let set_args = |cpu: &mut Processor, vals: [u32; 3]| {
+            for (i, v) in vals.into_iter().enumerate() {
+                cpu.registers
+                    .set(Register::GeneralPurpose(5 + i), v)
+                    .unwrap();
+            }
+        };
+
+        let mut cpu = build_processor(&[bcpy, bcpy, bset]);
+        for i in 0..8 {
+            cpu.memory.set(0x800 + i, i as u8 + 1).unwrap();
+        }
+
+        set_args(&mut cpu, [0x802, 0x800, 3]);
+        assert_eq!(
+            cpu.step().unwrap(),
+            StepResult::Executed(2 + 3 * Processor::CYCLES_BLOCK_ELEMENT)
+        );
+        assert_eq!(
+            (0..8)
+                .map(|i| cpu.memory.get(0x800 + i).unwrap())
+                .collect::<Vec<_>>(),
+            [1, 2, 1, 2, 3, 4, 5, 6]
+        );
+
+        set_args(&mut cpu, [0x800, 0x801, 2]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.memory.get_u32(0x800).unwrap(), 0x02010203);
+
+        set_args(&mut cpu, [0x804, 0xab, 3]);
+        cpu.step().unwrap();
+        assert_eq!(cpu.memory.get_u32(0x804).unwrap(), 0xababab06);
+    }
+    /// Ensure that long block copies are split into steps recording progress in the registers,
+    /// such that an interrupt may be entered and returned from before the copy completes
+    #[test]
+    fn test_block_copy_interrupt() {
+        let bcpy = u32::from_be_bytes([Processor::OP_BLOCK_COPY.to_byte(), (1 << 5) | 5, 6, 7]);
+        let retint = u32::from_be_bytes([Processor::OP_INTERRUPT_RETURN.to_byte(), 0, 0, 0]);
+
+        let mut code = vec![0; 0x11];
+        code[0] = bcpy;
+        code[0x10] = retint;
+        let mut cpu = build_processor(&code);
+        cpu.memory
+            .set_u32(
+                Processor::interrupt_address(Interrupt::Hardware(0)).unwrap(),
+                0x40,
+            )
+            .unwrap();
+        for i in 0..150 {
+            cpu.memory.set(0x900 + i, i as u8).unwrap();
+        }
+
+        for (i, v) in [0x800, 0x900, 150].into_iter().enumerate() {
+            cpu.registers
+                .set(Register::GeneralPurpose(5 + i), v)
+                .unwrap();
+        }
+        cpu.registers.set(Register::StackPointer, 0xC00).unwrap();
+        cpu.registers
+            .set_flag(RegisterFlag::InterruptEnable, true)
+            .unwrap();
+        cpu.add_breakpoint(0);
+
+        assert_eq!(cpu.step().unwrap(), StepResult::Breakpoint(0));
+        let n = Processor::BLOCK_STEP_ELEMENTS;
+        assert_eq!(
+            cpu.step().unwrap(),
+            StepResult::Executed(2 + n * Processor::CYCLES_BLOCK_ELEMENT)
+        );
+        let regs = cpu.get_register_state();
+        assert_eq!(regs.get(Register::ProgramCounter).unwrap(), 0);
+        assert_eq!(regs.get(Register::GeneralPurpose(5)).unwrap(), 0x800 + n);
+        assert_eq!(regs.get(Register::GeneralPurpose(6)).unwrap(), 0x900 + n);
+        assert_eq!(regs.get(Register::GeneralPurpose(7)).unwrap(), 150 - n);
+
+        // The breakpoint is not hit again until the instruction completes
+        assert!(matches!(cpu.step(), Ok(StepResult::Executed(_))));
+        cpu.clear_breakpoints();
+
+        // The interrupt is entered between steps, and retint resumes the remaining elements
+        assert!(cpu.trigger_hardware_interrupt(0).unwrap());
+        assert_eq!(cpu.get_current_pc().unwrap(), 0x40);
+        cpu.step().unwrap();
+        assert_eq!(cpu.get_current_pc().unwrap(), 0);
+
+        while cpu.get_current_pc().unwrap() == 0 {
+            assert!(matches!(cpu.step(), Ok(StepResult::Executed(_))));
+        }
+        assert_eq!(cpu.get_current_pc().unwrap(), 4);
+        assert_eq!(
+            cpu.get_register_state()
+                .get(Register::GeneralPurpose(7))
+                .unwrap(),
+            0
+        );
+        assert!((0..150).all(|i| cpu.memory.get(0x800 + i).unwrap() == i as u8));
+    }
+
+    /// Ensure that user-mode violations trap to the supervisor on the supervisor stack, leaving
+    /// memory unchanged, and that retint resumes the faulting instruction in user mode
+    #[test]
+    fn test_protection_trap() {
+        let sav = u32::from_be_bytes([Processor::OP_SAVE.to_byte(), (5 << 5) | 6, 7, 0]);
+        let copy_stat = u32::from_be_bytes([Processor::OP_COPY.to_byte(), 1, 8, 0]);
+        let intoff = u32::from_be_bytes([Processor::OP_INTERRUPT_DISABLE.to_byte(), 0, 0, 0]);
+        let retint = u32::from_be_bytes([Processor::OP_INTERRUPT_RETURN.to_byte(), 0, 0, 0]);
+
+        let mut code = vec![0; 0x14];
+        code[..3].copy_from_slice(&[sav, copy_stat, intoff]);
+        code[0x10] = retint;
+        let mut cpu = build_processor(&code);
+        cpu.memory
+            .set_u32(
+                Processor::interrupt_address(Interrupt::Hardware(3)).unwrap(),
+                0x40,
+            )
+            .unwrap();
+
+        let unit = Rc::new(RefCell::new(ProtectionUnit::new(2)));
+        unit.borrow_mut().set_region(
+            0,
+            ProtectionRegion {
+                base: 0,
+                size: 0x40,
+                permissions: ProtectionUnit::PERMISSION_READ | ProtectionUnit::PERMISSION_EXECUTE,
+            },
+        );
+        unit.borrow_mut().set_region(
+            1,
+            ProtectionRegion {
+                base: 0x800,
+                size: 0x100,
+                permissions: ProtectionUnit::PERMISSION_READ | ProtectionUnit::PERMISSION_WRITE,
+            },
+        );
+        unit.borrow_mut().set_trap_irq(Some(3));
+        unit.borrow_mut().set_supervisor_stack(0xC00);
+        cpu.set_protection_unit(Some(unit.clone()));
+
+        cpu.registers
+            .set_flag(RegisterFlag::UserMode, true)
+            .unwrap();
+        cpu.registers
+            .set_flag(RegisterFlag::InterruptEnable, true)
+            .unwrap();
+        cpu.registers.set(Register::StackPointer, 0x800).unwrap();
+        cpu.registers
+            .set(Register::GeneralPurpose(6), 0x900)
+            .unwrap();
+        cpu.registers.set(Register::GeneralPurpose(7), 77).unwrap();
+        assert!(cpu.user_mode());
+
+        cpu.step().unwrap();
+        assert!(!cpu.user_mode());
+        assert_eq!(cpu.get_current_pc().unwrap(), 0x40);
+        assert_eq!(
+            unit.borrow().last_fault(),
+            Some(ProtectionFault::Access(Access::Write, 0x900))
+        );
+        assert_eq!(cpu.memory.get_u32(0x900).unwrap(), 0);
+        assert_eq!(cpu.memory.get_u32(0xC00).unwrap(), 0);
+        assert_eq!(cpu.memory.get_u32(0xC08).unwrap(), 0x800);
+
+        cpu.step().unwrap();
+        assert!(cpu.user_mode());
+        assert_eq!(cpu.get_current_pc().unwrap(), 0);
+        assert_eq!(cpu.registers.get(Register::StackPointer).unwrap(), 0x800);
+
+        cpu.registers
+            .set(Register::GeneralPurpose(6), 0x804)
+            .unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.memory.get_u32(0x804).unwrap(), 77);
+
+        // User-mode code may change the other flags, but not the mode or interrupt enable
+        cpu.step().unwrap();
+        assert!(cpu.user_mode());
+        assert!(cpu
+            .registers
+            .get_flag(RegisterFlag::InterruptEnable)
+            .unwrap());
+
+        cpu.step().unwrap();
+        assert_eq!(
+            unit.borrow().last_fault(),
+            Some(ProtectionFault::PrivilegedInstruction(8))
+        );
+        cpu.step().unwrap();
+
+        unit.borrow_mut().set_trap_irq(None);
+        assert!(matches!(
+            cpu.step(),
+            Err(ProcessorError::Protection(
+                ProtectionFault::PrivilegedInstruction(8)
+            ))
+        ));
+    }
+
+    /// Ensure that unhandled faults are recorded in the trap-info block with the registers
+    /// before the faulting instruction, calling the trap interrupt unless a trap is pending
+    #[test]
+    fn test_trap_report() {
+        let div_inst = u32::from_be_bytes([Processor::OP_DIV.to_byte(), (5 << 5) | 6, 7, 8]);
+        let mut cpu = build_processor(&[0, div_inst]);
+        cpu.memory
+            .set_u32(
+                Processor::interrupt_address(Interrupt::Hardware(2)).unwrap(),
+                0x40,
+            )
+            .unwrap();
+
+        let reporter = Rc::new(RefCell::new(TrapInfoDevice::new()));
+        reporter.borrow_mut().set_trap_irq(Some(2));
+        reporter.borrow_mut().set_snapshot_pointer(0x800);
+        cpu.set_trap_reporter(Some(reporter.clone()));
+
+        cpu.registers
+            .set_flag(RegisterFlag::InterruptEnable, true)
+            .unwrap();
+        cpu.registers.set(Register::StackPointer, 0x900).unwrap();
+        cpu.registers.set(Register::GeneralPurpose(6), 5).unwrap();
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.get_current_pc().unwrap(), 0x40);
+
+        let trap = reporter.borrow().last_trap().unwrap();
+        assert_eq!(trap.cause, TrapCause::Arithmetic);
+        assert_eq!(trap.pc, 4);
+        assert_eq!(trap.registers[6], 5);
+        assert!(reporter.borrow().pending());
+        assert_eq!(cpu.memory.get_u32(0x800).unwrap(), 4);
+        assert_eq!(cpu.memory.get_u32(0x818).unwrap(), 5);
+
+        // A fault while the trap is pending stops the processor, keeping the first trap
+        cpu.registers.set(Register::ProgramCounter, 2).unwrap();
+        assert!(matches!(
+            cpu.step(),
+            Err(ProcessorError::OpcodeAlignment(2))
+        ));
+        assert_eq!(reporter.borrow().last_trap().unwrap().pc, 4);
+
+        reporter.borrow_mut().set(2, 0).unwrap();
+        reporter.borrow_mut().set_trap_irq(None);
+        assert!(cpu.step().is_err());
+
+        let trap = reporter.borrow().last_trap().unwrap();
+        assert_eq!(trap.cause, TrapCause::OpcodeAlignment);
+        assert_eq!(trap.address, 2);
+    }
+
+    /// Ensure that provided instruction words are executed in place of memory, and that random
+    /// instruction streams produce errors rather than panics, with the registers randomized
+    /// after each error and periodically otherwise
+    #[test]
+    fn test_step_with_instruction() {
+        let mut cpu = build_processor(&[jmpri(-8)]);
+        let ldi = u32::from_be_bytes([Processor::OP_LOAD_IMM.to_byte(), 4 << 5 | 6, 0xff, 0xfe]);
+        assert!(matches!(
+            cpu.step_with_instruction(ldi),
+            Ok(StepResult::Executed(_))
+        ));
+        assert_eq!(
+            cpu.registers.get(Register::GeneralPurpose(6)).unwrap(),
+            -2i32 as u32
+        );
+        assert_eq!(cpu.get_current_pc().unwrap(), 4);
+
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as u32
+        };
+
+        for _ in 0..50_000 {
+            let def = &INSTRUCTION_TABLE[next() as usize % INSTRUCTION_TABLE.len()];
+            let word = (def.opcode.to_byte() as u32) << 24 | (next() & 0xff_ffff);
+            if cpu.step_with_instruction(word).is_err() || next() % 64 == 0 {
+                cpu.reset(ResetType::Hard).unwrap();
+                for i in 0..Register::NUM_REGISTERS {
+                    let val = next() >> (next() % 32);
+                    cpu.registers
+                        .set(Register::try_from(i).unwrap(), val)
+                        .unwrap();
+                }
+                cpu.registers
+                    .set(Register::ProgramCounter, next() & 0xffc)
+                    .unwrap();
+            }
+        }
+    }
+}
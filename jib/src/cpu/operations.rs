@@ -3,6 +3,7 @@ use core::fmt;
 pub struct OperationValue {
     pub val: u32,
     pub carry: bool,
+    pub flags: Option<ArithmeticFlags>,
 }
 
 impl From<(u32, bool)> for OperationValue {
@@ -10,6 +11,7 @@ impl From<(u32, bool)> for OperationValue {
         Self {
             val: value.0,
             carry: value.1,
+            flags: None,
         }
     }
 }
@@ -19,10 +21,37 @@ impl From<f32> for OperationValue {
         Self {
             val: value.to_bits(),
             carry: false,
+            flags: None,
         }
     }
 }
 
+impl OperationValue {
+    /// Provides the operation value for a floating point add, subtract, or multiply result,
+    /// where an infinite result is considered an overflow
+    fn float_arith(value: f32) -> Self {
+        Self {
+            flags: Some(ArithmeticFlags {
+                overflow: value.is_infinite(),
+                zero: value == 0.0,
+                negative: value.is_sign_negative() && !value.is_nan(),
+            }),
+            ..value.into()
+        }
+    }
+}
+
+/// Provides the status flags, in addition to the carry flag, resulting from an arithmetic operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArithmeticFlags {
+    /// The signed result did not fit within the data type
+    pub overflow: bool,
+    /// The result is zero
+    pub zero: bool,
+    /// The most significant bit of the result is set
+    pub negative: bool,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OperationError {
     DivideByZero,
@@ -38,6 +67,8 @@ impl fmt::Display for OperationError {
     }
 }
 
+impl core::error::Error for OperationError {}
+
 pub trait ArithmeticOperations {
     fn add(&self, a: u32, b: u32) -> Result<OperationValue, OperationError>;
     fn sub(&self, a: u32, b: u32) -> Result<OperationValue, OperationError>;
@@ -45,6 +76,13 @@ pub trait ArithmeticOperations {
     fn div(&self, a: u32, b: u32) -> Result<OperationValue, OperationError>;
     fn rem(&self, a: u32, b: u32) -> Result<OperationValue, OperationError>;
     fn neg(&self, a: u32) -> Result<OperationValue, OperationError>;
+
+    /// Provides the accumulator plus the product of the two values
+    fn mac(&self, acc: u32, a: u32, b: u32) -> Result<OperationValue, OperationError>;
+
+    /// Provides the upper half of the full product of the two values, extended to the register
+    /// width in the same way as the data type
+    fn mul_high(&self, a: u32, b: u32) -> Result<u32, OperationError>;
 }
 
 pub trait RelationalOperations {
@@ -65,37 +103,59 @@ pub trait BinaryOperations {
     fn bnot(&self, a: u32) -> Result<OperationValue, OperationError>;
 }
 
+/// Provides the operation value for an integer add, subtract, or multiply result, where the
+/// carry and overflow flags are computed using the unsigned and signed types of the same width
+macro_rules! arith_value {
+    ($res:expr, $carry:expr, $overflow:expr, $iname:ident) => {
+        OperationValue {
+            val: (($res.0 as i32) as u32),
+            carry: $carry,
+            flags: Some(ArithmeticFlags {
+                overflow: $overflow,
+                zero: $res.0 == 0,
+                negative: ($res.0 as $iname) < 0,
+            }),
+        }
+    };
+}
+
 macro_rules! define_arith_for_type {
-    ($sname:ident, $tname:ident) => {
+    ($sname:ident, $tname:ident, $uname:ident, $iname:ident) => {
         impl ArithmeticOperations for $sname {
             fn add(&self, a: u32, b: u32) -> Result<OperationValue, OperationError> {
                 let res = (a as $tname).overflowing_add(b as $tname);
-                Ok((((res.0 as i32) as u32), res.1).into())
+                let carry = (a as $uname).overflowing_add(b as $uname).1;
+                let overflow = (a as $iname).overflowing_add(b as $iname).1;
+                Ok(arith_value!(res, carry, overflow, $iname))
             }
 
             fn sub(&self, a: u32, b: u32) -> Result<OperationValue, OperationError> {
                 let res = (a as $tname).overflowing_sub(b as $tname);
-                Ok(((res.0 as i32) as u32, res.1).into())
+                let carry = (a as $uname).overflowing_sub(b as $uname).1;
+                let overflow = (a as $iname).overflowing_sub(b as $iname).1;
+                Ok(arith_value!(res, carry, overflow, $iname))
             }
 
             fn mul(&self, a: u32, b: u32) -> Result<OperationValue, OperationError> {
                 let res = (a as $tname).overflowing_mul(b as $tname);
-                Ok((((res.0 as i32) as u32), res.1).into())
+                let carry = (a as $uname).overflowing_mul(b as $uname).1;
+                let overflow = (a as $iname).overflowing_mul(b as $iname).1;
+                Ok(arith_value!(res, carry, overflow, $iname))
             }
 
             fn div(&self, a: u32, b: u32) -> Result<OperationValue, OperationError> {
-                if b == 0 {
+                if (b as $tname) == 0 {
                     return Err(OperationError::DivideByZero);
                 }
-                let res = (a as $tname) / (b as $tname);
+                let res = (a as $tname).wrapping_div(b as $tname);
                 Ok((((res as i32) as u32), false).into())
             }
 
             fn rem(&self, a: u32, b: u32) -> Result<OperationValue, OperationError> {
-                if b == 0 {
+                if (b as $tname) == 0 {
                     return Err(OperationError::DivideByZero);
                 }
-                let res = (a as $tname) % (b as $tname);
+                let res = (a as $tname).wrapping_rem(b as $tname);
                 Ok((((res as i32) as u32), false).into())
             }
 
@@ -103,6 +163,23 @@ macro_rules! define_arith_for_type {
                 let res = (a as $tname).overflowing_neg();
                 Ok((((res.0 as i32) as u32), res.1).into())
             }
+
+            fn mac(&self, acc: u32, a: u32, b: u32) -> Result<OperationValue, OperationError> {
+                // The carry and overflow flags are set if either the multiply or the add
+                // exceeds the data type
+                let prod = (a as $tname).wrapping_mul(b as $tname);
+                let res = (acc as $tname).overflowing_add(prod);
+                let carry = (a as $uname).overflowing_mul(b as $uname).1
+                    || (acc as $uname).overflowing_add(prod as $uname).1;
+                let overflow = (a as $iname).overflowing_mul(b as $iname).1
+                    || (acc as $iname).overflowing_add(prod as $iname).1;
+                Ok(arith_value!(res, carry, overflow, $iname))
+            }
+
+            fn mul_high(&self, a: u32, b: u32) -> Result<u32, OperationError> {
+                let full = (a as $tname as i128) * (b as $tname as i128);
+                Ok((full >> <$tname>::BITS) as $tname as i32 as u32)
+            }
         }
     };
 }
@@ -126,13 +203,18 @@ macro_rules! define_bitwise_for_type {
             }
 
             fn bsftr(&self, a: u32, b: u32) -> Result<OperationValue, OperationError> {
-                let res = (a as $tname).overflowing_shr(b);
-                Ok(((res.0 as i32) as u32, res.1).into())
+                // Shifting by the width of the type or more shifts out every bit, leaving
+                // the sign for signed types
+                let val = a as $tname;
+                let res = val
+                    .checked_shr(b)
+                    .unwrap_or(val >> (<$tname>::BITS - 1) >> 1);
+                Ok(((res as i32) as u32, b >= <$tname>::BITS).into())
             }
 
             fn bsftl(&self, a: u32, b: u32) -> Result<OperationValue, OperationError> {
-                let res = (a as $tname).overflowing_shl(b);
-                Ok(((res.0 as i32) as u32, res.1).into())
+                let res = (a as $tname).checked_shl(b).unwrap_or(0);
+                Ok(((res as i32) as u32, b >= <$tname>::BITS).into())
             }
 
             fn bnot(&self, a: u32) -> Result<OperationValue, OperationError> {
@@ -174,32 +256,32 @@ macro_rules! define_rel_for_type {
 }
 
 pub struct IntegerU8Operations;
-define_arith_for_type!(IntegerU8Operations, u8);
+define_arith_for_type!(IntegerU8Operations, u8, u8, i8);
 define_bitwise_for_type!(IntegerU8Operations, u8);
 define_rel_for_type!(IntegerU8Operations, u8);
 
 pub struct IntegerU16Operations;
-define_arith_for_type!(IntegerU16Operations, u16);
+define_arith_for_type!(IntegerU16Operations, u16, u16, i16);
 define_bitwise_for_type!(IntegerU16Operations, u16);
 define_rel_for_type!(IntegerU16Operations, u16);
 
 pub struct IntegerU32Operations;
-define_arith_for_type!(IntegerU32Operations, u32);
+define_arith_for_type!(IntegerU32Operations, u32, u32, i32);
 define_bitwise_for_type!(IntegerU32Operations, u32);
 define_rel_for_type!(IntegerU32Operations, u32);
 
 pub struct IntegerI8Operations;
-define_arith_for_type!(IntegerI8Operations, i8);
+define_arith_for_type!(IntegerI8Operations, i8, u8, i8);
 define_bitwise_for_type!(IntegerI8Operations, i8);
 define_rel_for_type!(IntegerI8Operations, i8);
 
 pub struct IntegerI16Operations;
-define_arith_for_type!(IntegerI16Operations, i16);
+define_arith_for_type!(IntegerI16Operations, i16, u16, i16);
 define_bitwise_for_type!(IntegerI16Operations, i16);
 define_rel_for_type!(IntegerI16Operations, i16);
 
 pub struct IntegerI32Operations;
-define_arith_for_type!(IntegerI32Operations, i32);
+define_arith_for_type!(IntegerI32Operations, i32, u32, i32);
 define_bitwise_for_type!(IntegerI32Operations, i32);
 define_rel_for_type!(IntegerI32Operations, i32);
 
@@ -207,17 +289,17 @@ pub struct FloatOperations;
 impl ArithmeticOperations for FloatOperations {
     fn add(&self, a: u32, b: u32) -> Result<OperationValue, OperationError> {
         let r = f32::from_bits(a) + f32::from_bits(b);
-        Ok(r.into())
+        Ok(OperationValue::float_arith(r))
     }
 
     fn sub(&self, a: u32, b: u32) -> Result<OperationValue, OperationError> {
         let r = f32::from_bits(a) - f32::from_bits(b);
-        Ok(r.into())
+        Ok(OperationValue::float_arith(r))
     }
 
     fn mul(&self, a: u32, b: u32) -> Result<OperationValue, OperationError> {
         let r = f32::from_bits(a) * f32::from_bits(b);
-        Ok(r.into())
+        Ok(OperationValue::float_arith(r))
     }
 
     fn div(&self, a: u32, b: u32) -> Result<OperationValue, OperationError> {
@@ -242,6 +324,15 @@ impl ArithmeticOperations for FloatOperations {
         let bf = f32::from_bits(a);
         Ok((-bf).into())
     }
+
+    fn mac(&self, acc: u32, a: u32, b: u32) -> Result<OperationValue, OperationError> {
+        let r = f32::from_bits(acc) + f32::from_bits(a) * f32::from_bits(b);
+        Ok(OperationValue::float_arith(r))
+    }
+
+    fn mul_high(&self, _a: u32, _b: u32) -> Result<u32, OperationError> {
+        Err(OperationError::UnuspportedOperation)
+    }
 }
 
 impl RelationalOperations for FloatOperations {
@@ -0,0 +1,108 @@
+use core::time::Duration;
+
+/// Provides the cycle budgets to run a processor at a fixed frame rate of host time, such that
+/// programs run at a consistent speed on any host. Each frame runs a fixed number of processor
+/// cycles, which should match the frame period of the display for one vertical sync per frame.
+/// When the host falls behind by more than the catch-up limit, the missed frames are skipped
+/// rather than run in a burst
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    frame_rate: u32,
+    frame_cycles: u64,
+    max_catch_up: u64,
+    frames_run: u64,
+    frames_skipped: u64,
+}
+
+impl FramePacer {
+    /// Defines the default number of late frames that may be run together to catch up
+    pub const DEFAULT_MAX_CATCH_UP: u64 = 4;
+
+    /// Constructs a new frame pacer running the provided number of frames per second of host
+    /// time, each of the provided number of cycles
+    pub fn new(frame_rate: u32, frame_cycles: u64) -> Self {
+        Self {
+            frame_rate: frame_rate.max(1),
+            frame_cycles: frame_cycles.max(1),
+            max_catch_up: Self::DEFAULT_MAX_CATCH_UP,
+            frames_run: 0,
+            frames_skipped: 0,
+        }
+    }
+
+    /// Sets the number of late frames that may be run together before frames are skipped
+    pub fn set_max_catch_up(&mut self, frames: u64) {
+        self.max_catch_up = frames.max(1);
+    }
+
+    pub fn frame_rate(&self) -> u32 {
+        self.frame_rate
+    }
+
+    pub fn frame_cycles(&self) -> u64 {
+        self.frame_cycles
+    }
+
+    /// Provides the number of frames budgeted since the pacer started
+    pub fn frames_run(&self) -> u64 {
+        self.frames_run
+    }
+
+    /// Provides the number of frames skipped as the host fell behind
+    pub fn frames_skipped(&self) -> u64 {
+        self.frames_skipped
+    }
+
+    /// Provides the number of frames that should have started by the elapsed host time
+    fn frames_due(&self, elapsed: Duration) -> u64 {
+        (elapsed.as_nanos() * self.frame_rate as u128 / 1_000_000_000) as u64 + 1
+    }
+
+    /// Provides the number of cycles to run for the host time elapsed since the pacer started,
+    /// being a whole number of frames, and marks those frames as run
+    pub fn budget(&mut self, elapsed: Duration) -> u64 {
+        let done = self.frames_run + self.frames_skipped;
+        let due = self.frames_due(elapsed).saturating_sub(done);
+
+        let frames = due.min(self.max_catch_up);
+        self.frames_skipped += due - frames;
+        self.frames_run += frames;
+
+        frames * self.frame_cycles
+    }
+
+    /// Provides the host time remaining until the next frame is due
+    pub fn until_next_frame(&self, elapsed: Duration) -> Duration {
+        let next = self.frames_run + self.frames_skipped;
+        let next_start = Duration::from_nanos(
+            (next as u128 * 1_000_000_000 / self.frame_rate as u128).min(u64::MAX as u128) as u64,
+        );
+        next_start.saturating_sub(elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensure that frames are budgeted as host time passes, and that frames are skipped when
+    /// the host falls too far behind
+    #[test]
+    fn test_frame_budget() {
+        let mut pacer = FramePacer::new(60, 1000);
+        let ms = Duration::from_millis;
+
+        assert_eq!(pacer.budget(ms(0)), 1000);
+        assert_eq!(pacer.budget(ms(10)), 0);
+        assert_eq!(pacer.until_next_frame(ms(10)).as_micros(), 6666);
+        assert_eq!(pacer.budget(ms(17)), 1000);
+        assert_eq!(pacer.budget(ms(50)), 2000);
+        assert_eq!(pacer.frames_run(), 4);
+
+        assert_eq!(pacer.budget(ms(1000)), 4000);
+        assert_eq!(pacer.frames_run(), 8);
+        assert_eq!(pacer.frames_skipped(), 53);
+        assert_eq!(pacer.budget(ms(1001)), 0);
+        assert_eq!(pacer.budget(ms(1017)), 1000);
+    }
+}
@@ -0,0 +1,137 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use super::Processor;
+use crate::memory::AccessCounts;
+
+/// Provides the number of times each instruction address was executed, along with the cycles
+/// consumed there and the memory accesses made by the program
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub executions: BTreeMap<u32, u64>,
+    pub cycles: BTreeMap<u32, u64>,
+    /// The number of times the instruction at the second address was executed directly after
+    /// the instruction at the first address, without any other step in between
+    pub transitions: BTreeMap<(u32, u32), u64>,
+    pub accesses: AccessCounts,
+    previous: Option<u32>,
+}
+
+impl Profile {
+    pub fn total_executions(&self) -> u64 {
+        self.executions.values().sum()
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.cycles.values().sum()
+    }
+
+    /// Provides each executed address along with its execution count, from the most executed
+    /// address, with ties in address order
+    pub fn hot_spots(&self) -> Vec<(u32, u64)> {
+        let mut spots = self
+            .executions
+            .iter()
+            .map(|(a, n)| (*a, *n))
+            .collect::<Vec<_>>();
+        spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        spots
+    }
+}
+
+impl Processor {
+    /// Starts counting the executions of each instruction address and the memory accesses made
+    /// by each instruction, discarding any counts already recorded. Instruction fetches are not
+    /// counted as memory reads
+    pub fn start_profile(&mut self) {
+        self.profile = Some(Profile::default());
+        self.memory.start_access_counts();
+    }
+
+    /// Stops profiling, providing the counts recorded
+    pub fn stop_profile(&mut self) -> Option<Profile> {
+        let mut profile = self.profile.take()?;
+        profile.accesses = self.memory.take_access_counts().unwrap_or_default();
+        Some(profile)
+    }
+
+    /// Provides a copy of the counts recorded so far, if profiling
+    pub fn profile(&self) -> Option<Profile> {
+        let mut profile = self.profile.clone()?;
+        profile.accesses = self.memory.access_counts().unwrap_or_default();
+        Some(profile)
+    }
+
+    pub fn profiling(&self) -> bool {
+        self.profile.is_some()
+    }
+
+    /// Counts the executed instruction, along with the transition from the previous executed
+    /// instruction, if profiling
+    pub(super) fn profile_step(&mut self, pc: u32, cycles: u32) {
+        if let Some(profile) = self.profile.as_mut() {
+            *profile.executions.entry(pc).or_default() += 1;
+            *profile.cycles.entry(pc).or_default() += cycles as u64;
+            if let Some(prev) = profile.previous.replace(pc) {
+                *profile.transitions.entry((prev, pc)).or_default() += 1;
+            }
+        }
+    }
+
+    /// Ends the current sequence of executed instructions, such that no transition is counted
+    /// into the next instruction executed
+    pub(super) fn profile_break(&mut self) {
+        if let Some(profile) = self.profile.as_mut() {
+            profile.previous = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::ReadWriteSegment;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    /// Ensure that executions and memory accesses are counted at each address, without counting
+    /// instruction fetches as reads
+    #[test]
+    fn test_profile() {
+        let ldi = |reg: u8, val: u16| {
+            let [hi, lo] = val.to_be_bytes();
+            u32::from_be_bytes([Processor::OP_LOAD_IMM.to_byte(), (3 << 5) | reg, hi, lo])
+        };
+        let sav = |dst: u8, src: u8| {
+            u32::from_be_bytes([Processor::OP_SAVE.to_byte(), (5 << 5) | dst, src, 0])
+        };
+        let jmpri = |offset: i16| {
+            ((Processor::OP_JUMP_REL_IMM.to_byte() as u32) << 24) | (offset as u16 as u32)
+        };
+        let program = [ldi(6, 0x80), sav(6, 7), jmpri(-4)];
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x100))))
+            .unwrap();
+        for (i, inst) in program.iter().enumerate() {
+            cpu.memory_set_range(i as u32 * 4, &inst.to_be_bytes())
+                .unwrap();
+        }
+
+        cpu.step().unwrap();
+        assert!(cpu.profile().is_none());
+        cpu.start_profile();
+        for _ in 0..6 {
+            cpu.step().unwrap();
+        }
+
+        let profile = cpu.stop_profile().unwrap();
+        assert!(!cpu.profiling());
+        assert_eq!(profile.total_executions(), 6);
+        assert_eq!(profile.hot_spots(), [(4, 3), (8, 3)]);
+        assert_eq!(profile.transitions.get(&(4, 8)), Some(&3));
+        assert_eq!(profile.transitions.get(&(8, 4)), Some(&2));
+        assert_eq!(profile.total_cycles(), cpu.cycle_count() - 1);
+        assert_eq!(profile.accesses.writes.get(&0x80), Some(&3));
+        assert!(profile.accesses.reads.is_empty());
+    }
+}
@@ -107,10 +107,16 @@ impl fmt::Display for RegisterError {
     }
 }
 
+impl core::error::Error for RegisterError {}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum RegisterFlag {
     InterruptEnable,
     Carry,
+    Overflow,
+    Zero,
+    Negative,
+    UserMode,
 }
 
 impl RegisterFlag {
@@ -118,6 +124,10 @@ impl RegisterFlag {
         match self {
             Self::InterruptEnable => 0,
             Self::Carry => 1,
+            Self::Overflow => 2,
+            Self::Zero => 3,
+            Self::Negative => 4,
+            Self::UserMode => 5,
         }
     }
 
@@ -191,3 +201,42 @@ impl Default for RegisterManager {
         }
     }
 }
+
+/// Provides the set of registers that were modified between two register states
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct RegisterChanges {
+    mask: u32,
+}
+
+impl RegisterChanges {
+    pub fn between(before: &RegisterManager, after: &RegisterManager) -> Self {
+        let mask = before
+            .registers
+            .iter()
+            .zip(after.registers.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .fold(0, |mask, (i, _)| mask | (1 << i));
+
+        Self { mask }
+    }
+
+    pub fn contains(&self, reg: Register) -> bool {
+        let ind = reg.get_index();
+        ind < Register::NUM_REGISTERS && (self.mask & (1 << ind)) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mask == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.mask.count_ones() as usize
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Register> + '_ {
+        (0..Register::NUM_REGISTERS)
+            .filter(|i| (self.mask & (1 << i)) != 0)
+            .filter_map(|i| Register::try_from(i).ok())
+    }
+}
@@ -0,0 +1,232 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{CpuSnapshot, Interrupt, RegisterManager};
+use crate::memory::SegmentState;
+
+/// Provides error conditions for parsing a serialized processor snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    InvalidHeader,
+    Truncated,
+    InvalidValue(usize),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "Invalid Snapshot Header"),
+            Self::Truncated => write!(f, "Truncated Snapshot"),
+            Self::InvalidValue(offset) => write!(f, "Invalid Snapshot Value at Offset {offset}"),
+        }
+    }
+}
+
+impl core::error::Error for SnapshotError {}
+
+/// Provides the remaining bytes of a serialized snapshot, along with the current offset
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() - self.offset < len {
+            return Err(SnapshotError::Truncated);
+        }
+        let res = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(res)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Reads a tag value, which must be less than the provided count
+    fn tag(&mut self, count: u8) -> Result<u8, SnapshotError> {
+        let offset = self.offset;
+        match self.u8()? {
+            v if v < count => Ok(v),
+            _ => Err(SnapshotError::InvalidValue(offset)),
+        }
+    }
+}
+
+impl CpuSnapshot {
+    /// Defines the identifier at the start of a serialized snapshot
+    pub const MAGIC: [u8; 4] = *b"JSNP";
+
+    /// Serializes the snapshot as the magic identifier, followed by the register values, the
+    /// held interrupt, the breakpoint resume address, the cycle count, the halted flag, and the
+    /// base address, length, and data of each memory segment state. Optional values are given
+    /// as a tag byte followed by any value. All values are big-endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(Self::MAGIC);
+
+        for r in self.registers.iter() {
+            bytes.extend(r.to_be_bytes());
+        }
+
+        match self.interrupt_hold {
+            None => bytes.push(0),
+            Some(Interrupt::Software(v)) => {
+                bytes.push(1);
+                bytes.extend(v.to_be_bytes());
+            }
+            Some(Interrupt::Hardware(v)) => {
+                bytes.push(2);
+                bytes.extend(v.to_be_bytes());
+            }
+        }
+
+        match self.breakpoint_resume {
+            None => bytes.push(0),
+            Some(addr) => {
+                bytes.push(1);
+                bytes.extend(addr.to_be_bytes());
+            }
+        }
+
+        bytes.extend(self.cycle_count.to_be_bytes());
+        bytes.push(self.halted as u8);
+
+        bytes.extend((self.memory.len() as u32).to_be_bytes());
+        for s in self.memory.iter() {
+            bytes.extend(s.base.to_be_bytes());
+            bytes.extend((s.data.len() as u32).to_be_bytes());
+            bytes.extend(&s.data);
+        }
+
+        bytes
+    }
+
+    /// Parses a snapshot serialized by [`CpuSnapshot::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        if !Self::is_snapshot(bytes) {
+            return Err(SnapshotError::InvalidHeader);
+        }
+
+        let mut reader = Reader {
+            bytes,
+            offset: Self::MAGIC.len(),
+        };
+
+        let mut registers = [0; RegisterManager::REGISTER_COUNT];
+        for r in registers.iter_mut() {
+            *r = reader.u32()?;
+        }
+
+        let interrupt_hold = match reader.tag(3)? {
+            1 => Some(Interrupt::Software(reader.u32()?)),
+            2 => Some(Interrupt::Hardware(reader.u32()?)),
+            _ => None,
+        };
+
+        let breakpoint_resume = match reader.tag(2)? {
+            1 => Some(reader.u32()?),
+            _ => None,
+        };
+
+        let cycle_count = reader.u64()?;
+        let halted = reader.tag(2)? != 0;
+
+        let count = reader.u32()?;
+        let mut memory = Vec::new();
+        for _ in 0..count {
+            let base = reader.u32()?;
+            let len = reader.u32()?;
+            memory.push(SegmentState {
+                base,
+                data: reader.take(len as usize)?.to_vec(),
+            });
+        }
+
+        if reader.offset != bytes.len() {
+            return Err(SnapshotError::InvalidValue(reader.offset));
+        }
+
+        Ok(Self {
+            registers,
+            interrupt_hold,
+            breakpoint_resume,
+            cycle_count,
+            halted,
+            memory,
+        })
+    }
+
+    /// Determines whether the provided data starts with the snapshot magic identifier
+    pub fn is_snapshot(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::MAGIC)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Ensure that snapshots are serialized and parsed without modification
+    #[test]
+    fn test_round_trip() {
+        let mut registers = [0; RegisterManager::REGISTER_COUNT];
+        registers[0] = 0x400;
+        registers[7] = 0xdeadbeef;
+
+        let snapshot = CpuSnapshot {
+            registers,
+            interrupt_hold: Some(Interrupt::Hardware(3)),
+            breakpoint_resume: Some(0x404),
+            cycle_count: 1200,
+            halted: true,
+            memory: vec![
+                SegmentState {
+                    base: 0,
+                    data: vec![1, 2, 3],
+                },
+                SegmentState {
+                    base: 0x1000,
+                    data: Vec::new(),
+                },
+            ],
+        };
+
+        let bytes = snapshot.to_bytes();
+        assert!(CpuSnapshot::is_snapshot(&bytes));
+        assert_eq!(CpuSnapshot::from_bytes(&bytes), Ok(snapshot));
+
+        assert_eq!(
+            CpuSnapshot::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SnapshotError::Truncated)
+        );
+        assert_eq!(
+            CpuSnapshot::from_bytes(&bytes[1..]),
+            Err(SnapshotError::InvalidHeader)
+        );
+
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert_eq!(
+            CpuSnapshot::from_bytes(&extra),
+            Err(SnapshotError::InvalidValue(bytes.len()))
+        );
+
+        let tag_offset = 4 + 4 * RegisterManager::REGISTER_COUNT;
+        let mut invalid = bytes.clone();
+        invalid[tag_offset] = 3;
+        assert_eq!(
+            CpuSnapshot::from_bytes(&invalid),
+            Err(SnapshotError::InvalidValue(tag_offset))
+        );
+    }
+}
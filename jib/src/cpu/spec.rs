@@ -0,0 +1,997 @@
+use alloc::vec::Vec;
+
+use super::{ErrorCategory, Interrupt, Opcode, Processor, Register, RegisterFlag};
+
+/// Provides the architectural state of a processor with a single read-write memory starting at
+/// address zero, as used by the executable specification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchState {
+    /// The register values
+    pub registers: [u32; Register::NUM_REGISTERS],
+    /// The memory contents, where any access past the end of memory is a memory fault
+    pub memory: Vec<u8>,
+    /// Any interrupt waiting to be called
+    pub interrupt_hold: Option<Interrupt>,
+    /// Whether the processor is halted by a halt instruction
+    pub halted: bool,
+}
+
+/// Provides the result of the specification, where an error provides the category of the
+/// processor error. The state reached when an error occurs is not specified
+pub type SpecResult<T> = Result<T, ErrorCategory>;
+
+/// Provides the data type of a typed argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpecType {
+    Int { bits: u32, signed: bool },
+    Float,
+}
+
+impl SpecType {
+    /// Decodes the type code held in the upper bits of an argument byte
+    fn decode(arg: u8) -> SpecResult<Self> {
+        Ok(match arg >> 5 {
+            1 => Self::Int {
+                bits: 8,
+                signed: false,
+            },
+            2 => Self::Int {
+                bits: 8,
+                signed: true,
+            },
+            3 => Self::Int {
+                bits: 16,
+                signed: false,
+            },
+            4 => Self::Int {
+                bits: 16,
+                signed: true,
+            },
+            5 => Self::Int {
+                bits: 32,
+                signed: false,
+            },
+            6 => Self::Int {
+                bits: 32,
+                signed: true,
+            },
+            7 => Self::Float,
+            _ => return Err(ErrorCategory::Arithmetic),
+        })
+    }
+
+    fn bytes(&self) -> u32 {
+        match self {
+            Self::Int { bits, .. } => bits / 8,
+            Self::Float => 4,
+        }
+    }
+
+    /// Provides the register word for a value of the type read from memory, extending integers
+    /// to the full register width
+    fn extend_word(&self, val: u32) -> u32 {
+        match *self {
+            Self::Int { bits, signed } => wrap(extend(val, bits, signed), bits, signed),
+            Self::Float => val,
+        }
+    }
+}
+
+/// Provides the value of the lower bits of the word, sign-extended if signed
+fn extend(val: u32, bits: u32, signed: bool) -> i128 {
+    let low = (val as u64 & ((1 << bits) - 1)) as i128;
+    if signed && low >> (bits - 1) != 0 {
+        low - (1 << bits)
+    } else {
+        low
+    }
+}
+
+/// Provides the register word for the value truncated to the width of the type, where values
+/// of signed types are sign-extended to the full register width and unsigned values are not
+fn wrap(val: i128, bits: u32, signed: bool) -> u32 {
+    extend(val.rem_euclid(1 << bits) as u32, bits, signed) as u32
+}
+
+/// Determines whether the value is representable in the type
+fn fits(val: i128, bits: u32, signed: bool) -> bool {
+    if signed {
+        (-(1 << (bits - 1))..(1 << (bits - 1))).contains(&val)
+    } else {
+        (0..(1 << bits)).contains(&val)
+    }
+}
+
+/// Provides the result register word and flags of an arithmetic or bitwise instruction, where
+/// the carry flag is always written, and the overflow, zero, and negative flags are only written
+/// when provided
+struct SpecValue {
+    val: u32,
+    carry: bool,
+    flags: Option<[(RegisterFlag, bool); 3]>,
+}
+
+impl SpecValue {
+    fn plain(val: u32, carry: bool) -> Self {
+        Self {
+            val,
+            carry,
+            flags: None,
+        }
+    }
+
+    /// Provides an integer result with the full set of flags, where the carry flag reports
+    /// unsigned carry and the overflow flag reports signed overflow for any data type
+    fn int(val: i128, bits: u32, signed: bool, carry: bool, overflow: bool) -> Self {
+        let word = wrap(val, bits, signed);
+        Self {
+            val: word,
+            carry,
+            flags: Some([
+                (RegisterFlag::Overflow, overflow),
+                (RegisterFlag::Zero, word == 0),
+                (RegisterFlag::Negative, (word >> (bits - 1)) & 1 != 0),
+            ]),
+        }
+    }
+
+    /// Provides a float result with the full set of flags, where an infinite result is
+    /// considered an overflow
+    fn float(val: f32) -> Self {
+        Self {
+            val: val.to_bits(),
+            carry: false,
+            flags: Some([
+                (RegisterFlag::Overflow, val.is_infinite()),
+                (RegisterFlag::Zero, val == 0.0),
+                (
+                    RegisterFlag::Negative,
+                    val.is_sign_negative() && !val.is_nan(),
+                ),
+            ]),
+        }
+    }
+}
+
+/// Executes the instruction at the program counter on a copy of the provided state, providing
+/// the new state. A halted processor is left unchanged
+pub fn step(state: &ArchState) -> SpecResult<ArchState> {
+    if state.halted {
+        return Ok(state.clone());
+    }
+
+    let pc = state.registers[Register::IDX_PROGRAM_COUNTER];
+    if !pc.is_multiple_of(Processor::BYTES_PER_WORD) {
+        return Err(ErrorCategory::ControlFlow);
+    }
+
+    execute(state, state.read(pc, 4)?)
+}
+
+/// Executes the instruction word on a copy of the provided state, as if it were located at the
+/// program counter, providing the new state. This includes calling any interrupt waiting to be
+/// called once the instruction completes. The specification does not include instruction
+/// extensions, and so each escape instruction is an unknown extension
+pub fn execute(state: &ArchState, inst: u32) -> SpecResult<ArchState> {
+    let mut next = state.clone();
+
+    if next.instruction(inst)? {
+        if let Some(int) = next.interrupt_hold {
+            if next.call_interrupt(int)? {
+                next.interrupt_hold = None;
+            }
+        }
+    }
+
+    Ok(next)
+}
+
+impl ArchState {
+    const PC: usize = Register::IDX_PROGRAM_COUNTER;
+    const SP: usize = Register::IDX_STACK_POINTER;
+    const OVF: usize = Register::IDX_OVERFLOW;
+    const FP: usize = Register::IDX_ARGUMENT_BASE;
+
+    fn flag(&self, flag: RegisterFlag) -> bool {
+        self.registers[Register::IDX_STATUS] & flag.get_mask() != 0
+    }
+
+    fn set_flag(&mut self, flag: RegisterFlag, value: bool) {
+        let status = &mut self.registers[Register::IDX_STATUS];
+        if value {
+            *status |= flag.get_mask();
+        } else {
+            *status &= !flag.get_mask();
+        }
+    }
+
+    /// Reads the big-endian value of the provided number of bytes from memory
+    fn read(&self, addr: u32, bytes: u32) -> SpecResult<u32> {
+        (0..bytes).try_fold(0, |val, i| {
+            let b = self.memory.get(addr.wrapping_add(i) as usize);
+            Ok((val << 8) | *b.ok_or(ErrorCategory::MemoryFault)? as u32)
+        })
+    }
+
+    /// Writes the lower bytes of the value to memory in big-endian order
+    fn write(&mut self, addr: u32, bytes: u32, val: u32) -> SpecResult<()> {
+        for i in 0..bytes {
+            let b = self
+                .memory
+                .get_mut(addr.wrapping_add(i) as usize)
+                .ok_or(ErrorCategory::MemoryFault)?;
+            *b = (val >> (8 * (bytes - 1 - i))) as u8;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, val: u32) -> SpecResult<()> {
+        let sp = self.registers[Self::SP];
+        self.write(sp, 4, val)?;
+        self.registers[Self::SP] = sp.wrapping_add(Processor::BYTES_PER_WORD);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> SpecResult<u32> {
+        let sp = self.registers[Self::SP]
+            .checked_sub(Processor::BYTES_PER_WORD)
+            .ok_or(ErrorCategory::MemoryFault)?;
+        self.registers[Self::SP] = sp;
+        self.read(sp, 4)
+    }
+
+    /// Pushes the value of every register, as held before the first push
+    fn push_registers(&mut self) -> SpecResult<()> {
+        for val in self.registers {
+            self.push(val)?;
+        }
+        Ok(())
+    }
+
+    /// Pops the value of every register in reverse order, optionally keeping the return register
+    fn pop_registers(&mut self, keep_return: bool) -> SpecResult<()> {
+        let mut registers = self.registers;
+        for i in (0..Register::NUM_REGISTERS).rev() {
+            let val = self.pop()?;
+            if !keep_return || i != Register::IDX_RETURN {
+                registers[i] = val;
+            }
+        }
+        self.registers = registers;
+        Ok(())
+    }
+
+    /// Holds the interrupt to be called, unless an interrupt of higher priority is held
+    fn queue_interrupt(&mut self, int: Interrupt) {
+        if self.interrupt_hold.is_none_or(|held| int < held) {
+            self.interrupt_hold = Some(int);
+        }
+    }
+
+    /// Calls the interrupt if interrupts are enabled and the interrupt vector is non-zero,
+    /// providing true if the interrupt was called
+    fn call_interrupt(&mut self, int: Interrupt) -> SpecResult<bool> {
+        if !self.flag(RegisterFlag::InterruptEnable) {
+            return Ok(false);
+        }
+
+        let (base, num) = match int {
+            Interrupt::Software(n) => (Processor::BASE_SW_INT_ADDR, n),
+            Interrupt::Hardware(n) => (Processor::BASE_HW_INT_ADDR, n),
+        };
+
+        if num >= Processor::NUM_INTERRUPT {
+            return Err(ErrorCategory::ControlFlow);
+        }
+
+        let target = self.read(base + num * Processor::BYTES_PER_WORD, 4)?;
+        if target == 0 {
+            return Ok(false);
+        }
+
+        self.push_registers()?;
+        self.registers[Self::PC] = target;
+        Ok(true)
+    }
+
+    /// Provides the identification word of a processor without any instruction extensions
+    fn identification(selector: u32) -> u32 {
+        match selector {
+            Processor::CPUID_VERSION => Processor::ISA_VERSION,
+            Processor::CPUID_FEATURES => {
+                Processor::FEATURE_INDIRECT_OPERANDS
+                    | Processor::FEATURE_MULTIPLY_ACCUMULATE
+                    | Processor::FEATURE_BLOCK_MEMORY
+                    | Processor::FEATURE_DEBUG_PORT
+                    | Processor::FEATURE_SYSCALL
+            }
+            Processor::CPUID_REGISTERS => Register::NUM_REGISTERS as u32,
+            _ => 0,
+        }
+    }
+
+    /// Provides the value of a source argument of an arithmetic, bitwise, or test instruction,
+    /// reading memory with the data type of the instruction if register-indirect
+    fn source(&self, arg: u8, dt: SpecType) -> SpecResult<u32> {
+        let val = self.registers[(arg & 0x1F) as usize];
+        if arg & 0x20 == 0 {
+            Ok(val)
+        } else {
+            Ok(dt.extend_word(self.read(val, dt.bytes())?))
+        }
+    }
+
+    /// Executes the instruction, providing false if the processor halted, such that interrupts
+    /// are not called
+    fn instruction(&mut self, inst: u32) -> SpecResult<bool> {
+        let [op, a0, a1, a2] = inst.to_be_bytes();
+        let [r0, r1, r2] = [a0, a1, a2].map(|a| (a & 0x1F) as usize);
+        let imm = u16::from_be_bytes([a1, a2]);
+
+        let pc = self.registers[Self::PC];
+        let mut advance = Some(1);
+
+        match Opcode::from(op) {
+            Processor::OP_NOOP => (),
+            Processor::OP_RESET => {
+                let target = self.read(Processor::SOFT_RESET_VECTOR, 4)?;
+                self.registers = [0; Register::NUM_REGISTERS];
+                self.registers[Self::PC] = target;
+                self.set_flag(RegisterFlag::InterruptEnable, true);
+                self.interrupt_hold = None;
+                advance = None;
+            }
+            Processor::OP_INTERRUPT_ENABLE => self.set_flag(RegisterFlag::InterruptEnable, true),
+            Processor::OP_INTERRUPT_DISABLE => self.set_flag(RegisterFlag::InterruptEnable, false),
+            Processor::OP_INTERRUPT | Processor::OP_SYSCALL => {
+                self.queue_interrupt(Interrupt::Software(imm as u32))
+            }
+            Processor::OP_INTERRUPT_REGISTER => {
+                self.queue_interrupt(Interrupt::Software(self.registers[r0]))
+            }
+            Processor::OP_CALL => {
+                // The target register is read after the registers are saved, such that the
+                // stack pointer provides the address past the saved registers
+                self.registers[Self::PC] = pc.wrapping_add(Processor::BYTES_PER_WORD);
+                self.push_registers()?;
+                self.registers[Self::PC] = self.registers[r0];
+                advance = None;
+            }
+            op @ (Processor::OP_RETURN | Processor::OP_INTERRUPT_RETURN) => {
+                self.pop_registers(op == Processor::OP_RETURN)?;
+                advance = None;
+            }
+            Processor::OP_CALL_FRAME => {
+                let target = self.registers[r0];
+                self.push(pc.wrapping_add(Processor::BYTES_PER_WORD))?;
+                self.push(self.registers[Self::FP])?;
+                self.registers[Self::FP] = self.registers[Self::SP];
+                self.registers[Self::PC] = target;
+                advance = None;
+            }
+            Processor::OP_RETURN_FRAME => {
+                self.registers[Self::SP] = self.registers[Self::FP];
+                self.registers[Self::FP] = self.pop()?;
+                self.registers[Self::PC] = self.pop()?;
+                advance = None;
+            }
+            Processor::OP_PUSH => self.push(self.registers[r0])?,
+            Processor::OP_POP => {
+                self.pop()?;
+            }
+            Processor::OP_POP_REG => self.registers[r0] = self.pop()?,
+            Processor::OP_JUMP => {
+                self.registers[Self::PC] = self.registers[r0];
+                advance = None;
+            }
+            Processor::OP_JUMP_REL => {
+                self.registers[Self::PC] = pc.wrapping_add(self.registers[r0]);
+                advance = None;
+            }
+            Processor::OP_JUMP_REL_IMM => {
+                self.registers[Self::PC] = pc.wrapping_add_signed(imm as i16 as i32);
+                advance = None;
+            }
+            Processor::OP_CPUID => self.registers[r0] = Self::identification(self.registers[r1]),
+            Processor::OP_ESCAPE => return Err(ErrorCategory::ControlFlow),
+            Processor::OP_HALT => {
+                self.halted = true;
+                return Ok(false);
+            }
+            op @ Opcode {
+                base: Processor::OP_BASE_BRANCH,
+                code,
+            } => {
+                let flag = match code / 2 {
+                    0 => RegisterFlag::Carry,
+                    1 => RegisterFlag::Overflow,
+                    2 => RegisterFlag::Zero,
+                    3 => RegisterFlag::Negative,
+                    _ => return Err(ErrorCategory::ControlFlow),
+                };
+
+                // Even codes branch if the flag is set, and odd codes if the flag is clear
+                if self.flag(flag) == (op.code % 2 == 0) {
+                    self.registers[Self::PC] = pc.wrapping_add_signed(imm as i16 as i32);
+                    advance = None;
+                }
+            }
+            Processor::OP_NOT => self.registers[r0] = (self.registers[r1] == 0) as u32,
+            Processor::OP_BOOL => self.registers[r0] = (self.registers[r1] != 0) as u32,
+            op @ (Processor::OP_TEST_ZERO | Processor::OP_TEST_NOT_ZERO) => {
+                let is_zero = self.registers[r0] == 0;
+                let is_true = is_zero == (op == Processor::OP_TEST_ZERO);
+                advance = Some(if is_true { 1 } else { 2 });
+            }
+            Processor::OP_LOAD_IMM => {
+                self.registers[r0] = match SpecType::decode(a0)? {
+                    SpecType::Int {
+                        bits: 16,
+                        signed: true,
+                    } => imm as i16 as u32,
+                    SpecType::Int {
+                        bits: 16,
+                        signed: false,
+                    } => imm as u32,
+                    _ => return Err(ErrorCategory::Arithmetic),
+                };
+            }
+            op @ (Processor::OP_LOAD
+            | Processor::OP_LOAD_REL
+            | Processor::OP_LOAD_IMM_REL
+            | Processor::OP_LOAD_NEXT) => {
+                let dt = SpecType::decode(a0)?;
+                let addr = match op {
+                    Processor::OP_LOAD => self.registers[r1],
+                    Processor::OP_LOAD_REL => pc.wrapping_add(self.registers[r1]),
+                    Processor::OP_LOAD_IMM_REL => pc.wrapping_add_signed(imm as i16 as i32),
+                    _ => {
+                        advance = Some(2);
+                        pc.wrapping_add(Processor::BYTES_PER_WORD)
+                    }
+                };
+
+                self.registers[r0] = dt.extend_word(self.read(addr, dt.bytes())?);
+            }
+            op @ (Processor::OP_SAVE | Processor::OP_SAVE_REL) => {
+                let dt = SpecType::decode(a0)?;
+                let addr = match op {
+                    Processor::OP_SAVE => self.registers[r0],
+                    _ => pc.wrapping_add(self.registers[r0]),
+                };
+
+                self.write(addr, dt.bytes(), self.registers[r1])?;
+            }
+            Processor::OP_COPY => self.registers[r0] = self.registers[r1],
+            Processor::OP_CONV => {
+                let src = SpecType::decode(a1)?;
+                let dst = SpecType::decode(a0)?;
+                let val = self.registers[r1];
+
+                self.registers[r0] = match (src, dst) {
+                    (SpecType::Float, SpecType::Float) => val,
+                    (SpecType::Int { bits, signed }, SpecType::Float) => {
+                        (extend(val, bits, signed) as f32).to_bits()
+                    }
+                    (SpecType::Float, SpecType::Int { bits, signed }) => {
+                        // Floats are converted through a saturating signed 32-bit integer
+                        wrap(f32::from_bits(val) as i32 as i128, bits, signed)
+                    }
+                    (
+                        SpecType::Int {
+                            bits: src_bits,
+                            signed: src_signed,
+                        },
+                        SpecType::Int { bits, signed },
+                    ) => wrap(extend(val, src_bits, src_signed), bits, signed),
+                };
+            }
+            op @ (Processor::OP_BLOCK_COPY | Processor::OP_BLOCK_SET) => {
+                let size = SpecType::decode(a0)?.bytes();
+                let (dst, src, count) =
+                    (self.registers[r0], self.registers[r1], self.registers[r2]);
+                let addr = |base: u32, i: u32| base.wrapping_add(i.wrapping_mul(size));
+
+                // Each step moves up to a fixed number of elements, taken from the end of the
+                // block when copying towards a following destination
+                let n = count.min(Processor::BLOCK_STEP_ELEMENTS);
+                let backward = op == Processor::OP_BLOCK_COPY && dst > src;
+                let first = if backward { count - n } else { 0 };
+
+                if op == Processor::OP_BLOCK_COPY {
+                    // Copy as if through an intermediate buffer, such that overlapping regions
+                    // provide the original source values
+                    let vals = (first..first + n)
+                        .map(|i| self.read(addr(src, i), size))
+                        .collect::<SpecResult<Vec<_>>>()?;
+                    for (i, val) in (first..first + n).zip(vals) {
+                        self.write(addr(dst, i), size, val)?;
+                    }
+                } else {
+                    for i in first..first + n {
+                        self.write(addr(dst, i), size, src)?;
+                    }
+                }
+
+                // Progress is kept in the registers, repeating the instruction until done
+                if !backward {
+                    self.registers[r0] = addr(dst, n);
+                    if op == Processor::OP_BLOCK_COPY {
+                        self.registers[r1] = addr(src, n);
+                    }
+                }
+                self.registers[r2] = count - n;
+                if n < count {
+                    advance = None;
+                }
+            }
+            op @ (Processor::OP_ADD
+            | Processor::OP_SUB
+            | Processor::OP_MUL
+            | Processor::OP_DIV
+            | Processor::OP_REM
+            | Processor::OP_NEG
+            | Processor::OP_MAC
+            | Processor::OP_MULW
+            | Processor::OP_DIVREM
+            | Processor::OP_BAND
+            | Processor::OP_BOR
+            | Processor::OP_BXOR
+            | Processor::OP_BSHL
+            | Processor::OP_BSHR
+            | Processor::OP_BNOT) => {
+                let dt = SpecType::decode(a0)?;
+                let unary = matches!(op, Processor::OP_NEG | Processor::OP_BNOT);
+                let a = self.source(a1, dt)?;
+                let b = if unary { 0 } else { self.source(a2, dt)? };
+
+                let res = if op.base == Processor::OP_BASE_MATH {
+                    Self::arithmetic(op, dt, self.registers[r0], a, b)?
+                } else {
+                    Self::bitwise(op, dt, a, b)?
+                };
+
+                // The destination takes precedence over the excess register if the same
+                if let Some(excess) = Self::excess(op, dt, a, b)? {
+                    self.registers[Self::OVF] = excess;
+                }
+                self.registers[r0] = res.val;
+                self.set_flag(RegisterFlag::Carry, res.carry);
+                for (flag, val) in res.flags.into_iter().flatten() {
+                    self.set_flag(flag, val);
+                }
+            }
+            op @ (Processor::OP_EQ
+            | Processor::OP_NEQ
+            | Processor::OP_GREATER
+            | Processor::OP_GREATER_EQ
+            | Processor::OP_LESS
+            | Processor::OP_LESS_EQ) => {
+                let dt = SpecType::decode(a0)?;
+                let (a, b) = (self.source(a1, dt)?, self.source(a2, dt)?);
+
+                let ord = match dt {
+                    SpecType::Int { bits, signed } => {
+                        Some(extend(a, bits, signed).cmp(&extend(b, bits, signed)))
+                    }
+                    SpecType::Float => f32::from_bits(a).partial_cmp(&f32::from_bits(b)),
+                };
+
+                let res = match op {
+                    Processor::OP_EQ => ord.is_some_and(|o| o.is_eq()),
+                    Processor::OP_NEQ => ord.is_none_or(|o| o.is_ne()),
+                    Processor::OP_GREATER => ord.is_some_and(|o| o.is_gt()),
+                    Processor::OP_GREATER_EQ => ord.is_some_and(|o| o.is_ge()),
+                    Processor::OP_LESS => ord.is_some_and(|o| o.is_lt()),
+                    Processor::OP_LESS_EQ => ord.is_some_and(|o| o.is_le()),
+                    _ => return Err(ErrorCategory::ControlFlow),
+                };
+
+                self.registers[r0] = res as u32;
+            }
+            _ => return Err(ErrorCategory::ControlFlow),
+        }
+
+        if let Some(n) = advance {
+            self.registers[Self::PC] = pc.wrapping_add(n * Processor::BYTES_PER_WORD);
+        }
+
+        Ok(true)
+    }
+
+    /// Provides the result of an arithmetic instruction, where the accumulator is only used by
+    /// the multiply-accumulate instruction
+    fn arithmetic(op: Opcode, dt: SpecType, acc: u32, a: u32, b: u32) -> SpecResult<SpecValue> {
+        let SpecType::Int { bits, signed } = dt else {
+            let (acc, a, b) = (f32::from_bits(acc), f32::from_bits(a), f32::from_bits(b));
+            return Ok(match op {
+                Processor::OP_ADD => SpecValue::float(a + b),
+                Processor::OP_SUB => SpecValue::float(a - b),
+                Processor::OP_MUL => SpecValue::float(a * b),
+                Processor::OP_MAC => SpecValue::float(acc + a * b),
+                Processor::OP_MULW => return Err(ErrorCategory::Arithmetic),
+                Processor::OP_DIV | Processor::OP_REM | Processor::OP_DIVREM if b == 0.0 => {
+                    return Err(ErrorCategory::Arithmetic);
+                }
+                Processor::OP_DIV | Processor::OP_DIVREM => {
+                    SpecValue::plain((a / b).to_bits(), false)
+                }
+                Processor::OP_REM => SpecValue::plain((a % b).to_bits(), false),
+                Processor::OP_NEG => SpecValue::plain((-a).to_bits(), false),
+                _ => return Err(ErrorCategory::ControlFlow),
+            });
+        };
+
+        let unsigned = |v| extend(v, bits, false);
+        let signed_val = |v| extend(v, bits, true);
+        let (ua, ub, sa, sb) = (unsigned(a), unsigned(b), signed_val(a), signed_val(b));
+
+        Ok(match op {
+            Processor::OP_ADD => SpecValue::int(
+                ua + ub,
+                bits,
+                signed,
+                !fits(ua + ub, bits, false),
+                !fits(sa + sb, bits, true),
+            ),
+            Processor::OP_SUB => SpecValue::int(
+                ua - ub,
+                bits,
+                signed,
+                !fits(ua - ub, bits, false),
+                !fits(sa - sb, bits, true),
+            ),
+            Processor::OP_MUL | Processor::OP_MULW => SpecValue::int(
+                ua * ub,
+                bits,
+                signed,
+                !fits(ua * ub, bits, false),
+                !fits(sa * sb, bits, true),
+            ),
+            Processor::OP_MAC => {
+                // The carry or overflow flag is set if either the multiply or the add would
+                // set the flag, where the add uses the truncated product
+                let prod = wrap(ua * ub, bits, false);
+                let (uacc, sacc) = (unsigned(acc), signed_val(acc));
+                SpecValue::int(
+                    uacc + unsigned(prod),
+                    bits,
+                    signed,
+                    !fits(ua * ub, bits, false) || !fits(uacc + unsigned(prod), bits, false),
+                    !fits(sa * sb, bits, true) || !fits(sacc + signed_val(prod), bits, true),
+                )
+            }
+            Processor::OP_DIV | Processor::OP_REM | Processor::OP_DIVREM | Processor::OP_NEG => {
+                let x = extend(a, bits, signed);
+                let y = extend(b, bits, signed);
+
+                match op {
+                    Processor::OP_NEG => {
+                        SpecValue::plain(wrap(-x, bits, signed), !fits(-x, bits, signed))
+                    }
+                    _ if y == 0 => return Err(ErrorCategory::Arithmetic),
+                    // Division truncates towards zero, wrapping if the quotient doesn't fit
+                    Processor::OP_DIV | Processor::OP_DIVREM => {
+                        SpecValue::plain(wrap(x / y, bits, signed), false)
+                    }
+                    _ => SpecValue::plain(wrap(x % y, bits, signed), false),
+                }
+            }
+            _ => return Err(ErrorCategory::ControlFlow),
+        })
+    }
+
+    /// Provides the value written to the excess register by the widening multiply, being the
+    /// upper half of the full product, or by the combined divide, being the remainder
+    fn excess(op: Opcode, dt: SpecType, a: u32, b: u32) -> SpecResult<Option<u32>> {
+        Ok(match (op, dt) {
+            (Processor::OP_MULW, SpecType::Int { bits, signed }) => {
+                let prod = extend(a, bits, signed) * extend(b, bits, signed);
+                Some(wrap(prod >> bits, bits, signed))
+            }
+            (Processor::OP_MULW, SpecType::Float) => return Err(ErrorCategory::Arithmetic),
+            (Processor::OP_DIVREM, _) => {
+                Some(Self::arithmetic(Processor::OP_REM, dt, 0, a, b)?.val)
+            }
+            _ => None,
+        })
+    }
+
+    /// Provides the result of a bitwise instruction, which is only defined for integer types
+    fn bitwise(op: Opcode, dt: SpecType, a: u32, b: u32) -> SpecResult<SpecValue> {
+        let SpecType::Int { bits, signed } = dt else {
+            return Err(ErrorCategory::Arithmetic);
+        };
+
+        let (x, y) = (extend(a, bits, signed), extend(b, bits, signed));
+
+        // Shift amounts are limited to the width of the type, shifting out every bit except the
+        // sign of signed right shifts, and setting the carry flag if the amount is not less
+        // than the width
+        let (shift, carry) = (b.min(bits), b >= bits);
+
+        Ok(match op {
+            Processor::OP_BAND => SpecValue::plain(wrap(x & y, bits, signed), false),
+            Processor::OP_BOR => SpecValue::plain(wrap(x | y, bits, signed), false),
+            Processor::OP_BXOR => SpecValue::plain(wrap(x ^ y, bits, signed), false),
+            Processor::OP_BNOT => SpecValue::plain(wrap(!x, bits, signed), false),
+            Processor::OP_BSHL => SpecValue::plain(wrap(x << shift, bits, signed), carry),
+            Processor::OP_BSHR => SpecValue::plain(wrap(x >> shift, bits, signed), carry),
+            _ => return Err(ErrorCategory::ControlFlow),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::InstructionFormat;
+    use crate::memory::ReadWriteSegment;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    const MEMORY_SIZE: u32 = 0x200;
+
+    /// Provides register values at the edges of each data type
+    const EDGE_VALUES: &[u32] = &[
+        0,
+        1,
+        2,
+        0x7F,
+        0x80,
+        0xFF,
+        0x7FFF,
+        0x8000,
+        0xFFFF,
+        0x7FFF_FFFF,
+        0x8000_0000,
+        u32::MAX,
+        0x3F80_0000,
+        0xC020_0000,
+        0x7F80_0000,
+        0x7FC0_0000,
+    ];
+
+    /// Provides a xorshift random number generator, such that each run checks the same cases
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 32) as u32
+        }
+
+        fn below(&mut self, n: u32) -> u32 {
+            self.next() % n
+        }
+
+        /// Provides a register value, biased towards addresses within memory, small values, and
+        /// the edges of each data type
+        fn value(&mut self) -> u32 {
+            match self.below(4) {
+                0 => self.below(MEMORY_SIZE),
+                1 => self.below(64),
+                2 => EDGE_VALUES[self.below(EDGE_VALUES.len() as u32) as usize],
+                _ => self.next(),
+            }
+        }
+
+        fn register(&mut self) -> u8 {
+            self.below(Register::NUM_REGISTERS as u32) as u8
+        }
+
+        /// Provides a typed register argument, occasionally with an invalid type code
+        fn typed(&mut self) -> u8 {
+            let dt = if self.below(16) == 0 {
+                0
+            } else {
+                self.below(7) + 1
+            };
+            ((dt as u8) << 5) | self.register()
+        }
+
+        /// Provides a source register argument, which may be register-indirect
+        fn source(&mut self) -> u8 {
+            let indirect = if self.below(4) == 0 { 0x20 } else { 0 };
+            indirect | self.register()
+        }
+    }
+
+    /// Provides an instruction word for the opcode, with arguments generated from the
+    /// instruction format. Arguments are occasionally random, as unused bits are ignored
+    fn instruction(rng: &mut Rng, op: u8) -> u32 {
+        use InstructionFormat::*;
+
+        let args = match Processor::instruction_format(Opcode::from(op)) {
+            _ if rng.below(8) == 0 => [rng.next() as u8, rng.next() as u8, rng.next() as u8],
+            Some(NoArgument) => [0, 0, 0],
+            Some(Immediate) => [0, rng.next() as u8, rng.next() as u8],
+            Some(Register) => [rng.register(), 0, 0],
+            Some(RegisterType) => [rng.typed(), 0, 0],
+            Some(RegisterTypeImmediate) => [rng.typed(), rng.next() as u8, rng.next() as u8],
+            Some(DoubleRegister) => [rng.register(), rng.register(), 0],
+            Some(DoubleRegisterType) => [rng.typed(), rng.source(), 0],
+            Some(Conversion) => [rng.typed(), rng.typed(), 0],
+            Some(Arithmetic) => [rng.typed(), rng.source(), rng.source()],
+            Some(Extension) | None => [rng.next() as u8, rng.next() as u8, rng.next() as u8],
+        };
+
+        u32::from_be_bytes([op, args[0], args[1], args[2]])
+    }
+
+    /// Provides a random state with the instruction at the program counter
+    fn random_state(rng: &mut Rng, inst: u32) -> ArchState {
+        let mut memory = (0..MEMORY_SIZE)
+            .map(|_| rng.next() as u8)
+            .collect::<Vec<_>>();
+        if rng.below(2) == 0 {
+            // Disable the interrupt vectors, such that held interrupts remain held
+            let top =
+                Processor::BASE_HW_INT_ADDR + Processor::NUM_INTERRUPT * Processor::BYTES_PER_WORD;
+            memory[..top as usize].fill(0);
+        }
+
+        let mut registers = [0; Register::NUM_REGISTERS];
+        registers.iter_mut().for_each(|r| *r = rng.value());
+        registers[Register::IDX_STATUS] = rng.below(32);
+        registers[Register::IDX_STACK_POINTER] = rng.below(MEMORY_SIZE);
+
+        let pc = rng.below(MEMORY_SIZE / Processor::BYTES_PER_WORD) * Processor::BYTES_PER_WORD;
+        registers[Register::IDX_PROGRAM_COUNTER] = pc;
+        memory[pc as usize..pc as usize + 4].copy_from_slice(&inst.to_be_bytes());
+
+        let interrupt_hold = match rng.below(8) {
+            0 => Some(Interrupt::Software(rng.below(Processor::NUM_INTERRUPT + 4))),
+            1 => Some(Interrupt::Hardware(rng.below(Processor::NUM_INTERRUPT + 4))),
+            _ => None,
+        };
+
+        ArchState {
+            registers,
+            memory,
+            interrupt_hold,
+            halted: rng.below(64) == 0,
+        }
+    }
+
+    /// Steps a processor from the provided state, providing the resulting state
+    fn processor_step(state: &ArchState) -> SpecResult<ArchState> {
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(
+            0,
+            Rc::new(RefCell::new(ReadWriteSegment::new(MEMORY_SIZE as usize))),
+        )
+        .unwrap();
+
+        for (i, b) in state.memory.iter().enumerate() {
+            cpu.memory_set(i as u32, *b).unwrap();
+        }
+        cpu.registers.set_state(state.registers);
+        cpu.interrupt_hold = state.interrupt_hold;
+        cpu.halted = state.halted;
+
+        cpu.step().map_err(|e| e.category())?;
+
+        Ok(ArchState {
+            registers: cpu.registers.get_state(),
+            memory: (0..MEMORY_SIZE)
+                .map(|a| cpu.memory_inspect(a).unwrap())
+                .collect(),
+            interrupt_hold: cpu.interrupt_hold,
+            halted: cpu.halted,
+        })
+    }
+
+    /// Ensure that the processor matches the specification from randomized states, for every
+    /// opcode defined by the instruction set and a sample of undefined opcodes
+    #[test]
+    fn test_processor_matches_spec() {
+        let mut rng = Rng(0x5EED_0000_0001);
+
+        for op in 0..=u8::MAX {
+            let cases = match Processor::instruction_format(Opcode::from(op)) {
+                Some(_) => 250,
+                None => 4,
+            };
+
+            for _ in 0..cases {
+                let inst = instruction(&mut rng, op);
+                let state = random_state(&mut rng, inst);
+
+                let expected = step(&state);
+                let actual = processor_step(&state);
+                assert_eq!(
+                    actual
+                        .as_ref()
+                        .map(|s| (s.registers, s.interrupt_hold, s.halted)),
+                    expected
+                        .as_ref()
+                        .map(|s| (s.registers, s.interrupt_hold, s.halted)),
+                    "instruction {inst:08x} with registers {:x?}, interrupt {:?}",
+                    state.registers,
+                    state.interrupt_hold,
+                );
+                assert_eq!(actual, expected, "memory for instruction {inst:08x}");
+            }
+        }
+    }
+
+    /// Ensure that the specification provides the expected results for cases corrected while
+    /// auditing the processor against the specification
+    #[test]
+    fn test_audited_cases() {
+        let word = |op: Opcode, args: [u8; 3]| {
+            u32::from_be_bytes([op.to_byte(), args[0], args[1], args[2]])
+        };
+
+        let mut state = ArchState {
+            registers: [0; Register::NUM_REGISTERS],
+            memory: alloc::vec![0; MEMORY_SIZE as usize],
+            interrupt_hold: None,
+            halted: false,
+        };
+        state.registers[6] = 0x8000_0000;
+        state.registers[7] = u32::MAX;
+        state.registers[8] = 0x100;
+        state.registers[9] = 0x40;
+        state.memory[0x40] = 0x80;
+        state.memory[4..8].copy_from_slice(&0x20u32.to_be_bytes());
+
+        let i32_type = 6 << 5;
+        let i8_type = 2 << 5;
+        let u8_type = 1 << 5;
+        let u16_type = 3 << 5;
+
+        let cases = [
+            // Signed division of the minimum value by -1 wraps
+            (
+                word(Processor::OP_DIV, [i32_type | 10, 6, 7]),
+                Ok(0x8000_0000),
+            ),
+            (word(Processor::OP_REM, [i32_type | 10, 6, 7]), Ok(0)),
+            // Divisors are checked for zero within the data type
+            (
+                word(Processor::OP_DIV, [u8_type | 10, 7, 8]),
+                Err(ErrorCategory::Arithmetic),
+            ),
+            // Signed loads are sign-extended
+            (
+                word(Processor::OP_LOAD, [i8_type | 10, 9, 0]),
+                Ok(0xFFFF_FF80),
+            ),
+            // Conversions interpret the source register as the source data type
+            (
+                word(Processor::OP_CONV, [u16_type | 10, u8_type | 8, 0]),
+                Ok(0),
+            ),
+            // Shifts by the width of the type or more clear the value, or fill with the sign
+            (word(Processor::OP_BSHL, [i32_type | 10, 7, 8]), Ok(0)),
+            (
+                word(Processor::OP_BSHR, [i32_type | 10, 6, 8]),
+                Ok(u32::MAX),
+            ),
+            (word(Processor::OP_BSHR, [u16_type | 10, 7, 9]), Ok(0)),
+        ];
+
+        for (inst, expected) in cases {
+            let spec = execute(&state, inst).map(|s| s.registers[10]);
+            assert_eq!(spec, expected, "instruction {inst:08x}");
+            state.memory[..4].copy_from_slice(&inst.to_be_bytes());
+            assert_eq!(processor_step(&state).map(|s| s.registers[10]), expected);
+        }
+
+        // A soft reset starts at the soft reset vector, and relative saves are relative to the
+        // program counter
+        state.memory[..4].copy_from_slice(&word(Processor::OP_RESET, [0; 3]).to_be_bytes());
+        assert_eq!(step(&state).unwrap().registers[0], 0x20);
+        assert_eq!(processor_step(&state).unwrap().registers[0], 0x20);
+
+        let inst = word(Processor::OP_SAVE_REL, [u8_type | 9, 7, 0]);
+        state.memory[0x10..0x14].copy_from_slice(&inst.to_be_bytes());
+        state.registers[0] = 0x10;
+        assert_eq!(step(&state).unwrap().memory[0x50], 0xFF);
+        assert_eq!(processor_step(&state).unwrap().memory[0x50], 0xFF);
+    }
+}
@@ -0,0 +1,121 @@
+use alloc::boxed::Box;
+
+use super::{Processor, ProcessorError};
+
+/// Provides the action taken by the processor once a host system call handler returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallAction {
+    /// Continues with the instruction following the system call
+    Resume,
+    /// Halts the processor at the system call, as with the halt instruction
+    Halt,
+}
+
+/// Provides a host-side handler for a system call, intercepting the `sys` instruction before
+/// the matching software interrupt is raised. This allows tests and tools to provide services,
+/// such as printing or exiting, to guest programs without building a device first
+pub trait SyscallHandler {
+    /// Handles the system call with full access to the processor. The program counter is
+    /// advanced past the instruction once the handler returns, unless the processor is halted
+    fn call(&mut self, cpu: &mut Processor) -> Result<SyscallAction, ProcessorError>;
+}
+
+impl<F> SyscallHandler for F
+where
+    F: FnMut(&mut Processor) -> Result<SyscallAction, ProcessorError>,
+{
+    fn call(&mut self, cpu: &mut Processor) -> Result<SyscallAction, ProcessorError> {
+        self(cpu)
+    }
+}
+
+impl Processor {
+    /// Registers the host handler for the provided system call number, which is provided as
+    /// the immediate of a `sys` instruction. Any existing handler for the number is replaced
+    pub fn register_syscall_handler<T: SyscallHandler + 'static>(&mut self, num: u16, handler: T) {
+        self.syscalls.insert(num, Box::new(handler));
+    }
+
+    /// Removes the host handler for the provided system call number, returning true if a
+    /// handler was registered
+    pub fn unregister_syscall_handler(&mut self, num: u16) -> bool {
+        self.syscalls.remove(&num).is_some()
+    }
+
+    /// Calls the host handler for the system call, if registered. The handler is removed while
+    /// it runs, such that it may access the processor, and is then restored unless the handler
+    /// registered a replacement
+    pub(super) fn execute_syscall(
+        &mut self,
+        num: u16,
+    ) -> Option<Result<SyscallAction, ProcessorError>> {
+        let mut handler = self.syscalls.remove(&num)?;
+        let res = handler.call(self);
+        self.syscalls.entry(num).or_insert(handler);
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{Interrupt, Register, RegisterFlag, StepResult};
+    use crate::memory::ReadWriteSegment;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    /// Ensure that system calls are dispatched to the registered host handler, and otherwise
+    /// raise the matching software interrupt
+    #[test]
+    fn test_syscall() {
+        let sys = |num: u16| {
+            let [hi, lo] = num.to_be_bytes();
+            u32::from_be_bytes([Processor::OP_SYSCALL.to_byte(), 0, hi, lo])
+        };
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x400))))
+            .unwrap();
+        for (i, inst) in [sys(3), sys(4), sys(5)].iter().enumerate() {
+            for (j, b) in inst.to_be_bytes().into_iter().enumerate() {
+                cpu.memory_set((i * 4 + j) as u32, b).unwrap();
+            }
+        }
+
+        cpu.register_syscall_handler(3, |cpu: &mut Processor| {
+            let val = cpu.get_register_state().get(Register::ArgumentBase)?;
+            cpu.set_register(Register::Return, val * 2)?;
+            Ok(SyscallAction::Resume)
+        });
+        cpu.register_syscall_handler(5, |_: &mut Processor| Ok(SyscallAction::Halt));
+        cpu.set_register(Register::ArgumentBase, 0x1234).unwrap();
+
+        assert!(matches!(cpu.step(), Ok(StepResult::Executed(_))));
+        assert_eq!(
+            cpu.get_register_state().get(Register::Return).unwrap(),
+            0x2468
+        );
+        assert_eq!(cpu.get_current_pc().unwrap(), 4);
+
+        // Without a host handler, the software interrupt is raised for the guest
+        let vector = Processor::interrupt_address(Interrupt::Software(4)).unwrap();
+        for (i, b) in 0x200u32.to_be_bytes().into_iter().enumerate() {
+            cpu.memory_set(vector + i as u32, b).unwrap();
+        }
+        cpu.set_register(Register::StackPointer, 0x300).unwrap();
+        cpu.registers
+            .set_flag(RegisterFlag::InterruptEnable, true)
+            .unwrap();
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.get_current_pc().unwrap(), 0x200);
+
+        cpu.set_register(Register::ProgramCounter, 8).unwrap();
+        assert_eq!(cpu.step().unwrap(), StepResult::Halted);
+        assert!(cpu.halted());
+        assert_eq!(cpu.get_current_pc().unwrap(), 8);
+
+        assert!(cpu.unregister_syscall_handler(3));
+        assert!(!cpu.unregister_syscall_handler(3));
+    }
+}
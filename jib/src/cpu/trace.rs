@@ -0,0 +1,190 @@
+use alloc::{boxed::Box, collections::VecDeque, rc::Rc};
+use core::cell::RefCell;
+
+use super::instruction::Instruction;
+use super::{DecodedInstruction, Processor, Register, RegisterChanges, RegisterManager};
+
+/// Provides the details of a single executed instruction, as provided to a tracer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub pc: u32,
+    pub word: u32,
+    pub instruction: Instruction,
+    pub cycles: u32,
+    pub changes: RegisterChanges,
+    pub before: [u32; RegisterManager::REGISTER_COUNT],
+    pub after: [u32; RegisterManager::REGISTER_COUNT],
+}
+
+impl TraceEvent {
+    /// Provides each register modified by the instruction, including any interrupt call made at
+    /// the end of the step, along with the values before and after the instruction
+    pub fn deltas(&self) -> impl Iterator<Item = (Register, u32, u32)> + '_ {
+        (0..RegisterManager::REGISTER_COUNT)
+            .filter(|i| self.before[*i] != self.after[*i])
+            .filter_map(|i| Some((Register::try_from(i).ok()?, self.before[i], self.after[i])))
+    }
+
+    /// Provides the executed instruction decoded with the decoder used by the processor
+    pub fn decoded(&self) -> Option<DecodedInstruction> {
+        DecodedInstruction::decode(self.instruction).ok()
+    }
+}
+
+/// Receives an event for each instruction executed by the processor. Instructions that raise an
+/// error are not traced, as the error is reported through the trap-info device instead
+pub trait Tracer {
+    fn trace(&mut self, event: &TraceEvent);
+}
+
+impl<F> Tracer for F
+where
+    F: FnMut(&TraceEvent),
+{
+    fn trace(&mut self, event: &TraceEvent) {
+        self(event)
+    }
+}
+
+/// Allows a tracer to be shared with the host, such that the trace can be read while the
+/// processor holds the tracer
+impl<T: Tracer> Tracer for Rc<RefCell<T>> {
+    fn trace(&mut self, event: &TraceEvent) {
+        self.borrow_mut().trace(event)
+    }
+}
+
+/// Provides a tracer keeping the most recent events, discarding the oldest events once the
+/// capacity is reached
+pub struct RingBufferTracer {
+    events: VecDeque<TraceEvent>,
+    capacity: usize,
+}
+
+impl RingBufferTracer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Provides the recorded events, from oldest to newest
+    pub fn events(&self) -> impl DoubleEndedIterator<Item = &TraceEvent> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+impl Tracer for RingBufferTracer {
+    fn trace(&mut self, event: &TraceEvent) {
+        if self.capacity == 0 {
+            return;
+        } else if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(*event);
+    }
+}
+
+impl Processor {
+    /// Sets the tracer receiving an event for each executed instruction, replacing any existing
+    /// tracer
+    pub fn set_tracer<T: Tracer + 'static>(&mut self, tracer: T) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// Removes the tracer, returning true if a tracer was set
+    pub fn clear_tracer(&mut self) -> bool {
+        self.tracer.take().is_some()
+    }
+
+    /// Provides the executed instruction to the tracer, if set
+    pub(super) fn trace_step(&mut self, pc: u32, initial: &RegisterManager, cycles: u32) {
+        let Some(tracer) = self.tracer.as_mut() else {
+            return;
+        };
+
+        let word = self.memory.inspect_u32(pc).unwrap_or(0);
+        tracer.trace(&TraceEvent {
+            pc,
+            word,
+            instruction: Instruction::from(word),
+            cycles,
+            changes: RegisterChanges::between(initial, &self.registers),
+            before: initial.get_state(),
+            after: self.registers.get_state(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::StepResult;
+    use crate::memory::ReadWriteSegment;
+    use alloc::vec::Vec;
+
+    /// Ensure that each executed instruction is traced with the registers it modified, and
+    /// that the ring buffer keeps only the most recent events
+    #[test]
+    fn test_trace() {
+        let ldi = |reg: u8, val: u16| {
+            let [hi, lo] = val.to_be_bytes();
+            u32::from_be_bytes([Processor::OP_LOAD_IMM.to_byte(), (3 << 5) | reg, hi, lo])
+        };
+        let program = [ldi(6, 1), ldi(7, 2), ldi(6, 3), ldi(8, 4)];
+
+        let mut cpu = Processor::new();
+        cpu.memory_add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x100))))
+            .unwrap();
+        for (i, inst) in program.iter().enumerate() {
+            for (j, b) in inst.to_be_bytes().into_iter().enumerate() {
+                cpu.memory_set((i * 4 + j) as u32, b).unwrap();
+            }
+        }
+
+        let ring = Rc::new(RefCell::new(RingBufferTracer::new(2)));
+        cpu.set_tracer(ring.clone());
+
+        for _ in 0..3 {
+            assert!(matches!(cpu.step(), Ok(StepResult::Executed(_))));
+        }
+
+        let ring = ring.borrow();
+        assert_eq!(ring.len(), 2);
+        let events = ring.events().collect::<Vec<_>>();
+        assert_eq!(events[0].pc, 4);
+        assert_eq!(events[0].word, program[1]);
+        assert_eq!(events[1].instruction, Instruction::from(program[2]));
+        assert_eq!(events[1].changes.len(), 2);
+
+        let deltas = events[1].deltas().collect::<Vec<_>>();
+        assert_eq!(
+            deltas,
+            [
+                (Register::ProgramCounter, 8, 12),
+                (Register::GeneralPurpose(6), 1, 3)
+            ]
+        );
+
+        assert!(cpu.clear_tracer());
+        cpu.step().unwrap();
+        assert_eq!(ring.len(), 2);
+    }
+}
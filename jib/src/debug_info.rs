@@ -0,0 +1,333 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Write};
+
+use crate::memory::RelocationTable;
+
+/// Provides error conditions for parsing debug information, along with the line number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugInfoError {
+    InvalidHeader,
+    UnknownEntry(usize, String),
+    InvalidEntry(usize),
+    UnknownFile(usize, usize),
+}
+
+impl fmt::Display for DebugInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "Invalid Debug Info Header"),
+            Self::UnknownEntry(line, name) => write!(f, "Line {line} - Unknown Entry '{name}'"),
+            Self::InvalidEntry(line) => write!(f, "Line {line} - Invalid Entry"),
+            Self::UnknownFile(line, file) => write!(f, "Line {line} - Unknown File {file}"),
+        }
+    }
+}
+
+impl core::error::Error for DebugInfoError {}
+
+/// Provides the source line that produced the instruction at an address, where the file is an
+/// index into the source files of the debug information
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourceLocation {
+    pub file: usize,
+    pub line: usize,
+}
+
+/// Provides the label and source line addresses of a program, written by the assembler as a
+/// sidecar file alongside the program image, such that tools loading only the image may show
+/// labels and source lines instead of raw addresses. The text format starts with a `jdbg 1`
+/// header line, followed by one entry per line:
+///
+/// ```text
+/// file 0 src/main.jsm
+/// label 0x00000400 start
+/// line 0x00000400 0 3
+/// ```
+///
+/// File entries provide the index and path of each source file, label entries provide the
+/// address and name of each label, and line entries provide the address of an instruction with
+/// the file index and line number of its source. Empty lines are ignored
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    pub files: Vec<String>,
+    pub labels: BTreeMap<String, u32>,
+    pub lines: BTreeMap<u32, SourceLocation>,
+}
+
+impl DebugInfo {
+    /// Defines the file extension of debug information written alongside a program image
+    pub const EXTENSION: &'static str = "jdbg";
+
+    const HEADER: &'static str = "jdbg 1";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.lines.is_empty()
+    }
+
+    /// Adds the files, labels, and source lines of the other debug information, replacing any
+    /// labels or lines at the same name or address
+    pub fn merge(&mut self, other: DebugInfo) {
+        let offset = self.files.len();
+        self.files.extend(other.files);
+        self.labels.extend(other.labels);
+        self.lines
+            .extend(other.lines.into_iter().map(|(addr, loc)| {
+                let loc = SourceLocation {
+                    file: loc.file + offset,
+                    line: loc.line,
+                };
+                (addr, loc)
+            }));
+    }
+
+    /// Provides the debug information with the addresses of the program moved to start at the
+    /// new base address, as with [`RelocationTable::relocate_image`]
+    pub fn relocated(&self, table: &RelocationTable, new_base: u32) -> Self {
+        let mv = |addr: u32| table.relocate_address(addr, new_base);
+        Self {
+            files: self.files.clone(),
+            labels: self
+                .labels
+                .iter()
+                .map(|(name, addr)| (name.clone(), mv(*addr)))
+                .collect(),
+            lines: self
+                .lines
+                .iter()
+                .map(|(addr, loc)| (mv(*addr), *loc))
+                .collect(),
+        }
+    }
+
+    /// Provides the debug information in the text format
+    pub fn to_text(&self) -> String {
+        let mut s = String::new();
+        let _ = writeln!(s, "{}", Self::HEADER);
+        for (i, path) in self.files.iter().enumerate() {
+            let _ = writeln!(s, "file {i} {path}");
+        }
+
+        let mut labels = self.labels.iter().collect::<Vec<_>>();
+        labels.sort_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(b.0)));
+        for (name, addr) in labels {
+            let _ = writeln!(s, "label 0x{addr:08x} {name}");
+        }
+
+        for (addr, loc) in self.lines.iter() {
+            let _ = writeln!(s, "line 0x{addr:08x} {} {}", loc.file, loc.line);
+        }
+        s
+    }
+
+    /// Parses debug information from the text format. File entries must be provided before any
+    /// line entries referring to them
+    pub fn from_text(s: &str) -> Result<Self, DebugInfoError> {
+        let mut lines = s.lines().enumerate();
+        if lines.next().map(|(_, l)| l.trim()) != Some(Self::HEADER) {
+            return Err(DebugInfoError::InvalidHeader);
+        }
+
+        let mut info = Self::new();
+        for (i, line) in lines {
+            let line_num = i + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut words = line.splitn(3, ' ');
+            let kind = words.next().unwrap_or_default();
+            let first = words.next().ok_or(DebugInfoError::InvalidEntry(line_num))?;
+            let rest = words.next().ok_or(DebugInfoError::InvalidEntry(line_num))?;
+
+            match kind {
+                "file" => {
+                    if first.parse() != Ok(info.files.len()) {
+                        return Err(DebugInfoError::InvalidEntry(line_num));
+                    }
+                    info.files.push(rest.to_string());
+                }
+                "label" => {
+                    let addr =
+                        parse_address(first).ok_or(DebugInfoError::InvalidEntry(line_num))?;
+                    info.labels.insert(rest.to_string(), addr);
+                }
+                "line" => {
+                    let addr =
+                        parse_address(first).ok_or(DebugInfoError::InvalidEntry(line_num))?;
+                    let (file, src_line) = rest
+                        .split_once(' ')
+                        .and_then(|(f, l)| Some((f.parse().ok()?, l.parse().ok()?)))
+                        .ok_or(DebugInfoError::InvalidEntry(line_num))?;
+
+                    if file >= info.files.len() {
+                        return Err(DebugInfoError::UnknownFile(line_num, file));
+                    }
+                    info.lines.insert(
+                        addr,
+                        SourceLocation {
+                            file,
+                            line: src_line,
+                        },
+                    );
+                }
+                name => return Err(DebugInfoError::UnknownEntry(line_num, name.to_string())),
+            }
+        }
+
+        Ok(info)
+    }
+
+    /// Provides the path of the debug information written alongside the program image
+    #[cfg(feature = "std")]
+    pub fn sidecar_path(image: &std::path::Path) -> std::path::PathBuf {
+        image.with_extension(Self::EXTENSION)
+    }
+
+    /// Reads the debug information written alongside the program image, providing None if no
+    /// debug information was written
+    #[cfg(feature = "std")]
+    pub fn load_sidecar(image: &std::path::Path) -> Result<Option<Self>, String> {
+        let path = Self::sidecar_path(image);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let txt = std::fs::read_to_string(&path)
+            .map_err(|e| alloc::format!("Unable to read {} - {e}", path.display()))?;
+        Self::from_text(&txt)
+            .map(Some)
+            .map_err(|e| alloc::format!("{} - {e}", path.display()))
+    }
+
+    /// Provides the first label, in name order, located exactly at the address
+    pub fn label_at(&self, addr: u32) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(_, a)| **a == addr)
+            .map(|(n, _)| n.as_str())
+    }
+
+    /// Provides the closest label at or before the address, along with its address
+    pub fn nearest_label(&self, addr: u32) -> Option<(u32, &str)> {
+        self.labels
+            .iter()
+            .filter(|(_, a)| **a <= addr)
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map(|(n, a)| (*a, n.as_str()))
+    }
+
+    /// Provides the address of the label with the provided name
+    pub fn address_of(&self, label: &str) -> Option<u32> {
+        self.labels.get(label).copied()
+    }
+
+    /// Provides the lowest address of an instruction produced by the source line, where the
+    /// file is given either as the full path or as the trailing components of the path
+    pub fn line_address(&self, file: &str, line: usize) -> Option<u32> {
+        let matches = |path: &str| {
+            path == file
+                || path
+                    .strip_suffix(file)
+                    .is_some_and(|p| p.ends_with(['/', '\\']))
+        };
+
+        self.lines
+            .iter()
+            .find(|(_, loc)| {
+                loc.line == line && self.files.get(loc.file).is_some_and(|p| matches(p))
+            })
+            .map(|(addr, _)| *addr)
+    }
+
+    /// Provides the source file path and line number of the instruction at the address
+    pub fn source_line(&self, addr: u32) -> Option<(&str, usize)> {
+        let loc = self.lines.get(&addr)?;
+        Some((self.files.get(loc.file)?.as_str(), loc.line))
+    }
+}
+
+fn parse_address(s: &str) -> Option<u32> {
+    u32::from_str_radix(s.strip_prefix("0x")?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensure that debug information is preserved through the text format, and that labels and
+    /// source lines can be found by address
+    #[test]
+    fn test_debug_info_text() {
+        let mut info = DebugInfo::new();
+        info.files.push("src/main.jsm".into());
+        info.labels.insert("start".into(), 0x400);
+        info.labels.insert("loop".into(), 0x408);
+        info.lines
+            .insert(0x400, SourceLocation { file: 0, line: 3 });
+        info.lines
+            .insert(0x408, SourceLocation { file: 0, line: 5 });
+
+        let text = info.to_text();
+        assert!(text.starts_with("jdbg 1\nfile 0 src/main.jsm\nlabel 0x00000400 start\n"));
+        assert_eq!(DebugInfo::from_text(&text), Ok(info.clone()));
+
+        assert_eq!(info.label_at(0x408), Some("loop"));
+        assert_eq!(info.label_at(0x404), None);
+        assert_eq!(info.nearest_label(0x404), Some((0x400, "start")));
+        assert_eq!(info.nearest_label(0x3fc), None);
+        assert_eq!(info.address_of("loop"), Some(0x408));
+        assert_eq!(info.source_line(0x408), Some(("src/main.jsm", 5)));
+        assert_eq!(info.source_line(0x404), None);
+        assert_eq!(info.line_address("main.jsm", 5), Some(0x408));
+        assert_eq!(info.line_address("src/main.jsm", 3), Some(0x400));
+        assert_eq!(info.line_address("ain.jsm", 3), None);
+        assert_eq!(info.line_address("main.jsm", 4), None);
+
+        let mut other = DebugInfo::new();
+        other.files.push("src/lib.jsm".into());
+        other.labels.insert("helper".into(), 0x500);
+        other
+            .lines
+            .insert(0x500, SourceLocation { file: 0, line: 1 });
+        info.merge(other);
+        assert_eq!(info.address_of("helper"), Some(0x500));
+        assert_eq!(info.source_line(0x500), Some(("src/lib.jsm", 1)));
+        assert_eq!(info.source_line(0x400), Some(("src/main.jsm", 3)));
+
+        let table = RelocationTable::new(0x400..0x500, Vec::new());
+        let moved = info.relocated(&table, 0x1000);
+        assert_eq!(moved.address_of("loop"), Some(0x1008));
+        assert_eq!(moved.source_line(0x1008), Some(("src/main.jsm", 5)));
+        assert_eq!(moved.address_of("helper"), Some(0x500));
+    }
+
+    /// Ensure that malformed debug information is rejected with the line of the error
+    #[test]
+    fn test_debug_info_errors() {
+        assert_eq!(
+            DebugInfo::from_text("label 0x0 start"),
+            Err(DebugInfoError::InvalidHeader)
+        );
+        assert_eq!(
+            DebugInfo::from_text("jdbg 1\nline 0x400 0 3"),
+            Err(DebugInfoError::UnknownFile(2, 0))
+        );
+        assert_eq!(
+            DebugInfo::from_text("jdbg 1\nlabel 400 start"),
+            Err(DebugInfoError::InvalidEntry(2))
+        );
+        assert_eq!(
+            DebugInfo::from_text("jdbg 1\n\nsymbol 0x400 start"),
+            Err(DebugInfoError::UnknownEntry(3, "symbol".into()))
+        );
+    }
+}
@@ -0,0 +1,536 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::fmt;
+
+use super::{DEVICE_ID_SIZE, DEVICE_MEM_SIZE, DeviceAction, ProcessorDevice};
+
+use crate::memory::{block_range, range_offset, MemorySegment, MemorySegmentError};
+
+/// Defines the number of bytes in each sector of block storage
+pub const SECTOR_SIZE: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockStorageError {
+    InvalidSector(u32),
+    Io,
+}
+
+impl fmt::Display for BlockStorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSector(s) => write!(f, "Invalid Sector {s}"),
+            Self::Io => write!(f, "Storage I/O Error"),
+        }
+    }
+}
+
+impl core::error::Error for BlockStorageError {}
+
+/// Provides sector-based storage for a block storage device, such as a disk image provided by
+/// the emulator host
+pub trait BlockStorage {
+    /// Provides the number of sectors available
+    fn sector_count(&self) -> u32;
+
+    /// Reads the contents of the sector into the provided buffer
+    fn read_sector(
+        &mut self,
+        sector: u32,
+        data: &mut [u8; SECTOR_SIZE],
+    ) -> Result<(), BlockStorageError>;
+
+    /// Writes the provided buffer into the sector
+    fn write_sector(
+        &mut self,
+        sector: u32,
+        data: &[u8; SECTOR_SIZE],
+    ) -> Result<(), BlockStorageError>;
+}
+
+/// Provides block storage held in memory, which is lost when dropped
+pub struct MemoryBlockStorage {
+    data: Vec<u8>,
+}
+
+impl MemoryBlockStorage {
+    /// Constructs a new zero-filled storage with the provided number of sectors
+    pub fn new(sectors: u32) -> Self {
+        Self {
+            data: vec![0; sectors as usize * SECTOR_SIZE],
+        }
+    }
+
+    /// Provides the contents of the storage
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn range(&self, sector: u32) -> Result<core::ops::Range<usize>, BlockStorageError> {
+        if sector < self.sector_count() {
+            let start = sector as usize * SECTOR_SIZE;
+            Ok(start..start + SECTOR_SIZE)
+        } else {
+            Err(BlockStorageError::InvalidSector(sector))
+        }
+    }
+}
+
+impl BlockStorage for MemoryBlockStorage {
+    fn sector_count(&self) -> u32 {
+        (self.data.len() / SECTOR_SIZE) as u32
+    }
+
+    fn read_sector(
+        &mut self,
+        sector: u32,
+        data: &mut [u8; SECTOR_SIZE],
+    ) -> Result<(), BlockStorageError> {
+        data.copy_from_slice(&self.data[self.range(sector)?]);
+        Ok(())
+    }
+
+    fn write_sector(
+        &mut self,
+        sector: u32,
+        data: &[u8; SECTOR_SIZE],
+    ) -> Result<(), BlockStorageError> {
+        let range = self.range(sector)?;
+        self.data[range].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Provides block storage backed by a host disk image file, where a partial final sector is
+/// read as zero-filled
+#[cfg(feature = "std")]
+pub struct FileBlockStorage {
+    file: std::fs::File,
+    sectors: u32,
+}
+
+#[cfg(feature = "std")]
+impl FileBlockStorage {
+    /// Opens an existing disk image for reading and writing
+    pub fn open(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let file = std::fs::File::options().read(true).write(true).open(path)?;
+        let sectors = file.metadata()?.len().div_ceil(SECTOR_SIZE as u64) as u32;
+        Ok(Self { file, sectors })
+    }
+
+    /// Creates a new zero-filled disk image with the provided number of sectors, replacing any
+    /// existing file
+    pub fn create(path: impl AsRef<std::path::Path>, sectors: u32) -> std::io::Result<Self> {
+        let file = std::fs::File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(sectors as u64 * SECTOR_SIZE as u64)?;
+        Ok(Self { file, sectors })
+    }
+
+    fn seek(&mut self, sector: u32) -> Result<(), BlockStorageError> {
+        use std::io::{Seek, SeekFrom};
+
+        if sector >= self.sectors {
+            return Err(BlockStorageError::InvalidSector(sector));
+        }
+
+        self.file
+            .seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE as u64))
+            .map(|_| ())
+            .map_err(|_| BlockStorageError::Io)
+    }
+}
+
+#[cfg(feature = "std")]
+impl BlockStorage for FileBlockStorage {
+    fn sector_count(&self) -> u32 {
+        self.sectors
+    }
+
+    fn read_sector(
+        &mut self,
+        sector: u32,
+        data: &mut [u8; SECTOR_SIZE],
+    ) -> Result<(), BlockStorageError> {
+        use std::io::Read;
+
+        self.seek(sector)?;
+        data.fill(0);
+
+        let mut read = 0;
+        while read < SECTOR_SIZE {
+            match self.file.read(&mut data[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(_) => return Err(BlockStorageError::Io),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_sector(
+        &mut self,
+        sector: u32,
+        data: &[u8; SECTOR_SIZE],
+    ) -> Result<(), BlockStorageError> {
+        use std::io::Write;
+
+        self.seek(sector)?;
+        self.file
+            .write_all(data)
+            .and_then(|_| self.file.flush())
+            .map_err(|_| BlockStorageError::Io)
+    }
+}
+
+/// Provides a memory-mapped block storage device, transferring whole sectors between the
+/// attached storage and a sector buffer window with a single command. The guest selects the
+/// sector, writes the command, and then checks the status register, with an optional interrupt
+/// raised once a command completes. The attached storage is kept across resets, such that
+/// programs may persist data across runs
+pub struct BlockStorageDevice {
+    storage: Option<Box<dyn BlockStorage>>,
+    buffer: [u8; SECTOR_SIZE],
+    sector: u32,
+    status: u8,
+    irq_enabled: bool,
+    irq: u8,
+    pending: bool,
+}
+
+impl BlockStorageDevice {
+    const OFFSET_COMMAND: u32 = 2;
+    const OFFSET_STATUS: u32 = 3;
+    const OFFSET_SECTOR: u32 = 4;
+    const OFFSET_SECTOR_COUNT: u32 = 8;
+    const OFFSET_IRQ_ENABLE: u32 = 12;
+    const OFFSET_IRQ: u32 = 13;
+    const OFFSET_BUFFER: u32 = DEVICE_MEM_SIZE;
+
+    pub const DEVICE_ID: u16 = 9;
+
+    pub const COMMAND_READ: u8 = 1;
+    pub const COMMAND_WRITE: u8 = 2;
+
+    pub const STATUS_OK: u8 = 0;
+    pub const STATUS_NO_STORAGE: u8 = 1;
+    pub const STATUS_INVALID_SECTOR: u8 = 2;
+    pub const STATUS_IO_ERROR: u8 = 3;
+    pub const STATUS_INVALID_COMMAND: u8 = 4;
+
+    /// Constructs a new block storage device without any attached storage
+    pub fn new() -> Self {
+        Self {
+            storage: None,
+            buffer: [0; SECTOR_SIZE],
+            sector: 0,
+            status: Self::STATUS_OK,
+            irq_enabled: false,
+            irq: 0,
+            pending: false,
+        }
+    }
+
+    /// Attaches the storage to the device, providing any previously attached storage
+    pub fn attach(&mut self, storage: Box<dyn BlockStorage>) -> Option<Box<dyn BlockStorage>> {
+        self.storage.replace(storage)
+    }
+
+    /// Detaches and provides the attached storage, if any
+    pub fn detach(&mut self) -> Option<Box<dyn BlockStorage>> {
+        self.storage.take()
+    }
+
+    /// Determines if storage is attached to the device
+    pub fn is_attached(&self) -> bool {
+        self.storage.is_some()
+    }
+
+    fn execute(&mut self, command: u8) -> u8 {
+        let Some(storage) = self.storage.as_mut() else {
+            return Self::STATUS_NO_STORAGE;
+        };
+
+        let res = match command {
+            Self::COMMAND_READ => storage.read_sector(self.sector, &mut self.buffer),
+            Self::COMMAND_WRITE => storage.write_sector(self.sector, &self.buffer),
+            _ => return Self::STATUS_INVALID_COMMAND,
+        };
+
+        match res {
+            Ok(()) => Self::STATUS_OK,
+            Err(BlockStorageError::InvalidSector(_)) => Self::STATUS_INVALID_SECTOR,
+            Err(BlockStorageError::Io) => Self::STATUS_IO_ERROR,
+        }
+    }
+
+    fn sector_count(&self) -> u32 {
+        self.storage.as_ref().map_or(0, |s| s.sector_count())
+    }
+}
+
+impl Default for BlockStorageDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemorySegment for BlockStorageDevice {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            n if n < DEVICE_ID_SIZE => Ok(Self::DEVICE_ID.to_be_bytes()[n as usize]),
+            Self::OFFSET_COMMAND => Ok(0),
+            Self::OFFSET_STATUS => Ok(self.status),
+            n if (Self::OFFSET_SECTOR..Self::OFFSET_SECTOR_COUNT).contains(&n) => {
+                Ok(self.sector.to_be_bytes()[(n - Self::OFFSET_SECTOR) as usize])
+            }
+            n if (Self::OFFSET_SECTOR_COUNT..Self::OFFSET_IRQ_ENABLE).contains(&n) => {
+                Ok(self.sector_count().to_be_bytes()[(n - Self::OFFSET_SECTOR_COUNT) as usize])
+            }
+            Self::OFFSET_IRQ_ENABLE => Ok(self.irq_enabled as u8),
+            Self::OFFSET_IRQ => Ok(self.irq),
+            n if n >= Self::OFFSET_BUFFER && n < self.len() => {
+                Ok(self.buffer[(n - Self::OFFSET_BUFFER) as usize])
+            }
+            _ => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        match offset {
+            Self::OFFSET_COMMAND => {
+                self.status = self.execute(data);
+                self.pending = true;
+            }
+            n if (Self::OFFSET_SECTOR..Self::OFFSET_SECTOR_COUNT).contains(&n) => {
+                let mut bytes = self.sector.to_be_bytes();
+                bytes[(n - Self::OFFSET_SECTOR) as usize] = data;
+                self.sector = u32::from_be_bytes(bytes);
+            }
+            Self::OFFSET_IRQ_ENABLE => self.irq_enabled = data != 0,
+            Self::OFFSET_IRQ => self.irq = data,
+            n if n >= Self::OFFSET_BUFFER && n < self.len() => {
+                self.buffer[(n - Self::OFFSET_BUFFER) as usize] = data
+            }
+            _ => return Err(MemorySegmentError::InvalidMemoryWrite(offset, data)),
+        }
+
+        Ok(())
+    }
+
+    /// Provides the values starting at the requested memory location, copying directly from the
+    /// sector buffer when the range is within the buffer window
+    fn get_range(&self, offset: u32, buf: &mut [u8]) -> Result<(), MemorySegmentError> {
+        match block_range(offset, buf.len(), Self::OFFSET_BUFFER..self.len()) {
+            Some(range) => buf.copy_from_slice(&self.buffer[range]),
+            None => {
+                for (i, v) in buf.iter_mut().enumerate() {
+                    *v = self.get(range_offset(offset, i)?)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the values starting at the requested memory location, copying directly into the
+    /// sector buffer when the range is within the buffer window
+    fn set_range(&mut self, offset: u32, data: &[u8]) -> Result<(), MemorySegmentError> {
+        match block_range(offset, data.len(), Self::OFFSET_BUFFER..self.len()) {
+            Some(range) => self.buffer[range].copy_from_slice(data),
+            None => {
+                for (i, v) in data.iter().enumerate() {
+                    self.set(range_offset(offset, i)?, *v)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resets the memory segment, keeping any attached storage
+    fn reset(&mut self) {
+        self.buffer.fill(0);
+        self.sector = 0;
+        self.status = Self::STATUS_OK;
+        self.irq_enabled = false;
+        self.irq = 0;
+        self.pending = false;
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        Self::OFFSET_BUFFER + SECTOR_SIZE as u32
+    }
+
+    /// Provides the device registers followed by the sector buffer. The contents of the
+    /// attached storage are not included
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::from(self.sector.to_be_bytes());
+        state.extend([
+            self.status,
+            self.irq_enabled as u8,
+            self.irq,
+            self.pending as u8,
+        ]);
+        state.extend(self.buffer);
+        state
+    }
+
+    /// Restores the device registers and sector buffer
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let ([s0, s1, s2, s3, status, enabled, irq, pending], buffer) = state
+            .split_first_chunk::<8>()
+            .ok_or(MemorySegmentError::InvalidState)?;
+
+        self.buffer = buffer
+            .try_into()
+            .map_err(|_| MemorySegmentError::InvalidState)?;
+        self.sector = u32::from_be_bytes([*s0, *s1, *s2, *s3]);
+        self.status = *status;
+        self.irq_enabled = *enabled != 0;
+        self.irq = *irq;
+        self.pending = *pending != 0;
+
+        Ok(())
+    }
+}
+
+impl ProcessorDevice for BlockStorageDevice {
+    fn on_step(&mut self, _cycles: u32) -> Option<DeviceAction> {
+        let pending = core::mem::take(&mut self.pending);
+        if pending && self.irq_enabled {
+            Some(DeviceAction::CallInterrupt(self.irq as u32))
+        } else {
+            None
+        }
+    }
+
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "std")]
+    use alloc::format;
+
+    /// Ensure that sectors are transferred between the buffer window and the attached storage,
+    /// and that the storage persists when detached and attached to a new device
+    #[test]
+    fn test_sector_transfer() {
+        let mut dev = BlockStorageDevice::new();
+        let buffer = BlockStorageDevice::OFFSET_BUFFER;
+
+        dev.set(
+            BlockStorageDevice::OFFSET_COMMAND,
+            BlockStorageDevice::COMMAND_READ,
+        )
+        .unwrap();
+        assert_eq!(
+            dev.get(BlockStorageDevice::OFFSET_STATUS).unwrap(),
+            BlockStorageDevice::STATUS_NO_STORAGE
+        );
+
+        assert!(dev.attach(Box::new(MemoryBlockStorage::new(4))).is_none());
+        assert_eq!(
+            dev.get(BlockStorageDevice::OFFSET_SECTOR_COUNT + 3)
+                .unwrap(),
+            4
+        );
+
+        dev.set(BlockStorageDevice::OFFSET_SECTOR + 3, 2).unwrap();
+        dev.set(buffer, 0xAB).unwrap();
+        dev.set(buffer + SECTOR_SIZE as u32 - 1, 0xCD).unwrap();
+        dev.set(BlockStorageDevice::OFFSET_IRQ, 5).unwrap();
+        dev.set(BlockStorageDevice::OFFSET_IRQ_ENABLE, 1).unwrap();
+        dev.set(
+            BlockStorageDevice::OFFSET_COMMAND,
+            BlockStorageDevice::COMMAND_WRITE,
+        )
+        .unwrap();
+        assert_eq!(
+            dev.get(BlockStorageDevice::OFFSET_STATUS).unwrap(),
+            BlockStorageDevice::STATUS_OK
+        );
+        assert!(matches!(
+            dev.on_step(1),
+            Some(DeviceAction::CallInterrupt(5))
+        ));
+        assert!(dev.on_step(1).is_none());
+
+        dev.set(BlockStorageDevice::OFFSET_SECTOR + 3, 4).unwrap();
+        dev.set(
+            BlockStorageDevice::OFFSET_COMMAND,
+            BlockStorageDevice::COMMAND_READ,
+        )
+        .unwrap();
+        assert_eq!(
+            dev.get(BlockStorageDevice::OFFSET_STATUS).unwrap(),
+            BlockStorageDevice::STATUS_INVALID_SECTOR
+        );
+        dev.set(BlockStorageDevice::OFFSET_COMMAND, 7).unwrap();
+        assert_eq!(
+            dev.get(BlockStorageDevice::OFFSET_STATUS).unwrap(),
+            BlockStorageDevice::STATUS_INVALID_COMMAND
+        );
+
+        let storage = dev.detach().unwrap();
+        assert!(!dev.is_attached());
+
+        let mut other = BlockStorageDevice::new();
+        other.attach(storage);
+        other.set(BlockStorageDevice::OFFSET_SECTOR + 3, 2).unwrap();
+        other
+            .set(
+                BlockStorageDevice::OFFSET_COMMAND,
+                BlockStorageDevice::COMMAND_READ,
+            )
+            .unwrap();
+        assert_eq!(other.get(buffer).unwrap(), 0xAB);
+        assert_eq!(other.get(buffer + SECTOR_SIZE as u32 - 1).unwrap(), 0xCD);
+        assert!(other.get(other.len()).is_err());
+
+        let state = other.save_state();
+        dev.load_state(&state).unwrap();
+        assert_eq!(dev.get(buffer).unwrap(), 0xAB);
+        assert!(dev.load_state(&state[..8]).is_err());
+    }
+
+    /// Ensure that a file-backed disk image keeps written sectors when reopened
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_file_storage() {
+        let path = std::env::temp_dir().join(format!("jib-disk-{}.img", std::process::id()));
+
+        let mut data = [0; SECTOR_SIZE];
+        data[0] = 0x12;
+        data[SECTOR_SIZE - 1] = 0x34;
+
+        let mut disk = FileBlockStorage::create(&path, 3).unwrap();
+        assert_eq!(disk.sector_count(), 3);
+        disk.write_sector(1, &data).unwrap();
+        assert_eq!(
+            disk.write_sector(3, &data),
+            Err(BlockStorageError::InvalidSector(3))
+        );
+        drop(disk);
+
+        let mut disk = FileBlockStorage::open(&path).unwrap();
+        let mut read = [0xFF; SECTOR_SIZE];
+        disk.read_sector(1, &mut read).unwrap();
+        assert_eq!(read, data);
+        disk.read_sector(2, &mut read).unwrap();
+        assert_eq!(read, [0; SECTOR_SIZE]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,204 @@
+use alloc::{format, rc::Rc, string::String, vec::Vec};
+use core::cell::RefCell;
+
+use super::ProcessorDevice;
+
+use crate::memory::{MemoryError, MemoryMap, MemorySegment};
+
+/// Describes a device attached to the device bus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachedDevice {
+    pub name: String,
+    pub device_id: u16,
+    /// The base address of the memory claimed by the device, if mapped into memory
+    pub base: Option<u32>,
+    /// The number of bytes of memory claimed by the device
+    pub size: u32,
+}
+
+impl AttachedDevice {
+    /// Determines whether the address is within the memory claimed by the device
+    pub fn within(&self, addr: u32) -> bool {
+        self.base
+            .is_some_and(|b| addr >= b && (addr as u64) < b as u64 + self.size as u64)
+    }
+}
+
+struct BusEntry {
+    name: String,
+    claim: Option<(u32, Rc<RefCell<dyn MemorySegment>>)>,
+    device: Rc<RefCell<dyn ProcessorDevice>>,
+}
+
+/// Provides the devices attached to the processor, stepped in the order attached. Mapped devices
+/// claim their addresses within the memory map when attached and release them when detached,
+/// such that devices may be changed while the processor is running
+#[derive(Default)]
+pub struct DeviceBus {
+    entries: Vec<BusEntry>,
+}
+
+impl DeviceBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attaches the device, claiming the memory from the base address for the length of the
+    /// device. The claim fails if it overlaps any existing memory segment or device
+    pub fn attach<T: MemorySegment + ProcessorDevice + 'static>(
+        &mut self,
+        memory: &mut MemoryMap,
+        name: &str,
+        base: u32,
+        dev: Rc<RefCell<T>>,
+    ) -> Result<(), MemoryError> {
+        memory.add_segment(base, dev.clone())?;
+        self.entries.push(BusEntry {
+            name: name.into(),
+            claim: Some((base, dev.clone())),
+            device: dev,
+        });
+        Ok(())
+    }
+
+    /// Attaches a device that is stepped with the processor without claiming any memory, named
+    /// by its device ID
+    pub fn attach_unmapped(&mut self, dev: Rc<RefCell<dyn ProcessorDevice>>) {
+        let name = format!("device {}", dev.borrow().device_id());
+        self.entries.push(BusEntry {
+            name,
+            claim: None,
+            device: dev,
+        });
+    }
+
+    /// Detaches the first device with the provided name, releasing any memory it claimed, and
+    /// provides the description of the removed device
+    pub fn detach(&mut self, memory: &mut MemoryMap, name: &str) -> Option<AttachedDevice> {
+        let ind = self.entries.iter().position(|e| e.name == name)?;
+        let desc = Self::describe(&self.entries[ind]);
+
+        if let Some((base, _)) = self.entries[ind].claim {
+            memory.remove_segment(base).ok();
+        }
+
+        self.entries.remove(ind);
+        Some(desc)
+    }
+
+    /// Provides the description of each attached device, in the order attached
+    pub fn devices(&self) -> impl Iterator<Item = AttachedDevice> + '_ {
+        self.entries.iter().map(Self::describe)
+    }
+
+    /// Provides the description of the device claiming the address, if any
+    pub fn device_at(&self, addr: u32) -> Option<AttachedDevice> {
+        self.devices().find(|d| d.within(addr))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Provides the devices to step after each instruction
+    pub(crate) fn step_devices(&self) -> Vec<Rc<RefCell<dyn ProcessorDevice>>> {
+        self.entries.iter().map(|e| e.device.clone()).collect()
+    }
+
+    fn describe(entry: &BusEntry) -> AttachedDevice {
+        AttachedDevice {
+            name: entry.name.clone(),
+            device_id: entry.device.borrow().device_id(),
+            base: entry.claim.as_ref().map(|(b, _)| *b),
+            size: entry
+                .claim
+                .as_ref()
+                .map(|(_, s)| s.borrow().len())
+                .unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{DEVICE_MEM_SIZE, InterruptClockDevice, SerialInputOutputDevice};
+    use crate::memory::{ReadWriteSegment, SegmentRange};
+
+    /// Ensure that devices claim and release their memory as they are attached and detached,
+    /// and that overlapping claims are refused
+    #[test]
+    fn test_attach_detach() {
+        let mut memory = MemoryMap::new();
+        let mut bus = DeviceBus::new();
+        memory
+            .add_segment(0, Rc::new(RefCell::new(ReadWriteSegment::new(0x100))))
+            .unwrap();
+
+        let serial = Rc::new(RefCell::new(SerialInputOutputDevice::new(4)));
+        let clock = Rc::new(RefCell::new(InterruptClockDevice::new(0)));
+
+        bus.attach(&mut memory, "serial", 0x100, serial.clone())
+            .unwrap();
+        assert!(matches!(
+            bus.attach(&mut memory, "clock", 0xF0, clock.clone()),
+            Err(MemoryError::SegmentOverlap {
+                existing: SegmentRange { base: 0, .. },
+                requested: SegmentRange { base: 0xF0, .. },
+            })
+        ));
+        assert!(bus
+            .attach(&mut memory, "clock", 0x110, clock.clone())
+            .is_err());
+        bus.attach(&mut memory, "clock", 0x120, clock.clone())
+            .unwrap();
+        bus.attach_unmapped(Rc::new(RefCell::new(InterruptClockDevice::new(0))));
+
+        assert_eq!(
+            bus.devices().collect::<Vec<_>>(),
+            [
+                AttachedDevice {
+                    name: "serial".into(),
+                    device_id: 1,
+                    base: Some(0x100),
+                    size: DEVICE_MEM_SIZE
+                },
+                AttachedDevice {
+                    name: "clock".into(),
+                    device_id: 2,
+                    base: Some(0x120),
+                    size: DEVICE_MEM_SIZE
+                },
+                AttachedDevice {
+                    name: "device 2".into(),
+                    device_id: 2,
+                    base: None,
+                    size: 0
+                },
+            ]
+        );
+        assert_eq!(bus.device_at(0x11F).unwrap().name, "serial");
+        assert!(bus.device_at(0x80).is_none());
+        assert_eq!(memory.inspect(0x121).unwrap(), 2);
+
+        assert_eq!(bus.detach(&mut memory, "serial").unwrap().base, Some(0x100));
+        assert!(bus.detach(&mut memory, "serial").is_none());
+        assert!(memory.inspect(0x101).is_err());
+        assert_eq!(bus.len(), 2);
+
+        bus.attach(&mut memory, "serial", 0x100, serial).unwrap();
+        assert_eq!(memory.inspect(0x101).unwrap(), 1);
+        assert_eq!(
+            memory.segments().collect::<Vec<_>>(),
+            [
+                (0, 0x100),
+                (0x120, DEVICE_MEM_SIZE),
+                (0x100, DEVICE_MEM_SIZE)
+            ]
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use super::{DEVICE_ID_SIZE, DEVICE_MEM_SIZE, DeviceAction, ProcessorDevice};
+
+use crate::memory::{MemorySegment, MemorySegmentError};
+
+/// Provides a change in the level of the output pins, as bitmasks of the pins driven high before
+/// and after the change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpioChange {
+    pub previous: u32,
+    pub current: u32,
+}
+
+impl GpioChange {
+    /// Provides the bitmask of the pins that changed level
+    pub fn changed(&self) -> u32 {
+        self.previous ^ self.current
+    }
+}
+
+type GpioHook = Box<dyn FnMut(GpioChange)>;
+
+/// Provides a memory-mapped general-purpose I/O port of up to 32 pins, where each pin is a bit
+/// of the direction, output, and input registers. Pins with the direction bit set are outputs,
+/// driven by the output register, while the remaining pins are inputs driven by the host. The
+/// host may register hooks which are called as the driven outputs change, and when enabled, the
+/// device raises the configured hardware interrupt after each step in which an input changes.
+/// As registers are written a byte at a time, a hook may be called for each byte that changes
+pub struct GpioDevice {
+    pin_mask: u32,
+    direction: u32,
+    output: u32,
+    input: u32,
+    irq_enabled: bool,
+    irq: u8,
+    pending: bool,
+    hooks: Vec<GpioHook>,
+}
+
+impl GpioDevice {
+    const OFFSET_PIN_COUNT: u32 = 2;
+    const OFFSET_IRQ_ENABLE: u32 = 3;
+    const OFFSET_IRQ: u32 = 4;
+    const OFFSET_DIRECTION: u32 = 8;
+    const OFFSET_OUTPUT: u32 = 12;
+    const OFFSET_INPUT: u32 = 16;
+    const OFFSET_END: u32 = 20;
+
+    pub const DEVICE_ID: u16 = 12;
+
+    pub const MAX_PINS: u8 = 32;
+
+    /// Constructs a new GPIO device with the provided number of pins, limited to the maximum
+    /// supported, with every pin initially an input
+    pub fn new(pin_count: u8) -> Self {
+        let pin_count = pin_count.min(Self::MAX_PINS);
+        Self {
+            pin_mask: u32::MAX.checked_shr(32 - pin_count as u32).unwrap_or(0),
+            direction: 0,
+            output: 0,
+            input: 0,
+            irq_enabled: false,
+            irq: 0,
+            pending: false,
+            hooks: Vec::new(),
+        }
+    }
+
+    pub fn pin_count(&self) -> u8 {
+        self.pin_mask.count_ones() as u8
+    }
+
+    /// Registers a hook called with each change of the driven output pins. Hooks are called
+    /// while the device is borrowed, and so must not access the device itself
+    pub fn add_output_hook<F: FnMut(GpioChange) + 'static>(&mut self, hook: F) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Provides the bitmask of output pins currently driven high
+    pub fn outputs(&self) -> u32 {
+        self.output & self.direction
+    }
+
+    /// Provides the bitmask of input pins driven high by the host
+    pub fn inputs(&self) -> u32 {
+        self.input & !self.direction
+    }
+
+    /// Provides the bitmask of pins configured as outputs
+    pub fn direction(&self) -> u32 {
+        self.direction
+    }
+
+    /// Drives the input pins from the host, where bits for pins that don't exist are ignored.
+    /// Levels are kept for pins configured as outputs, and apply once the pin becomes an input
+    pub fn set_inputs(&mut self, levels: u32) {
+        let levels = levels & self.pin_mask;
+        if (levels ^ self.input) & !self.direction != 0 {
+            self.pending = true;
+        }
+        self.input = levels;
+    }
+
+    /// Drives a single input pin from the host, returning false if the pin doesn't exist
+    pub fn set_input(&mut self, pin: u8, high: bool) -> bool {
+        let Some(bit) = 1u32
+            .checked_shl(pin as u32)
+            .filter(|b| b & self.pin_mask != 0)
+        else {
+            return false;
+        };
+
+        self.set_inputs(if high {
+            self.input | bit
+        } else {
+            self.input & !bit
+        });
+        true
+    }
+
+    /// Updates the direction and output registers, calling the output hooks if the driven
+    /// outputs change as a result
+    fn update_outputs(&mut self, direction: u32, output: u32) {
+        let previous = self.outputs();
+        self.direction = direction & self.pin_mask;
+        self.output = output & self.pin_mask;
+
+        let change = GpioChange {
+            previous,
+            current: self.outputs(),
+        };
+
+        if change.changed() != 0 {
+            for hook in self.hooks.iter_mut() {
+                hook(change);
+            }
+        }
+    }
+
+    /// Provides the register containing the offset, along with the byte index into the register
+    fn register(offset: u32) -> Option<(u32, usize)> {
+        if (Self::OFFSET_DIRECTION..Self::OFFSET_END).contains(&offset) {
+            let base = offset - (offset - Self::OFFSET_DIRECTION) % 4;
+            Some((base, (offset - base) as usize))
+        } else {
+            None
+        }
+    }
+}
+
+impl MemorySegment for GpioDevice {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            n if n < DEVICE_ID_SIZE => Ok(Self::DEVICE_ID.to_be_bytes()[n as usize]),
+            Self::OFFSET_PIN_COUNT => Ok(self.pin_count()),
+            Self::OFFSET_IRQ_ENABLE => Ok(self.irq_enabled as u8),
+            Self::OFFSET_IRQ => Ok(self.irq),
+            n => match Self::register(n) {
+                Some((base, ind)) => {
+                    let val = match base {
+                        Self::OFFSET_DIRECTION => self.direction,
+                        Self::OFFSET_OUTPUT => self.output,
+                        _ => self.inputs(),
+                    };
+                    Ok(val.to_be_bytes()[ind])
+                }
+                None => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+            },
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        match offset {
+            Self::OFFSET_IRQ_ENABLE => self.irq_enabled = data != 0,
+            Self::OFFSET_IRQ => self.irq = data,
+            n => match Self::register(n) {
+                Some((base, ind)) if base != Self::OFFSET_INPUT => {
+                    let (mut direction, mut output) = (self.direction, self.output);
+                    let reg = if base == Self::OFFSET_DIRECTION {
+                        &mut direction
+                    } else {
+                        &mut output
+                    };
+
+                    let mut bytes = reg.to_be_bytes();
+                    bytes[ind] = data;
+                    *reg = u32::from_be_bytes(bytes);
+
+                    self.update_outputs(direction, output);
+                }
+                _ => return Err(MemorySegmentError::InvalidMemoryWrite(offset, data)),
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Resets the memory segment, returning every pin to an input. Input levels are kept, as
+    /// they are driven by the host
+    fn reset(&mut self) {
+        self.update_outputs(0, 0);
+        self.irq_enabled = false;
+        self.irq = 0;
+        self.pending = false;
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        DEVICE_MEM_SIZE
+    }
+
+    /// Provides the direction, output, and input registers, followed by the interrupt enable,
+    /// interrupt number, and pending flag
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        for reg in [self.direction, self.output, self.input] {
+            state.extend(reg.to_be_bytes());
+        }
+        state.extend([self.irq_enabled as u8, self.irq, self.pending as u8]);
+        state
+    }
+
+    /// Restores the registers and interrupt configuration, calling the output hooks if the
+    /// driven outputs change
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let [d0, d1, d2, d3, o0, o1, o2, o3, i0, i1, i2, i3, enabled, irq, pending] = state else {
+            return Err(MemorySegmentError::InvalidState);
+        };
+
+        self.input = u32::from_be_bytes([*i0, *i1, *i2, *i3]) & self.pin_mask;
+        self.irq_enabled = *enabled != 0;
+        self.irq = *irq;
+        self.pending = *pending != 0;
+        self.update_outputs(
+            u32::from_be_bytes([*d0, *d1, *d2, *d3]),
+            u32::from_be_bytes([*o0, *o1, *o2, *o3]),
+        );
+
+        Ok(())
+    }
+}
+
+impl ProcessorDevice for GpioDevice {
+    fn on_step(&mut self, _cycles: u32) -> Option<DeviceAction> {
+        let pending = core::mem::take(&mut self.pending);
+        if pending && self.irq_enabled {
+            Some(DeviceAction::CallInterrupt(self.irq as u32))
+        } else {
+            None
+        }
+    }
+
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    /// Ensure that output hooks observe each change of the driven outputs, and that inputs
+    /// driven by the host are read by the guest and raise an interrupt when enabled
+    #[test]
+    fn test_pins() {
+        let mut dev = GpioDevice::new(12);
+        assert_eq!(dev.get(GpioDevice::OFFSET_PIN_COUNT).unwrap(), 12);
+
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let hook_changes = changes.clone();
+        dev.add_output_hook(move |c| hook_changes.borrow_mut().push(c));
+
+        dev.set(GpioDevice::OFFSET_OUTPUT + 3, 0x03).unwrap();
+        assert!(changes.borrow().is_empty());
+
+        dev.set(GpioDevice::OFFSET_DIRECTION + 3, 0x01).unwrap();
+        dev.set(GpioDevice::OFFSET_DIRECTION + 2, 0xFF).unwrap();
+        assert_eq!(dev.direction(), 0x0F01);
+        assert_eq!(
+            *changes.borrow(),
+            [GpioChange {
+                previous: 0,
+                current: 1
+            }]
+        );
+
+        assert!(dev.set_input(1, true));
+        assert!(dev.set_input(4, true));
+        assert!(!dev.set_input(12, true));
+        assert_eq!(dev.get(GpioDevice::OFFSET_INPUT + 3).unwrap(), 0x12);
+        assert!(dev.on_step(1).is_none());
+
+        dev.set(GpioDevice::OFFSET_IRQ, 5).unwrap();
+        dev.set(GpioDevice::OFFSET_IRQ_ENABLE, 1).unwrap();
+        dev.set_inputs(0x12);
+        assert!(dev.on_step(1).is_none());
+        dev.set_inputs(0x03);
+        assert!(matches!(
+            dev.on_step(1),
+            Some(DeviceAction::CallInterrupt(5))
+        ));
+        assert!(dev.set(GpioDevice::OFFSET_INPUT, 0).is_err());
+
+        let state = dev.save_state();
+        dev.reset();
+        assert_eq!(dev.outputs(), 0);
+        assert_eq!(changes.borrow().len(), 2);
+
+        dev.load_state(&state).unwrap();
+        assert_eq!(dev.outputs(), 1);
+        assert_eq!(dev.get(GpioDevice::OFFSET_IRQ).unwrap(), 5);
+        assert_eq!(changes.borrow().len(), 3);
+    }
+}
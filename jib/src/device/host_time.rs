@@ -0,0 +1,195 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use super::{DEVICE_ID_SIZE, DEVICE_MEM_SIZE, DeviceAction, ProcessorDevice};
+
+use crate::memory::{MemorySegment, MemorySegmentError};
+
+/// Provides the source of the millisecond timestamp of a host time device
+enum TimeSource {
+    Host(Box<dyn Fn() -> u64>),
+    /// Derives the timestamp from the cycle count at the provided cycles per millisecond
+    Cycles(u64),
+}
+
+/// Provides a monotonic host timestamp, in milliseconds since the emulator started, alongside
+/// the number of processor cycles executed since the device was reset. Writing to the latch
+/// register captures both values at the same instant, which may then be read as high and
+/// low words without tearing. This allows guest benchmarks to compare cycles against wall
+/// time, independent of any real-time clock
+pub struct HostTimeDevice {
+    source: TimeSource,
+    cycles: u64,
+    latched_ms: u64,
+    latched_cycles: u64,
+}
+
+impl HostTimeDevice {
+    const OFFSET_LATCH: u32 = 2;
+    const OFFSET_MS: u32 = 4;
+    const OFFSET_CYCLES: u32 = 12;
+    const OFFSET_END: u32 = 20;
+
+    pub const DEVICE_ID: u16 = 5;
+
+    /// Constructs a new host time device, which reads the current host time in milliseconds
+    /// from the provided source whenever the values are latched
+    pub fn new(source: impl Fn() -> u64 + 'static) -> Self {
+        Self {
+            source: TimeSource::Host(Box::new(source)),
+            cycles: 0,
+            latched_ms: 0,
+            latched_cycles: 0,
+        }
+    }
+
+    /// Constructs a new host time device with a timestamp derived from the cycles executed
+    /// since the device was reset, rather than from the host time, such that repeated runs of a
+    /// program read the same timestamps
+    pub fn cycle_clock(cycles_per_ms: u64) -> Self {
+        Self {
+            source: TimeSource::Cycles(cycles_per_ms.max(1)),
+            cycles: 0,
+            latched_ms: 0,
+            latched_cycles: 0,
+        }
+    }
+
+    /// Captures the current host time and cycle count
+    pub fn latch(&mut self) {
+        self.latched_ms = match &self.source {
+            TimeSource::Host(f) => f(),
+            TimeSource::Cycles(per_ms) => self.cycles / per_ms,
+        };
+        self.latched_cycles = self.cycles;
+    }
+}
+
+impl MemorySegment for HostTimeDevice {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            n if n < DEVICE_ID_SIZE => Ok(Self::DEVICE_ID.to_be_bytes()[n as usize]),
+            n if n < Self::OFFSET_MS => Ok(0),
+            n if n < Self::OFFSET_CYCLES => {
+                Ok(self.latched_ms.to_be_bytes()[(n - Self::OFFSET_MS) as usize])
+            }
+            n if n < Self::OFFSET_END => {
+                Ok(self.latched_cycles.to_be_bytes()[(n - Self::OFFSET_CYCLES) as usize])
+            }
+            _ => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+        }
+    }
+
+    /// Latches the current values when any byte of the latch register is written
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        if (Self::OFFSET_LATCH..Self::OFFSET_MS).contains(&offset) {
+            self.latch();
+            Ok(())
+        } else {
+            Err(MemorySegmentError::InvalidMemoryWrite(offset, data))
+        }
+    }
+
+    /// Resets the memory segment
+    fn reset(&mut self) {
+        self.cycles = 0;
+        self.latched_ms = 0;
+        self.latched_cycles = 0;
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        DEVICE_MEM_SIZE
+    }
+
+    /// Provides the cycle count and latched values. The host time source is not saved
+    fn save_state(&self) -> Vec<u8> {
+        [self.cycles, self.latched_ms, self.latched_cycles]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect()
+    }
+
+    /// Restores the cycle count and latched values
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let words: [u8; 24] = state
+            .try_into()
+            .map_err(|_| MemorySegmentError::InvalidState)?;
+        let word = |i: usize| u64::from_be_bytes(words[8 * i..8 * i + 8].try_into().unwrap());
+
+        self.cycles = word(0);
+        self.latched_ms = word(1);
+        self.latched_cycles = word(2);
+
+        Ok(())
+    }
+}
+
+impl ProcessorDevice for HostTimeDevice {
+    fn on_step(&mut self, cycles: u32) -> Option<DeviceAction> {
+        self.cycles = self.cycles.wrapping_add(cycles as u64);
+        None
+    }
+
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    fn read_u32(dev: &HostTimeDevice, offset: u32) -> u32 {
+        u32::from_be_bytes(core::array::from_fn(|i| {
+            dev.get(offset + i as u32).unwrap()
+        }))
+    }
+
+    /// Ensure that the host time and cycle count are only updated when latched
+    #[test]
+    fn test_latch() {
+        let now = Rc::new(Cell::new(0x1_0000_0002u64));
+        let mut dev = HostTimeDevice::new({
+            let now = now.clone();
+            move || now.get()
+        });
+
+        dev.on_step(7);
+        assert_eq!(read_u32(&dev, HostTimeDevice::OFFSET_MS + 4), 0);
+
+        dev.set(HostTimeDevice::OFFSET_LATCH, 1).unwrap();
+        now.set(50);
+        dev.on_step(3);
+
+        assert_eq!(read_u32(&dev, HostTimeDevice::OFFSET_MS), 1);
+        assert_eq!(read_u32(&dev, HostTimeDevice::OFFSET_MS + 4), 2);
+        assert_eq!(read_u32(&dev, HostTimeDevice::OFFSET_CYCLES + 4), 7);
+
+        dev.set(HostTimeDevice::OFFSET_LATCH, 1).unwrap();
+        assert_eq!(read_u32(&dev, HostTimeDevice::OFFSET_MS), 0);
+        assert_eq!(read_u32(&dev, HostTimeDevice::OFFSET_MS + 4), 50);
+        assert_eq!(read_u32(&dev, HostTimeDevice::OFFSET_CYCLES + 4), 10);
+
+        assert!(dev.set(HostTimeDevice::OFFSET_MS, 0).is_err());
+        assert!(dev.get(HostTimeDevice::OFFSET_END).is_err());
+    }
+
+    /// Ensure that the timestamp of a cycle clock follows the cycle count
+    #[test]
+    fn test_cycle_clock() {
+        let mut dev = HostTimeDevice::cycle_clock(100);
+
+        dev.on_step(250);
+        dev.latch();
+        assert_eq!(read_u32(&dev, HostTimeDevice::OFFSET_MS + 4), 2);
+        assert_eq!(read_u32(&dev, HostTimeDevice::OFFSET_CYCLES + 4), 250);
+
+        dev.reset();
+        dev.on_step(99);
+        dev.latch();
+        assert_eq!(read_u32(&dev, HostTimeDevice::OFFSET_MS + 4), 0);
+    }
+}
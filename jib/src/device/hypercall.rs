@@ -0,0 +1,232 @@
+use alloc::vec::Vec;
+
+use super::{DEVICE_ID_SIZE, DEVICE_MEM_SIZE, DeviceAction, ProcessorDevice};
+
+use crate::memory::{MemorySegment, MemorySegmentError};
+
+/// Provides a request made by the guest that must be carried out by the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HypercallRequest {
+    /// Save a snapshot of the processor, labelled with the provided tag
+    Snapshot(u32),
+    /// Stop the guest once the provided number of cycles have elapsed from the request
+    SetCycleBudget(u32),
+}
+
+/// Provides a memory-mapped control device, allowing guest test programs to request processor
+/// snapshots, set their own cycle budget, and raise hardware interrupts. Commands are only
+/// accepted while the host has enabled test mode, such that normal programs cannot control
+/// the emulator. Interrupts are raised by the device directly, while snapshot and budget
+/// requests are queued for the host to collect after each step
+pub struct HypercallDevice {
+    test_mode: bool,
+    argument: u32,
+    status: u8,
+    pending_irq: Option<u32>,
+    requests: Vec<HypercallRequest>,
+}
+
+impl HypercallDevice {
+    const OFFSET_COMMAND: u32 = 2;
+    const OFFSET_STATUS: u32 = 3;
+    const OFFSET_ARGUMENT: u32 = 4;
+    const OFFSET_TEST_MODE: u32 = 8;
+
+    pub const DEVICE_ID: u16 = 10;
+
+    pub const COMMAND_SNAPSHOT: u8 = 1;
+    pub const COMMAND_SET_CYCLE_BUDGET: u8 = 2;
+    pub const COMMAND_RAISE_IRQ: u8 = 3;
+
+    pub const STATUS_OK: u8 = 0;
+    pub const STATUS_DISABLED: u8 = 1;
+    pub const STATUS_INVALID_COMMAND: u8 = 2;
+
+    /// Constructs a new hypercall device, only accepting commands if test mode is enabled
+    pub fn new(test_mode: bool) -> Self {
+        Self {
+            test_mode,
+            argument: 0,
+            status: Self::STATUS_OK,
+            pending_irq: None,
+            requests: Vec::new(),
+        }
+    }
+
+    pub fn test_mode(&self) -> bool {
+        self.test_mode
+    }
+
+    pub fn set_test_mode(&mut self, enabled: bool) {
+        self.test_mode = enabled;
+    }
+
+    /// Provides and clears the requests made by the guest since the last call
+    pub fn take_requests(&mut self) -> Vec<HypercallRequest> {
+        core::mem::take(&mut self.requests)
+    }
+
+    fn execute(&mut self, command: u8) -> u8 {
+        if !self.test_mode {
+            return Self::STATUS_DISABLED;
+        }
+
+        match command {
+            Self::COMMAND_SNAPSHOT => self
+                .requests
+                .push(HypercallRequest::Snapshot(self.argument)),
+            Self::COMMAND_SET_CYCLE_BUDGET => self
+                .requests
+                .push(HypercallRequest::SetCycleBudget(self.argument)),
+            Self::COMMAND_RAISE_IRQ => self.pending_irq = Some(self.argument),
+            _ => return Self::STATUS_INVALID_COMMAND,
+        }
+
+        Self::STATUS_OK
+    }
+}
+
+impl MemorySegment for HypercallDevice {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            n if n < DEVICE_ID_SIZE => Ok(Self::DEVICE_ID.to_be_bytes()[n as usize]),
+            Self::OFFSET_COMMAND => Ok(0),
+            Self::OFFSET_STATUS => Ok(self.status),
+            n if (Self::OFFSET_ARGUMENT..Self::OFFSET_TEST_MODE).contains(&n) => {
+                Ok(self.argument.to_be_bytes()[(n - Self::OFFSET_ARGUMENT) as usize])
+            }
+            Self::OFFSET_TEST_MODE => Ok(self.test_mode as u8),
+            _ => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        match offset {
+            Self::OFFSET_COMMAND => self.status = self.execute(data),
+            n if (Self::OFFSET_ARGUMENT..Self::OFFSET_TEST_MODE).contains(&n) => {
+                let mut bytes = self.argument.to_be_bytes();
+                bytes[(n - Self::OFFSET_ARGUMENT) as usize] = data;
+                self.argument = u32::from_be_bytes(bytes);
+            }
+            _ => return Err(MemorySegmentError::InvalidMemoryWrite(offset, data)),
+        }
+
+        Ok(())
+    }
+
+    /// Resets the memory segment, keeping the test mode set by the host
+    fn reset(&mut self) {
+        self.argument = 0;
+        self.status = Self::STATUS_OK;
+        self.pending_irq = None;
+        self.requests.clear();
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        DEVICE_MEM_SIZE
+    }
+
+    /// Provides the argument, status, and any interrupt waiting to be raised. Requests not yet
+    /// collected by the host are not included
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::from(self.argument.to_be_bytes());
+        state.push(self.status);
+        state.push(self.pending_irq.is_some() as u8);
+        state.extend(self.pending_irq.unwrap_or(0).to_be_bytes());
+        state
+    }
+
+    /// Restores the argument, status, and any interrupt waiting to be raised
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let [a0, a1, a2, a3, status, pending, i0, i1, i2, i3] = state else {
+            return Err(MemorySegmentError::InvalidState);
+        };
+
+        self.argument = u32::from_be_bytes([*a0, *a1, *a2, *a3]);
+        self.status = *status;
+        self.pending_irq = (*pending != 0).then(|| u32::from_be_bytes([*i0, *i1, *i2, *i3]));
+
+        Ok(())
+    }
+}
+
+impl ProcessorDevice for HypercallDevice {
+    fn on_step(&mut self, _cycles: u32) -> Option<DeviceAction> {
+        self.pending_irq.take().map(DeviceAction::CallInterrupt)
+    }
+
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(dev: &mut HypercallDevice, command: u8, argument: u32) -> u8 {
+        for (i, b) in argument.to_be_bytes().into_iter().enumerate() {
+            dev.set(HypercallDevice::OFFSET_ARGUMENT + i as u32, b)
+                .unwrap();
+        }
+        dev.set(HypercallDevice::OFFSET_COMMAND, command).unwrap();
+        dev.get(HypercallDevice::OFFSET_STATUS).unwrap()
+    }
+
+    /// Ensure that commands are only accepted in test mode, and that requests and interrupts are
+    /// provided to the host and processor
+    #[test]
+    fn test_hypercalls() {
+        let mut dev = HypercallDevice::new(false);
+        assert_eq!(dev.get(HypercallDevice::OFFSET_TEST_MODE).unwrap(), 0);
+        assert_eq!(
+            command(&mut dev, HypercallDevice::COMMAND_SNAPSHOT, 1),
+            HypercallDevice::STATUS_DISABLED
+        );
+        assert!(dev.take_requests().is_empty());
+
+        dev.set_test_mode(true);
+        assert_eq!(dev.get(HypercallDevice::OFFSET_TEST_MODE).unwrap(), 1);
+        assert_eq!(
+            command(&mut dev, HypercallDevice::COMMAND_SNAPSHOT, 7),
+            HypercallDevice::STATUS_OK
+        );
+        command(&mut dev, HypercallDevice::COMMAND_SET_CYCLE_BUDGET, 500);
+        assert_eq!(
+            dev.take_requests(),
+            [
+                HypercallRequest::Snapshot(7),
+                HypercallRequest::SetCycleBudget(500)
+            ]
+        );
+        assert!(dev.take_requests().is_empty());
+
+        assert!(dev.on_step(1).is_none());
+        command(&mut dev, HypercallDevice::COMMAND_RAISE_IRQ, 3);
+        let state = dev.save_state();
+        assert!(matches!(
+            dev.on_step(1),
+            Some(DeviceAction::CallInterrupt(3))
+        ));
+        assert!(dev.on_step(1).is_none());
+
+        dev.load_state(&state).unwrap();
+        assert!(matches!(
+            dev.on_step(1),
+            Some(DeviceAction::CallInterrupt(3))
+        ));
+
+        assert_eq!(
+            command(&mut dev, 0x42, 0),
+            HypercallDevice::STATUS_INVALID_COMMAND
+        );
+        assert!(dev.set(HypercallDevice::OFFSET_TEST_MODE, 0).is_err());
+
+        dev.reset();
+        assert!(dev.test_mode());
+        assert_eq!(dev.get(HypercallDevice::OFFSET_STATUS).unwrap(), 0);
+    }
+}
@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 use crate::{
     cpu::Processor,
     memory::{MemorySegment, MemorySegmentError},
@@ -84,16 +86,40 @@ impl MemorySegment for InterruptClockDevice {
     fn len(&self) -> u32 {
         DEVICE_MEM_SIZE
     }
+
+    /// Provides the clock interval, current count, and interrupt values
+    fn save_state(&self) -> Vec<u8> {
+        [self.clock_interval, self.current_count, self.interrupt]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect()
+    }
+
+    /// Restores the clock interval, current count, and interrupt values
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let words: [u8; 12] = state
+            .try_into()
+            .map_err(|_| MemorySegmentError::InvalidState)?;
+        let word = |i: usize| u32::from_be_bytes(words[4 * i..4 * i + 4].try_into().unwrap());
+
+        self.clock_interval = word(0);
+        self.current_count = word(1);
+        self.interrupt = word(2);
+
+        Ok(())
+    }
 }
 
 impl ProcessorDevice for InterruptClockDevice {
-    fn on_step(&mut self) -> Option<DeviceAction> {
+    fn on_step(&mut self, cycles: u32) -> Option<DeviceAction> {
         if self.clock_interval != 0 {
-            self.current_count = (self.current_count + 1) % self.clock_interval;
+            let count = self.current_count as u64 + cycles as u64;
+            self.current_count = (count % self.clock_interval as u64) as u32;
 
-            match self.current_count {
-                0 => Some(DeviceAction::CallInterrupt(self.interrupt)),
-                _ => None,
+            if count >= self.clock_interval as u64 {
+                Some(DeviceAction::CallInterrupt(self.interrupt))
+            } else {
+                None
             }
         } else {
             self.current_count = 0;
@@ -0,0 +1,205 @@
+use alloc::{collections::VecDeque, vec::Vec};
+use core::cell::RefCell;
+
+use super::{DEVICE_ID_SIZE, DEVICE_MEM_SIZE, DeviceAction, ProcessorDevice};
+
+use crate::memory::{MemorySegment, MemorySegmentError};
+
+/// Provides a memory-mapped keyboard, which buffers key presses from the host until read by the
+/// guest. Printable characters and newlines use the character mapping, while other keys use the
+/// key codes defined by the device. When enabled, the device raises the configured hardware
+/// interrupt after each step in which a key arrives
+pub struct KeyboardDevice {
+    queue: RefCell<VecDeque<u8>>,
+    buffer_size: usize,
+    irq_enabled: bool,
+    irq: u8,
+    pending: bool,
+}
+
+impl KeyboardDevice {
+    const OFFSET_SIZE: u32 = 2;
+    const OFFSET_GET: u32 = 3;
+    const OFFSET_IRQ_ENABLE: u32 = 4;
+    const OFFSET_IRQ: u32 = 5;
+    const OFFSET_RESET: u32 = 6;
+
+    pub const DEVICE_ID: u16 = 7;
+
+    pub const KEY_BACKSPACE: u8 = 0x08;
+    pub const KEY_TAB: u8 = 0x09;
+    pub const KEY_ESCAPE: u8 = 0x1B;
+    pub const KEY_DELETE: u8 = 0x7F;
+    pub const KEY_UP: u8 = 0x80;
+    pub const KEY_DOWN: u8 = 0x81;
+    pub const KEY_LEFT: u8 = 0x82;
+    pub const KEY_RIGHT: u8 = 0x83;
+
+    /// Constructs a new keyboard device, buffering up to the provided number of key presses
+    pub fn new(buffer_size: usize) -> Self {
+        Self {
+            queue: RefCell::new(VecDeque::new()),
+            buffer_size,
+            irq_enabled: false,
+            irq: 0,
+            pending: false,
+        }
+    }
+
+    /// Pushes the key code into the key queue, returning false if the queue is full
+    pub fn push_key(&mut self, key: u8) -> bool {
+        if self.queue.borrow().len() < self.buffer_size {
+            self.queue.borrow_mut().push_back(key);
+            self.pending = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Pushes the character into the key queue, returning false if the character has no
+    /// mapping or the queue is full
+    pub fn push_char(&mut self, c: char) -> bool {
+        match crate::text::character_to_byte(c) {
+            Ok(key) => self.push_key(key),
+            Err(_) => false,
+        }
+    }
+
+    fn common_get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            n if n < DEVICE_ID_SIZE => Ok(Self::DEVICE_ID.to_be_bytes()[n as usize]),
+            Self::OFFSET_SIZE => Ok((u8::MAX as usize).min(self.queue.borrow().len()) as u8),
+            Self::OFFSET_IRQ_ENABLE => Ok(self.irq_enabled as u8),
+            Self::OFFSET_IRQ => Ok(self.irq),
+            _ => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+        }
+    }
+}
+
+impl MemorySegment for KeyboardDevice {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            Self::OFFSET_GET => Ok(self.queue.borrow_mut().pop_front().unwrap_or(0)),
+            _ => self.common_get(offset),
+        }
+    }
+
+    /// Provides the word at the requested memory location without affecting the device state
+    fn inspect(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            Self::OFFSET_GET => Ok(self.queue.borrow().front().copied().unwrap_or(0)),
+            _ => self.common_get(offset),
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        match offset {
+            Self::OFFSET_IRQ_ENABLE => self.irq_enabled = data != 0,
+            Self::OFFSET_IRQ => self.irq = data,
+            Self::OFFSET_RESET => {
+                if data != 0 {
+                    self.queue.borrow_mut().clear();
+                }
+            }
+            _ => return Err(MemorySegmentError::InvalidMemoryWrite(offset, data)),
+        }
+
+        Ok(())
+    }
+
+    /// Resets the memory segment
+    fn reset(&mut self) {
+        self.queue.borrow_mut().clear();
+        self.irq_enabled = false;
+        self.irq = 0;
+        self.pending = false;
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        DEVICE_MEM_SIZE
+    }
+
+    /// Provides the interrupt enable, interrupt number, and pending flag, followed by the queue
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::from([self.irq_enabled as u8, self.irq, self.pending as u8]);
+        state.extend(self.queue.borrow().iter());
+        state
+    }
+
+    /// Restores the interrupt configuration and queued keys
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let ([enabled, irq, pending], queue) = state
+            .split_first_chunk::<3>()
+            .ok_or(MemorySegmentError::InvalidState)?;
+
+        self.irq_enabled = *enabled != 0;
+        self.irq = *irq;
+        self.pending = *pending != 0;
+        *self.queue.borrow_mut() = queue.iter().copied().collect();
+
+        Ok(())
+    }
+}
+
+impl ProcessorDevice for KeyboardDevice {
+    fn on_step(&mut self, _cycles: u32) -> Option<DeviceAction> {
+        let pending = core::mem::take(&mut self.pending);
+        if pending && self.irq_enabled {
+            Some(DeviceAction::CallInterrupt(self.irq as u32))
+        } else {
+            None
+        }
+    }
+
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensure that keys are read in order, and that an interrupt is only raised when enabled
+    /// and a key has arrived since the last step
+    #[test]
+    fn test_keys() {
+        let mut dev = KeyboardDevice::new(2);
+
+        assert!(dev.push_char('a'));
+        assert!(dev.push_key(KeyboardDevice::KEY_UP));
+        assert!(!dev.push_key(KeyboardDevice::KEY_DOWN));
+        assert!(dev.on_step(1).is_none());
+
+        assert_eq!(dev.get(KeyboardDevice::OFFSET_SIZE).unwrap(), 2);
+        assert_eq!(dev.inspect(KeyboardDevice::OFFSET_GET).unwrap(), b'a');
+        assert_eq!(dev.get(KeyboardDevice::OFFSET_GET).unwrap(), b'a');
+
+        dev.set(KeyboardDevice::OFFSET_IRQ, 3).unwrap();
+        dev.set(KeyboardDevice::OFFSET_IRQ_ENABLE, 1).unwrap();
+        assert!(dev.push_char('\n'));
+        assert!(matches!(
+            dev.on_step(1),
+            Some(DeviceAction::CallInterrupt(3))
+        ));
+        assert!(dev.on_step(1).is_none());
+
+        assert!(!dev.push_char('\u{e9}'));
+        assert_eq!(
+            dev.get(KeyboardDevice::OFFSET_GET).unwrap(),
+            KeyboardDevice::KEY_UP
+        );
+        assert_eq!(dev.get(KeyboardDevice::OFFSET_GET).unwrap(), b'\n');
+        assert_eq!(dev.get(KeyboardDevice::OFFSET_GET).unwrap(), 0);
+
+        let state = dev.save_state();
+        let mut other = KeyboardDevice::new(2);
+        other.load_state(&state).unwrap();
+        assert_eq!(other.get(KeyboardDevice::OFFSET_IRQ).unwrap(), 3);
+        assert!(other.set(KeyboardDevice::OFFSET_GET, 0).is_err());
+    }
+}
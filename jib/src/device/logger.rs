@@ -0,0 +1,253 @@
+use alloc::{collections::VecDeque, fmt, string::String, vec::Vec};
+
+use super::{DEVICE_ID_SIZE, DEVICE_MEM_SIZE, ProcessorDevice};
+
+use crate::{
+    cpu::{Processor, ProcessorError},
+    memory::{MemorySegment, MemorySegmentError},
+    text::byte_to_character,
+};
+
+/// Defines the severity levels supported by the logging device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+impl From<LogLevel> for u8 {
+    fn from(value: LogLevel) -> Self {
+        match value {
+            LogLevel::Debug => 0,
+            LogLevel::Info => 1,
+            LogLevel::Warning => 2,
+            LogLevel::Error => 3,
+        }
+    }
+}
+
+impl From<u8> for LogLevel {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::Debug,
+            1 => Self::Info,
+            2 => Self::Warning,
+            _ => Self::Error,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Debug => write!(f, "DEBUG"),
+            Self::Info => write!(f, "INFO"),
+            Self::Warning => write!(f, "WARNING"),
+            Self::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+/// Provides a log message request made by the guest program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogEntry {
+    pub level: LogLevel,
+    pub address: u32,
+}
+
+impl LogEntry {
+    /// Defines the maximum number of characters read for a single message
+    pub const MAX_MESSAGE_LEN: u32 = 256;
+
+    /// Reads the null-terminated message text from the processor memory
+    pub fn read_message(&self, cpu: &Processor) -> Result<String, ProcessorError> {
+        let mut msg = String::new();
+
+        for i in 0..Self::MAX_MESSAGE_LEN {
+            let val = cpu.memory_inspect(self.address.wrapping_add(i))?;
+            if val == 0 {
+                break;
+            }
+
+            msg.push(byte_to_character(val).unwrap_or('?'));
+        }
+
+        Ok(msg)
+    }
+}
+
+/// Provides a memory-mapped device that allows the guest to send log messages to the host
+pub struct LogDevice {
+    level: u8,
+    address: u32,
+    entries: VecDeque<LogEntry>,
+    min_level: LogLevel,
+    buffer_size: usize,
+}
+
+impl LogDevice {
+    const OFFSET_LEVEL: u32 = 2;
+    const OFFSET_COMMIT: u32 = 3;
+    const OFFSET_ADDRESS: u32 = 4;
+    const OFFSET_QUEUE_SIZE: u32 = Self::OFFSET_ADDRESS + Processor::BYTES_PER_WORD;
+
+    pub const DEVICE_ID: u16 = 3;
+
+    /// Constructs a new logging device
+    pub fn new(buffer_size: usize) -> Self {
+        Self {
+            level: 0,
+            address: 0,
+            entries: VecDeque::new(),
+            min_level: LogLevel::Debug,
+            buffer_size,
+        }
+    }
+
+    /// Sets the minimum level for messages to be retained by the device
+    pub fn set_min_level(&mut self, level: LogLevel) {
+        self.min_level = level;
+    }
+
+    /// Pops the next log entry from the device queue
+    pub fn pop_entry(&mut self) -> Option<LogEntry> {
+        self.entries.pop_front()
+    }
+}
+
+impl MemorySegment for LogDevice {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            n if n < DEVICE_ID_SIZE => Ok(Self::DEVICE_ID.to_be_bytes()[offset as usize]),
+            Self::OFFSET_LEVEL => Ok(self.level),
+            Self::OFFSET_COMMIT => Ok(0),
+            n if (Self::OFFSET_ADDRESS..Self::OFFSET_QUEUE_SIZE).contains(&n) => {
+                Ok(self.address.to_be_bytes()[(n - Self::OFFSET_ADDRESS) as usize])
+            }
+            Self::OFFSET_QUEUE_SIZE => Ok((u8::MAX as usize).min(self.entries.len()) as u8),
+            _ => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    /// Returns true if the value could be set; otherwise returns false
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        match offset {
+            Self::OFFSET_LEVEL => {
+                self.level = data;
+                Ok(())
+            }
+            Self::OFFSET_COMMIT => {
+                let level = LogLevel::from(self.level);
+                if data != 0 && level >= self.min_level && self.entries.len() < self.buffer_size {
+                    self.entries.push_back(LogEntry {
+                        level,
+                        address: self.address,
+                    });
+                }
+                Ok(())
+            }
+            n if (Self::OFFSET_ADDRESS..Self::OFFSET_QUEUE_SIZE).contains(&n) => {
+                let mut val = self.address.to_be_bytes();
+                val[(n - Self::OFFSET_ADDRESS) as usize] = data;
+                self.address = u32::from_be_bytes(val);
+                Ok(())
+            }
+            _ => Err(MemorySegmentError::InvalidMemoryWrite(offset, data)),
+        }
+    }
+
+    /// Resets the memory segment
+    fn reset(&mut self) {
+        self.level = 0;
+        self.address = 0;
+        self.entries.clear();
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        DEVICE_MEM_SIZE
+    }
+
+    /// Provides the level and address values, followed by the level and address of each queued entry
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        state.push(self.level);
+        state.extend(self.address.to_be_bytes());
+
+        for e in self.entries.iter() {
+            state.push(e.level.into());
+            state.extend(e.address.to_be_bytes());
+        }
+
+        state
+    }
+
+    /// Restores the level and address values, along with the queued entries
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        const ENTRY_SIZE: usize = 5;
+
+        if !state.len().is_multiple_of(ENTRY_SIZE) || state.is_empty() {
+            return Err(MemorySegmentError::InvalidState);
+        }
+
+        let mut chunks = state
+            .chunks_exact(ENTRY_SIZE)
+            .map(|c| (c[0], u32::from_be_bytes([c[1], c[2], c[3], c[4]])));
+
+        let (level, address) = chunks.next().unwrap();
+        self.level = level;
+        self.address = address;
+        self.entries = chunks
+            .map(|(level, address)| LogEntry {
+                level: level.into(),
+                address,
+            })
+            .collect();
+
+        Ok(())
+    }
+}
+
+impl ProcessorDevice for LogDevice {
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a log request into the device
+    fn write_entry(dev: &mut LogDevice, level: u8, address: u32) {
+        dev.set(LogDevice::OFFSET_LEVEL, level).unwrap();
+        for (i, b) in address.to_be_bytes().into_iter().enumerate() {
+            dev.set(LogDevice::OFFSET_ADDRESS + i as u32, b).unwrap();
+        }
+        dev.set(LogDevice::OFFSET_COMMIT, 1).unwrap();
+    }
+
+    /// Ensure that committed entries are queued and filtered by level
+    #[test]
+    fn test_log_entries() {
+        let mut dev = LogDevice::new(4);
+        dev.set_min_level(LogLevel::Info);
+
+        write_entry(&mut dev, 0, 0x1000);
+        write_entry(&mut dev, 2, 0x2000);
+        assert_eq!(dev.get(LogDevice::OFFSET_QUEUE_SIZE).unwrap(), 1);
+
+        assert_eq!(
+            dev.pop_entry(),
+            Some(LogEntry {
+                level: LogLevel::Warning,
+                address: 0x2000
+            })
+        );
+        assert_eq!(dev.pop_entry(), None);
+    }
+}
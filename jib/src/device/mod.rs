@@ -1,8 +1,41 @@
+mod block_storage;
+mod bus;
+mod gpio;
+mod host_time;
+mod hypercall;
 mod irq_clock;
+mod keyboard;
+mod logger;
+mod playback;
+mod ring_buffer;
 mod serial_io;
+mod serial_mux;
+mod text_display;
+mod trap_info;
 
+#[cfg(feature = "std")]
+pub use block_storage::FileBlockStorage;
+pub use block_storage::{
+    BlockStorage, BlockStorageDevice, BlockStorageError, MemoryBlockStorage, SECTOR_SIZE,
+};
+pub use bus::{AttachedDevice, DeviceBus};
+pub use gpio::{GpioChange, GpioDevice};
+pub use host_time::HostTimeDevice;
+pub use hypercall::{HypercallDevice, HypercallRequest};
 pub use irq_clock::InterruptClockDevice;
+pub use keyboard::KeyboardDevice;
+pub use logger::{LogDevice, LogEntry, LogLevel};
+pub use playback::{
+    InputPlaybackDevice, InputRecordDevice, PlaybackError, PlaybackEvent, PlaybackInput,
+    PlaybackScript,
+};
+pub use ring_buffer::RingBufferDevice;
 pub use serial_io::SerialInputOutputDevice;
+#[cfg(feature = "std")]
+pub use serial_io::{SerialTcpBridge, SerialTcpEvent};
+pub use serial_mux::SerialMuxDevice;
+pub use text_display::{DisplayCell, DisplayScreen, TextDisplayDevice};
+pub use trap_info::{TrapCause, TrapInfo, TrapInfoDevice};
 
 pub const DEVICE_MEM_SIZE: u32 = 32;
 pub const DEVICE_ID_SIZE: u32 = 2;
@@ -12,7 +45,8 @@ pub enum DeviceAction {
 }
 
 pub trait ProcessorDevice {
-    fn on_step(&mut self) -> Option<DeviceAction> {
+    /// Called after each executed instruction with the number of cycles the instruction consumed
+    fn on_step(&mut self, _cycles: u32) -> Option<DeviceAction> {
         None
     }
 
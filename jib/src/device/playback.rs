@@ -0,0 +1,697 @@
+use alloc::{collections::VecDeque, rc::Rc, string::String, vec::Vec};
+use core::{
+    cell::RefCell,
+    fmt::{self, Write},
+};
+
+use super::{DeviceAction, KeyboardDevice, ProcessorDevice, SerialInputOutputDevice};
+
+use crate::text::{CharacterError, byte_to_character, character_to_byte};
+
+/// Provides error conditions for parsing a playback script, along with the line number
+#[derive(Debug, Clone)]
+pub enum PlaybackError {
+    InvalidCycle(usize),
+    UnorderedEvent(usize),
+    UnknownEvent(usize, String),
+    InvalidData(usize),
+    Character(usize, CharacterError),
+}
+
+impl fmt::Display for PlaybackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidCycle(line) => write!(f, "Line {line} - Invalid Cycle Count"),
+            Self::UnorderedEvent(line) => {
+                write!(f, "Line {line} - Event Occurs Before the Previous Event")
+            }
+            Self::UnknownEvent(line, name) => write!(f, "Line {line} - Unknown Event '{name}'"),
+            Self::InvalidData(line) => write!(f, "Line {line} - Invalid Event Data"),
+            Self::Character(line, e) => write!(f, "Line {line} - {e}"),
+        }
+    }
+}
+
+impl core::error::Error for PlaybackError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Character(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Provides the input of a playback event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaybackInput {
+    /// Bytes added to the serial device input
+    Serial(Vec<u8>),
+    /// Key codes added to the keyboard device
+    Key(Vec<u8>),
+    /// A hardware interrupt triggered by the host
+    Irq(u32),
+}
+
+/// Provides input to be provided once the processor has executed the given number of cycles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaybackEvent {
+    pub cycle: u64,
+    pub input: PlaybackInput,
+}
+
+/// Provides a script of timestamped input events, allowing interactive programs to be driven
+/// reproducibly. Each line provides the cycle count of the event, the event type, and the event
+/// data, such as `1200 serial "run\n" 0x00`. Serial and key events provide data as quoted text,
+/// supporting the `\n`, `\0`, `\"`, and `\\` escapes, or as byte values, while irq events
+/// provide the interrupt number, such as `1500 irq 2`. A `seed 1234` line provides the seed of
+/// any random sequence used by the host, such as for fault injection. Events must be provided in
+/// cycle order, and empty lines or lines starting with # are ignored
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlaybackScript {
+    events: Vec<PlaybackEvent>,
+    seed: Option<u64>,
+}
+
+impl PlaybackScript {
+    pub fn parse(s: &str) -> Result<Self, PlaybackError> {
+        let mut script = Self::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let line_num = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.splitn(3, char::is_whitespace);
+            let first = words.next().unwrap_or_default();
+            if first == "seed" {
+                let seed = words.next().and_then(parse_number);
+                if seed.is_none() || words.next().is_some() {
+                    return Err(PlaybackError::InvalidData(line_num));
+                }
+                script.seed = seed;
+                continue;
+            }
+
+            let cycle = parse_number(first).ok_or(PlaybackError::InvalidCycle(line_num))?;
+
+            if script.events.last().is_some_and(|e| e.cycle > cycle) {
+                return Err(PlaybackError::UnorderedEvent(line_num));
+            }
+
+            let kind = words.next();
+            let data = words.next().unwrap_or_default();
+            let input = match kind {
+                Some("serial") => PlaybackInput::Serial(parse_data(data, line_num)?),
+                Some("key") => PlaybackInput::Key(parse_data(data, line_num)?),
+                Some("irq") => parse_number(data.trim())
+                    .and_then(|v| u32::try_from(v).ok())
+                    .map(PlaybackInput::Irq)
+                    .ok_or(PlaybackError::InvalidData(line_num))?,
+                Some(name) => return Err(PlaybackError::UnknownEvent(line_num, name.into())),
+                None => return Err(PlaybackError::InvalidData(line_num)),
+            };
+
+            script.events.push(PlaybackEvent { cycle, input });
+        }
+
+        Ok(script)
+    }
+
+    pub fn events(&self) -> &[PlaybackEvent] {
+        &self.events
+    }
+
+    /// Provides the seed of any random sequence used by the host
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    /// Adds an event to the end of the script, such as when recording the input provided to a
+    /// program, providing false if the event is empty or occurs before the previous event
+    pub fn push(&mut self, cycle: u64, input: PlaybackInput) -> bool {
+        let empty = match &input {
+            PlaybackInput::Serial(data) | PlaybackInput::Key(data) => data.is_empty(),
+            PlaybackInput::Irq(_) => false,
+        };
+
+        if empty || self.events.last().is_some_and(|e| e.cycle > cycle) {
+            return false;
+        }
+
+        self.events.push(PlaybackEvent { cycle, input });
+        true
+    }
+
+    /// Provides the script in the text format, where bytes are written as quoted text if they
+    /// have a character, and as byte values otherwise
+    pub fn to_text(&self) -> String {
+        let mut s = String::new();
+        if let Some(seed) = self.seed {
+            let _ = writeln!(s, "seed {seed}");
+        }
+
+        for evt in self.events.iter() {
+            let data = match &evt.input {
+                PlaybackInput::Serial(data) => {
+                    let _ = write!(s, "{} serial", evt.cycle);
+                    data
+                }
+                PlaybackInput::Key(data) => {
+                    let _ = write!(s, "{} key", evt.cycle);
+                    data
+                }
+                PlaybackInput::Irq(irq) => {
+                    let _ = writeln!(s, "{} irq {irq}", evt.cycle);
+                    continue;
+                }
+            };
+
+            let mut quoted = false;
+            for b in data.iter().copied() {
+                let Ok(c) = byte_to_character(b) else {
+                    if quoted {
+                        s.push('"');
+                        quoted = false;
+                    }
+                    let _ = write!(s, " {b}");
+                    continue;
+                };
+
+                if !quoted {
+                    s.push_str(" \"");
+                    quoted = true;
+                }
+                match c {
+                    '\n' => s.push_str("\\n"),
+                    '\0' => s.push_str("\\0"),
+                    '"' | '\\' => {
+                        s.push('\\');
+                        s.push(c);
+                    }
+                    c => s.push(c),
+                }
+            }
+
+            if quoted {
+                s.push('"');
+            }
+            s.push('\n');
+        }
+        s
+    }
+}
+
+fn parse_number(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Parses the event data as a sequence of quoted strings and byte values
+fn parse_data(s: &str, line: usize) -> Result<Vec<u8>, PlaybackError> {
+    let mut data = Vec::new();
+    let mut chars = s.trim().chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        } else if c == '"' {
+            loop {
+                let c = match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => '\n',
+                        Some('0') => '\0',
+                        Some(e @ ('"' | '\\')) => e,
+                        _ => return Err(PlaybackError::InvalidData(line)),
+                    },
+                    Some(c) => c,
+                    None => return Err(PlaybackError::InvalidData(line)),
+                };
+
+                data.push(character_to_byte(c).map_err(|e| PlaybackError::Character(line, e))?);
+            }
+        } else {
+            let mut word = String::from(c);
+            while let Some(n) = chars.next_if(|n| !n.is_whitespace()) {
+                word.push(n);
+            }
+
+            let val = parse_number(&word)
+                .and_then(|v| u8::try_from(v).ok())
+                .ok_or(PlaybackError::InvalidData(line))?;
+            data.push(val);
+        }
+    }
+
+    if data.is_empty() {
+        Err(PlaybackError::InvalidData(line))
+    } else {
+        Ok(data)
+    }
+}
+
+/// Provides a device that plays back a script into the serial and keyboard device inputs,
+/// adding the data of each event once the processor has executed the event cycle count, and
+/// calling the interrupt of each irq event. If an input buffer is full, the remaining data is
+/// held until space is available. Key events are skipped if no keyboard device is provided.
+/// Events at cycle zero are provided when the device is created, before the first instruction,
+/// and so the device should be created after the processor is reset. The device is not mapped
+/// into memory, and so a new device should be added to restart the script after a reset
+pub struct InputPlaybackDevice {
+    script: PlaybackScript,
+    serial: Rc<RefCell<SerialInputOutputDevice>>,
+    keyboard: Option<Rc<RefCell<KeyboardDevice>>>,
+    irqs: VecDeque<u32>,
+    cycles: u64,
+    event: usize,
+    offset: usize,
+}
+
+impl InputPlaybackDevice {
+    pub const DEVICE_ID: u16 = 6;
+
+    pub fn new(script: PlaybackScript, serial: Rc<RefCell<SerialInputOutputDevice>>) -> Self {
+        let mut dev = Self {
+            script,
+            serial,
+            keyboard: None,
+            irqs: VecDeque::new(),
+            cycles: 0,
+            event: 0,
+            offset: 0,
+        };
+        dev.feed();
+        dev
+    }
+
+    /// Provides key events to the keyboard device
+    pub fn with_keyboard(mut self, keyboard: Rc<RefCell<KeyboardDevice>>) -> Self {
+        self.keyboard = Some(keyboard);
+        self.feed();
+        self
+    }
+
+    /// Determines whether every event in the script has been provided to the devices
+    pub fn is_finished(&self) -> bool {
+        self.event >= self.script.events.len() && self.irqs.is_empty()
+    }
+
+    fn feed(&mut self) {
+        while let Some(evt) = self.script.events.get(self.event) {
+            if evt.cycle > self.cycles {
+                return;
+            }
+
+            match &evt.input {
+                PlaybackInput::Serial(data) => {
+                    for b in data[self.offset..].iter() {
+                        if !self.serial.borrow_mut().push_input(*b) {
+                            return;
+                        }
+                        self.offset += 1;
+                    }
+                }
+                PlaybackInput::Key(data) => match &self.keyboard {
+                    Some(keyboard) => {
+                        for k in data[self.offset..].iter() {
+                            if !keyboard.borrow_mut().push_key(*k) {
+                                return;
+                            }
+                            self.offset += 1;
+                        }
+                    }
+                    // Hold key events before the first instruction, where the keyboard device
+                    // may still be provided
+                    None if self.cycles == 0 => return,
+                    None => (),
+                },
+                PlaybackInput::Irq(irq) => self.irqs.push_back(*irq),
+            }
+
+            self.event += 1;
+            self.offset = 0;
+        }
+    }
+}
+
+impl ProcessorDevice for InputPlaybackDevice {
+    /// Provides the input due by the cycle count, calling a single pending interrupt per step
+    fn on_step(&mut self, cycles: u32) -> Option<DeviceAction> {
+        self.cycles += cycles as u64;
+        self.feed();
+        self.irqs.pop_front().map(DeviceAction::CallInterrupt)
+    }
+
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+/// Provides a device that records the input provided to the serial and keyboard devices, and
+/// the interrupts triggered by the host, as a playback script. Events are timestamped with the
+/// cycles executed as counted by [`InputPlaybackDevice`], such that playing back the script
+/// provides each input at the same point of the program. Input of the same type provided
+/// between instructions at the same cycle count is recorded as a single event
+pub struct InputRecordDevice {
+    serial: Rc<RefCell<SerialInputOutputDevice>>,
+    keyboard: Option<Rc<RefCell<KeyboardDevice>>>,
+    cycles: u64,
+    script: PlaybackScript,
+}
+
+impl InputRecordDevice {
+    pub const DEVICE_ID: u16 = 16;
+
+    pub fn new(serial: Rc<RefCell<SerialInputOutputDevice>>) -> Self {
+        Self {
+            serial,
+            keyboard: None,
+            cycles: 0,
+            script: PlaybackScript::default(),
+        }
+    }
+
+    /// Records key codes pushed into the keyboard device
+    pub fn with_keyboard(mut self, keyboard: Rc<RefCell<KeyboardDevice>>) -> Self {
+        self.keyboard = Some(keyboard);
+        self
+    }
+
+    /// Adds the byte to the last event if of the same type and cycle count, or a new event
+    fn record(&mut self, b: u8, key: bool) {
+        let last = self.script.events.last_mut();
+        match last.map(|e| (e.cycle, &mut e.input)) {
+            Some((cycle, PlaybackInput::Serial(data))) if cycle == self.cycles && !key => {
+                data.push(b)
+            }
+            Some((cycle, PlaybackInput::Key(data))) if cycle == self.cycles && key => data.push(b),
+            _ => {
+                let data = alloc::vec![b];
+                let input = if key {
+                    PlaybackInput::Key(data)
+                } else {
+                    PlaybackInput::Serial(data)
+                };
+                self.script.push(self.cycles, input);
+            }
+        }
+    }
+
+    /// Pushes the byte into the serial input buffer, recording it if there was space
+    pub fn push_input(&mut self, b: u8) -> bool {
+        if !self.serial.borrow_mut().push_input(b) {
+            return false;
+        }
+
+        self.record(b, false);
+        true
+    }
+
+    /// Pushes the key code into the keyboard device, recording it if there was space. Keys are
+    /// refused without a keyboard device
+    pub fn push_key(&mut self, key: u8) -> bool {
+        match &self.keyboard {
+            Some(keyboard) if keyboard.borrow_mut().push_key(key) => (),
+            _ => return false,
+        }
+
+        self.record(key, true);
+        true
+    }
+
+    /// Records an interrupt triggered by the host, which should only be recorded once the
+    /// interrupt has been called
+    pub fn record_irq(&mut self, irq: u32) {
+        self.script.push(self.cycles, PlaybackInput::Irq(irq));
+    }
+
+    /// Records the seed of a random sequence used by the host
+    pub fn record_seed(&mut self, seed: u64) {
+        self.script.set_seed(Some(seed));
+    }
+
+    /// Provides the input recorded so far
+    pub fn script(&self) -> &PlaybackScript {
+        &self.script
+    }
+}
+
+impl ProcessorDevice for InputRecordDevice {
+    fn on_step(&mut self, cycles: u32) -> Option<DeviceAction> {
+        self.cycles += cycles as u64;
+        None
+    }
+
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemorySegment;
+    use alloc::vec;
+
+    /// Ensure that scripts are parsed into ordered events, and that invalid lines are reported
+    #[test]
+    fn test_parse() {
+        let script = PlaybackScript::parse(
+            "# demo\n10 serial \"ab\\n\"\n\n10 serial 0x41 66\n200 serial \"\\\"\" 0\n",
+        )
+        .unwrap();
+        assert_eq!(
+            script.events(),
+            [
+                PlaybackEvent {
+                    cycle: 10,
+                    input: PlaybackInput::Serial(vec![b'a', b'b', b'\n']),
+                },
+                PlaybackEvent {
+                    cycle: 10,
+                    input: PlaybackInput::Serial(vec![0x41, 66]),
+                },
+                PlaybackEvent {
+                    cycle: 200,
+                    input: PlaybackInput::Serial(vec![b'"', 0]),
+                },
+            ]
+        );
+
+        assert!(matches!(
+            PlaybackScript::parse("x serial 1"),
+            Err(PlaybackError::InvalidCycle(1))
+        ));
+        assert!(matches!(
+            PlaybackScript::parse("5 serial 1\n4 serial 2"),
+            Err(PlaybackError::UnorderedEvent(2))
+        ));
+        assert!(matches!(
+            PlaybackScript::parse("5 mouse 1"),
+            Err(PlaybackError::UnknownEvent(1, _))
+        ));
+        for data in ["", "256", "\"abc", "\"\\q\""] {
+            assert!(matches!(
+                PlaybackScript::parse(&alloc::format!("5 serial {data}")),
+                Err(PlaybackError::InvalidData(1))
+            ));
+        }
+        assert!(matches!(
+            PlaybackScript::parse("5 serial \"\t\""),
+            Err(PlaybackError::Character(1, _))
+        ));
+
+        let script = PlaybackScript::parse("seed 0x10\n5 key 27 \"a\"\n6 irq 2").unwrap();
+        assert_eq!(script.seed(), Some(16));
+        assert_eq!(
+            script.events(),
+            [
+                PlaybackEvent {
+                    cycle: 5,
+                    input: PlaybackInput::Key(vec![27, b'a']),
+                },
+                PlaybackEvent {
+                    cycle: 6,
+                    input: PlaybackInput::Irq(2),
+                },
+            ]
+        );
+        for line in [
+            "seed",
+            "seed x",
+            "seed 1 2",
+            "5 irq",
+            "5 irq 0x100000000",
+            "5 key",
+        ] {
+            assert!(matches!(
+                PlaybackScript::parse(line),
+                Err(PlaybackError::InvalidData(1))
+            ));
+        }
+    }
+
+    /// Ensure that recorded events are preserved through the text format
+    #[test]
+    fn test_record() {
+        let mut script = PlaybackScript::default();
+        script.set_seed(Some(7));
+        assert!(script.push(10, PlaybackInput::Serial(b"run\n".to_vec())));
+        assert!(script.push(10, PlaybackInput::Serial(vec![b'"', 0xff, b'\\', 0])));
+        assert!(script.push(12, PlaybackInput::Key(vec![27])));
+        assert!(script.push(12, PlaybackInput::Irq(3)));
+        assert!(!script.push(5, PlaybackInput::Irq(1)));
+        assert!(!script.push(20, PlaybackInput::Serial(Vec::new())));
+
+        let text = script.to_text();
+        assert_eq!(
+            text,
+            "seed 7\n10 serial \"run\\n\"\n10 serial \"\\\"\" 255 \"\\\\\\0\"\n12 key 27\n12 irq 3\n"
+        );
+        assert_eq!(PlaybackScript::parse(&text).unwrap(), script);
+    }
+
+    /// Ensure that event data is provided once the cycle count is reached, and is held while
+    /// the serial input buffer is full
+    #[test]
+    fn test_playback() {
+        let serial = Rc::new(RefCell::new(SerialInputOutputDevice::new(2)));
+        let script = PlaybackScript::parse("3 serial 1 2 3\n4 serial 4").unwrap();
+        let mut dev = InputPlaybackDevice::new(script, serial.clone());
+
+        dev.on_step(2);
+        assert!(!serial.borrow().has_input());
+
+        dev.on_step(2);
+        assert!(!dev.is_finished());
+
+        let read = || {
+            let s = serial.borrow();
+            let size = s.get(2).unwrap();
+            (0..size).map(|_| s.get(3).unwrap()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(read(), [1, 2]);
+
+        dev.on_step(1);
+        assert_eq!(read(), [3, 4]);
+        assert!(dev.is_finished());
+    }
+
+    /// Ensure that key events are provided to the keyboard device, and that each irq event
+    /// calls its interrupt on a separate step
+    #[test]
+    fn test_playback_keys_irqs() {
+        let serial = Rc::new(RefCell::new(SerialInputOutputDevice::new(2)));
+        let keyboard = Rc::new(RefCell::new(KeyboardDevice::new(4)));
+        let script = PlaybackScript::parse("1 key 65 66\n1 irq 2\n1 irq 3").unwrap();
+        let mut dev = InputPlaybackDevice::new(script, serial).with_keyboard(keyboard.clone());
+
+        assert!(matches!(
+            dev.on_step(1),
+            Some(DeviceAction::CallInterrupt(2))
+        ));
+        assert!(!dev.is_finished());
+        assert!(matches!(
+            dev.on_step(1),
+            Some(DeviceAction::CallInterrupt(3))
+        ));
+        assert!(dev.on_step(1).is_none());
+        assert!(dev.is_finished());
+
+        // Both keys were provided, leaving room for two of the four buffered keys
+        assert!(keyboard.borrow_mut().push_key(1));
+        assert!(keyboard.borrow_mut().push_key(2));
+        assert!(!keyboard.borrow_mut().push_key(3));
+    }
+
+    /// Ensure that input recorded before the first instruction is provided before the first
+    /// instruction when played back
+    #[test]
+    fn test_playback_startup() {
+        let serial = Rc::new(RefCell::new(SerialInputOutputDevice::new(4)));
+        let keyboard = Rc::new(RefCell::new(KeyboardDevice::new(4)));
+        let mut rec = InputRecordDevice::new(serial.clone()).with_keyboard(keyboard.clone());
+        assert!(rec.push_key(65));
+        assert!(rec.push_input(1));
+        rec.on_step(2);
+        assert!(rec.push_input(2));
+
+        let script = rec.script().clone();
+        assert_eq!(script.events()[0].cycle, 0);
+
+        let serial = Rc::new(RefCell::new(SerialInputOutputDevice::new(4)));
+        let keyboard = Rc::new(RefCell::new(KeyboardDevice::new(2)));
+        let mut dev =
+            InputPlaybackDevice::new(script, serial.clone()).with_keyboard(keyboard.clone());
+
+        let read = || {
+            let s = serial.borrow();
+            let size = s.get(2).unwrap();
+            (0..size).map(|_| s.get(3).unwrap()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(read(), [1]);
+        assert!(keyboard.borrow_mut().push_key(1));
+        assert!(!keyboard.borrow_mut().push_key(2));
+
+        dev.on_step(2);
+        assert_eq!(read(), [2]);
+        assert!(dev.is_finished());
+    }
+
+    /// Ensure that input is recorded with the cycle count it was provided at, and that input
+    /// refused by a full buffer is not recorded
+    #[test]
+    fn test_record_device() {
+        let serial = Rc::new(RefCell::new(SerialInputOutputDevice::new(4)));
+        let keyboard = Rc::new(RefCell::new(KeyboardDevice::new(1)));
+        let mut dev = InputRecordDevice::new(serial.clone());
+
+        dev.on_step(3);
+        assert!(dev.push_input(1));
+        assert!(dev.push_input(2));
+        assert!(!dev.push_key(65));
+        dev.on_step(2);
+        assert!(dev.push_input(3));
+        assert!(dev.push_input(4));
+        assert!(!dev.push_input(5));
+
+        let mut dev = dev.with_keyboard(keyboard);
+        assert!(dev.push_key(65));
+        assert!(!dev.push_key(66));
+        dev.record_irq(2);
+        dev.record_seed(9);
+
+        assert_eq!(dev.script().seed(), Some(9));
+        assert_eq!(
+            dev.script().events(),
+            [
+                PlaybackEvent {
+                    cycle: 3,
+                    input: PlaybackInput::Serial(vec![1, 2]),
+                },
+                PlaybackEvent {
+                    cycle: 5,
+                    input: PlaybackInput::Serial(vec![3, 4]),
+                },
+                PlaybackEvent {
+                    cycle: 5,
+                    input: PlaybackInput::Key(vec![65]),
+                },
+                PlaybackEvent {
+                    cycle: 5,
+                    input: PlaybackInput::Irq(2),
+                },
+            ]
+        );
+    }
+}
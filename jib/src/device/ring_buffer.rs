@@ -0,0 +1,315 @@
+use alloc::{vec, vec::Vec};
+
+use super::{DEVICE_ID_SIZE, DEVICE_MEM_SIZE, ProcessorDevice};
+
+use crate::memory::{MemorySegment, MemorySegmentError};
+
+/// Provides a single ring buffer, with free-running head and tail counters
+struct Ring {
+    data: Vec<u8>,
+    head: u16,
+    tail: u16,
+}
+
+impl Ring {
+    fn new(capacity: usize) -> Self {
+        Self {
+            data: vec![0; capacity],
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Provides the number of bytes between the tail and head, limited to the buffer capacity
+    fn used(&self) -> usize {
+        (self.head.wrapping_sub(self.tail) as usize).min(self.capacity())
+    }
+
+    fn free(&self) -> usize {
+        self.capacity() - self.used()
+    }
+
+    fn index(&self, counter: u16, offset: usize) -> usize {
+        (counter as usize + offset) % self.capacity()
+    }
+
+    fn push(&mut self, data: &[u8]) -> usize {
+        let count = data.len().min(self.free());
+        for (i, val) in data.iter().take(count).enumerate() {
+            let ind = self.index(self.head, i);
+            self.data[ind] = *val;
+        }
+
+        self.head = self.head.wrapping_add(count as u16);
+        count
+    }
+
+    fn pop(&mut self, max: usize) -> Vec<u8> {
+        let count = max.min(self.used());
+        let vals = (0..count)
+            .map(|i| self.data[self.index(self.tail, i)])
+            .collect();
+
+        self.tail = self.tail.wrapping_add(count as u16);
+        vals
+    }
+
+    fn reset(&mut self) {
+        self.data.fill(0);
+        self.head = 0;
+        self.tail = 0;
+    }
+}
+
+/// Provides a memory-mapped pair of ring buffers for exchanging blocks of data between the host
+/// and the guest. The guest consumes host data by reading the data window, which starts at the
+/// receive tail, and then advancing the receive tail. The guest provides data by writing the
+/// data window, which starts at the transmit head, and then advancing the transmit head
+pub struct RingBufferDevice {
+    rx: Ring,
+    tx: Ring,
+}
+
+impl RingBufferDevice {
+    const OFFSET_CAPACITY: u32 = 2;
+    const OFFSET_RX_HEAD: u32 = 4;
+    const OFFSET_RX_TAIL: u32 = 6;
+    const OFFSET_TX_HEAD: u32 = 8;
+    const OFFSET_TX_TAIL: u32 = 10;
+    const OFFSET_STATUS: u32 = 12;
+    const OFFSET_WINDOW: u32 = 16;
+
+    const STATUS_RX_AVAILABLE: u8 = 1 << 0;
+    const STATUS_TX_FULL: u8 = 1 << 1;
+
+    pub const DEVICE_ID: u16 = 4;
+
+    /// Defines the maximum capacity of each ring buffer, such that the free-running 16-bit
+    /// head and tail counters remain unambiguous
+    pub const MAX_CAPACITY: usize = 0x8000;
+
+    /// Defines the number of bytes accessible within the data window
+    pub const WINDOW_SIZE: u32 = DEVICE_MEM_SIZE - Self::OFFSET_WINDOW;
+
+    /// Constructs a new ring buffer device, with the capacity clamped to the maximum capacity
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.clamp(1, Self::MAX_CAPACITY);
+        Self {
+            rx: Ring::new(capacity),
+            tx: Ring::new(capacity),
+        }
+    }
+
+    /// Pushes data for the guest to read, providing the number of bytes accepted
+    pub fn push_bytes(&mut self, data: &[u8]) -> usize {
+        self.rx.push(data)
+    }
+
+    /// Pops up to the maximum number of bytes provided by the guest
+    pub fn pop_bytes(&mut self, max: usize) -> Vec<u8> {
+        self.tx.pop(max)
+    }
+
+    /// Provides the number of bytes provided by the guest that are waiting to be read
+    pub fn available(&self) -> usize {
+        self.tx.used()
+    }
+
+    /// Provides the number of bytes that may currently be pushed for the guest
+    pub fn free(&self) -> usize {
+        self.rx.free()
+    }
+
+    fn status(&self) -> u8 {
+        let mut status = 0;
+        if self.rx.used() > 0 {
+            status |= Self::STATUS_RX_AVAILABLE;
+        }
+        if self.tx.free() == 0 {
+            status |= Self::STATUS_TX_FULL;
+        }
+        status
+    }
+
+    fn register_byte(val: u16, offset: u32) -> u8 {
+        val.to_be_bytes()[(offset % 2) as usize]
+    }
+
+    fn set_register_byte(val: &mut u16, offset: u32, data: u8) {
+        let mut bytes = val.to_be_bytes();
+        bytes[(offset % 2) as usize] = data;
+        *val = u16::from_be_bytes(bytes);
+    }
+}
+
+impl MemorySegment for RingBufferDevice {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            n if n < DEVICE_ID_SIZE => Ok(Self::DEVICE_ID.to_be_bytes()[offset as usize]),
+            n if (Self::OFFSET_CAPACITY..Self::OFFSET_RX_HEAD).contains(&n) => {
+                Ok(Self::register_byte(self.rx.capacity() as u16, n))
+            }
+            n if n < Self::OFFSET_RX_TAIL => Ok(Self::register_byte(self.rx.head, n)),
+            n if n < Self::OFFSET_TX_HEAD => Ok(Self::register_byte(self.rx.tail, n)),
+            n if n < Self::OFFSET_TX_TAIL => Ok(Self::register_byte(self.tx.head, n)),
+            n if n < Self::OFFSET_STATUS => Ok(Self::register_byte(self.tx.tail, n)),
+            Self::OFFSET_STATUS => Ok(self.status()),
+            n if (Self::OFFSET_WINDOW..DEVICE_MEM_SIZE).contains(&n) => {
+                let ind = self
+                    .rx
+                    .index(self.rx.tail, (n - Self::OFFSET_WINDOW) as usize);
+                Ok(self.rx.data[ind])
+            }
+            _ => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        match offset {
+            n if (Self::OFFSET_RX_TAIL..Self::OFFSET_TX_HEAD).contains(&n) => {
+                Self::set_register_byte(&mut self.rx.tail, n, data);
+                Ok(())
+            }
+            n if (Self::OFFSET_TX_HEAD..Self::OFFSET_TX_TAIL).contains(&n) => {
+                Self::set_register_byte(&mut self.tx.head, n, data);
+                Ok(())
+            }
+            n if (Self::OFFSET_WINDOW..DEVICE_MEM_SIZE).contains(&n) => {
+                let ind = self
+                    .tx
+                    .index(self.tx.head, (n - Self::OFFSET_WINDOW) as usize);
+                self.tx.data[ind] = data;
+                Ok(())
+            }
+            _ => Err(MemorySegmentError::InvalidMemoryWrite(offset, data)),
+        }
+    }
+
+    /// Resets the memory segment
+    fn reset(&mut self) {
+        self.rx.reset();
+        self.tx.reset();
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        DEVICE_MEM_SIZE
+    }
+
+    /// Provides the head and tail counters of each buffer, followed by the buffer contents
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        for r in [&self.rx, &self.tx] {
+            state.extend(r.head.to_be_bytes());
+            state.extend(r.tail.to_be_bytes());
+        }
+        state.extend(self.rx.data.iter());
+        state.extend(self.tx.data.iter());
+        state
+    }
+
+    /// Restores the buffer state, which must have the same capacity as the device
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let (counters, data) = state
+            .split_first_chunk::<8>()
+            .ok_or(MemorySegmentError::InvalidState)?;
+
+        if data.len() != self.rx.capacity() + self.tx.capacity() {
+            return Err(MemorySegmentError::InvalidState);
+        }
+
+        let counter = |i: usize| u16::from_be_bytes([counters[i], counters[i + 1]]);
+        self.rx.head = counter(0);
+        self.rx.tail = counter(2);
+        self.tx.head = counter(4);
+        self.tx.tail = counter(6);
+
+        let (rx, tx) = data.split_at(self.rx.capacity());
+        self.rx.data.copy_from_slice(rx);
+        self.tx.data.copy_from_slice(tx);
+
+        Ok(())
+    }
+}
+
+impl ProcessorDevice for RingBufferDevice {
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u16(dev: &RingBufferDevice, offset: u32) -> u16 {
+        u16::from_be_bytes([dev.get(offset).unwrap(), dev.get(offset + 1).unwrap()])
+    }
+
+    fn write_u16(dev: &mut RingBufferDevice, offset: u32, val: u16) {
+        for (i, b) in val.to_be_bytes().into_iter().enumerate() {
+            dev.set(offset + i as u32, b).unwrap();
+        }
+    }
+
+    /// Ensure that data pushed by the host is readable by the guest through the data window,
+    /// including when the data wraps around the end of the buffer
+    #[test]
+    fn test_host_to_guest() {
+        let mut dev = RingBufferDevice::new(8);
+        assert_eq!(read_u16(&dev, RingBufferDevice::OFFSET_CAPACITY), 8);
+
+        assert_eq!(dev.push_bytes(&[1, 2, 3, 4, 5, 6]), 6);
+        assert_eq!(read_u16(&dev, RingBufferDevice::OFFSET_RX_HEAD), 6);
+        write_u16(&mut dev, RingBufferDevice::OFFSET_RX_TAIL, 6);
+
+        assert_eq!(dev.push_bytes(&[7, 8, 9, 10, 11, 12, 13, 14, 15]), 8);
+        assert_eq!(dev.free(), 0);
+
+        let window = (0..4)
+            .map(|i| dev.get(RingBufferDevice::OFFSET_WINDOW + i).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(window, [7, 8, 9, 10]);
+        assert_eq!(
+            dev.get(RingBufferDevice::OFFSET_STATUS).unwrap(),
+            RingBufferDevice::STATUS_RX_AVAILABLE
+        );
+
+        write_u16(&mut dev, RingBufferDevice::OFFSET_RX_TAIL, 14);
+        assert_eq!(dev.get(RingBufferDevice::OFFSET_STATUS).unwrap(), 0);
+    }
+
+    /// Ensure that data written by the guest is only visible to the host once the head advances
+    #[test]
+    fn test_guest_to_host() {
+        let mut dev = RingBufferDevice::new(4);
+
+        for (i, v) in [10, 20, 30, 40].into_iter().enumerate() {
+            dev.set(RingBufferDevice::OFFSET_WINDOW + i as u32, v)
+                .unwrap();
+        }
+        assert_eq!(dev.available(), 0);
+
+        write_u16(&mut dev, RingBufferDevice::OFFSET_TX_HEAD, 4);
+        assert_eq!(
+            dev.get(RingBufferDevice::OFFSET_STATUS).unwrap(),
+            RingBufferDevice::STATUS_TX_FULL
+        );
+
+        assert_eq!(dev.pop_bytes(3), [10, 20, 30]);
+        assert_eq!(read_u16(&dev, RingBufferDevice::OFFSET_TX_TAIL), 3);
+
+        let state = dev.save_state();
+        assert_eq!(dev.pop_bytes(8), [40]);
+
+        dev.load_state(&state).unwrap();
+        assert_eq!(dev.pop_bytes(8), [40]);
+        assert!(dev.load_state(&state[1..]).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use alloc::collections::VecDeque;
+use alloc::{collections::VecDeque, vec::Vec};
 use core::cell::RefCell;
 
 use super::{DEVICE_ID_SIZE, DEVICE_MEM_SIZE, ProcessorDevice};
@@ -143,6 +143,35 @@ impl MemorySegment for SerialInputOutputDevice {
     fn len(&self) -> u32 {
         DEVICE_MEM_SIZE
     }
+
+    /// Provides the queued values, as the input queue length followed by the input and output queues
+    fn save_state(&self) -> Vec<u8> {
+        let input = self.input_queue.borrow();
+
+        let mut state = Vec::new();
+        state.extend((input.len() as u32).to_be_bytes());
+        state.extend(input.iter());
+        state.extend(self.output_queue.iter());
+        state
+    }
+
+    /// Restores the queued values
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let (len, queues) = state
+            .split_first_chunk::<4>()
+            .ok_or(MemorySegmentError::InvalidState)?;
+        let len = u32::from_be_bytes(*len) as usize;
+
+        if len > queues.len() {
+            return Err(MemorySegmentError::InvalidState);
+        }
+
+        let (input, output) = queues.split_at(len);
+        *self.input_queue.borrow_mut() = input.iter().copied().collect();
+        self.output_queue = output.iter().copied().collect();
+
+        Ok(())
+    }
 }
 
 impl ProcessorDevice for SerialInputOutputDevice {
@@ -150,3 +179,268 @@ impl ProcessorDevice for SerialInputOutputDevice {
         Self::DEVICE_ID
     }
 }
+
+/// Provides a change in the client connected to a serial TCP bridge
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialTcpEvent {
+    Connected(std::net::SocketAddr),
+    Disconnected(std::net::SocketAddr),
+}
+
+/// Bridges the queues of a serial device to a telnet-style TCP socket, such that external
+/// terminal programs may act as the serial console. A single client is served at a time, with
+/// further connections closed until the client disconnects. Received line endings are converted
+/// to newlines and telnet option negotiation is discarded, while output newlines are sent as
+/// line endings. Output is only taken from the device while a client is connected, such that
+/// the host may otherwise display it as usual
+#[cfg(feature = "std")]
+pub struct SerialTcpBridge {
+    listener: std::net::TcpListener,
+    client: Option<(std::net::TcpStream, std::net::SocketAddr)>,
+    input: VecDeque<u8>,
+    output: VecDeque<u8>,
+    telnet: TelnetState,
+    last_cr: bool,
+}
+
+/// Tracks the position within a telnet command received from the client
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TelnetState {
+    Data,
+    Command,
+    Option,
+    Subnegotiation,
+    SubnegotiationCommand,
+}
+
+#[cfg(feature = "std")]
+impl SerialTcpBridge {
+    const IAC: u8 = 0xFF;
+    const SE: u8 = 0xF0;
+    const SB: u8 = 0xFA;
+    const WILL: u8 = 0xFB;
+    const DONT: u8 = 0xFE;
+
+    const READ_SIZE: usize = 256;
+
+    /// Listens for clients on the provided address without blocking the caller
+    pub fn bind(addr: impl std::net::ToSocketAddrs) -> std::io::Result<Self> {
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            client: None,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            telnet: TelnetState::Data,
+            last_cr: false,
+        })
+    }
+
+    /// Provides the address the bridge is listening on
+    pub fn local_addr(&self) -> std::io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Provides the address of the connected client, if any
+    pub fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        self.client.as_ref().map(|(_, addr)| *addr)
+    }
+
+    /// Accepts waiting clients and moves data between the client and the serial device without
+    /// blocking, providing any changes to the connected client. Received data that doesn't fit
+    /// in the device input queue is kept until space is available
+    pub fn pump(&mut self, dev: &mut SerialInputOutputDevice) -> Vec<SerialTcpEvent> {
+        let mut events = Vec::new();
+
+        if let Some((mut stream, addr)) = self.client.take() {
+            if self.transfer(&mut stream, dev) {
+                self.client = Some((stream, addr));
+            } else {
+                events.push(SerialTcpEvent::Disconnected(addr));
+            }
+        }
+
+        while let Ok((stream, addr)) = self.listener.accept() {
+            if self.client.is_some() || stream.set_nonblocking(true).is_err() {
+                continue;
+            }
+
+            self.input.clear();
+            self.output.clear();
+            self.telnet = TelnetState::Data;
+            self.last_cr = false;
+            self.client = Some((stream, addr));
+            events.push(SerialTcpEvent::Connected(addr));
+        }
+
+        events
+    }
+
+    /// Moves data between the connected client and the device, providing false if the client
+    /// has disconnected
+    fn transfer(
+        &mut self,
+        stream: &mut std::net::TcpStream,
+        dev: &mut SerialInputOutputDevice,
+    ) -> bool {
+        use std::io::{ErrorKind, Read, Write};
+
+        if self.input.is_empty() {
+            let mut buf = [0; Self::READ_SIZE];
+            match stream.read(&mut buf) {
+                Ok(0) => return false,
+                Ok(n) => {
+                    for b in buf[..n].iter() {
+                        self.receive(*b);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(_) => return false,
+            }
+        }
+
+        while let Some(b) = self.input.front() {
+            if !dev.push_input(*b) {
+                break;
+            }
+            self.input.pop_front();
+        }
+
+        while let Some(w) = dev.pop_output() {
+            match crate::text::byte_to_character(w) {
+                Ok('\n') => self.output.extend(b"\r\n"),
+                Ok('\0') => (),
+                Ok(c) => self.output.push_back(c as u8),
+                Err(_) => self.output.push_back(b'?'),
+            }
+        }
+
+        while !self.output.is_empty() {
+            let (data, _) = self.output.as_slices();
+            match stream.write(data) {
+                Ok(0) => return false,
+                Ok(n) => drop(self.output.drain(..n)),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(_) => return false,
+            }
+        }
+
+        true
+    }
+
+    /// Processes a byte received from the client, queueing any resulting serial input
+    fn receive(&mut self, b: u8) {
+        self.telnet = match (self.telnet, b) {
+            (TelnetState::Data, Self::IAC) => TelnetState::Command,
+            (TelnetState::Data, _) => {
+                let last_cr = core::mem::replace(&mut self.last_cr, b == b'\r');
+                match b {
+                    b'\r' => self.input.push_back(b'\n'),
+                    b'\n' | b'\0' if last_cr => (),
+                    _ => {
+                        if let Ok(w) = crate::text::character_to_byte(b as char) {
+                            self.input.push_back(w);
+                        }
+                    }
+                }
+                TelnetState::Data
+            }
+            (TelnetState::Command, Self::SB) => TelnetState::Subnegotiation,
+            (TelnetState::Command, Self::WILL..=Self::DONT) => TelnetState::Option,
+            (TelnetState::Command | TelnetState::Option, _) => TelnetState::Data,
+            (TelnetState::Subnegotiation, Self::IAC) => TelnetState::SubnegotiationCommand,
+            (TelnetState::Subnegotiation, _) => TelnetState::Subnegotiation,
+            (TelnetState::SubnegotiationCommand, Self::SE) => TelnetState::Data,
+            (TelnetState::SubnegotiationCommand, _) => TelnetState::Subnegotiation,
+        };
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    /// Ensure that a TCP client receives device output and provides device input, with line
+    /// endings converted and telnet negotiation discarded
+    #[test]
+    fn test_tcp_bridge() {
+        use std::io::{Read, Write};
+        use std::time::Duration;
+
+        let mut dev = SerialInputOutputDevice::new(16);
+        let mut bridge = SerialTcpBridge::bind("127.0.0.1:0").unwrap();
+        let addr = bridge.local_addr().unwrap();
+
+        let pump = |bridge: &mut SerialTcpBridge, dev: &mut SerialInputOutputDevice| {
+            for _ in 0..100 {
+                let events = bridge.pump(dev);
+                if !events.is_empty() {
+                    return events;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Vec::new()
+        };
+
+        dev.set(SerialInputOutputDevice::OFFSET_OUTPUT_SET, b'a')
+            .unwrap();
+        assert!(bridge.pump(&mut dev).is_empty());
+        assert!(dev.has_output());
+
+        let mut client = std::net::TcpStream::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let events = pump(&mut bridge, &mut dev);
+        assert_eq!(
+            events,
+            [SerialTcpEvent::Connected(client.local_addr().unwrap())]
+        );
+        assert_eq!(bridge.peer_addr(), Some(client.local_addr().unwrap()));
+
+        let second = std::net::TcpStream::connect(addr).unwrap();
+        assert!(pump(&mut bridge, &mut dev).is_empty());
+        drop(second);
+
+        dev.set(SerialInputOutputDevice::OFFSET_OUTPUT_SET, b'\n')
+            .unwrap();
+        bridge.pump(&mut dev);
+        let mut buf = [0; 3];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"a\r\n");
+
+        client
+            .write_all(&[
+                b'h', 0xFF, 0xFD, 0x01, b'i', b'\r', b'\n', 0xFF, 0xFA, 0x18, 0x01,
+            ])
+            .unwrap();
+        client.write_all(&[0xFF, 0xF0, b'!', b'\r', 0x00]).unwrap();
+
+        let mut input = Vec::new();
+        for _ in 0..100 {
+            bridge.pump(&mut dev);
+            while dev.has_input() {
+                input.push(dev.get(SerialInputOutputDevice::OFFSET_INPUT_GET).unwrap());
+            }
+            if input.len() >= 5 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(input, b"hi\n!\n");
+
+        let peer = client.local_addr().unwrap();
+        drop(client);
+        assert_eq!(
+            pump(&mut bridge, &mut dev),
+            [SerialTcpEvent::Disconnected(peer)]
+        );
+        assert!(bridge.peer_addr().is_none());
+    }
+}
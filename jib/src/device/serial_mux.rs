@@ -0,0 +1,290 @@
+use alloc::{collections::VecDeque, vec::Vec};
+use core::cell::RefCell;
+
+use super::{DEVICE_ID_SIZE, DEVICE_MEM_SIZE, ProcessorDevice};
+
+use crate::memory::{MemorySegment, MemorySegmentError};
+
+/// Provides the input and output queues of a single logical serial channel
+#[derive(Default)]
+struct SerialChannel {
+    input: VecDeque<u8>,
+    output: VecDeque<u8>,
+}
+
+/// Provides a memory-mapped serial multiplexer, carrying several logical serial channels over a
+/// single device. The guest selects a channel, after which the queue registers act upon the
+/// queues of the selected channel in the same manner as the serial I/O device. A mask of the
+/// channels with input waiting allows the guest to find channels to service without selecting
+/// each in turn
+pub struct SerialMuxDevice {
+    channels: RefCell<Vec<SerialChannel>>,
+    selected: u8,
+    buffer_size: usize,
+}
+
+impl SerialMuxDevice {
+    const OFFSET_CHANNEL_COUNT: u32 = 2;
+    const OFFSET_SELECT: u32 = 3;
+    const OFFSET_INPUT_SIZE: u32 = 4;
+    const OFFSET_INPUT_GET: u32 = 5;
+    const OFFSET_OUTPUT_SIZE: u32 = 6;
+    const OFFSET_OUTPUT_SET: u32 = 7;
+    const OFFSET_RESET_IN: u32 = 8;
+    const OFFSET_RESET_OUT: u32 = 9;
+    const OFFSET_INPUT_MASK: u32 = 10;
+    const OFFSET_INPUT_MASK_END: u32 = 12;
+
+    pub const DEVICE_ID: u16 = 13;
+
+    pub const MAX_CHANNELS: u8 = 16;
+
+    /// Constructs a new multiplexer with the provided number of channels, limited to the
+    /// maximum supported, where each queue of each channel holds up to the buffer size
+    pub fn new(channel_count: u8, buffer_size: usize) -> Self {
+        let count = channel_count.clamp(1, Self::MAX_CHANNELS);
+        Self {
+            channels: RefCell::new((0..count).map(|_| SerialChannel::default()).collect()),
+            selected: 0,
+            buffer_size,
+        }
+    }
+
+    pub fn channel_count(&self) -> u8 {
+        self.channels.borrow().len() as u8
+    }
+
+    /// Pushes the input value into the input queue of the channel, returning false if the
+    /// channel doesn't exist or the queue is full
+    pub fn push_input(&mut self, channel: u8, val: u8) -> bool {
+        match self.channels.get_mut().get_mut(channel as usize) {
+            Some(c) if c.input.len() < self.buffer_size => {
+                c.input.push_back(val);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Pops the next output value from the output queue of the channel
+    pub fn pop_output(&mut self, channel: u8) -> Option<u8> {
+        self.channels
+            .get_mut()
+            .get_mut(channel as usize)
+            .and_then(|c| c.output.pop_front())
+    }
+
+    /// Determines if there is output in the queue of the channel
+    pub fn has_output(&self, channel: u8) -> bool {
+        self.channels
+            .borrow()
+            .get(channel as usize)
+            .is_some_and(|c| !c.output.is_empty())
+    }
+
+    /// Provides the mask of channels with input waiting
+    fn input_mask(channels: &[SerialChannel]) -> u16 {
+        channels
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.input.is_empty())
+            .fold(0, |mask, (i, _)| mask | (1 << i))
+    }
+
+    fn common_get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        let channels = self.channels.borrow();
+        let channel = &channels[self.selected as usize];
+
+        match offset {
+            n if n < DEVICE_ID_SIZE => Ok(Self::DEVICE_ID.to_be_bytes()[n as usize]),
+            Self::OFFSET_CHANNEL_COUNT => Ok(channels.len() as u8),
+            Self::OFFSET_SELECT => Ok(self.selected),
+            Self::OFFSET_INPUT_SIZE => Ok(channel.input.len().min(u8::MAX as usize) as u8),
+            Self::OFFSET_OUTPUT_SIZE => Ok(channel.output.len().min(u8::MAX as usize) as u8),
+            Self::OFFSET_OUTPUT_SET => Ok(0),
+            n if (Self::OFFSET_INPUT_MASK..Self::OFFSET_INPUT_MASK_END).contains(&n) => {
+                Ok(Self::input_mask(&channels).to_be_bytes()
+                    [(n - Self::OFFSET_INPUT_MASK) as usize])
+            }
+            _ => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+        }
+    }
+}
+
+impl MemorySegment for SerialMuxDevice {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            Self::OFFSET_INPUT_GET => Ok(self.channels.borrow_mut()[self.selected as usize]
+                .input
+                .pop_front()
+                .unwrap_or(0)),
+            _ => self.common_get(offset),
+        }
+    }
+
+    /// Provides the word at the requested memory location without affecting the device state
+    fn inspect(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            Self::OFFSET_INPUT_GET => Ok(self.channels.borrow()[self.selected as usize]
+                .input
+                .front()
+                .copied()
+                .unwrap_or(0)),
+            _ => self.common_get(offset),
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        let count = self.channel_count();
+        let buffer_size = self.buffer_size;
+        let channel = &mut self.channels.get_mut()[self.selected as usize];
+
+        match offset {
+            Self::OFFSET_SELECT if data < count => self.selected = data,
+            Self::OFFSET_OUTPUT_SET if channel.output.len() < buffer_size => {
+                channel.output.push_back(data)
+            }
+            Self::OFFSET_RESET_IN => {
+                if data != 0 {
+                    channel.input.clear();
+                }
+            }
+            Self::OFFSET_RESET_OUT => {
+                if data != 0 {
+                    channel.output.clear();
+                }
+            }
+            _ => return Err(MemorySegmentError::InvalidMemoryWrite(offset, data)),
+        }
+
+        Ok(())
+    }
+
+    /// Resets the memory segment, clearing the queues of every channel
+    fn reset(&mut self) {
+        for c in self.channels.get_mut().iter_mut() {
+            c.input.clear();
+            c.output.clear();
+        }
+        self.selected = 0;
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        DEVICE_MEM_SIZE
+    }
+
+    /// Provides the selected channel, followed by the length and values of the input and
+    /// output queues of each channel in turn
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::from([self.selected]);
+        for c in self.channels.borrow().iter() {
+            for queue in [&c.input, &c.output] {
+                state.extend((queue.len() as u32).to_be_bytes());
+                state.extend(queue.iter());
+            }
+        }
+        state
+    }
+
+    /// Restores the selected channel and the queues of each channel
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let (selected, mut rest) = state
+            .split_first()
+            .ok_or(MemorySegmentError::InvalidState)?;
+
+        let mut channels = Vec::new();
+        for _ in 0..self.channel_count() {
+            let mut channel = SerialChannel::default();
+            for queue in [&mut channel.input, &mut channel.output] {
+                let (len, data) = rest
+                    .split_first_chunk::<4>()
+                    .ok_or(MemorySegmentError::InvalidState)?;
+                let len = u32::from_be_bytes(*len) as usize;
+                if len > data.len() {
+                    return Err(MemorySegmentError::InvalidState);
+                }
+
+                let (values, remaining) = data.split_at(len);
+                *queue = values.iter().copied().collect();
+                rest = remaining;
+            }
+            channels.push(channel);
+        }
+
+        if !rest.is_empty() || *selected as usize >= channels.len() {
+            return Err(MemorySegmentError::InvalidState);
+        }
+
+        *self.channels.get_mut() = channels;
+        self.selected = *selected;
+
+        Ok(())
+    }
+}
+
+impl ProcessorDevice for SerialMuxDevice {
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensure that the queue registers act upon the selected channel, and that the input mask
+    /// reports each channel with input waiting
+    #[test]
+    fn test_channels() {
+        let mut dev = SerialMuxDevice::new(3, 4);
+        assert_eq!(dev.get(SerialMuxDevice::OFFSET_CHANNEL_COUNT).unwrap(), 3);
+
+        assert!(dev.push_input(0, b'a'));
+        assert!(dev.push_input(2, b'b'));
+        assert!(dev.push_input(2, b'c'));
+        assert!(!dev.push_input(3, b'd'));
+        assert_eq!(
+            dev.get(SerialMuxDevice::OFFSET_INPUT_MASK + 1).unwrap(),
+            0b101
+        );
+
+        dev.set(SerialMuxDevice::OFFSET_SELECT, 2).unwrap();
+        assert!(dev.set(SerialMuxDevice::OFFSET_SELECT, 3).is_err());
+        assert_eq!(dev.get(SerialMuxDevice::OFFSET_SELECT).unwrap(), 2);
+        assert_eq!(dev.get(SerialMuxDevice::OFFSET_INPUT_SIZE).unwrap(), 2);
+        assert_eq!(
+            dev.inspect(SerialMuxDevice::OFFSET_INPUT_GET).unwrap(),
+            b'b'
+        );
+        assert_eq!(dev.get(SerialMuxDevice::OFFSET_INPUT_GET).unwrap(), b'b');
+        assert_eq!(dev.get(SerialMuxDevice::OFFSET_INPUT_GET).unwrap(), b'c');
+        assert_eq!(dev.get(SerialMuxDevice::OFFSET_INPUT_GET).unwrap(), 0);
+        assert_eq!(
+            dev.get(SerialMuxDevice::OFFSET_INPUT_MASK + 1).unwrap(),
+            0b001
+        );
+
+        for b in b"xyzw" {
+            dev.set(SerialMuxDevice::OFFSET_OUTPUT_SET, *b).unwrap();
+        }
+        assert!(dev.set(SerialMuxDevice::OFFSET_OUTPUT_SET, b'!').is_err());
+        assert!(dev.has_output(2));
+        assert!(!dev.has_output(0));
+
+        let state = dev.save_state();
+        assert_eq!(dev.pop_output(2), Some(b'x'));
+        assert_eq!(dev.pop_output(0), None);
+
+        dev.load_state(&state).unwrap();
+        assert_eq!(dev.pop_output(2), Some(b'x'));
+        assert!(dev.load_state(&state[..state.len() - 1]).is_err());
+
+        dev.reset();
+        assert_eq!(dev.get(SerialMuxDevice::OFFSET_SELECT).unwrap(), 0);
+        assert_eq!(dev.get(SerialMuxDevice::OFFSET_INPUT_MASK + 1).unwrap(), 0);
+        assert!(!dev.has_output(2));
+    }
+}
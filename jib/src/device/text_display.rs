@@ -0,0 +1,504 @@
+use alloc::{string::String, vec, vec::Vec};
+
+use super::{DEVICE_ID_SIZE, DEVICE_MEM_SIZE, DeviceAction, ProcessorDevice};
+
+use crate::memory::{block_range, range_offset, MemorySegment, MemorySegmentError};
+
+/// Provides a single character cell of the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayCell {
+    pub character: u8,
+    pub attribute: u8,
+}
+
+impl DisplayCell {
+    /// Provides the palette index of the foreground color, in the lower nibble of the attribute
+    pub fn foreground(&self) -> u8 {
+        self.attribute & 0xF
+    }
+
+    /// Provides the palette index of the background color, in the upper nibble of the attribute
+    pub fn background(&self) -> u8 {
+        self.attribute >> 4
+    }
+
+    /// Provides the character to show for the cell, where unmapped bytes are shown as blank
+    pub fn to_char(&self) -> char {
+        match crate::text::byte_to_character(self.character) {
+            Ok(c) if !c.is_control() => c,
+            _ => ' ',
+        }
+    }
+}
+
+impl Default for DisplayCell {
+    fn default() -> Self {
+        Self {
+            character: 0,
+            attribute: TextDisplayDevice::DEFAULT_ATTRIBUTE,
+        }
+    }
+}
+
+/// Provides a copy of the display contents, to be rendered by the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayScreen {
+    pub columns: usize,
+    pub rows: usize,
+    pub cells: Vec<DisplayCell>,
+}
+
+impl DisplayScreen {
+    /// Provides the cells of the requested row
+    pub fn row(&self, row: usize) -> &[DisplayCell] {
+        &self.cells[row * self.columns..(row + 1) * self.columns]
+    }
+
+    /// Provides the text of each row, ignoring attributes
+    pub fn lines(&self) -> Vec<String> {
+        (0..self.rows)
+            .map(|r| self.row(r).iter().map(|c| c.to_char()).collect())
+            .collect()
+    }
+}
+
+/// Provides a memory-mapped character display. Each cell of the screen is a character byte
+/// followed by an attribute byte, stored in row-major order after the device registers. The
+/// attribute provides the foreground palette index in the lower nibble and the background
+/// palette index in the upper nibble.
+///
+/// The guest may enable double buffering with the control register, after which the cell
+/// memory provides the back buffer and the host only shows the front buffer. Requesting a flip
+/// swaps the buffers at the next vertical sync, which occurs once per frame period of processor
+/// cycles, such that the host never shows a partially-drawn frame
+pub struct TextDisplayDevice {
+    columns: u8,
+    rows: u8,
+    cells: Vec<DisplayCell>,
+    front: Vec<DisplayCell>,
+    double_buffered: bool,
+    flip_pending: bool,
+    frame_cycles: u32,
+    cycle_count: u32,
+    changed: bool,
+}
+
+impl TextDisplayDevice {
+    const OFFSET_COLUMNS: u32 = 2;
+    const OFFSET_ROWS: u32 = 3;
+    const OFFSET_CLEAR: u32 = 4;
+    const OFFSET_CONTROL: u32 = 5;
+    const OFFSET_CELLS: u32 = DEVICE_MEM_SIZE;
+
+    pub const DEVICE_ID: u16 = 8;
+
+    pub const DEFAULT_COLUMNS: u8 = 80;
+    pub const DEFAULT_ROWS: u8 = 25;
+
+    /// Defines the attribute of a cleared cell, as light grey on black
+    pub const DEFAULT_ATTRIBUTE: u8 = 0x07;
+
+    /// Defines the number of processor cycles between each vertical sync
+    pub const DEFAULT_FRAME_CYCLES: u32 = 10_000;
+
+    /// Enables double buffering when set in the control register
+    pub const CONTROL_DOUBLE_BUFFER: u8 = 1 << 0;
+    /// Requests a flip at the next vertical sync when written to the control register, and
+    /// reads as set until the flip occurs
+    pub const CONTROL_FLIP: u8 = 1 << 1;
+
+    /// Defines the red, green, and blue values of each palette index
+    pub const PALETTE: [(u8, u8, u8); 16] = [
+        (0x00, 0x00, 0x00),
+        (0x00, 0x00, 0xAA),
+        (0x00, 0xAA, 0x00),
+        (0x00, 0xAA, 0xAA),
+        (0xAA, 0x00, 0x00),
+        (0xAA, 0x00, 0xAA),
+        (0xAA, 0x55, 0x00),
+        (0xAA, 0xAA, 0xAA),
+        (0x55, 0x55, 0x55),
+        (0x55, 0x55, 0xFF),
+        (0x55, 0xFF, 0x55),
+        (0x55, 0xFF, 0xFF),
+        (0xFF, 0x55, 0x55),
+        (0xFF, 0x55, 0xFF),
+        (0xFF, 0xFF, 0x55),
+        (0xFF, 0xFF, 0xFF),
+    ];
+
+    /// Constructs a new display with the provided number of columns and rows
+    pub fn new(columns: u8, rows: u8) -> Self {
+        let cells = vec![DisplayCell::default(); columns as usize * rows as usize];
+        Self {
+            columns,
+            rows,
+            front: cells.clone(),
+            cells,
+            double_buffered: false,
+            flip_pending: false,
+            frame_cycles: Self::DEFAULT_FRAME_CYCLES,
+            cycle_count: 0,
+            changed: true,
+        }
+    }
+
+    /// Sets the number of processor cycles between each vertical sync
+    pub fn set_frame_cycles(&mut self, cycles: u32) {
+        self.frame_cycles = cycles.max(1);
+    }
+
+    pub fn frame_cycles(&self) -> u32 {
+        self.frame_cycles
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns as usize
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows as usize
+    }
+
+    /// Provides the cells shown by the display, being the front buffer if double buffered
+    fn shown(&self) -> &[DisplayCell] {
+        if self.double_buffered {
+            &self.front
+        } else {
+            &self.cells
+        }
+    }
+
+    /// Provides the shown cell at the requested column and row, if within the display
+    pub fn cell(&self, column: usize, row: usize) -> Option<DisplayCell> {
+        if column < self.columns() && row < self.rows() {
+            Some(self.shown()[row * self.columns() + column])
+        } else {
+            None
+        }
+    }
+
+    /// Provides a copy of the shown display contents, only including completed frames when
+    /// double buffered
+    pub fn screen(&self) -> DisplayScreen {
+        DisplayScreen {
+            columns: self.columns(),
+            rows: self.rows(),
+            cells: self.shown().to_vec(),
+        }
+    }
+
+    fn control(&self) -> u8 {
+        let mut control = 0;
+        if self.double_buffered {
+            control |= Self::CONTROL_DOUBLE_BUFFER;
+        }
+        if self.flip_pending {
+            control |= Self::CONTROL_FLIP;
+        }
+        control
+    }
+
+    fn set_control(&mut self, control: u8) {
+        let double_buffered = control & Self::CONTROL_DOUBLE_BUFFER != 0;
+        if double_buffered != self.double_buffered {
+            // Copy the shown frame into the other buffer, such that the shown frame doesn't change
+            if double_buffered {
+                self.front.copy_from_slice(&self.cells);
+            } else {
+                self.cells.copy_from_slice(&self.front);
+            }
+            self.double_buffered = double_buffered;
+        }
+
+        self.flip_pending = double_buffered && control & Self::CONTROL_FLIP != 0;
+    }
+
+    /// Swaps the front and back buffers if a flip is pending
+    fn vertical_sync(&mut self) {
+        if self.flip_pending {
+            core::mem::swap(&mut self.cells, &mut self.front);
+            self.flip_pending = false;
+            self.changed = true;
+        }
+    }
+
+    /// Determines if the display contents have changed since the last call, allowing the host
+    /// to only render the display when required
+    pub fn take_changed(&mut self) -> bool {
+        core::mem::take(&mut self.changed)
+    }
+
+    /// Clears the cells written by the guest, being the back buffer if double buffered
+    fn clear(&mut self, attribute: u8) {
+        self.cells.fill(DisplayCell {
+            character: 0,
+            attribute,
+        });
+        self.changed |= !self.double_buffered;
+    }
+}
+
+impl MemorySegment for TextDisplayDevice {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            n if n < DEVICE_ID_SIZE => Ok(Self::DEVICE_ID.to_be_bytes()[n as usize]),
+            Self::OFFSET_COLUMNS => Ok(self.columns),
+            Self::OFFSET_ROWS => Ok(self.rows),
+            Self::OFFSET_CLEAR => Ok(0),
+            Self::OFFSET_CONTROL => Ok(self.control()),
+            n if n >= Self::OFFSET_CELLS && n < self.len() => {
+                let ind = (n - Self::OFFSET_CELLS) as usize;
+                let cell = &self.cells[ind / 2];
+                Ok(match ind % 2 {
+                    0 => cell.character,
+                    _ => cell.attribute,
+                })
+            }
+            _ => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        match offset {
+            Self::OFFSET_CLEAR => self.clear(data),
+            Self::OFFSET_CONTROL => self.set_control(data),
+            n if n >= Self::OFFSET_CELLS && n < self.len() => {
+                let ind = (n - Self::OFFSET_CELLS) as usize;
+                let cell = &mut self.cells[ind / 2];
+                match ind % 2 {
+                    0 => cell.character = data,
+                    _ => cell.attribute = data,
+                }
+                self.changed |= !self.double_buffered;
+            }
+            _ => return Err(MemorySegmentError::InvalidMemoryWrite(offset, data)),
+        }
+
+        Ok(())
+    }
+
+    /// Provides the values starting at the requested memory location, reading the cells
+    /// directly when the range is within the cell memory
+    fn get_range(&self, offset: u32, buf: &mut [u8]) -> Result<(), MemorySegmentError> {
+        match block_range(offset, buf.len(), Self::OFFSET_CELLS..self.len()) {
+            Some(range) => {
+                for (v, ind) in buf.iter_mut().zip(range) {
+                    let cell = &self.cells[ind / 2];
+                    *v = match ind % 2 {
+                        0 => cell.character,
+                        _ => cell.attribute,
+                    };
+                }
+            }
+            None => {
+                for (i, v) in buf.iter_mut().enumerate() {
+                    *v = self.get(range_offset(offset, i)?)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the values starting at the requested memory location, writing the cells directly
+    /// when the range is within the cell memory
+    fn set_range(&mut self, offset: u32, data: &[u8]) -> Result<(), MemorySegmentError> {
+        match block_range(offset, data.len(), Self::OFFSET_CELLS..self.len()) {
+            Some(range) => {
+                for (v, ind) in data.iter().zip(range) {
+                    let cell = &mut self.cells[ind / 2];
+                    match ind % 2 {
+                        0 => cell.character = *v,
+                        _ => cell.attribute = *v,
+                    }
+                }
+                self.changed |= !self.double_buffered && !data.is_empty();
+            }
+            None => {
+                for (i, v) in data.iter().enumerate() {
+                    self.set(range_offset(offset, i)?, *v)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resets the memory segment, returning to a single buffer
+    fn reset(&mut self) {
+        self.double_buffered = false;
+        self.flip_pending = false;
+        self.cycle_count = 0;
+        self.clear(Self::DEFAULT_ATTRIBUTE);
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        Self::OFFSET_CELLS + 2 * self.cells.len() as u32
+    }
+
+    /// Provides the control register and the cycles since the last vertical sync, followed by
+    /// the character and attribute of each cell in the back and then the front buffer
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![self.control()];
+        state.extend(self.cycle_count.to_be_bytes());
+        state.extend(
+            self.cells
+                .iter()
+                .chain(self.front.iter())
+                .flat_map(|c| [c.character, c.attribute]),
+        );
+        state
+    }
+
+    /// Restores the control register, vertical sync timing, and the cells of each buffer
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let ([control, c0, c1, c2, c3], cells) = state
+            .split_first_chunk::<5>()
+            .ok_or(MemorySegmentError::InvalidState)?;
+
+        if cells.len() != 4 * self.cells.len() {
+            return Err(MemorySegmentError::InvalidState);
+        }
+
+        for (cell, vals) in self
+            .cells
+            .iter_mut()
+            .chain(self.front.iter_mut())
+            .zip(cells.chunks_exact(2))
+        {
+            cell.character = vals[0];
+            cell.attribute = vals[1];
+        }
+
+        self.double_buffered = control & Self::CONTROL_DOUBLE_BUFFER != 0;
+        self.flip_pending = control & Self::CONTROL_FLIP != 0;
+        self.cycle_count = u32::from_be_bytes([*c0, *c1, *c2, *c3]);
+        self.changed = true;
+
+        Ok(())
+    }
+}
+
+impl ProcessorDevice for TextDisplayDevice {
+    fn on_step(&mut self, cycles: u32) -> Option<DeviceAction> {
+        self.cycle_count = self.cycle_count.saturating_add(cycles);
+        if self.cycle_count >= self.frame_cycles {
+            self.cycle_count %= self.frame_cycles;
+            self.vertical_sync();
+        }
+
+        None
+    }
+
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensure that writes to the cell memory set the characters and attributes of the screen
+    #[test]
+    fn test_display_cells() {
+        let mut dev = TextDisplayDevice::new(4, 2);
+        assert_eq!(dev.len(), DEVICE_MEM_SIZE + 16);
+        assert_eq!(dev.get(TextDisplayDevice::OFFSET_COLUMNS).unwrap(), 4);
+        assert_eq!(dev.get(TextDisplayDevice::OFFSET_ROWS).unwrap(), 2);
+        assert!(dev.take_changed());
+        assert!(!dev.take_changed());
+
+        let base = TextDisplayDevice::OFFSET_CELLS + 2 * 5;
+        dev.set(base, b'h').unwrap();
+        dev.set(base + 1, 0x1E).unwrap();
+        dev.set(base + 2, b'i').unwrap();
+        assert!(dev.take_changed());
+        assert_eq!(dev.get(base).unwrap(), b'h');
+
+        let cell = dev.cell(1, 1).unwrap();
+        assert_eq!(cell.foreground(), 0xE);
+        assert_eq!(cell.background(), 0x1);
+        assert!(dev.cell(4, 1).is_none());
+        assert_eq!(dev.screen().lines(), ["    ", " hi "]);
+
+        let state = dev.save_state();
+        dev.set(TextDisplayDevice::OFFSET_CLEAR, 0x70).unwrap();
+        assert_eq!(dev.screen().lines()[1], "    ");
+        assert_eq!(dev.cell(0, 0).unwrap().attribute, 0x70);
+
+        dev.load_state(&state).unwrap();
+        assert_eq!(dev.cell(2, 1).unwrap().character, b'i');
+        assert!(dev.set(dev.len(), 0).is_err());
+        assert!(dev.set(TextDisplayDevice::OFFSET_ROWS, 0).is_err());
+    }
+
+    /// Ensure that a double-buffered display only shows completed frames, flipping at the
+    /// vertical sync after the flip is requested
+    #[test]
+    fn test_double_buffer() {
+        let mut dev = TextDisplayDevice::new(2, 1);
+        dev.set_frame_cycles(10);
+        let base = TextDisplayDevice::OFFSET_CELLS;
+
+        dev.set(base, b'a').unwrap();
+        dev.set(
+            TextDisplayDevice::OFFSET_CONTROL,
+            TextDisplayDevice::CONTROL_DOUBLE_BUFFER,
+        )
+        .unwrap();
+        assert!(dev.take_changed());
+
+        dev.set(base, b'b').unwrap();
+        dev.set(base + 2, b'c').unwrap();
+        assert!(!dev.take_changed());
+        assert_eq!(dev.screen().lines(), ["a "]);
+        assert_eq!(dev.get(base).unwrap(), b'b');
+
+        dev.set(
+            TextDisplayDevice::OFFSET_CONTROL,
+            TextDisplayDevice::CONTROL_DOUBLE_BUFFER | TextDisplayDevice::CONTROL_FLIP,
+        )
+        .unwrap();
+        dev.on_step(5);
+        assert_eq!(dev.screen().lines(), ["a "]);
+        assert_ne!(
+            dev.get(TextDisplayDevice::OFFSET_CONTROL).unwrap() & TextDisplayDevice::CONTROL_FLIP,
+            0
+        );
+
+        let state = dev.save_state();
+        dev.on_step(5);
+        assert!(dev.take_changed());
+        assert_eq!(dev.screen().lines(), ["bc"]);
+        assert_eq!(dev.get(base).unwrap(), b'a');
+        assert_eq!(
+            dev.get(TextDisplayDevice::OFFSET_CONTROL).unwrap(),
+            TextDisplayDevice::CONTROL_DOUBLE_BUFFER
+        );
+
+        dev.load_state(&state).unwrap();
+        assert_eq!(dev.screen().lines(), ["a "]);
+        dev.on_step(5);
+        assert_eq!(dev.screen().lines(), ["bc"]);
+        assert!(dev.take_changed());
+
+        // Disabling double buffering keeps the shown frame, discarding the partial back buffer
+        dev.set(base, b'x').unwrap();
+        dev.set(TextDisplayDevice::OFFSET_CONTROL, 0).unwrap();
+        assert_eq!(dev.screen().lines(), ["bc"]);
+        assert_eq!(dev.get(base).unwrap(), b'b');
+        assert!(!dev.take_changed());
+
+        dev.set(base, b'd').unwrap();
+        assert!(dev.take_changed());
+        assert_eq!(dev.screen().lines(), ["dc"]);
+
+        dev.reset();
+        assert_eq!(dev.get(TextDisplayDevice::OFFSET_CONTROL).unwrap(), 0);
+    }
+}
@@ -0,0 +1,404 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{DEVICE_ID_SIZE, DEVICE_MEM_SIZE, ProcessorDevice};
+
+use crate::cpu::{ProcessorError, Register, RegisterManager};
+use crate::memory::{MemoryError, MemorySegment, MemorySegmentError};
+
+/// Provides the cause of a fault reported in a trap-info block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapCause {
+    Memory,
+    StackUnderflow,
+    UnknownInstruction,
+    UnsupportedDataType,
+    Arithmetic,
+    OpcodeAlignment,
+    UnsupportedInterrupt,
+    Device,
+    UnknownExtension,
+    Protection,
+    Register,
+    StackOverflow,
+}
+
+impl TrapCause {
+    /// Provides the cause code reported by the cause register
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Memory => 1,
+            Self::StackUnderflow => 2,
+            Self::UnknownInstruction => 3,
+            Self::UnsupportedDataType => 4,
+            Self::Arithmetic => 5,
+            Self::OpcodeAlignment => 6,
+            Self::UnsupportedInterrupt => 7,
+            Self::Device => 8,
+            Self::UnknownExtension => 9,
+            Self::Protection => 10,
+            Self::Register => 11,
+            Self::StackOverflow => 12,
+        }
+    }
+
+    /// Provides the cause with the provided code, if valid
+    pub fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            1 => Self::Memory,
+            2 => Self::StackUnderflow,
+            3 => Self::UnknownInstruction,
+            4 => Self::UnsupportedDataType,
+            5 => Self::Arithmetic,
+            6 => Self::OpcodeAlignment,
+            7 => Self::UnsupportedInterrupt,
+            8 => Self::Device,
+            9 => Self::UnknownExtension,
+            10 => Self::Protection,
+            11 => Self::Register,
+            12 => Self::StackOverflow,
+            _ => return None,
+        })
+    }
+
+    /// Provides the cause of the processor error
+    pub fn from_error(err: &ProcessorError) -> Self {
+        match err {
+            ProcessorError::Memory(_) => Self::Memory,
+            ProcessorError::StackUnderflow => Self::StackUnderflow,
+            ProcessorError::StackOverflow => Self::StackOverflow,
+            ProcessorError::UnknownInstruction(_) => Self::UnknownInstruction,
+            ProcessorError::UnsupportedDataType(_, _) => Self::UnsupportedDataType,
+            ProcessorError::Operation(_) | ProcessorError::DataType(_) => Self::Arithmetic,
+            ProcessorError::OpcodeAlignment(_) => Self::OpcodeAlignment,
+            ProcessorError::UnsupportedInterrupt(_) => Self::UnsupportedInterrupt,
+            ProcessorError::Device(_, _) => Self::Device,
+            ProcessorError::UnknownExtension(_) => Self::UnknownExtension,
+            ProcessorError::Protection(_) => Self::Protection,
+            ProcessorError::Register(_) => Self::Register,
+        }
+    }
+}
+
+impl fmt::Display for TrapCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory => write!(f, "Memory Error"),
+            Self::StackUnderflow => write!(f, "Stack Underflow"),
+            Self::UnknownInstruction => write!(f, "Unknown Instruction"),
+            Self::UnsupportedDataType => write!(f, "Unsupported Data Type"),
+            Self::Arithmetic => write!(f, "Arithmetic Error"),
+            Self::OpcodeAlignment => write!(f, "Opcode Alignment"),
+            Self::UnsupportedInterrupt => write!(f, "Unsupported Interrupt"),
+            Self::Device => write!(f, "Device Error"),
+            Self::UnknownExtension => write!(f, "Unknown Extension"),
+            Self::Protection => write!(f, "Protection Fault"),
+            Self::Register => write!(f, "Register Error"),
+            Self::StackOverflow => write!(f, "Stack Overflow"),
+        }
+    }
+}
+
+/// Provides the details of a fault that wasn't handled by the program, with the registers as
+/// they were before the faulting instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrapInfo {
+    pub cause: TrapCause,
+    pub pc: u32,
+    pub address: u32,
+    pub registers: [u32; RegisterManager::REGISTER_COUNT],
+}
+
+impl TrapInfo {
+    /// Constructs the trap info for the error raised by the instruction at the provided address
+    pub fn new(err: &ProcessorError, pc: u32, registers: &RegisterManager) -> Self {
+        Self {
+            cause: TrapCause::from_error(err),
+            pc,
+            address: Self::fault_address(err).unwrap_or(0),
+            registers: registers.get_state(),
+        }
+    }
+
+    /// Provides the memory address related to the error, if any
+    fn fault_address(err: &ProcessorError) -> Option<u32> {
+        match err {
+            ProcessorError::Memory(
+                MemoryError::InvalidMemoryAccess(a)
+                | MemoryError::InvalidMemoryWrite(a, _)
+                | MemoryError::ReadOnlyMemory(a)
+                | MemoryError::EmptySegment(a)
+                | MemoryError::InvalidAddress(a)
+                | MemoryError::InvalidState(a),
+            ) => Some(*a),
+            ProcessorError::Protection(p) => Some(p.address()),
+            ProcessorError::OpcodeAlignment(a) => Some(*a),
+            ProcessorError::Device(_, e) => Self::fault_address(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TrapInfo {
+    /// Provides a crash report of the cause, location, and registers of the trap
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "trap: {} at pc 0x{:08x}, address 0x{:08x}",
+            self.cause, self.pc, self.address
+        )?;
+
+        for (i, val) in self.registers.iter().enumerate() {
+            let reg = Register::try_from(i).ok().and_then(|r| r.as_special());
+            let name = reg.as_ref().map_or("", |r| r.get_special_name());
+            let sep = if i % 4 == 3 { "\n" } else { "  " };
+            write!(f, "{name:>4} {i:>2} 0x{val:08x}{sep}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Provides the trap-info block written by the processor when an instruction raises an error.
+/// The block provides the cause, the address of the faulting instruction, and any memory address
+/// related to the fault, and the registers before the faulting instruction are also written to
+/// the register snapshot pointer, if set. When the trap interrupt is enabled, the processor calls
+/// it such that a guest monitor may show a crash screen, rather than stopping with the error.
+/// Any fault raised while a trap is already pending, before the pending flag is cleared, stops
+/// the processor as a double fault
+pub struct TrapInfoDevice {
+    trap: Option<TrapInfo>,
+    pending: bool,
+    irq_enabled: bool,
+    irq: u8,
+    snapshot_pointer: u32,
+}
+
+impl TrapInfoDevice {
+    const OFFSET_PENDING: u32 = 2;
+    const OFFSET_IRQ_ENABLE: u32 = 3;
+    const OFFSET_IRQ: u32 = 4;
+    const OFFSET_CAUSE: u32 = 5;
+    const OFFSET_PC: u32 = 8;
+    const OFFSET_ADDRESS: u32 = 12;
+    const OFFSET_SNAPSHOT: u32 = 16;
+    const OFFSET_END: u32 = 20;
+
+    pub const DEVICE_ID: u16 = 15;
+
+    pub fn new() -> Self {
+        Self {
+            trap: None,
+            pending: false,
+            irq_enabled: false,
+            irq: 0,
+            snapshot_pointer: 0,
+        }
+    }
+
+    /// Provides the last trap recorded, if any
+    pub fn last_trap(&self) -> Option<TrapInfo> {
+        self.trap
+    }
+
+    /// Determines whether a trap was recorded and not yet cleared by the guest
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Provides the hardware interrupt called for each trap, if enabled
+    pub fn trap_irq(&self) -> Option<u8> {
+        self.irq_enabled.then_some(self.irq)
+    }
+
+    pub fn set_trap_irq(&mut self, irq: Option<u8>) {
+        self.irq_enabled = irq.is_some();
+        self.irq = irq.unwrap_or(0);
+    }
+
+    /// Provides the address the registers are written to for each trap, where zero disables
+    /// the register snapshot
+    pub fn snapshot_pointer(&self) -> u32 {
+        self.snapshot_pointer
+    }
+
+    pub fn set_snapshot_pointer(&mut self, pointer: u32) {
+        self.snapshot_pointer = pointer;
+    }
+
+    /// Records the trap, marking it as pending
+    pub fn record(&mut self, trap: TrapInfo) {
+        self.trap = Some(trap);
+        self.pending = true;
+    }
+
+    /// Provides the word register containing the offset, along with the byte index into it
+    fn register(offset: u32) -> Option<(u32, usize)> {
+        if (Self::OFFSET_PC..Self::OFFSET_END).contains(&offset) {
+            let base = offset - offset % 4;
+            Some((base, (offset - base) as usize))
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for TrapInfoDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemorySegment for TrapInfoDevice {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            n if n < DEVICE_ID_SIZE => Ok(Self::DEVICE_ID.to_be_bytes()[n as usize]),
+            Self::OFFSET_PENDING => Ok(self.pending as u8),
+            Self::OFFSET_IRQ_ENABLE => Ok(self.irq_enabled as u8),
+            Self::OFFSET_IRQ => Ok(self.irq),
+            Self::OFFSET_CAUSE => Ok(self.trap.map(|t| t.cause.code()).unwrap_or(0)),
+            n => match Self::register(n) {
+                Some((base, ind)) => {
+                    let val = match base {
+                        Self::OFFSET_PC => self.trap.map(|t| t.pc).unwrap_or(0),
+                        Self::OFFSET_ADDRESS => self.trap.map(|t| t.address).unwrap_or(0),
+                        _ => self.snapshot_pointer,
+                    };
+                    Ok(val.to_be_bytes()[ind])
+                }
+                None => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+            },
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        match offset {
+            Self::OFFSET_PENDING if data == 0 => self.pending = false,
+            Self::OFFSET_IRQ_ENABLE => self.irq_enabled = data != 0,
+            Self::OFFSET_IRQ => self.irq = data,
+            n => match Self::register(n) {
+                Some((Self::OFFSET_SNAPSHOT, ind)) => {
+                    let mut bytes = self.snapshot_pointer.to_be_bytes();
+                    bytes[ind] = data;
+                    self.snapshot_pointer = u32::from_be_bytes(bytes);
+                }
+                _ => return Err(MemorySegmentError::InvalidMemoryWrite(offset, data)),
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Resets the memory segment, clearing any trap and the trap configuration
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        DEVICE_MEM_SIZE
+    }
+
+    /// Provides the register values, followed by the registers of the last trap, if any
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = (Self::OFFSET_PENDING..Self::OFFSET_END)
+            .map(|offset| self.get(offset).unwrap_or(0))
+            .collect::<Vec<_>>();
+        if let Some(trap) = self.trap {
+            state.extend(trap.registers.iter().flat_map(|r| r.to_be_bytes()));
+        }
+        state
+    }
+
+    /// Restores the register values and trap provided by save_state
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let size = (Self::OFFSET_END - Self::OFFSET_PENDING) as usize;
+        if state.len() < size {
+            return Err(MemorySegmentError::InvalidState);
+        }
+
+        let (regs, rest) = state.split_at(size);
+        let byte = |offset: u32| regs[(offset - Self::OFFSET_PENDING) as usize];
+        let word = |offset: u32| {
+            let i = (offset - Self::OFFSET_PENDING) as usize;
+            u32::from_be_bytes(regs[i..i + 4].try_into().unwrap())
+        };
+
+        self.trap = match (byte(Self::OFFSET_CAUSE), rest.len()) {
+            (0, 0) => None,
+            (code, n) if n == 4 * RegisterManager::REGISTER_COUNT => {
+                let mut registers = [0; RegisterManager::REGISTER_COUNT];
+                for (r, b) in registers.iter_mut().zip(rest.chunks_exact(4)) {
+                    *r = u32::from_be_bytes(b.try_into().unwrap());
+                }
+
+                Some(TrapInfo {
+                    cause: TrapCause::from_code(code).ok_or(MemorySegmentError::InvalidState)?,
+                    pc: word(Self::OFFSET_PC),
+                    address: word(Self::OFFSET_ADDRESS),
+                    registers,
+                })
+            }
+            _ => return Err(MemorySegmentError::InvalidState),
+        };
+
+        self.pending = byte(Self::OFFSET_PENDING) != 0;
+        self.irq_enabled = byte(Self::OFFSET_IRQ_ENABLE) != 0;
+        self.irq = byte(Self::OFFSET_IRQ);
+        self.snapshot_pointer = word(Self::OFFSET_SNAPSHOT);
+
+        Ok(())
+    }
+}
+
+impl ProcessorDevice for TrapInfoDevice {
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensure that a recorded trap is provided through the device registers, and that the
+    /// state is saved and restored
+    #[test]
+    fn test_trap_registers() {
+        let mut dev = TrapInfoDevice::new();
+        assert_eq!(dev.get(5).unwrap(), 0);
+        assert!(dev.set(8, 0).is_err());
+
+        for (i, b) in 0x800u32.to_be_bytes().into_iter().enumerate() {
+            dev.set(16 + i as u32, b).unwrap();
+        }
+        assert_eq!(dev.snapshot_pointer(), 0x800);
+
+        let mut registers = [0; RegisterManager::REGISTER_COUNT];
+        registers[6] = 5;
+        dev.record(TrapInfo {
+            cause: TrapCause::Protection,
+            pc: 0x1234,
+            address: 0x5678,
+            registers,
+        });
+
+        assert_eq!(dev.get(2).unwrap(), 1);
+        assert_eq!(dev.get(5).unwrap(), TrapCause::Protection.code());
+        assert_eq!(dev.get(11).unwrap(), 0x34);
+        assert_eq!(dev.get(15).unwrap(), 0x78);
+
+        let state = dev.save_state();
+        let mut restored = TrapInfoDevice::new();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.last_trap(), dev.last_trap());
+        assert!(restored.pending());
+        assert!(restored.load_state(&state[..state.len() - 1]).is_err());
+
+        dev.set(2, 0).unwrap();
+        assert!(!dev.pending());
+        assert_eq!(dev.last_trap().unwrap().pc, 0x1234);
+    }
+}
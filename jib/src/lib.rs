@@ -1,8 +1,26 @@
+//! Provides the jib processor, along with the memory and devices attached to it. This crate is
+//! the single implementation of the instruction set, used by the assembler, the debugger, and
+//! each front-end. The items re-exported at the crate root form the stable interface for hosts
+//! embedding the processor:
+//!
+//! - [`Processor`] executes instructions from memory, stepping with [`Processor::step`]
+//! - [`MemoryMap`] provides the address space of the processor, built from each
+//!   [`MemorySegment`] added with [`Processor::memory_add_segment`]
+//! - [`MemoryImage`] provides a linked program, loaded with [`Processor::load_image`]
+//! - [`DecodedInstruction`] provides the decoded form of an instruction word, as executed
+//!
+//! Items only reachable through the modules may change as the instruction set develops
 #![no_std]
 
 pub mod cpu;
+pub mod debug_info;
 pub mod device;
 pub mod memory;
 pub mod text;
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+pub use cpu::{DecodedInstruction, Processor, ProcessorError, StepResult};
+pub use memory::{MemoryImage, MemoryMap, MemorySegment};
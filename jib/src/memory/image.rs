@@ -0,0 +1,243 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt;
+
+/// Provides error conditions for constructing or parsing a memory image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageError {
+    InvalidHeader,
+    Truncated,
+    OverlappingSegment(u32),
+    AddressBounds(u32),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "Invalid Image Header"),
+            Self::Truncated => write!(f, "Truncated Image"),
+            Self::OverlappingSegment(loc) => write!(f, "Overlapping Image Segment 0x{loc:08x}"),
+            Self::AddressBounds(loc) => write!(f, "Image Segment 0x{loc:08x} Exceeds Memory"),
+        }
+    }
+}
+
+impl core::error::Error for ImageError {}
+
+/// Provides a contiguous block of data to be loaded at the given base address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageSegment {
+    pub base: u32,
+    pub data: Vec<u8>,
+}
+
+impl ImageSegment {
+    /// Provides the address just past the end of the segment
+    pub fn top(&self) -> u64 {
+        self.base as u64 + self.data.len() as u64
+    }
+}
+
+/// Provides a program image made up of segments at independent base addresses, such that
+/// code and data may be placed in separate regions of memory without filling the space
+/// between them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryImage {
+    segments: Vec<ImageSegment>,
+}
+
+impl MemoryImage {
+    /// Defines the identifier at the start of a serialized image
+    pub const MAGIC: [u8; 4] = *b"JIMG";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs an image with a single segment, loaded at address zero
+    pub fn from_flat(data: Vec<u8>) -> Self {
+        let mut img = Self::new();
+        if !data.is_empty() {
+            img.segments.push(ImageSegment { base: 0, data });
+        }
+        img
+    }
+
+    /// Constructs an image from individual byte values by address, with a segment for each run
+    /// of contiguous addresses
+    pub fn from_values(values: BTreeMap<u32, u8>) -> Self {
+        let mut runs: Vec<(u32, Vec<u8>)> = Vec::new();
+        for (a, v) in values {
+            match runs.last_mut() {
+                Some((base, data)) if *base as u64 + data.len() as u64 == a as u64 => data.push(v),
+                _ => runs.push((a, alloc::vec![v])),
+            }
+        }
+
+        Self {
+            segments: runs
+                .into_iter()
+                .map(|(base, data)| ImageSegment { base, data })
+                .collect(),
+        }
+    }
+
+    /// Adds a segment to the image, which may not overlap any existing segment. Empty
+    /// segments are ignored
+    pub fn add_segment(&mut self, base: u32, data: Vec<u8>) -> Result<(), ImageError> {
+        let seg = ImageSegment { base, data };
+        if seg.data.is_empty() {
+            return Ok(());
+        } else if seg.top() > u32::MAX as u64 + 1 {
+            return Err(ImageError::AddressBounds(base));
+        }
+
+        for s in self.segments.iter() {
+            if (seg.base as u64) < s.top() && (s.base as u64) < seg.top() {
+                return Err(ImageError::OverlappingSegment(base));
+            }
+        }
+
+        self.segments.push(seg);
+        Ok(())
+    }
+
+    /// Provides the segments within the image, in the order they were added
+    pub fn segments(&self) -> &[ImageSegment] {
+        &self.segments
+    }
+
+    /// Provides the image value at the requested address, if any segment contains it
+    pub fn get(&self, address: u32) -> Option<u8> {
+        self.segments
+            .iter()
+            .find(|s| address >= s.base && (address as u64) < s.top())
+            .map(|s| s.data[(address - s.base) as usize])
+    }
+
+    /// Provides the image as a single block of data starting at address zero, with any
+    /// space not covered by a segment set to zero
+    pub fn to_flat(&self) -> Vec<u8> {
+        let top = self.segments.iter().map(|s| s.top()).max().unwrap_or(0);
+        let mut data = alloc::vec![0; top as usize];
+        for s in self.segments.iter() {
+            data[s.base as usize..s.top() as usize].copy_from_slice(&s.data);
+        }
+        data
+    }
+
+    /// Serializes the image as the magic identifier and segment count, followed by the base
+    /// address, length, and data of each segment. All values are big-endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(Self::MAGIC);
+        bytes.extend((self.segments.len() as u32).to_be_bytes());
+
+        for s in self.segments.iter() {
+            bytes.extend(s.base.to_be_bytes());
+            bytes.extend((s.data.len() as u32).to_be_bytes());
+            bytes.extend(&s.data);
+        }
+
+        bytes
+    }
+
+    /// Parses an image serialized by [`MemoryImage::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ImageError> {
+        let mut rest = bytes
+            .strip_prefix(&Self::MAGIC)
+            .ok_or(ImageError::InvalidHeader)?;
+
+        let mut take = |len: usize| -> Result<&[u8], ImageError> {
+            if rest.len() < len {
+                return Err(ImageError::Truncated);
+            }
+            let (head, tail) = rest.split_at(len);
+            rest = tail;
+            Ok(head)
+        };
+
+        let read_u32 = |b: &[u8]| u32::from_be_bytes(b.try_into().unwrap());
+
+        let mut img = Self::new();
+        let count = read_u32(take(4)?);
+
+        for _ in 0..count {
+            let base = read_u32(take(4)?);
+            let len = read_u32(take(4)?);
+            img.add_segment(base, take(len as usize)?.to_vec())?;
+        }
+
+        if rest.is_empty() {
+            Ok(img)
+        } else {
+            Err(ImageError::InvalidHeader)
+        }
+    }
+
+    /// Determines whether the provided data starts with the image magic identifier
+    pub fn is_image(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::MAGIC)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Ensure that images are serialized and parsed without modification
+    #[test]
+    fn test_round_trip() {
+        let mut img = MemoryImage::new();
+        img.add_segment(0x2000, vec![5, 6]).unwrap();
+        img.add_segment(0, vec![1, 2, 3, 4]).unwrap();
+        img.add_segment(0x100, Vec::new()).unwrap();
+
+        let bytes = img.to_bytes();
+        assert_eq!(
+            bytes,
+            [
+                b'J', b'I', b'M', b'G', 0, 0, 0, 2, 0, 0, 0x20, 0, 0, 0, 0, 2, 5, 6, 0, 0, 0, 0, 0,
+                0, 0, 4, 1, 2, 3, 4
+            ]
+        );
+        assert!(MemoryImage::is_image(&bytes));
+        assert_eq!(MemoryImage::from_bytes(&bytes).unwrap(), img);
+
+        assert_eq!(img.get(0x2001), Some(6));
+        assert_eq!(img.get(0x2002), None);
+
+        let flat = img.to_flat();
+        assert_eq!(flat.len(), 0x2002);
+        assert_eq!(flat[..5], [1, 2, 3, 4, 0]);
+
+        assert_eq!(
+            MemoryImage::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ImageError::Truncated)
+        );
+        assert_eq!(
+            MemoryImage::from_bytes(&bytes[1..]),
+            Err(ImageError::InvalidHeader)
+        );
+    }
+
+    /// Ensure that overlapping segments and segments past the end of memory are rejected
+    #[test]
+    fn test_invalid_segments() {
+        let mut img = MemoryImage::new();
+        img.add_segment(0x10, vec![0; 0x10]).unwrap();
+        assert_eq!(
+            img.add_segment(0x1f, vec![0; 2]),
+            Err(ImageError::OverlappingSegment(0x1f))
+        );
+        assert_eq!(
+            img.add_segment(0x8, vec![0; 0x20]),
+            Err(ImageError::OverlappingSegment(0x8))
+        );
+        assert!(img.add_segment(0x20, vec![0; 2]).is_ok());
+        assert!(img.add_segment(u32::MAX, vec![0]).is_ok());
+        assert_eq!(
+            img.add_segment(u32::MAX - 1, vec![0; 3]),
+            Err(ImageError::AddressBounds(u32::MAX - 1))
+        );
+    }
+}
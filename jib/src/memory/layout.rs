@@ -0,0 +1,349 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+/// Describes the intended usage of a region of memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    ReadOnly,
+    ReadWrite,
+    Device,
+    Stack,
+}
+
+impl fmt::Display for RegionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReadOnly => write!(f, "Read Only"),
+            Self::ReadWrite => write!(f, "Read Write"),
+            Self::Device => write!(f, "Device"),
+            Self::Stack => write!(f, "Stack"),
+        }
+    }
+}
+
+/// Describes a single named region of memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub name: String,
+    pub base: u32,
+    pub size: u32,
+    pub kind: RegionKind,
+}
+
+impl MemoryRegion {
+    pub fn new(name: &str, base: u32, size: u32, kind: RegionKind) -> Self {
+        Self {
+            name: name.into(),
+            base,
+            size,
+            kind,
+        }
+    }
+
+    /// Provides the address just past the end of the region
+    pub fn top(&self) -> u64 {
+        self.base as u64 + self.size as u64
+    }
+
+    /// Determines whether the address is within the region
+    pub fn within(&self, addr: u32) -> bool {
+        addr >= self.base && (addr as u64) < self.top()
+    }
+}
+
+/// Provides a single conflict found when validating a program image against a memory layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadConflict {
+    /// The image is larger than the maximum allowed program size
+    ProgramSize { size: u32, max_size: u32 },
+    /// The image contains data in the given address range that is not mapped to any region,
+    /// which may extend past the end of the address space
+    Unmapped { start: u64, end: u64 },
+    /// The image contains data in the given address range that overlaps a region that may not be loaded into
+    Overlap {
+        start: u32,
+        end: u32,
+        region: String,
+        kind: RegionKind,
+    },
+}
+
+impl fmt::Display for LoadConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ProgramSize { size, max_size } => {
+                write!(f, "Program size {size} exceeds maximum {max_size}")
+            }
+            Self::Unmapped { start, end } => {
+                write!(f, "Unmapped memory 0x{start:08x}-0x{end:08x}")
+            }
+            Self::Overlap {
+                start,
+                end,
+                region,
+                kind,
+            } => write!(
+                f,
+                "Memory 0x{start:08x}-0x{end:08x} overlaps {kind} region '{region}'"
+            ),
+        }
+    }
+}
+
+/// Provides the full set of conflicts found when validating a program image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    pub conflicts: Vec<LoadConflict>,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unable to load program image")?;
+        for c in self.conflicts.iter() {
+            write!(f, "\n    {c}")?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for LoadError {}
+
+/// Describes the layout of the memory map, used to validate program images before loading
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLayout {
+    regions: Vec<MemoryRegion>,
+    max_program_size: Option<u32>,
+}
+
+impl MemoryLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a region to the layout description
+    pub fn add_region(&mut self, region: MemoryRegion) {
+        self.regions.push(region);
+    }
+
+    /// Provides the regions within the layout
+    pub fn regions(&self) -> &[MemoryRegion] {
+        &self.regions
+    }
+
+    /// Sets the maximum size, in bytes, of a program image
+    pub fn set_max_program_size(&mut self, size: Option<u32>) {
+        self.max_program_size = size;
+    }
+
+    /// Validates that the provided image, to be loaded starting at the base address, fits
+    /// within loadable memory regions. Every byte of the image must be within a read-only or
+    /// read-write region, with the exception that zero-valued bytes may fall within stack regions,
+    /// as these are equivalent to the default memory state. All conflicts are returned together
+    pub fn validate_image(&self, base: u32, data: &[u8]) -> Result<(), LoadError> {
+        let mut conflicts = Vec::new();
+
+        if let Some(max_size) = self.max_program_size {
+            if data.len() as u64 > max_size as u64 {
+                conflicts.push(LoadConflict::ProgramSize {
+                    size: u32::try_from(data.len()).unwrap_or(u32::MAX),
+                    max_size,
+                });
+            }
+        }
+
+        // Bytes past the end of the address space are reported as a single unmapped range
+        let space = u32::MAX as u64 + 1 - base as u64;
+        let (data, past_end) = match usize::try_from(space) {
+            Ok(n) if n < data.len() => data.split_at(n),
+            _ => (data, &[][..]),
+        };
+        let past_end = (!past_end.is_empty()).then(|| LoadConflict::Unmapped {
+            start: u32::MAX as u64 + 1,
+            end: u32::MAX as u64 + past_end.len() as u64,
+        });
+
+        let byte_conflicts = data
+            .iter()
+            .enumerate()
+            .map(|(i, val)| self.byte_conflict(base + i as u32, *val));
+
+        let mut current: Option<LoadConflict> = None;
+
+        for conflict in byte_conflicts.chain(core::iter::once(past_end)) {
+            current = match (current, conflict) {
+                (Some(c), Some(n)) => match Self::merge_conflict(c, &n) {
+                    Ok(merged) => Some(merged),
+                    Err(prev) => {
+                        conflicts.push(prev);
+                        Some(n)
+                    }
+                },
+                (Some(c), None) => {
+                    conflicts.push(c);
+                    None
+                }
+                (None, n) => n,
+            };
+        }
+
+        if let Some(c) = current {
+            conflicts.push(c);
+        }
+
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(LoadError { conflicts })
+        }
+    }
+
+    /// Provides the conflict for loading the value into the address, if any
+    fn byte_conflict(&self, addr: u32, val: u8) -> Option<LoadConflict> {
+        match self.regions.iter().find(|r| r.within(addr)) {
+            None => Some(LoadConflict::Unmapped {
+                start: addr as u64,
+                end: addr as u64,
+            }),
+            Some(r) => match r.kind {
+                RegionKind::ReadOnly | RegionKind::ReadWrite => None,
+                RegionKind::Stack if val == 0 => None,
+                RegionKind::Stack | RegionKind::Device => Some(LoadConflict::Overlap {
+                    start: addr,
+                    end: addr,
+                    region: r.name.clone(),
+                    kind: r.kind,
+                }),
+            },
+        }
+    }
+
+    /// Extends the existing conflict to the end of the next conflict if the next conflict is of
+    /// the same kind, otherwise returns the existing conflict as an error
+    fn merge_conflict(
+        existing: LoadConflict,
+        next: &LoadConflict,
+    ) -> Result<LoadConflict, LoadConflict> {
+        match (existing, next) {
+            (LoadConflict::Unmapped { start, .. }, LoadConflict::Unmapped { end, .. }) => {
+                Ok(LoadConflict::Unmapped { start, end: *end })
+            }
+            (
+                LoadConflict::Overlap {
+                    start,
+                    region,
+                    kind,
+                    ..
+                },
+                LoadConflict::Overlap {
+                    end,
+                    region: next_region,
+                    ..
+                },
+            ) if region == *next_region => Ok(LoadConflict::Overlap {
+                start,
+                end: *end,
+                region,
+                kind,
+            }),
+            (existing, _) => Err(existing),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn build_layout() -> MemoryLayout {
+        let mut layout = MemoryLayout::new();
+        layout.add_region(MemoryRegion::new("rom", 0, 0x100, RegionKind::ReadOnly));
+        layout.add_region(MemoryRegion::new(
+            "ram",
+            0x100,
+            0x100,
+            RegionKind::ReadWrite,
+        ));
+        layout.add_region(MemoryRegion::new("stack", 0x200, 0x100, RegionKind::Stack));
+        layout.add_region(MemoryRegion::new("serial", 0x300, 0x20, RegionKind::Device));
+        layout
+    }
+
+    /// Ensure that an image within the loadable regions is accepted
+    #[test]
+    fn test_valid_image() {
+        let layout = build_layout();
+        let mut data = vec![1; 0x200];
+        data.extend([0; 0x100]);
+        assert!(layout.validate_image(0, &data).is_ok());
+    }
+
+    /// Ensure that image bytes past the end of the address space are reported as unmapped,
+    /// joined with any unmapped range just below the end
+    #[test]
+    fn test_image_past_address_space() {
+        let mut layout = build_layout();
+        layout.add_region(MemoryRegion::new(
+            "top",
+            0xFFFF_FF00,
+            0xF0,
+            RegionKind::ReadWrite,
+        ));
+
+        let err = layout.validate_image(0xFFFF_FF00, &[1; 0x110]).unwrap_err();
+        assert_eq!(
+            err.conflicts,
+            [LoadConflict::Unmapped {
+                start: 0xFFFF_FFF0,
+                end: 0x1_0000_000F
+            }]
+        );
+
+        let err = layout.validate_image(u32::MAX, &[1; 2]).unwrap_err();
+        assert_eq!(
+            err.conflicts,
+            [LoadConflict::Unmapped {
+                start: 0xFFFF_FFFF,
+                end: 0x1_0000_0000
+            }]
+        );
+    }
+
+    /// Ensure that each conflict is reported with the full conflicting range
+    #[test]
+    fn test_image_conflicts() {
+        let mut layout = build_layout();
+        layout.set_max_program_size(Some(0x200));
+
+        let mut data = vec![0; 0x330];
+        data[0x210..0x218].fill(1);
+        data[0x310] = 1;
+
+        let err = layout.validate_image(0, &data).unwrap_err();
+        assert_eq!(
+            err.conflicts,
+            [
+                LoadConflict::ProgramSize {
+                    size: 0x330,
+                    max_size: 0x200
+                },
+                LoadConflict::Overlap {
+                    start: 0x210,
+                    end: 0x217,
+                    region: "stack".into(),
+                    kind: RegionKind::Stack
+                },
+                LoadConflict::Overlap {
+                    start: 0x300,
+                    end: 0x31f,
+                    region: "serial".into(),
+                    kind: RegionKind::Device
+                },
+                LoadConflict::Unmapped {
+                    start: 0x320,
+                    end: 0x32f
+                },
+            ]
+        );
+    }
+}
@@ -1,11 +1,20 @@
-use super::{MemoryError, MemorySegment, MemorySegmentError};
+use super::{MemoryError, MemoryImage, MemorySegment, MemorySegmentError, SegmentRange};
 
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 
+use alloc::collections::BTreeMap;
 use alloc::rc::Rc;
 use alloc::vec::Vec;
 use core::mem::size_of;
 
+/// Provides the saved internal state of the memory segment at the given base address
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SegmentState {
+    pub base: u32,
+    pub data: Vec<u8>,
+}
+
 struct SegmentData {
     base: u32,
     seg: Rc<RefCell<dyn MemorySegment>>,
@@ -20,6 +29,13 @@ impl SegmentData {
         self.base + self.seg.borrow().len()
     }
 
+    pub fn range(&self) -> SegmentRange {
+        SegmentRange {
+            base: self.base,
+            size: self.seg.borrow().len(),
+        }
+    }
+
     pub fn get(&self, addr: u32) -> Result<u8, MemoryError> {
         let offset = addr - self.base;
         let res = self.seg.borrow().get(offset);
@@ -32,12 +48,46 @@ impl SegmentData {
         self.segment_to_memory(res)
     }
 
+    pub fn load(&self, addr: u32, val: u8) -> Result<(), MemoryError> {
+        let offset = addr - self.base;
+        let res = self.seg.borrow_mut().load(offset, val);
+        self.segment_to_memory(res)
+    }
+
     pub fn inspect(&self, addr: u32) -> Result<u8, MemoryError> {
         let offset = addr - self.base;
         let res = self.seg.borrow().inspect(offset);
         self.segment_to_memory(res)
     }
 
+    /// Provides the number of values from the address to the end of the segment
+    pub fn remaining(&self, addr: u32) -> usize {
+        (self.top() - addr) as usize
+    }
+
+    pub fn set_range(&self, addr: u32, data: &[u8]) -> Result<(), MemoryError> {
+        let res = self.seg.borrow_mut().set_range(addr - self.base, data);
+        self.segment_to_memory(res)
+    }
+
+    pub fn load_range(&self, addr: u32, data: &[u8]) -> Result<(), MemoryError> {
+        let res = self.seg.borrow_mut().load_range(addr - self.base, data);
+        self.segment_to_memory(res)
+    }
+
+    pub fn inspect_range(&self, addr: u32, buf: &mut [u8]) -> Result<(), MemoryError> {
+        let res = self.seg.borrow().inspect_range(addr - self.base, buf);
+        self.segment_to_memory(res)
+    }
+
+    pub fn read_latency(&self, addr: u32) -> u32 {
+        self.seg.borrow().read_latency(addr - self.base)
+    }
+
+    pub fn write_latency(&self, addr: u32) -> u32 {
+        self.seg.borrow().write_latency(addr - self.base)
+    }
+
     fn segment_to_memory<T>(
         &self,
         result: Result<T, MemorySegmentError>,
@@ -52,36 +102,65 @@ impl SegmentData {
             Err(MemorySegmentError::InvalidMemoryWrite(offset, data)) => {
                 Err(MemoryError::InvalidMemoryWrite(self.base + offset, data))
             }
+            Err(MemorySegmentError::InvalidState) => Err(MemoryError::InvalidState(self.base)),
             Ok(v) => Ok(v),
         }
     }
 }
 
+/// Provides the number of bus reads and writes made at each address, where multi-byte
+/// accesses are counted at their first address
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessCounts {
+    pub reads: BTreeMap<u32, u64>,
+    pub writes: BTreeMap<u32, u64>,
+}
+
+/// Provides the processor memory bus, which maps each address to a memory segment and
+/// accumulates the stall cycles required by the segment access latencies
 pub struct MemoryMap {
     segments: Vec<SegmentData>,
+    stall_cycles: Cell<u32>,
+    journal: RefCell<Option<Vec<(u32, u8)>>>,
+    access_counts: RefCell<Option<AccessCounts>>,
 }
 
 macro_rules! GetSetInspectUnsignedType {
     ( $get_name: ident, $set_name: ident, $inspect_name: ident, $type: ident ) => {
+        /// Reads each byte of the value as a single bus access, stalling for the largest
+        /// read latency of the bytes accessed
         pub fn $get_name(&mut self, address: u32) -> Result<$type, MemoryError> {
             let mut bytes = [0; size_of::<$type>()];
+            let mut latency = 0;
             for i in 0..bytes.len() {
-                bytes[i] = self.get(address + i as u32)?;
+                let data = self.get_segment(address.wrapping_add(i as u32))?;
+                bytes[i] = data.get(address.wrapping_add(i as u32))?;
+                latency = latency.max(data.read_latency(address.wrapping_add(i as u32)));
             }
+            self.add_stall_cycles(latency);
+            self.count_access(address, false);
             Ok($type::from_be_bytes(bytes))
         }
 
+        /// Writes each byte of the value as a single bus access, stalling for the largest
+        /// write latency of the bytes accessed
         pub fn $set_name(&mut self, address: u32, val: $type) -> Result<(), MemoryError> {
+            let mut latency = 0;
             for (i, v) in val.to_be_bytes().iter().enumerate() {
-                self.set(address + i as u32, *v)?;
+                let data = self.get_segment(address.wrapping_add(i as u32))?;
+                self.record_write(data, address.wrapping_add(i as u32));
+                data.set(address.wrapping_add(i as u32), *v)?;
+                latency = latency.max(data.write_latency(address.wrapping_add(i as u32)));
             }
+            self.add_stall_cycles(latency);
+            self.count_access(address, true);
             Ok(())
         }
 
         pub fn $inspect_name(&self, address: u32) -> Result<$type, MemoryError> {
             let mut bytes = [0; size_of::<$type>()];
             for i in 0..bytes.len() {
-                bytes[i] = self.inspect(address + i as u32)?;
+                bytes[i] = self.inspect(address.wrapping_add(i as u32))?;
             }
             Ok($type::from_be_bytes(bytes))
         }
@@ -92,6 +171,9 @@ impl MemoryMap {
     pub fn new() -> Self {
         MemoryMap {
             segments: Vec::new(),
+            stall_cycles: Cell::new(0),
+            journal: RefCell::new(None),
+            access_counts: RefCell::new(None),
         }
     }
 
@@ -109,23 +191,48 @@ impl MemoryMap {
             return Err(MemoryError::EmptySegment(base));
         }
 
-        for sd in self.segments.iter() {
-            if new_seg.within(sd.base)
-                || new_seg.within(sd.top() - 1)
-                || sd.within(new_seg.base)
-                || sd.within(new_seg.top() - 1)
-            {
-                return Err(MemoryError::OverlappingSegment(base));
-            }
+        let requested = new_seg.range();
+        if let Some(existing) = self
+            .segments
+            .iter()
+            .map(|s| s.range())
+            .find(|r| r.overlaps(&requested))
+        {
+            return Err(MemoryError::SegmentOverlap {
+                existing,
+                requested,
+            });
         }
 
         self.segments.push(new_seg);
         Ok(())
     }
 
+    /// Removes the segment at the provided base address, such that its addresses may be
+    /// claimed by another segment, providing the removed segment
+    pub fn remove_segment(
+        &mut self,
+        base: u32,
+    ) -> Result<Rc<RefCell<dyn MemorySegment>>, MemoryError> {
+        let ind = self
+            .segments
+            .iter()
+            .position(|s| s.base == base)
+            .ok_or(MemoryError::InvalidAddress(base))?;
+        Ok(self.segments.remove(ind).seg)
+    }
+
+    /// Provides the base address and length of each segment, in the order added
+    pub fn segments(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.segments.iter().map(|s| (s.base, s.seg.borrow().len()))
+    }
+
     pub fn get(&self, address: u32) -> Result<u8, MemoryError> {
         let data = self.get_segment(address)?;
-        data.get(address)
+        let val = data.get(address)?;
+        self.add_stall_cycles(data.read_latency(address));
+        self.count_access(address, false);
+        Ok(val)
     }
 
     pub fn inspect(&self, address: u32) -> Result<u8, MemoryError> {
@@ -135,7 +242,151 @@ impl MemoryMap {
 
     pub fn set(&mut self, address: u32, val: u8) -> Result<(), MemoryError> {
         let data = self.get_segment(address)?;
-        data.set(address, val)
+        self.record_write(data, address);
+        data.set(address, val)?;
+        self.add_stall_cycles(data.write_latency(address));
+        self.count_access(address, true);
+        Ok(())
+    }
+
+    /// Loads each segment of the program image into memory at its base address. Read-only
+    /// segments may be loaded, and no stall cycles are added for the loaded values
+    pub fn load_image(&mut self, image: &MemoryImage) -> Result<(), MemoryError> {
+        for s in image.segments() {
+            self.load_range(s.base, &s.data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Provides the values starting at the requested address without affecting any device
+    /// state, copying the values from each segment within the range in turn
+    pub fn inspect_range(&self, address: u32, buf: &mut [u8]) -> Result<(), MemoryError> {
+        let mut done = 0;
+        while done < buf.len() {
+            let addr = Self::range_address(address, done)?;
+            let data = self.get_segment(addr)?;
+            let count = data.remaining(addr).min(buf.len() - done);
+            data.inspect_range(addr, &mut buf[done..done + count])?;
+            done += count;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the values starting at the requested address, writing the values to each segment
+    /// within the range in turn. No stall cycles are added, as the values are set by the host
+    pub fn set_range(&mut self, address: u32, data: &[u8]) -> Result<(), MemoryError> {
+        let mut done = 0;
+        while done < data.len() {
+            let addr = Self::range_address(address, done)?;
+            let seg = self.get_segment(addr)?;
+            let count = seg.remaining(addr).min(data.len() - done);
+            seg.set_range(addr, &data[done..done + count])?;
+            done += count;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the values starting at the requested address as when loading a program image,
+    /// such that read-only segments may be modified
+    pub fn load_range(&mut self, address: u32, data: &[u8]) -> Result<(), MemoryError> {
+        let mut done = 0;
+        while done < data.len() {
+            let addr = Self::range_address(address, done)?;
+            let seg = self.get_segment(addr)?;
+            let count = seg.remaining(addr).min(data.len() - done);
+            seg.load_range(addr, &data[done..done + count])?;
+            done += count;
+        }
+
+        Ok(())
+    }
+
+    /// Provides the address of the value at the index of a range starting at the address
+    fn range_address(address: u32, index: usize) -> Result<u32, MemoryError> {
+        u32::try_from(index)
+            .ok()
+            .and_then(|i| address.checked_add(i))
+            .ok_or(MemoryError::IndexBounds(
+                (address as usize).saturating_add(index),
+            ))
+    }
+
+    /// Sets the value at the requested address as when loading a program image, such that
+    /// read-only segments may be modified
+    pub fn load(&mut self, address: u32, val: u8) -> Result<(), MemoryError> {
+        self.get_segment(address)?.load(address, val)
+    }
+
+    /// Starts recording the previous value of each address written by set, set_u16, or set_u32,
+    /// discarding any values already recorded
+    pub fn start_journal(&mut self) {
+        self.journal.replace(Some(Vec::new()));
+    }
+
+    /// Stops recording written addresses, providing each address written and the value it held
+    /// before the write, in the order written
+    pub fn take_journal(&mut self) -> Vec<(u32, u8)> {
+        self.journal.take().unwrap_or_default()
+    }
+
+    /// Records the value at the address before it is written, if a journal is being recorded.
+    /// Addresses that can't be inspected can't be restored, and so are not recorded
+    fn record_write(&self, data: &SegmentData, address: u32) {
+        if let Some(journal) = self.journal.borrow_mut().as_mut() {
+            if let Ok(val) = data.inspect(address) {
+                journal.push((address, val));
+            }
+        }
+    }
+
+    /// Reads the instruction word at the address, as with get_u32, without counting the read
+    /// as a data access
+    pub fn fetch_u32(&mut self, address: u32) -> Result<u32, MemoryError> {
+        let counts = self.access_counts.take();
+        let res = self.get_u32(address);
+        self.access_counts.replace(counts);
+        res
+    }
+
+    /// Starts counting the reads and writes made at each address by get, set, and the
+    /// multi-byte variants, discarding any counts already recorded
+    pub fn start_access_counts(&mut self) {
+        self.access_counts.replace(Some(AccessCounts::default()));
+    }
+
+    /// Provides a copy of the access counts, if being recorded
+    pub fn access_counts(&self) -> Option<AccessCounts> {
+        self.access_counts.borrow().clone()
+    }
+
+    /// Stops counting accesses, providing the counts recorded
+    pub fn take_access_counts(&mut self) -> Option<AccessCounts> {
+        self.access_counts.take()
+    }
+
+    fn count_access(&self, address: u32, write: bool) {
+        if let Some(counts) = self.access_counts.borrow_mut().as_mut() {
+            let map = if write {
+                &mut counts.writes
+            } else {
+                &mut counts.reads
+            };
+            *map.entry(address).or_default() += 1;
+        }
+    }
+
+    fn add_stall_cycles(&self, cycles: u32) {
+        self.stall_cycles
+            .set(self.stall_cycles.get().saturating_add(cycles));
+    }
+
+    /// Provides the stall cycles accumulated by memory accesses since the last call,
+    /// clearing the accumulated count
+    pub fn take_stall_cycles(&mut self) -> u32 {
+        self.stall_cycles.take()
     }
 
     fn get_segment(&self, address: u32) -> Result<&SegmentData, MemoryError> {
@@ -152,6 +403,41 @@ impl MemoryMap {
         for s in self.segments.iter() {
             s.seg.borrow_mut().reset();
         }
+        self.stall_cycles.set(0);
+    }
+
+    /// Provides the saved state of each memory segment
+    pub fn save_state(&self) -> Vec<SegmentState> {
+        self.segments
+            .iter()
+            .map(|s| SegmentState {
+                base: s.base,
+                data: s.seg.borrow().save_state(),
+            })
+            .collect()
+    }
+
+    /// Restores the state of each memory segment. The provided states must match the
+    /// segments currently within the memory map
+    pub fn load_state(&mut self, states: &[SegmentState]) -> Result<(), MemoryError> {
+        if states.len() != self.segments.len() {
+            return Err(MemoryError::InvalidState(
+                states.first().map(|s| s.base).unwrap_or_default(),
+            ));
+        }
+
+        for (state, s) in states.iter().zip(self.segments.iter()) {
+            if state.base != s.base {
+                return Err(MemoryError::InvalidState(state.base));
+            }
+        }
+
+        for (state, s) in states.iter().zip(self.segments.iter()) {
+            let res = s.seg.borrow_mut().load_state(&state.data);
+            s.segment_to_memory(res)?;
+        }
+
+        Ok(())
     }
 
     GetSetInspectUnsignedType!(get_u32, set_u32, inspect_u32, u32);
@@ -163,3 +449,94 @@ impl Default for MemoryMap {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{BankedSegment, ReadOnlySegment, ReadWriteSegment};
+    use alloc::string::ToString;
+
+    fn segment(len: usize) -> Rc<RefCell<dyn MemorySegment>> {
+        Rc::new(RefCell::new(ReadWriteSegment::new(len)))
+    }
+
+    /// Ensure that segments claiming any address of an existing segment are refused, reporting
+    /// both address ranges
+    #[test]
+    fn test_segment_overlap() {
+        let mut map = MemoryMap::new();
+        map.add_segment(0x100, segment(0x100)).unwrap();
+        map.add_segment(0x80, segment(0x80)).unwrap();
+        map.add_segment(0x200, segment(0x10)).unwrap();
+
+        for (base, len) in [(0x1F0, 0x20), (0x120, 0x10), (0x0, 0x1000), (0x1FF, 1)] {
+            let requested = SegmentRange {
+                base,
+                size: len as u32,
+            };
+            match map.add_segment(base, segment(len)) {
+                Err(MemoryError::SegmentOverlap {
+                    existing,
+                    requested: r,
+                }) => {
+                    assert_eq!(r, requested);
+                    assert!(existing.overlaps(&requested));
+                }
+                r => panic!("unexpected result for 0x{base:x} - {r:?}"),
+            }
+        }
+
+        assert_eq!(map.segments().count(), 3);
+        assert_eq!(
+            MemoryError::SegmentOverlap {
+                existing: SegmentRange {
+                    base: 0x100,
+                    size: 0x100
+                },
+                requested: SegmentRange {
+                    base: 0x1F0,
+                    size: 0x20
+                },
+            }
+            .to_string(),
+            "Segment 0x000001f0-0x0000020f overlaps existing segment 0x00000100-0x000001ff"
+        );
+        map.add_segment(0x180, segment(0x80)).unwrap_err();
+        map.add_segment(0x210, segment(0x10)).unwrap();
+    }
+
+    /// Ensure that ranges of values spanning several segments are copied to and from each
+    /// segment in turn, including segments using the default range operations
+    #[test]
+    fn test_range_access() {
+        let mut map = MemoryMap::new();
+        map.add_segment(0, segment(0x100)).unwrap();
+        map.add_segment(
+            0x100,
+            Rc::new(RefCell::new(ReadOnlySegment::new(alloc::vec![0; 0x100]))),
+        )
+        .unwrap();
+        map.add_segment(0x200, Rc::new(RefCell::new(BankedSegment::new(0x10, 2))))
+            .unwrap();
+
+        let data = (0..0x20).collect::<Vec<u8>>();
+        map.load_range(0xF0, &data).unwrap();
+        map.set_range(0x1F8, &data[..0x10]).unwrap_err();
+        map.set_range(0x200, &data[..0x10]).unwrap();
+        map.take_stall_cycles();
+
+        let mut buf = [0; 0x20];
+        map.inspect_range(0xF0, &mut buf).unwrap();
+        assert_eq!(buf[..], data[..]);
+        map.inspect_range(0x1F8, &mut buf[..0x18]).unwrap();
+        assert_eq!(buf[..0x8], [0; 8]);
+        assert_eq!(buf[0x8..0x18], data[..0x10]);
+
+        assert!(matches!(
+            map.inspect_range(0x208, &mut buf),
+            Err(MemoryError::InvalidAddress(0x212))
+        ));
+        assert_eq!(map.get_u32(0x100).unwrap(), 0x10111213);
+        assert_eq!(map.take_stall_cycles(), 0);
+    }
+}
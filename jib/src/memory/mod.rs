@@ -1,23 +1,73 @@
+mod image;
+mod layout;
 mod memory_map;
+mod protection;
+mod relocation;
+mod segment_banked;
+mod segment_fault;
+mod segment_latency;
 mod segment_ro;
 mod segment_rw;
 
+use alloc::vec::Vec;
 use core::fmt;
+use core::ops::Range;
 
-pub use memory_map::MemoryMap;
+pub use image::{ImageError, ImageSegment, MemoryImage};
+pub use layout::{LoadConflict, LoadError, MemoryLayout, MemoryRegion, RegionKind};
+pub use memory_map::{AccessCounts, MemoryMap, SegmentState};
+pub use protection::{Access, ProtectionFault, ProtectionRegion, ProtectionUnit};
+pub use relocation::{RelocationError, RelocationTable};
+pub use segment_banked::BankedSegment;
+pub use segment_fault::FaultSegment;
+pub use segment_latency::LatencySegment;
 pub use segment_ro::ReadOnlySegment;
 pub use segment_rw::ReadWriteSegment;
 
+/// Provides the range of addresses claimed by a memory segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentRange {
+    pub base: u32,
+    pub size: u32,
+}
+
+impl SegmentRange {
+    /// Provides the address just past the end of the range
+    pub fn top(&self) -> u64 {
+        self.base as u64 + self.size as u64
+    }
+
+    /// Determines whether any address is within both ranges
+    pub fn overlaps(&self, other: &SegmentRange) -> bool {
+        (self.base as u64) < other.top() && (other.base as u64) < self.top()
+    }
+}
+
+impl fmt::Display for SegmentRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:08x}-0x{:08x}",
+            self.base,
+            self.top().saturating_sub(1)
+        )
+    }
+}
+
 /// Provides error conditions for memory segment parameters
 #[derive(Debug, Clone, Copy)]
 pub enum MemoryError {
     InvalidMemoryAccess(u32),
     InvalidMemoryWrite(u32, u8),
     ReadOnlyMemory(u32),
-    OverlappingSegment(u32),
+    SegmentOverlap {
+        existing: SegmentRange,
+        requested: SegmentRange,
+    },
     EmptySegment(u32),
     InvalidAddress(u32),
     IndexBounds(usize),
+    InvalidState(u32),
 }
 
 impl fmt::Display for MemoryError {
@@ -28,18 +78,29 @@ impl fmt::Display for MemoryError {
                 write!(f, "Invalid Memory Access 0x{loc:08x}[{data}]")
             }
             Self::ReadOnlyMemory(loc) => write!(f, "Read Only Memory 0x{loc:08x}"),
-            Self::OverlappingSegment(loc) => write!(f, "Overlapping Segment at 0x{loc:08x}"),
+            Self::SegmentOverlap {
+                existing,
+                requested,
+            } => write!(
+                f,
+                "Segment {requested} overlaps existing segment {existing}"
+            ),
             Self::EmptySegment(loc) => write!(f, "Empty Segment 0x{loc:08x}"),
             Self::InvalidAddress(loc) => write!(f, "Invalid Address 0x{loc:08x}"),
             Self::IndexBounds(loc) => write!(f, "Index Bounds 0x{loc:08x}"),
+            Self::InvalidState(loc) => write!(f, "Invalid State for Segment 0x{loc:08x}"),
         }
     }
 }
 
+impl core::error::Error for MemoryError {}
+
+#[derive(Debug, Clone, Copy)]
 pub enum MemorySegmentError {
     InvalidMemoryAccess(u32),
     ReadOnlyMemory(u32),
     InvalidMemoryWrite(u32, u8),
+    InvalidState,
 }
 
 pub trait MemorySegment {
@@ -55,12 +116,82 @@ pub trait MemorySegment {
     /// Returns true if the value could be set; otherwise returns false
     fn set(&mut self, offset: u32, val: u8) -> Result<(), MemorySegmentError>;
 
+    /// Sets the word at the requested memory location when loading a program image. This
+    /// defaults to a normal write, but may be provided to allow read-only memory to be loaded
+    fn load(&mut self, offset: u32, val: u8) -> Result<(), MemorySegmentError> {
+        self.set(offset, val)
+    }
+
+    /// Provides the values starting at the requested memory location, filling the buffer. This
+    /// defaults to reading each location in turn, but may be provided by segments that can
+    /// copy a range of values directly
+    fn get_range(&self, offset: u32, buf: &mut [u8]) -> Result<(), MemorySegmentError> {
+        for (i, v) in buf.iter_mut().enumerate() {
+            *v = self.get(range_offset(offset, i)?)?;
+        }
+        Ok(())
+    }
+
+    /// Provides the values starting at the requested memory location without affecting the
+    /// device state, defaulting to inspecting each location in turn
+    fn inspect_range(&self, offset: u32, buf: &mut [u8]) -> Result<(), MemorySegmentError> {
+        for (i, v) in buf.iter_mut().enumerate() {
+            *v = self.inspect(range_offset(offset, i)?)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the values starting at the requested memory location, defaulting to writing each
+    /// location in turn. Values before any location that can't be written may be set
+    fn set_range(&mut self, offset: u32, data: &[u8]) -> Result<(), MemorySegmentError> {
+        for (i, v) in data.iter().enumerate() {
+            self.set(range_offset(offset, i)?, *v)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the values starting at the requested memory location when loading a program image,
+    /// defaulting to loading each location in turn
+    fn load_range(&mut self, offset: u32, data: &[u8]) -> Result<(), MemorySegmentError> {
+        for (i, v) in data.iter().enumerate() {
+            self.load(range_offset(offset, i)?, *v)?;
+        }
+        Ok(())
+    }
+
     /// Provides the length of the memory segment
     fn len(&self) -> u32;
 
+    /// Provides the number of additional cycles the processor stalls for when reading the
+    /// requested memory location
+    fn read_latency(&self, _offset: u32) -> u32 {
+        0
+    }
+
+    /// Provides the number of additional cycles the processor stalls for when writing the
+    /// requested memory location
+    fn write_latency(&self, _offset: u32) -> u32 {
+        0
+    }
+
     /// Resets the memory segment
     fn reset(&mut self);
 
+    /// Provides the internal state of the segment, to be saved within a processor snapshot.
+    /// Segments without any modifiable state provide an empty state
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores the internal state of the segment from a state provided by save_state
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        if state.is_empty() {
+            Ok(())
+        } else {
+            Err(MemorySegmentError::InvalidState)
+        }
+    }
+
     /// Determines that the offset is within the memory segment
     fn within(&self, offset: u32) -> bool {
         offset < self.len()
@@ -71,3 +202,19 @@ pub trait MemorySegment {
         self.len() == 0
     }
 }
+
+/// Provides the offset of the value at the index of a range starting at the provided offset
+pub(crate) fn range_offset(offset: u32, index: usize) -> Result<u32, MemorySegmentError> {
+    u32::try_from(index)
+        .ok()
+        .and_then(|i| offset.checked_add(i))
+        .ok_or(MemorySegmentError::InvalidMemoryAccess(offset))
+}
+
+/// Provides the indices, relative to the start of the block, of the provided number of values
+/// starting at the offset, if each value is within the block of offsets
+pub(crate) fn block_range(offset: u32, count: usize, block: Range<u32>) -> Option<Range<usize>> {
+    let start = offset.checked_sub(block.start)? as usize;
+    let end = start.checked_add(count)?;
+    (end <= block.len()).then_some(start..end)
+}
@@ -0,0 +1,409 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{MemorySegment, MemorySegmentError};
+
+use crate::device::{DEVICE_ID_SIZE, ProcessorDevice};
+
+/// Provides the kind of memory access made by the processor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+impl Access {
+    /// Provides the permission bit of a protection region that allows the access
+    pub fn permission(&self) -> u32 {
+        match self {
+            Self::Read => ProtectionUnit::PERMISSION_READ,
+            Self::Write => ProtectionUnit::PERMISSION_WRITE,
+            Self::Execute => ProtectionUnit::PERMISSION_EXECUTE,
+        }
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read => write!(f, "Read"),
+            Self::Write => write!(f, "Write"),
+            Self::Execute => write!(f, "Execute"),
+        }
+    }
+}
+
+/// Provides a violation of the protection rules made while in user mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectionFault {
+    /// The access to the provided address isn't permitted by any region
+    Access(Access, u32),
+    /// The instruction at the provided address may only be executed in supervisor mode
+    PrivilegedInstruction(u32),
+}
+
+impl ProtectionFault {
+    /// Provides the fault kind reported by the fault kind register
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Access(Access::Read, _) => 1,
+            Self::Access(Access::Write, _) => 2,
+            Self::Access(Access::Execute, _) => 3,
+            Self::PrivilegedInstruction(_) => 4,
+        }
+    }
+
+    /// Provides the faulting address, or the instruction address for privileged instructions
+    pub fn address(&self) -> u32 {
+        match self {
+            Self::Access(_, addr) | Self::PrivilegedInstruction(addr) => *addr,
+        }
+    }
+}
+
+impl fmt::Display for ProtectionFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Access(access, addr) => write!(f, "{access} Access Violation 0x{addr:08x}"),
+            Self::PrivilegedInstruction(addr) => {
+                write!(f, "Privileged Instruction 0x{addr:08x}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for ProtectionFault {}
+
+/// Provides a region of memory that user-mode code may access, along with the permissions of
+/// the accesses allowed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProtectionRegion {
+    pub base: u32,
+    pub size: u32,
+    pub permissions: u32,
+}
+
+impl ProtectionRegion {
+    /// Determines whether the region allows the access to the provided address
+    pub fn permits(&self, address: u32, access: Access) -> bool {
+        self.permissions & access.permission() != 0 && address.wrapping_sub(self.base) < self.size
+    }
+}
+
+/// Provides a memory protection unit for the processor. When provided to the processor, code
+/// running in user mode may only access memory within a region permitting the access, and may
+/// not execute the privileged instructions. Any violation is trapped by calling the configured
+/// hardware interrupt in supervisor mode, with the fault kind and address recorded for the
+/// handler. The unit is configured from supervisor mode once attached as a device, such that
+/// user-mode code is unable to change the regions unless a region covers the unit itself
+pub struct ProtectionUnit {
+    regions: Vec<ProtectionRegion>,
+    trap_enabled: bool,
+    trap_irq: u8,
+    fault: Option<ProtectionFault>,
+    supervisor_stack: u32,
+}
+
+impl ProtectionUnit {
+    const OFFSET_REGION_COUNT: u32 = 2;
+    const OFFSET_TRAP_ENABLE: u32 = 3;
+    const OFFSET_TRAP_IRQ: u32 = 4;
+    const OFFSET_FAULT_KIND: u32 = 5;
+    const OFFSET_FAULT_ADDRESS: u32 = 8;
+    const OFFSET_SUPERVISOR_STACK: u32 = 12;
+    const OFFSET_REGIONS: u32 = 16;
+    const REGION_SIZE: u32 = 12;
+
+    pub const DEVICE_ID: u16 = 14;
+
+    pub const MAX_REGIONS: u8 = 16;
+
+    pub const PERMISSION_READ: u32 = 1;
+    pub const PERMISSION_WRITE: u32 = 2;
+    pub const PERMISSION_EXECUTE: u32 = 4;
+
+    /// Constructs a new protection unit with the provided number of regions, limited to the
+    /// maximum supported, where every region is initially empty
+    pub fn new(region_count: u8) -> Self {
+        Self {
+            regions: alloc::vec![
+                ProtectionRegion::default();
+                region_count.min(Self::MAX_REGIONS) as usize
+            ],
+            trap_enabled: false,
+            trap_irq: 0,
+            fault: None,
+            supervisor_stack: 0,
+        }
+    }
+
+    pub fn region_count(&self) -> u8 {
+        self.regions.len() as u8
+    }
+
+    pub fn region(&self, index: usize) -> Option<ProtectionRegion> {
+        self.regions.get(index).copied()
+    }
+
+    /// Sets the region at the provided index, returning false if the region doesn't exist
+    pub fn set_region(&mut self, index: usize, region: ProtectionRegion) -> bool {
+        match self.regions.get_mut(index) {
+            Some(r) => {
+                *r = region;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the hardware interrupt called for protection faults, if any. Faults without a trap
+    /// interrupt stop the processor with an error
+    pub fn set_trap_irq(&mut self, irq: Option<u8>) {
+        self.trap_enabled = irq.is_some();
+        self.trap_irq = irq.unwrap_or(0);
+    }
+
+    pub fn trap_irq(&self) -> Option<u8> {
+        self.trap_enabled.then_some(self.trap_irq)
+    }
+
+    /// Provides the stack pointer loaded when entering supervisor mode from user mode, where
+    /// zero keeps the current stack pointer
+    pub fn supervisor_stack(&self) -> u32 {
+        self.supervisor_stack
+    }
+
+    pub fn set_supervisor_stack(&mut self, sp: u32) {
+        self.supervisor_stack = sp;
+    }
+
+    /// Provides the last fault trapped, if any
+    pub fn last_fault(&self) -> Option<ProtectionFault> {
+        self.fault
+    }
+
+    /// Records the fault being trapped, to be read by the interrupt handler
+    pub fn record_fault(&mut self, fault: ProtectionFault) {
+        self.fault = Some(fault);
+    }
+
+    /// Determines whether user-mode code may make the access of the provided size, where each
+    /// byte accessed must be within a region permitting the access
+    pub fn permits(&self, address: u32, size: u32, access: Access) -> bool {
+        (0..size.max(1)).all(|i| {
+            let addr = address.wrapping_add(i);
+            self.regions.iter().any(|r| r.permits(addr, access))
+        })
+    }
+
+    /// Provides the word register containing the offset, along with the byte index into it
+    fn register(&self, offset: u32) -> Option<(u32, usize)> {
+        let end = Self::OFFSET_REGIONS + Self::REGION_SIZE * self.regions.len() as u32;
+        if (Self::OFFSET_FAULT_ADDRESS..end).contains(&offset) {
+            let base = offset - offset % 4;
+            Some((base, (offset - base) as usize))
+        } else {
+            None
+        }
+    }
+
+    /// Provides a mutable reference to the region word at the provided register offset
+    fn region_word(&mut self, base: u32) -> Option<&mut u32> {
+        let index = (base.checked_sub(Self::OFFSET_REGIONS)? / Self::REGION_SIZE) as usize;
+        let region = self.regions.get_mut(index)?;
+        match (base - Self::OFFSET_REGIONS) % Self::REGION_SIZE {
+            0 => Some(&mut region.base),
+            4 => Some(&mut region.size),
+            _ => Some(&mut region.permissions),
+        }
+    }
+}
+
+impl MemorySegment for ProtectionUnit {
+    /// Provides the word at the requested memory location
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        match offset {
+            n if n < DEVICE_ID_SIZE => Ok(Self::DEVICE_ID.to_be_bytes()[n as usize]),
+            Self::OFFSET_REGION_COUNT => Ok(self.region_count()),
+            Self::OFFSET_TRAP_ENABLE => Ok(self.trap_enabled as u8),
+            Self::OFFSET_TRAP_IRQ => Ok(self.trap_irq),
+            Self::OFFSET_FAULT_KIND => Ok(self.fault.map(|f| f.code()).unwrap_or(0)),
+            n => match self.register(n) {
+                Some((base, ind)) => {
+                    let val = match base {
+                        Self::OFFSET_FAULT_ADDRESS => self.fault.map(|f| f.address()).unwrap_or(0),
+                        Self::OFFSET_SUPERVISOR_STACK => self.supervisor_stack,
+                        _ => {
+                            let index =
+                                ((base - Self::OFFSET_REGIONS) / Self::REGION_SIZE) as usize;
+                            let region = self.regions[index];
+                            match (base - Self::OFFSET_REGIONS) % Self::REGION_SIZE {
+                                0 => region.base,
+                                4 => region.size,
+                                _ => region.permissions,
+                            }
+                        }
+                    };
+                    Ok(val.to_be_bytes()[ind])
+                }
+                None => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+            },
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        match offset {
+            Self::OFFSET_TRAP_ENABLE => self.trap_enabled = data != 0,
+            Self::OFFSET_TRAP_IRQ => self.trap_irq = data,
+            n => {
+                let reg = match self.register(n) {
+                    Some((Self::OFFSET_SUPERVISOR_STACK, ind)) => {
+                        Some((&mut self.supervisor_stack, ind))
+                    }
+                    Some((base, ind)) => self.region_word(base).map(|w| (w, ind)),
+                    None => None,
+                };
+
+                let Some((word, ind)) = reg else {
+                    return Err(MemorySegmentError::InvalidMemoryWrite(offset, data));
+                };
+
+                let mut bytes = word.to_be_bytes();
+                bytes[ind] = data;
+                *word = u32::from_be_bytes(bytes);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resets the memory segment, clearing every region and the trap configuration
+    fn reset(&mut self) {
+        self.regions.fill(ProtectionRegion::default());
+        self.trap_enabled = false;
+        self.trap_irq = 0;
+        self.fault = None;
+        self.supervisor_stack = 0;
+    }
+
+    /// Provides the length of the memory segment
+    fn len(&self) -> u32 {
+        Self::OFFSET_REGIONS + Self::REGION_SIZE * self.regions.len() as u32
+    }
+
+    /// Provides the register values, followed by the base, size, and permissions of each region
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        for offset in Self::OFFSET_REGION_COUNT..self.len() {
+            state.push(self.get(offset).unwrap_or(0));
+        }
+        state
+    }
+
+    /// Restores the register values and regions provided by save_state
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        if state.len() != (self.len() - Self::OFFSET_REGION_COUNT) as usize
+            || state[0] != self.region_count()
+        {
+            return Err(MemorySegmentError::InvalidState);
+        }
+
+        let byte = |offset: u32| state[(offset - Self::OFFSET_REGION_COUNT) as usize];
+        let word = |offset: u32| {
+            let i = (offset - Self::OFFSET_REGION_COUNT) as usize;
+            u32::from_be_bytes(state[i..i + 4].try_into().unwrap())
+        };
+
+        let address = word(Self::OFFSET_FAULT_ADDRESS);
+        self.fault = match byte(Self::OFFSET_FAULT_KIND) {
+            0 => None,
+            1 => Some(ProtectionFault::Access(Access::Read, address)),
+            2 => Some(ProtectionFault::Access(Access::Write, address)),
+            3 => Some(ProtectionFault::Access(Access::Execute, address)),
+            4 => Some(ProtectionFault::PrivilegedInstruction(address)),
+            _ => return Err(MemorySegmentError::InvalidState),
+        };
+
+        self.trap_enabled = byte(Self::OFFSET_TRAP_ENABLE) != 0;
+        self.trap_irq = byte(Self::OFFSET_TRAP_IRQ);
+        self.supervisor_stack = word(Self::OFFSET_SUPERVISOR_STACK);
+
+        for i in 0..self.regions.len() {
+            let base = Self::OFFSET_REGIONS + Self::REGION_SIZE * i as u32;
+            self.regions[i] = ProtectionRegion {
+                base: word(base),
+                size: word(base + 4),
+                permissions: word(base + 8),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+impl ProcessorDevice for ProtectionUnit {
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensure that regions configured through the device registers grant only the permitted
+    /// accesses, and that the state is saved and restored
+    #[test]
+    fn test_region_permissions() {
+        let mut unit = ProtectionUnit::new(2);
+        assert_eq!(unit.len(), 40);
+        assert!(!unit.permits(0x1000, 1, Access::Read));
+
+        let write_word = |unit: &mut ProtectionUnit, offset: u32, val: u32| {
+            for (i, b) in val.to_be_bytes().into_iter().enumerate() {
+                unit.set(offset + i as u32, b).unwrap();
+            }
+        };
+
+        write_word(&mut unit, 16, 0x1000);
+        write_word(&mut unit, 20, 0x100);
+        write_word(
+            &mut unit,
+            24,
+            ProtectionUnit::PERMISSION_READ | ProtectionUnit::PERMISSION_EXECUTE,
+        );
+        write_word(&mut unit, 28, 0x1100);
+        write_word(&mut unit, 32, 0x100);
+        write_word(&mut unit, 36, ProtectionUnit::PERMISSION_WRITE);
+
+        assert!(unit.permits(0x1000, 4, Access::Execute));
+        assert!(!unit.permits(0x1000, 4, Access::Write));
+        assert!(unit.permits(0x10FC, 4, Access::Read));
+        assert!(!unit.permits(0x10FE, 4, Access::Read));
+        assert!(unit.permits(0x11FC, 4, Access::Write));
+        assert!(!unit.permits(0x11FE, 4, Access::Write));
+        assert!(unit.set(44, 0).is_err());
+
+        unit.set(3, 1).unwrap();
+        unit.set(4, 7).unwrap();
+        assert_eq!(unit.trap_irq(), Some(7));
+
+        unit.record_fault(ProtectionFault::Access(Access::Write, 0x1234));
+        assert_eq!(unit.get(5).unwrap(), 2);
+        assert_eq!(unit.get(11).unwrap(), 0x34);
+
+        let state = unit.save_state();
+        let mut restored = ProtectionUnit::new(2);
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.region(1), unit.region(1));
+        assert_eq!(restored.last_fault(), unit.last_fault());
+        assert_eq!(restored.trap_irq(), Some(7));
+        assert!(ProtectionUnit::new(3).load_state(&state).is_err());
+
+        unit.reset();
+        assert!(!unit.permits(0x1000, 1, Access::Read));
+        assert_eq!(unit.trap_irq(), None);
+    }
+}
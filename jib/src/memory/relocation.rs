@@ -0,0 +1,260 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Write};
+use core::ops::Range;
+
+use super::MemoryImage;
+
+/// Provides error conditions for parsing or applying a relocation table, along with the line
+/// number for parse errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelocationError {
+    InvalidHeader,
+    UnknownEntry(usize, String),
+    InvalidEntry(usize),
+    MissingProgram,
+    AddressBounds(u32),
+}
+
+impl fmt::Display for RelocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "Invalid Relocation Table Header"),
+            Self::UnknownEntry(line, name) => write!(f, "Line {line} - Unknown Entry '{name}'"),
+            Self::InvalidEntry(line) => write!(f, "Line {line} - Invalid Entry"),
+            Self::MissingProgram => write!(f, "Relocation Table Missing Program Range"),
+            Self::AddressBounds(base) => {
+                write!(f, "Program Relocated to 0x{base:08x} Exceeds Memory")
+            }
+        }
+    }
+}
+
+impl core::error::Error for RelocationError {}
+
+/// Provides the information required to load a linked program at another base address, written
+/// by the assembler as a sidecar file alongside the program image. The program range is moved
+/// as a whole, such that relative references within the program are unchanged, while each
+/// reference provides the address of a word holding an absolute address, which is updated if it
+/// refers to the program. The text format starts with a `jrel 1` header line, followed by one
+/// entry per line:
+///
+/// ```text
+/// program 0x00000400 0x00000480
+/// ref 0x00000000
+/// ref 0x00000440
+/// ```
+///
+/// Empty lines are ignored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelocationTable {
+    pub program: Range<u32>,
+    pub refs: Vec<u32>,
+}
+
+impl RelocationTable {
+    /// Defines the file extension of relocation tables written alongside a program image
+    pub const EXTENSION: &'static str = "jrel";
+
+    const HEADER: &'static str = "jrel 1";
+
+    pub fn new(program: Range<u32>, refs: Vec<u32>) -> Self {
+        Self { program, refs }
+    }
+
+    /// Provides the address after moving the program to the new base address, where addresses
+    /// outside of the program are unchanged
+    pub fn relocate_address(&self, addr: u32, new_base: u32) -> u32 {
+        if self.program.contains(&addr) {
+            addr - self.program.start + new_base
+        } else {
+            addr
+        }
+    }
+
+    /// Ensures that the program fits within memory when moved to the new base address
+    pub fn check_base(&self, new_base: u32) -> Result<(), RelocationError> {
+        if new_base as u64 + self.program.len() as u64 > u32::MAX as u64 + 1 {
+            Err(RelocationError::AddressBounds(new_base))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Provides the image with the program moved to start at the new base address, and with each
+    /// absolute reference to the program updated to the relocated address. Data outside of the
+    /// program, such as the vector table, remains in place
+    pub fn relocate_image(
+        &self,
+        image: &MemoryImage,
+        new_base: u32,
+    ) -> Result<MemoryImage, RelocationError> {
+        self.check_base(new_base)?;
+
+        let mut values = image
+            .segments()
+            .iter()
+            .flat_map(|s| (s.base..).zip(s.data.iter().copied()))
+            .map(|(a, v)| (self.relocate_address(a, new_base), v))
+            .collect::<BTreeMap<_, _>>();
+
+        for addr in self
+            .refs
+            .iter()
+            .map(|a| self.relocate_address(*a, new_base))
+        {
+            let word = core::array::from_fn(|i| values.get(&(addr + i as u32)).copied());
+            if let [Some(a), Some(b), Some(c), Some(d)] = word {
+                let val = self.relocate_address(u32::from_be_bytes([a, b, c, d]), new_base);
+                for (i, b) in val.to_be_bytes().into_iter().enumerate() {
+                    values.insert(addr + i as u32, b);
+                }
+            }
+        }
+
+        Ok(MemoryImage::from_values(values))
+    }
+
+    /// Provides the table in the text format
+    pub fn to_text(&self) -> String {
+        let mut s = String::new();
+        let _ = writeln!(s, "{}", Self::HEADER);
+        let _ = writeln!(
+            s,
+            "program 0x{:08x} 0x{:08x}",
+            self.program.start, self.program.end
+        );
+        for addr in self.refs.iter() {
+            let _ = writeln!(s, "ref 0x{addr:08x}");
+        }
+        s
+    }
+
+    /// Parses a relocation table from the text format
+    pub fn from_text(s: &str) -> Result<Self, RelocationError> {
+        let mut lines = s.lines().enumerate();
+        if lines.next().map(|(_, l)| l.trim()) != Some(Self::HEADER) {
+            return Err(RelocationError::InvalidHeader);
+        }
+
+        let mut program = None;
+        let mut refs = Vec::new();
+
+        for (i, line) in lines {
+            let line_num = i + 1;
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let addr = |s: &str| parse_address(s).ok_or(RelocationError::InvalidEntry(line_num));
+
+            match words.as_slice() {
+                [] => (),
+                ["program", start, end] => {
+                    let (start, end) = (addr(start)?, addr(end)?);
+                    if start > end || program.is_some() {
+                        return Err(RelocationError::InvalidEntry(line_num));
+                    }
+                    program = Some(start..end);
+                }
+                ["ref", a] => refs.push(addr(a)?),
+                ["program" | "ref", ..] => return Err(RelocationError::InvalidEntry(line_num)),
+                [name, ..] => {
+                    return Err(RelocationError::UnknownEntry(line_num, name.to_string()))
+                }
+            }
+        }
+
+        let program = program.ok_or(RelocationError::MissingProgram)?;
+        Ok(Self { program, refs })
+    }
+
+    /// Provides the path of the relocation table written alongside the program image
+    #[cfg(feature = "std")]
+    pub fn sidecar_path(image: &std::path::Path) -> std::path::PathBuf {
+        image.with_extension(Self::EXTENSION)
+    }
+
+    /// Reads the relocation table written alongside the program image, providing None if no
+    /// relocation table was written
+    #[cfg(feature = "std")]
+    pub fn load_sidecar(image: &std::path::Path) -> Result<Option<Self>, String> {
+        let path = Self::sidecar_path(image);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let txt = std::fs::read_to_string(&path)
+            .map_err(|e| alloc::format!("Unable to read {} - {e}", path.display()))?;
+        Self::from_text(&txt)
+            .map(Some)
+            .map_err(|e| alloc::format!("{} - {e}", path.display()))
+    }
+}
+
+fn parse_address(s: &str) -> Option<u32> {
+    u32::from_str_radix(s.strip_prefix("0x")?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Ensure that the program is moved with absolute references to it updated, while data
+    /// outside of the program and references outside of the program are unchanged
+    #[test]
+    fn test_relocate_image() {
+        let mut image = MemoryImage::new();
+        image.add_segment(0, vec![0, 0, 0, 0x10]).unwrap();
+        image
+            .add_segment(0x10, vec![1, 2, 3, 4, 0, 0, 0, 0x14, 0, 0, 0, 0x02])
+            .unwrap();
+
+        let table = RelocationTable::new(0x10..0x1c, vec![0, 0x14, 0x18]);
+        let moved = table.relocate_image(&image, 0x100).unwrap();
+
+        assert_eq!(moved.get(2), Some(0x01));
+        assert_eq!(moved.get(3), Some(0x00));
+        assert_eq!(moved.get(0x100), Some(1));
+        assert_eq!(moved.get(0x107), Some(0x04));
+        assert_eq!(moved.get(0x106), Some(0x01));
+        assert_eq!(moved.get(0x10b), Some(0x02));
+        assert_eq!(moved.get(0x10), None);
+
+        assert_eq!(
+            table.relocate_image(&image, u32::MAX - 4),
+            Err(RelocationError::AddressBounds(u32::MAX - 4))
+        );
+    }
+
+    /// Ensure that tables are preserved through the text format, and that malformed tables are
+    /// rejected with the line of the error
+    #[test]
+    fn test_relocation_text() {
+        let table = RelocationTable::new(0x400..0x480, vec![0, 0x440]);
+        let text = table.to_text();
+        assert_eq!(
+            text,
+            "jrel 1\nprogram 0x00000400 0x00000480\nref 0x00000000\nref 0x00000440\n"
+        );
+        assert_eq!(RelocationTable::from_text(&text), Ok(table));
+
+        assert_eq!(
+            RelocationTable::from_text("ref 0x0"),
+            Err(RelocationError::InvalidHeader)
+        );
+        assert_eq!(
+            RelocationTable::from_text("jrel 1\nref 0x0"),
+            Err(RelocationError::MissingProgram)
+        );
+        assert_eq!(
+            RelocationTable::from_text("jrel 1\n\nref 400"),
+            Err(RelocationError::InvalidEntry(3))
+        );
+        assert_eq!(
+            RelocationTable::from_text("jrel 1\nlabel 0x400 start"),
+            Err(RelocationError::UnknownEntry(2, "label".into()))
+        );
+    }
+}
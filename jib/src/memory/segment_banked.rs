@@ -0,0 +1,190 @@
+use alloc::{vec, vec::Vec};
+
+use super::{MemorySegment, MemorySegmentError};
+
+/// Provides a read-write memory segment exposing a window into one of several banks of memory,
+/// such that programs and data larger than the address space set aside for them may be used.
+/// The window is followed by a control register selecting the visible bank, and a read-only
+/// register providing the number of banks. Writing a bank number outside of the available banks
+/// is refused, leaving the selected bank unchanged
+pub struct BankedSegment {
+    banks: Vec<Vec<u8>>,
+    window_size: u32,
+    selected: u8,
+}
+
+impl BankedSegment {
+    /// Defines the size of the control registers following the bank window
+    pub const CONTROL_SIZE: u32 = 2;
+
+    /// Constructs a new banked segment with the provided window size and number of banks, at
+    /// least one, where each bank is initially zero
+    pub fn new(window_size: u32, bank_count: u8) -> Self {
+        Self {
+            banks: vec![vec![0; window_size as usize]; bank_count.max(1) as usize],
+            window_size,
+            selected: 0,
+        }
+    }
+
+    pub fn bank_count(&self) -> u8 {
+        self.banks.len() as u8
+    }
+
+    pub fn window_size(&self) -> u32 {
+        self.window_size
+    }
+
+    /// Provides the bank currently visible within the window
+    pub fn selected_bank(&self) -> u8 {
+        self.selected
+    }
+
+    /// Provides the offset of the bank select register
+    pub fn select_offset(&self) -> u32 {
+        self.window_size
+    }
+
+    /// Provides the offset of the read-only bank count register
+    pub fn count_offset(&self) -> u32 {
+        self.window_size + 1
+    }
+
+    /// Copies the data into the bank, starting at the offset within the window, without changing
+    /// the selected bank
+    pub fn load_bank(
+        &mut self,
+        bank: u8,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<(), MemorySegmentError> {
+        let end = offset as u64 + data.len() as u64;
+        match self.banks.get_mut(bank as usize) {
+            Some(b) if end <= b.len() as u64 => {
+                b[offset as usize..end as usize].copy_from_slice(data);
+                Ok(())
+            }
+            _ => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+        }
+    }
+
+    /// Provides the value at the offset within the bank, without changing the selected bank
+    pub fn bank_value(&self, bank: u8, offset: u32) -> Option<u8> {
+        self.banks.get(bank as usize)?.get(offset as usize).copied()
+    }
+}
+
+impl MemorySegment for BankedSegment {
+    /// Provides the word at the requested memory location, within the selected bank for
+    /// offsets in the window
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        if offset < self.window_size {
+            Ok(self.banks[self.selected as usize][offset as usize])
+        } else if offset == self.select_offset() {
+            Ok(self.selected)
+        } else if offset == self.count_offset() {
+            Ok(self.bank_count())
+        } else {
+            Err(MemorySegmentError::InvalidMemoryAccess(offset))
+        }
+    }
+
+    /// Sets the word at the requested memory location with the given data
+    fn set(&mut self, offset: u32, data: u8) -> Result<(), MemorySegmentError> {
+        if offset < self.window_size {
+            self.banks[self.selected as usize][offset as usize] = data;
+        } else if offset == self.select_offset() && data < self.bank_count() {
+            self.selected = data;
+        } else {
+            return Err(MemorySegmentError::InvalidMemoryWrite(offset, data));
+        }
+
+        Ok(())
+    }
+
+    /// Resets the memory segment, clearing each bank and selecting the first bank
+    fn reset(&mut self) {
+        for b in self.banks.iter_mut() {
+            b.fill(0);
+        }
+        self.selected = 0;
+    }
+
+    /// Provides the length of the memory segment, including the control registers
+    fn len(&self) -> u32 {
+        self.window_size + Self::CONTROL_SIZE
+    }
+
+    /// Provides the selected bank, followed by the values of each bank in turn
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::from([self.selected]);
+        for b in self.banks.iter() {
+            state.extend(b);
+        }
+        state
+    }
+
+    /// Restores the selected bank and bank values, which must match the segment size
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        let (selected, data) = state
+            .split_first()
+            .ok_or(MemorySegmentError::InvalidState)?;
+
+        if *selected >= self.bank_count()
+            || data.len() != self.window_size as usize * self.banks.len()
+        {
+            return Err(MemorySegmentError::InvalidState);
+        }
+
+        if self.window_size > 0 {
+            for (b, d) in self
+                .banks
+                .iter_mut()
+                .zip(data.chunks(self.window_size as usize))
+            {
+                b.copy_from_slice(d);
+            }
+        }
+        self.selected = *selected;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensure that the window provides the values of the selected bank, and that invalid bank
+    /// selections are refused
+    #[test]
+    fn test_bank_switching() {
+        let mut seg = BankedSegment::new(16, 3);
+        assert_eq!(seg.len(), 18);
+        assert_eq!(seg.get(seg.count_offset()).unwrap(), 3);
+
+        seg.set(4, 0x11).unwrap();
+        seg.load_bank(2, 4, &[0x22, 0x33]).unwrap();
+        assert!(seg.load_bank(2, 15, &[0, 0]).is_err());
+        assert!(seg.load_bank(3, 0, &[0]).is_err());
+        assert_eq!(seg.get(4).unwrap(), 0x11);
+
+        seg.set(seg.select_offset(), 2).unwrap();
+        assert_eq!(seg.get(4).unwrap(), 0x22);
+        assert_eq!(seg.get(5).unwrap(), 0x33);
+        assert!(seg.set(seg.select_offset(), 3).is_err());
+        assert!(seg.set(seg.count_offset(), 1).is_err());
+        assert_eq!(seg.selected_bank(), 2);
+        assert!(seg.get(seg.len()).is_err());
+
+        let state = seg.save_state();
+        seg.reset();
+        assert_eq!(seg.selected_bank(), 0);
+        assert_eq!(seg.bank_value(0, 4), Some(0));
+
+        seg.load_state(&state).unwrap();
+        assert_eq!(seg.get(4).unwrap(), 0x22);
+        assert_eq!(seg.bank_value(0, 4), Some(0x11));
+        assert!(seg.load_state(&state[1..]).is_err());
+    }
+}
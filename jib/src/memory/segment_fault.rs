@@ -0,0 +1,233 @@
+use alloc::{collections::BTreeMap, rc::Rc, vec::Vec};
+use core::cell::{Cell, RefCell};
+
+use super::{MemorySegment, MemorySegmentError};
+
+use crate::device::{DeviceAction, ProcessorDevice};
+
+/// Provides a memory segment that corrupts data read from an existing segment, either at
+/// random with a configured probability or at scripted offsets, such that guest error-detection
+/// code may be tested. Each corrupted read is reported as a parity error, raising the configured
+/// hardware interrupt when the segment is also added to the processor as a device. Inspection
+/// and the saved state always provide the uncorrupted data
+pub struct FaultSegment {
+    inner: Rc<RefCell<dyn MemorySegment>>,
+    seed: u64,
+    rng: Cell<u64>,
+    probability: f64,
+    script: BTreeMap<u32, u8>,
+    armed: RefCell<BTreeMap<u32, u8>>,
+    parity_irq: Option<u32>,
+    parity_error: Cell<bool>,
+    fault_count: Cell<u64>,
+}
+
+impl FaultSegment {
+    pub const DEVICE_ID: u16 = 11;
+
+    /// Constructs a new fault segment around the provided segment, without any faults. The seed
+    /// selects the sequence of random faults, such that runs may be reproduced
+    pub fn new(inner: Rc<RefCell<dyn MemorySegment>>, seed: u64) -> Self {
+        let seed = seed.max(1);
+        Self {
+            inner,
+            seed,
+            rng: Cell::new(seed),
+            probability: 0.0,
+            script: BTreeMap::new(),
+            armed: RefCell::new(BTreeMap::new()),
+            parity_irq: None,
+            parity_error: Cell::new(false),
+            fault_count: Cell::new(0),
+        }
+    }
+
+    /// Sets the probability, from 0 to 1, that each byte read is corrupted by flipping a single
+    /// random bit
+    pub fn set_fault_probability(&mut self, probability: f64) {
+        self.probability = probability.clamp(0.0, 1.0);
+    }
+
+    /// Corrupts the next read of the offset by flipping the bits set in the mask. Scripted
+    /// faults are armed again when the segment is reset
+    pub fn add_fault(&mut self, offset: u32, mask: u8) {
+        self.script.insert(offset, mask);
+        self.armed.borrow_mut().insert(offset, mask);
+    }
+
+    /// Sets the hardware interrupt raised for each parity error, if any
+    pub fn set_parity_irq(&mut self, irq: Option<u32>) {
+        self.parity_irq = irq;
+    }
+
+    /// Provides the number of reads corrupted since the segment was created
+    pub fn fault_count(&self) -> u64 {
+        self.fault_count.get()
+    }
+
+    /// Provides the next value of the xorshift random number generator
+    fn next_random(&self) -> u64 {
+        let mut x = self.rng.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng.set(x);
+        x
+    }
+
+    /// Provides the mask of bits to flip for a read of the offset, if the read is corrupted
+    fn fault_mask(&self, offset: u32) -> Option<u8> {
+        if let Some(mask) = self.armed.borrow_mut().remove(&offset) {
+            return Some(mask);
+        }
+
+        if self.probability > 0.0 {
+            let sample = (self.next_random() >> 11) as f64 / (1u64 << 53) as f64;
+            if sample < self.probability {
+                return Some(1 << (self.next_random() % 8));
+            }
+        }
+
+        None
+    }
+}
+
+impl MemorySegment for FaultSegment {
+    /// Provides the word at the requested memory location, corrupted if a fault occurs
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        let val = self.inner.borrow().get(offset)?;
+
+        match self.fault_mask(offset) {
+            Some(mask) => {
+                self.parity_error.set(true);
+                self.fault_count.set(self.fault_count.get() + 1);
+                Ok(val ^ mask)
+            }
+            None => Ok(val),
+        }
+    }
+
+    fn inspect(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        self.inner.borrow().inspect(offset)
+    }
+
+    fn set(&mut self, offset: u32, val: u8) -> Result<(), MemorySegmentError> {
+        self.inner.borrow_mut().set(offset, val)
+    }
+
+    fn load(&mut self, offset: u32, val: u8) -> Result<(), MemorySegmentError> {
+        self.inner.borrow_mut().load(offset, val)
+    }
+
+    /// Provides the values without any faults, such that only processor reads are corrupted
+    fn inspect_range(&self, offset: u32, buf: &mut [u8]) -> Result<(), MemorySegmentError> {
+        self.inner.borrow().inspect_range(offset, buf)
+    }
+
+    fn set_range(&mut self, offset: u32, data: &[u8]) -> Result<(), MemorySegmentError> {
+        self.inner.borrow_mut().set_range(offset, data)
+    }
+
+    fn load_range(&mut self, offset: u32, data: &[u8]) -> Result<(), MemorySegmentError> {
+        self.inner.borrow_mut().load_range(offset, data)
+    }
+
+    fn len(&self) -> u32 {
+        self.inner.borrow().len()
+    }
+
+    fn read_latency(&self, offset: u32) -> u32 {
+        self.inner.borrow().read_latency(offset)
+    }
+
+    fn write_latency(&self, offset: u32) -> u32 {
+        self.inner.borrow().write_latency(offset)
+    }
+
+    /// Resets the inner segment, restarting the random fault sequence and re-arming the
+    /// scripted faults
+    fn reset(&mut self) {
+        self.inner.borrow_mut().reset();
+        self.rng.set(self.seed);
+        *self.armed.borrow_mut() = self.script.clone();
+        self.parity_error.set(false);
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        self.inner.borrow().save_state()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        self.inner.borrow_mut().load_state(state)
+    }
+}
+
+impl ProcessorDevice for FaultSegment {
+    fn on_step(&mut self, _cycles: u32) -> Option<DeviceAction> {
+        let error = self.parity_error.take();
+        match self.parity_irq {
+            Some(irq) if error => Some(DeviceAction::CallInterrupt(irq)),
+            _ => None,
+        }
+    }
+
+    fn device_id(&self) -> u16 {
+        Self::DEVICE_ID
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::ReadWriteSegment;
+
+    /// Ensure that scripted faults corrupt a single read, and that each fault reports a parity
+    /// error without changing the stored data
+    #[test]
+    fn test_scripted_faults() {
+        let inner = Rc::new(RefCell::new(ReadWriteSegment::new(16)));
+        let mut seg = FaultSegment::new(inner.clone(), 1);
+        seg.set(4, 0x5A).unwrap();
+        seg.add_fault(4, 0x81);
+        seg.set_parity_irq(Some(2));
+
+        assert!(seg.on_step(1).is_none());
+        assert_eq!(seg.inspect(4).unwrap(), 0x5A);
+        assert_eq!(seg.get(4).unwrap(), 0xDB);
+        assert!(matches!(
+            seg.on_step(1),
+            Some(DeviceAction::CallInterrupt(2))
+        ));
+        assert!(seg.on_step(1).is_none());
+
+        assert_eq!(seg.get(4).unwrap(), 0x5A);
+        assert_eq!(inner.borrow().get(4).unwrap(), 0x5A);
+        assert_eq!(seg.fault_count(), 1);
+
+        seg.reset();
+        assert_eq!(seg.get(4).unwrap(), 0x81);
+    }
+
+    /// Ensure that random faults occur at roughly the configured rate, and repeat for the same
+    /// seed after a reset
+    #[test]
+    fn test_random_faults() {
+        let inner = Rc::new(RefCell::new(ReadWriteSegment::new(256)));
+        let mut seg = FaultSegment::new(inner, 42);
+        seg.set_fault_probability(0.25);
+
+        let read_all =
+            |seg: &FaultSegment| (0..256).map(|i| seg.get(i).unwrap()).collect::<Vec<_>>();
+
+        let first = read_all(&seg);
+        let faults = first.iter().filter(|v| **v != 0).count();
+        assert!((32..96).contains(&faults), "{faults} faults");
+        assert!(first.iter().all(|v| v.count_ones() <= 1));
+
+        seg.reset();
+        assert_eq!(read_all(&seg), first);
+
+        seg.set_fault_probability(0.0);
+        assert!(read_all(&seg).iter().all(|v| *v == 0));
+    }
+}
@@ -0,0 +1,135 @@
+use alloc::{collections::BTreeMap, rc::Rc, vec::Vec};
+use core::cell::RefCell;
+
+use super::{MemorySegment, MemorySegmentError};
+
+/// Provides a memory segment that adds per-offset access latencies to an existing segment,
+/// such that drivers may be tested against slow peripherals. The inner segment may also be
+/// added to the processor as a device, as only memory access is passed through this segment
+pub struct LatencySegment {
+    inner: Rc<RefCell<dyn MemorySegment>>,
+    read: BTreeMap<u32, u32>,
+    write: BTreeMap<u32, u32>,
+}
+
+impl LatencySegment {
+    /// Constructs a new latency segment around the provided segment, without any added latency
+    pub fn new(inner: Rc<RefCell<dyn MemorySegment>>) -> Self {
+        Self {
+            inner,
+            read: BTreeMap::new(),
+            write: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the read latency for each byte in the provided number of bytes from the offset
+    pub fn set_read_latency(&mut self, offset: u32, len: u32, cycles: u32) {
+        for i in offset..offset.saturating_add(len) {
+            self.read.insert(i, cycles);
+        }
+    }
+
+    /// Sets the write latency for each byte in the provided number of bytes from the offset
+    pub fn set_write_latency(&mut self, offset: u32, len: u32, cycles: u32) {
+        for i in offset..offset.saturating_add(len) {
+            self.write.insert(i, cycles);
+        }
+    }
+}
+
+impl MemorySegment for LatencySegment {
+    fn get(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        self.inner.borrow().get(offset)
+    }
+
+    fn inspect(&self, offset: u32) -> Result<u8, MemorySegmentError> {
+        self.inner.borrow().inspect(offset)
+    }
+
+    fn set(&mut self, offset: u32, val: u8) -> Result<(), MemorySegmentError> {
+        self.inner.borrow_mut().set(offset, val)
+    }
+
+    fn load(&mut self, offset: u32, val: u8) -> Result<(), MemorySegmentError> {
+        self.inner.borrow_mut().load(offset, val)
+    }
+
+    fn get_range(&self, offset: u32, buf: &mut [u8]) -> Result<(), MemorySegmentError> {
+        self.inner.borrow().get_range(offset, buf)
+    }
+
+    fn inspect_range(&self, offset: u32, buf: &mut [u8]) -> Result<(), MemorySegmentError> {
+        self.inner.borrow().inspect_range(offset, buf)
+    }
+
+    fn set_range(&mut self, offset: u32, data: &[u8]) -> Result<(), MemorySegmentError> {
+        self.inner.borrow_mut().set_range(offset, data)
+    }
+
+    fn load_range(&mut self, offset: u32, data: &[u8]) -> Result<(), MemorySegmentError> {
+        self.inner.borrow_mut().load_range(offset, data)
+    }
+
+    fn len(&self) -> u32 {
+        self.inner.borrow().len()
+    }
+
+    /// Provides the configured read latency, adding any latency declared by the inner segment
+    fn read_latency(&self, offset: u32) -> u32 {
+        self.read.get(&offset).copied().unwrap_or_default()
+            + self.inner.borrow().read_latency(offset)
+    }
+
+    /// Provides the configured write latency, adding any latency declared by the inner segment
+    fn write_latency(&self, offset: u32) -> u32 {
+        self.write.get(&offset).copied().unwrap_or_default()
+            + self.inner.borrow().write_latency(offset)
+    }
+
+    fn reset(&mut self) {
+        self.inner.borrow_mut().reset();
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        self.inner.borrow().save_state()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        self.inner.borrow_mut().load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{MemoryMap, ReadWriteSegment};
+
+    /// Ensure that word accesses stall for the slowest byte, while inspection does not stall
+    #[test]
+    fn test_stall_cycles() {
+        let inner = Rc::new(RefCell::new(ReadWriteSegment::new(16)));
+        let mut seg = LatencySegment::new(inner.clone());
+        seg.set_read_latency(4, 4, 3);
+        seg.set_read_latency(6, 1, 5);
+        seg.set_write_latency(8, 4, 2);
+
+        let mut mem = MemoryMap::new();
+        mem.add_segment(0x100, Rc::new(RefCell::new(seg))).unwrap();
+
+        mem.set_u32(0x104, 0x01020304).unwrap();
+        assert_eq!(mem.take_stall_cycles(), 0);
+        assert_eq!(inner.borrow().get(7).unwrap(), 4);
+
+        assert_eq!(mem.get_u32(0x104).unwrap(), 0x01020304);
+        assert_eq!(mem.take_stall_cycles(), 5);
+        assert_eq!(mem.take_stall_cycles(), 0);
+
+        mem.get(0x104).unwrap();
+        mem.get(0x105).unwrap();
+        mem.inspect_u32(0x104).unwrap();
+        assert_eq!(mem.take_stall_cycles(), 6);
+
+        mem.set_u16(0x10a, 1).unwrap();
+        assert_eq!(mem.take_stall_cycles(), 2);
+    }
+}
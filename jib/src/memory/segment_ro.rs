@@ -1,6 +1,6 @@
 use alloc::vec::Vec;
 
-use super::{MemorySegment, MemorySegmentError};
+use super::{block_range, MemorySegment, MemorySegmentError};
 
 /// Provides a read-write memory segment type
 pub struct ReadOnlySegment {
@@ -31,6 +31,37 @@ impl MemorySegment for ReadOnlySegment {
         Err(MemorySegmentError::ReadOnlyMemory(offset))
     }
 
+    /// Sets the word at the requested memory location when loading a program image
+    fn load(&mut self, offset: u32, val: u8) -> Result<(), MemorySegmentError> {
+        match self.data.get_mut(offset as usize) {
+            Some(v) => {
+                *v = val;
+                Ok(())
+            }
+            None => Err(MemorySegmentError::InvalidMemoryAccess(offset)),
+        }
+    }
+
+    /// Copies the values starting at the requested memory location into the buffer
+    fn get_range(&self, offset: u32, buf: &mut [u8]) -> Result<(), MemorySegmentError> {
+        let range = block_range(offset, buf.len(), 0..self.len())
+            .ok_or(MemorySegmentError::InvalidMemoryAccess(offset))?;
+        buf.copy_from_slice(&self.data[range]);
+        Ok(())
+    }
+
+    fn inspect_range(&self, offset: u32, buf: &mut [u8]) -> Result<(), MemorySegmentError> {
+        self.get_range(offset, buf)
+    }
+
+    /// Copies the provided values into memory when loading a program image
+    fn load_range(&mut self, offset: u32, data: &[u8]) -> Result<(), MemorySegmentError> {
+        let range = block_range(offset, data.len(), 0..self.len())
+            .ok_or(MemorySegmentError::InvalidMemoryAccess(offset))?;
+        self.data[range].copy_from_slice(data);
+        Ok(())
+    }
+
     /// Resets the memory segment
     fn reset(&mut self) {
         // Do Nothing
@@ -107,6 +138,9 @@ mod tests {
             assert!(success.is_err());
         }
 
+        assert!(mem.load(size as u32, 0).is_err());
+        assert!(mem.load(0, 1).is_ok());
+
         for i in 0..MEM_MAX_SIZE {
             let should_be_within = i < size as u32;
             assert_eq!(mem.within(i), should_be_within);
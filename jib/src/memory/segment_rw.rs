@@ -1,6 +1,6 @@
 use alloc::vec::Vec;
 
-use super::{MemorySegment, MemorySegmentError};
+use super::{block_range, MemorySegment, MemorySegmentError};
 
 /// Provides a read-write memory segment type
 pub struct ReadWriteSegment {
@@ -38,6 +38,30 @@ impl MemorySegment for ReadWriteSegment {
         }
     }
 
+    /// Copies the values starting at the requested memory location into the buffer
+    fn get_range(&self, offset: u32, buf: &mut [u8]) -> Result<(), MemorySegmentError> {
+        let range = block_range(offset, buf.len(), 0..self.len())
+            .ok_or(MemorySegmentError::InvalidMemoryAccess(offset))?;
+        buf.copy_from_slice(&self.data[range]);
+        Ok(())
+    }
+
+    fn inspect_range(&self, offset: u32, buf: &mut [u8]) -> Result<(), MemorySegmentError> {
+        self.get_range(offset, buf)
+    }
+
+    /// Copies the provided values into memory starting at the requested memory location
+    fn set_range(&mut self, offset: u32, data: &[u8]) -> Result<(), MemorySegmentError> {
+        let range = block_range(offset, data.len(), 0..self.len())
+            .ok_or(MemorySegmentError::InvalidMemoryAccess(offset))?;
+        self.data[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn load_range(&mut self, offset: u32, data: &[u8]) -> Result<(), MemorySegmentError> {
+        self.set_range(offset, data)
+    }
+
     /// Resets the memory segment
     fn reset(&mut self) {
         // Reset all data values to 0 if not read only
@@ -48,6 +72,21 @@ impl MemorySegment for ReadWriteSegment {
     fn len(&self) -> u32 {
         self.data.len() as u32
     }
+
+    /// Provides the current memory values
+    fn save_state(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    /// Restores the memory values, which must match the segment length
+    fn load_state(&mut self, state: &[u8]) -> Result<(), MemorySegmentError> {
+        if state.len() == self.data.len() {
+            self.data.copy_from_slice(state);
+            Ok(())
+        } else {
+            Err(MemorySegmentError::InvalidState)
+        }
+    }
 }
 
 #[cfg(test)]
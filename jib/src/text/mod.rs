@@ -15,6 +15,8 @@ impl fmt::Display for CharacterError {
     }
 }
 
+impl core::error::Error for CharacterError {}
+
 /// Converts an input character into a memory-word supported by the SProc
 pub fn character_to_byte(c: char) -> Result<u8, CharacterError> {
     const NULL: u8 = b'\0';
@@ -0,0 +1,334 @@
+use std::{path::PathBuf, sync::mpsc::Receiver, time::Duration};
+
+use jib::{
+    cpu::{Processor, Register},
+    device::{KeyboardDevice, PlaybackScript},
+};
+use jib_asm::engine::{Engine, EngineCommand, EngineEvent, Speed};
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+use crate::machine::{MachineCommand, MachineUpdate, MachineView, Program};
+
+pub const HELP: &str = "\
+keys: s step, u step back, c run/stop, r reset, b toggle breakpoint at pc, i serial input, k keyboard, \
+v toggle display, : command, pgup/pgdn scroll memory, q quit
+commands: break <loc>, delete <loc>, mem <loc>, reg <n> <val>, step [n], back [n], disk [path], devices, \
+gpio <pin> <0|1>, pace [hz|off], reset, quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    Normal,
+    Command,
+    Serial,
+    Keyboard,
+}
+
+/// Provides the state of the terminal front-end, including the engine running the simulated
+/// machine, the latest view of the machine, and the current input line
+pub struct App {
+    engine: Engine<MachineCommand>,
+    updates: Receiver<MachineUpdate>,
+    pub program: Program,
+    pub view: Option<Box<MachineView>>,
+    pub console: String,
+    pub running: bool,
+    pub memory_base: u32,
+    pub mode: InputMode,
+    pub show_display: bool,
+    pub input: String,
+    pub messages: Vec<String>,
+    pub quit: bool,
+    /// The speed used while frame pacing is disabled
+    speed: Speed,
+}
+
+impl App {
+    const MAX_MESSAGES: usize = 100;
+    const MEMORY_PAGE: u32 = 0x40;
+
+    /// The time to wait for the engine to provide its state
+    const RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+    pub fn new(
+        engine: Engine<MachineCommand>,
+        updates: Receiver<MachineUpdate>,
+        program: Program,
+        speed: Speed,
+    ) -> Self {
+        engine.send(EngineCommand::SetSpeed(speed));
+        Self {
+            engine,
+            updates,
+            program,
+            view: None,
+            console: String::new(),
+            running: false,
+            memory_base: 0,
+            mode: InputMode::Normal,
+            show_display: false,
+            input: String::new(),
+            messages: HELP.lines().map(|s| s.to_string()).collect(),
+            quit: false,
+            speed,
+        }
+    }
+
+    pub fn message(&mut self, msg: &str) {
+        self.messages.extend(msg.lines().map(|s| s.to_string()));
+        if self.messages.len() > Self::MAX_MESSAGES {
+            self.messages
+                .drain(..self.messages.len() - Self::MAX_MESSAGES);
+        }
+    }
+
+    fn send(&self, cmd: MachineCommand) {
+        self.engine.send(EngineCommand::Machine(cmd));
+    }
+
+    /// Sets the number of display frames run per second of host time, where each frame runs the
+    /// cycles of a single vertical sync of the display. Without a frame rate, the processor runs
+    /// at the speed provided to the front-end
+    pub fn set_frame_rate(&mut self, frame_rate: Option<u32>) {
+        let speed = frame_rate.map_or(self.speed, Speed::FramesPerSecond);
+        self.engine.send(EngineCommand::SetSpeed(speed));
+    }
+
+    /// Applies the events and updates sent by the engine since the last call
+    pub fn update(&mut self) {
+        while let Ok(event) = self.engine.try_event() {
+            match event {
+                EngineEvent::Stopped(_) => self.running = false,
+                EngineEvent::Error(e) => self.message(&format!("error: {e}")),
+                EngineEvent::Exited => self.quit = true,
+                EngineEvent::State(_) => (),
+            }
+        }
+
+        while let Ok(update) = self.updates.try_recv() {
+            match update {
+                MachineUpdate::View(view) => self.view = Some(view),
+                MachineUpdate::Console(text) => self.console.push_str(&text),
+                MachineUpdate::Message(msg) => self.message(&msg),
+                MachineUpdate::Recording(_) => (),
+            }
+        }
+    }
+
+    /// Stops the engine, providing the serial input and key presses provided since the last
+    /// reset as a playback script
+    pub fn finish(self) -> Option<PlaybackScript> {
+        // The machine sends the recording as it is dropped along with the engine
+        let Self {
+            engine, updates, ..
+        } = self;
+        drop(engine);
+
+        updates.try_iter().find_map(|u| match u {
+            MachineUpdate::Recording(script) => Some(script),
+            _ => None,
+        })
+    }
+
+    /// Starts or stops the processor
+    fn toggle_running(&mut self) {
+        self.running = !self.running;
+        self.engine.send(if self.running {
+            EngineCommand::Start
+        } else {
+            EngineCommand::Stop
+        });
+    }
+
+    fn step(&mut self, count: usize) {
+        self.running = false;
+        self.engine.send(EngineCommand::Step(count));
+    }
+
+    fn step_back(&mut self, count: usize) {
+        self.running = false;
+        self.send(MachineCommand::StepBack(count));
+    }
+
+    fn reset(&mut self) {
+        self.running = false;
+        self.console.clear();
+        self.send(MachineCommand::Reset);
+    }
+
+    fn set_memory_base(&mut self, base: u32) {
+        self.memory_base = base;
+        self.send(MachineCommand::ShowMemory(base));
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        match self.mode {
+            InputMode::Normal => match key.code {
+                KeyCode::Char('q') => self.quit = true,
+                KeyCode::Char('s') => self.step(1),
+                KeyCode::Char('u') => self.step_back(1),
+                KeyCode::Char('c') => self.toggle_running(),
+                KeyCode::Char('r') => self.reset(),
+                KeyCode::Char('b') => {
+                    if let Some(view) = &self.view {
+                        self.send(MachineCommand::ToggleBreakpoint(view.pc));
+                    }
+                }
+                KeyCode::Char('i') => self.mode = InputMode::Serial,
+                KeyCode::Char('k') => self.mode = InputMode::Keyboard,
+                KeyCode::Char('v') => self.show_display = !self.show_display,
+                KeyCode::Char(':') => self.mode = InputMode::Command,
+                KeyCode::PageUp => {
+                    self.set_memory_base(self.memory_base.saturating_sub(Self::MEMORY_PAGE))
+                }
+                KeyCode::PageDown => {
+                    self.set_memory_base(self.memory_base.saturating_add(Self::MEMORY_PAGE))
+                }
+                _ => (),
+            },
+            InputMode::Keyboard => {
+                if key.code == KeyCode::Esc {
+                    self.mode = InputMode::Normal;
+                } else if let Some(code) = keyboard_code(key.code) {
+                    self.send(MachineCommand::KeyInput(code));
+                }
+            }
+            InputMode::Command | InputMode::Serial => match key.code {
+                KeyCode::Esc => {
+                    self.input.clear();
+                    self.mode = InputMode::Normal;
+                }
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Char(c) => self.input.push(c),
+                KeyCode::Enter => {
+                    let line = std::mem::take(&mut self.input);
+                    if self.mode == InputMode::Command {
+                        self.mode = InputMode::Normal;
+                        if let Err(e) = self.command(&line) {
+                            self.message(&format!("error: {e}"));
+                        }
+                    } else {
+                        self.send(MachineCommand::SerialInput(line));
+                    }
+                }
+                _ => (),
+            },
+        }
+    }
+
+    /// Executes a single command entered at the command prompt
+    fn command(&mut self, line: &str) -> Result<(), String> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let Some(cmd) = words.first() else {
+            return Ok(());
+        };
+        let arg_loc = |i: usize| words.get(i).map(|s| self.program.parse_loc(s)).transpose();
+
+        match *cmd {
+            "b" | "break" => {
+                let addr = arg_loc(1)?.ok_or("break requires a location")?;
+                self.send(MachineCommand::AddBreakpoint(addr));
+            }
+            "d" | "delete" => {
+                let addr = arg_loc(1)?.ok_or("delete requires a location")?;
+                self.send(MachineCommand::RemoveBreakpoint(addr));
+            }
+            "x" | "mem" => {
+                let addr = arg_loc(1)?.ok_or("mem requires a location")?;
+                self.set_memory_base(addr - addr % Processor::BYTES_PER_WORD);
+            }
+            "reg" => {
+                let index = words.get(1).ok_or("reg requires a register")?;
+                let reg = index
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| Register::try_from(i).ok())
+                    .ok_or_else(|| format!("invalid register '{index}'"))?;
+                let val = arg_loc(2)?.ok_or("reg requires a value")?;
+                self.send(MachineCommand::SetRegister(reg, val));
+            }
+            "s" | "step" => {
+                let count = match words.get(1) {
+                    Some(s) => s.parse().map_err(|_| format!("invalid count '{s}'"))?,
+                    None => 1,
+                };
+                self.step(count);
+            }
+            "back" => {
+                let count = match words.get(1) {
+                    Some(s) => s.parse().map_err(|_| format!("invalid count '{s}'"))?,
+                    None => 1,
+                };
+                self.step_back(count);
+            }
+            "disk" => match words.get(1) {
+                Some(path) => self.send(MachineCommand::AttachDisk(PathBuf::from(path))),
+                None => self.send(MachineCommand::DetachDisk),
+            },
+            "devices" => self.send(MachineCommand::ListDevices),
+            "gpio" => {
+                let pin = words.get(1).ok_or("gpio requires a pin")?;
+                let pin = pin
+                    .parse::<u8>()
+                    .map_err(|_| format!("invalid pin '{pin}'"))?;
+                let high = match words.get(2) {
+                    Some(&"1") => true,
+                    Some(&"0") => false,
+                    _ => return Err("gpio requires a level of 0 or 1".into()),
+                };
+                self.send(MachineCommand::SetGpioInput(pin, high));
+            }
+            "pace" => match words.get(1) {
+                Some(&"off") => {
+                    self.set_frame_rate(None);
+                    self.message("frame pacing disabled");
+                }
+                Some(s) => {
+                    let rate = s
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|r| *r > 0)
+                        .ok_or_else(|| format!("invalid frame rate '{s}'"))?;
+                    self.set_frame_rate(Some(rate));
+                    self.message(&format!("pacing to {rate} frames per second"));
+                }
+                None => {
+                    let state = self
+                        .engine
+                        .query_state(Self::RESPONSE_TIMEOUT)
+                        .ok_or("no response from the engine")?;
+                    match state.speed {
+                        Speed::FramesPerSecond(rate) => self.message(&format!(
+                            "pacing to {rate} frames per second, {} frames skipped",
+                            state.frames_skipped
+                        )),
+                        _ => self.message("frame pacing disabled"),
+                    }
+                }
+            },
+            "reset" => self.reset(),
+            "h" | "help" => self.message(HELP),
+            "q" | "quit" => self.quit = true,
+            c => return Err(format!("unknown command '{c}', see 'help'")),
+        }
+
+        Ok(())
+    }
+}
+
+/// Provides the keyboard device code for the key, if the key is supported by the device
+fn keyboard_code(key: KeyCode) -> Option<u8> {
+    match key {
+        KeyCode::Up => Some(KeyboardDevice::KEY_UP),
+        KeyCode::Down => Some(KeyboardDevice::KEY_DOWN),
+        KeyCode::Left => Some(KeyboardDevice::KEY_LEFT),
+        KeyCode::Right => Some(KeyboardDevice::KEY_RIGHT),
+        KeyCode::Backspace => Some(KeyboardDevice::KEY_BACKSPACE),
+        KeyCode::Tab => Some(KeyboardDevice::KEY_TAB),
+        KeyCode::Delete => Some(KeyboardDevice::KEY_DELETE),
+        KeyCode::Enter => jib::text::character_to_byte('\n').ok(),
+        KeyCode::Char(c) if c.is_ascii() => jib::text::character_to_byte(c).ok(),
+        _ => None,
+    }
+}
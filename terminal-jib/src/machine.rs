@@ -0,0 +1,464 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::mpsc::Sender,
+    time::{Duration, Instant},
+};
+
+use jib::{
+    cpu::{Processor, ProcessorError, Register, RegisterManager, StopReason},
+    debug_info::DebugInfo,
+    device::{
+        DisplayScreen, FileBlockStorage, InputRecordDevice, PlaybackScript, SerialTcpBridge,
+        SerialTcpEvent,
+    },
+    memory::MemoryImage,
+};
+use jib_asm::config::DeviceConfig;
+use jib_asm::disassemble::{disassemble_range, DisassembledWord};
+use jib_asm::engine::EngineMachine;
+use jib_asm::machine::{MachineBuilder, StandardDevices};
+use jib_asm::unwind::{unwind, Backtrace, Symbolizer};
+
+/// Provides the labels and source lines of the program, used to parse and describe locations
+#[derive(Clone)]
+pub struct Program {
+    pub labels: HashMap<String, u32>,
+    pub symbols: Symbolizer,
+    pub debug_info: DebugInfo,
+}
+
+impl Program {
+    pub fn new(debug_info: DebugInfo) -> Self {
+        let labels = debug_info.labels.clone().into_iter().collect();
+        Self {
+            symbols: Symbolizer::new(&labels),
+            labels,
+            debug_info,
+        }
+    }
+
+    /// Parses an address or value, given as a label name or a decimal or hexadecimal number
+    pub fn parse_loc(&self, s: &str) -> Result<u32, String> {
+        if let Some(addr) = self.labels.get(s) {
+            Ok(*addr)
+        } else if let Some(hex) = s.strip_prefix("0x") {
+            u32::from_str_radix(hex, 16).map_err(|_| format!("invalid address '{s}'"))
+        } else {
+            s.parse::<u32>()
+                .map_err(|_| format!("unknown label or address '{s}'"))
+        }
+    }
+
+    pub fn label_for(&self, addr: u32) -> Option<&str> {
+        self.symbols.exact(addr)
+    }
+
+    pub fn describe_location(&self, addr: u32) -> String {
+        match self.label_for(addr) {
+            Some(l) => format!("0x{addr:08x} <{l}>"),
+            None => format!("0x{addr:08x}"),
+        }
+    }
+}
+
+/// Provides the state of the machine shown by the front-end
+pub struct MachineView {
+    pub registers: RegisterManager,
+    pub pc: u32,
+    pub cycles: u64,
+    pub breakpoints: Vec<u32>,
+    /// The words disassembled from before the program counter
+    pub disassembly: Vec<DisassembledWord>,
+    pub memory_base: u32,
+    /// The bytes of memory from the memory base, where unmapped addresses are None
+    pub memory: Vec<Option<u8>>,
+    pub display: DisplayScreen,
+    /// The levels of the GPIO pins as binary digits, with pin 0 last, showing the driven level
+    /// of outputs and the host level of inputs
+    pub gpio_levels: String,
+}
+
+/// Provides the updates sent by the machine to the front-end
+pub enum MachineUpdate {
+    View(Box<MachineView>),
+    /// Provides text written to the serial output
+    Console(String),
+    Message(String),
+    /// Provides the serial input and key presses provided since the last reset, as a playback
+    /// script, sent as the machine is dropped
+    Recording(PlaybackScript),
+}
+
+/// Provides the commands carried out by the machine on the engine thread
+pub enum MachineCommand {
+    Reset,
+    StepBack(usize),
+    /// Adds a breakpoint at the address, or removes it if already present
+    ToggleBreakpoint(u32),
+    AddBreakpoint(u32),
+    RemoveBreakpoint(u32),
+    /// Sets the register, which is rejected while running
+    SetRegister(Register, u32),
+    /// Pushes the text into the serial input buffer, followed by a newline
+    SerialInput(String),
+    KeyInput(u8),
+    /// Attaches the disk image file to the block storage device, replacing any attached disk
+    AttachDisk(PathBuf),
+    DetachDisk,
+    SetGpioInput(u8, bool),
+    ListDevices,
+    /// Sets the base address of the memory provided in the view
+    ShowMemory(u32),
+}
+
+/// Provides the processor and the standard devices attached to it, run on the engine thread and
+/// sending updates to the front-end
+pub struct Machine {
+    cpu: Processor,
+    program: Program,
+    image: MemoryImage,
+    devices: StandardDevices,
+    input_record_dev: Rc<RefCell<InputRecordDevice>>,
+    serial_bridge: Option<SerialTcpBridge>,
+    updates: Sender<MachineUpdate>,
+    memory_base: u32,
+    changed: bool,
+    last_view: Instant,
+}
+
+impl Machine {
+    const MAX_BACKTRACE: usize = 16;
+    const HISTORY_LIMIT: usize = 10_000;
+
+    /// The host time between each view sent without a change
+    const VIEW_TIME: Duration = Duration::from_millis(50);
+
+    /// The number of words provided in the disassembly of the view, and the number of those
+    /// before the program counter
+    const DISASSEMBLY_WORDS: usize = 64;
+    const DISASSEMBLY_BEFORE: u32 = 16;
+
+    /// The number of bytes of memory provided in the view
+    const MEMORY_BYTES: usize = 0x100;
+
+    pub fn new(
+        image: MemoryImage,
+        program: Program,
+        devices: &DeviceConfig,
+        updates: Sender<MachineUpdate>,
+    ) -> Self {
+        let devices = StandardDevices::new(devices.host_time_device());
+        Self {
+            cpu: Processor::new(),
+            image,
+            program,
+            input_record_dev: Rc::new(RefCell::new(
+                InputRecordDevice::new(devices.serial.clone())
+                    .with_keyboard(devices.keyboard.clone()),
+            )),
+            devices,
+            serial_bridge: None,
+            updates,
+            memory_base: 0,
+            changed: true,
+            last_view: Instant::now(),
+        }
+    }
+
+    pub fn message(&self, msg: String) {
+        // The front-end may have exited before the engine, in which case updates are dropped
+        let _ = self.updates.send(MachineUpdate::Message(msg));
+    }
+
+    /// Rebuilds the processor and memory map, reloading the program while retaining breakpoints
+    pub fn reset(&mut self) -> Result<(), ProcessorError> {
+        let breakpoints = self.cpu.breakpoints().collect::<Vec<_>>();
+
+        self.cpu = Processor::new();
+        self.cpu.set_history_limit(Self::HISTORY_LIMIT);
+        for brk in breakpoints {
+            self.cpu.add_breakpoint(brk);
+        }
+
+        self.devices.reset();
+
+        MachineBuilder::standard(&self.devices).build(&mut self.cpu)?;
+
+        // Input is recorded from the reset, such that the recording replays from startup
+        self.input_record_dev = Rc::new(RefCell::new(
+            InputRecordDevice::new(self.devices.serial.clone())
+                .with_keyboard(self.devices.keyboard.clone()),
+        ));
+        self.cpu.device_add(self.input_record_dev.clone())?;
+
+        self.cpu.load_image(&self.image)
+    }
+
+    fn serial_input(&mut self, s: &str) -> Result<(), String> {
+        for c in s.chars().chain(['\n']) {
+            let word = jib::text::character_to_byte(c).map_err(|e| e.to_string())?;
+            if !self.input_record_dev.borrow_mut().push_input(word) {
+                return Err("serial input buffer full".into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Attaches the disk image file to the block storage device, replacing any attached disk
+    pub fn attach_disk(&mut self, path: &Path) -> Result<(), String> {
+        let disk = FileBlockStorage::open(path)
+            .map_err(|e| format!("unable to open disk {} - {e}", path.display()))?;
+        self.devices.disk.borrow_mut().attach(Box::new(disk));
+        Ok(())
+    }
+
+    /// Bridges the serial device to clients connecting to the provided address, providing the
+    /// address listened on
+    pub fn listen_serial(&mut self, addr: &str) -> Result<String, String> {
+        let bridge = SerialTcpBridge::bind(addr)
+            .map_err(|e| format!("unable to listen for serial clients on {addr} - {e}"))?;
+        let local = bridge.local_addr().map_err(|e| e.to_string())?;
+        self.serial_bridge = Some(bridge);
+        Ok(local.to_string())
+    }
+
+    fn toggle_breakpoint(&mut self, addr: u32) {
+        let loc = self.program.describe_location(addr);
+        if self.cpu.remove_breakpoint(addr) {
+            self.message(format!("breakpoint removed at {loc}"));
+        } else {
+            self.cpu.add_breakpoint(addr);
+            self.message(format!("breakpoint added at {loc}"));
+        }
+    }
+
+    fn step_back(&mut self, count: usize) -> Result<(), String> {
+        for _ in 0..count {
+            match self.cpu.step_back() {
+                Ok(true) => (),
+                Ok(false) => return Err("no instructions to undo".into()),
+                Err(e) => return Err(format!("unable to step back - {e}")),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn device_lines(&self) -> Vec<String> {
+        self.cpu
+            .devices()
+            .map(|d| match d.base {
+                Some(base) => format!(
+                    "{:<10} id {:<3} 0x{base:08x}-0x{:08x}",
+                    d.name,
+                    d.device_id,
+                    base as u64 + d.size as u64 - 1
+                ),
+                None => format!("{:<10} id {:<3} unmapped", d.name, d.device_id),
+            })
+            .collect()
+    }
+
+    fn run_command(&mut self, cmd: MachineCommand, running: &mut bool) -> Result<(), String> {
+        match cmd {
+            MachineCommand::Reset => {
+                *running = false;
+                self.reset().map_err(|e| format!("unable to reset - {e}"))?;
+                self.message("reset".into());
+            }
+            MachineCommand::StepBack(count) => {
+                *running = false;
+                self.step_back(count)?;
+            }
+            MachineCommand::ToggleBreakpoint(addr) => self.toggle_breakpoint(addr),
+            MachineCommand::AddBreakpoint(addr) => {
+                if self.cpu.add_breakpoint(addr) {
+                    let loc = self.program.describe_location(addr);
+                    self.message(format!("breakpoint added at {loc}"));
+                }
+            }
+            MachineCommand::RemoveBreakpoint(addr) => {
+                if !self.cpu.remove_breakpoint(addr) {
+                    return Err(format!(
+                        "no breakpoint at {}",
+                        self.program.describe_location(addr)
+                    ));
+                }
+            }
+            MachineCommand::SetRegister(reg, val) => {
+                if *running {
+                    return Err("registers may only be set while stopped".into());
+                }
+
+                self.cpu.set_register(reg, val).map_err(|e| e.to_string())?;
+                self.message(format!("register {reg} set to 0x{val:08x}"));
+            }
+            MachineCommand::SerialInput(s) => self.serial_input(&s)?,
+            MachineCommand::KeyInput(key) => {
+                if !self.input_record_dev.borrow_mut().push_key(key) {
+                    return Err("keyboard buffer full".into());
+                }
+            }
+            MachineCommand::AttachDisk(path) => {
+                self.attach_disk(&path)?;
+                self.message(format!("disk {} attached", path.display()));
+            }
+            MachineCommand::DetachDisk => {
+                if self.devices.disk.borrow_mut().detach().is_none() {
+                    return Err("no disk attached".into());
+                }
+                self.message("disk detached".into());
+            }
+            MachineCommand::SetGpioInput(pin, high) => {
+                if !self.devices.gpio.borrow_mut().set_input(pin, high) {
+                    return Err(format!("no gpio pin {pin}"));
+                }
+            }
+            MachineCommand::ListDevices => {
+                for l in self.device_lines() {
+                    self.message(l);
+                }
+            }
+            MachineCommand::ShowMemory(base) => self.memory_base = base,
+        }
+
+        Ok(())
+    }
+
+    /// Moves any pending serial output to the console, or to the connected serial client, and
+    /// sends any log messages produced by the program
+    fn flush_devices(&mut self) {
+        if let Some(bridge) = self.serial_bridge.as_mut() {
+            for event in bridge.pump(&mut self.devices.serial.borrow_mut()) {
+                self.message(match event {
+                    SerialTcpEvent::Connected(addr) => format!("serial client {addr} connected"),
+                    SerialTcpEvent::Disconnected(addr) => {
+                        format!("serial client {addr} disconnected")
+                    }
+                });
+            }
+        }
+
+        let mut console = String::new();
+        while let Some(w) = self.devices.serial.borrow_mut().pop_output() {
+            console.push(jib::text::byte_to_character(w).unwrap_or('?'));
+        }
+        if !console.is_empty() {
+            let _ = self.updates.send(MachineUpdate::Console(console));
+        }
+
+        while let Some(entry) = self.devices.log.borrow_mut().pop_entry() {
+            self.message(match entry.read_message(&self.cpu) {
+                Ok(m) => format!("[{}] {m}", entry.level),
+                Err(e) => format!(
+                    "[{}] unable to read log message at 0x{:08x} => {e}",
+                    entry.level, entry.address
+                ),
+            });
+        }
+    }
+
+    /// Provides the current state of the machine shown by the front-end
+    fn view(&self) -> MachineView {
+        let pc = self.cpu.get_current_pc().unwrap_or_default();
+        let before = pc.min(Self::DISASSEMBLY_BEFORE * Processor::BYTES_PER_WORD);
+
+        let memory = (0..Self::MEMORY_BYTES as u32)
+            .map(|i| {
+                let addr = self.memory_base.checked_add(i)?;
+                self.cpu.memory_inspect(addr).ok()
+            })
+            .collect();
+
+        let gpio = self.devices.gpio.borrow();
+        let levels = gpio.outputs() | gpio.inputs();
+
+        MachineView {
+            registers: self.cpu.get_register_state(),
+            pc,
+            cycles: self.cpu.cycle_count(),
+            breakpoints: self.cpu.breakpoints().collect(),
+            disassembly: disassemble_range(&self.cpu, pc - before, Self::DISASSEMBLY_WORDS),
+            memory_base: self.memory_base,
+            memory,
+            display: self.devices.display.borrow().screen(),
+            gpio_levels: format!("{levels:0width$b}", width = gpio.pin_count() as usize),
+        }
+    }
+
+    fn stop_message(&self, reason: &StopReason) -> Option<String> {
+        match reason {
+            StopReason::BudgetExhausted => None,
+            StopReason::Halted => Some("halted".into()),
+            StopReason::DebugHalt => Some("halted by debug port".into()),
+            StopReason::Breakpoint(addr) => Some(format!(
+                "breakpoint at {}",
+                self.program.describe_location(*addr)
+            )),
+            StopReason::Error(e) => Some(self.error_message(e.clone())),
+        }
+    }
+
+    fn error_message(&self, e: ProcessorError) -> String {
+        let frames = unwind(&self.cpu, Self::MAX_BACKTRACE);
+        let backtrace = Backtrace {
+            frames: &frames,
+            symbols: &self.program.symbols,
+        };
+        match self.devices.trap_info.borrow().last_trap() {
+            Some(trap) => format!("{e}\n{trap}{backtrace}"),
+            None => format!("{e}\n{backtrace}"),
+        }
+    }
+}
+
+impl EngineMachine for Machine {
+    type Command = MachineCommand;
+
+    fn processor(&mut self) -> &mut Processor {
+        &mut self.cpu
+    }
+
+    fn command(&mut self, cmd: MachineCommand, running: &mut bool) {
+        if let Err(e) = self.run_command(cmd, running) {
+            self.message(format!("error: {e}"));
+        }
+        self.changed = true;
+    }
+
+    fn frame_cycles(&mut self) -> u64 {
+        self.devices.display.borrow().frame_cycles() as u64
+    }
+
+    /// Sends any device output, along with the view once changed by a command or stop, or
+    /// periodically otherwise
+    fn service(&mut self) {
+        self.flush_devices();
+
+        if self.changed || self.last_view.elapsed() >= Self::VIEW_TIME {
+            let _ = self
+                .updates
+                .send(MachineUpdate::View(Box::new(self.view())));
+            self.changed = false;
+            self.last_view = Instant::now();
+        }
+    }
+
+    fn stopped(&mut self, reason: &StopReason) {
+        if let Some(msg) = self.stop_message(reason) {
+            self.message(msg);
+        }
+        self.changed = true;
+    }
+}
+
+impl Drop for Machine {
+    fn drop(&mut self) {
+        let script = self.input_record_dev.borrow().script().clone();
+        let _ = self.updates.send(MachineUpdate::Recording(script));
+    }
+}
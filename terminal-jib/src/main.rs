@@ -0,0 +1,156 @@
+mod app;
+mod machine;
+mod ui;
+
+use std::{path::PathBuf, sync::mpsc, time::Duration};
+
+use clap::Parser;
+use jib_asm::{
+    config::ProjectConfig,
+    engine::{Engine, EngineEvent, Speed},
+    object::parse_address,
+};
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+
+use crate::app::App;
+use crate::machine::{Machine, Program};
+
+/// Runs a program within a terminal front-end, providing registers, disassembly, memory, serial
+/// console, and breakpoint panels. The layout, entry label, and devices of any scpu.toml project
+/// file within the working directory or its parents are applied
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// The program to run, either an assembly file, a raw memory image with a .bin extension,
+    /// or a segmented memory image with a .jimg extension. Labels and source lines for memory
+    /// images are read from any debug information written alongside by jasm --debug-info
+    input: PathBuf,
+
+    /// The number of processor cycles run per second of host time while running, where zero
+    /// runs the processor as fast as the host allows
+    #[arg(short, long, value_name = "HZ", default_value_t = 200_000)]
+    cycles_per_second: u64,
+
+    /// A disk image file to attach to the block storage device
+    #[arg(long)]
+    disk: Option<PathBuf>,
+
+    /// An address to listen on for a telnet-style TCP client, which is bridged to the serial
+    /// device in place of the console while connected
+    #[arg(long, value_name = "ADDR")]
+    serial_tcp: Option<String>,
+
+    /// Runs the number of display frames per second of host time while running, in place of
+    /// the cycles per second, such that programs run at the same speed on any host
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(1..))]
+    frame_rate: Option<u32>,
+
+    /// Loads the program at the provided base address, moving the program with its relocation
+    /// table, where memory images require a table written alongside by jasm --relocations
+    #[arg(long, value_parser = parse_address)]
+    base: Option<u32>,
+
+    /// Records the serial input and key presses provided since the last reset to the file on
+    /// exit, which may be replayed headlessly by jrun --playback
+    #[arg(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+}
+
+/// Defines the time between display updates
+const TICK: Duration = Duration::from_millis(50);
+
+/// Defines the time to wait for the machine to be built on the engine thread
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn main() {
+    let args = Args::parse();
+
+    let config = match ProjectConfig::current() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let (image, debug_info) = match config.read_program(&args.input, args.base) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{} - {e}", args.input.display());
+            std::process::exit(2);
+        }
+    };
+
+    // The processor and its devices are built on the engine thread, as they may not be moved
+    // between threads
+    let program = Program::new(debug_info);
+    let machine_program = program.clone();
+    let devices = config.devices.clone();
+    let disk = args.disk.or(config.devices.disk);
+    let serial_tcp = args.serial_tcp.or(config.devices.serial_tcp);
+    let (tx_updates, updates) = mpsc::channel();
+    let engine = Engine::spawn(move || {
+        let mut machine = Machine::new(image, machine_program, &devices, tx_updates);
+        if let Some(p) = &disk {
+            machine.attach_disk(p)?;
+        }
+
+        if let Some(addr) = &serial_tcp {
+            let local = machine.listen_serial(addr)?;
+            machine.message(format!("serial clients accepted on {local}"));
+        }
+
+        machine
+            .reset()
+            .map_err(|e| format!("Unable to initialize processor - {e}"))?;
+        Ok(machine)
+    });
+
+    // Any error building the machine is reported before the engine exits
+    if engine.query_state(STARTUP_TIMEOUT).is_none() {
+        while let Ok(event) = engine.try_event() {
+            if let EngineEvent::Error(e) = event {
+                eprintln!("{e}");
+            }
+        }
+        std::process::exit(2);
+    }
+
+    let speed = match args.cycles_per_second {
+        0 => Speed::Unlimited,
+        hz => Speed::CyclesPerSecond(hz),
+    };
+    let mut app = App::new(engine, updates, program, speed);
+    app.set_frame_rate(args.frame_rate);
+
+    let mut terminal = ratatui::init();
+    let res = (|| -> std::io::Result<()> {
+        while !app.quit {
+            app.update();
+            terminal.draw(|f| ui::draw(f, &app))?;
+
+            if event::poll(TICK)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        app.handle_key(key);
+                    }
+                }
+            }
+        }
+        Ok(())
+    })();
+    ratatui::restore();
+
+    if let Err(e) = res {
+        eprintln!("Terminal error - {e}");
+        std::process::exit(1);
+    }
+
+    let recording = app.finish();
+    if let (Some(p), Some(script)) = (&args.record, recording) {
+        if let Err(e) = std::fs::write(p, script.to_text()) {
+            eprintln!("Unable to write {} - {e}", p.display());
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use jib::{
+    cpu::{Processor, Register, RegisterManager},
+    device::TextDisplayDevice,
+};
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    Frame,
+};
+
+use crate::app::{App, InputMode};
+use crate::machine::MachineView;
+
+/// Defines the number of bytes shown in each row of the memory panel
+const MEMORY_COLUMNS: u32 = 8;
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [main, input] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(3)]).areas(frame.area());
+    let [left, middle, right] = Layout::horizontal([
+        Constraint::Length(40),
+        Constraint::Min(40),
+        Constraint::Length(50),
+    ])
+    .areas(main);
+    let [registers, breakpoints] = Layout::vertical([
+        Constraint::Length(RegisterManager::REGISTER_COUNT as u16 / 2 + 2),
+        Constraint::Min(0),
+    ])
+    .areas(left);
+    let [memory, console, messages] = Layout::vertical([
+        Constraint::Length(18),
+        Constraint::Min(5),
+        Constraint::Length(10),
+    ])
+    .areas(right);
+
+    // The machine panels are left empty until the first view is provided by the engine
+    let view = app.view.as_deref();
+    if let Some(view) = view {
+        draw_registers(frame, view, registers);
+        draw_breakpoints(frame, app, view, breakpoints);
+        if app.show_display {
+            draw_display(frame, view, middle);
+        } else {
+            draw_disassembly(frame, app, view, middle);
+        }
+        draw_memory(frame, view, memory);
+    }
+    draw_tail(frame, "Serial Console", app.console.lines(), console);
+    draw_tail(
+        frame,
+        "Messages",
+        app.messages.iter().map(|s| s.as_str()),
+        messages,
+    );
+
+    let (title, prefix) = match app.mode {
+        InputMode::Normal => ("Input (: command, i serial)", ""),
+        InputMode::Command => ("Command", ":"),
+        InputMode::Serial => ("Serial Input (esc to leave)", "> "),
+        InputMode::Keyboard => ("Keyboard (keys sent to the device, esc to leave)", ""),
+    };
+    let status = if app.running { "running" } else { "stopped" };
+    let block = match view {
+        Some(view) => Block::bordered().title(title).title_bottom(format!(
+            " {status} - cycle {} - gpio {} ",
+            view.cycles, view.gpio_levels
+        )),
+        None => Block::bordered().title(title),
+    };
+    frame.render_widget(
+        Paragraph::new(format!("{prefix}{}", app.input)).block(block),
+        input,
+    );
+}
+
+fn draw_registers(frame: &mut Frame, view: &MachineView, area: Rect) {
+    let regs = view.registers.get_state();
+    let half = regs.len() / 2;
+
+    let name = |i: usize| match Register::try_from(i) {
+        Ok(r) => r.to_string(),
+        Err(_) => i.to_string(),
+    };
+
+    let lines = (0..half)
+        .map(|i| {
+            Line::from(format!(
+                "{:>7} {:08x}  {:>7} {:08x}",
+                name(i),
+                regs[i],
+                name(i + half),
+                regs[i + half]
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Registers")),
+        area,
+    );
+}
+
+fn draw_breakpoints(frame: &mut Frame, app: &App, view: &MachineView, area: Rect) {
+    let mut brks = view.breakpoints.clone();
+    brks.sort();
+
+    let lines = brks
+        .into_iter()
+        .map(|b| Line::from(app.program.describe_location(b)))
+        .collect::<Vec<_>>();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Breakpoints")),
+        area,
+    );
+}
+
+/// Shows the instructions around the program counter, with a label line before each labelled
+/// address and the source line of each instruction, marking the program counter and any
+/// breakpoints
+fn draw_disassembly(frame: &mut Frame, app: &App, view: &MachineView, area: Rect) {
+    let rows = area.height.saturating_sub(2) as usize;
+    let pc = view.pc;
+    let start = pc.saturating_sub((rows as u32 / 3) * Processor::BYTES_PER_WORD);
+
+    let mut lines = Vec::new();
+    let words = view.disassembly.iter().skip_while(|w| w.address < start);
+    for w in words.take(rows) {
+        if let Some(l) = app.program.label_for(w.address) {
+            lines.push(Line::from(format!("{l}:")));
+        }
+
+        let marker = if w.address == pc {
+            "=>"
+        } else if view.breakpoints.contains(&w.address) {
+            " *"
+        } else {
+            "  "
+        };
+
+        let line = match app.program.debug_info.source_line(w.address) {
+            Some((file, n)) => Line::from(format!("{marker} {w}  ; {file}:{n}")),
+            None => Line::from(format!("{marker} {w}")),
+        };
+        lines.push(if w.address == pc {
+            line.style(Style::default().add_modifier(Modifier::REVERSED))
+        } else {
+            line
+        });
+    }
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Disassembly")),
+        area,
+    );
+}
+
+/// Shows the contents of the text display, with the colors provided by each cell attribute
+fn draw_display(frame: &mut Frame, view: &MachineView, area: Rect) {
+    let screen = &view.display;
+    let color = |i: u8| {
+        let (r, g, b) = TextDisplayDevice::PALETTE[i as usize];
+        Color::Rgb(r, g, b)
+    };
+
+    let lines = (0..screen.rows)
+        .map(|r| {
+            Line::from(
+                screen
+                    .row(r)
+                    .iter()
+                    .map(|c| {
+                        Span::styled(
+                            c.to_char().to_string(),
+                            Style::default()
+                                .fg(color(c.foreground()))
+                                .bg(color(c.background())),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Display")),
+        area,
+    );
+}
+
+fn draw_memory(frame: &mut Frame, view: &MachineView, area: Rect) {
+    let rows = area.height.saturating_sub(2) as usize;
+
+    let lines = view
+        .memory
+        .chunks(MEMORY_COLUMNS as usize)
+        .take(rows)
+        .enumerate()
+        .filter_map(|(r, row)| {
+            let base = view.memory_base.checked_add(r as u32 * MEMORY_COLUMNS)?;
+            let vals = row
+                .iter()
+                .map(|v| match v {
+                    Some(v) => format!("{v:02x}"),
+                    None => "??".into(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            Some(Line::from(format!("{base:08x}  {vals}")))
+        })
+        .collect::<Vec<_>>();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title("Memory")),
+        area,
+    );
+}
+
+/// Shows the last lines of the text that fit within the area
+fn draw_tail<'a>(
+    frame: &mut Frame,
+    title: &str,
+    text: impl DoubleEndedIterator<Item = &'a str>,
+    area: Rect,
+) {
+    let rows = area.height.saturating_sub(2) as usize;
+    let mut lines = text.rev().take(rows).map(Line::from).collect::<Vec<_>>();
+    lines.reverse();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title)),
+        area,
+    );
+}
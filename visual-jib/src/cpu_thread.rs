@@ -1,202 +1,203 @@
-use crate::messages::{ThreadToUi, UiToThread};
-use jib::cpu::{Processor, ProcessorError};
-use jib::device::{InterruptClockDevice, SerialInputOutputDevice};
-use jib::memory::{MemorySegment, ReadOnlySegment, ReadWriteSegment};
-use jib_asm::InstructionList;
-use std::sync::mpsc::{Receiver, RecvError, Sender, TryRecvError};
+use crate::messages::{
+    Disassembly, ThreadToUi, UiToThread, DISASSEMBLY_BEFORE, DISASSEMBLY_LINES, STACK_LINES,
+};
+use jib::cpu::{Processor, ProcessorError, Register, RingBufferTracer, StopReason};
+use jib::device::{
+    FileBlockStorage, InputPlaybackDevice, InputRecordDevice, PlaybackScript, SerialTcpBridge,
+    SerialTcpEvent,
+};
+use jib::memory::{MemoryLayout, MemoryRegion, RegionKind};
+use jib_asm::config::DeviceConfig;
+use jib_asm::disassemble::{disassemble, disassemble_range};
+use jib_asm::engine::{Engine, EngineCommand, EngineEvent, EngineMachine, Speed};
+use jib_asm::machine::{MachineBuilder, StandardDevices};
+use jib_asm::object::LinkedImage;
+use jib_asm::relocate::relocate_program;
+use jib_asm::state_diff::{DiffReport, StateDiff};
+use jib_asm::unwind::{stack_words, unwind, Backtrace, Symbolizer};
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::{Duration, Instant};
 
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::rc::Rc;
 
-struct CircularBuffer<T> {
-    history: Vec<Option<T>>,
-    index: usize,
-}
-
-impl<T: Clone> CircularBuffer<T> {
-    pub fn new(len: usize) -> Self {
-        if len == 0 {
-            panic!("size may not be 0");
-        }
-
-        Self {
-            history: (0..len).map(|_| None).collect(),
-            index: 0,
-        }
-    }
-
-    pub fn reset(&mut self) {
-        self.history.fill(None);
-        self.index = 0;
-    }
-
-    pub fn push(&mut self, val: T) {
-        self.history[self.index] = Some(val);
-        self.index = (self.index + 1) % self.history.len();
-    }
-
-    pub fn list(&self) -> Vec<T> {
-        let mut vals = Vec::new();
-
-        for i in 0..self.history.len() {
-            let iv = (self.index + i) % self.history.len();
-
-            if let Some(t) = self.history[iv].as_ref() {
-                vals.push(t.clone());
-            }
-        }
-
-        vals
-    }
-}
-
+/// Provides the machine run by the engine for the user interface, sending the state of the
+/// processor and its devices back to the interface
 struct ThreadState {
-    running: bool,
-    multiplier: f64,
-    run_thread: bool,
+    tx: Sender<ThreadToUi>,
     memory_request: (u32, u32),
     cpu: Processor,
-    serial_io_dev: Rc<RefCell<SerialInputOutputDevice>>,
-    last_code: Vec<u8>,
-    inst_history: CircularBuffer<String>,
-    inst_map: InstructionList,
-    breakpoint: Option<u32>,
+    devices: StandardDevices,
+    serial_paste: VecDeque<u8>,
+    input_record_dev: Rc<RefCell<InputRecordDevice>>,
+    serial_bridge: Option<SerialTcpBridge>,
+    last_image: LinkedImage,
+    playback: Option<PlaybackScript>,
+    inst_history: Rc<RefCell<RingBufferTracer>>,
+    /// Set once a command or stop changes the state, such that it is sent without waiting for
+    /// the next update
+    changed: bool,
+    last_update: Instant,
+    update_cycles: u64,
 }
 
 impl ThreadState {
-    const DEVICE_START_IND: u32 = 0xA000;
-
-    fn new() -> Result<Self, ProcessorError> {
+    const MAX_BACKTRACE: usize = 16;
+    const HISTORY_LIMIT: usize = 10_000;
+    const HISTORY_LINES: usize = 10;
+    // Host time between each update sent to the interface while the processor runs
+    const UPDATE_TIME: Duration = Duration::from_millis(50);
+    // Number of pasted bytes provided to the serial device per update, such that large pastes
+    // neither overflow the device input buffer nor arrive faster than a typist could provide them
+    const PASTE_BYTES_PER_UPDATE: usize = 64;
+
+    fn new(tx: Sender<ThreadToUi>) -> Result<Self, ProcessorError> {
+        let devices = StandardDevices::new(DeviceConfig::default().host_time_device());
         let mut s = Self {
-            run_thread: true,
-            running: false,
-            multiplier: 1.0,
+            tx,
             cpu: Processor::new(),
-            serial_io_dev: Rc::new(RefCell::new(SerialInputOutputDevice::new(2048))),
-            last_code: Vec::new(),
+            input_record_dev: Rc::new(RefCell::new(
+                InputRecordDevice::new(devices.serial.clone())
+                    .with_keyboard(devices.keyboard.clone()),
+            )),
+            devices,
+            serial_paste: VecDeque::new(),
+            serial_bridge: None,
+            last_image: LinkedImage::default(),
+            playback: None,
             memory_request: (0, 0),
-            inst_history: CircularBuffer::<String>::new(10),
-            inst_map: InstructionList::default(),
-            breakpoint: None,
+            inst_history: Rc::new(RefCell::new(RingBufferTracer::new(Self::HISTORY_LINES))),
+            changed: true,
+            last_update: Instant::now(),
+            update_cycles: 0,
         };
 
         s.reset()?;
         Ok(s)
     }
 
-    fn step_cpu(&mut self, enable_breakpoints: bool) -> Result<(), ThreadToUi> {
-        let mut inst_details = "??".to_string();
+    fn send(&self, msg: ThreadToUi) {
+        // The interface may close before the engine exits, in which case updates are dropped
+        let _ = self.tx.send(msg);
+    }
 
+    /// Provides the most recently executed instructions, followed by the instruction at the
+    /// program counter
+    fn history(&self) -> String {
         let pc = self.cpu.get_current_pc().unwrap_or(0);
-        if let Ok(mem) = self.cpu.get_current_inst() {
-            if let Some(disp_val) = self.inst_map.get_display_inst(mem) {
-                inst_details = disp_val;
-            }
-        }
+        let current = match self.cpu.get_current_inst() {
+            Ok(word) => disassemble(word),
+            Err(_) => "??".to_string(),
+        };
+
+        self.inst_history
+            .borrow()
+            .events()
+            .map(|e| format!("    0x{:08x} = {}", e.pc, disassemble(e.word)))
+            .chain([format!("    0x{pc:08x} = {current}")])
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 
-        inst_details = format!("0x{pc:08x} = {inst_details}");
-        self.inst_history.push(inst_details);
-
-        if let Some(brk) = self.breakpoint {
-            if brk == pc && enable_breakpoints {
-                self.running = false;
-
-                let msg = format!(
-                    "Breaking at 0x{brk:08x}\n{}",
-                    self.inst_history
-                        .list()
-                        .into_iter()
-                        .map(|s| format!("    {s}"))
-                        .collect::<Vec<_>>()
-                        .join("\n")
-                );
-                return Err(ThreadToUi::LogMessage(msg));
+    fn layout(&self) -> MemoryLayout {
+        let mut layout = MemoryLayout::new();
+        layout.add_region(MemoryRegion::new(
+            "vectors",
+            0,
+            MachineBuilder::RAM_START,
+            RegionKind::ReadOnly,
+        ));
+        layout.add_region(MemoryRegion::new(
+            "ram",
+            MachineBuilder::RAM_START,
+            MachineBuilder::RAM_LEN as u32,
+            RegionKind::ReadWrite,
+        ));
+
+        for dev in self.cpu.devices() {
+            if let Some(base) = dev.base {
+                layout.add_region(MemoryRegion::new(
+                    &dev.name,
+                    base,
+                    dev.size,
+                    RegionKind::Device,
+                ));
             }
         }
 
-        if let Err(e) = self.cpu.step() {
-            let msg = format!(
-                "{}\n{}",
-                e,
-                self.inst_history
-                    .list()
-                    .into_iter()
-                    .map(|s| format!("    {s}"))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            );
-
-            Err(ThreadToUi::LogMessage(msg))
-        } else {
-            Ok(())
-        }
+        layout
     }
 
     fn reset(&mut self) -> Result<(), ProcessorError> {
-        const INIT_RO_LEN: u32 = Processor::TOP_VEC_SEG_ADDR;
+        let breakpoints = self.cpu.breakpoints().collect::<Vec<_>>();
 
         self.cpu = Processor::new();
-        self.serial_io_dev.borrow_mut().reset();
-
-        self.inst_history.reset();
-
-        let reset_vec_data: Vec<u8> = (0..INIT_RO_LEN)
-            .map(|i| {
-                let is = i as usize;
-                if is < self.last_code.len() {
-                    self.last_code[is]
-                } else {
-                    0
-                }
-            })
-            .collect();
-        assert!(reset_vec_data.len() == INIT_RO_LEN as usize);
-
-        self.cpu.memory_add_segment(
-            0,
-            Rc::new(RefCell::new(ReadOnlySegment::new(reset_vec_data))),
-        )?;
+        self.cpu.set_history_limit(Self::HISTORY_LIMIT);
+        for brk in breakpoints {
+            self.cpu.add_breakpoint(brk);
+        }
 
-        self.cpu.memory_add_segment(
-            INIT_RO_LEN,
-            Rc::new(RefCell::new(ReadWriteSegment::new(
-                (Self::DEVICE_START_IND - INIT_RO_LEN) as usize,
-            ))),
-        )?;
-        self.cpu
-            .memory_add_segment(Self::DEVICE_START_IND, self.serial_io_dev.clone())?;
+        self.devices.reset();
+        self.serial_paste.clear();
 
-        self.cpu.device_add(self.serial_io_dev.clone())?;
+        self.inst_history.borrow_mut().clear();
+        self.cpu.set_tracer(self.inst_history.clone());
 
-        let dev_interrupt = Rc::new(RefCell::new(InterruptClockDevice::new(0)));
+        MachineBuilder::standard(&self.devices)
+            .vector_table(self.last_image.bytes.clone())
+            .build(&mut self.cpu)?;
 
-        self.cpu.device_add(dev_interrupt.clone())?;
-        self.cpu.memory_add_segment(
-            Self::DEVICE_START_IND + self.serial_io_dev.borrow().len(),
-            dev_interrupt,
-        )?;
+        // Input is recorded from the reset, such that the recording replays from startup
+        self.input_record_dev = Rc::new(RefCell::new(
+            InputRecordDevice::new(self.devices.serial.clone())
+                .with_keyboard(self.devices.keyboard.clone()),
+        ));
+        self.cpu.device_add(self.input_record_dev.clone())?;
 
         self.cpu.reset(jib::cpu::ResetType::Hard)?;
 
-        for (i, val) in self.last_code.iter().enumerate() {
-            if i < INIT_RO_LEN as usize {
-                continue;
-            }
+        let ram_start = MachineBuilder::RAM_START;
+        if let Some(bytes) = self.last_image.bytes.get(ram_start as usize..) {
+            self.cpu.memory_set_range(ram_start, bytes)?;
+        }
 
-            self.cpu.memory_set(i as u32, *val)?;
+        // Playback starts after the reset, such that the reset doesn't clear any input provided
+        // before the first instruction
+        if let Some(script) = &self.playback {
+            self.cpu.device_add(Rc::new(RefCell::new(
+                InputPlaybackDevice::new(script.clone(), self.devices.serial.clone())
+                    .with_keyboard(self.devices.keyboard.clone()),
+            )))?;
         }
 
         Ok(())
     }
 
-    fn handle_msg(&mut self, msg: UiToThread) -> Option<ThreadToUi> {
+    /// Moves the loaded program to the new base address, keeping the current processor state
+    fn relocate(&mut self, base: u32) -> Result<(), String> {
+        let moved = self.last_image.relocated(base).map_err(|e| e.to_string())?;
+        self.layout()
+            .validate_image(0, &moved.bytes)
+            .map_err(|e| e.to_string())?;
+
+        self.last_image =
+            relocate_program(&mut self.cpu, &self.last_image, base).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn handle_msg(&mut self, msg: UiToThread, running: &mut bool) -> Option<ThreadToUi> {
         fn inner_handler(
             state: &mut ThreadState,
             msg: UiToThread,
+            running: &mut bool,
         ) -> Result<Option<ThreadToUi>, ProcessorError> {
             match msg {
                 UiToThread::SetBreakpoint(brk) => {
-                    state.breakpoint = if brk == 0 { None } else { Some(brk) };
+                    state.cpu.clear_breakpoints();
+                    if brk != 0 {
+                        state.cpu.add_breakpoint(brk);
+                    }
                     let msg = if brk == 0 {
                         "Disabling Breakpoint".into()
                     } else {
@@ -204,156 +205,456 @@ impl ThreadState {
                     };
                     return Ok(Some(ThreadToUi::LogMessage(msg)));
                 }
-                UiToThread::CpuStep => {
-                    if let Err(e) = state.step_cpu(false) {
-                        return Ok(Some(e));
+                UiToThread::ToggleBreakpoint(brk) => {
+                    let msg = if state.cpu.remove_breakpoint(brk) {
+                        format!("Removing Breakpoint at 0x{brk:08x}")
+                    } else {
+                        state.cpu.add_breakpoint(brk);
+                        format!("Adding Breakpoint at 0x{brk:08x}")
+                    };
+                    return Ok(Some(ThreadToUi::LogMessage(msg)));
+                }
+                UiToThread::CpuStepBack => {
+                    *running = false;
+                    match state.cpu.step_back() {
+                        Ok(true) => (),
+                        Ok(false) => {
+                            return Ok(Some(ThreadToUi::LogMessage("No steps to undo".into())));
+                        }
+                        Err(e) => {
+                            return Ok(Some(ThreadToUi::LogMessage(format!(
+                                "Unable to step back - {e}"
+                            ))));
+                        }
                     }
                 }
-                UiToThread::CpuStart => state.running = true,
-                UiToThread::CpuStop => state.running = false,
-                UiToThread::Exit => state.run_thread = false,
+                // Carried out by the engine, as sent by the engine bridge
+                UiToThread::CpuStep
+                | UiToThread::CpuStart
+                | UiToThread::CpuStop
+                | UiToThread::SetMultiplier(_)
+                | UiToThread::SetFrameRate(_)
+                | UiToThread::Exit => (),
                 UiToThread::CpuReset => {
                     state.reset()?;
                     return Ok(Some(ThreadToUi::ProcessorReset));
                 }
+                UiToThread::SetPlayback(script) => {
+                    *running = false;
+                    state.playback = script;
+                    state.reset()?;
+                    return Ok(Some(ThreadToUi::ProcessorReset));
+                }
+                UiToThread::SetDisk(path) => {
+                    let msg = match path {
+                        Some(path) => match FileBlockStorage::open(&path) {
+                            Ok(disk) => {
+                                state.devices.disk.borrow_mut().attach(Box::new(disk));
+                                format!("Attached disk image {path}")
+                            }
+                            Err(e) => format!("Unable to open disk image - {e}"),
+                        },
+                        None if state.devices.disk.borrow_mut().detach().is_some() => {
+                            "Detached disk image".into()
+                        }
+                        None => "No disk image attached".into(),
+                    };
+                    return Ok(Some(ThreadToUi::LogMessage(msg)));
+                }
+                UiToThread::SetSerialTcp(addr) => {
+                    let msg = match addr {
+                        Some(addr) => match SerialTcpBridge::bind(&addr) {
+                            Ok(bridge) => {
+                                let msg = match bridge.local_addr() {
+                                    Ok(local) => format!("Serial clients accepted on {local}"),
+                                    Err(_) => format!("Serial clients accepted on {addr}"),
+                                };
+                                state.serial_bridge = Some(bridge);
+                                msg
+                            }
+                            Err(e) => format!("Unable to listen for serial clients - {e}"),
+                        },
+                        None if state.serial_bridge.take().is_some() => {
+                            "Closed serial bridge".into()
+                        }
+                        None => "No serial bridge open".into(),
+                    };
+                    return Ok(Some(ThreadToUi::LogMessage(msg)));
+                }
                 UiToThread::CpuIrq(irq) => {
                     if !state.cpu.trigger_hardware_interrupt(irq as u32)? {
                         return Ok(Some(ThreadToUi::LogMessage(format!(
                             "irq {irq} not triggered"
                         ))));
                     }
+                    state.input_record_dev.borrow_mut().record_irq(irq as u32);
                 }
-                UiToThread::SetMultiplier(m) => {
-                    state.multiplier = m;
+                UiToThread::SaveSnapshot(path) => {
+                    let msg = match std::fs::write(&path, state.cpu.save_state().to_bytes()) {
+                        Ok(()) => format!(
+                            "Saved snapshot at cycle {} to {path}",
+                            state.cpu.cycle_count()
+                        ),
+                        Err(e) => format!("Unable to save snapshot - {e}"),
+                    };
+                    return Ok(Some(ThreadToUi::LogMessage(msg)));
+                }
+                UiToThread::SaveRecording(path) => {
+                    let script = state.input_record_dev.borrow().script().clone();
+                    let msg = match std::fs::write(&path, script.to_text()) {
+                        Ok(()) => {
+                            format!("Saved {} recorded inputs to {path}", script.events().len())
+                        }
+                        Err(e) => format!("Unable to save recording - {e}"),
+                    };
+                    return Ok(Some(ThreadToUi::LogMessage(msg)));
+                }
+                UiToThread::CompareSnapshots(a, b) => {
+                    let diff = StateDiff::new(&a, &b);
+                    let report = DiffReport {
+                        diff: &diff,
+                        symbols: &Symbolizer::new(&state.last_image.labels),
+                    };
+                    return Ok(Some(ThreadToUi::SnapshotDiff(report.to_string())));
                 }
-                UiToThread::SetCode(data) => {
-                    state.running = false;
-                    state.last_code = data;
+                UiToThread::SetCode(image) => {
+                    *running = false;
+                    if let Err(e) = state.layout().validate_image(0, &image.bytes) {
+                        return Ok(Some(ThreadToUi::LogMessage(e.to_string())));
+                    }
+
+                    state.last_image = image;
                     state.reset()?;
                     return Ok(Some(ThreadToUi::ProcessorReset));
                 }
-                UiToThread::SerialInput(s) => {
+                UiToThread::Relocate(base) => {
+                    if *running {
+                        return Ok(Some(ThreadToUi::LogMessage(
+                            "Stop the processor before relocating".into(),
+                        )));
+                    }
+
+                    let msg = match state.relocate(base) {
+                        Ok(()) => format!("Relocated program to 0x{base:08x}"),
+                        Err(e) => format!("Unable to relocate - {e}"),
+                    };
+                    return Ok(Some(ThreadToUi::LogMessage(msg)));
+                }
+                UiToThread::SerialInput(bytes) => {
+                    for b in bytes {
+                        if !state.input_record_dev.borrow_mut().push_input(b) {
+                            return Ok(Some(ThreadToUi::LogMessage(
+                                "device serial input buffer full".to_string(),
+                            )));
+                        }
+                    }
+                }
+                UiToThread::SerialPaste(s) => {
+                    // Carriage returns from host line endings are dropped, leaving the newline
+                    let mut skipped = 0;
+                    for c in s.chars().filter(|c| *c != '\r') {
+                        match jib::text::character_to_byte(c) {
+                            Ok(word) => state.serial_paste.push_back(word),
+                            Err(_) => skipped += 1,
+                        }
+                    }
+
+                    if skipped > 0 {
+                        return Ok(Some(ThreadToUi::LogMessage(format!(
+                            "skipped {skipped} unsupported characters in paste"
+                        ))));
+                    }
+                }
+                UiToThread::MuxInput(channel, s) => {
                     for c in s.chars().chain(['\n'; 1]) {
                         match jib::text::character_to_byte(c) {
                             Ok(word) => {
-                                if !state.serial_io_dev.borrow_mut().push_input(word) {
-                                    return Ok(Some(ThreadToUi::LogMessage(
-                                        "device serial input buffer full".to_string(),
-                                    )));
+                                if !state.devices.mux.borrow_mut().push_input(channel, word) {
+                                    return Ok(Some(ThreadToUi::LogMessage(format!(
+                                        "channel {channel} input buffer full"
+                                    ))));
                                 }
                             }
                             Err(e) => return Ok(Some(ThreadToUi::LogMessage(e.to_string()))),
                         }
                     }
                 }
+                UiToThread::KeyPress(key) => {
+                    if !state.input_record_dev.borrow_mut().push_key(key) {
+                        return Ok(Some(ThreadToUi::LogMessage(
+                            "device keyboard buffer full".to_string(),
+                        )));
+                    }
+                }
                 UiToThread::RequestMemory(base, size) => state.memory_request = (base, size),
+                UiToThread::WriteMemory(addr, val) => {
+                    if *running {
+                        return Ok(Some(ThreadToUi::LogMessage(
+                            "Memory may only be edited while the processor is stopped".into(),
+                        )));
+                    }
+                    state.cpu.memory_set(addr, val)?;
+                }
+                UiToThread::SetRegister(index, val) => {
+                    if *running {
+                        return Ok(Some(ThreadToUi::LogMessage(
+                            "Registers may only be edited while the processor is stopped".into(),
+                        )));
+                    }
+                    state.cpu.set_register(Register::try_from(index)?, val)?;
+                }
             }
 
             Ok(None)
         }
 
-        match inner_handler(self, msg) {
+        match inner_handler(self, msg, running) {
             Ok(resp) => resp,
             Err(e) => Some(ThreadToUi::LogMessage(format!("error: {e}"))),
         }
     }
 }
 
-pub fn cpu_thread(rx: Receiver<UiToThread>, tx: Sender<ThreadToUi>) {
-    let mut state = ThreadState::new().unwrap();
-
-    const THREAD_LOOP_MS: u64 = 50;
-    //const THREAD_LOOP_HZ: u64 = 1000 / THREAD_LOOP_MS;
-
-    'mainloop: while state.run_thread {
-        if state.running {
-            for _ in 0..1000 {
-                let resp = match rx.try_recv() {
-                    Ok(msg) => state.handle_msg(msg),
-                    Err(TryRecvError::Disconnected) => break 'mainloop,
-                    Err(TryRecvError::Empty) => break,
-                };
+impl EngineMachine for ThreadState {
+    type Command = UiToThread;
 
-                if let Some(r) = &resp {
-                    tx.send(r.clone())
-                        .expect("Unable to send response to main thread!");
-                }
+    fn processor(&mut self) -> &mut Processor {
+        &mut self.cpu
+    }
+
+    fn command(&mut self, cmd: UiToThread, running: &mut bool) {
+        if let Some(resp) = self.handle_msg(cmd, running) {
+            self.send(resp);
+        }
+        self.changed = true;
+    }
+
+    fn frame_cycles(&mut self) -> u64 {
+        self.devices.display.borrow().frame_cycles() as u64
+    }
+
+    fn stopped(&mut self, reason: &StopReason) {
+        self.changed = true;
+
+        let msg = match reason {
+            StopReason::BudgetExhausted => return,
+            StopReason::DebugHalt => "Halted by debug port".to_string(),
+            StopReason::Halted => format!(
+                "Halted at 0x{:08x}",
+                self.cpu.get_current_pc().unwrap_or_default()
+            ),
+            StopReason::Breakpoint(brk) => format!("Breaking at 0x{brk:08x}\n{}", self.history()),
+            StopReason::Error(e) => {
+                let frames = unwind(&self.cpu, Self::MAX_BACKTRACE);
+                let backtrace = Backtrace {
+                    frames: &frames,
+                    symbols: &Symbolizer::new(&self.last_image.labels),
+                };
+                let trap = self
+                    .devices
+                    .trap_info
+                    .borrow()
+                    .last_trap()
+                    .map(|t| t.to_string())
+                    .unwrap_or_default();
+                format!(
+                    "{}\n{}{}\nBacktrace:\n{}",
+                    e,
+                    trap,
+                    self.history(),
+                    backtrace
+                )
             }
-        } else {
-            let resp = match rx.recv() {
-                Ok(msg) => state.handle_msg(msg),
-                Err(RecvError) => break 'mainloop,
-            };
+        };
+
+        self.send(ThreadToUi::LogMessage(msg));
+    }
+
+    /// Sends the device output and processor state to the interface once per update while the
+    /// processor runs or pasted input remains, or once changed by a command or stop
+    fn service(&mut self) {
+        let cycles = self.cpu.cycle_count();
+        let idle = cycles == self.update_cycles
+            && self.serial_paste.is_empty()
+            && self.serial_bridge.is_none();
+        if !self.changed && (idle || self.last_update.elapsed() < Self::UPDATE_TIME) {
+            return;
+        }
 
-            if let Some(r) = &resp {
-                tx.send(r.clone())
-                    .expect("Unable to send response to main thread!");
+        self.changed = false;
+        self.last_update = Instant::now();
+        self.update_cycles = cycles;
+
+        // Exchange serial data with any bridged client, which takes the serial output while
+        // connected
+        if let Some(bridge) = self.serial_bridge.as_mut() {
+            for event in bridge.pump(&mut self.devices.serial.borrow_mut()) {
+                let msg = match event {
+                    SerialTcpEvent::Connected(addr) => format!("Serial client {addr} connected"),
+                    SerialTcpEvent::Disconnected(addr) => {
+                        format!("Serial client {addr} disconnected")
+                    }
+                };
+                self.send(ThreadToUi::LogMessage(msg));
             }
         }
 
-        // Check for serial output
-        let mut char_vec = Vec::new();
-        while let Some(w) = state.serial_io_dev.borrow_mut().pop_output() {
-            let c = match jib::text::byte_to_character(w) {
-                Ok(v) => v,
-                Err(e) => {
-                    tx.send(ThreadToUi::LogMessage(format!("{e}"))).unwrap();
-                    '?'
-                }
+        // Provide pasted input while the device has room, leaving the remainder for later updates
+        for _ in 0..Self::PASTE_BYTES_PER_UPDATE {
+            let Some(word) = self.serial_paste.front() else {
+                break;
             };
 
-            char_vec.push(c);
+            if !self.input_record_dev.borrow_mut().push_input(*word) {
+                break;
+            }
+            self.serial_paste.pop_front();
         }
 
-        if !char_vec.is_empty() {
-            tx.send(ThreadToUi::SerialOutput(
-                char_vec.into_iter().collect::<String>(),
-            ))
-            .unwrap();
+        // Check for serial output, provided as raw bytes such that binary output is preserved
+        let mut serial_bytes = Vec::new();
+        while let Some(w) = self.devices.serial.borrow_mut().pop_output() {
+            serial_bytes.push(w);
         }
 
-        // Step if required
-        if state.running {
-            let step_repeat_count = state.multiplier as i64;
+        if !serial_bytes.is_empty() {
+            self.send(ThreadToUi::SerialOutput(serial_bytes));
+        }
 
-            for _ in 0..step_repeat_count {
-                if let Err(msg) = state.step_cpu(true) {
-                    state.running = false;
-                    tx.send(msg).unwrap();
-                    break;
-                }
+        // Check for output on each multiplexed channel
+        let channel_count = self.devices.mux.borrow().channel_count();
+        for channel in 0..channel_count {
+            let mut text = String::new();
+            while let Some(w) = self.devices.mux.borrow_mut().pop_output(channel) {
+                text.push(jib::text::byte_to_character(w).unwrap_or('?'));
+            }
+
+            if !text.is_empty() {
+                self.send(ThreadToUi::MuxOutput(channel, text));
             }
         }
 
+        // Check for display changes
+        if self.devices.display.borrow_mut().take_changed() {
+            self.send(ThreadToUi::DisplayContents(Box::new(
+                self.devices.display.borrow().screen(),
+            )));
+        }
+
+        // Check for log messages
+        while let Some(entry) = self.devices.log.borrow_mut().pop_entry() {
+            let msg = match entry.read_message(&self.cpu) {
+                Ok(m) => m,
+                Err(e) => format!(
+                    "unable to read log message at 0x{:08x} => {e}",
+                    entry.address
+                ),
+            };
+
+            self.send(ThreadToUi::LogMessage(format!("[{}] {msg}", entry.level)));
+        }
+
         // Send Registers
-        tx.send(ThreadToUi::RegisterState(Box::new(
-            state.cpu.get_register_state(),
-        )))
-        .unwrap();
+        self.send(ThreadToUi::RegisterState(Box::new(
+            self.cpu.get_register_state(),
+        )));
 
-        let pc = state
+        let pc = self
             .cpu
             .get_register_state()
             .get(jib::cpu::Register::ProgramCounter)
             .unwrap_or(0);
-        let mem = state.cpu.memory_inspect_u32(pc).unwrap_or(0);
+        let mem = self.cpu.memory_inspect_u32(pc).unwrap_or(0);
+
+        self.send(ThreadToUi::ProgramCounterValue(pc, mem));
 
-        tx.send(ThreadToUi::ProgramCounterValue(pc, mem)).unwrap();
+        // Send the instructions around the program counter
+        let start = pc.saturating_sub(DISASSEMBLY_BEFORE as u32 * Processor::BYTES_PER_WORD);
+        self.send(ThreadToUi::Disassembly(Box::new(Disassembly {
+            pc,
+            words: disassemble_range(&self.cpu, start, DISASSEMBLY_LINES),
+            breakpoints: self.cpu.breakpoints().collect(),
+        })));
+
+        // Send the stack, with the registers saved by each call
+        self.send(ThreadToUi::StackContents(stack_words(
+            &self.cpu,
+            STACK_LINES,
+            Self::MAX_BACKTRACE,
+        )));
 
         // Send memory if needed
-        let (base, size) = state.memory_request;
-        let mut resp_memory = Vec::new();
-        for i in 0..size {
-            resp_memory.push(state.cpu.memory_inspect(base + i).unwrap_or_default());
+        let (base, size) = self.memory_request;
+        let mut resp_memory = vec![0; size as usize];
+        if self
+            .cpu
+            .memory_inspect_range(base, &mut resp_memory)
+            .is_err()
+        {
+            // Fall back to each value in turn, such that unmapped addresses are provided as zero
+            for (i, v) in resp_memory.iter_mut().enumerate() {
+                *v = self
+                    .cpu
+                    .memory_inspect(base.wrapping_add(i as u32))
+                    .unwrap_or_default();
+            }
         }
-        tx.send(ThreadToUi::ResponseMemory(base, resp_memory))
-            .unwrap();
+        self.send(ThreadToUi::ResponseMemory(base, resp_memory));
+    }
+}
 
-        // Final sleep
-        if state.running {
-            std::thread::sleep(std::time::Duration::from_millis(THREAD_LOOP_MS));
+/// Runs the machine for the user interface on the engine, carrying out the processor controls
+/// with engine commands and passing the remaining messages to the machine
+pub fn cpu_thread(rx: Receiver<UiToThread>, tx: Sender<ThreadToUi>) {
+    // Number of simulated cycles run per second for each unit of the speed multiplier
+    const CYCLES_PER_SECOND: f64 = 80.0;
+
+    let machine_tx = tx.clone();
+    let engine = Engine::spawn(move || ThreadState::new(machine_tx).map_err(|e| e.to_string()));
+
+    // Pacing runs a fixed number of frames per second in place of the speed multiplier
+    let mut multiplier = 1.0;
+    let mut frame_rate = None;
+    let speed = |multiplier: f64, frame_rate: Option<u32>| match frame_rate {
+        Some(r) => Speed::FramesPerSecond(r),
+        None => Speed::CyclesPerSecond((multiplier * CYCLES_PER_SECOND) as u64),
+    };
+    engine.send(EngineCommand::SetSpeed(speed(multiplier, frame_rate)));
+
+    for msg in rx.iter() {
+        let cmd = match msg {
+            UiToThread::CpuStep => EngineCommand::Step(1),
+            UiToThread::CpuStart => EngineCommand::Start,
+            UiToThread::CpuStop => EngineCommand::Stop,
+            UiToThread::SetMultiplier(m) => {
+                multiplier = m;
+                EngineCommand::SetSpeed(speed(multiplier, frame_rate))
+            }
+            UiToThread::SetFrameRate(rate) => {
+                frame_rate = rate;
+                let msg = match rate {
+                    Some(r) => format!("Pacing to {r} frames per second"),
+                    None => "Frame pacing disabled".into(),
+                };
+                let _ = tx.send(ThreadToUi::LogMessage(msg));
+                EngineCommand::SetSpeed(speed(multiplier, frame_rate))
+            }
+            UiToThread::Exit => break,
+            msg => EngineCommand::Machine(msg),
+        };
+
+        if !engine.send(cmd) {
+            break;
+        }
+
+        // Stops are reported by the machine, leaving any errors from the engine
+        while let Ok(event) = engine.try_event() {
+            if let EngineEvent::Error(e) = event {
+                let _ = tx.send(ThreadToUi::LogMessage(format!("error: {e}")));
+            }
         }
     }
 
+    drop(engine);
     let _ = tx.send(ThreadToUi::ThreadExit);
 }
@@ -1,16 +1,33 @@
 //use gtk::glib::clone;
 use crate::cpu_thread::cpu_thread;
-use crate::messages::{ThreadToUi, UiToThread};
+use crate::messages::{
+    Disassembly, ThreadToUi, UiToThread, DISASSEMBLY_LINES, MUX_CHANNELS, PACED_FRAME_RATE,
+};
 use gtk::glib::clone;
 use gtk::{Application, ApplicationWindow};
 use gtk::{glib, prelude::*};
-use jib::cpu::RegisterManager;
+use jib::cpu::{Register, RegisterManager};
+use jib::device::{DisplayScreen, TextDisplayDevice};
+use jib_asm::config::ProjectConfig;
+use jib_asm::preprocess::IncludePaths;
+use jib_asm::project::{assemble_project, ObjectCache, ProjectSource};
+use jib_asm::unwind::StackWord;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 pub fn build_ui(app: &Application) {
     // Create the tx/rx for the secondary thread
     let (tx_ui, rx_thread) = std::sync::mpsc::channel::<UiToThread>();
     let (tx_thread, rx_ui) = std::sync::mpsc::channel::<ThreadToUi>();
 
+    // Settings of any project file are applied to the assembler and the attached devices
+    let config = Rc::new(ProjectConfig::current().unwrap_or_else(|e| {
+        tx_thread.send(ThreadToUi::LogMessage(e)).unwrap();
+        ProjectConfig::default()
+    }));
+
     let columns = gtk::Box::builder()
         .orientation(gtk::Orientation::Horizontal)
         .spacing(4)
@@ -20,11 +37,18 @@ pub fn build_ui(app: &Application) {
         .margin_end(4)
         .build();
 
-    columns.append(&build_code_column(&tx_ui, &tx_thread));
-    let (column_cpu, register_fields, text_log) = build_cpu_column(&tx_ui);
+    columns.append(&build_code_column(&tx_ui, &tx_thread, &config));
+    let (column_cpu, register_fields, text_log) = build_cpu_column(&tx_ui, &tx_thread);
+    let disassembly = build_disassembly_frame(&tx_ui);
+    column_cpu.append(&disassembly.frame);
+    let (stack_frame, label_stack) = build_stack_frame();
+    column_cpu.append(&stack_frame);
+    column_cpu.append(&build_snapshot_frame(&tx_ui, &tx_thread));
     columns.append(&column_cpu);
-    let serial_details = build_serial_column(&tx_ui, &tx_thread);
+    let serial_details = build_serial_column(&tx_ui, &tx_thread, &config);
     columns.append(&serial_details.column_serial);
+    let (display_frame, label_display) = build_display_frame();
+    columns.append(&display_frame);
 
     // Create a window and set the title
     let window = ApplicationWindow::builder()
@@ -47,6 +71,7 @@ pub fn build_ui(app: &Application) {
 
     // Create the
     let inst = jib_asm::InstructionList::default();
+    let diff_parent = window.clone();
 
     // Setup the UI receiver
     glib::spawn_future_local(async move {
@@ -54,10 +79,17 @@ pub fn build_ui(app: &Application) {
             match msg {
                 ThreadToUi::ProcessorReset => {
                     serial_details.text_serial.buffer().set_text("");
+                    for t in serial_details.text_channels.iter() {
+                        t.buffer().set_text("");
+                    }
                 }
                 ThreadToUi::RegisterState(regs) => {
                     for (i, r) in regs.registers.iter().enumerate() {
-                        register_fields[i].set_markup(&format!("<tt>0x{:08x}</tt>", r));
+                        // Fields being edited keep the text entered until editing finishes
+                        let field = &register_fields[i];
+                        if !field.state_flags().contains(gtk::StateFlags::FOCUS_WITHIN) {
+                            field.set_text(&format!("0x{:08x}", r));
+                        }
                     }
                 }
                 ThreadToUi::ProgramCounterValue(pc, val) => {
@@ -75,6 +107,8 @@ pub fn build_ui(app: &Application) {
                         .label_instruction
                         .set_markup(&format!("<tt>Mem[0x{:08x}] = 0x{:08x}</tt>", pc, val));
                 }
+                ThreadToUi::Disassembly(view) => disassembly.update(&view),
+                ThreadToUi::StackContents(words) => label_stack.set_text(&stack_text(&words)),
                 ThreadToUi::LogMessage(msg) => {
                     text_log
                         .buffer()
@@ -87,16 +121,18 @@ pub fn build_ui(app: &Application) {
                         0.0,
                     );
                 }
-                ThreadToUi::SerialOutput(msg) => {
-                    let buf = serial_details.text_serial.buffer().clone();
-                    buf.insert(&mut buf.end_iter(), msg.as_str());
-                    serial_details.text_serial.scroll_to_iter(
-                        &mut buf.end_iter(),
-                        0.0,
-                        false,
-                        0.0,
-                        0.0,
-                    );
+                ThreadToUi::SerialOutput(bytes) => {
+                    append_serial(&serial_details.text_serial, &bytes);
+                }
+                ThreadToUi::MuxOutput(channel, msg) => {
+                    if let Some(text) = serial_details.text_channels.get(channel as usize) {
+                        let buf = text.buffer();
+                        buf.insert(&mut buf.end_iter(), msg.as_str());
+                        text.scroll_to_iter(&mut buf.end_iter(), 0.0, false, 0.0, 0.0);
+                    }
+                }
+                ThreadToUi::DisplayContents(screen) => {
+                    label_display.set_markup(&display_markup(&screen));
                 }
                 ThreadToUi::ResponseMemory(base, vals) => {
                     for (i, l) in serial_details.memory.labels.iter().enumerate() {
@@ -107,12 +143,35 @@ pub fn build_ui(app: &Application) {
                     }
 
                     for (i, m) in serial_details.memory.locations.iter().enumerate() {
+                        // Cells being edited keep the text entered until editing finishes
+                        if m.state_flags().contains(gtk::StateFlags::FOCUS_WITHIN) {
+                            continue;
+                        }
+
                         if i < vals.len() {
                             m.set_text(&format!("{:02x}", vals[i]));
                         } else {
                             m.set_text("");
                         }
                     }
+
+                    *serial_details.memory.contents.borrow_mut() = (base, vals);
+                }
+                ThreadToUi::SnapshotDiff(report) => {
+                    let text_diff = gtk::TextView::builder()
+                        .editable(false)
+                        .monospace(true)
+                        .build();
+                    text_diff.buffer().set_text(&report);
+
+                    gtk::Window::builder()
+                        .title("Snapshot Differences")
+                        .transient_for(&diff_parent)
+                        .default_width(900)
+                        .default_height(500)
+                        .child(&gtk::ScrolledWindow::builder().child(&text_diff).build())
+                        .build()
+                        .present();
                 }
                 ThreadToUi::ThreadExit => break,
             };
@@ -141,6 +200,7 @@ pub fn build_ui(app: &Application) {
 fn build_code_column(
     tx_ui: &std::sync::mpsc::Sender<UiToThread>,
     tx_thread: &std::sync::mpsc::Sender<ThreadToUi>,
+    config: &Rc<ProjectConfig>,
 ) -> gtk::Box {
     let code_stack = gtk::Stack::builder().build();
 
@@ -178,20 +238,171 @@ fn build_code_column(
             .spacing(4)
             .build();
         let btn_build = gtk::Button::builder().label(button_verb).build();
+        let file_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(4)
+            .build();
 
         if is_assembly {
+            // The file being edited, used as the source name such that included files are
+            // found relative to it
+            let current_file = Rc::new(RefCell::new(None));
+            if let Some(path) = last_file() {
+                match std::fs::read_to_string(&path) {
+                    Ok(txt) => {
+                        buffer_assembly_code.set_text(&txt);
+                        *current_file.borrow_mut() = Some(path);
+                    }
+                    Err(e) => tx_thread
+                        .send(ThreadToUi::LogMessage(format!(
+                            "Unable to open {} - {e}",
+                            path.display()
+                        )))
+                        .unwrap(),
+                }
+            }
+
+            let dialog = Rc::new(RefCell::new(None));
+
+            let btn_open = gtk::Button::builder().label("Open").hexpand(true).build();
+            btn_open.connect_clicked(clone!(
+                #[strong]
+                tx_thread,
+                #[strong]
+                buffer_assembly_code,
+                #[strong]
+                current_file,
+                #[strong]
+                dialog,
+                move |btn| {
+                    choose_file(
+                        btn,
+                        gtk::FileChooserAction::Open,
+                        &dialog,
+                        clone!(
+                            #[strong]
+                            tx_thread,
+                            #[strong]
+                            buffer_assembly_code,
+                            #[strong]
+                            current_file,
+                            move |path| {
+                                let msg = match std::fs::read_to_string(&path) {
+                                    Ok(txt) => {
+                                        buffer_assembly_code.set_text(&txt);
+                                        remember_file(&path, &tx_thread);
+                                        let msg = format!("Opened {}", path.display());
+                                        *current_file.borrow_mut() = Some(path);
+                                        msg
+                                    }
+                                    Err(e) => format!("Unable to open {} - {e}", path.display()),
+                                };
+                                tx_thread.send(ThreadToUi::LogMessage(msg)).unwrap();
+                            }
+                        ),
+                    );
+                }
+            ));
+
+            let btn_save_as = gtk::Button::builder()
+                .label("Save As")
+                .hexpand(true)
+                .build();
+            btn_save_as.connect_clicked(clone!(
+                #[strong]
+                tx_thread,
+                #[strong]
+                buffer_assembly_code,
+                #[strong]
+                current_file,
+                #[strong]
+                dialog,
+                move |btn| {
+                    choose_file(
+                        btn,
+                        gtk::FileChooserAction::Save,
+                        &dialog,
+                        clone!(
+                            #[strong]
+                            tx_thread,
+                            #[strong]
+                            buffer_assembly_code,
+                            #[strong]
+                            current_file,
+                            move |path| {
+                                if save_file(&buffer_assembly_code, &path, &tx_thread) {
+                                    *current_file.borrow_mut() = Some(path);
+                                }
+                            }
+                        ),
+                    );
+                }
+            ));
+
+            // Saves to the current file, or asks for a file if none has been opened or saved
+            let btn_save = gtk::Button::builder().label("Save").hexpand(true).build();
+            btn_save.connect_clicked(clone!(
+                #[strong]
+                tx_thread,
+                #[strong]
+                buffer_assembly_code,
+                #[strong]
+                current_file,
+                #[strong]
+                btn_save_as,
+                move |_| {
+                    let path = current_file.borrow().clone();
+                    match path {
+                        Some(path) => {
+                            save_file(&buffer_assembly_code, &path, &tx_thread);
+                        }
+                        None => btn_save_as.emit_clicked(),
+                    }
+                }
+            ));
+
+            file_box.append(&btn_open);
+            file_box.append(&btn_save);
+            file_box.append(&btn_save_as);
+
+            // Keeps the object of the last build, such that rebuilding unchanged code does not
+            // require assembling again
+            let cache = Rc::new(RefCell::new(ObjectCache::new()));
+
             btn_build.connect_clicked(clone!(
                 #[strong]
                 tx_thread,
                 #[strong]
                 tx_ui,
+                #[strong]
+                cache,
+                #[strong]
+                config,
+                #[strong]
+                current_file,
                 move |_| {
                     let asm = buffer_assembly_code.text(
                         &buffer_assembly_code.start_iter(),
                         &buffer_assembly_code.end_iter(),
                         false,
                     );
-                    match jib_asm::assemble_text(asm.as_str()) {
+                    let name = match &*current_file.borrow() {
+                        Some(path) => path.display().to_string(),
+                        None => short_name.to_string(),
+                    };
+                    let sources = [ProjectSource {
+                        name,
+                        text: asm.to_string(),
+                    }];
+
+                    let mut cache = cache.borrow_mut();
+                    let image =
+                        assemble_project(&sources, 1, Some(&mut *cache), &IncludePaths::default())
+                            .map_err(|e| e.err)
+                            .and_then(|objs| config.link_image(&objs, &HashMap::new()));
+                    cache.retain_used();
+
+                    match image {
                         Ok(v) => {
                             tx_ui.send(UiToThread::SetCode(v)).unwrap();
                             tx_thread
@@ -218,6 +429,7 @@ fn build_code_column(
             ));
         }
 
+        code_box.append(&file_box);
         code_box.append(&text_code_frame);
         code_box.append(&btn_build);
 
@@ -236,9 +448,100 @@ fn build_code_column(
     column_code
 }
 
+/// Provides the file recording the last assembly file opened or saved in the editor, within
+/// the user configuration directory
+fn last_file_record() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(dir.join("visual-jib").join("last_file"))
+}
+
+/// Provides the last assembly file opened or saved in the editor, if it still exists
+fn last_file() -> Option<PathBuf> {
+    let txt = std::fs::read_to_string(last_file_record()?).ok()?;
+    Some(PathBuf::from(txt.trim_end())).filter(|p| p.is_file())
+}
+
+/// Records the file as the last assembly file, such that it is reopened in the next session
+fn remember_file(path: &Path, tx_thread: &std::sync::mpsc::Sender<ThreadToUi>) {
+    let Some(record) = last_file_record() else {
+        return;
+    };
+
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let res = record
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&record, path.display().to_string()));
+
+    if let Err(e) = res {
+        tx_thread
+            .send(ThreadToUi::LogMessage(format!(
+                "Unable to record last file in {} - {e}",
+                record.display()
+            )))
+            .unwrap();
+    }
+}
+
+/// Writes the editor contents to the file, providing true if successful
+fn save_file(
+    buffer: &gtk::TextBuffer,
+    path: &Path,
+    tx_thread: &std::sync::mpsc::Sender<ThreadToUi>,
+) -> bool {
+    let txt = buffer.text(&buffer.start_iter(), &buffer.end_iter(), false);
+    let (msg, saved) = match std::fs::write(path, txt.as_str()) {
+        Ok(()) => {
+            remember_file(path, tx_thread);
+            (format!("Saved {}", path.display()), true)
+        }
+        Err(e) => (format!("Unable to save {} - {e}", path.display()), false),
+    };
+
+    tx_thread.send(ThreadToUi::LogMessage(msg)).unwrap();
+    saved
+}
+
+/// Shows a file dialog for the assembly editor, calling the handler with the chosen path. The
+/// dialog is kept within the slot while shown, as native dialogs are not otherwise retained
+fn choose_file(
+    widget: &impl IsA<gtk::Widget>,
+    action: gtk::FileChooserAction,
+    slot: &Rc<RefCell<Option<gtk::FileChooserNative>>>,
+    on_path: impl Fn(PathBuf) + 'static,
+) {
+    let (title, accept) = match action {
+        gtk::FileChooserAction::Save => ("Save Assembly", "_Save"),
+        _ => ("Open Assembly", "_Open"),
+    };
+
+    let parent = widget.root().and_downcast::<gtk::Window>();
+    let dialog = gtk::FileChooserNative::new(
+        Some(title),
+        parent.as_ref(),
+        action,
+        Some(accept),
+        Some("_Cancel"),
+    );
+
+    dialog.connect_response(move |d, resp| {
+        if resp == gtk::ResponseType::Accept {
+            if let Some(path) = d.file().and_then(|f| f.path()) {
+                on_path(path);
+            }
+        }
+    });
+
+    dialog.show();
+    *slot.borrow_mut() = Some(dialog);
+}
+
 fn build_cpu_column(
     tx_ui: &std::sync::mpsc::Sender<UiToThread>,
-) -> (gtk::Box, Vec<gtk::Label>, gtk::TextView) {
+    tx_thread: &std::sync::mpsc::Sender<ThreadToUi>,
+) -> (gtk::Box, Vec<gtk::Entry>, gtk::TextView) {
     let column_cpu = gtk::Box::builder()
         .orientation(gtk::Orientation::Vertical)
         .spacing(4)
@@ -265,6 +568,7 @@ fn build_cpu_column(
 
     let cpu_btns = vec![
         ("Step", UiToThread::CpuStep),
+        ("Back", UiToThread::CpuStepBack),
         ("Start", UiToThread::CpuStart),
         ("Stop", UiToThread::CpuStop),
         ("Reset", UiToThread::CpuReset),
@@ -305,6 +609,22 @@ fn build_cpu_column(
     ));
     cpu_controls_box.append(&cpu_speed_scale);
 
+    // Pacing runs a fixed number of display frames per second of host time, in place of the
+    // speed multiplier, such that demos run at the same speed on any host
+    let cpu_pacing_check = gtk::CheckButton::builder()
+        .label(format!("Pace to {PACED_FRAME_RATE} frames per second"))
+        .build();
+
+    cpu_pacing_check.connect_toggled(clone!(
+        #[strong]
+        tx_ui,
+        move |btn| {
+            let rate = btn.is_active().then_some(PACED_FRAME_RATE);
+            tx_ui.send(UiToThread::SetFrameRate(rate)).unwrap();
+        }
+    ));
+    cpu_controls_box.append(&cpu_pacing_check);
+
     let register_box = gtk::Box::builder()
         .orientation(gtk::Orientation::Horizontal)
         .spacing(4)
@@ -349,12 +669,36 @@ fn build_cpu_column(
             .label(&format!("<tt>R{i:02}</tt>"))
             .margin_end(6)
             .build();
-        let text = gtk::Label::builder()
-            .use_markup(true)
-            .label(&format!("<tt>0x{:04x}</tt>", 0))
+        let text = gtk::Entry::builder()
+            .text(format!("0x{:08x}", 0))
+            .css_classes(["monospace"])
+            .has_frame(false)
             .hexpand(true)
             .build();
 
+        // Sets the register to the entered value, which the processor thread only accepts
+        // while stopped
+        text.connect_activate(clone!(
+            #[strong]
+            tx_ui,
+            #[strong]
+            tx_thread,
+            move |t| {
+                let txt = t.text();
+                let digits = txt.trim().trim_start_matches("0x");
+                match u32::from_str_radix(digits, 16) {
+                    Ok(v) => tx_ui.send(UiToThread::SetRegister(i, v)).unwrap(),
+                    Err(_) => {
+                        tx_thread
+                            .send(ThreadToUi::LogMessage(format!(
+                                "Unable to set '{txt}' as register value in hex"
+                            )))
+                            .unwrap();
+                    }
+                }
+            }
+        ));
+
         inner_box.append(&label);
         inner_box.append(&text);
 
@@ -392,8 +736,10 @@ fn build_cpu_column(
 
 struct MemoryLocationData {
     labels: Vec<gtk::Label>,
-    locations: Vec<gtk::Label>,
+    locations: Vec<gtk::Entry>,
     base_input: Option<gtk::Entry>,
+    /// The base address and values of the most recent memory response, used when copying
+    contents: Rc<RefCell<(u32, Vec<u8>)>>,
     num_rows: usize,
     num_cols: usize,
 }
@@ -404,6 +750,7 @@ impl MemoryLocationData {
             labels: Vec::new(),
             locations: Vec::new(),
             base_input: None,
+            contents: Rc::new(RefCell::new((0, Vec::new()))),
             num_rows: rows,
             num_cols: cols,
         }
@@ -414,6 +761,7 @@ struct SerialElements {
     column_serial: gtk::Box,
     memory: MemoryLocationData,
     text_serial: gtk::TextView,
+    text_channels: Vec<gtk::TextView>,
     label_instruction: gtk::Label,
     label_instruction_details: gtk::Label,
 }
@@ -421,6 +769,7 @@ struct SerialElements {
 fn build_serial_column(
     tx_ui: &std::sync::mpsc::Sender<UiToThread>,
     tx_thread: &std::sync::mpsc::Sender<ThreadToUi>,
+    config: &ProjectConfig,
 ) -> SerialElements {
     let column_serial = gtk::Box::builder()
         .orientation(gtk::Orientation::Vertical)
@@ -436,7 +785,7 @@ fn build_serial_column(
         .build();
     let memory_base_entry = gtk::Entry::builder().text("0").build();
 
-    let mut memory = MemoryLocationData::new(6, 8);
+    let mut memory = MemoryLocationData::new(32, 8);
 
     {
         let memory_frame = gtk::Frame::builder()
@@ -456,15 +805,55 @@ fn build_serial_column(
             memory.labels.push(label);
 
             for j in 0..memory.num_cols {
-                let mem_lbl = gtk::Label::builder()
-                    .label(format!("{:02x}", i * memory.num_cols + j))
+                let offset = i * memory.num_cols + j;
+                let mem_entry = gtk::Entry::builder()
+                    .text(format!("{offset:02x}"))
+                    .width_chars(2)
+                    .max_width_chars(2)
+                    .max_length(2)
+                    .has_frame(false)
                     .hexpand(true)
                     .build();
-                memory_grid.attach(&mem_lbl, 1 + j as i32, i as i32, 1, 1);
-                memory.locations.push(mem_lbl);
+
+                // Writes the entered value to the address shown in the cell, which the
+                // processor thread only accepts while stopped
+                mem_entry.connect_activate(clone!(
+                    #[strong]
+                    tx_ui,
+                    #[strong]
+                    tx_thread,
+                    #[strong(rename_to = contents)]
+                    memory.contents,
+                    move |t| {
+                        match u8::from_str_radix(&t.text(), 16) {
+                            Ok(v) => {
+                                let addr = contents.borrow().0.wrapping_add(offset as u32);
+                                tx_ui.send(UiToThread::WriteMemory(addr, v)).unwrap();
+                            }
+                            Err(_) => {
+                                tx_thread
+                                    .send(ThreadToUi::LogMessage(format!(
+                                        "Unable to set '{}' as a byte in hex",
+                                        t.text()
+                                    )))
+                                    .unwrap();
+                            }
+                        }
+                    }
+                ));
+
+                memory_grid.attach(&mem_entry, 1 + j as i32, i as i32, 1, 1);
+                memory.locations.push(mem_entry);
             }
         }
 
+        let memory_scroll = gtk::ScrolledWindow::builder()
+            .child(&memory_grid)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .min_content_height(200)
+            .vexpand(true)
+            .build();
+
         let memory_count = memory.locations.len() as u32;
         memory_base_entry.connect_activate(clone!(
             #[strong]
@@ -488,8 +877,50 @@ fn build_serial_column(
             }
         ));
 
+        // Copies the memory shown to the host clipboard, either as rows of hex values or as
+        // disassembled instructions
+        let memory_copy_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(4)
+            .build();
+        let num_cols = memory.num_cols;
+
+        let btn_copy_hex = gtk::Button::builder()
+            .label("Copy Hex")
+            .hexpand(true)
+            .build();
+        btn_copy_hex.connect_clicked(clone!(
+            #[strong(rename_to = contents)]
+            memory.contents,
+            move |btn| {
+                let (base, vals) = &*contents.borrow();
+                btn.clipboard().set_text(&memory_hex(*base, vals, num_cols));
+            }
+        ));
+
+        let btn_copy_asm = gtk::Button::builder()
+            .label("Copy Asm")
+            .hexpand(true)
+            .build();
+        btn_copy_asm.connect_clicked(clone!(
+            #[strong(rename_to = contents)]
+            memory.contents,
+            move |btn| {
+                let (base, vals) = &*contents.borrow();
+                let text = jib_asm::disassemble::disassemble_bytes(*base, vals)
+                    .iter()
+                    .map(|w| format!("{w}\n"))
+                    .collect::<String>();
+                btn.clipboard().set_text(&text);
+            }
+        ));
+
+        memory_copy_box.append(&btn_copy_hex);
+        memory_copy_box.append(&btn_copy_asm);
+
         memory_box.append(&memory_base_entry);
-        memory_box.append(&memory_grid);
+        memory_box.append(&memory_scroll);
+        memory_box.append(&memory_copy_box);
         column_serial.append(&memory_frame);
     }
 
@@ -535,6 +966,31 @@ fn build_serial_column(
 
     instruction_box.append(&breakpoint_text);
 
+    let relocate_text = gtk::Entry::builder()
+        .placeholder_text("Relocate Program (hex)")
+        .build();
+    relocate_text.connect_activate(clone!(
+        #[strong]
+        tx_ui,
+        #[strong]
+        tx_thread,
+        move |t| {
+            match u32::from_str_radix(&t.text(), 16) {
+                Ok(v) => tx_ui.send(UiToThread::Relocate(v)).unwrap(),
+                Err(_) => {
+                    tx_thread
+                        .send(ThreadToUi::LogMessage(format!(
+                            "Unable to set '{}' as relocation base in hex",
+                            t.text()
+                        )))
+                        .unwrap();
+                }
+            }
+        }
+    ));
+
+    instruction_box.append(&relocate_text);
+
     instruction_box.append(&overall_instruction);
     instruction_box.append(&instruction_details);
 
@@ -554,9 +1010,69 @@ fn build_serial_column(
         .monospace(true)
         .build();
     let text_serial_scroll = gtk::ScrolledWindow::builder().child(&text_serial).build();
+
+    // Each channel of the serial multiplexer is shown in its own tab, alongside the serial log
+    let serial_tabs = gtk::Notebook::builder().build();
+    serial_tabs.append_page(&text_serial_scroll, Some(&gtk::Label::new(Some("Serial"))));
+
+    let mut text_channels = Vec::new();
+    for channel in 0..MUX_CHANNELS {
+        let text_channel = gtk::TextView::builder()
+            .hexpand(true)
+            .vexpand(true)
+            .editable(false)
+            .monospace(true)
+            .build();
+
+        let channel_input = gtk::Entry::builder()
+            .placeholder_text(format!("Channel {channel} Input"))
+            .build();
+        channel_input.connect_activate(clone!(
+            #[strong]
+            tx_ui,
+            move |t| {
+                tx_ui
+                    .send(UiToThread::MuxInput(channel, t.text().to_string()))
+                    .unwrap();
+                t.set_text("");
+            }
+        ));
+
+        let channel_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(4)
+            .build();
+        channel_box.append(&gtk::ScrolledWindow::builder().child(&text_channel).build());
+        channel_box.append(&channel_input);
+
+        serial_tabs.append_page(
+            &channel_box,
+            Some(&gtk::Label::new(Some(&format!("Channel {channel}")))),
+        );
+        text_channels.push(text_channel);
+    }
+
+    let btn_copy_serial = gtk::Button::builder().label("Copy Serial Log").build();
+    btn_copy_serial.connect_clicked(clone!(
+        #[strong]
+        text_serial,
+        move |btn| {
+            let buf = text_serial.buffer();
+            btn.clipboard()
+                .set_text(&buf.text(&buf.start_iter(), &buf.end_iter(), false));
+        }
+    ));
+
+    let serial_log_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(4)
+        .build();
+    serial_log_box.append(&serial_tabs);
+    serial_log_box.append(&btn_copy_serial);
+
     let text_serial_frame = gtk::Frame::builder()
         .label("Serial Log")
-        .child(&text_serial_scroll)
+        .child(&serial_log_box)
         .build();
 
     column_serial.append(&text_serial_frame);
@@ -575,47 +1091,649 @@ fn build_serial_column(
         .child(&text_input_box)
         .build();
 
-    let text_input = gtk::Entry::builder().build();
+    let check_echo = gtk::CheckButton::builder().label("Local Echo").build();
+    let check_newline = gtk::CheckButton::builder()
+        .label("Append Newline")
+        .active(true)
+        .build();
+    let history = Rc::new(RefCell::new(InputHistory::default()));
+
+    let text_input = gtk::Entry::builder()
+        .placeholder_text("Escapes: \\n \\r \\t \\0 \\\\ \\xHH")
+        .build();
     text_input.connect_activate(clone!(
         #[strong]
         tx_ui,
+        #[strong]
+        tx_thread,
+        #[strong]
+        text_serial,
+        #[strong]
+        check_echo,
+        #[strong]
+        check_newline,
+        #[strong]
+        history,
         move |t| {
-            tx_ui
-                .send(UiToThread::SerialInput(t.text().to_string()))
-                .unwrap();
-            t.set_text("");
+            let line = t.text().to_string();
+            match serial_input_bytes(&line) {
+                Ok(mut bytes) => {
+                    if check_newline.is_active() {
+                        bytes.push(b'\n');
+                    }
+                    if check_echo.is_active() {
+                        append_serial(&text_serial, &bytes);
+                    }
+
+                    history.borrow_mut().push(&line);
+                    tx_ui.send(UiToThread::SerialInput(bytes)).unwrap();
+                    t.set_text("");
+                }
+                Err(e) => tx_thread
+                    .send(ThreadToUi::LogMessage(format!(
+                        "Unable to send serial input - {e}"
+                    )))
+                    .unwrap(),
+            }
         }
     ));
 
+    // The up and down keys move through the lines previously sent
+    let history_keys = gtk::EventControllerKey::new();
+    history_keys.connect_key_pressed(clone!(
+        #[weak]
+        text_input,
+        #[strong]
+        history,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, _| {
+            let line = match key {
+                gtk::gdk::Key::Up => history.borrow_mut().older().map(str::to_string),
+                gtk::gdk::Key::Down => history.borrow_mut().newer().map(str::to_string),
+                _ => return glib::Propagation::Proceed,
+            };
+
+            if let Some(line) = line {
+                text_input.set_text(&line);
+                text_input.set_position(-1);
+            }
+            glib::Propagation::Stop
+        }
+    ));
+    text_input.add_controller(history_keys);
+
     text_input_box.append(&text_input);
 
+    let text_input_option_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .spacing(4)
+        .build();
+    text_input_option_box.append(&check_echo);
+    text_input_option_box.append(&check_newline);
+    text_input_box.append(&text_input_option_box);
+
     let text_input_button_box = gtk::Box::builder()
         .orientation(gtk::Orientation::Horizontal)
         .spacing(4)
         .build();
     let text_input_btn_submit = gtk::Button::builder().label("Submit").build();
-    text_input_btn_submit.connect_clicked(clone!(
+    text_input_btn_submit.connect_clicked(move |_| {
+        text_input.emit_activate();
+    });
+
+    // Pasted text is sent as provided, without a trailing newline, and is fed to the device
+    // gradually by the processor thread
+    let text_input_btn_paste = gtk::Button::builder().label("Paste").build();
+    text_input_btn_paste.connect_clicked(clone!(
         #[strong]
         tx_ui,
-        move |_| {
-            tx_ui
-                .send(UiToThread::SerialInput(text_input.text().to_string()))
-                .unwrap();
-            text_input.set_text("");
+        #[strong]
+        tx_thread,
+        move |btn| {
+            btn.clipboard().read_text_async(
+                None::<&gtk::gio::Cancellable>,
+                clone!(
+                    #[strong]
+                    tx_ui,
+                    #[strong]
+                    tx_thread,
+                    move |res| match res {
+                        Ok(Some(text)) => tx_ui
+                            .send(UiToThread::SerialPaste(text.to_string()))
+                            .unwrap(),
+                        Ok(None) => (),
+                        Err(e) => tx_thread
+                            .send(ThreadToUi::LogMessage(format!("Unable to paste - {e}")))
+                            .unwrap(),
+                    }
+                ),
+            );
         }
     ));
 
     text_input_button_box.append(&text_input_btn_submit);
+    text_input_button_box.append(&text_input_btn_paste);
 
     text_input_box.append(&text_input_button_box);
 
+    let playback_text = gtk::Entry::builder()
+        .placeholder_text("Playback Script (path)")
+        .build();
+    playback_text.connect_activate(clone!(
+        #[strong]
+        tx_ui,
+        #[strong]
+        tx_thread,
+        move |t| {
+            let path = t.text().to_string();
+            if path.is_empty() {
+                tx_ui.send(UiToThread::SetPlayback(None)).unwrap();
+                tx_thread
+                    .send(ThreadToUi::LogMessage("Playback cleared".into()))
+                    .unwrap();
+                return;
+            }
+
+            let script = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Unable to read - {e}"))
+                .and_then(|txt| {
+                    jib::device::PlaybackScript::parse(&txt).map_err(|e| e.to_string())
+                });
+
+            let msg = match script {
+                Ok(s) => {
+                    let msg = format!("Loaded {} playback events from {path}", s.events().len());
+                    tx_ui.send(UiToThread::SetPlayback(Some(s))).unwrap();
+                    msg
+                }
+                Err(e) => format!("{path} - {e}"),
+            };
+            tx_thread.send(ThreadToUi::LogMessage(msg)).unwrap();
+        }
+    ));
+
+    text_input_box.append(&playback_text);
+
+    let disk_text = gtk::Entry::builder()
+        .placeholder_text("Disk Image (path)")
+        .build();
+    disk_text.connect_activate(clone!(
+        #[strong]
+        tx_ui,
+        move |t| {
+            let path = t.text().to_string();
+            let path = if path.is_empty() { None } else { Some(path) };
+            tx_ui.send(UiToThread::SetDisk(path)).unwrap();
+        }
+    ));
+
+    text_input_box.append(&disk_text);
+
+    let serial_tcp_text = gtk::Entry::builder()
+        .placeholder_text("Serial TCP Bridge (address)")
+        .build();
+    serial_tcp_text.connect_activate(clone!(
+        #[strong]
+        tx_ui,
+        move |t| {
+            let addr = t.text().to_string();
+            let addr = if addr.is_empty() { None } else { Some(addr) };
+            tx_ui.send(UiToThread::SetSerialTcp(addr)).unwrap();
+        }
+    ));
+
+    text_input_box.append(&serial_tcp_text);
+
     column_serial.append(&text_input_frame);
 
+    // Devices provided by the project file are attached as if entered by the user
+    let path_text = |p: &Option<std::path::PathBuf>| p.as_ref().map(|p| p.display().to_string());
+    let project_devices = [
+        (&playback_text, path_text(&config.devices.playback)),
+        (&disk_text, path_text(&config.devices.disk)),
+        (&serial_tcp_text, config.devices.serial_tcp.clone()),
+    ];
+    for (entry, value) in project_devices {
+        if let Some(v) = value {
+            entry.set_text(&v);
+            entry.emit_activate();
+        }
+    }
+
+    // Key presses made while the keyboard field is focused are sent to the keyboard device,
+    // rather than being entered into the field
+    let keyboard_input = gtk::Entry::builder()
+        .placeholder_text("Focus to send key presses")
+        .editable(false)
+        .margin_start(4)
+        .margin_end(4)
+        .margin_top(4)
+        .margin_bottom(4)
+        .build();
+    let keyboard_controller = gtk::EventControllerKey::new();
+    keyboard_controller.connect_key_pressed(clone!(
+        #[strong]
+        tx_ui,
+        move |_, key, _, _| match keyboard_code(key) {
+            Some(code) => {
+                tx_ui.send(UiToThread::KeyPress(code)).unwrap();
+                glib::Propagation::Stop
+            }
+            None => glib::Propagation::Proceed,
+        }
+    ));
+    keyboard_input.add_controller(keyboard_controller);
+
+    column_serial.append(
+        &gtk::Frame::builder()
+            .label("Keyboard")
+            .child(&keyboard_input)
+            .build(),
+    );
+
     SerialElements {
         column_serial,
         memory,
         text_serial,
+        text_channels,
         label_instruction: overall_instruction,
         label_instruction_details: instruction_details,
     }
 }
+
+fn build_display_frame() -> (gtk::Frame, gtk::Label) {
+    let label_display = gtk::Label::builder()
+        .use_markup(true)
+        .xalign(0.0)
+        .yalign(0.0)
+        .margin_start(4)
+        .margin_end(4)
+        .margin_top(4)
+        .margin_bottom(4)
+        .build();
+
+    let display_frame = gtk::Frame::builder()
+        .label("Display")
+        .child(&label_display)
+        .build();
+
+    (display_frame, label_display)
+}
+
+/// Provides the markup for the text display, with a span for each run of cells sharing the same
+/// attribute
+fn display_markup(screen: &DisplayScreen) -> String {
+    let color = |i: u8| {
+        let (r, g, b) = TextDisplayDevice::PALETTE[i as usize];
+        format!("#{r:02x}{g:02x}{b:02x}")
+    };
+
+    let rows = (0..screen.rows)
+        .map(|r| {
+            screen
+                .row(r)
+                .chunk_by(|a, b| a.attribute == b.attribute)
+                .map(|run| {
+                    let text = run.iter().map(|c| c.to_char()).collect::<String>();
+                    format!(
+                        "<span foreground=\"{}\" background=\"{}\">{}</span>",
+                        color(run[0].foreground()),
+                        color(run[0].background()),
+                        glib::markup_escape_text(&text)
+                    )
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+
+    format!("<tt>{}</tt>", rows.join("\n"))
+}
+
+/// Provides the lines previously sent to the serial input, navigated with the arrow keys
+#[derive(Default)]
+struct InputHistory {
+    lines: Vec<String>,
+    /// The index of the line shown, where the number of lines indicates a new line
+    index: usize,
+}
+
+impl InputHistory {
+    const MAX_LINES: usize = 100;
+
+    /// Adds the line to the history, skipping empty lines and repeats of the last line, and
+    /// returns to a new line
+    fn push(&mut self, line: &str) {
+        if !line.is_empty() && self.lines.last().map(|l| l.as_str()) != Some(line) {
+            self.lines.push(line.to_string());
+            if self.lines.len() > Self::MAX_LINES {
+                self.lines.remove(0);
+            }
+        }
+        self.index = self.lines.len();
+    }
+
+    /// Moves to the previous line, if any
+    fn older(&mut self) -> Option<&str> {
+        self.index = self.index.checked_sub(1)?;
+        self.lines.get(self.index).map(|l| l.as_str())
+    }
+
+    /// Moves to the following line, providing an empty line once past the newest line
+    fn newer(&mut self) -> Option<&str> {
+        if self.index >= self.lines.len() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.lines.get(self.index).map_or("", |l| l.as_str()))
+    }
+}
+
+/// Provides the serial bytes for a line of input, where the `\n`, `\r`, `\t`, `\0`, `\\`, and
+/// `\xHH` escapes provide control characters and other raw bytes
+fn serial_input_bytes(line: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            bytes.push(jib::text::character_to_byte(c).map_err(|e| e.to_string())?);
+            continue;
+        }
+
+        bytes.push(match chars.next() {
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('0') => 0,
+            Some('\\') => b'\\',
+            Some('x') => {
+                let hex = chars.by_ref().take(2).collect::<String>();
+                u8::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 2)
+                    .ok_or_else(|| format!("invalid escape '\\x{hex}'"))?
+            }
+            Some(e) => return Err(format!("unknown escape '\\{e}'")),
+            None => return Err("incomplete escape at end of line".into()),
+        });
+    }
+
+    Ok(bytes)
+}
+
+/// Appends the serial bytes to the view, where bytes without a printable character are shown
+/// as `\xHH` escapes such that binary output is visible
+fn append_serial(view: &gtk::TextView, bytes: &[u8]) {
+    let text = bytes
+        .iter()
+        .map(|b| match jib::text::byte_to_character(*b) {
+            Ok(c) if c != '\0' => c.to_string(),
+            _ => format!("\\x{b:02x}"),
+        })
+        .collect::<String>();
+
+    let buf = view.buffer();
+    buf.insert(&mut buf.end_iter(), &text);
+    view.scroll_to_iter(&mut buf.end_iter(), 0.0, false, 0.0, 0.0);
+}
+
+/// Provides the memory values as rows of hex bytes, each prefixed with the address of the row
+fn memory_hex(base: u32, vals: &[u8], num_cols: usize) -> String {
+    vals.chunks(num_cols)
+        .enumerate()
+        .map(|(i, row)| {
+            let bytes = row
+                .iter()
+                .map(|v| format!("{v:02x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!(
+                "0x{:08x}  {bytes}\n",
+                base.wrapping_add((i * num_cols) as u32)
+            )
+        })
+        .collect()
+}
+
+/// Provides the keyboard device code for the key, if the key is supported by the device
+fn keyboard_code(key: gtk::gdk::Key) -> Option<u8> {
+    use gtk::gdk::Key;
+    use jib::device::KeyboardDevice;
+
+    match key {
+        Key::Up => Some(KeyboardDevice::KEY_UP),
+        Key::Down => Some(KeyboardDevice::KEY_DOWN),
+        Key::Left => Some(KeyboardDevice::KEY_LEFT),
+        Key::Right => Some(KeyboardDevice::KEY_RIGHT),
+        Key::BackSpace => Some(KeyboardDevice::KEY_BACKSPACE),
+        Key::Tab => Some(KeyboardDevice::KEY_TAB),
+        Key::Escape => Some(KeyboardDevice::KEY_ESCAPE),
+        Key::Delete => Some(KeyboardDevice::KEY_DELETE),
+        Key::Return | Key::KP_Enter => jib::text::character_to_byte('\n').ok(),
+        _ => key
+            .to_unicode()
+            .filter(|c| c.is_ascii())
+            .and_then(|c| jib::text::character_to_byte(c).ok()),
+    }
+}
+
+struct DisassemblyElements {
+    frame: gtk::Frame,
+    lines: Vec<gtk::Label>,
+    /// The address of each line, used to toggle a breakpoint when a line is clicked
+    addresses: Rc<RefCell<Vec<u32>>>,
+}
+
+impl DisassemblyElements {
+    /// Shows the disassembled words, marking the current instruction and any breakpoints
+    fn update(&self, view: &Disassembly) {
+        for (i, l) in self.lines.iter().enumerate() {
+            let Some(w) = view.words.get(i) else {
+                l.set_markup("");
+                continue;
+            };
+
+            let current = if w.address == view.pc { ">" } else { " " };
+            let brk = if view.breakpoints.contains(&w.address) {
+                "*"
+            } else {
+                " "
+            };
+            let text = glib::markup_escape_text(&w.to_string());
+
+            if w.address == view.pc {
+                l.set_markup(&format!("<tt><b>{current}{brk} {text}</b></tt>"));
+            } else {
+                l.set_markup(&format!("<tt>{current}{brk} {text}</tt>"));
+            }
+        }
+
+        *self.addresses.borrow_mut() = view.words.iter().map(|w| w.address).collect();
+    }
+}
+
+fn build_disassembly_frame(tx_ui: &std::sync::mpsc::Sender<UiToThread>) -> DisassemblyElements {
+    let list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .activate_on_single_click(true)
+        .margin_start(4)
+        .margin_end(4)
+        .margin_top(4)
+        .margin_bottom(4)
+        .build();
+
+    let lines = (0..DISASSEMBLY_LINES)
+        .map(|_| {
+            let label = gtk::Label::builder().use_markup(true).xalign(0.0).build();
+            list.append(&label);
+            label
+        })
+        .collect();
+
+    let addresses = Rc::new(RefCell::new(Vec::new()));
+
+    // Clicking a line toggles a breakpoint at the address of the line
+    list.connect_row_activated(clone!(
+        #[strong]
+        tx_ui,
+        #[strong]
+        addresses,
+        move |_, row| {
+            if let Some(addr) = addresses.borrow().get(row.index() as usize) {
+                tx_ui.send(UiToThread::ToggleBreakpoint(*addr)).unwrap();
+            }
+        }
+    ));
+
+    let frame = gtk::Frame::builder()
+        .label("Disassembly")
+        .child(&list)
+        .build();
+
+    DisassemblyElements {
+        frame,
+        lines,
+        addresses,
+    }
+}
+
+fn build_stack_frame() -> (gtk::Frame, gtk::Label) {
+    let label_stack = gtk::Label::builder()
+        .xalign(0.0)
+        .yalign(0.0)
+        .css_classes(["monospace"])
+        .margin_start(4)
+        .margin_end(4)
+        .margin_top(4)
+        .margin_bottom(4)
+        .build();
+
+    let stack_scroll = gtk::ScrolledWindow::builder()
+        .child(&label_stack)
+        .min_content_height(150)
+        .build();
+
+    let stack_frame = gtk::Frame::builder()
+        .label("Stack")
+        .child(&stack_scroll)
+        .build();
+
+    (stack_frame, label_stack)
+}
+
+/// Provides a line for each stack word, starting with the most recently pushed, followed by a
+/// separator after the registers saved by each call
+fn stack_text(words: &[StackWord]) -> String {
+    let mut lines = Vec::new();
+    for w in words {
+        match w.saved {
+            Some((frame, reg)) => {
+                lines.push(format!(
+                    "0x{:08x}  0x{:08x}  #{frame} {reg}",
+                    w.address, w.value
+                ));
+                if reg == Register::ProgramCounter {
+                    lines.push(format!("---- call of frame #{frame}"));
+                }
+            }
+            None => lines.push(format!("0x{:08x}  0x{:08x}", w.address, w.value)),
+        }
+    }
+    lines.join("\n")
+}
+
+fn build_snapshot_frame(
+    tx_ui: &std::sync::mpsc::Sender<UiToThread>,
+    tx_thread: &std::sync::mpsc::Sender<ThreadToUi>,
+) -> gtk::Frame {
+    let snapshot_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(4)
+        .margin_start(4)
+        .margin_end(4)
+        .margin_top(4)
+        .margin_bottom(4)
+        .build();
+
+    let save_text = gtk::Entry::builder()
+        .placeholder_text("Save Snapshot (path)")
+        .build();
+    save_text.connect_activate(clone!(
+        #[strong]
+        tx_ui,
+        move |t| {
+            tx_ui
+                .send(UiToThread::SaveSnapshot(t.text().to_string()))
+                .unwrap();
+        }
+    ));
+    snapshot_box.append(&save_text);
+
+    // Recorded input may be replayed headlessly by jrun --playback to reproduce a run
+    let recording_text = gtk::Entry::builder()
+        .placeholder_text("Save Input Recording (path)")
+        .build();
+    recording_text.connect_activate(clone!(
+        #[strong]
+        tx_ui,
+        move |t| {
+            tx_ui
+                .send(UiToThread::SaveRecording(t.text().to_string()))
+                .unwrap();
+        }
+    ));
+    snapshot_box.append(&recording_text);
+
+    let compare_box = gtk::Box::builder()
+        .orientation(gtk::Orientation::Horizontal)
+        .spacing(4)
+        .build();
+    let snapshot_a = gtk::Entry::builder()
+        .placeholder_text("Snapshot A (path)")
+        .hexpand(true)
+        .build();
+    let snapshot_b = gtk::Entry::builder()
+        .placeholder_text("Snapshot B (path)")
+        .hexpand(true)
+        .build();
+    let btn_compare = gtk::Button::builder().label("Compare").build();
+
+    btn_compare.connect_clicked(clone!(
+        #[strong]
+        tx_ui,
+        #[strong]
+        tx_thread,
+        #[strong]
+        snapshot_a,
+        #[strong]
+        snapshot_b,
+        move |_| {
+            let load = |path: String| {
+                std::fs::read(&path)
+                    .map_err(|e| format!("Unable to read - {e}"))
+                    .and_then(|b| jib::cpu::CpuSnapshot::from_bytes(&b).map_err(|e| e.to_string()))
+                    .map_err(|e| format!("{path} - {e}"))
+            };
+
+            match load(snapshot_a.text().to_string())
+                .and_then(|a| Ok((a, load(snapshot_b.text().to_string())?)))
+            {
+                Ok((a, b)) => tx_ui
+                    .send(UiToThread::CompareSnapshots(Box::new(a), Box::new(b)))
+                    .unwrap(),
+                Err(e) => tx_thread.send(ThreadToUi::LogMessage(e)).unwrap(),
+            }
+        }
+    ));
+
+    compare_box.append(&snapshot_a);
+    compare_box.append(&snapshot_b);
+    compare_box.append(&btn_compare);
+    snapshot_box.append(&compare_box);
+
+    gtk::Frame::builder()
+        .label("Snapshots")
+        .child(&snapshot_box)
+        .build()
+}
@@ -1,27 +1,85 @@
-use jib::cpu::RegisterManager;
+use jib::cpu::{CpuSnapshot, RegisterManager};
+use jib::device::{DisplayScreen, PlaybackScript};
+use jib_asm::disassemble::DisassembledWord;
+use jib_asm::machine::StandardDevices;
+use jib_asm::object::LinkedImage;
+use jib_asm::unwind::StackWord;
+
+/// Defines the number of channels of the serial multiplexer, each shown in its own tab
+pub const MUX_CHANNELS: u8 = StandardDevices::MUX_CHANNELS;
+
+/// Defines the number of display frames run per second of host time when pacing is enabled
+pub const PACED_FRAME_RATE: u32 = 60;
+
+/// Defines the number of words shown in the disassembly panel
+pub const DISASSEMBLY_LINES: usize = 16;
+
+/// Defines the number of words shown in the disassembly panel before the program counter
+pub const DISASSEMBLY_BEFORE: usize = 4;
+
+/// Defines the number of words below the stack pointer shown in the stack panel
+pub const STACK_LINES: usize = 48;
+
+/// Provides the words disassembled around the program counter, along with the breakpoints
+/// marked within them
+#[derive(Clone)]
+pub struct Disassembly {
+    pub pc: u32,
+    pub words: Vec<DisassembledWord>,
+    pub breakpoints: Vec<u32>,
+}
 
 #[derive(Clone)]
 pub enum UiToThread {
     CpuStep,
+    CpuStepBack,
     CpuStart,
     CpuStop,
     CpuReset,
     CpuIrq(u8),
-    SetCode(Vec<u8>),
-    SerialInput(String),
+    SetCode(LinkedImage),
+    Relocate(u32),
+    /// Sends the bytes to the serial input unchanged, such that control characters and other
+    /// raw bytes may be sent
+    SerialInput(Vec<u8>),
+    /// Queues text from the host clipboard as serial input, fed to the device at a paced rate
+    SerialPaste(String),
+    MuxInput(u8, String),
+    KeyPress(u8),
+    SetPlayback(Option<PlaybackScript>),
+    SetDisk(Option<String>),
+    SetSerialTcp(Option<String>),
     RequestMemory(u32, u32),
+    /// Writes a byte of memory from the memory editor, which is rejected while running
+    WriteMemory(u32, u8),
+    /// Sets a register by index from the register editor, which is rejected while running
+    SetRegister(usize, u32),
     SetBreakpoint(u32),
+    /// Adds a breakpoint at the address, or removes it if already present
+    ToggleBreakpoint(u32),
     SetMultiplier(f64),
+    SetFrameRate(Option<u32>),
+    SaveSnapshot(String),
+    /// Saves the serial input, key presses, and interrupts provided since the last reset as a
+    /// playback script
+    SaveRecording(String),
+    CompareSnapshots(Box<CpuSnapshot>, Box<CpuSnapshot>),
     Exit,
 }
 
 #[derive(Clone)]
 pub enum ThreadToUi {
     ResponseMemory(u32, Vec<u8>),
-    SerialOutput(String),
+    /// Provides the bytes written to the serial output, which need not be text
+    SerialOutput(Vec<u8>),
+    MuxOutput(u8, String),
+    DisplayContents(Box<DisplayScreen>),
     LogMessage(String),
     RegisterState(Box<RegisterManager>),
     ProgramCounterValue(u32, u32),
+    Disassembly(Box<Disassembly>),
+    StackContents(Vec<StackWord>),
     ProcessorReset,
+    SnapshotDiff(String),
     ThreadExit,
 }